@@ -233,6 +233,11 @@ impl<'c> Monerod {
         &self.client
     }
 
+    /// The port of the JSON RPC interface on the host.
+    pub fn rpc_port(&self) -> u16 {
+        self.rpc_port
+    }
+
     /// Spawns a task to mine blocks in a regular interval to the provided
     /// address
     pub async fn start_miner(&self, miner_wallet_address: &str) -> Result<()> {
@@ -285,6 +290,11 @@ impl<'c> MoneroWalletRpc {
         &self.client
     }
 
+    /// The port of the JSON RPC interface on the host.
+    pub fn rpc_port(&self) -> u16 {
+        self.rpc_port
+    }
+
     // It takes a little while for the wallet to sync with monerod.
     pub async fn wait_for_wallet_height(&self, height: u32) -> Result<()> {
         let mut retry: u8 = 0;
@@ -310,7 +320,7 @@ impl<'c> MoneroWalletRpc {
 
     pub async fn balance(&self) -> Result<u64> {
         self.client().refresh().await?;
-        let balance = self.client().get_balance(0).await?.balance;
+        let balance = self.client().get_balance(0, Vec::new()).await?.balance;
 
         Ok(balance)
     }
@@ -21,6 +21,7 @@ pub struct Client {
     base_url: reqwest::Url,
     get_o_indexes_bin_url: reqwest::Url,
     get_outs_bin_url: reqwest::Url,
+    get_transactions_url: reqwest::Url,
 }
 
 impl Client {
@@ -30,18 +31,27 @@ impl Client {
     }
 
     fn new(host: String, port: u16) -> Result<Self> {
+        Self::from_daemon_address(&format!("{}:{}", host, port))
+    }
+
+    /// New monerod RPC client for a daemon given as `<host>:<port>`.
+    pub fn from_daemon_address(address: &str) -> Result<Self> {
+        let base_url = reqwest::Url::parse(&format!("http://{}/", address))
+            .with_context(|| format!("Invalid monerod address {}", address))?;
+
         Ok(Self {
             inner: reqwest::ClientBuilder::new()
                 .connection_verbose(true)
                 .build()?,
-            base_url: format!("http://{}:{}/json_rpc", host, port)
-                .parse()
+            base_url: base_url.join("json_rpc").context("url is well formed")?,
+            get_o_indexes_bin_url: base_url
+                .join("get_o_indexes.bin")
                 .context("url is well formed")?,
-            get_o_indexes_bin_url: format!("http://{}:{}/get_o_indexes.bin", host, port)
-                .parse()
+            get_outs_bin_url: base_url
+                .join("get_outs.bin")
                 .context("url is well formed")?,
-            get_outs_bin_url: format!("http://{}:{}/get_outs.bin", host, port)
-                .parse()
+            get_transactions_url: base_url
+                .join("get_transactions")
                 .context("url is well formed")?,
         })
     }
@@ -58,6 +68,31 @@ impl Client {
             .await
     }
 
+    /// Fetches the given transactions from the blockchain or the mempool.
+    ///
+    /// Transactions monerod does not know about are reported in
+    /// `missed_tx`.
+    pub async fn get_transactions(
+        &self,
+        txs_hashes: Vec<String>,
+    ) -> Result<GetTransactionsResponse> {
+        let response = self
+            .inner
+            .post(self.get_transactions_url.clone())
+            .json(&GetTransactionsPayload {
+                txs_hashes,
+                decode_as_json: true,
+            })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Request failed with status code {}", response.status())
+        }
+
+        Ok(response.json().await?)
+    }
+
     async fn binary_request<Req, Res>(&self, url: reqwest::Url, request: Req) -> Result<Res>
     where
         Req: Serialize,
@@ -132,6 +167,32 @@ struct GetOutsPayload {
     outputs: Vec<GetOutputsOut>,
 }
 
+#[derive(Clone, Debug, Serialize)]
+struct GetTransactionsPayload {
+    txs_hashes: Vec<String>,
+    decode_as_json: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct GetTransactionsResponse {
+    pub status: String,
+    #[serde(default)]
+    pub txs: Vec<TransactionEntry>,
+    #[serde(default)]
+    pub missed_tx: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TransactionEntry {
+    pub tx_hash: String,
+    /// The transaction decoded as JSON by monerod.
+    pub as_json: String,
+    pub in_pool: bool,
+    /// Only set if the transaction is not in the mempool.
+    #[serde(default)]
+    pub block_height: u64,
+}
+
 #[derive(Clone, Copy, Debug, Serialize)]
 pub struct GetOutputsOut {
     pub amount: u64,
@@ -5,8 +5,10 @@ use serde::{Deserialize, Deserializer, Serialize};
 #[jsonrpc_client::api(version = "2.0")]
 pub trait MoneroWalletRpc {
     async fn get_address(&self, account_index: u32) -> GetAddress;
-    async fn get_balance(&self, account_index: u32) -> GetBalance;
+    async fn get_balance(&self, account_index: u32, address_indices: Vec<u32>) -> GetBalance;
+    async fn get_address_index(&self, address: String) -> GetAddressIndex;
     async fn create_account(&self, label: String) -> CreateAccount;
+    async fn create_address(&self, account_index: u32, label: String) -> CreateAddress;
     async fn get_accounts(&self, tag: String) -> GetAccounts;
     async fn open_wallet(&self, filename: String) -> WalletOpened;
     async fn close_wallet(&self) -> WalletClosed;
@@ -16,9 +18,11 @@ pub trait MoneroWalletRpc {
         account_index: u32,
         destinations: Vec<Destination>,
         get_tx_key: bool,
+        subaddr_indices: Vec<u32>,
     ) -> Transfer;
     async fn get_height(&self) -> BlockHeight;
     async fn check_tx_key(&self, txid: String, tx_key: String, address: String) -> CheckTxKey;
+    async fn get_transfer_by_txid(&self, txid: String) -> GetTransferByTxid;
     #[allow(clippy::too_many_arguments)]
     async fn generate_from_keys(
         &self,
@@ -74,7 +78,26 @@ impl Client {
             address: address.to_owned(),
         }];
 
-        Ok(self.transfer(account_index, dest, true).await?)
+        Ok(self.transfer(account_index, dest, true, Vec::new()).await?)
+    }
+
+    /// Transfers `amount` monero to `address`, spending only the outputs
+    /// received on the given subaddresses of `account_index`.
+    pub async fn transfer_from_subaddresses(
+        &self,
+        account_index: u32,
+        subaddr_indices: Vec<u32>,
+        amount: u64,
+        address: &str,
+    ) -> Result<Transfer> {
+        let dest = vec![Destination {
+            amount,
+            address: address.to_owned(),
+        }];
+
+        Ok(self
+            .transfer(account_index, dest, true, subaddr_indices)
+            .await?)
     }
 }
 
@@ -90,6 +113,28 @@ pub struct GetBalance {
     pub multisig_import_needed: bool,
     pub time_to_unlock: u32,
     pub unlocked_balance: u64,
+    /// The balance of the subaddresses that were asked for, or of all
+    /// subaddresses that received Monero if none were.
+    #[serde(default)]
+    pub per_subaddress: Vec<SubaddressBalance>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct SubaddressBalance {
+    pub address_index: u32,
+    pub balance: u64,
+    pub unlocked_balance: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct GetAddressIndex {
+    pub index: SubaddressIndex,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SubaddressIndex {
+    pub major: u32,
+    pub minor: u32,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -98,6 +143,12 @@ pub struct CreateAccount {
     pub address: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreateAddress {
+    pub address: String,
+    pub address_index: u32,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetAccounts {
     pub subaddress_accounts: Vec<SubAddressAccount>,
@@ -170,6 +221,20 @@ impl From<CheckTxKeyResponse> for CheckTxKey {
     }
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetTransferByTxid {
+    pub transfer: TransferEntry,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransferEntry {
+    pub txid: String,
+    pub amount: u64,
+    pub fee: u64,
+    pub height: u64,
+    pub timestamp: u64,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct GenerateFromKeys {
     pub address: String,
@@ -184,8 +249,8 @@ pub struct Refreshed {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SweepAll {
-    amount_list: Vec<u64>,
-    fee_list: Vec<u64>,
+    pub amount_list: Vec<u64>,
+    pub fee_list: Vec<u64>,
     multisig_txset: String,
     pub tx_hash_list: Vec<String>,
     unsigned_txset: String,
@@ -247,6 +312,92 @@ mod tests {
         let _: Response<SweepAll> = serde_json::from_str(&response).unwrap();
     }
 
+    #[test]
+    fn can_deserialize_get_transfer_by_txid() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "transfer": {
+              "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+              "amount": 300000000000,
+              "confirmations": 1,
+              "double_spend_seen": false,
+              "fee": 21650200000,
+              "height": 153624,
+              "note": "",
+              "payment_id": "0000000000000000",
+              "subaddr_index": {"major": 0, "minor": 0},
+              "suggested_confirmations_threshold": 1,
+              "timestamp": 1535918400,
+              "txid": "c36258a276018c3a4bc1f195a7fb530f50cd63a4fa765fb7c6f7f49fc051762a",
+              "type": "out",
+              "unlock_time": 0
+            }
+          }
+        }"#;
+
+        let _: Response<GetTransferByTxid> = serde_json::from_str(&response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_create_address() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "address": "7BG5jr9QS5sGMdpbBrZEwVLZjSKJGJBsXdZLt8wiXyhfKcg2bmBNGT2W8Hx3hBxkeP8sn3oPT15P1F2Ka3BgxG5uqXr3Ysk",
+            "address_index": 1
+          }
+        }"#;
+
+        let _: Response<CreateAddress> = serde_json::from_str(&response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_get_balance_of_subaddresses() {
+        let result = r#"{
+          "balance": 157443303037455077,
+          "blocks_to_unlock": 0,
+          "multisig_import_needed": false,
+          "per_subaddress": [{
+            "account_index": 0,
+            "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+            "address_index": 0,
+            "balance": 157360317826255077,
+            "blocks_to_unlock": 0,
+            "label": "Primary account",
+            "num_unspent_outputs": 5281,
+            "time_to_unlock": 0,
+            "unlocked_balance": 157360317826255077
+          }],
+          "time_to_unlock": 0,
+          "unlocked_balance": 157443303037455077
+        }"#;
+
+        let balance: GetBalance = serde_json::from_str(result).unwrap();
+
+        assert_eq!(balance.per_subaddress.len(), 1);
+        assert_eq!(balance.per_subaddress[0].address_index, 0);
+        assert_eq!(balance.per_subaddress[0].balance, 157360317826255077);
+    }
+
+    #[test]
+    fn can_deserialize_get_address_index() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "index": {
+              "major": 0,
+              "minor": 1
+            }
+          }
+        }"#;
+
+        let _: Response<GetAddressIndex> = serde_json::from_str(&response).unwrap();
+    }
+
     #[test]
     fn can_deserialize_create_wallet() {
         let response = r#"{
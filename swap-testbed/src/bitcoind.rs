@@ -85,7 +85,7 @@ impl Bitcoind {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct BitcoindArgs;
 
 impl Default for BitcoindArgs {
@@ -0,0 +1,108 @@
+//! The regtest blockchains the binaries run against.
+
+use anyhow::{Context, Result};
+use monero_harness::{image, Monero};
+use std::str::FromStr;
+use swap_testbed::{bitcoind, electrs, init_bitcoind_container, init_electrs_container, mint};
+use testcontainers::clients::Cli;
+use testcontainers::Container;
+use url::Url;
+use uuid::Uuid;
+
+/// The name of the wallet the asb opens in monero-wallet-rpc.
+pub const ASB_WALLET_NAME: &str = "asb-wallet";
+
+/// bitcoind, electrs, monerod and the monero-wallet-rpc of the asb, all of
+/// them accessible on localhost.
+///
+/// Blocks are mined continuously on both blockchains, a Bitcoin block every
+/// second and a Monero block every second.
+pub struct Environment<'c> {
+    monero: Monero,
+    bitcoind_url: Url,
+    electrs_rpc_port: u16,
+    _containers: Containers<'c>,
+}
+
+// This is just to keep the containers alive
+#[allow(dead_code)]
+struct Containers<'c> {
+    bitcoind: Container<'c, Cli, bitcoind::Bitcoind>,
+    electrs: Container<'c, Cli, electrs::Electrs>,
+    monerod: Container<'c, Cli, image::Monerod>,
+    monero_wallet_rpcs: Vec<Container<'c, Cli, image::MoneroWalletRpc>>,
+}
+
+impl<'c> Environment<'c> {
+    /// Starts the containers and funds the Monero wallet of the asb with an
+    /// output for every amount in `asb_xmr_outputs`, in piconero.
+    pub async fn start(cli: &'c Cli, asb_xmr_outputs: Vec<u64>) -> Result<Environment<'c>> {
+        let prefix = Uuid::new_v4().to_simple().to_string()[..8].to_owned();
+        let bitcoind_name = format!("{}_bitcoind", prefix);
+
+        tracing::info!("Starting bitcoind and electrs");
+        let (bitcoind, bitcoind_url) =
+            init_bitcoind_container(cli, prefix.clone(), bitcoind_name.clone(), prefix.clone())
+                .await
+                .context("Failed to start bitcoind")?;
+        let electrs = init_electrs_container(cli, prefix.clone(), bitcoind_name, prefix)
+            .await
+            .context("Failed to start electrs")?;
+        let electrs_rpc_port = electrs
+            .get_host_port(electrs::RPC_PORT)
+            .context("Could not map electrs rpc port")?;
+
+        tracing::info!("Starting monerod and monero-wallet-rpc");
+        let (monero, monerod, monero_wallet_rpcs) = Monero::new(cli, vec![ASB_WALLET_NAME])
+            .await
+            .context("Failed to start monerod")?;
+        monero.init_miner().await?;
+        monero.init_wallet(ASB_WALLET_NAME, asb_xmr_outputs).await?;
+        monero.start_miner().await?;
+
+        Ok(Self {
+            monero,
+            bitcoind_url,
+            electrs_rpc_port,
+            _containers: Containers {
+                bitcoind,
+                electrs,
+                monerod,
+                monero_wallet_rpcs,
+            },
+        })
+    }
+
+    pub fn electrum_rpc_url(&self) -> Result<Url> {
+        let url = Url::parse(&format!("tcp://127.0.0.1:{}", self.electrs_rpc_port))?;
+
+        Ok(url)
+    }
+
+    /// The address of monerod as `<host>:<port>`.
+    pub fn monero_daemon_address(&self) -> String {
+        format!("127.0.0.1:{}", self.monero.monerod().rpc_port())
+    }
+
+    pub fn asb_wallet_rpc_url(&self) -> Result<Url> {
+        let wallet = self.monero.wallet(ASB_WALLET_NAME)?;
+        let url = Url::parse(&format!("http://127.0.0.1:{}/json_rpc", wallet.rpc_port()))?;
+
+        Ok(url)
+    }
+
+    /// An address of the miner, for wallets that are not checked.
+    pub async fn monero_address(&self) -> Result<String> {
+        let address = self.monero.wallet("miner")?.address().await?.address;
+
+        Ok(address)
+    }
+
+    /// Sends the amount to the given address and confirms it.
+    pub async fn mint(&self, address: &str, amount: bitcoin::Amount) -> Result<()> {
+        let address = bitcoin::Address::from_str(address)
+            .with_context(|| format!("Invalid Bitcoin address {}", address))?;
+
+        mint(self.bitcoind_url.clone(), address, amount).await
+    }
+}
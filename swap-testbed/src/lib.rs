@@ -0,0 +1,140 @@
+#![warn(
+    unused_extern_crates,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+
+//! # swap-testbed
+//!
+//! Docker images and setup of a regtest bitcoind node with an electrs server
+//! in front of it. The bitcoind node mines a block every second.
+//!
+//! Used by the integration tests of the swap crate and by the `swap-testbed`
+//! binary, which runs scripted swaps between the `asb` and `swap` binaries
+//! against these containers and the ones of `monero-harness`.
+pub mod bitcoind;
+pub mod electrs;
+
+use anyhow::{Context, Result};
+use bitcoin_harness::{BitcoindRpcApi, Client};
+use std::time::Duration;
+use testcontainers::clients::Cli;
+use testcontainers::{Container, Docker, RunArgs};
+use url::Url;
+
+/// The bitcoind wallet that receives the mined blocks and funds the wallets
+/// of the participants.
+const BITCOIN_TEST_WALLET_NAME: &str = "testwallet";
+
+/// How often we mine a block.
+const BLOCK_TIME_SECS: u64 = 1;
+
+/// Starts bitcoind and mines `101 + spendable_quantity` blocks, returns the
+/// RPC url of the node including the credentials.
+pub async fn init_bitcoind_container(
+    cli: &Cli,
+    volume: String,
+    name: String,
+    network: String,
+) -> Result<(Container<'_, Cli, bitcoind::Bitcoind>, Url)> {
+    let image = bitcoind::Bitcoind::default().with_volume(volume);
+
+    let run_args = RunArgs::default().with_name(name).with_network(network);
+
+    let docker = cli.run_with_args(image, run_args);
+    let a = docker
+        .get_host_port(bitcoind::RPC_PORT)
+        .context("Could not map bitcoind rpc port")?;
+
+    let bitcoind_url = {
+        let input = format!(
+            "http://{}:{}@localhost:{}",
+            bitcoind::RPC_USER,
+            bitcoind::RPC_PASSWORD,
+            a
+        );
+        Url::parse(&input).unwrap()
+    };
+
+    init_bitcoind(bitcoind_url.clone(), 5).await?;
+
+    Ok((docker, bitcoind_url.clone()))
+}
+
+/// Starts electrs for the bitcoind container with the given name, the
+/// Electrum RPC port is [`electrs::RPC_PORT`].
+pub async fn init_electrs_container(
+    cli: &Cli,
+    volume: String,
+    bitcoind_container_name: String,
+    network: String,
+) -> Result<Container<'_, Cli, electrs::Electrs>> {
+    let bitcoind_rpc_addr = format!("{}:{}", bitcoind_container_name, bitcoind::RPC_PORT);
+    let image = electrs::Electrs::default()
+        .with_volume(volume)
+        .with_daemon_rpc_addr(bitcoind_rpc_addr)
+        .with_tag("latest");
+
+    let run_args = RunArgs::default().with_network(network);
+
+    let docker = cli.run_with_args(image, run_args);
+
+    Ok(docker)
+}
+
+async fn mine(bitcoind_client: Client, reward_address: bitcoin::Address) -> Result<()> {
+    loop {
+        tokio::time::sleep(Duration::from_secs(BLOCK_TIME_SECS)).await;
+        bitcoind_client
+            .generatetoaddress(1, reward_address.clone(), None)
+            .await?;
+    }
+}
+
+async fn init_bitcoind(node_url: Url, spendable_quantity: u32) -> Result<Client> {
+    let bitcoind_client = Client::new(node_url.clone());
+
+    bitcoind_client
+        .createwallet(BITCOIN_TEST_WALLET_NAME, None, None, None, None)
+        .await?;
+
+    let reward_address = bitcoind_client
+        .with_wallet(BITCOIN_TEST_WALLET_NAME)?
+        .getnewaddress(None, None)
+        .await?;
+
+    bitcoind_client
+        .generatetoaddress(101 + spendable_quantity, reward_address.clone(), None)
+        .await?;
+    let _ = tokio::spawn(mine(bitcoind_client.clone(), reward_address));
+    Ok(bitcoind_client)
+}
+
+/// Send Bitcoin to the specified address, limited to the spendable bitcoin
+/// quantity.
+pub async fn mint(node_url: Url, address: bitcoin::Address, amount: bitcoin::Amount) -> Result<()> {
+    let bitcoind_client = Client::new(node_url.clone());
+
+    bitcoind_client
+        .send_to_address(BITCOIN_TEST_WALLET_NAME, address.clone(), amount)
+        .await?;
+
+    // Confirm the transaction
+    let reward_address = bitcoind_client
+        .with_wallet(BITCOIN_TEST_WALLET_NAME)?
+        .getnewaddress(None, None)
+        .await?;
+    bitcoind_client
+        .generatetoaddress(1, reward_address, None)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+#![warn(
+    unused_extern_crates,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+
+mod environment;
+mod monitor;
+mod process;
+mod scenario;
+mod testbed;
+
+use crate::environment::Environment;
+use crate::scenario::Scenario;
+use crate::testbed::Testbed;
+use anyhow::{Context, Result};
+use std::env;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use testcontainers::clients::Cli;
+
+/// The XMR of the asb, ten outputs so it can lock XMR again right after a
+/// swap.
+const ASB_XMR_OUTPUTS: [u64; 10] = [1_000_000_000_000; 10];
+
+#[derive(structopt::StructOpt, Debug)]
+#[structopt(
+    name = "swap-testbed",
+    about = "Runs scripted swaps between the asb and swap binaries on regtest blockchains in docker",
+    author
+)]
+struct Arguments {
+    #[structopt(
+        long = "bin-dir",
+        help = "The directory of the asb and swap binaries, defaults to the directory of swap-testbed",
+        parse(from_os_str)
+    )]
+    bin_dir: Option<PathBuf>,
+
+    #[structopt(
+        long = "data-dir",
+        help = "Keep the data and logs of the asb and swap binaries in this directory instead of a temporary one",
+        parse(from_os_str)
+    )]
+    data_dir: Option<PathBuf>,
+
+    #[structopt(
+        long = "keep-running",
+        help = "Keep the blockchains running after the scenario until interrupted, e.g. to inspect the swap with the asb and swap binaries"
+    )]
+    keep_running: bool,
+
+    #[structopt(subcommand)]
+    scenario: Scenario,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Arguments::from_args();
+
+    tracing_subscriber::fmt()
+        .with_env_filter("warn,swap_testbed=info,monero_harness=info")
+        .with_target(false)
+        .init();
+
+    let bin_dir = match args.bin_dir {
+        Some(bin_dir) => bin_dir,
+        None => env::current_exe()?
+            .parent()
+            .context("The testbed binary has no parent directory")?
+            .to_path_buf(),
+    };
+    let temp_dir = tempfile::tempdir()?;
+    let data_dir = args
+        .data_dir
+        .unwrap_or_else(|| temp_dir.path().to_path_buf());
+
+    let cli = Cli::default();
+    let environment = Environment::start(&cli, ASB_XMR_OUTPUTS.to_vec()).await?;
+    let testbed = Testbed::new(environment, bin_dir, data_dir)?;
+    testbed.describe()?;
+
+    let result = scenario::run(&testbed, &args.scenario).await;
+
+    if args.keep_running {
+        tracing::info!("Keeping the environment running, press Ctrl+C to stop it");
+        testbed.describe()?;
+        tokio::signal::ctrl_c().await?;
+    }
+
+    result
+}
@@ -0,0 +1,187 @@
+//! Follows the state transitions of the swaps of the asb and the swap binary.
+//!
+//! Both binaries serve the state transitions recorded in their database as
+//! server-sent events. The stream is resumed after the last received event
+//! whenever the connection is lost, e.g. because the asb is restarted.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use url::Url;
+use uuid::Uuid;
+
+/// How long to wait for the monitor to come up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often to try to connect to the monitor.
+const CONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The final states of the swap of each role, a swap in Bob's `BtcRedeemed`
+/// is only finished because the testbed uses the Monero light wallet.
+const ALICE_FINAL_STATES: &[&str] = &["BtcRedeemed", "XmrRefunded", "BtcPunished", "SafelyAborted"];
+const BOB_FINAL_STATES: &[&str] = &[
+    "BtcRedeemed",
+    "XmrRedeemed",
+    "BtcRefunded",
+    "BtcPunished",
+    "SafelyAborted",
+];
+
+/// A state transition as served by the monitor.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Event {
+    pub id: i64,
+    pub swap_id: Uuid,
+    pub role: String,
+    /// The name of the state, e.g. `BtcLocked`.
+    pub event: String,
+    /// A human readable description of the state.
+    pub state: String,
+}
+
+impl Event {
+    fn is_final(&self) -> bool {
+        let final_states = match self.role.as_str() {
+            "alice" => ALICE_FINAL_STATES,
+            _ => BOB_FINAL_STATES,
+        };
+
+        final_states.contains(&self.event.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub struct Monitor {
+    name: &'static str,
+    events: mpsc::UnboundedReceiver<Event>,
+}
+
+impl Monitor {
+    /// Waits until the monitor on `address` is up, only the state
+    /// transitions recorded from then on are received.
+    pub async fn connect(name: &'static str, address: SocketAddr) -> Result<Self> {
+        let url = Url::parse(&format!("http://{}/", address))?;
+        let client = reqwest::Client::new();
+
+        let response = timeout(CONNECT_TIMEOUT, async {
+            loop {
+                match client.get(url.clone()).send().await {
+                    Ok(response) => return response,
+                    Err(_) => sleep(CONNECT_INTERVAL).await,
+                }
+            }
+        })
+        .await
+        .with_context(|| format!("The monitor of the {} did not come up", name))?;
+
+        let (sender, events) = mpsc::unbounded_channel();
+        tokio::spawn(follow(client, url, response, sender));
+
+        Ok(Self { name, events })
+    }
+
+    /// Waits until the swap enters the state with the given name.
+    ///
+    /// Fails if the swap finishes in another state first, or if it takes
+    /// longer than `max_wait`.
+    pub async fn wait_for(&mut self, state: &str, max_wait: Duration) -> Result<Event> {
+        let event = self
+            .wait_until(max_wait, |event| event.event == state || event.is_final())
+            .await
+            .with_context(|| format!("The swap of the {} did not reach {}", self.name, state))?;
+
+        if event.event != state {
+            bail!(
+                "The swap of the {} finished in {} instead of reaching {}",
+                self.name,
+                event.event,
+                state
+            )
+        }
+
+        Ok(event)
+    }
+
+    /// Waits until the swap is finished and returns its final state.
+    pub async fn wait_for_final(&mut self, max_wait: Duration) -> Result<Event> {
+        self.wait_until(max_wait, Event::is_final)
+            .await
+            .with_context(|| format!("The swap of the {} did not finish", self.name))
+    }
+
+    async fn wait_until(
+        &mut self,
+        max_wait: Duration,
+        until: impl Fn(&Event) -> bool,
+    ) -> Result<Event> {
+        let name = self.name;
+        let events = &mut self.events;
+
+        timeout(max_wait, async {
+            loop {
+                let event = events.recv().await.context("The monitor stopped")?;
+                tracing::info!(%name, state = %event.state, "Swap advanced");
+
+                if until(&event) {
+                    return Ok(event);
+                }
+            }
+        })
+        .await
+        .context("Timed out")?
+    }
+}
+
+async fn follow(
+    client: reqwest::Client,
+    url: Url,
+    mut response: reqwest::Response,
+    events: mpsc::UnboundedSender<Event>,
+) {
+    let mut last_event_id = None;
+    let mut buffer = String::new();
+
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+                while let Some(end) = buffer.find("\n\n") {
+                    let message = buffer.drain(..end + 2).collect::<String>();
+
+                    if let Some(event) = parse(&message) {
+                        last_event_id = Some(event.id);
+                        if events.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+            Ok(None) | Err(_) => {
+                buffer.clear();
+                response = loop {
+                    let mut request = client.get(url.clone());
+                    if let Some(id) = last_event_id {
+                        request = request.header("Last-Event-ID", id.to_string());
+                    }
+
+                    match request.send().await {
+                        Ok(response) => break response,
+                        Err(_) => sleep(CONNECT_INTERVAL).await,
+                    }
+                };
+            }
+        }
+    }
+}
+
+/// Parses a server-sent event, the keep-alive comments carry no event.
+fn parse(message: &str) -> Option<Event> {
+    message
+        .lines()
+        .find_map(|line| line.strip_prefix("data: "))
+        .and_then(|data| serde_json::from_str(data).ok())
+}
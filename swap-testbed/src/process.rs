@@ -0,0 +1,106 @@
+//! The asb and swap processes of a scenario.
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+
+/// A running binary whose output is appended to a log file.
+///
+/// The binaries have to log in JSON, the fields of their log messages can be
+/// waited for with [`Process::wait_for_log`].
+#[derive(Debug)]
+pub struct Process {
+    name: &'static str,
+    child: Child,
+    logs: mpsc::UnboundedReceiver<Map<String, Value>>,
+}
+
+impl Process {
+    /// The process is killed when the returned [`Process`] is dropped.
+    pub fn spawn(
+        name: &'static str,
+        program: &Path,
+        args: &[String],
+        log_file: &Path,
+    ) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .with_context(|| format!("Failed to open log file {}", log_file.display()))?;
+
+        tracing::debug!(%name, program = %program.display(), ?args, "Starting process");
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", program.display()))?;
+
+        let stdout = child.stdout.take().context("stdout is piped")?;
+        let stderr = child.stderr.take().context("stderr is piped")?;
+        let (sender, logs) = mpsc::unbounded_channel();
+        tokio::spawn(forward(stdout, file.try_clone()?, None));
+        tokio::spawn(forward(stderr, file, Some(sender)));
+
+        Ok(Self { name, child, logs })
+    }
+
+    /// Waits for the next log message with the given text and returns its
+    /// fields.
+    pub async fn wait_for_log(&mut self, message: &str) -> Result<Map<String, Value>> {
+        loop {
+            let fields = self.logs.recv().await.with_context(|| {
+                format!("The {} exited before logging `{}`", self.name, message)
+            })?;
+
+            if fields.get("message").and_then(Value::as_str) == Some(message) {
+                return Ok(fields);
+            }
+        }
+    }
+
+    /// Kills the process without giving it a chance to shut down, like a
+    /// crash or a power outage would.
+    pub async fn kill(mut self) -> Result<()> {
+        self.child
+            .kill()
+            .await
+            .with_context(|| format!("Failed to kill the {}", self.name))?;
+
+        tracing::info!(name = %self.name, "Killed process");
+
+        Ok(())
+    }
+}
+
+/// Appends the lines of `output` to `file`, the fields of the JSON log
+/// messages among them are sent to `logs`.
+async fn forward(
+    output: impl AsyncRead + Unpin,
+    mut file: File,
+    logs: Option<mpsc::UnboundedSender<Map<String, Value>>>,
+) {
+    let mut lines = BufReader::new(output).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let _ = writeln!(file, "{}", line);
+
+        if let Some(logs) = &logs {
+            if let Ok(Value::Object(mut message)) = serde_json::from_str(&line) {
+                if let Some(Value::Object(fields)) = message.remove("fields") {
+                    let _ = logs.send(fields);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! The scripted swaps, each of them runs one swap between a fresh asb and
+//! Bob and checks the final states of both sides.
+
+use crate::monitor::Event;
+use crate::testbed::{Testbed, Timelocks};
+use anyhow::{bail, Result};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// How long a single step of a scenario may take, e.g. until the timelocks
+/// expire.
+const MAX_WAIT: Duration = Duration::from_secs(10 * 60);
+
+/// Timelocks that expire within a minute or two, for the scenarios that end
+/// in a refund or punishment.
+const FAST_TIMELOCKS: Timelocks = Timelocks {
+    cancel: 30,
+    punish: 30,
+};
+
+#[derive(structopt::StructOpt, Debug, Clone)]
+pub enum Scenario {
+    /// Bob buys XMR from the asb without interruptions
+    HappyPath,
+    /// Bob goes offline once his swap reaches a state and resumes it later
+    BobOffline {
+        #[structopt(
+            long = "at",
+            help = "The state of Bob's swap when he goes offline",
+            possible_values = &["SwapSetupCompleted", "BtcLocked", "XmrLockProofReceived", "XmrLocked", "EncSigSent"]
+        )]
+        at: String,
+
+        #[structopt(
+            long = "offline-secs",
+            help = "How long Bob stays offline",
+            default_value = "10"
+        )]
+        offline_secs: u64,
+    },
+    /// The asb goes offline before Bob sends the encrypted signature, so Bob
+    /// refunds and the asb refunds its XMR once it is back
+    AliceRefusesEncsig,
+    /// Bob goes offline after locking his BTC and only comes back after the
+    /// asb punished him
+    Punish,
+}
+
+impl Scenario {
+    /// The final states of the asb and Bob.
+    fn expected(&self) -> (&'static str, &'static str) {
+        match self {
+            Scenario::HappyPath | Scenario::BobOffline { .. } => ("BtcRedeemed", "BtcRedeemed"),
+            Scenario::AliceRefusesEncsig => ("XmrRefunded", "BtcRefunded"),
+            Scenario::Punish => ("BtcPunished", "BtcPunished"),
+        }
+    }
+
+    fn timelocks(&self) -> Option<Timelocks> {
+        match self {
+            Scenario::HappyPath | Scenario::BobOffline { .. } => None,
+            Scenario::AliceRefusesEncsig | Scenario::Punish => Some(FAST_TIMELOCKS),
+        }
+    }
+}
+
+/// Runs the scenario and fails if the swap does not end in the expected
+/// states.
+pub async fn run(testbed: &Testbed<'_>, scenario: &Scenario) -> Result<()> {
+    let (_bob_monitor_process, mut bob) = testbed.start_bob_monitor().await?;
+    let (asb_process, peer_id) = testbed.start_asb().await?;
+    let mut alice = testbed.asb_monitor().await?;
+    let bob_process = testbed.buy_xmr(&peer_id, scenario.timelocks()).await?;
+
+    let (alice_final, bob_final) = match scenario {
+        Scenario::HappyPath => {
+            let bob_final = bob.wait_for_final(MAX_WAIT).await?;
+            let alice_final = alice.wait_for_final(MAX_WAIT).await?;
+
+            (alice_final, bob_final)
+        }
+        Scenario::BobOffline { at, offline_secs } => {
+            let swap_id = bob.wait_for(at, MAX_WAIT).await?.swap_id;
+            bob_process.kill().await?;
+
+            tracing::info!(seconds = %offline_secs, "Bob is offline");
+            sleep(Duration::from_secs(*offline_secs)).await;
+
+            let _bob_process = testbed.resume(swap_id)?;
+            let bob_final = bob.wait_for_final(MAX_WAIT).await?;
+            let alice_final = alice.wait_for_final(MAX_WAIT).await?;
+
+            (alice_final, bob_final)
+        }
+        Scenario::AliceRefusesEncsig => {
+            alice.wait_for("XmrLockTransferProofSent", MAX_WAIT).await?;
+            asb_process.kill().await?;
+
+            tracing::info!("The asb is offline until Bob refunded");
+            let bob_final = bob.wait_for_final(MAX_WAIT).await?;
+
+            let _asb_process = testbed.start_asb().await?;
+            let alice_final = alice.wait_for_final(MAX_WAIT).await?;
+
+            (alice_final, bob_final)
+        }
+        Scenario::Punish => {
+            let swap_id = bob.wait_for("BtcLocked", MAX_WAIT).await?.swap_id;
+            bob_process.kill().await?;
+
+            tracing::info!("Bob is offline until the asb punished him");
+            let alice_final = alice.wait_for_final(MAX_WAIT).await?;
+
+            let _bob_process = testbed.resume(swap_id)?;
+            let bob_final = bob.wait_for_final(MAX_WAIT).await?;
+
+            (alice_final, bob_final)
+        }
+    };
+
+    check(scenario, &alice_final, &bob_final)
+}
+
+fn check(scenario: &Scenario, alice: &Event, bob: &Event) -> Result<()> {
+    let (expected_alice, expected_bob) = scenario.expected();
+
+    println!("Swap {}", bob.swap_id);
+    println!("  asb: {}", alice.state);
+    println!("  Bob: {}", bob.state);
+
+    if alice.event != expected_alice || bob.event != expected_bob {
+        bail!(
+            "Expected the asb to end in {} and Bob in {}, got {} and {}",
+            expected_alice,
+            expected_bob,
+            alice.event,
+            bob.event
+        )
+    }
+
+    Ok(())
+}
@@ -0,0 +1,292 @@
+//! Starts the asb and swap binaries against the [`Environment`].
+//!
+//! The asb is the only maker Bob knows about and every scenario runs exactly
+//! one swap, so the data directories of both binaries can be inspected after
+//! the scenario like the ones of a user.
+
+use crate::environment::Environment;
+use crate::monitor::Monitor;
+use crate::process::Process;
+use anyhow::{Context, Result};
+use get_port::get_port;
+use serde_json::Value;
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// The Bitcoin Bob deposits in satoshi, buying about 2 XMR at the price of
+/// the asb.
+const BOB_BTC_SAT: u64 = 1_000_000;
+
+/// The price of 1 XMR in BTC the asb asks for.
+const ASK_BTC: &str = "0.005";
+
+/// The cancel and punish timelocks Bob proposes.
+#[derive(Debug, Clone, Copy)]
+pub struct Timelocks {
+    pub cancel: u32,
+    pub punish: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ports {
+    asb_listen: u16,
+    asb_monitor: u16,
+    bob_monitor: u16,
+    unused: u16,
+}
+
+impl Ports {
+    fn new() -> Result<Self> {
+        let mut ports = std::iter::from_fn(get_port);
+        let mut next = || ports.next().context("No free port found");
+
+        Ok(Self {
+            asb_listen: next()?,
+            asb_monitor: next()?,
+            bob_monitor: next()?,
+            unused: next()?,
+        })
+    }
+}
+
+pub struct Testbed<'c> {
+    environment: Environment<'c>,
+    bin_dir: PathBuf,
+    data_dir: PathBuf,
+    ports: Ports,
+}
+
+impl<'c> Testbed<'c> {
+    /// Writes the config of the asb, the data of the asb and Bob is kept in
+    /// subdirectories of `data_dir`.
+    pub fn new(environment: Environment<'c>, bin_dir: PathBuf, data_dir: PathBuf) -> Result<Self> {
+        let testbed = Self {
+            environment,
+            bin_dir,
+            data_dir,
+            ports: Ports::new()?,
+        };
+
+        fs::create_dir_all(testbed.logs_dir())
+            .with_context(|| format!("Failed to create {}", testbed.logs_dir().display()))?;
+        fs::write(testbed.asb_config_file(), testbed.asb_config()?)
+            .context("Failed to write the config of the asb")?;
+
+        Ok(testbed)
+    }
+
+    /// Starts the asb and waits until it is listening, returns the process
+    /// and the peer id of the asb.
+    pub async fn start_asb(&self) -> Result<(Process, String)> {
+        let args = vec![
+            "--regtest".to_owned(),
+            "--log-format".to_owned(),
+            "json".to_owned(),
+            "--config".to_owned(),
+            self.asb_config_file().display().to_string(),
+            "start".to_owned(),
+            "--monitor-bind-address".to_owned(),
+            self.asb_monitor_address().to_string(),
+        ];
+
+        let mut asb = Process::spawn(
+            "asb",
+            &self.bin_dir.join("asb"),
+            &args,
+            &self.logs_dir().join("asb.log"),
+        )?;
+        let fields = asb.wait_for_log("Network layer initialized").await?;
+        let peer_id = fields
+            .get("peer_id")
+            .and_then(Value::as_str)
+            .context("The asb did not log its peer id")?
+            .to_owned();
+
+        tracing::info!(%peer_id, "Started asb");
+
+        Ok((asb, peer_id))
+    }
+
+    /// Follows the swaps of the asb, it has to be running already.
+    pub async fn asb_monitor(&self) -> Result<Monitor> {
+        Monitor::connect("asb", self.asb_monitor_address()).await
+    }
+
+    /// Serves and follows the swaps in the database of Bob, has to be started
+    /// before Bob's swap to receive all of its state transitions.
+    pub async fn start_bob_monitor(&self) -> Result<(Process, Monitor)> {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, self.ports.bob_monitor));
+        let args = self.swap_args(vec![
+            "monitor".to_owned(),
+            "--bind-address".to_owned(),
+            address.to_string(),
+        ]);
+
+        let process = Process::spawn(
+            "swap monitor",
+            &self.bin_dir.join("swap"),
+            &args,
+            &self.logs_dir().join("bob-monitor.log"),
+        )?;
+        let monitor = Monitor::connect("swap", address).await?;
+
+        Ok((process, monitor))
+    }
+
+    /// Starts a swap with the asb and deposits the Bitcoin Bob's swap waits
+    /// for.
+    pub async fn buy_xmr(&self, peer_id: &str, timelocks: Option<Timelocks>) -> Result<Process> {
+        let seller = format!(
+            "/ip4/127.0.0.1/tcp/{}/p2p/{}",
+            self.ports.asb_listen, peer_id
+        );
+        let mut command = vec![
+            "buy-xmr".to_owned(),
+            "--seller".to_owned(),
+            seller,
+            "--receive-address".to_owned(),
+            self.environment.monero_address().await?,
+        ];
+        if let Some(timelocks) = timelocks {
+            command.extend(vec![
+                "--cancel-timelock".to_owned(),
+                timelocks.cancel.to_string(),
+                "--punish-timelock".to_owned(),
+                timelocks.punish.to_string(),
+            ]);
+        }
+
+        let mut bob = self.start_bob(command)?;
+        let fields = bob.wait_for_log("Waiting for Bitcoin deposit").await?;
+        let deposit_address = fields
+            .get("deposit_address")
+            .and_then(Value::as_str)
+            .context("Bob did not log the deposit address")?;
+
+        let amount = bitcoin::Amount::from_sat(BOB_BTC_SAT);
+        self.environment.mint(deposit_address, amount).await?;
+        tracing::info!(%deposit_address, %amount, "Deposited Bitcoin for Bob");
+
+        Ok(bob)
+    }
+
+    /// Resumes Bob's swap after he went offline.
+    pub fn resume(&self, swap_id: Uuid) -> Result<Process> {
+        self.start_bob(vec![
+            "resume".to_owned(),
+            "--swap-id".to_owned(),
+            swap_id.to_string(),
+        ])
+    }
+
+    /// Logs how to use the environment with the binaries, e.g. to inspect it
+    /// after the scenario.
+    pub fn describe(&self) -> Result<()> {
+        tracing::info!(
+            electrum_rpc_url = %self.environment.electrum_rpc_url()?,
+            monero_daemon_address = %self.environment.monero_daemon_address(),
+            asb_config = %self.asb_config_file().display(),
+            bob_data_dir = %self.bob_data_dir().display(),
+            logs = %self.logs_dir().display(),
+            "Environment"
+        );
+
+        Ok(())
+    }
+
+    /// Starts the swap binary with a `command` that talks to the blockchains,
+    /// i.e. `buy-xmr` or `resume`.
+    fn start_bob(&self, mut command: Vec<String>) -> Result<Process> {
+        command.extend(vec![
+            "--electrum-rpc".to_owned(),
+            self.environment.electrum_rpc_url()?.to_string(),
+            "--monero-daemon-address".to_owned(),
+            self.environment.monero_daemon_address(),
+            "--monero-light-wallet".to_owned(),
+        ]);
+
+        Process::spawn(
+            "swap",
+            &self.bin_dir.join("swap"),
+            &self.swap_args(command),
+            &self.logs_dir().join("bob.log"),
+        )
+    }
+
+    fn swap_args(&self, command: Vec<String>) -> Vec<String> {
+        let mut args = vec![
+            "--regtest".to_owned(),
+            "--data-base-dir".to_owned(),
+            self.bob_data_dir().display().to_string(),
+            "--log-format".to_owned(),
+            "json".to_owned(),
+        ];
+        args.extend(command);
+
+        args
+    }
+
+    fn asb_config(&self) -> Result<String> {
+        // The Tor ports point to a closed port so the asb runs on clear net,
+        // even if Tor is running on the machine.
+        let config = format!(
+            r#"[data]
+dir = '{data_dir}'
+
+[network]
+listen = ["/ip4/127.0.0.1/tcp/{listen_port}"]
+
+[bitcoin]
+electrum_rpc_url = "{electrum_rpc_url}"
+target_block = 1
+network = "Regtest"
+cancel_timelock_range = {{ min = 10, max = 100 }}
+punish_timelock_range = {{ min = 10, max = 50 }}
+
+[monero]
+wallet_rpc_url = "{wallet_rpc_url}"
+network = "Mainnet"
+
+[tor]
+control_port = {unused_port}
+socks5_port = {unused_port}
+
+[maker]
+min_buy_btc = 0.0001
+max_buy_btc = 1.0
+ask_spread = 0.0
+price_ticker_ws_url = "wss://ws.kraken.com"
+
+[[maker.price_feeds]]
+type = "static"
+ask_btc = {ask_btc}
+"#,
+            data_dir = self.data_dir.join("asb").display(),
+            listen_port = self.ports.asb_listen,
+            electrum_rpc_url = self.environment.electrum_rpc_url()?,
+            wallet_rpc_url = self.environment.asb_wallet_rpc_url()?,
+            unused_port = self.ports.unused,
+            ask_btc = ASK_BTC,
+        );
+
+        Ok(config)
+    }
+
+    fn asb_monitor_address(&self) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.ports.asb_monitor))
+    }
+
+    fn asb_config_file(&self) -> PathBuf {
+        self.data_dir.join("asb-config.toml")
+    }
+
+    fn bob_data_dir(&self) -> PathBuf {
+        self.data_dir.join("bob")
+    }
+
+    fn logs_dir(&self) -> PathBuf {
+        self.data_dir.join("logs")
+    }
+}
@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS swap_setup_parameters
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    parameters  TEXT                NOT NULL
+);
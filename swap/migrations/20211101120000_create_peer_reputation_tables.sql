@@ -0,0 +1,14 @@
+CREATE TABLE if NOT EXISTS peer_misbehaviours
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    peer_id     TEXT                NOT NULL,
+    recorded_at INTEGER             NOT NULL,
+    kind        TEXT                NOT NULL
+);
+
+CREATE TABLE if NOT EXISTS banned_peers
+(
+    peer_id         TEXT    PRIMARY KEY NOT NULL,
+    banned_until    INTEGER,
+    reason          TEXT                NOT NULL
+);
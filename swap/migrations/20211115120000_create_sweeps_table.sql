@@ -0,0 +1,8 @@
+CREATE TABLE if NOT EXISTS sweeps
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    txid        TEXT                NOT NULL,
+    address     TEXT                NOT NULL,
+    amount      INTEGER             NOT NULL,
+    swept_at    INTEGER             NOT NULL
+);
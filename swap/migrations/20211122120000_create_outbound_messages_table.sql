@@ -0,0 +1,6 @@
+CREATE TABLE if NOT EXISTS outbound_messages
+(
+    swap_id     TEXT PRIMARY KEY    NOT NULL,
+    peer_id     TEXT                NOT NULL,
+    message     TEXT                NOT NULL
+);
@@ -0,0 +1,7 @@
+CREATE TABLE if NOT EXISTS address_book
+(
+    label       TEXT                NOT NULL,
+    kind        TEXT                NOT NULL,
+    address     TEXT                NOT NULL,
+    PRIMARY KEY (label, kind)
+);
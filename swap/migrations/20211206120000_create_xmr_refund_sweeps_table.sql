@@ -0,0 +1,9 @@
+CREATE TABLE if NOT EXISTS xmr_refund_sweeps
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    swap_id     TEXT                NOT NULL,
+    txid        TEXT                NOT NULL,
+    address     TEXT                NOT NULL,
+    amount      INTEGER             NOT NULL,
+    fee         INTEGER             NOT NULL
+);
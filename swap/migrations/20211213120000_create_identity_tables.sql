@@ -0,0 +1,14 @@
+CREATE TABLE if NOT EXISTS identity_rotations
+(
+    generation                  INTEGER PRIMARY KEY NOT NULL,
+    rotated_at                  INTEGER             NOT NULL,
+    overlap_until               INTEGER             NOT NULL,
+    previous_listen             TEXT                NOT NULL,
+    previous_external_addresses TEXT                NOT NULL
+);
+
+CREATE TABLE if NOT EXISTS swap_identities
+(
+    swap_id     TEXT PRIMARY KEY    NOT NULL,
+    generation  INTEGER             NOT NULL
+);
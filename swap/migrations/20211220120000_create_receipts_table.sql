@@ -0,0 +1,6 @@
+CREATE TABLE if NOT EXISTS receipts
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    swap_id     TEXT                NOT NULL,
+    receipt     TEXT                NOT NULL
+);
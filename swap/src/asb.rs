@@ -1,12 +1,27 @@
+pub mod accounting;
 pub mod command;
 pub mod config;
 mod event_loop;
+pub mod identity;
+pub mod metrics;
 mod network;
+pub mod outbox;
+pub mod pricing;
+pub mod punish_scheduler;
 mod rate;
+pub mod reachability;
 mod recovery;
+pub mod reputation;
+pub mod reservations;
+pub mod rpc;
+pub mod shutdown;
+pub mod sweep;
 pub mod tracing;
 
-pub use event_loop::{EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate};
+pub use event_loop::{
+    Control, ControlResponse, EventLoop, EventLoopController, EventLoopHandle, FixedRate,
+    LatestRate, MakerStatus, PriceFeedRate,
+};
 pub use network::behaviour::{Behaviour, OutEvent};
 pub use network::transport;
 pub use rate::Rate;
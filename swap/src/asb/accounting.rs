@@ -0,0 +1,526 @@
+//! Reports the Bitcoin and Monero every swap of the ASB received and sent, for
+//! accounting.
+//!
+//! The report is assembled from the states of the swaps in the database. The
+//! fees of the lock transactions are not recorded there, they are looked up in
+//! the Bitcoin wallet after syncing it with Electrum and in the Monero wallet.
+
+use crate::protocol::alice::AliceState;
+use crate::protocol::bob::BobState;
+use crate::protocol::{Database, State};
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use rust_decimal::Decimal;
+use std::convert::TryFrom;
+use std::fmt;
+use time::{Date, Month};
+use uuid::Uuid;
+
+/// Whether the ASB sold or bought XMR in a swap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Sell,
+    Buy,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Sell => write!(f, "sell"),
+            Side::Buy => write!(f, "buy"),
+        }
+    }
+}
+
+/// The days of which the swaps are reported, both inclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Period {
+    pub from: Option<Date>,
+    pub to: Option<Date>,
+}
+
+impl Period {
+    pub fn new(from: Option<Date>, to: Option<Date>) -> Result<Self> {
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                bail!("The period ends on {} before it starts on {}", to, from)
+            }
+        }
+
+        Ok(Self { from, to })
+    }
+
+    pub fn contains(&self, date: Date) -> bool {
+        self.from.map_or(true, |from| from <= date) && self.to.map_or(true, |to| date <= to)
+    }
+}
+
+/// Parses a date in the format `YYYY-MM-DD`.
+pub fn parse_date(s: &str) -> Result<Date> {
+    let parts = s.split('-').collect::<Vec<_>>();
+    let (year, month, day) = match parts.as_slice() {
+        [year, month, day] => (year, month, day),
+        _ => bail!("Invalid date `{}`, expected the format YYYY-MM-DD", s),
+    };
+
+    let month = Month::try_from(month.parse::<u8>()?)?;
+    let date = Date::from_calendar_date(year.parse()?, month, day.parse()?)
+        .with_context(|| format!("Invalid date `{}`", s))?;
+
+    Ok(date)
+}
+
+/// What a swap moved on both chains.
+///
+/// The amounts are the ones agreed upon in the swap, the fees the ASB paid are
+/// reported separately. Received and sent amounts are only reported once the
+/// swap is finished.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapReport {
+    pub swap_id: Uuid,
+    pub side: Side,
+    /// When the swap entered its latest state.
+    pub updated_at: String,
+    pub outcome: String,
+    pub btc_received: bitcoin::Amount,
+    pub btc_sent: bitcoin::Amount,
+    pub xmr_received: monero::Amount,
+    pub xmr_sent: monero::Amount,
+    pub bitcoin_fees: bitcoin::Amount,
+    pub monero_fees: monero::Amount,
+    /// The subaddress the Monero of the swap is refunded or received on.
+    pub monero_subaddress: Option<monero::Address>,
+}
+
+impl SwapReport {
+    pub const HEADER: [&'static str; 12] = [
+        "swap_id",
+        "side",
+        "updated_at",
+        "outcome",
+        "btc_received",
+        "btc_sent",
+        "xmr_received",
+        "xmr_sent",
+        "rate",
+        "bitcoin_fees",
+        "monero_fees",
+        "monero_subaddress",
+    ];
+
+    /// The fields of the report in the order of the [`SwapReport::HEADER`],
+    /// BTC and XMR without the unit.
+    pub fn row(&self) -> Vec<String> {
+        vec![
+            self.swap_id.to_string(),
+            self.side.to_string(),
+            self.updated_at.clone(),
+            self.outcome.clone(),
+            btc(self.btc_received),
+            btc(self.btc_sent),
+            xmr(self.xmr_received),
+            xmr(self.xmr_sent),
+            self.rate().map(|rate| rate.to_string()).unwrap_or_default(),
+            btc(self.bitcoin_fees),
+            xmr(self.monero_fees),
+            self.monero_subaddress
+                .as_ref()
+                .map(|address| address.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+
+    /// The BTC paid per XMR, if the swap exchanged both.
+    pub fn rate(&self) -> Option<Decimal> {
+        let btc = self.btc_received + self.btc_sent;
+        let xmr = self.xmr_received.as_piconero() + self.xmr_sent.as_piconero();
+        if btc == bitcoin::Amount::ZERO || xmr == 0 {
+            return None;
+        }
+
+        let rate = Decimal::from(btc.as_sat()) * Decimal::from(monero::PICONERO_OFFSET)
+            / Decimal::from(xmr)
+            / Decimal::from(bitcoin::Amount::ONE_BTC.as_sat());
+
+        Some(rate.round_dp(8))
+    }
+}
+
+/// Formats the reports as CSV, starting with the header.
+pub fn to_csv(reports: &[SwapReport]) -> String {
+    let mut csv = SwapReport::HEADER.join(",");
+    csv.push('\n');
+
+    for report in reports {
+        let row = report
+            .row()
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn btc(amount: bitcoin::Amount) -> String {
+    amount.to_string_in(::bitcoin::Denomination::Bitcoin)
+}
+
+fn xmr(amount: monero::Amount) -> String {
+    let mut decimal = Decimal::from(amount.as_piconero());
+    decimal
+        .set_scale(12)
+        .expect("12 is smaller than max precision of 28");
+
+    decimal.to_string()
+}
+
+/// The report of every swap that entered its latest state within the period.
+pub async fn report(
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &bitcoin::Wallet,
+    monero_wallet: &monero::Wallet,
+    period: Period,
+) -> Result<Vec<SwapReport>> {
+    bitcoin_wallet.sync().await?;
+
+    let mut reports = Vec::new();
+    for (swap_id, _) in db.all().await? {
+        let recorded = match RecordedSwap::new(swap_id, db.get_states(swap_id).await?) {
+            Some(recorded) => recorded,
+            None => continue,
+        };
+        if !period.contains(recorded.updated_on()?) {
+            continue;
+        }
+
+        let bitcoin_lock_fee = match recorded.published_tx_lock {
+            Some(txid) => bitcoin_wallet
+                .transaction_fee(txid)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to look up the fee of the Bitcoin lock of swap {}",
+                        swap_id
+                    )
+                })?,
+            None => bitcoin::Amount::ZERO,
+        };
+        let mut monero_fees = match &recorded.published_xmr_lock {
+            Some(tx_hash) => monero_wallet
+                .transaction_fee(tx_hash)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to look up the fee of the Monero lock of swap {}",
+                        swap_id
+                    )
+                })?,
+            None => monero::Amount::ZERO,
+        };
+        for sweep in db.get_xmr_refund_sweeps(swap_id).await? {
+            monero_fees = monero_fees + sweep.fee;
+        }
+
+        let mut report = recorded.report(bitcoin_lock_fee, monero_fees);
+        report.monero_subaddress = db.get_monero_address(swap_id).await.ok();
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+/// How a finished swap ended for the ASB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Settlement {
+    /// BTC and XMR were exchanged.
+    Exchanged,
+    /// Both sides got their funds back.
+    Refunded,
+    /// The party that sold XMR took the BTC after the buyer did not refund.
+    Punished,
+}
+
+/// What the database knows about a swap.
+#[derive(Debug, Clone, PartialEq)]
+struct RecordedSwap {
+    swap_id: Uuid,
+    side: Side,
+    updated_at: String,
+    outcome: String,
+    settlement: Option<Settlement>,
+    btc: Option<bitcoin::Amount>,
+    xmr: Option<monero::Amount>,
+    tx_fees: Option<bitcoin::TxFees>,
+    /// The Bitcoin lock transaction, if the ASB published it.
+    published_tx_lock: Option<bitcoin::Txid>,
+    /// The Monero lock transaction, if the ASB published it.
+    published_xmr_lock: Option<monero::TxHash>,
+}
+
+impl RecordedSwap {
+    /// Returns `None` for a swap without states.
+    fn new(swap_id: Uuid, states: Vec<(String, State)>) -> Option<Self> {
+        let (updated_at, latest) = match states.last() {
+            Some((entered_at, state)) => (entered_at.clone(), state),
+            None => return None,
+        };
+
+        let mut recorded = RecordedSwap {
+            swap_id,
+            side: match latest {
+                State::Alice(_) => Side::Sell,
+                State::Bob(_) => Side::Buy,
+            },
+            updated_at,
+            outcome: latest.to_string(),
+            settlement: settlement(latest),
+            btc: None,
+            xmr: None,
+            tx_fees: None,
+            published_tx_lock: None,
+            published_xmr_lock: None,
+        };
+
+        for (_, state) in &states {
+            match state {
+                State::Alice(state) => {
+                    recorded.btc = recorded.btc.or_else(|| state.btc_amount());
+                    recorded.xmr = recorded.xmr.or_else(|| state.xmr_amount());
+                    recorded.tx_fees = recorded.tx_fees.or_else(|| state.tx_fees());
+                    if recorded.published_xmr_lock.is_none() {
+                        recorded.published_xmr_lock = state.xmr_lock_tx_hash();
+                    }
+                }
+                State::Bob(state) => {
+                    // The lock amount is only known precisely once the lock
+                    // transaction was built, hence later states take precedence.
+                    recorded.btc = state.btc_amount().or(recorded.btc);
+                    recorded.xmr = recorded.xmr.or_else(|| state.xmr_amount());
+                    recorded.tx_fees = recorded.tx_fees.or_else(|| state.tx_fees());
+                    if let BobState::BtcLocked(state3) = state {
+                        recorded.published_tx_lock = Some(state3.tx_lock_id());
+                    }
+                }
+            }
+        }
+
+        Some(recorded)
+    }
+
+    fn updated_on(&self) -> Result<Date> {
+        // the states are recorded with the `Display` of `OffsetDateTime`, it starts
+        // with the date
+        let date = self.updated_at.split(' ').next().unwrap_or_default();
+
+        parse_date(date).with_context(|| {
+            format!(
+                "Failed to read when swap {} entered its latest state",
+                self.swap_id
+            )
+        })
+    }
+
+    fn report(&self, bitcoin_lock_fee: bitcoin::Amount, monero_fees: monero::Amount) -> SwapReport {
+        let btc = self.btc.unwrap_or(bitcoin::Amount::ZERO);
+        let xmr = self.xmr.unwrap_or(monero::Amount::ZERO);
+        let tx_fees = self.tx_fees.unwrap_or(bitcoin::TxFees {
+            redeem: bitcoin::Amount::ZERO,
+            cancel: bitcoin::Amount::ZERO,
+            refund: bitcoin::Amount::ZERO,
+            punish: bitcoin::Amount::ZERO,
+        });
+
+        let mut report = SwapReport {
+            swap_id: self.swap_id,
+            side: self.side,
+            updated_at: self.updated_at.clone(),
+            outcome: self.outcome.clone(),
+            btc_received: bitcoin::Amount::ZERO,
+            btc_sent: bitcoin::Amount::ZERO,
+            xmr_received: monero::Amount::ZERO,
+            xmr_sent: monero::Amount::ZERO,
+            bitcoin_fees: bitcoin_lock_fee,
+            monero_fees,
+            monero_subaddress: None,
+        };
+
+        // The transactions spending the locked Bitcoin pay their fee from it, the party
+        // that ends up with the Bitcoin pays them.
+        match (self.side, self.settlement) {
+            (Side::Sell, Some(Settlement::Exchanged)) => {
+                report.btc_received = btc;
+                report.xmr_sent = xmr;
+                report.bitcoin_fees += tx_fees.redeem;
+            }
+            (Side::Sell, Some(Settlement::Punished)) => {
+                report.btc_received = btc;
+                report.xmr_sent = xmr;
+                report.bitcoin_fees += tx_fees.cancel + tx_fees.punish;
+            }
+            (Side::Buy, Some(Settlement::Exchanged)) => {
+                report.btc_sent = btc;
+                report.xmr_received = xmr;
+            }
+            (Side::Buy, Some(Settlement::Punished)) => {
+                report.btc_sent = btc;
+            }
+            (Side::Buy, Some(Settlement::Refunded)) => {
+                report.bitcoin_fees += tx_fees.cancel + tx_fees.refund;
+            }
+            (Side::Sell, Some(Settlement::Refunded)) | (_, None) => {}
+        }
+
+        report
+    }
+}
+
+fn settlement(state: &State) -> Option<Settlement> {
+    match state {
+        State::Alice(AliceState::BtcRedeemed) | State::Bob(BobState::XmrRedeemed { .. }) => {
+            Some(Settlement::Exchanged)
+        }
+        State::Alice(AliceState::XmrRefunded) | State::Bob(BobState::BtcRefunded(_)) => {
+            Some(Settlement::Refunded)
+        }
+        State::Alice(AliceState::BtcPunished) | State::Bob(BobState::BtcPunished { .. }) => {
+            Some(Settlement::Punished)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::OffsetDateTime;
+
+    fn recorded(side: Side, settlement: Option<Settlement>) -> RecordedSwap {
+        RecordedSwap {
+            swap_id: Uuid::new_v4(),
+            side,
+            updated_at: OffsetDateTime::from_unix_timestamp(1_640_000_000)
+                .unwrap()
+                .to_string(),
+            outcome: "finished".to_string(),
+            settlement,
+            btc: Some(bitcoin::Amount::from_sat(1_000_000)),
+            xmr: Some(monero::Amount::from_monero(0.4).unwrap()),
+            tx_fees: Some(bitcoin::TxFees {
+                redeem: bitcoin::Amount::from_sat(100),
+                cancel: bitcoin::Amount::from_sat(200),
+                refund: bitcoin::Amount::from_sat(300),
+                punish: bitcoin::Amount::from_sat(400),
+            }),
+            published_tx_lock: None,
+            published_xmr_lock: None,
+        }
+    }
+
+    #[test]
+    fn sold_xmr_is_reported_with_the_redeem_fee() {
+        let report = recorded(Side::Sell, Some(Settlement::Exchanged)).report(
+            bitcoin::Amount::ZERO,
+            monero::Amount::from_piconero(30_000_000),
+        );
+
+        assert_eq!(report.btc_received, bitcoin::Amount::from_sat(1_000_000));
+        assert_eq!(report.xmr_sent, monero::Amount::from_monero(0.4).unwrap());
+        assert_eq!(report.bitcoin_fees, bitcoin::Amount::from_sat(100));
+        assert_eq!(
+            report.monero_fees,
+            monero::Amount::from_piconero(30_000_000)
+        );
+        assert_eq!(report.rate(), Some(Decimal::new(25, 3)));
+    }
+
+    #[test]
+    fn refunded_swaps_only_report_fees() {
+        let sold = recorded(Side::Sell, Some(Settlement::Refunded))
+            .report(bitcoin::Amount::ZERO, monero::Amount::from_piconero(50));
+        assert_eq!(sold.btc_received, bitcoin::Amount::ZERO);
+        assert_eq!(sold.xmr_sent, monero::Amount::ZERO);
+        assert_eq!(sold.bitcoin_fees, bitcoin::Amount::ZERO);
+        assert_eq!(sold.rate(), None);
+
+        let bought = recorded(Side::Buy, Some(Settlement::Refunded))
+            .report(bitcoin::Amount::from_sat(1_000), monero::Amount::ZERO);
+        assert_eq!(bought.btc_sent, bitcoin::Amount::ZERO);
+        assert_eq!(bought.bitcoin_fees, bitcoin::Amount::from_sat(1_500));
+    }
+
+    #[test]
+    fn punished_buyer_loses_the_bitcoin() {
+        let report = recorded(Side::Buy, Some(Settlement::Punished))
+            .report(bitcoin::Amount::from_sat(1_000), monero::Amount::ZERO);
+
+        assert_eq!(report.btc_sent, bitcoin::Amount::from_sat(1_000_000));
+        assert_eq!(report.xmr_received, monero::Amount::ZERO);
+        assert_eq!(report.bitcoin_fees, bitcoin::Amount::from_sat(1_000));
+    }
+
+    #[test]
+    fn unfinished_swaps_move_nothing() {
+        let report = recorded(Side::Buy, None)
+            .report(bitcoin::Amount::from_sat(1_000), monero::Amount::ZERO);
+
+        assert_eq!(report.btc_sent, bitcoin::Amount::ZERO);
+        assert_eq!(report.xmr_received, monero::Amount::ZERO);
+        assert_eq!(report.bitcoin_fees, bitcoin::Amount::from_sat(1_000));
+    }
+
+    #[test]
+    fn csv_has_a_row_per_swap() {
+        let report = recorded(Side::Sell, Some(Settlement::Exchanged)).report(
+            bitcoin::Amount::ZERO,
+            monero::Amount::from_piconero(30_000_000),
+        );
+
+        let csv = to_csv(&[report.clone()]);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], SwapReport::HEADER.join(","));
+
+        let fields = lines[1].split(',').collect::<Vec<_>>();
+        assert_eq!(fields.len(), SwapReport::HEADER.len());
+        assert_eq!(fields[0], report.swap_id.to_string());
+        assert_eq!(
+            bitcoin::Amount::from_str_in(fields[4], ::bitcoin::Denomination::Bitcoin).unwrap(),
+            report.btc_received
+        );
+        assert_eq!(
+            monero::Amount::parse_monero(fields[7]).unwrap(),
+            report.xmr_sent
+        );
+        assert_eq!(fields[8].parse::<Decimal>().unwrap(), Decimal::new(25, 3));
+        assert_eq!(fields[11], "");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+    }
+
+    #[test]
+    fn swaps_are_reported_on_the_day_of_their_latest_state() {
+        let swap = recorded(Side::Sell, None);
+        let day = parse_date("2021-12-20").unwrap();
+        assert_eq!(swap.updated_on().unwrap(), day);
+
+        assert!(Period::default().contains(day));
+        assert!(Period::new(Some(day), Some(day)).unwrap().contains(day));
+        assert!(!Period::new(None, Some(parse_date("2021-12-19").unwrap()))
+            .unwrap()
+            .contains(day));
+        assert!(Period::new(Some(day), Some(parse_date("2021-12-19").unwrap())).is_err());
+
+        assert!(parse_date("2021-13-01").is_err());
+        assert!(parse_date("20.12.2021").is_err());
+    }
+}
@@ -1,15 +1,24 @@
+use crate::asb::accounting::{parse_date, Period};
 use crate::asb::config::GetDefaults;
 use crate::bitcoin::Amount;
+use crate::encryption::PassphraseSource;
 use crate::env;
 use crate::env::GetConfig;
+use crate::logging::LogFormat;
 use anyhow::{bail, Result};
 use bitcoin::Address;
+use libp2p::PeerId;
 use serde::Serialize;
 use std::ffi::OsString;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 use structopt::StructOpt;
+use time::Date;
 use uuid::Uuid;
 
+pub const PASSPHRASE_ENV_VAR: &str = "ASB_PASSPHRASE";
+
 pub fn parse_args<I, T>(raw_args: I) -> Result<Arguments>
 where
     I: IntoIterator<Item = T>,
@@ -18,33 +27,80 @@ where
     let matches = RawArguments::clap().get_matches_from_safe(raw_args)?;
     let args = RawArguments::from_clap(&matches);
 
-    let json = args.json;
+    // `--json` predates `--log-format`
+    let log_format = if args.json {
+        LogFormat::Json
+    } else {
+        args.log_format
+    };
     let disable_timestamp = args.disable_timestamp;
+    let passphrase = match (args.passphrase_file, args.encrypt) {
+        (Some(path), _) => Some(PassphraseSource::File(path)),
+        (None, true) => Some(PassphraseSource::Prompt),
+        (None, false) => None,
+    };
     let testnet = args.testnet;
+    let regtest = args.regtest;
     let config = args.config;
     let command: RawCommand = args.cmd;
 
-    let arguments = match command {
-        RawCommand::Start { resume_only } => Arguments {
+    let mut arguments = match command {
+        RawCommand::Start {
+            resume_only,
+            rpc_bind_address,
+            monitor_bind_address,
+            metrics_bind_address,
+            drain_timeout_secs,
+        } => Arguments {
+            testnet,
+            log_format,
+            disable_timestamp,
+            passphrase,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Start {
+                resume_only,
+                rpc_bind_address,
+                monitor_bind_address,
+                metrics_bind_address,
+                drain_timeout: Duration::from_secs(drain_timeout_secs),
+            },
+        },
+        RawCommand::History { from, to, csv } => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
-            cmd: Command::Start { resume_only },
+            cmd: Command::History {
+                period: Period::new(from, to)?,
+                csv,
+            },
         },
-        RawCommand::History => Arguments {
+        RawCommand::Sweeps => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
-            cmd: Command::History,
+            cmd: Command::Sweeps,
+        },
+        RawCommand::ImportSled { sled_dir } => Arguments {
+            testnet,
+            log_format,
+            disable_timestamp,
+            passphrase,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::ImportSled { sled_dir },
         },
         RawCommand::WithdrawBtc { amount, address } => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::WithdrawBtc {
@@ -54,35 +110,74 @@ where
         },
         RawCommand::Balance => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Balance,
         },
         RawCommand::Config => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Config,
         },
         RawCommand::ExportBitcoinWallet => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::ExportBitcoinWallet,
         },
+        RawCommand::Ban {
+            peer_id,
+            hours,
+            reason,
+        } => Arguments {
+            testnet,
+            log_format,
+            disable_timestamp,
+            passphrase,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Ban {
+                peer_id,
+                hours,
+                reason,
+            },
+        },
+        RawCommand::Unban { peer_id } => Arguments {
+            testnet,
+            log_format,
+            disable_timestamp,
+            passphrase,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Unban { peer_id },
+        },
+        RawCommand::RotateIdentity { overlap_hours } => Arguments {
+            testnet,
+            log_format,
+            disable_timestamp,
+            passphrase,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::RotateIdentity { overlap_hours },
+        },
         RawCommand::ManualRecovery(ManualRecovery::Redeem {
             redeem_params: RecoverCommandParams { swap_id },
             do_not_await_finality,
         }) => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Redeem {
@@ -95,8 +190,9 @@ where
             cancel_params: RecoverCommandParams { swap_id },
         }) => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Cancel { swap_id },
@@ -105,8 +201,9 @@ where
             refund_params: RecoverCommandParams { swap_id },
         }) => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Refund { swap_id },
@@ -115,22 +212,29 @@ where
             punish_params: RecoverCommandParams { swap_id },
         }) => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Punish { swap_id },
         },
         RawCommand::ManualRecovery(ManualRecovery::SafelyAbort { swap_id }) => Arguments {
             testnet,
-            json,
+            log_format,
             disable_timestamp,
+            passphrase,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::SafelyAbort { swap_id },
         },
     };
 
+    // everything but the blockchain parameters keeps the mainnet defaults
+    if regtest {
+        arguments.env_config = env::Regtest::get_config();
+    }
+
     Ok(arguments)
 }
 
@@ -183,8 +287,11 @@ pub struct BitcoinAddressNetworkMismatch {
 #[derive(Debug, PartialEq)]
 pub struct Arguments {
     pub testnet: bool,
-    pub json: bool,
+    pub log_format: LogFormat,
     pub disable_timestamp: bool,
+    /// Where to read the passphrase from that encrypts the seed and the
+    /// database, if it is not given in the environment.
+    pub passphrase: Option<PassphraseSource>,
     pub config_path: PathBuf,
     pub env_config: env::Config,
     pub cmd: Command,
@@ -194,8 +301,20 @@ pub struct Arguments {
 pub enum Command {
     Start {
         resume_only: bool,
+        rpc_bind_address: Option<SocketAddr>,
+        monitor_bind_address: Option<SocketAddr>,
+        metrics_bind_address: Option<SocketAddr>,
+        drain_timeout: Duration,
+    },
+    History {
+        period: Period,
+        csv: bool,
+    },
+    Sweeps,
+    ImportSled {
+        /// The `database` directory in the data directory if not given.
+        sled_dir: Option<PathBuf>,
     },
-    History,
     Config,
     WithdrawBtc {
         amount: Option<Amount>,
@@ -219,6 +338,17 @@ pub enum Command {
         swap_id: Uuid,
     },
     ExportBitcoinWallet,
+    Ban {
+        peer_id: PeerId,
+        hours: Option<u32>,
+        reason: String,
+    },
+    Unban {
+        peer_id: PeerId,
+    },
+    RotateIdentity {
+        overlap_hours: u32,
+    },
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -232,13 +362,29 @@ pub struct RawArguments {
     #[structopt(long, help = "Swap on testnet")]
     pub testnet: bool,
 
+    #[structopt(
+        long,
+        hidden = true,
+        conflicts_with = "testnet",
+        help = "Swap on regtest, for local test environments like swap-testbed. The config file has to be for the Regtest Bitcoin network and the Mainnet Monero network."
+    )]
+    pub regtest: bool,
+
     #[structopt(
         short,
         long = "json",
-        help = "Changes the log messages to json vs plain-text. If you run ASB as a service, it is recommended to set this to true to simplify log analyses."
+        help = "Changes the log messages to json vs plain-text, same as `--log-format json`."
     )]
     pub json: bool,
 
+    #[structopt(
+        long = "log-format",
+        help = "The format of the log messages, either text or json. If you run ASB as a service, it is recommended to set this to json to simplify log analyses.",
+        default_value = "text",
+        possible_values = &["text", "json"]
+    )]
+    pub log_format: LogFormat,
+
     #[structopt(
         short,
         long = "disable-timestamp",
@@ -253,6 +399,20 @@ pub struct RawArguments {
     )]
     pub config: Option<PathBuf>,
 
+    #[structopt(
+        long = "passphrase-file",
+        help = "Read the passphrase that encrypts the seed and the database from the first line of this file, e.g. /dev/fd/3. The passphrase can also be given in the ASB_PASSPHRASE environment variable.",
+        parse(from_os_str)
+    )]
+    pub passphrase_file: Option<PathBuf>,
+
+    #[structopt(
+        long = "encrypt",
+        conflicts_with = "passphrase-file",
+        help = "Ask for a passphrase to encrypt the seed and the database with. Once they are encrypted, the passphrase is asked for on every start unless it is given otherwise."
+    )]
+    pub encrypt: bool,
+
     #[structopt(subcommand)]
     pub cmd: RawCommand,
 }
@@ -267,9 +427,60 @@ pub enum RawCommand {
             help = "For maintenance only. When set, no new swap requests will be accepted, but existing unfinished swaps will be resumed."
         )]
         resume_only: bool,
+        #[structopt(
+            long = "rpc-bind-address",
+            help = "Start a JSON-RPC server on the given loopback address that allows to control the ASB at runtime. Requests are authenticated with the token the server writes to the .rpc-cookie file in the data directory."
+        )]
+        rpc_bind_address: Option<SocketAddr>,
+        #[structopt(
+            long = "monitor-bind-address",
+            help = "Serve the state transitions of all swaps as server-sent events on the given address. The event stream is unauthenticated, only bind it to a local interface."
+        )]
+        monitor_bind_address: Option<SocketAddr>,
+        #[structopt(
+            long = "metrics-bind-address",
+            help = "Serve metrics in the Prometheus text format on the given address. The endpoint is unauthenticated, only bind it to a local interface."
+        )]
+        metrics_bind_address: Option<SocketAddr>,
+        #[structopt(
+            long = "drain-timeout",
+            help = "The number of seconds to wait on SIGTERM or SIGINT for the running swaps to reach a point where they can be stopped safely, before exiting anyway. The swaps are resumed on the next start.",
+            default_value = "600"
+        )]
+        drain_timeout_secs: u64,
+    },
+    #[structopt(
+        about = "Prints the BTC and XMR each swap received and sent, the fees paid and the outcome. Requires the monero-wallet-rpc to be running."
+    )]
+    History {
+        #[structopt(
+            long = "from",
+            help = "Only report swaps that entered their latest state on or after this day, given as YYYY-MM-DD.",
+            parse(try_from_str = parse_date)
+        )]
+        from: Option<Date>,
+        #[structopt(
+            long = "to",
+            help = "Only report swaps that entered their latest state on or before this day, given as YYYY-MM-DD.",
+            parse(try_from_str = parse_date)
+        )]
+        to: Option<Date>,
+        #[structopt(long = "csv", help = "Print the report as CSV instead of a table.")]
+        csv: bool,
+    },
+    #[structopt(about = "Prints the sweeps of the redeemed Bitcoin to the cold wallet.")]
+    Sweeps,
+    #[structopt(
+        about = "Imports the swaps of the sled database of releases before 0.10.0 into the sqlite database. Requires a build with the sled-import feature."
+    )]
+    ImportSled {
+        #[structopt(
+            long = "sled-dir",
+            help = "The directory of the sled database, the database directory in the data directory by default.",
+            parse(from_os_str)
+        )]
+        sled_dir: Option<PathBuf>,
     },
-    #[structopt(about = "Prints swap-id and the state of each swap ever made.")]
-    History,
     #[structopt(about = "Prints the current config")]
     Config,
     #[structopt(about = "Allows withdrawing BTC from the internal Bitcoin wallet.")]
@@ -288,6 +499,40 @@ pub enum RawCommand {
     Balance,
     #[structopt(about = "Print the internal bitcoin wallet descriptor.")]
     ExportBitcoinWallet,
+    #[structopt(
+        about = "Bans a peer from connecting to the ASB. A running ASB enforces the ban within a minute."
+    )]
+    Ban {
+        #[structopt(long = "peer-id", help = "The peer id of the peer to ban.")]
+        peer_id: PeerId,
+        #[structopt(
+            long = "hours",
+            help = "Optionally specify for how many hours the peer is banned. If not specified the peer is banned until it is unbanned."
+        )]
+        hours: Option<u32>,
+        #[structopt(
+            long = "reason",
+            help = "Why the peer is banned, shown in the logs of the ASB.",
+            default_value = "banned by operator"
+        )]
+        reason: String,
+    },
+    #[structopt(about = "Lifts the ban of a peer, including bans made automatically.")]
+    Unban {
+        #[structopt(long = "peer-id", help = "The peer id of the peer to unban.")]
+        peer_id: PeerId,
+    },
+    #[structopt(
+        about = "Rotates the libp2p identity, the ASB gets a new peer id on the next start. The previous identity keeps listening on the addresses in `network.listen`, change them to addresses for the new identity before the next start."
+    )]
+    RotateIdentity {
+        #[structopt(
+            long = "overlap-hours",
+            help = "For how many hours the previous identity stays registered at the rendezvous points and accepts new swaps. Afterwards it only finishes the swaps it set up.",
+            default_value = "72"
+        )]
+        overlap_hours: u32,
+    },
     #[structopt(about = "Contains sub-commands for recovering a swap manually.")]
     ManualRecovery(ManualRecovery),
 }
@@ -356,6 +601,7 @@ mod tests {
     const BITCOIN_MAINNET_ADDRESS: &str = "1KFHE7w8BhaENAswwryaoccDb6qcT6DbYY";
     const BITCOIN_TESTNET_ADDRESS: &str = "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a";
     const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
+    const PEER_ID: &str = "12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
 
     #[test]
     fn ensure_start_command_mapping_mainnet() {
@@ -365,16 +611,220 @@ mod tests {
         let raw_ars = vec![BINARY_NAME, "start"];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Start {
+                resume_only: false,
+                rpc_bind_address: None,
+                monitor_bind_address: None,
+                metrics_bind_address: None,
+                drain_timeout: Duration::from_secs(600),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_regtest_then_regtest_env_config_is_used() {
+        let args = parse_args(vec![BINARY_NAME, "--regtest", "start"]).unwrap();
+        assert_eq!(args.env_config, env::Regtest::get_config());
+        assert!(!args.testnet);
+
+        assert!(parse_args(vec![BINARY_NAME, "--regtest", "--testnet", "start"]).is_err());
+    }
+
+    #[test]
+    fn given_log_format_or_json_then_logs_are_json() {
+        let args = parse_args(vec![BINARY_NAME, "--log-format", "json", "start"]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Json);
+
+        let args = parse_args(vec![BINARY_NAME, "--json", "start"]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Json);
+
+        assert!(parse_args(vec![BINARY_NAME, "--log-format", "yaml", "start"]).is_err());
+    }
+
+    #[test]
+    fn ensure_start_command_with_rpc_bind_address_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "start", "--rpc-bind-address", "127.0.0.1:9944"];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                rpc_bind_address: Some("127.0.0.1:9944".parse().unwrap()),
+                monitor_bind_address: None,
+                metrics_bind_address: None,
+                drain_timeout: Duration::from_secs(600),
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_start_command_with_monitor_bind_address_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "start",
+            "--monitor-bind-address",
+            "127.0.0.1:9945",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Start {
+                resume_only: false,
+                rpc_bind_address: None,
+                monitor_bind_address: Some("127.0.0.1:9945".parse().unwrap()),
+                metrics_bind_address: None,
+                drain_timeout: Duration::from_secs(600),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_start_command_with_metrics_bind_address_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "start",
+            "--metrics-bind-address",
+            "127.0.0.1:9946",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Start {
+                resume_only: false,
+                rpc_bind_address: None,
+                monitor_bind_address: None,
+                metrics_bind_address: Some("127.0.0.1:9946".parse().unwrap()),
+                drain_timeout: Duration::from_secs(600),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_start_command_with_drain_timeout_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "start", "--drain-timeout", "60"];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Start {
+                resume_only: false,
+                rpc_bind_address: None,
+                monitor_bind_address: None,
+                metrics_bind_address: None,
+                drain_timeout: Duration::from_secs(60),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_ban_command_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "ban", "--peer-id", PEER_ID, "--hours", "24"];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Ban {
+                peer_id: PeerId::from_str(PEER_ID).unwrap(),
+                hours: Some(24),
+                reason: "banned by operator".to_string(),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_unban_command_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "unban", "--peer-id", PEER_ID];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Unban {
+                peer_id: PeerId::from_str(PEER_ID).unwrap(),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_rotate_identity_command_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "rotate-identity", "--overlap-hours", "24"];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::RotateIdentity { overlap_hours: 24 },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "rotate-identity"];
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(args.cmd, Command::RotateIdentity { overlap_hours: 72 });
+    }
+
     #[test]
     fn ensure_history_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -383,11 +833,99 @@ mod tests {
         let raw_ars = vec![BINARY_NAME, "history"];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::History,
+            cmd: Command::History {
+                period: Period::default(),
+                csv: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_history_command_with_period_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "history",
+            "--from",
+            "2021-01-01",
+            "--to",
+            "2021-12-31",
+            "--csv",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::History {
+                period: Period::new(
+                    Some(parse_date("2021-01-01").unwrap()),
+                    Some(parse_date("2021-12-31").unwrap()),
+                )
+                .unwrap(),
+                csv: true,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "history",
+            "--from",
+            "2021-12-31",
+            "--to",
+            "2021-01-01",
+        ];
+        assert!(parse_args(raw_ars).is_err());
+    }
+
+    #[test]
+    fn ensure_sweeps_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "sweeps"];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Sweeps,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_import_sled_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "import-sled", "--sled-dir", "/tmp/database"];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::ImportSled {
+                sled_dir: Some(PathBuf::from("/tmp/database")),
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -401,8 +939,9 @@ mod tests {
         let raw_ars = vec![BINARY_NAME, "balance"];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Balance,
@@ -423,8 +962,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::WithdrawBtc {
@@ -450,8 +990,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Cancel {
@@ -476,8 +1017,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Refund {
@@ -502,8 +1044,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Punish {
@@ -528,8 +1071,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::SafelyAbort {
@@ -548,11 +1092,18 @@ mod tests {
         let raw_ars = vec![BINARY_NAME, "--testnet", "start"];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                rpc_bind_address: None,
+                monitor_bind_address: None,
+                metrics_bind_address: None,
+                drain_timeout: Duration::from_secs(600),
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -566,11 +1117,15 @@ mod tests {
         let raw_ars = vec![BINARY_NAME, "--testnet", "history"];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
-            cmd: Command::History,
+            cmd: Command::History {
+                period: Period::default(),
+                csv: false,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -584,8 +1139,9 @@ mod tests {
         let raw_ars = vec![BINARY_NAME, "--testnet", "balance"];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Balance,
@@ -608,8 +1164,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::WithdrawBtc {
@@ -635,8 +1192,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Cancel {
@@ -662,8 +1220,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Refund {
@@ -689,8 +1248,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Punish {
@@ -716,8 +1276,9 @@ mod tests {
         ];
         let expected_args = Arguments {
             testnet: true,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: false,
+            passphrase: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::SafelyAbort {
@@ -736,11 +1297,40 @@ mod tests {
         let raw_ars = vec![BINARY_NAME, "--disable-timestamp", "start"];
         let expected_args = Arguments {
             testnet: false,
-            json: false,
+            log_format: LogFormat::Text,
             disable_timestamp: true,
+            passphrase: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                rpc_bind_address: None,
+                monitor_bind_address: None,
+                metrics_bind_address: None,
+                drain_timeout: Duration::from_secs(600),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_passphrase_file_mapping() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--passphrase-file", "/dev/fd/3", "history"];
+        let expected_args = Arguments {
+            testnet: false,
+            log_format: LogFormat::Text,
+            disable_timestamp: false,
+            passphrase: Some(PassphraseSource::File(PathBuf::from("/dev/fd/3"))),
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::History {
+                period: Period::default(),
+                csv: false,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
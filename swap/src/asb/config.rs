@@ -1,5 +1,17 @@
-use crate::env::{Mainnet, Testnet};
+use crate::asb::pricing::{FixedSpread, PricingStrategy, Tier, TieredPricing};
+use crate::asb::reputation::BanPolicy;
+use crate::asb::sweep;
+use crate::bitcoin::{BitcoindAuth, ConfirmationTargets};
+use crate::env::{self, Mainnet, Testnet};
 use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
+use crate::network::rendezvous::{AssetPair, RendezvousNamespace};
+use crate::network::swap_setup::alice::ConcurrencyLimits;
+use crate::network::swap_setup::{
+    ConfirmationSchedule, ConfirmationTier, TimelockRange, TimelockRanges, Timelocks,
+};
+use crate::notifications::BalanceThresholds;
+use crate::price_feed;
+use crate::protocol::alice::RemoteRedeemSigner;
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
 use anyhow::{bail, Context, Result};
 use config::ConfigError;
@@ -13,6 +25,7 @@ use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use time::Duration;
 use url::Url;
 
 pub trait GetDefaults {
@@ -93,6 +106,8 @@ pub struct Config {
     pub monero: Monero,
     pub tor: TorConf,
     pub maker: Maker,
+    #[serde(default)]
+    pub notifications: Option<Notifications>,
 }
 
 impl Config {
@@ -119,30 +134,248 @@ pub struct Data {
 pub struct Network {
     pub listen: Vec<Multiaddr>,
     #[serde(default)]
-    pub rendezvous_point: Option<Multiaddr>,
+    pub rendezvous_point: Vec<Multiaddr>,
     #[serde(default)]
     pub external_addresses: Vec<Multiaddr>,
+    /// The namespaces to register in at the rendezvous points, defaults to
+    /// the namespace of the network.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rendezvous_namespaces: Vec<RendezvousNamespace>,
+    /// Learn from peers which addresses they see us on, at the cost of telling
+    /// them the addresses we listen on.
+    #[serde(default)]
+    pub reachability_check: bool,
+    /// Circuit relay v1 nodes to listen through, for an ASB that takers
+    /// cannot dial directly. Each address must contain the peer ID of the
+    /// node.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relay_nodes: Vec<Multiaddr>,
+}
+
+impl Network {
+    pub fn rendezvous_namespaces(&self, testnet: bool) -> Result<Vec<RendezvousNamespace>> {
+        if self.rendezvous_namespaces.is_empty() {
+            let namespace = if testnet {
+                RendezvousNamespace::testnet()
+            } else {
+                RendezvousNamespace::mainnet()
+            };
+
+            return Ok(vec![namespace]);
+        }
+
+        if let Some(namespace) = self
+            .rendezvous_namespaces
+            .iter()
+            .find(|namespace| namespace.pair() != AssetPair::XMR_BTC)
+        {
+            bail!(
+                "Cannot register in namespace {}, the ASB only swaps {}",
+                namespace,
+                AssetPair::XMR_BTC
+            )
+        }
+
+        Ok(self.rendezvous_namespaces.clone())
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Bitcoin {
     pub electrum_rpc_url: Url,
+    /// Electrum servers to fail over to if `electrum_rpc_url` is unreachable
+    /// or lags behind.
+    #[serde(default)]
+    pub fallback_electrum_rpc_urls: Vec<Url>,
+    /// Use a bitcoind node instead of the Electrum servers.
+    #[serde(default)]
+    pub bitcoind: Option<Bitcoind>,
     pub target_block: usize,
+    /// Overrides `target_block` for the redeem transaction.
+    pub redeem_target_block: Option<usize>,
+    /// Overrides `target_block` for the punish transaction.
+    pub punish_target_block: Option<usize>,
     pub finality_confirmations: Option<u32>,
+    /// The cancel timelocks accepted if the taker proposes its own, defaults
+    /// to the cancel timelock of the network.
+    pub cancel_timelock_range: Option<TimelockRange>,
+    /// The punish timelocks accepted if the taker proposes its own, defaults
+    /// to the punish timelock of the network.
+    pub punish_timelock_range: Option<TimelockRange>,
+    /// Sweep the redeemed Bitcoin to a cold wallet.
+    #[serde(default)]
+    pub sweep: Option<Sweep>,
+    /// Sign the redeem transactions with an external signing service.
+    #[serde(default)]
+    pub redeem_signer: Option<RedeemSigner>,
     #[serde(with = "crate::bitcoin::network")]
     pub network: bitcoin::Network,
 }
 
+impl Bitcoin {
+    /// All configured Electrum servers in order of preference.
+    pub fn electrum_rpc_urls(&self) -> Vec<Url> {
+        std::iter::once(self.electrum_rpc_url.clone())
+            .chain(self.fallback_electrum_rpc_urls.iter().cloned())
+            .collect()
+    }
+
+    pub fn confirmation_targets(&self) -> ConfirmationTargets {
+        ConfirmationTargets {
+            redeem: self.redeem_target_block.unwrap_or(self.target_block),
+            punish: self.punish_target_block.unwrap_or(self.target_block),
+            ..ConfirmationTargets::uniform(self.target_block)
+        }
+    }
+
+    pub fn timelock_ranges(&self, env_config: env::Config) -> TimelockRanges {
+        let network = TimelockRanges::exactly(Timelocks::of_network(&env_config));
+
+        TimelockRanges {
+            cancel: self.cancel_timelock_range.unwrap_or(network.cancel),
+            punish: self.punish_timelock_range.unwrap_or(network.punish),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sweep {
+    /// The address the Bitcoin is swept to.
+    pub address: Option<bitcoin::Address>,
+    /// A descriptor of the cold wallet, every sweep goes to the next address
+    /// of it. Used instead of `address`.
+    pub descriptor: Option<String>,
+    /// Sweep once the balance reaches this amount.
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_btc::opt")]
+    pub threshold_btc: Option<bitcoin::Amount>,
+    /// Sweep if this many hours passed since the last sweep.
+    pub interval_hours: Option<u32>,
+    /// The amount left in the hot wallet, e.g. to buy XMR with.
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_btc::opt")]
+    pub keep_btc: Option<bitcoin::Amount>,
+}
+
+impl Sweep {
+    pub fn policy(&self) -> Result<sweep::Policy> {
+        let destination = match (&self.address, &self.descriptor) {
+            (Some(address), None) => sweep::Destination::Address(address.clone()),
+            (None, Some(descriptor)) => sweep::Destination::Descriptor(descriptor.clone()),
+            _ => bail!("Exactly one of `address` and `descriptor` is required to sweep"),
+        };
+
+        if self.threshold_btc.is_none() && self.interval_hours.is_none() {
+            bail!("At least one of `threshold_btc` and `interval_hours` is required to sweep")
+        }
+
+        Ok(sweep::Policy {
+            destination,
+            threshold: self.threshold_btc,
+            interval: self
+                .interval_hours
+                .map(|hours| Duration::hours(i64::from(hours))),
+            keep: self.keep_btc.unwrap_or(bitcoin::Amount::ZERO),
+        })
+    }
+}
+
+const DEFAULT_REDEEM_SIGNER_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedeemSigner {
+    /// The endpoint the signature requests are posted to.
+    pub url: Url,
+    /// How long to wait for a signature, defaults to 10 seconds.
+    pub timeout_secs: Option<u64>,
+    /// Sign with the local key of the swap if the signing service fails or
+    /// times out, instead of giving up on redeeming.
+    #[serde(default)]
+    pub fallback_to_local: bool,
+}
+
+impl RedeemSigner {
+    pub fn signer(&self) -> Result<RemoteRedeemSigner> {
+        RemoteRedeemSigner::new(
+            self.url.clone(),
+            std::time::Duration::from_secs(
+                self.timeout_secs
+                    .unwrap_or(DEFAULT_REDEEM_SIGNER_TIMEOUT_SECS),
+            ),
+            self.fallback_to_local,
+        )
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bitcoind {
+    pub rpc_url: Url,
+    /// Authenticate with the cookie file of bitcoind, unless `rpc_user` is
+    /// given.
+    pub cookie_file: Option<PathBuf>,
+    pub rpc_user: Option<String>,
+    pub rpc_password: Option<String>,
+    /// The name of the watch-only wallet the ASB creates in bitcoind.
+    pub wallet_name: String,
+    /// The height from which bitcoind rescans the blockchain for transactions
+    /// of the wallet when it is created, defaults to the genesis block.
+    pub rescan_from_height: Option<u32>,
+}
+
+impl Bitcoind {
+    pub fn auth(&self) -> Result<BitcoindAuth> {
+        match (&self.rpc_user, &self.rpc_password, &self.cookie_file) {
+            (Some(user), Some(password), _) => {
+                Ok(BitcoindAuth::UserPass(user.clone(), password.clone()))
+            }
+            (Some(_), None, _) => bail!("`rpc_password` is required if `rpc_user` is set"),
+            (None, _, Some(cookie_file)) => Ok(BitcoindAuth::CookieFile(cookie_file.clone())),
+            (None, _, None) => bail!("Either `cookie_file` or `rpc_user` and `rpc_password` are required to authenticate with bitcoind"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Monero {
     pub wallet_rpc_url: Url,
     pub finality_confirmations: Option<u64>,
+    /// Swaps of at least `from_xmr` wait for the confirmations of the tier
+    /// before the Monero lock of the taker counts as final. Smaller swaps
+    /// wait for `finality_confirmations`.
+    #[serde(default)]
+    pub confirmation_schedule: Vec<MoneroConfirmationTier>,
     #[serde(with = "crate::monero::network")]
     pub network: monero::Network,
 }
 
+impl Monero {
+    pub fn confirmation_schedule(&self, env_config: env::Config) -> Result<ConfirmationSchedule> {
+        let tiers = self
+            .confirmation_schedule
+            .iter()
+            .map(|tier| {
+                Ok(ConfirmationTier {
+                    from: crate::monero::Amount::from_monero(tier.from_xmr)
+                        .context("Invalid `from_xmr` of confirmation tier")?,
+                    confirmations: tier.confirmations,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        ConfirmationSchedule::new(tiers, env_config.monero_finality_confirmations)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MoneroConfirmationTier {
+    pub from_xmr: f64,
+    pub confirmations: u64,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct TorConf {
@@ -159,6 +392,219 @@ pub struct Maker {
     pub max_buy_btc: bitcoin::Amount,
     pub ask_spread: Decimal,
     pub price_ticker_ws_url: Url,
+    /// The price feeds to use in order of preference. If a price feed
+    /// disconnects or goes stale, the next one is used.
+    #[serde(default = "default_price_feeds")]
+    pub price_feeds: Vec<PriceFeed>,
+    /// The maximum number of swaps executed at the same time, further swap
+    /// requests are declined until one of the swaps is finished.
+    pub max_concurrent_swaps: Option<usize>,
+    /// The maximum number of swaps executed with the same peer at the same
+    /// time.
+    pub max_concurrent_swaps_per_peer: Option<usize>,
+    /// Refuse quotes and swaps once the latest price is older than this,
+    /// defaults to 300 seconds.
+    pub max_rate_age_secs: Option<u64>,
+    /// The number of latest prices kept for every price feed, defaults to 10.
+    pub rate_cache_size: Option<usize>,
+    /// For how long after a price feed disconnected the average of its cached
+    /// prices is used, defaults to 30 seconds.
+    pub max_rate_interpolation_secs: Option<u64>,
+    /// Ban peers that misbehave repeatedly.
+    #[serde(default)]
+    pub bans: Option<Bans>,
+    /// Also buy XMR from takers that sell XMR, paying with the BTC of the
+    /// Bitcoin wallet within the same limits.
+    #[serde(default)]
+    pub buy_xmr: bool,
+    /// Serve quotes and spot prices but decline to set up swaps, e.g. to
+    /// test the discovery and the pricing before funding the wallets.
+    #[serde(default)]
+    pub quotes_only: bool,
+    /// Widen the spread of a swap depending on the XMR inventory, the size of
+    /// the swap and the recent volume.
+    #[serde(default)]
+    pub pricing: Option<Pricing>,
+}
+
+impl Maker {
+    pub fn concurrency_limits(&self) -> ConcurrencyLimits {
+        ConcurrencyLimits {
+            max_swaps: self.max_concurrent_swaps,
+            max_swaps_per_peer: self.max_concurrent_swaps_per_peer,
+        }
+    }
+
+    pub fn rate_freshness(&self) -> price_feed::Freshness {
+        let defaults = price_feed::Freshness::default();
+
+        price_feed::Freshness {
+            max_age: self
+                .max_rate_age_secs
+                .map_or(defaults.max_age, std::time::Duration::from_secs),
+            cache_size: self.rate_cache_size.unwrap_or(defaults.cache_size),
+            max_interpolation: self
+                .max_rate_interpolation_secs
+                .map_or(defaults.max_interpolation, std::time::Duration::from_secs),
+        }
+    }
+
+    pub fn pricing_strategy(&self) -> Result<Box<dyn PricingStrategy>> {
+        match &self.pricing {
+            Some(pricing) => Ok(Box::new(pricing.strategy()?)),
+            None => Ok(Box::new(FixedSpread)),
+        }
+    }
+
+    pub fn ban_policy(&self) -> Option<BanPolicy> {
+        self.bans.as_ref().map(|bans| BanPolicy {
+            max_misbehaviours: bans.max_misbehaviours,
+            window: Duration::hours(i64::from(bans.window_hours)),
+            ban_duration: Duration::hours(i64::from(bans.ban_hours)),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pricing {
+    /// Widen the spread as the XMR inventory depletes.
+    #[serde(default)]
+    pub inventory: Vec<InventoryTier>,
+    /// Widen the spread of large swaps.
+    #[serde(default)]
+    pub size: Vec<AmountTier>,
+    /// Widen the spread once the volume of the swaps set up within the last 24
+    /// hours reaches an amount.
+    #[serde(default)]
+    pub volume: Vec<AmountTier>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InventoryTier {
+    /// Applies once the XMR that is not reserved for other swaps is below
+    /// this amount.
+    pub below_xmr: f64,
+    pub add_spread: Decimal,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmountTier {
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    pub from_btc: bitcoin::Amount,
+    pub add_spread: Decimal,
+}
+
+impl Pricing {
+    pub fn strategy(&self) -> Result<TieredPricing> {
+        let inventory: Vec<Tier<crate::monero::Amount>> = self
+            .inventory
+            .iter()
+            .map(|tier| {
+                Ok(Tier {
+                    threshold: crate::monero::Amount::from_monero(tier.below_xmr)
+                        .context("Invalid `below_xmr` of inventory tier")?,
+                    add_spread: valid_spread(tier.add_spread)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let amount_tiers = |tiers: &[AmountTier]| -> Result<Vec<Tier<bitcoin::Amount>>> {
+            tiers
+                .iter()
+                .map(|tier| {
+                    Ok(Tier {
+                        threshold: tier.from_btc,
+                        add_spread: valid_spread(tier.add_spread)?,
+                    })
+                })
+                .collect()
+        };
+
+        Ok(TieredPricing {
+            inventory,
+            size: amount_tiers(&self.size)?,
+            volume: amount_tiers(&self.volume)?,
+        })
+    }
+}
+
+fn valid_spread(spread: Decimal) -> Result<Decimal> {
+    if spread.is_sign_negative() || spread >= Decimal::ONE {
+        bail!(
+            "Invalid `add_spread` {}, only values in the interval [0..1) are allowed",
+            spread
+        )
+    }
+
+    Ok(spread)
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bans {
+    /// The number of misbehaviours within `window_hours` at which a peer is
+    /// banned, e.g. aborted swap setups or swaps that ended in punish.
+    pub max_misbehaviours: usize,
+    pub window_hours: u32,
+    /// How long a peer is banned for.
+    pub ban_hours: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Notifications {
+    /// The URL notifications about swaps that need attention are posted to.
+    pub webhook_url: Url,
+    /// Notify when the Bitcoin balance drops below this amount.
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_btc::opt")]
+    pub min_bitcoin_balance_btc: Option<bitcoin::Amount>,
+    /// Notify when the Monero balance drops below this amount.
+    pub min_monero_balance_xmr: Option<f64>,
+}
+
+impl Notifications {
+    pub fn balance_thresholds(&self) -> Result<BalanceThresholds> {
+        let monero = self
+            .min_monero_balance_xmr
+            .map(crate::monero::Amount::from_monero)
+            .transpose()
+            .context("Invalid `min_monero_balance_xmr`")?;
+
+        Ok(BalanceThresholds {
+            bitcoin: self.min_bitcoin_balance_btc,
+            monero,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PriceFeed {
+    /// The Kraken websocket API at `price_ticker_ws_url`.
+    Kraken,
+    /// The Binance websocket API.
+    Binance {
+        #[serde(default = "default_binance_ws_url")]
+        ws_url: Url,
+    },
+    /// A fixed price of 1 XMR in BTC.
+    Static {
+        #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+        ask_btc: bitcoin::Amount,
+    },
+    /// A file containing the price of 1 XMR in BTC, e.g. `0.0065`.
+    File { path: PathBuf },
+}
+
+fn default_price_feeds() -> Vec<PriceFeed> {
+    vec![PriceFeed::Kraken]
+}
+
+fn default_binance_ws_url() -> Url {
+    Url::parse("wss://stream.binance.com:9443/ws/xmrbtc@bookTicker")
+        .expect("static url to be valid")
 }
 
 impl Default for TorConf {
@@ -240,12 +686,12 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         .interact_text()?;
 
     let listen_addresses = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("Enter multiaddresses (comma separated) on which asb should list for peer-to-peer communications or hit return to use default")
+        .with_prompt("Enter multiaddresses (comma separated, IPv4 and IPv6, e.g. /ip6/::/tcp/9939) on which asb should list for peer-to-peer communications or hit return to use default")
         .default( format!("{},{}", defaults.listen_address_tcp, defaults.listen_address_ws))
         .interact_text()?;
     let listen_addresses = listen_addresses
         .split(',')
-        .map(|str| str.parse())
+        .map(|str| str.trim().parse())
         .collect::<Result<Vec<Multiaddr>, _>>()?;
 
     let electrum_rpc_url = Input::with_theme(&ColorfulTheme::default())
@@ -289,10 +735,28 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
     }
     let ask_spread = Decimal::from_f64(ask_spread).context("Unable to parse spread")?;
 
-    let rendezvous_point = Input::<Multiaddr>::with_theme(&ColorfulTheme::default())
-        .with_prompt("Do you want to advertise your ASB instance with a rendezvous node? Enter an empty string if not.")
-        .allow_empty(true)
-        .interact_text()?;
+    let mut number = 1;
+    let mut done = false;
+    let mut rendezvous_points = Vec::new();
+    println!("ASB can register with multiple rendezvous nodes for discoverability. This can also be edited in the config file later.");
+    while !done {
+        let prompt = format!(
+            "Enter the address for rendezvous node ({}). Or just hit Enter to continue.",
+            number
+        );
+        let rendezvous_addr = Input::<Multiaddr>::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .allow_empty(true)
+            .interact_text()?;
+        if rendezvous_addr.is_empty() {
+            done = true;
+        } else if rendezvous_points.contains(&rendezvous_addr) {
+            println!("That rendezvous address is already in the list.");
+        } else {
+            rendezvous_points.push(rendezvous_addr);
+            number += 1;
+        }
+    }
 
     println!();
 
@@ -300,22 +764,30 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         data: Data { dir: data_dir },
         network: Network {
             listen: listen_addresses,
-            rendezvous_point: if rendezvous_point.is_empty() {
-                None
-            } else {
-                Some(rendezvous_point)
-            },
+            rendezvous_point: rendezvous_points,
             external_addresses: vec![],
+            rendezvous_namespaces: vec![],
+            reachability_check: false,
+            relay_nodes: vec![],
         },
         bitcoin: Bitcoin {
             electrum_rpc_url,
+            fallback_electrum_rpc_urls: vec![],
+            bitcoind: None,
             target_block,
+            redeem_target_block: None,
+            punish_target_block: None,
             finality_confirmations: None,
+            cancel_timelock_range: None,
+            punish_timelock_range: None,
+            sweep: None,
+            redeem_signer: None,
             network: bitcoin_network,
         },
         monero: Monero {
             wallet_rpc_url: monero_wallet_rpc_url,
             finality_confirmations: None,
+            confirmation_schedule: vec![],
             network: monero_network,
         },
         tor: TorConf {
@@ -327,13 +799,25 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             max_buy_btc: max_buy,
             ask_spread,
             price_ticker_ws_url: defaults.price_ticker_ws_url,
+            price_feeds: default_price_feeds(),
+            max_concurrent_swaps: None,
+            max_concurrent_swaps_per_peer: None,
+            max_rate_age_secs: None,
+            rate_cache_size: None,
+            max_rate_interpolation_secs: None,
+            bans: None,
+            buy_xmr: false,
+            quotes_only: false,
+            pricing: None,
         },
+        notifications: None,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::env::GetConfig;
     use tempfile::tempdir;
 
     #[test]
@@ -349,19 +833,31 @@ mod tests {
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
+                fallback_electrum_rpc_urls: vec![],
+                bitcoind: None,
                 target_block: defaults.bitcoin_confirmation_target,
+                redeem_target_block: None,
+                punish_target_block: None,
                 finality_confirmations: None,
+                cancel_timelock_range: None,
+                punish_timelock_range: None,
+                sweep: None,
+                redeem_signer: None,
                 network: bitcoin::Network::Testnet,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
-                rendezvous_point: None,
+                rendezvous_point: vec![],
                 external_addresses: vec![],
+                rendezvous_namespaces: vec![],
+                reachability_check: false,
+                relay_nodes: vec![],
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
+                confirmation_schedule: vec![],
                 network: monero::Network::Stagenet,
             },
             tor: Default::default(),
@@ -370,7 +866,18 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                price_feeds: default_price_feeds(),
+                max_concurrent_swaps: None,
+                max_concurrent_swaps_per_peer: None,
+                max_rate_age_secs: None,
+                rate_cache_size: None,
+                max_rate_interpolation_secs: None,
+                bans: None,
+                buy_xmr: false,
+                quotes_only: false,
+                pricing: None,
             },
+            notifications: None,
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -392,19 +899,332 @@ mod tests {
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
+                fallback_electrum_rpc_urls: vec![],
+                bitcoind: None,
+                target_block: defaults.bitcoin_confirmation_target,
+                redeem_target_block: None,
+                punish_target_block: None,
+                finality_confirmations: None,
+                cancel_timelock_range: None,
+                punish_timelock_range: None,
+                sweep: None,
+                redeem_signer: None,
+                network: bitcoin::Network::Bitcoin,
+            },
+            network: Network {
+                listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                rendezvous_point: vec![],
+                external_addresses: vec![],
+                rendezvous_namespaces: vec![],
+                reachability_check: false,
+                relay_nodes: vec![],
+            },
+
+            monero: Monero {
+                wallet_rpc_url: defaults.monero_wallet_rpc_url,
+                finality_confirmations: None,
+                confirmation_schedule: vec![],
+                network: monero::Network::Mainnet,
+            },
+            tor: Default::default(),
+            maker: Maker {
+                min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
+                max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
+                ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
+                price_ticker_ws_url: defaults.price_ticker_ws_url,
+                price_feeds: default_price_feeds(),
+                max_concurrent_swaps: None,
+                max_concurrent_swaps_per_peer: None,
+                max_rate_age_secs: None,
+                rate_cache_size: None,
+                max_rate_interpolation_secs: None,
+                bans: None,
+                buy_xmr: false,
+                quotes_only: false,
+                pricing: None,
+            },
+            notifications: None,
+        };
+
+        initial_setup(config_path.clone(), expected.clone()).unwrap();
+        let actual = read_config(config_path).unwrap().unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn redeem_and_punish_target_blocks_override_target_block() {
+        let bitcoin = Bitcoin {
+            electrum_rpc_url: Url::from_str("tcp://localhost:50001").unwrap(),
+            fallback_electrum_rpc_urls: vec![],
+            bitcoind: None,
+            target_block: 3,
+            redeem_target_block: Some(1),
+            punish_target_block: Some(6),
+            finality_confirmations: None,
+            cancel_timelock_range: None,
+            punish_timelock_range: None,
+            sweep: None,
+            redeem_signer: None,
+            network: bitcoin::Network::Testnet,
+        };
+
+        let targets = bitcoin.confirmation_targets();
+
+        assert_eq!(targets, ConfirmationTargets {
+            lock: 3,
+            cancel: 3,
+            refund: 3,
+            redeem: 1,
+            punish: 6,
+        });
+    }
+
+    #[test]
+    fn timelock_ranges_default_to_the_timelocks_of_the_network() {
+        let bitcoin = Bitcoin {
+            electrum_rpc_url: Url::from_str("tcp://localhost:50001").unwrap(),
+            fallback_electrum_rpc_urls: vec![],
+            bitcoind: None,
+            target_block: 3,
+            redeem_target_block: None,
+            punish_target_block: None,
+            finality_confirmations: None,
+            cancel_timelock_range: Some(TimelockRange { min: 12, max: 144 }),
+            punish_timelock_range: None,
+            sweep: None,
+            redeem_signer: None,
+            network: bitcoin::Network::Testnet,
+        };
+
+        let ranges = bitcoin.timelock_ranges(Testnet::get_config());
+
+        assert_eq!(ranges, TimelockRanges {
+            cancel: TimelockRange { min: 12, max: 144 },
+            punish: TimelockRange::exactly(6u32),
+        });
+    }
+
+    #[test]
+    fn rendezvous_namespaces_default_to_the_namespace_of_the_network() {
+        let mut network = Network {
+            listen: vec![],
+            rendezvous_point: vec![],
+            external_addresses: vec![],
+            rendezvous_namespaces: vec![],
+            reachability_check: false,
+            relay_nodes: vec![],
+        };
+
+        assert_eq!(network.rendezvous_namespaces(true).unwrap(), vec![
+            RendezvousNamespace::testnet()
+        ]);
+
+        network.rendezvous_namespaces = vec![
+            RendezvousNamespace::testnet(),
+            "xmr-btc-swap-stagenet".parse().unwrap(),
+        ];
+        assert_eq!(
+            network.rendezvous_namespaces(true).unwrap(),
+            network.rendezvous_namespaces
+        );
+
+        network.rendezvous_namespaces = vec!["btc-xmr-swap-testnet".parse().unwrap()];
+        assert!(network.rendezvous_namespaces(true).is_err());
+    }
+
+    #[test]
+    fn sweep_requires_exactly_one_destination_and_a_trigger() {
+        let mut sweep = Sweep {
+            address: Some(
+                bitcoin::Address::from_str("tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a").unwrap(),
+            ),
+            descriptor: None,
+            threshold_btc: None,
+            interval_hours: Some(24),
+            keep_btc: None,
+        };
+        assert_eq!(sweep.policy().unwrap().keep, bitcoin::Amount::ZERO);
+
+        sweep.descriptor = Some("wpkh(tpubD6NzVbkrYhZ4Y2T5diX3fWTxxTY8ahSRd6LUm3zPLzxE7HvrBk6f5m3WPctwyXixoUpyZFKP1JgPZRw3Seqi6gok8MCZ73VZwJC8ZepLvJs/0/*)".to_string());
+        assert!(sweep.policy().is_err());
+
+        sweep.address = None;
+        sweep.interval_hours = None;
+        assert!(sweep.policy().is_err());
+    }
+
+    #[test]
+    fn pricing_tiers_must_add_a_spread_below_one() {
+        let mut pricing = Pricing {
+            inventory: vec![InventoryTier {
+                below_xmr: 10.0,
+                add_spread: Decimal::from_f64(0.01).unwrap(),
+            }],
+            size: vec![],
+            volume: vec![],
+        };
+        let strategy = pricing.strategy().unwrap();
+        assert_eq!(
+            strategy.inventory[0].threshold,
+            crate::monero::Amount::from_monero(10.0).unwrap()
+        );
+
+        pricing.size.push(AmountTier {
+            from_btc: bitcoin::Amount::from_btc(0.5).unwrap(),
+            add_spread: Decimal::from_f64(1.0).unwrap(),
+        });
+        assert!(pricing.strategy().is_err());
+    }
+
+    #[test]
+    fn monero_confirmations_default_to_the_finality_confirmations() {
+        let mut monero = Monero {
+            wallet_rpc_url: Url::from_str("http://localhost:18083/json_rpc").unwrap(),
+            finality_confirmations: None,
+            confirmation_schedule: vec![MoneroConfirmationTier {
+                from_xmr: 10.0,
+                confirmations: 20,
+            }],
+            network: monero::Network::Stagenet,
+        };
+        let schedule = monero.confirmation_schedule(Testnet::get_config()).unwrap();
+        assert_eq!(
+            schedule.confirmations(crate::monero::Amount::from_monero(1.0).unwrap()),
+            10
+        );
+        assert_eq!(
+            schedule.confirmations(crate::monero::Amount::from_monero(10.0).unwrap()),
+            20
+        );
+
+        monero.confirmation_schedule[0].confirmations = 0;
+        assert!(monero.confirmation_schedule(Testnet::get_config()).is_err());
+    }
+
+    #[test]
+    fn bitcoind_authenticates_with_user_and_password_over_cookie_file() {
+        let mut bitcoind = Bitcoind {
+            rpc_url: Url::from_str("http://127.0.0.1:8332").unwrap(),
+            cookie_file: Some(PathBuf::from("/home/bitcoin/.bitcoin/.cookie")),
+            rpc_user: None,
+            rpc_password: None,
+            wallet_name: "asb".to_string(),
+            rescan_from_height: None,
+        };
+
+        assert_eq!(
+            bitcoind.auth().unwrap(),
+            BitcoindAuth::CookieFile(PathBuf::from("/home/bitcoin/.bitcoin/.cookie"))
+        );
+
+        bitcoind.rpc_user = Some("asb".to_string());
+        assert!(bitcoind.auth().is_err());
+
+        bitcoind.rpc_password = Some("secret".to_string());
+        assert_eq!(
+            bitcoind.auth().unwrap(),
+            BitcoindAuth::UserPass("asb".to_string(), "secret".to_string())
+        );
+    }
+
+    #[test]
+    fn fallback_electrum_servers_follow_primary_server() {
+        let bitcoin = Bitcoin {
+            electrum_rpc_url: Url::from_str("tcp://localhost:50001").unwrap(),
+            fallback_electrum_rpc_urls: vec![
+                Url::from_str("tcp://localhost:50002").unwrap(),
+                Url::from_str("tcp://localhost:50003").unwrap(),
+            ],
+            bitcoind: None,
+            target_block: 3,
+            redeem_target_block: None,
+            punish_target_block: None,
+            finality_confirmations: None,
+            cancel_timelock_range: None,
+            punish_timelock_range: None,
+            sweep: None,
+            redeem_signer: None,
+            network: bitcoin::Network::Testnet,
+        };
+
+        assert_eq!(bitcoin.electrum_rpc_urls(), vec![
+            Url::from_str("tcp://localhost:50001").unwrap(),
+            Url::from_str("tcp://localhost:50002").unwrap(),
+            Url::from_str("tcp://localhost:50003").unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn rate_freshness_defaults_to_price_feed_defaults() {
+        let mut maker = Maker {
+            min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
+            max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
+            ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
+            price_ticker_ws_url: Url::from_str("wss://ws.kraken.com").unwrap(),
+            price_feeds: default_price_feeds(),
+            max_concurrent_swaps: None,
+            max_concurrent_swaps_per_peer: None,
+            max_rate_age_secs: None,
+            rate_cache_size: None,
+            max_rate_interpolation_secs: None,
+            bans: None,
+            buy_xmr: false,
+            quotes_only: false,
+            pricing: None,
+        };
+
+        assert_eq!(maker.rate_freshness(), price_feed::Freshness::default());
+
+        maker.max_rate_age_secs = Some(60);
+        maker.rate_cache_size = Some(5);
+        maker.max_rate_interpolation_secs = Some(10);
+
+        assert_eq!(maker.rate_freshness(), price_feed::Freshness {
+            max_age: std::time::Duration::from_secs(60),
+            cache_size: 5,
+            max_interpolation: std::time::Duration::from_secs(10),
+        });
+    }
+
+    #[test]
+    fn config_with_multiple_price_feeds_roundtrip() {
+        let temp_dir = tempdir().unwrap().path().to_path_buf();
+        let config_path = Path::join(&temp_dir, "config.toml");
+
+        let defaults = Mainnet::getConfigFileDefaults().unwrap();
+
+        let expected = Config {
+            data: Data {
+                dir: Default::default(),
+            },
+            bitcoin: Bitcoin {
+                electrum_rpc_url: defaults.electrum_rpc_url,
+                fallback_electrum_rpc_urls: vec![],
+                bitcoind: None,
                 target_block: defaults.bitcoin_confirmation_target,
+                redeem_target_block: Some(1),
+                punish_target_block: None,
                 finality_confirmations: None,
+                cancel_timelock_range: None,
+                punish_timelock_range: None,
+                sweep: None,
+                redeem_signer: None,
                 network: bitcoin::Network::Bitcoin,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
-                rendezvous_point: None,
+                rendezvous_point: vec![],
                 external_addresses: vec![],
+                rendezvous_namespaces: vec![],
+                reachability_check: false,
+                relay_nodes: vec![],
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
+                confirmation_schedule: vec![],
                 network: monero::Network::Mainnet,
             },
             tor: Default::default(),
@@ -413,7 +1233,47 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                price_feeds: vec![
+                    PriceFeed::Binance {
+                        ws_url: default_binance_ws_url(),
+                    },
+                    PriceFeed::Kraken,
+                    PriceFeed::File {
+                        path: temp_dir.join("price"),
+                    },
+                    PriceFeed::Static {
+                        ask_btc: bitcoin::Amount::from_btc(0.0065).unwrap(),
+                    },
+                ],
+                max_concurrent_swaps: Some(5),
+                max_concurrent_swaps_per_peer: Some(1),
+                max_rate_age_secs: Some(60),
+                rate_cache_size: Some(5),
+                max_rate_interpolation_secs: Some(10),
+                bans: Some(Bans {
+                    max_misbehaviours: 3,
+                    window_hours: 24,
+                    ban_hours: 48,
+                }),
+                buy_xmr: true,
+                quotes_only: true,
+                pricing: Some(Pricing {
+                    inventory: vec![InventoryTier {
+                        below_xmr: 10.0,
+                        add_spread: Decimal::from_f64(0.01).unwrap(),
+                    }],
+                    size: vec![AmountTier {
+                        from_btc: bitcoin::Amount::from_btc(0.5).unwrap(),
+                        add_spread: Decimal::from_f64(0.005).unwrap(),
+                    }],
+                    volume: vec![],
+                }),
             },
+            notifications: Some(Notifications {
+                webhook_url: Url::from_str("http://127.0.0.1:8080/notify").unwrap(),
+                min_bitcoin_balance_btc: Some(bitcoin::Amount::from_btc(0.1).unwrap()),
+                min_monero_balance_xmr: Some(10.0),
+            }),
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -1,25 +1,54 @@
+use crate::asb::metrics::Metrics;
+use crate::asb::outbox::{OutboundMessage, Outbox};
+use crate::asb::pricing::{Pricing, PricingInput, PricingStrategy, RecentVolume};
+use crate::asb::reachability::{self, AddressStatus, Reachability};
+use crate::asb::reputation::{Ban, BanPolicy, Misbehaviour, Reputation};
+use crate::asb::reservations::{Reservation, Reservations};
 use crate::asb::{Behaviour, OutEvent, Rate};
-use crate::network::quote::BidQuote;
-use crate::network::swap_setup::alice::WalletSnapshot;
-use crate::network::transfer_proof;
-use crate::protocol::alice::{AliceState, State3, Swap};
-use crate::protocol::{Database, State};
-use crate::{bitcoin, env, kraken, monero};
-use anyhow::{Context, Result};
+use crate::libp2p_ext::MultiAddrExt;
+use crate::network::connection_manager::UNREACHABLE_AFTER;
+use crate::network::quote::{BidQuote, FeeAssumptions, QuoteDetails};
+use crate::network::rendezvous::AssetPair;
+use crate::network::swap_setup::alice::{OngoingSwaps, WalletSnapshot};
+use crate::network::swap_setup::sell_xmr;
+use crate::network::{abort, encrypted_signature, transfer_proof};
+use crate::protocol::alice::{AliceState, LocalRedeemSigner, RedeemSigner, State3, Swap};
+use crate::protocol::bob::{BobState, State2};
+use crate::protocol::{bob, Database, State};
+use crate::{bitcoin, cli, env, monero, price_feed};
+use anyhow::{bail, Context, Result};
 use futures::future;
 use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
+use libp2p::multiaddr::Protocol;
 use libp2p::request_response::{RequestId, ResponseChannel};
-use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::swarm::{AddressScore, SwarmEvent};
+use libp2p::{Multiaddr, PeerId, Swarm};
 use rust_decimal::Decimal;
+use serde::Serialize;
 use std::collections::HashMap;
-use std::convert::{Infallible, TryInto};
+use std::convert::Infallible;
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use time::OffsetDateTime;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+/// How often bans are reloaded from the database, to pick up bans made with
+/// `asb ban` and `asb unban` and to lift expired bans.
+const BAN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the connections to the peers of finished swaps are released.
+const KEPT_CONNECTIONS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long the price of a quote is guaranteed for.
+const QUOTE_VALIDITY: Duration = Duration::from_secs(5 * 60);
+
+/// How long a swap waits for the peer to acknowledge that the swap was
+/// aborted. Aborting is a courtesy to the peer, so the swap gives up quickly.
+const ABORT_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// A future that resolves to a tuple of `PeerId`, `transfer_proof::Request` and
 /// `Responder`.
 ///
@@ -30,6 +59,15 @@ use uuid::Uuid;
 type OutgoingTransferProof =
     BoxFuture<'static, Result<(PeerId, transfer_proof::Request, bmrng::Responder<()>)>>;
 
+/// Like [`OutgoingTransferProof`], for the encrypted signatures we send in
+/// swaps in which we buy XMR.
+type OutgoingEncryptedSignature =
+    BoxFuture<'static, Result<(PeerId, encrypted_signature::Request, bmrng::Responder<()>)>>;
+
+/// Like [`OutgoingTransferProof`], for telling the peer that a swap was aborted
+/// before the Bitcoin was locked.
+type OutgoingAbort = BoxFuture<'static, Result<(PeerId, abort::Request, bmrng::Responder<()>)>>;
+
 #[allow(missing_debug_implementations)]
 pub struct EventLoop<LR>
 where
@@ -40,9 +78,20 @@ where
     bitcoin_wallet: Arc<bitcoin::Wallet>,
     monero_wallet: Arc<monero::Wallet>,
     db: Arc<dyn Database + Send + Sync>,
+    /// Signs the redeem transactions of the swaps in which we sell XMR.
+    redeem_signer: Arc<dyn RedeemSigner>,
     latest_rate: LR,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    pricing_strategy: Box<dyn PricingStrategy>,
+
+    /// The generation of the libp2p identity of the swarm, only the swaps set
+    /// up with it are resumed.
+    identity_generation: u32,
+
+    /// The BTC of the recently set up swaps, some pricing strategies widen the
+    /// spread with it.
+    recent_volume: RecentVolume,
 
     swap_sender: mpsc::Sender<Swap>,
 
@@ -50,15 +99,68 @@ where
     recv_encrypted_signature: HashMap<Uuid, bmrng::RequestSender<bitcoin::EncryptedSignature, ()>>,
     inflight_encrypted_signatures: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
 
+    /// Tracks the swaps that are currently executed. A swap is finished once
+    /// the [`EventLoopHandle`] that was created for it is dropped.
+    running_swaps: HashMap<Uuid, RunningSwap>,
+
+    /// The funds of the swaps that did not lock them yet.
+    reservations: Reservations,
+
     send_transfer_proof: FuturesUnordered<OutgoingTransferProof>,
 
-    /// Tracks [`transfer_proof::Request`]s which could not yet be sent because
-    /// we are currently disconnected from the peer.
-    buffered_transfer_proofs: HashMap<PeerId, Vec<(transfer_proof::Request, bmrng::Responder<()>)>>,
+    /// Tracks [`transfer_proof::Request`]s which were not acknowledged yet,
+    /// they are sent again whenever the peer reconnects.
+    transfer_proofs: Outbox<transfer_proof::Request>,
+
+    /// Stores incoming [`transfer_proof::Request`]s per swap in which we buy
+    /// XMR.
+    recv_transfer_proof: HashMap<Uuid, bmrng::RequestSender<monero::TransferProof, ()>>,
+    inflight_received_transfer_proofs: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
+
+    send_encrypted_signature: FuturesUnordered<OutgoingEncryptedSignature>,
+
+    /// Tracks [`encrypted_signature::Request`]s which were not acknowledged
+    /// yet, like [`EventLoop::transfer_proofs`].
+    encrypted_signatures: Outbox<encrypted_signature::Request>,
+
+    /// Stores the senders that relay an abort by the peer to the swap.
+    recv_abort: HashMap<Uuid, bmrng::RequestSender<(), ()>>,
+    inflight_received_aborts: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
 
-    /// Tracks [`transfer_proof::Request`]s which are currently inflight and
-    /// awaiting an acknowledgement.
-    inflight_transfer_proofs: HashMap<RequestId, bmrng::Responder<()>>,
+    send_abort: FuturesUnordered<OutgoingAbort>,
+
+    /// Tracks [`abort::Request`]s which are currently inflight and awaiting an
+    /// acknowledgement.
+    inflight_abort_requests: HashMap<RequestId, bmrng::Responder<()>>,
+
+    /// Whether incoming quote requests are currently answered with a quote
+    /// that does not allow any swaps, and swap requests are declined.
+    quotes_paused: bool,
+
+    /// Whether swap requests are priced and then declined, the quotes are
+    /// served as usual.
+    quotes_only: bool,
+
+    /// Whether the ASB is shutting down, no new swaps are accepted then.
+    shutting_down: bool,
+
+    /// Whether the listen addresses that are reachable from the internet are
+    /// advertised as external addresses.
+    advertise_listen_addresses: bool,
+    /// The addresses peers see us on, as told by the identify protocol.
+    reachability: Reachability,
+
+    /// Control requests sent to the event loop at runtime, for example from
+    /// the RPC server. We hold on to a sender ourselves so the channel is never
+    /// closed, even if nobody ever asks for an [`EventLoopController`].
+    control_sender: bmrng::RequestSender<Control, Result<ControlResponse>>,
+    control_receiver: bmrng::RequestReceiver<Control, Result<ControlResponse>>,
+
+    metrics: Arc<Metrics>,
+
+    reputation: Reputation,
+    /// The peers that are currently banned from connecting to us.
+    banned_peers: HashMap<PeerId, Ban>,
 }
 
 impl<LR> EventLoop<LR>
@@ -75,8 +177,13 @@ where
         latest_rate: LR,
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        pricing_strategy: Box<dyn PricingStrategy>,
+        ban_policy: Option<BanPolicy>,
+        identity_generation: u32,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
+        let (control_sender, control_receiver) = bmrng::channel(10);
+        let reputation = Reputation::new(db.clone(), ban_policy);
 
         let event_loop = EventLoop {
             swarm,
@@ -84,31 +191,99 @@ where
             bitcoin_wallet,
             monero_wallet,
             db,
+            redeem_signer: Arc::new(LocalRedeemSigner),
             latest_rate,
             swap_sender: swap_channel.sender,
             min_buy,
             max_buy,
+            pricing_strategy,
+            identity_generation,
+            recent_volume: Default::default(),
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
+            running_swaps: Default::default(),
+            reservations: Default::default(),
             send_transfer_proof: Default::default(),
-            buffered_transfer_proofs: Default::default(),
-            inflight_transfer_proofs: Default::default(),
+            transfer_proofs: Default::default(),
+            recv_transfer_proof: Default::default(),
+            inflight_received_transfer_proofs: Default::default(),
+            send_encrypted_signature: Default::default(),
+            encrypted_signatures: Default::default(),
+            recv_abort: Default::default(),
+            inflight_received_aborts: Default::default(),
+            send_abort: Default::default(),
+            inflight_abort_requests: Default::default(),
+            quotes_paused: false,
+            quotes_only: false,
+            shutting_down: false,
+            advertise_listen_addresses: false,
+            reachability: Default::default(),
+            control_sender,
+            control_receiver,
+            metrics: Default::default(),
+            reputation,
+            banned_peers: Default::default(),
         };
         Ok((event_loop, swap_channel.receiver))
     }
 
+    /// Signs the redeem transactions with the given signer instead of the
+    /// local keys of the swaps.
+    pub fn with_redeem_signer(mut self, redeem_signer: Arc<dyn RedeemSigner>) -> Self {
+        self.redeem_signer = redeem_signer;
+        self
+    }
+
+    /// Declines all new swaps once they are priced, see
+    /// [`Control::EnableQuotesOnly`].
+    pub fn with_quotes_only(mut self, quotes_only: bool) -> Self {
+        self.quotes_only = quotes_only;
+        self
+    }
+
+    /// Advertises the listen addresses that are reachable from the internet,
+    /// for when no external addresses are configured.
+    pub fn with_advertised_listen_addresses(mut self, advertise: bool) -> Self {
+        self.advertise_listen_addresses = advertise;
+        self
+    }
+
     pub fn peer_id(&self) -> PeerId {
         *Swarm::local_peer_id(&self.swarm)
     }
 
+    /// Returns a controller that allows to adjust the event loop at runtime.
+    pub fn controller(&self) -> EventLoopController {
+        EventLoopController {
+            sender: self.control_sender.clone(),
+        }
+    }
+
+    /// Returns the metrics the event loop collects.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Returns a handle to record the misbehaviour of peers.
+    pub fn reputation(&self) -> Reputation {
+        self.reputation.clone()
+    }
+
     pub async fn run(mut self) {
         // ensure that these streams are NEVER empty, otherwise it will
         // terminate forever.
         self.send_transfer_proof.push(future::pending().boxed());
         self.inflight_encrypted_signatures
             .push(future::pending().boxed());
+        self.send_encrypted_signature
+            .push(future::pending().boxed());
+        self.inflight_received_transfer_proofs
+            .push(future::pending().boxed());
+        self.send_abort.push(future::pending().boxed());
+        self.inflight_received_aborts
+            .push(future::pending().boxed());
 
-        let swaps = match self.db.all().await {
+        let swaps = match self.swaps_of_identity().await {
             Ok(swaps) => swaps,
             Err(e) => {
                 tracing::error!("Failed to load swaps from database: {}", e);
@@ -116,6 +291,8 @@ where
             }
         };
 
+        self.load_outbound_messages(&swaps).await;
+
         let unfinished_swaps = swaps
             .into_iter()
             .filter(|(_swap_id, state)| !state.swap_finished())
@@ -130,7 +307,32 @@ where
                 }
             };
 
-            let handle = self.new_handle(peer_id, swap_id);
+            let state = match state {
+                State::Alice(state) => state,
+                State::Bob(state) => {
+                    tracing::info!(%swap_id, "Resuming swap in which we buy XMR");
+                    let reservation = match (&state, state.btc_amount()) {
+                        (BobState::SwapSetupCompleted(_), Some(btc)) => {
+                            Some(self.reservations.reserve_btc(btc))
+                        }
+                        _ => None,
+                    };
+                    self.start_bob_swap(peer_id, swap_id, state, reservation)
+                        .await;
+                    continue;
+                }
+            };
+
+            let reservation = match (&state, state.xmr_amount()) {
+                (
+                    AliceState::Started { .. }
+                    | AliceState::BtcLockTransactionSeen { .. }
+                    | AliceState::BtcLocked { .. },
+                    Some(xmr),
+                ) => Some(self.reservations.reserve_xmr(xmr + monero::MONERO_FEE)),
+                _ => None,
+            };
+            let handle = self.new_handle(peer_id, swap_id, reservation);
 
             let swap = Swap {
                 event_loop_handle: handle,
@@ -138,8 +340,9 @@ where
                 monero_wallet: self.monero_wallet.clone(),
                 env_config: self.env_config,
                 db: self.db.clone(),
-                state: state.try_into().expect("Alice state loaded from db"),
+                state,
                 swap_id,
+                redeem_signer: self.redeem_signer.clone(),
             };
 
             match self.swap_sender.send(swap).await {
@@ -150,22 +353,34 @@ where
             }
         }
 
+        let mut refresh_bans = tokio::time::interval(BAN_REFRESH_INTERVAL);
+        let mut refresh_kept_connections = tokio::time::interval(KEPT_CONNECTIONS_REFRESH_INTERVAL);
+
         loop {
             tokio::select! {
                 swarm_event = self.swarm.select_next_some() => {
                     match swarm_event {
-                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { mut send_wallet_snapshot }) => {
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { peer_id, mut send_wallet_snapshot }) => {
+                            if self.shutting_down {
+                                tracing::info!(%peer_id, "Swap request will be ignored because we are shutting down");
+                                continue;
+                            }
 
-                            let (btc, responder) = match send_wallet_snapshot.recv().await {
-                                Ok((btc, responder)) => (btc, responder),
+                            let ((btc, rate), responder) = match send_wallet_snapshot.recv().await {
+                                Ok((amounts, responder)) => (amounts, responder),
                                 Err(error) => {
-                                    tracing::error!("Swap request will be ignored because of a failure when requesting information for the wallet snapshot: {:#}", error);
+                                    tracing::debug!("Swap request will be ignored because it was declined before requesting a wallet snapshot: {:#}", error);
                                     continue;
                                 }
                             };
 
-                            let wallet_snapshot = match WalletSnapshot::capture(&self.bitcoin_wallet, &self.monero_wallet, btc).await {
-                                Ok(wallet_snapshot) => wallet_snapshot,
+                            // The setup counts towards the concurrency limits right away, a burst of
+                            // swap requests must not pass the limits before any swap was started.
+                            let ongoing_swaps = self.ongoing_swaps(peer_id);
+                            let slot = self.reservations.reserve_slot(peer_id);
+                            let recent_volume = self.recent_volume.total();
+                            let wallet_snapshot = match WalletSnapshot::capture(&self.bitcoin_wallet, &self.monero_wallet, &mut self.reservations, btc, rate, self.pricing_strategy.as_ref(), recent_volume, ongoing_swaps).await {
+                                Ok(wallet_snapshot) => wallet_snapshot.with_quotes_paused(self.quotes_paused).with_quotes_only(self.quotes_only).with_slot(slot),
                                 Err(error) => {
                                     tracing::error!("Swap request will be ignored because we were unable to create wallet snapshot for swap: {:#}", error);
                                     continue;
@@ -175,29 +390,79 @@ where
                             // Ignore result, we should never hit this because the receiver will alive as long as the connection is.
                             let _ = responder.respond(wallet_snapshot);
                         }
-                        SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted{peer_id, swap_id, state3}) => {
-                            let _ = self.handle_execution_setup_done(peer_id, swap_id, state3).await;
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted{peer_id, swap_id, state3, reservation, slot, pricing}) => {
+                            let Pricing { input, spread } = pricing;
+                            tracing::info!(%swap_id, %peer_id, %spread, btc = %input.btc, inventory = %input.inventory, recent_volume = %input.recent_volume, "Priced swap");
+                            self.recent_volume.record(input.btc);
+
+                            let _ = self.handle_execution_setup_done(peer_id, swap_id, state3, reservation).await;
+                            // the running swap counts towards the concurrency limits from here on
+                            drop(slot);
+                        }
+                        SwarmEvent::Behaviour(OutEvent::SellXmrSetupInitiated { peer_id, mut send_wallet_snapshot }) => {
+                            if self.shutting_down {
+                                tracing::info!(%peer_id, "Sell XMR request will be ignored because we are shutting down");
+                                continue;
+                            }
+                            let (btc, responder) = match send_wallet_snapshot.recv().await {
+                                Ok((btc, responder)) => (btc, responder),
+                                Err(error) => {
+                                    tracing::debug!("Sell XMR request will be ignored because it was declined before requesting a wallet snapshot: {:#}", error);
+                                    continue;
+                                }
+                            };
+
+                            let ongoing_swaps = self.ongoing_swaps(peer_id);
+                            let slot = self.reservations.reserve_slot(peer_id);
+                            let wallet_snapshot = match sell_xmr::bob::WalletSnapshot::capture(&self.bitcoin_wallet, &mut self.reservations, btc, ongoing_swaps).await {
+                                Ok(wallet_snapshot) => wallet_snapshot.with_quotes_paused(self.quotes_paused).with_quotes_only(self.quotes_only).with_slot(slot),
+                                Err(error) => {
+                                    tracing::error!("Sell XMR request will be ignored because we were unable to create wallet snapshot for swap: {:#}", error);
+                                    continue;
+                                }
+                            };
+
+                            let _ = responder.respond(wallet_snapshot);
+                        }
+                        SwarmEvent::Behaviour(OutEvent::SellXmrSetupCompleted{peer_id, swap_id, state2, reservation, slot}) => {
+                            self.handle_sell_xmr_setup_done(peer_id, swap_id, state2, reservation).await;
+                            drop(slot);
                         }
                         SwarmEvent::Behaviour(OutEvent::SwapDeclined { peer, error }) => {
-                            tracing::warn!(%peer, "Ignoring spot price request: {}", error);
+                            tracing::warn!(peer_id = %peer, "Ignoring spot price request: {}", error);
+                            self.metrics.swap_setup_failed(&error.to_error_response());
                         }
                         SwarmEvent::Behaviour(OutEvent::QuoteRequested { channel, peer }) => {
-                            let quote = match self.make_quote(self.min_buy, self.max_buy).await {
+                            if self.banned_peers.contains_key(&peer) {
+                                tracing::debug!(peer_id = %peer, "Ignoring quote request from banned peer");
+                                continue;
+                            }
+
+                            let max_buy = if self.quotes_paused || self.shutting_down {
+                                tracing::debug!(peer_id = %peer, "Quotes are paused, responding with zero maximum quantity");
+                                bitcoin::Amount::ZERO
+                            } else {
+                                self.max_buy
+                            };
+
+                            let quote = match self.make_quote(self.min_buy, max_buy).await {
                                 Ok(quote) => quote,
                                 Err(error) => {
-                                    tracing::warn!(%peer, "Failed to make quote: {:#}", error);
+                                    tracing::warn!(peer_id = %peer, "Failed to make quote: {:#}", error);
                                     continue;
                                 }
                             };
 
                             if self.swarm.behaviour_mut().quote.send_response(channel, quote).is_err() {
-                                tracing::debug!(%peer, "Failed to respond with quote");
+                                tracing::debug!(peer_id = %peer, "Failed to respond with quote");
+                            } else {
+                                self.metrics.quote_served();
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::TransferProofAcknowledged { peer, id }) => {
-                            tracing::debug!(%peer, "Bob acknowledged transfer proof");
-                            if let Some(responder) = self.inflight_transfer_proofs.remove(&id) {
-                                let _ = responder.respond(());
+                            tracing::debug!(peer_id = %peer, "Bob acknowledged transfer proof");
+                            if let Some(swap_id) = self.transfer_proofs.acknowledged(&id) {
+                                self.remove_outbound_message(swap_id).await;
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::EncryptedSignatureReceived{ msg, channel, peer }) => {
@@ -210,7 +475,7 @@ where
                                 Err(_) => {
                                     tracing::warn!(
                                         unknown_swap_id = %swap_id,
-                                        from = %peer,
+                                        peer_id = %peer,
                                         "Ignoring encrypted signature for unknown swap");
                                     continue;
                                 }
@@ -219,10 +484,11 @@ where
                             if swap_peer != peer {
                                 tracing::warn!(
                                     %swap_id,
-                                    received_from = %peer,
-                                    expected_from = %swap_peer,
+                                    received_peer_id = %peer,
+                                    expected_peer_id = %swap_peer,
                                     "Ignoring malicious encrypted signature which was not expected from this peer",
                                     );
+                                self.record_misbehaviour(peer, Misbehaviour::ProtocolViolation).await;
                                 continue;
                             }
 
@@ -230,7 +496,7 @@ where
                                 Some(sender) => sender,
                                 None => {
                                     // TODO: Don't just drop encsig if we currently don't have a running swap for it, save in db
-                                    tracing::warn!(%swap_id, "No sender for encrypted signature, maybe already handled?");
+                                    tracing::warn!(peer_id = %peer, %swap_id, "No sender for encrypted signature, maybe already handled?");
                                     continue;
                                 }
                             };
@@ -238,7 +504,7 @@ where
                             let mut responder = match sender.send(msg.tx_redeem_encsig).await {
                                 Ok(responder) => responder,
                                 Err(_) => {
-                                    tracing::warn!(%swap_id, "Failed to relay encrypted signature to swap");
+                                    tracing::warn!(peer_id = %peer, %swap_id, "Failed to relay encrypted signature to swap");
                                     continue;
                                 }
                             };
@@ -249,40 +515,218 @@ where
                                 channel
                             }.boxed());
                         }
-                        SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::Registered { .. })) => {
+                        SwarmEvent::Behaviour(OutEvent::TransferProofReceived { msg, channel, peer }) => {
+                            let swap_id = msg.swap_id;
+
+                            // Ensure that an incoming transfer proof is sent by the peer-id associated with the swap
+                            let swap_peer = match self.db.get_peer_id(swap_id).await {
+                                Ok(swap_peer) => swap_peer,
+                                Err(_) => {
+                                    tracing::warn!(
+                                        unknown_swap_id = %swap_id,
+                                        peer_id = %peer,
+                                        "Ignoring transfer proof for unknown swap");
+                                    continue;
+                                }
+                            };
+
+                            if swap_peer != peer {
+                                tracing::warn!(
+                                    %swap_id,
+                                    received_peer_id = %peer,
+                                    expected_peer_id = %swap_peer,
+                                    "Ignoring malicious transfer proof which was not expected from this peer",
+                                    );
+                                self.record_misbehaviour(peer, Misbehaviour::ProtocolViolation).await;
+                                continue;
+                            }
+
+                            let sender = match self.recv_transfer_proof.remove(&swap_id) {
+                                Some(sender) => sender,
+                                None => {
+                                    tracing::warn!(peer_id = %peer, %swap_id, "No sender for transfer proof, maybe already handled?");
+                                    continue;
+                                }
+                            };
+
+                            let mut responder = match sender.send(msg.tx_lock_proof).await {
+                                Ok(responder) => responder,
+                                Err(_) => {
+                                    tracing::warn!(peer_id = %peer, %swap_id, "Failed to relay transfer proof to swap");
+                                    continue;
+                                }
+                            };
+
+                            self.inflight_received_transfer_proofs.push(async move {
+                                let _ = responder.recv().await;
+
+                                channel
+                            }.boxed());
+                        }
+                        SwarmEvent::Behaviour(OutEvent::EncryptedSignatureAcknowledged { id }) => {
+                            if let Some(swap_id) = self.encrypted_signatures.acknowledged(&id) {
+                                self.remove_outbound_message(swap_id).await;
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::AbortReceived { msg, channel, peer }) => {
+                            let swap_id = msg.swap_id;
+
+                            // Ensure that an incoming abort is sent by the peer-id associated with the swap
+                            let swap_peer = match self.db.get_peer_id(swap_id).await {
+                                Ok(swap_peer) => swap_peer,
+                                Err(_) => {
+                                    tracing::warn!(
+                                        unknown_swap_id = %swap_id,
+                                        peer_id = %peer,
+                                        "Ignoring abort for unknown swap");
+                                    continue;
+                                }
+                            };
+
+                            if swap_peer != peer {
+                                tracing::warn!(
+                                    %swap_id,
+                                    received_peer_id = %peer,
+                                    expected_peer_id = %swap_peer,
+                                    "Ignoring malicious abort which was not expected from this peer",
+                                    );
+                                self.record_misbehaviour(peer, Misbehaviour::ProtocolViolation).await;
+                                continue;
+                            }
+
+                            let sender = match self.recv_abort.remove(&swap_id) {
+                                Some(sender) => sender,
+                                None => {
+                                    tracing::warn!(peer_id = %peer, %swap_id, "No sender for abort, maybe already handled?");
+                                    continue;
+                                }
+                            };
+
+                            // The swap only listens for an abort as long as the Bitcoin is not locked,
+                            // afterwards relaying it fails.
+                            let mut responder = match sender.send(()).await {
+                                Ok(responder) => responder,
+                                Err(_) => {
+                                    tracing::warn!(peer_id = %peer, %swap_id, "Ignoring abort for swap that is past the point where it can be aborted");
+                                    continue;
+                                }
+                            };
+
+                            self.inflight_received_aborts.push(async move {
+                                let _ = responder.recv().await;
+
+                                channel
+                            }.boxed());
+                        }
+                        SwarmEvent::Behaviour(OutEvent::AbortAcknowledged { id }) => {
+                            if let Some(responder) = self.inflight_abort_requests.remove(&id) {
+                                let _ = responder.respond(());
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::Registered { rendezvous_node, ttl, .. })) => {
                             tracing::info!("Successfully registered with rendezvous node");
+                            self.metrics.rendezvous_registered(rendezvous_node, ttl);
                         }
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::RegisterFailed(error))) => {
                             tracing::error!("Registration with rendezvous node failed: {:?}", error);
+
+                            let rendezvous_node = match &error {
+                                libp2p::rendezvous::client::RegisterError::Remote { rendezvous_node, .. } => Some(*rendezvous_node),
+                                _ => None,
+                            };
+                            self.metrics.rendezvous_registration_failed(rendezvous_node);
+                        }
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupFailed {peer, error}) => {
+                            tracing::error!(
+                                peer_id = %peer,
+                                "Swap setup failed: {:#}", error);
+                            self.record_misbehaviour(peer, Misbehaviour::AbortedSwapSetup).await;
+                        }
+                        SwarmEvent::Behaviour(OutEvent::ProtocolViolation {peer, error}) => {
+                            tracing::warn!(
+                                peer_id = %peer,
+                                "Protocol violation: {:#}", error);
+                            self.record_misbehaviour(peer, Misbehaviour::ProtocolViolation).await;
+                        }
+                        SwarmEvent::Behaviour(OutEvent::PeerUnreachable { peer }) => {
+                            let swap_ids = self.running_swaps
+                                .iter()
+                                .filter(|(_, swap)| swap.peer == peer && swap.handle.strong_count() > 0)
+                                .map(|(swap_id, _)| *swap_id)
+                                .collect::<Vec<_>>();
+
+                            tracing::warn!(
+                                peer_id = %peer,
+                                ?swap_ids,
+                                "Peer with running swaps did not reconnect within {} minutes, messages to it are sent once it does",
+                                UNREACHABLE_AFTER.as_secs() / 60);
                         }
                         SwarmEvent::Behaviour(OutEvent::Failure {peer, error}) => {
                             tracing::error!(
-                                %peer,
+                                peer_id = %peer,
                                 "Communication error: {:#}", error);
                         }
                         SwarmEvent::ConnectionEstablished { peer_id: peer, endpoint, .. } => {
-                            tracing::debug!(%peer, address = %endpoint.get_remote_address(), "New connection established");
+                            tracing::debug!(peer_id = %peer, address = %endpoint.get_remote_address(), "New connection established");
 
-                            if let Some(transfer_proofs) = self.buffered_transfer_proofs.remove(&peer) {
-                                for (transfer_proof, responder) in transfer_proofs {
-                                    tracing::debug!(%peer, "Found buffered transfer proof for peer");
+                            // The peer may not have received the messages we sent before it
+                            // disconnected, so all unacknowledged ones are sent again.
+                            for (swap_id, transfer_proof) in self.transfer_proofs.to_send(peer) {
+                                tracing::debug!(peer_id = %peer, %swap_id, "Sending unacknowledged transfer proof");
 
-                                    let id = self.swarm.behaviour_mut().transfer_proof.send_request(&peer, transfer_proof);
-                                    self.inflight_transfer_proofs.insert(id, responder);
-                                }
+                                let id = self.swarm.behaviour_mut().transfer_proof.send_request(&peer, transfer_proof);
+                                self.transfer_proofs.sent(id, swap_id);
+                            }
+
+                            for (swap_id, encrypted_signature) in self.encrypted_signatures.to_send(peer) {
+                                tracing::debug!(peer_id = %peer, %swap_id, "Sending unacknowledged encrypted signature");
+
+                                let id = self.swarm.behaviour_mut().encrypted_signature.send_request(&peer, encrypted_signature);
+                                self.encrypted_signatures.sent(id, swap_id);
                             }
                         }
                         SwarmEvent::IncomingConnectionError { send_back_addr: address, error, .. } => {
                             tracing::warn!(%address, "Failed to set up connection with peer: {:#}", error);
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: Some(error) } if num_established == 0 => {
-                            tracing::debug!(%peer, address = %endpoint.get_remote_address(), "Lost connection to peer: {:#}", error);
+                            tracing::debug!(peer_id = %peer, address = %endpoint.get_remote_address(), "Lost connection to peer: {:#}", error);
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: None } if num_established == 0 => {
-                            tracing::info!(%peer, address = %endpoint.get_remote_address(), "Successfully closed connection");
+                            tracing::info!(peer_id = %peer, address = %endpoint.get_remote_address(), "Successfully closed connection");
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Identified { peer, observed_addr }) => {
+                            if self.reachability.observe(observed_addr.clone()) {
+                                if Swarm::external_addresses(&self.swarm).any(|record| record.addr == observed_addr) {
+                                    tracing::info!(peer_id = %peer, address = %observed_addr, "Peer reached us on an advertised address");
+                                } else {
+                                    tracing::debug!(peer_id = %peer, address = %observed_addr, "Peer sees us on an address that is not advertised");
+                                }
+                            }
                         }
                         SwarmEvent::NewListenAddr{address, ..} => {
                             tracing::info!(%address, "New listen address reported");
+
+                            // The relay nodes are configured explicitly, so listening through
+                            // them always advertises the relayed address.
+                            if address.is_relayed() {
+                                let address = self.relayed_address(address);
+                                tracing::info!(%address, "Advertising relayed address");
+                                let _ = Swarm::add_external_address(&mut self.swarm, address, AddressScore::Infinite);
+                            } else if self.advertise_listen_addresses && reachability::is_global(&address) {
+                                tracing::info!(%address, "Advertising listen address");
+                                let _ = Swarm::add_external_address(&mut self.swarm, address, AddressScore::Infinite);
+                            }
+                        }
+                        SwarmEvent::ExpiredListenAddr{address, ..} if address.is_relayed() => {
+                            let address = self.relayed_address(address);
+                            if Swarm::remove_external_address(&mut self.swarm, &address) {
+                                tracing::info!(%address, "Stopped advertising relayed address");
+                            }
+                        }
+                        SwarmEvent::ExpiredListenAddr{address, ..} => {
+                            if self.advertise_listen_addresses && Swarm::remove_external_address(&mut self.swarm, &address) {
+                                tracing::info!(%address, "Stopped advertising expired listen address");
+                            }
                         }
                         _ => {}
                     }
@@ -290,14 +734,18 @@ where
                 next_transfer_proof = self.send_transfer_proof.next() => {
                     match next_transfer_proof {
                         Some(Ok((peer, transfer_proof, responder))) => {
+                            let swap_id = transfer_proof.swap_id;
+
+                            self.persist_outbound_message(peer, OutboundMessage::TransferProof(transfer_proof.clone())).await;
+                            self.transfer_proofs.insert(swap_id, peer, transfer_proof.clone(), Some(responder));
+
                             if !self.swarm.behaviour_mut().transfer_proof.is_connected(&peer) {
-                                tracing::warn!(%peer, "No active connection to peer, buffering transfer proof");
-                                self.buffered_transfer_proofs.entry(peer).or_insert_with(Vec::new).push((transfer_proof, responder));
+                                tracing::warn!(peer_id = %peer, %swap_id, "No active connection to peer, sending transfer proof once it reconnects");
                                 continue;
                             }
 
                             let id = self.swarm.behaviour_mut().transfer_proof.send_request(&peer, transfer_proof);
-                            self.inflight_transfer_proofs.insert(id, responder);
+                            self.transfer_proofs.sent(id, swap_id);
                         },
                         Some(Err(error)) => {
                             tracing::debug!("A swap stopped without sending a transfer proof: {:#}", error);
@@ -307,11 +755,374 @@ where
                         }
                     }
                 }
+                next_encrypted_signature = self.send_encrypted_signature.next() => {
+                    match next_encrypted_signature {
+                        Some(Ok((peer, encrypted_signature, responder))) => {
+                            let swap_id = encrypted_signature.swap_id;
+
+                            self.persist_outbound_message(peer, OutboundMessage::EncryptedSignature(encrypted_signature.clone())).await;
+                            self.encrypted_signatures.insert(swap_id, peer, encrypted_signature.clone(), Some(responder));
+
+                            if !self.swarm.behaviour_mut().encrypted_signature.is_connected(&peer) {
+                                tracing::warn!(peer_id = %peer, %swap_id, "No active connection to peer, sending encrypted signature once it reconnects");
+                                continue;
+                            }
+
+                            let id = self.swarm.behaviour_mut().encrypted_signature.send_request(&peer, encrypted_signature);
+                            self.encrypted_signatures.sent(id, swap_id);
+                        },
+                        Some(Err(error)) => {
+                            tracing::debug!("A swap stopped without sending an encrypted signature: {:#}", error);
+                        }
+                        None => {
+                            unreachable!("stream of encrypted signature receivers must never terminate")
+                        }
+                    }
+                }
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                Some(response_channel) = self.inflight_received_transfer_proofs.next() => {
+                    let _ = self.swarm.behaviour_mut().transfer_proof.send_response(response_channel, ());
+                }
+                next_abort = self.send_abort.next() => {
+                    match next_abort {
+                        Some(Ok((peer, abort, responder))) => {
+                            // There is no point in buffering an abort, by the time we reconnect the
+                            // peer will have given up on the swap on its own.
+                            if !self.swarm.behaviour_mut().abort.is_connected(&peer) {
+                                tracing::debug!(peer_id = %peer, swap_id = %abort.swap_id, "No active connection to peer, not sending abort");
+                                continue;
+                            }
+
+                            let id = self.swarm.behaviour_mut().abort.send_request(&peer, abort);
+                            self.inflight_abort_requests.insert(id, responder);
+                        },
+                        Some(Err(_)) => {}
+                        None => {
+                            unreachable!("stream of abort receivers must never terminate")
+                        }
+                    }
+                }
+                Some(response_channel) = self.inflight_received_aborts.next() => {
+                    let _ = self.swarm.behaviour_mut().abort.send_response(response_channel, ());
+                }
+                Ok((control, responder)) = self.control_receiver.recv() => {
+                    let response = self.handle_control(control).await;
+
+                    let _ = responder.respond(response);
+                }
+                _ = refresh_bans.tick() => {
+                    self.refresh_bans().await;
+                }
+                _ = refresh_kept_connections.tick() => {
+                    self.refresh_kept_connections();
+                }
+            }
+        }
+    }
+
+    /// Returns the swaps that were set up with the identity of the swarm.
+    async fn swaps_of_identity(&self) -> Result<Vec<(Uuid, State)>> {
+        let mut swaps = Vec::new();
+        for (swap_id, state) in self.db.all().await? {
+            if self.db.get_swap_identity(swap_id).await? == self.identity_generation {
+                swaps.push((swap_id, state));
+            }
+        }
+
+        Ok(swaps)
+    }
+
+    /// Queues the messages that were not acknowledged before the last
+    /// shutdown, they are sent as soon as the peer connects.
+    ///
+    /// The messages of finished swaps are of no use to the peer anymore and
+    /// are removed instead.
+    async fn load_outbound_messages(&mut self, swaps: &[(Uuid, State)]) {
+        let messages = match self.db.get_outbound_messages().await {
+            Ok(messages) => messages,
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to load unacknowledged messages from database: {:#}",
+                    error
+                );
+                return;
+            }
+        };
+
+        for (peer, message) in messages {
+            let swap_id = message.swap_id();
+            let state = match swaps.iter().find(|(id, _)| *id == swap_id) {
+                Some((_, state)) => state,
+                // the message belongs to a swap of another identity
+                None => continue,
+            };
+
+            if state.swap_finished() {
+                self.remove_outbound_message(swap_id).await;
+                continue;
+            }
+
+            match message {
+                OutboundMessage::TransferProof(request) => {
+                    self.transfer_proofs.insert(swap_id, peer, request, None)
+                }
+                OutboundMessage::EncryptedSignature(request) => self
+                    .encrypted_signatures
+                    .insert(swap_id, peer, request, None),
+            }
+        }
+    }
+
+    /// Persists the message before it is sent, so it can be sent again after
+    /// a restart if the peer does not acknowledge it in time.
+    async fn persist_outbound_message(&self, peer: PeerId, message: OutboundMessage) {
+        let swap_id = message.swap_id();
+
+        if let Err(error) = self.db.insert_outbound_message(peer, message).await {
+            tracing::warn!(%swap_id, "Failed to persist message, it is not sent again after a restart: {:#}", error);
+        }
+    }
+
+    async fn remove_outbound_message(&self, swap_id: Uuid) {
+        if let Err(error) = self.db.remove_outbound_message(swap_id).await {
+            tracing::warn!(%swap_id, "Failed to remove acknowledged message from database: {:#}", error);
+        }
+    }
+
+    async fn record_misbehaviour(&mut self, peer: PeerId, misbehaviour: Misbehaviour) {
+        match self.reputation.record(peer, misbehaviour).await {
+            Ok(Some(ban)) => self.ban(ban),
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!(peer_id = %peer, %misbehaviour, "Failed to record misbehaviour of peer: {:#}", error)
+            }
+        }
+    }
+
+    /// Syncs the banned peers with the bans stored in the database.
+    async fn refresh_bans(&mut self) {
+        let bans = match self.db.get_bans().await {
+            Ok(bans) => bans,
+            Err(error) => {
+                tracing::warn!("Failed to load bans from database: {:#}", error);
+                return;
+            }
+        };
+
+        let now = OffsetDateTime::now_utc();
+        let active = bans
+            .into_iter()
+            .filter(|ban| ban.is_active(now))
+            .map(|ban| (ban.peer_id, ban))
+            .collect::<HashMap<_, _>>();
+
+        let lifted = self
+            .banned_peers
+            .keys()
+            .filter(|peer| !active.contains_key(peer))
+            .copied()
+            .collect::<Vec<_>>();
+        for peer in lifted {
+            tracing::info!(peer_id = %peer, "Lifting ban of peer");
+            self.banned_peers.remove(&peer);
+            self.swarm.unban_peer_id(peer);
+        }
+
+        for (_, ban) in active {
+            self.ban(ban);
+        }
+    }
+
+    fn ban(&mut self, ban: Ban) {
+        let peer = ban.peer_id;
+
+        if !self.banned_peers.contains_key(&peer) {
+            tracing::warn!(peer_id = %peer, reason = %ban.reason, "Banning peer");
+            self.swarm.ban_peer_id(peer);
+        }
+
+        self.banned_peers.insert(peer, ban);
+    }
+
+    async fn handle_control(&mut self, control: Control) -> Result<ControlResponse> {
+        match control {
+            Control::PauseQuotes => {
+                tracing::info!("Pausing quotes");
+                self.quotes_paused = true;
+            }
+            Control::ResumeQuotes => {
+                tracing::info!("Resuming quotes");
+                self.quotes_paused = false;
+            }
+            Control::EnableQuotesOnly => {
+                tracing::info!("Only serving quotes, declining new swaps");
+                self.quotes_only = true;
+            }
+            Control::DisableQuotesOnly => {
+                tracing::info!("Accepting new swaps again");
+                self.quotes_only = false;
+            }
+            Control::SetLimits { min_buy, max_buy } => {
+                tracing::info!(%min_buy, %max_buy, "Updating swap limits");
+                self.min_buy = min_buy;
+                self.max_buy = max_buy;
+                self.swarm
+                    .behaviour_mut()
+                    .swap_setup
+                    .set_limits(min_buy, max_buy);
+                self.swarm
+                    .behaviour_mut()
+                    .sell_xmr_setup
+                    .set_limits(min_buy, max_buy);
+            }
+            Control::Shutdown => {
+                tracing::info!("Shutting down, no longer accepting new swaps");
+                self.shutting_down = true;
+            }
+            Control::Retire => {
+                tracing::info!(peer_id = %self.peer_id(), "Retiring identity, no longer accepting new swaps");
+                self.shutting_down = true;
+                if let Some(rendezvous) = self.swarm.behaviour_mut().rendezvous.as_mut() {
+                    rendezvous.unregister();
+                }
+            }
+            Control::Status => {}
+            Control::RunningSwaps => {
+                return Ok(ControlResponse::RunningSwaps(self.running_swap_ids()));
+            }
+            Control::WithdrawBtc { address, amount } => {
+                let txid = self.withdraw_btc(address, amount).await?;
+
+                return Ok(ControlResponse::BtcWithdrawn(txid));
+            }
+            Control::WithdrawXmr { address, amount } => {
+                let tx_hash = self.withdraw_xmr(address, amount).await?;
+
+                return Ok(ControlResponse::XmrWithdrawn(tx_hash));
+            }
+        }
+
+        Ok(ControlResponse::Status(self.maker_status()))
+    }
+
+    /// Withdraws BTC that is not reserved for swaps that did not lock their
+    /// BTC yet, all of it if no amount is given.
+    async fn withdraw_btc(
+        &mut self,
+        address: bitcoin::Address,
+        amount: Option<bitcoin::Amount>,
+    ) -> Result<bitcoin::Txid> {
+        self.bitcoin_wallet.sync().await?;
+
+        let reserved = self.reservations.reserved_btc();
+        let unreserved = self
+            .bitcoin_wallet
+            .max_giveable(address.script_pubkey().len())
+            .await?
+            .checked_sub(reserved)
+            .unwrap_or(bitcoin::Amount::ZERO);
+
+        let amount = match amount {
+            Some(amount) if amount > unreserved => bail!(
+                "Cannot withdraw {}, only {} are not reserved for swaps that did not lock their Bitcoin yet",
+                amount,
+                unreserved
+            ),
+            Some(amount) => amount,
+            None if unreserved == bitcoin::Amount::ZERO => bail!(
+                "No Bitcoin to withdraw, {} are reserved for swaps that did not lock their Bitcoin yet",
+                reserved
+            ),
+            None => unreserved,
+        };
+
+        let psbt = self
+            .bitcoin_wallet
+            .send_to_address(address, amount, None)
+            .await?;
+        let signed_tx = self.bitcoin_wallet.sign_and_finalize(psbt).await?;
+        let (txid, _) = self.bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+
+        tracing::info!(%amount, %reserved, %txid, "Withdrew Bitcoin");
+
+        Ok(txid)
+    }
+
+    /// Withdraws XMR from the primary account unless it is reserved for swaps
+    /// that did not lock their XMR yet.
+    async fn withdraw_xmr(
+        &mut self,
+        address: monero::Address,
+        amount: monero::Amount,
+    ) -> Result<monero::TxHash> {
+        let reserved = self.reservations.reserved_xmr();
+        let unreserved = monero::Amount::from_piconero(
+            self.monero_wallet
+                .get_balance()
+                .await?
+                .as_piconero()
+                .saturating_sub(reserved.as_piconero()),
+        );
+
+        if amount > unreserved {
+            bail!(
+                "Cannot withdraw {}, only {} are not reserved for swaps that did not lock their Monero yet",
+                amount,
+                unreserved
+            );
+        }
+
+        self.monero_wallet.withdraw(address, amount).await
+    }
+
+    /// Gives a new swap a Monero subaddress of its own and records the
+    /// identity it was set up with.
+    ///
+    /// The Monero is refunded, or the XMR we buy is swept, to the subaddress so
+    /// the funds of concurrent swaps can be told apart. If it cannot be created
+    /// the swap starts anyway and uses the main address.
+    async fn record_new_swap(&self, swap_id: Uuid) {
+        match self
+            .monero_wallet
+            .create_subaddress(format!("swap {}", swap_id))
+            .await
+        {
+            Ok(address) => {
+                if let Err(error) = self.db.insert_monero_address(swap_id, address).await {
+                    tracing::warn!(%swap_id, "Unable to save Monero subaddress in database: {:#}", error);
+                }
+            }
+            Err(error) => {
+                tracing::warn!(%swap_id, "Failed to create Monero subaddress: {:#}", error);
             }
         }
+
+        self.record_swap_identity(swap_id).await;
+    }
+
+    async fn record_swap_identity(&self, swap_id: Uuid) {
+        if let Err(error) = self
+            .db
+            .insert_swap_identity(swap_id, self.identity_generation)
+            .await
+        {
+            tracing::warn!(%swap_id, "Unable to save identity of swap in database: {:#}", error);
+        }
+    }
+
+    fn maker_status(&self) -> MakerStatus {
+        MakerStatus {
+            quotes_paused: self.quotes_paused,
+            quotes_only: self.quotes_only,
+            shutting_down: self.shutting_down,
+            min_buy: self.min_buy,
+            max_buy: self.max_buy,
+            external_addresses: self
+                .reachability
+                .report(Swarm::external_addresses(&self.swarm).map(|record| &record.addr)),
+        }
     }
 
     async fn make_quote(
@@ -324,10 +1135,70 @@ where
             .latest_rate()
             .context("Failed to get latest rate")?;
 
+        let expires_at = (SystemTime::now() + QUOTE_VALIDITY)
+            .duration_since(UNIX_EPOCH)
+            .context("System time is before the unix epoch")?
+            .as_secs();
+        let bitcoin_redeem = self
+            .bitcoin_wallet
+            .estimate_fee(
+                bitcoin::TxKind::Redeem,
+                bitcoin::TxRedeem::weight(),
+                max_buy,
+            )
+            .await?;
+        let bitcoin_punish = self
+            .bitcoin_wallet
+            .estimate_fee(
+                bitcoin::TxKind::Punish,
+                bitcoin::TxPunish::weight(),
+                max_buy,
+            )
+            .await?;
+        let buy_price = if self.swarm.behaviour().sell_xmr_setup.is_enabled() {
+            Some(rate.bid().context("Failed to compute bid price")?)
+        } else {
+            None
+        };
+
+        // The quoted price is the one of a swap of the minimum amount, the pricing
+        // strategy may widen the spread for larger swaps.
+        let inventory = monero::Amount::from_piconero(
+            self.monero_wallet
+                .lockable_balance()
+                .await?
+                .as_piconero()
+                .saturating_sub(self.reservations.reserved_xmr().as_piconero()),
+        );
+        let recent_volume = self.recent_volume.total();
+        let rate_for = |btc| {
+            let input = PricingInput {
+                btc,
+                inventory,
+                recent_volume,
+            };
+            rate.with_additional_spread(self.pricing_strategy.additional_spread(&input))
+        };
+        let min_rate = rate_for(min_buy);
+        let max_rate = rate_for(max_buy);
+
         Ok(BidQuote {
-            price: rate.ask().context("Failed to compute asking price")?,
+            price: min_rate.ask().context("Failed to compute asking price")?,
             min_quantity: min_buy,
             max_quantity: max_buy,
+            details: Some(QuoteDetails {
+                pair: AssetPair::XMR_BTC,
+                expires_at,
+                min_quantity_xmr: min_rate.sell_quote(min_buy)?,
+                max_quantity_xmr: max_rate.sell_quote(max_buy)?,
+                fees: FeeAssumptions {
+                    bitcoin_redeem,
+                    bitcoin_punish,
+                    monero_lock: monero::MONERO_FEE,
+                },
+                buy_price,
+                timelocks: Some(self.swarm.behaviour().swap_setup.timelock_ranges()),
+            }),
         })
     }
 
@@ -336,8 +1207,9 @@ where
         bob_peer_id: PeerId,
         swap_id: Uuid,
         state3: State3,
+        reservation: Option<Reservation>,
     ) {
-        let handle = self.new_handle(bob_peer_id, swap_id);
+        let handle = self.new_handle(bob_peer_id, swap_id, reservation);
 
         let initial_state = AliceState::Started {
             state3: Box::new(state3),
@@ -351,10 +1223,13 @@ where
             db: self.db.clone(),
             state: initial_state,
             swap_id,
+            redeem_signer: self.redeem_signer.clone(),
         };
 
         // TODO: Consider adding separate components for start/resume of swaps
 
+        self.record_new_swap(swap_id).await;
+
         // swaps save peer id so we can resume
         match self.db.insert_peer_id(swap_id, bob_peer_id).await {
             Ok(_) => {
@@ -370,7 +1245,15 @@ where
 
     /// Create a new [`EventLoopHandle`] that is scoped for communication with
     /// the given peer.
-    fn new_handle(&mut self, peer: PeerId, swap_id: Uuid) -> EventLoopHandle {
+    ///
+    /// The handle holds on to the reservation of the swap's XMR until the swap
+    /// locks it.
+    fn new_handle(
+        &mut self,
+        peer: PeerId,
+        swap_id: Uuid,
+        reservation: Option<Reservation>,
+    ) -> EventLoopHandle {
         // we deliberately don't put timeouts on these channels because the swap always
         // races these futures against a timelock
 
@@ -380,6 +1263,13 @@ where
         self.recv_encrypted_signature
             .insert(swap_id, encrypted_signature.0);
 
+        let running = Arc::new(());
+        self.running_swaps.insert(swap_id, RunningSwap {
+            peer,
+            handle: Arc::downgrade(&running),
+        });
+        self.refresh_kept_connections();
+
         self.send_transfer_proof.push(
             async move {
                 let (transfer_proof, responder) = transfer_proof_receiver.recv().await?;
@@ -394,11 +1284,223 @@ where
             .boxed(),
         );
 
+        let (recv_abort, send_abort) = self.abort_channels(peer, swap_id);
+
         EventLoopHandle {
+            peer,
             recv_encrypted_signature: Some(encrypted_signature.1),
             send_transfer_proof: Some(transfer_proof_sender),
+            recv_abort: Some(recv_abort),
+            send_abort: Some(send_abort),
+            reservation,
+            _running: running,
         }
     }
+
+    /// Creates the channels through which a swap with the given peer receives
+    /// and sends an abort, regardless of the role we take in the swap.
+    fn abort_channels(
+        &mut self,
+        peer: PeerId,
+        swap_id: Uuid,
+    ) -> (bmrng::RequestReceiver<(), ()>, bmrng::RequestSender<(), ()>) {
+        let (recv_abort_sender, recv_abort_receiver) = bmrng::channel(1);
+        let (send_abort_sender, mut send_abort_receiver) =
+            bmrng::channel_with_timeout(1, ABORT_TIMEOUT);
+
+        self.recv_abort.insert(swap_id, recv_abort_sender);
+
+        self.send_abort.push(
+            async move {
+                let ((), responder) = send_abort_receiver.recv().await?;
+
+                Ok((peer, abort::Request { swap_id }, responder))
+            }
+            .boxed(),
+        );
+
+        (recv_abort_receiver, send_abort_sender)
+    }
+
+    async fn handle_sell_xmr_setup_done(
+        &mut self,
+        peer_id: PeerId,
+        swap_id: Uuid,
+        state2: State2,
+        reservation: Option<Reservation>,
+    ) {
+        self.record_new_swap(swap_id).await;
+
+        if let Err(error) = self.db.insert_peer_id(swap_id, peer_id).await {
+            tracing::warn!(%swap_id, "Unable to save peer-id in database: {}", error);
+            return;
+        }
+
+        tracing::info!(%swap_id, xmr = %state2.xmr, "Starting new swap in which we buy XMR");
+
+        self.start_bob_swap(
+            peer_id,
+            swap_id,
+            BobState::SwapSetupCompleted(state2),
+            reservation,
+        )
+        .await;
+    }
+
+    /// Executes a swap in which we buy XMR, taking the role of Bob.
+    ///
+    /// Unlike the swaps in which we sell XMR, these swaps are spawned by the
+    /// event loop itself. The reservation of the swap's BTC is released once
+    /// the lock transaction has been seen.
+    async fn start_bob_swap(
+        &mut self,
+        peer: PeerId,
+        swap_id: Uuid,
+        state: BobState,
+        reservation: Option<Reservation>,
+    ) {
+        let monero_receive_address = match self.db.get_monero_address(swap_id).await {
+            Ok(address) => address,
+            Err(_) => self.monero_wallet.get_main_address(),
+        };
+
+        let (transfer_proof_sender, transfer_proof_receiver) = bmrng::channel(1);
+        let (encrypted_signature_sender, mut encrypted_signature_receiver) = bmrng::channel(1);
+
+        self.recv_transfer_proof
+            .insert(swap_id, transfer_proof_sender);
+
+        let running = Arc::new(());
+        self.running_swaps.insert(swap_id, RunningSwap {
+            peer,
+            handle: Arc::downgrade(&running),
+        });
+        self.refresh_kept_connections();
+
+        self.send_encrypted_signature.push(
+            async move {
+                let (tx_redeem_encsig, responder) = encrypted_signature_receiver.recv().await?;
+
+                let request = encrypted_signature::Request {
+                    swap_id,
+                    tx_redeem_encsig,
+                };
+
+                Ok((peer, request, responder))
+            }
+            .boxed(),
+        );
+
+        let (recv_abort, send_abort) = self.abort_channels(peer, swap_id);
+
+        let bitcoin_wallet = self.bitcoin_wallet.clone();
+        let tx_lock = state.tx_lock().cloned();
+        let release_reservation = async move {
+            if let (Some(_), Some(tx_lock)) = (&reservation, tx_lock) {
+                let subscription = bitcoin_wallet.subscribe_to(tx_lock).await;
+
+                if let Err(error) = subscription.wait_until_seen().await {
+                    tracing::warn!(%swap_id, "Failed to watch for the Bitcoin lock transaction, keeping the BTC reserved until the swap finishes: {:#}", error);
+                    return future::pending().await;
+                }
+            }
+
+            drop(reservation);
+            future::pending::<Infallible>().await
+        };
+
+        let swap = bob::Swap {
+            state,
+            event_loop_handle: cli::EventLoopHandle::for_swap_set_up_by_peer(
+                peer,
+                transfer_proof_receiver,
+                encrypted_signature_sender,
+                recv_abort,
+                send_abort,
+                self.env_config,
+            ),
+            db: self.db.clone(),
+            bitcoin_wallet: self.bitcoin_wallet.clone(),
+            monero_wallet: bob::MoneroWallet::Shared(self.monero_wallet.clone()),
+            env_config: self.env_config,
+            id: swap_id,
+            monero_receive_address,
+        };
+
+        tokio::spawn(async move {
+            // Keeps the swap marked as running until it is finished.
+            let _running = running;
+
+            let result = tokio::select! {
+                result = bob::run(swap) => result,
+                never = release_reservation => match never {},
+            };
+
+            match result {
+                Ok(state) => {
+                    tracing::info!(%swap_id, %state, "Swap in which we bought XMR finished")
+                }
+                Err(error) => {
+                    tracing::error!(%swap_id, "Swap in which we buy XMR failed: {:#}", error)
+                }
+            }
+        });
+    }
+
+    /// Keeps the connections to the peers of the running swaps alive and
+    /// releases the ones of the peers whose swaps finished.
+    fn refresh_kept_connections(&mut self) {
+        self.running_swaps
+            .retain(|_, swap| swap.handle.strong_count() > 0);
+
+        let peers = self
+            .running_swaps
+            .values()
+            .map(|swap| swap.peer)
+            .collect::<Vec<_>>();
+        self.swarm
+            .behaviour_mut()
+            .connection_manager
+            .keep_connected_to(peers);
+    }
+
+    /// The address takers dial to reach us through the relay node we listen
+    /// on the given `/p2p-circuit` address through.
+    fn relayed_address(&self, listen_address: Multiaddr) -> Multiaddr {
+        listen_address.with(Protocol::P2p((*self.swarm.local_peer_id()).into()))
+    }
+
+    /// Returns the ids of the swaps that are currently executed.
+    fn running_swap_ids(&mut self) -> Vec<Uuid> {
+        self.running_swaps
+            .retain(|_, swap| swap.handle.strong_count() > 0);
+
+        self.running_swaps.keys().copied().collect()
+    }
+
+    /// Counts the swaps that are currently executed or set up, in total and
+    /// with the given peer.
+    fn ongoing_swaps(&mut self, peer: PeerId) -> OngoingSwaps {
+        self.running_swaps
+            .retain(|_, swap| swap.handle.strong_count() > 0);
+        let setups = self.reservations.reserved_slots(peer);
+
+        OngoingSwaps {
+            total: self.running_swaps.len() + setups.total,
+            with_peer: self
+                .running_swaps
+                .values()
+                .filter(|swap| swap.peer == peer)
+                .count()
+                + setups.with_peer,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RunningSwap {
+    peer: PeerId,
+    handle: Weak<()>,
 }
 
 pub trait LatestRate {
@@ -413,6 +1515,10 @@ pub struct FixedRate(Rate);
 impl FixedRate {
     pub const RATE: f64 = 0.01;
 
+    pub fn new(rate: Rate) -> Self {
+        Self(rate)
+    }
+
     pub fn value(&self) -> Rate {
         self.0
     }
@@ -435,16 +1541,16 @@ impl LatestRate for FixedRate {
     }
 }
 
-/// Produces [`Rate`]s based on [`PriceUpdate`]s from kraken and a configured
-/// spread.
+/// Produces [`Rate`]s based on [`PriceUpdate`]s from the configured price
+/// feeds and a configured spread.
 #[derive(Debug, Clone)]
-pub struct KrakenRate {
+pub struct PriceFeedRate {
     ask_spread: Decimal,
-    price_updates: kraken::PriceUpdates,
+    price_updates: price_feed::PriceUpdates,
 }
 
-impl KrakenRate {
-    pub fn new(ask_spread: Decimal, price_updates: kraken::PriceUpdates) -> Self {
+impl PriceFeedRate {
+    pub fn new(ask_spread: Decimal, price_updates: price_feed::PriceUpdates) -> Self {
         Self {
             ask_spread,
             price_updates,
@@ -452,8 +1558,8 @@ impl KrakenRate {
     }
 }
 
-impl LatestRate for KrakenRate {
-    type Error = kraken::Error;
+impl LatestRate for PriceFeedRate {
+    type Error = price_feed::Error;
 
     fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
         let update = self.price_updates.latest_update()?;
@@ -465,11 +1571,49 @@ impl LatestRate for KrakenRate {
 
 #[derive(Debug)]
 pub struct EventLoopHandle {
+    peer: PeerId,
     recv_encrypted_signature: Option<bmrng::RequestReceiver<bitcoin::EncryptedSignature, ()>>,
     send_transfer_proof: Option<bmrng::RequestSender<monero::TransferProof, ()>>,
+    recv_abort: Option<bmrng::RequestReceiver<(), ()>>,
+    send_abort: Option<bmrng::RequestSender<(), ()>>,
+    /// The reservation of the swap's XMR, released once it is locked.
+    reservation: Option<Reservation>,
+    /// Keeps the swap marked as running in the [`EventLoop`] for as long as
+    /// the handle is alive.
+    _running: Arc<()>,
 }
 
 impl EventLoopHandle {
+    /// Creates a handle that is not tracked by an [`EventLoop`], for the CLI
+    /// which takes the role of Alice when selling XMR.
+    pub(crate) fn new(
+        peer: PeerId,
+        recv_encrypted_signature: bmrng::RequestReceiver<bitcoin::EncryptedSignature, ()>,
+        send_transfer_proof: bmrng::RequestSender<monero::TransferProof, ()>,
+        recv_abort: bmrng::RequestReceiver<(), ()>,
+        send_abort: bmrng::RequestSender<(), ()>,
+    ) -> Self {
+        Self {
+            peer,
+            recv_encrypted_signature: Some(recv_encrypted_signature),
+            send_transfer_proof: Some(send_transfer_proof),
+            recv_abort: Some(recv_abort),
+            send_abort: Some(send_abort),
+            reservation: None,
+            _running: Arc::new(()),
+        }
+    }
+
+    /// The peer the swap is executed with.
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    /// Releases the XMR that was reserved for the swap, once it is locked.
+    pub fn release_reservation(&mut self) {
+        self.reservation = None;
+    }
+
     pub async fn recv_encrypted_signature(&mut self) -> Result<bitcoin::EncryptedSignature> {
         let (tx_redeem_encsig, responder) = self
             .recv_encrypted_signature
@@ -495,6 +1639,156 @@ impl EventLoopHandle {
 
         Ok(())
     }
+
+    /// Resolves once the peer told us that it will not proceed with the swap.
+    pub async fn recv_abort(&mut self) -> Result<()> {
+        let ((), responder) = self
+            .recv_abort
+            .take()
+            .context("Abort was already received")?
+            .recv()
+            .await?;
+
+        responder
+            .respond(())
+            .context("Failed to acknowledge receipt of abort")?;
+
+        Ok(())
+    }
+
+    /// Tells the peer that we will not proceed with the swap.
+    pub async fn send_abort(&mut self) -> Result<()> {
+        self.send_abort
+            .take()
+            .context("Abort was already sent")?
+            .send_receive(())
+            .await
+            .context("Failed to send abort")?;
+
+        Ok(())
+    }
+}
+
+/// Requests that adjust the behaviour of a running [`EventLoop`].
+#[derive(Debug, Clone)]
+pub enum Control {
+    /// Answer quote requests with a maximum quantity of zero and decline to
+    /// set up new swaps, also with takers that were quoted before.
+    PauseQuotes,
+    ResumeQuotes,
+    /// Keep serving quotes and spot prices, but decline to set up new swaps.
+    EnableQuotesOnly,
+    DisableQuotesOnly,
+    SetLimits {
+        min_buy: bitcoin::Amount,
+        max_buy: bitcoin::Amount,
+    },
+    /// Stop accepting new swaps, the running swaps are not affected.
+    Shutdown,
+    /// Like [`Control::Shutdown`], and unregister from the rendezvous points
+    /// because the identity was rotated.
+    Retire,
+    Status,
+    /// Report the swaps that are currently executed.
+    RunningSwaps,
+    /// Withdraw BTC that is not reserved for swaps, all of it if no amount is
+    /// given.
+    WithdrawBtc {
+        address: bitcoin::Address,
+        amount: Option<bitcoin::Amount>,
+    },
+    /// Withdraw XMR that is not reserved for swaps.
+    WithdrawXmr {
+        address: monero::Address,
+        amount: monero::Amount,
+    },
+}
+
+/// What the [`EventLoop`] answers a [`Control`] request with.
+#[derive(Debug)]
+pub enum ControlResponse {
+    /// The maker settings after the request was handled.
+    Status(MakerStatus),
+    RunningSwaps(Vec<Uuid>),
+    BtcWithdrawn(bitcoin::Txid),
+    XmrWithdrawn(monero::TxHash),
+}
+
+/// The maker settings an [`EventLoop`] currently operates with.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MakerStatus {
+    pub quotes_paused: bool,
+    pub quotes_only: bool,
+    pub shutting_down: bool,
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    pub min_buy: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    pub max_buy: bitcoin::Amount,
+    /// The addresses we advertise and whether peers reached us on them.
+    pub external_addresses: Vec<AddressStatus>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventLoopController {
+    sender: bmrng::RequestSender<Control, Result<ControlResponse>>,
+}
+
+impl EventLoopController {
+    /// Sends a request that adjusts the maker settings, use
+    /// [`EventLoopController::withdraw_btc`] and
+    /// [`EventLoopController::withdraw_xmr`] to withdraw.
+    pub async fn send(&self, control: Control) -> Result<MakerStatus> {
+        match self.request(control).await? {
+            ControlResponse::Status(status) => Ok(status),
+            other => bail!("Unexpected response to control request: {:?}", other),
+        }
+    }
+
+    /// Returns the ids of the swaps that are currently executed.
+    pub async fn running_swaps(&self) -> Result<Vec<Uuid>> {
+        match self.request(Control::RunningSwaps).await? {
+            ControlResponse::RunningSwaps(swap_ids) => Ok(swap_ids),
+            other => bail!("Unexpected response to running swaps request: {:?}", other),
+        }
+    }
+
+    /// Withdraws BTC that is not reserved for swaps, all of it if no amount is
+    /// given.
+    pub async fn withdraw_btc(
+        &self,
+        address: bitcoin::Address,
+        amount: Option<bitcoin::Amount>,
+    ) -> Result<bitcoin::Txid> {
+        match self
+            .request(Control::WithdrawBtc { address, amount })
+            .await?
+        {
+            ControlResponse::BtcWithdrawn(txid) => Ok(txid),
+            other => bail!("Unexpected response to withdrawal: {:?}", other),
+        }
+    }
+
+    /// Withdraws XMR that is not reserved for swaps.
+    pub async fn withdraw_xmr(
+        &self,
+        address: monero::Address,
+        amount: monero::Amount,
+    ) -> Result<monero::TxHash> {
+        match self
+            .request(Control::WithdrawXmr { address, amount })
+            .await?
+        {
+            ControlResponse::XmrWithdrawn(tx_hash) => Ok(tx_hash),
+            other => bail!("Unexpected response to withdrawal: {:?}", other),
+        }
+    }
+
+    async fn request(&self, control: Control) -> Result<ControlResponse> {
+        self.sender
+            .send_receive(control)
+            .await
+            .context("Failed to send control request to event loop")?
+    }
 }
 
 #[allow(missing_debug_implementations)]
@@ -0,0 +1,201 @@
+//! Rotates the libp2p identity of the ASB.
+//!
+//! Every identity is derived from the seed by its generation, the identity the
+//! ASB started out with is generation 0. A rotation moves the ASB to the next
+//! generation, the previous identity stays around for a while:
+//!
+//! - During the overlap it keeps being registered at the rendezvous points and
+//!   keeps accepting new swaps, so takers can move over to the new identity.
+//! - After the overlap it only finishes the swaps it set up, because the takers
+//!   of these swaps only know its peer id.
+//! - Once all of its swaps are finished it is retired and no longer started.
+//!
+//! A previous identity keeps listening on the addresses it listened on when it
+//! was rotated, the current identity needs addresses of its own until then.
+
+use anyhow::{Context, Result};
+use libp2p::Multiaddr;
+use std::collections::HashSet;
+use time::OffsetDateTime;
+
+/// The rotation to a new identity, as recorded in the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityRotation {
+    /// The generation of the new identity.
+    pub generation: u32,
+    pub rotated_at: OffsetDateTime,
+    /// Until when the previous identity is registered at the rendezvous
+    /// points and accepts new swaps.
+    pub overlap_until: OffsetDateTime,
+    /// The addresses the previous identity keeps listening on.
+    pub previous_listen: Vec<Multiaddr>,
+    /// The addresses the previous identity keeps announcing.
+    pub previous_external_addresses: Vec<Multiaddr>,
+}
+
+impl IdentityRotation {
+    /// Rotates from the current identity to the next generation.
+    pub fn next(
+        rotations: &[IdentityRotation],
+        overlap: time::Duration,
+        listen: Vec<Multiaddr>,
+        external_addresses: Vec<Multiaddr>,
+        now: OffsetDateTime,
+    ) -> Result<Self> {
+        let generation = current_generation(rotations)
+            .checked_add(1)
+            .context("The identity cannot be rotated any further")?;
+
+        Ok(Self {
+            generation,
+            rotated_at: now,
+            overlap_until: now + overlap,
+            previous_listen: listen,
+            previous_external_addresses: external_addresses,
+        })
+    }
+
+    pub fn previous_generation(&self) -> u32 {
+        self.generation - 1
+    }
+}
+
+/// The generation of the identity new swaps are set up with.
+pub fn current_generation(rotations: &[IdentityRotation]) -> u32 {
+    rotations
+        .iter()
+        .map(|rotation| rotation.generation)
+        .max()
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retirement {
+    /// Registered at the rendezvous points and accepting new swaps until the
+    /// given time.
+    Overlapping { until: OffsetDateTime },
+    /// Only finishes the swaps it set up.
+    Draining,
+}
+
+/// An identity the ASB was rotated away from that still has to be started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviousIdentity {
+    pub generation: u32,
+    pub listen: Vec<Multiaddr>,
+    pub external_addresses: Vec<Multiaddr>,
+    pub retirement: Retirement,
+}
+
+/// Returns the previous identities that are not retired yet, given the
+/// generations of the unfinished swaps.
+pub fn previous_identities(
+    rotations: &[IdentityRotation],
+    unfinished_swaps: &[u32],
+    now: OffsetDateTime,
+) -> Vec<PreviousIdentity> {
+    let unfinished_swaps = unfinished_swaps.iter().copied().collect::<HashSet<_>>();
+
+    rotations
+        .iter()
+        .filter_map(|rotation| {
+            let generation = rotation.previous_generation();
+            let retirement = if now < rotation.overlap_until {
+                Retirement::Overlapping {
+                    until: rotation.overlap_until,
+                }
+            } else if unfinished_swaps.contains(&generation) {
+                Retirement::Draining
+            } else {
+                return None;
+            };
+
+            Some(PreviousIdentity {
+                generation,
+                listen: rotation.previous_listen.clone(),
+                external_addresses: rotation.previous_external_addresses.clone(),
+                retirement,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn rotation(
+        rotations: &[IdentityRotation],
+        port: u16,
+        rotated_at: OffsetDateTime,
+    ) -> IdentityRotation {
+        let listen = Multiaddr::from_str(&format!("/ip4/0.0.0.0/tcp/{}", port)).unwrap();
+
+        IdentityRotation::next(
+            rotations,
+            time::Duration::hours(72),
+            vec![listen],
+            vec![],
+            rotated_at,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rotations_move_to_the_next_generation() {
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(current_generation(&[]), 0);
+
+        let first = rotation(&[], 9939, now);
+        assert_eq!(first.generation, 1);
+        assert_eq!(first.previous_generation(), 0);
+
+        let second = rotation(&[first.clone()], 9940, now);
+        assert_eq!(second.generation, 2);
+        assert_eq!(current_generation(&[first, second]), 2);
+    }
+
+    #[test]
+    fn previous_identity_overlaps_then_drains_then_retires() {
+        let rotated_at = OffsetDateTime::now_utc();
+        let rotations = vec![rotation(&[], 9939, rotated_at)];
+
+        let during_overlap = rotated_at + time::Duration::hours(1);
+        assert_eq!(previous_identities(&rotations, &[], during_overlap), vec![
+            PreviousIdentity {
+                generation: 0,
+                listen: rotations[0].previous_listen.clone(),
+                external_addresses: vec![],
+                retirement: Retirement::Overlapping {
+                    until: rotations[0].overlap_until
+                },
+            }
+        ]);
+
+        let after_overlap = rotated_at + time::Duration::hours(73);
+        let draining = previous_identities(&rotations, &[0, 1], after_overlap);
+        assert_eq!(draining.len(), 1);
+        assert_eq!(draining[0].retirement, Retirement::Draining);
+
+        // the unfinished swap belongs to the current identity
+        assert!(previous_identities(&rotations, &[1], after_overlap).is_empty());
+    }
+
+    #[test]
+    fn every_previous_identity_retires_on_its_own() {
+        let first_rotated_at = OffsetDateTime::now_utc();
+        let first = rotation(&[], 9939, first_rotated_at);
+        let second = rotation(
+            &[first.clone()],
+            9940,
+            first_rotated_at + time::Duration::hours(48),
+        );
+
+        let now = first_rotated_at + time::Duration::hours(96);
+        let previous = previous_identities(&[first, second], &[], now);
+
+        assert_eq!(previous.len(), 1);
+        assert_eq!(previous[0].generation, 1);
+    }
+}
@@ -0,0 +1,453 @@
+//! Exposes metrics of a running ASB in the Prometheus text format.
+//!
+//! Counters are updated by the event loop, balances, active swaps and
+//! latencies are read whenever the metrics are scraped. Like the ASB's RPC
+//! server, the endpoint does not provide any authentication and is meant to be
+//! bound to a local interface only.
+
+use crate::monitor::alice_event;
+use crate::network::swap_setup::SpotPriceError;
+use crate::protocol::{Database, State};
+use crate::{bitcoin, monero};
+use ::bitcoin::Denomination;
+use anyhow::{Context as _, Result};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use libp2p::PeerId;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const CONTENT_TYPE_TEXT_FORMAT: &str = "text/plain; version=0.0.4";
+
+/// The metrics collected by the event loop.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    quotes_served: AtomicU64,
+    swap_setup_failures: Mutex<BTreeMap<&'static str, u64>>,
+    /// Until when we are registered with each rendezvous node.
+    rendezvous_registrations: Mutex<BTreeMap<String, Instant>>,
+    rendezvous_registration_failures: AtomicU64,
+}
+
+impl Metrics {
+    pub fn quote_served(&self) {
+        self.quotes_served.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn swap_setup_failed(&self, error: &SpotPriceError) {
+        *self
+            .swap_setup_failures
+            .lock()
+            .expect("metrics lock not to be poisoned")
+            .entry(failure_reason(error))
+            .or_default() += 1;
+    }
+
+    pub fn rendezvous_registered(&self, rendezvous_node: PeerId, ttl: u64) {
+        self.rendezvous_registrations
+            .lock()
+            .expect("metrics lock not to be poisoned")
+            .insert(
+                rendezvous_node.to_string(),
+                Instant::now() + Duration::from_secs(ttl),
+            );
+    }
+
+    /// Records a failed registration, the rendezvous node is only known if it
+    /// rejected the registration.
+    pub fn rendezvous_registration_failed(&self, rendezvous_node: Option<PeerId>) {
+        self.rendezvous_registration_failures
+            .fetch_add(1, Ordering::Relaxed);
+
+        if let Some(rendezvous_node) = rendezvous_node {
+            self.rendezvous_registrations
+                .lock()
+                .expect("metrics lock not to be poisoned")
+                .insert(rendezvous_node.to_string(), Instant::now());
+        }
+    }
+
+    fn counters(&self) -> Vec<Family> {
+        let now = Instant::now();
+
+        let swap_setup_failures = self
+            .swap_setup_failures
+            .lock()
+            .expect("metrics lock not to be poisoned")
+            .iter()
+            .map(|(reason, count)| Sample::new(count).with_label("reason", reason))
+            .collect();
+        let rendezvous_registrations = self
+            .rendezvous_registrations
+            .lock()
+            .expect("metrics lock not to be poisoned")
+            .iter()
+            .map(|(node, registered_until)| {
+                Sample::new(u8::from(*registered_until > now)).with_label("rendezvous_node", node)
+            })
+            .collect();
+
+        vec![
+            Family {
+                name: "asb_quotes_served_total",
+                help: "Number of quotes sent to peers.",
+                kind: Kind::Counter,
+                samples: vec![Sample::new(self.quotes_served.load(Ordering::Relaxed))],
+            },
+            Family {
+                name: "asb_swap_setup_failures_total",
+                help: "Number of declined swap setups by the error reported to the peer.",
+                kind: Kind::Counter,
+                samples: swap_setup_failures,
+            },
+            Family {
+                name: "asb_rendezvous_registered",
+                help: "Whether the ASB is currently registered with the rendezvous node.",
+                kind: Kind::Gauge,
+                samples: rendezvous_registrations,
+            },
+            Family {
+                name: "asb_rendezvous_registration_failures_total",
+                help: "Number of failed registrations with rendezvous nodes.",
+                kind: Kind::Counter,
+                samples: vec![Sample::new(
+                    self.rendezvous_registration_failures
+                        .load(Ordering::Relaxed),
+                )],
+            },
+        ]
+    }
+}
+
+fn failure_reason(error: &SpotPriceError) -> &'static str {
+    match error {
+        SpotPriceError::NoSwapsAccepted => "NoSwapsAccepted",
+        SpotPriceError::AmountBelowMinimum { .. } => "AmountBelowMinimum",
+        SpotPriceError::AmountAboveMaximum { .. } => "AmountAboveMaximum",
+        SpotPriceError::BalanceTooLow { .. } => "BalanceTooLow",
+        SpotPriceError::BlockchainNetworkMismatch { .. } => "BlockchainNetworkMismatch",
+        SpotPriceError::Busy => "Busy",
+        SpotPriceError::TimelocksNotAccepted { .. } => "TimelocksNotAccepted",
+        SpotPriceError::MoneroConfirmationsNotAccepted { .. } => "MoneroConfirmationsNotAccepted",
+        SpotPriceError::RateStale => "RateStale",
+        SpotPriceError::QuotesOnly { .. } => "QuotesOnly",
+        SpotPriceError::Other => "Other",
+    }
+}
+
+/// Everything the metrics endpoint needs to serve requests.
+#[allow(missing_debug_implementations)]
+pub struct Context {
+    pub metrics: Arc<Metrics>,
+    pub bitcoin_wallet: Arc<bitcoin::Wallet>,
+    pub monero_wallet: Arc<monero::Wallet>,
+    pub db: Arc<dyn Database + Send + Sync>,
+}
+
+/// Serves the metrics at `/metrics` on the given address until the server
+/// fails.
+pub async fn serve(address: SocketAddr, context: Context) -> Result<()> {
+    let context = Arc::new(context);
+
+    let make_service = make_service_fn(move |_| {
+        let context = context.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, context.clone())
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)
+        .with_context(|| format!("Failed to bind metrics endpoint to {}", address))?
+        .serve(make_service);
+
+    tracing::info!(%address, "Metrics endpoint listening");
+
+    server.await.context("Metrics endpoint failed")?;
+
+    Ok(())
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    context: Arc<Context>,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::GET {
+        return Ok(empty_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    if request.uri().path() != "/metrics" {
+        return Ok(empty_response(StatusCode::NOT_FOUND));
+    }
+
+    let mut families = context.metrics.counters();
+    families.extend(probe(&context).await);
+
+    let mut response = Response::new(Body::from(encode(&families)));
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(CONTENT_TYPE_TEXT_FORMAT),
+    );
+
+    Ok(response)
+}
+
+/// Reads the metrics that are not tracked by the event loop.
+///
+/// A metric that cannot be read is left out, so that a single unreachable
+/// backend does not fail the whole scrape.
+async fn probe(context: &Context) -> Vec<Family> {
+    let mut families = Vec::new();
+
+    match context.bitcoin_wallet.balance().await {
+        Ok(balance) => families.push(Family {
+            name: "asb_bitcoin_balance_btc",
+            help: "Balance of the Bitcoin wallet.",
+            kind: Kind::Gauge,
+            samples: vec![Sample::new(balance.to_string_in(Denomination::Bitcoin))],
+        }),
+        Err(error) => tracing::debug!("Failed to get Bitcoin balance for metrics: {:#}", error),
+    }
+
+    match context.monero_wallet.get_balance().await {
+        Ok(balance) => families.push(Family {
+            name: "asb_monero_balance_xmr",
+            help: "Balance of the primary account of the Monero wallet.",
+            kind: Kind::Gauge,
+            samples: vec![Sample::new(xmr(balance))],
+        }),
+        Err(error) => tracing::debug!("Failed to get Monero balance for metrics: {:#}", error),
+    }
+
+    match context.db.all().await {
+        Ok(swaps) => families.push(Family {
+            name: "asb_active_swaps",
+            help: "Number of swaps that are not finished, by their current state.",
+            kind: Kind::Gauge,
+            samples: active_swaps_by_state(&swaps)
+                .into_iter()
+                .map(|(state, count)| Sample::new(count).with_label("state", state))
+                .collect(),
+        }),
+        Err(error) => tracing::debug!("Failed to load swaps for metrics: {:#}", error),
+    }
+
+    match context.bitcoin_wallet.backend_latency().await {
+        Ok(latency) => families.push(Family {
+            name: "asb_bitcoin_backend_latency_seconds",
+            help: "Round trip time of a request to the Electrum server or bitcoind node.",
+            kind: Kind::Gauge,
+            samples: vec![Sample::new(latency.as_secs_f64())],
+        }),
+        Err(error) => tracing::debug!("Failed to reach Bitcoin backend for metrics: {:#}", error),
+    }
+
+    let start = Instant::now();
+    match context.monero_wallet.block_height().await {
+        Ok(_) => families.push(Family {
+            name: "asb_monero_wallet_rpc_latency_seconds",
+            help: "Round trip time of a request to monero-wallet-rpc.",
+            kind: Kind::Gauge,
+            samples: vec![Sample::new(start.elapsed().as_secs_f64())],
+        }),
+        Err(error) => tracing::debug!("Failed to reach Monero wallet RPC for metrics: {:#}", error),
+    }
+
+    families
+}
+
+fn active_swaps_by_state(swaps: &[(Uuid, State)]) -> BTreeMap<&'static str, u64> {
+    let mut by_state = BTreeMap::new();
+
+    for (_, state) in swaps {
+        if let State::Alice(alice) = state {
+            if !state.swap_finished() {
+                *by_state.entry(alice_event(alice)).or_default() += 1;
+            }
+        }
+    }
+
+    by_state
+}
+
+fn xmr(amount: monero::Amount) -> Decimal {
+    let mut decimal = Decimal::from(amount.as_piconero());
+    decimal
+        .set_scale(12)
+        .expect("12 is smaller than max precision of 28");
+
+    decimal
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+/// All samples of a metric.
+#[derive(Debug, Clone, PartialEq)]
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+    samples: Vec<Sample>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Sample {
+    labels: Vec<(&'static str, String)>,
+    value: String,
+}
+
+impl Sample {
+    fn new(value: impl ToString) -> Self {
+        Self {
+            labels: vec![],
+            value: value.to_string(),
+        }
+    }
+
+    fn with_label(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.labels.push((name, value.to_string()));
+        self
+    }
+}
+
+fn encode(families: &[Family]) -> String {
+    let mut out = String::new();
+
+    for family in families {
+        let kind = match family.kind {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        };
+
+        let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+        let _ = writeln!(out, "# TYPE {} {}", family.name, kind);
+
+        for sample in &family.samples {
+            out.push_str(family.name);
+
+            if !sample.labels.is_empty() {
+                let labels = sample
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+                    .collect::<Vec<_>>();
+                let _ = write!(out, "{{{}}}", labels.join(","));
+            }
+
+            let _ = writeln!(out, " {}", sample.value);
+        }
+    }
+
+    out
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .expect("static response to be valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::alice::AliceState;
+
+    #[test]
+    fn metrics_are_encoded_in_text_format() {
+        let families = vec![
+            Family {
+                name: "asb_quotes_served_total",
+                help: "Number of quotes sent to peers.",
+                kind: Kind::Counter,
+                samples: vec![Sample::new(3)],
+            },
+            Family {
+                name: "asb_swap_setup_failures_total",
+                help: "Number of declined swap setups by the error reported to the peer.",
+                kind: Kind::Counter,
+                samples: vec![
+                    Sample::new(1).with_label("reason", "Busy"),
+                    Sample::new(2).with_label("reason", "Other"),
+                ],
+            },
+        ];
+
+        assert_eq!(
+            encode(&families),
+            "# HELP asb_quotes_served_total Number of quotes sent to peers.\n\
+             # TYPE asb_quotes_served_total counter\n\
+             asb_quotes_served_total 3\n\
+             # HELP asb_swap_setup_failures_total Number of declined swap setups by the error reported to the peer.\n\
+             # TYPE asb_swap_setup_failures_total counter\n\
+             asb_swap_setup_failures_total{reason=\"Busy\"} 1\n\
+             asb_swap_setup_failures_total{reason=\"Other\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn swap_setup_failures_are_counted_by_reason() {
+        let metrics = Metrics::default();
+
+        metrics.swap_setup_failed(&SpotPriceError::Busy);
+        metrics.swap_setup_failed(&SpotPriceError::Busy);
+        metrics.swap_setup_failed(&SpotPriceError::NoSwapsAccepted);
+
+        let failures = &metrics.counters()[1];
+
+        assert_eq!(failures.samples, vec![
+            Sample::new(2).with_label("reason", "Busy"),
+            Sample::new(1).with_label("reason", "NoSwapsAccepted"),
+        ]);
+    }
+
+    #[test]
+    fn rejected_registration_marks_rendezvous_node_as_unregistered() {
+        let metrics = Metrics::default();
+        let rendezvous_node = PeerId::random();
+
+        metrics.rendezvous_registered(rendezvous_node, 7200);
+        assert_eq!(metrics.counters()[2].samples[0].value, "1");
+
+        metrics.rendezvous_registration_failed(Some(rendezvous_node));
+        let counters = metrics.counters();
+        assert_eq!(counters[2].samples[0].value, "0");
+        assert_eq!(counters[3].samples[0].value, "1");
+    }
+
+    #[test]
+    fn finished_swaps_are_not_active() {
+        let swaps = vec![
+            (Uuid::new_v4(), State::Alice(AliceState::BtcRedeemed)),
+            (Uuid::new_v4(), State::Alice(AliceState::SafelyAborted)),
+        ];
+
+        assert!(active_swaps_by_state(&swaps).is_empty());
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
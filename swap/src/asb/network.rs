@@ -1,12 +1,18 @@
 use crate::asb::event_loop::LatestRate;
+use crate::asb::pricing::Pricing;
+use crate::asb::reservations::Reservation;
+use crate::asb::Rate;
 use crate::env;
 use crate::network::quote::BidQuote;
-use crate::network::rendezvous::XmrBtcNamespace;
-use crate::network::swap_setup::alice;
-use crate::network::swap_setup::alice::WalletSnapshot;
+use crate::network::rendezvous::RendezvousNamespace;
+use crate::network::swap_setup::alice::{ConcurrencyLimits, WalletSnapshot};
+use crate::network::swap_setup::{alice, sell_xmr, ConfirmationSchedule, TimelockRanges};
 use crate::network::transport::authenticate_and_multiplex;
-use crate::network::{encrypted_signature, quote, transfer_proof};
+use crate::network::{
+    abort, connection_manager, encrypted_signature, identify, quote, transfer_proof,
+};
 use crate::protocol::alice::State3;
+use crate::protocol::bob::State2;
 use anyhow::{anyhow, Error, Result};
 use futures::FutureExt;
 use libp2p::core::connection::ConnectionId;
@@ -14,6 +20,7 @@ use libp2p::core::muxing::StreamMuxerBox;
 use libp2p::core::transport::Boxed;
 use libp2p::dns::TokioDnsConfig;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::relay::{Relay, RelayConfig};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::{
     DialPeerCondition, IntoProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction,
@@ -22,6 +29,7 @@ use libp2p::swarm::{
 use libp2p::tcp::TokioTcpConfig;
 use libp2p::websocket::WsConfig;
 use libp2p::{identity, Multiaddr, NetworkBehaviour, PeerId, Transport};
+use std::sync::Arc;
 use std::task::Poll;
 use std::time::Duration;
 use uuid::Uuid;
@@ -30,14 +38,24 @@ pub mod transport {
     use super::*;
 
     /// Creates the libp2p transport for the ASB.
-    pub fn new(identity: &identity::Keypair) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    ///
+    /// Besides TCP and websocket connections the transport listens on and
+    /// dials `/p2p-circuit` addresses through circuit relay v1 nodes, which
+    /// only works with the returned [`Relay`] behaviour in the swarm.
+    pub fn new(identity: &identity::Keypair) -> Result<(Boxed<(PeerId, StreamMuxerBox)>, Relay)> {
         let tcp = TokioTcpConfig::new().nodelay(true);
         let tcp_with_dns = TokioDnsConfig::system(tcp)?;
         let websocket_with_dns = WsConfig::new(tcp_with_dns.clone());
 
-        let transport = tcp_with_dns.or_transport(websocket_with_dns).boxed();
+        let (transport, relay) = libp2p::relay::new_transport_and_behaviour(
+            RelayConfig::default(),
+            tcp_with_dns.or_transport(websocket_with_dns),
+        );
 
-        authenticate_and_multiplex(transport, identity)
+        Ok((
+            authenticate_and_multiplex(transport.boxed(), identity)?,
+            relay,
+        ))
     }
 }
 
@@ -48,12 +66,28 @@ pub mod behaviour {
     #[derive(Debug)]
     pub enum OutEvent {
         SwapSetupInitiated {
-            send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
+            peer_id: PeerId,
+            send_wallet_snapshot: bmrng::RequestReceiver<(bitcoin::Amount, Rate), WalletSnapshot>,
         },
         SwapSetupCompleted {
             peer_id: PeerId,
             swap_id: Uuid,
             state3: State3,
+            reservation: Option<Reservation>,
+            slot: Option<Reservation>,
+            pricing: Pricing,
+        },
+        SellXmrSetupInitiated {
+            peer_id: PeerId,
+            send_wallet_snapshot:
+                bmrng::RequestReceiver<bitcoin::Amount, sell_xmr::bob::WalletSnapshot>,
+        },
+        SellXmrSetupCompleted {
+            peer_id: PeerId,
+            swap_id: Uuid,
+            state2: State2,
+            reservation: Option<Reservation>,
+            slot: Option<Reservation>,
         },
         SwapDeclined {
             peer: PeerId,
@@ -72,7 +106,44 @@ pub mod behaviour {
             channel: ResponseChannel<()>,
             peer: PeerId,
         },
+        /// Received in swaps in which we buy XMR.
+        TransferProofReceived {
+            msg: Box<transfer_proof::Request>,
+            channel: ResponseChannel<()>,
+            peer: PeerId,
+        },
+        /// Received in swaps in which we buy XMR.
+        EncryptedSignatureAcknowledged {
+            id: RequestId,
+        },
+        AbortReceived {
+            msg: abort::Request,
+            channel: ResponseChannel<()>,
+            peer: PeerId,
+        },
+        AbortAcknowledged {
+            id: RequestId,
+        },
+        /// A peer with running swaps did not reconnect in time.
+        PeerUnreachable {
+            peer: PeerId,
+        },
         Rendezvous(libp2p::rendezvous::client::Event),
+        /// The peer told us the address it sees us on.
+        Identified {
+            peer: PeerId,
+            observed_addr: Multiaddr,
+        },
+        /// The peer started a swap setup that did not complete.
+        SwapSetupFailed {
+            peer: PeerId,
+            error: Error,
+        },
+        /// The peer sent a message it was not supposed to send.
+        ProtocolViolation {
+            peer: PeerId,
+            error: Error,
+        },
         Failure {
             peer: PeerId,
             error: Error,
@@ -84,21 +155,22 @@ pub mod behaviour {
 
     impl OutEvent {
         pub fn unexpected_request(peer: PeerId) -> OutEvent {
-            OutEvent::Failure {
+            OutEvent::ProtocolViolation {
                 peer,
                 error: anyhow!("Unexpected request received"),
             }
         }
 
         pub fn unexpected_response(peer: PeerId) -> OutEvent {
-            OutEvent::Failure {
+            OutEvent::ProtocolViolation {
                 peer,
                 error: anyhow!("Unexpected response received"),
             }
         }
     }
 
-    /// A `NetworkBehaviour` that represents an XMR/BTC swap node as Alice.
+    /// A `NetworkBehaviour` that represents an XMR/BTC swap node as Alice, and
+    /// as Bob for swaps in which it buys XMR.
     #[derive(NetworkBehaviour)]
     #[behaviour(out_event = "OutEvent", event_process = false)]
     #[allow(missing_debug_implementations)]
@@ -109,8 +181,17 @@ pub mod behaviour {
         pub rendezvous: libp2p::swarm::toggle::Toggle<rendezous::Behaviour>,
         pub quote: quote::Behaviour,
         pub swap_setup: alice::Behaviour<LR>,
+        pub sell_xmr_setup: sell_xmr::bob::Behaviour<LR>,
         pub transfer_proof: transfer_proof::Behaviour,
         pub encrypted_signature: encrypted_signature::Behaviour,
+        pub abort: abort::Behaviour,
+        pub connection_manager: connection_manager::Behaviour,
+        /// Only enabled for the reachability check, as it tells peers the
+        /// addresses we listen on.
+        pub identify: libp2p::swarm::toggle::Toggle<identify::Behaviour>,
+        /// Only enabled if relay nodes are configured, the ASB listens for
+        /// relayed connections through them.
+        relay: libp2p::swarm::toggle::Toggle<Relay>,
 
         /// Ping behaviour that ensures that the underlying network connection
         /// is still alive. If the ping fails a connection close event
@@ -120,39 +201,74 @@ pub mod behaviour {
 
     impl<LR> Behaviour<LR>
     where
-        LR: LatestRate + Send + 'static,
+        LR: LatestRate + Send + 'static + Clone,
     {
+        #[allow(clippy::too_many_arguments)]
         pub fn new(
             min_buy: bitcoin::Amount,
             max_buy: bitcoin::Amount,
             latest_rate: LR,
             resume_only: bool,
+            concurrency_limits: ConcurrencyLimits,
+            buy_xmr: bool,
+            bitcoin_wallet: Arc<crate::bitcoin::Wallet>,
             env_config: env::Config,
-            rendezvous_params: Option<(identity::Keypair, PeerId, Multiaddr, XmrBtcNamespace)>,
+            timelock_ranges: TimelockRanges,
+            confirmation_schedule: ConfirmationSchedule,
+            identity: identity::Keypair,
+            rendezvous_nodes: Vec<(PeerId, Multiaddr)>,
+            namespaces: Vec<RendezvousNamespace>,
+            reachability_check: bool,
+            relay: Option<Relay>,
         ) -> Self {
+            let identify = if reachability_check {
+                Some(identify::new(identity.public()))
+            } else {
+                None
+            };
+
+            let rendezvous = if rendezvous_nodes.is_empty() {
+                None
+            } else {
+                Some(rendezous::Behaviour::new(
+                    identity.clone(),
+                    rendezvous_nodes,
+                    namespaces,
+                    None, // use default ttl on rendezvous point
+                ))
+            };
+
             Self {
-                rendezvous: libp2p::swarm::toggle::Toggle::from(rendezvous_params.map(
-                    |(identity, rendezvous_peer_id, rendezvous_address, namespace)| {
-                        rendezous::Behaviour::new(
-                            identity,
-                            rendezvous_peer_id,
-                            rendezvous_address,
-                            namespace,
-                            None, // use default ttl on rendezvous point
-                        )
-                    },
-                )),
+                rendezvous: libp2p::swarm::toggle::Toggle::from(rendezvous),
                 quote: quote::asb(),
                 swap_setup: alice::Behaviour::new(
                     min_buy,
                     max_buy,
                     env_config,
+                    timelock_ranges,
+                    latest_rate.clone(),
+                    resume_only,
+                    concurrency_limits,
+                    identity,
+                ),
+                sell_xmr_setup: sell_xmr::bob::Behaviour::new(
+                    buy_xmr,
+                    min_buy,
+                    max_buy,
+                    env_config,
+                    confirmation_schedule,
+                    bitcoin_wallet,
                     latest_rate,
                     resume_only,
+                    concurrency_limits,
                 ),
-                transfer_proof: transfer_proof::alice(),
-                encrypted_signature: encrypted_signature::alice(),
-                ping: Ping::new(PingConfig::new().with_keep_alive(true)),
+                transfer_proof: transfer_proof::alice_and_bob(),
+                encrypted_signature: encrypted_signature::alice_and_bob(),
+                abort: abort::new(),
+                connection_manager: connection_manager::asb(),
+                identify: libp2p::swarm::toggle::Toggle::from(identify),
+                relay: libp2p::swarm::toggle::Toggle::from(relay),
+                ping: Ping::new(PingConfig::new()),
             }
         }
     }
@@ -163,6 +279,25 @@ pub mod behaviour {
         }
     }
 
+    /// The relay behaviour does not emit any events.
+    impl From<()> for OutEvent {
+        fn from(_: ()) -> Self {
+            OutEvent::Other
+        }
+    }
+
+    impl From<identify::OutEvent> for OutEvent {
+        fn from(event: identify::OutEvent) -> Self {
+            match event {
+                identify::OutEvent::Received { peer_id, info } => OutEvent::Identified {
+                    peer: peer_id,
+                    observed_addr: info.observed_addr,
+                },
+                _ => OutEvent::Other,
+            }
+        }
+    }
+
     impl From<libp2p::rendezvous::client::Event> for OutEvent {
         fn from(event: libp2p::rendezvous::client::Event) -> Self {
             OutEvent::Rendezvous(event)
@@ -192,39 +327,92 @@ pub mod rendezous {
 
     pub struct Behaviour {
         inner: libp2p::rendezvous::client::Behaviour,
-        rendezvous_point: Multiaddr,
-        rendezvous_peer_id: PeerId,
-        namespace: XmrBtcNamespace,
-        registration_status: RegistrationStatus,
+        rendezvous_nodes: Vec<RendezvousNode>,
+    }
+
+    /// A node running the rendezvous server protocol.
+    ///
+    /// We register in all namespaces at once, so the registrations of a node
+    /// share their status.
+    struct RendezvousNode {
+        address: Multiaddr,
         connection_status: ConnectionStatus,
+        peer_id: PeerId,
+        registration_status: RegistrationStatus,
         registration_ttl: Option<u64>,
+        namespaces: Vec<RendezvousNamespace>,
+    }
+
+    impl RendezvousNode {
+        fn new(
+            address: &Multiaddr,
+            peer_id: PeerId,
+            namespaces: Vec<RendezvousNamespace>,
+            registration_ttl: Option<u64>,
+        ) -> Self {
+            Self {
+                address: address.to_owned(),
+                connection_status: ConnectionStatus::Disconnected,
+                namespaces,
+                peer_id,
+                registration_status: RegistrationStatus::RegisterOnNextConnection,
+                registration_ttl,
+            }
+        }
+
+        fn set_connection(&mut self, status: ConnectionStatus) {
+            self.connection_status = status;
+        }
+
+        fn set_registration(&mut self, status: RegistrationStatus) {
+            self.registration_status = status;
+        }
     }
 
     impl Behaviour {
         pub fn new(
             identity: identity::Keypair,
-            rendezvous_peer_id: PeerId,
-            rendezvous_address: Multiaddr,
-            namespace: XmrBtcNamespace,
+            rendezvous_nodes: Vec<(PeerId, Multiaddr)>,
+            namespaces: Vec<RendezvousNamespace>,
             registration_ttl: Option<u64>,
         ) -> Self {
+            let rendezvous_nodes = rendezvous_nodes
+                .iter()
+                .map(|(peer_id, address)| {
+                    RendezvousNode::new(address, *peer_id, namespaces.clone(), registration_ttl)
+                })
+                .collect();
+
             Self {
                 inner: libp2p::rendezvous::client::Behaviour::new(identity),
-                rendezvous_point: rendezvous_address,
-                rendezvous_peer_id,
-                namespace,
-                registration_status: RegistrationStatus::RegisterOnNextConnection,
-                connection_status: ConnectionStatus::Disconnected,
-                registration_ttl,
+                rendezvous_nodes,
             }
         }
 
-        fn register(&mut self) {
-            self.inner.register(
-                self.namespace.into(),
-                self.rendezvous_peer_id,
-                self.registration_ttl,
-            );
+        /// Calls the rendezvous register method of the node at node_index in
+        /// the Vec of rendezvous nodes, once for every namespace
+        fn register(&mut self, node_index: usize) {
+            let node = &self.rendezvous_nodes[node_index];
+            for namespace in &node.namespaces {
+                self.inner
+                    .register(namespace.into(), node.peer_id, node.registration_ttl);
+            }
+        }
+
+        /// Removes the registrations at all rendezvous nodes and stops
+        /// registering again.
+        pub fn unregister(&mut self) {
+            for node in self.rendezvous_nodes.drain(..) {
+                for namespace in &node.namespaces {
+                    self.inner.unregister(namespace.into(), node.peer_id);
+                }
+            }
+        }
+
+        fn node_mut(&mut self, peer_id: &PeerId) -> Option<&mut RendezvousNode> {
+            self.rendezvous_nodes
+                .iter_mut()
+                .find(|node| &node.peer_id == peer_id)
         }
     }
 
@@ -238,21 +426,27 @@ pub mod rendezous {
         }
 
         fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
-            if peer_id == &self.rendezvous_peer_id {
-                return vec![self.rendezvous_point.clone()];
-            }
-
-            vec![]
+            self.rendezvous_nodes
+                .iter()
+                .filter(|node| &node.peer_id == peer_id)
+                .map(|node| node.address.clone())
+                .collect()
         }
 
         fn inject_connected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Connected;
+            let node_index = self
+                .rendezvous_nodes
+                .iter()
+                .position(|node| &node.peer_id == peer_id);
+
+            if let Some(node_index) = node_index {
+                let node = &mut self.rendezvous_nodes[node_index];
+                node.set_connection(ConnectionStatus::Connected);
 
-                match &self.registration_status {
+                match node.registration_status {
                     RegistrationStatus::RegisterOnNextConnection => {
-                        self.register();
-                        self.registration_status = RegistrationStatus::Pending;
+                        node.set_registration(RegistrationStatus::Pending);
+                        self.register(node_index);
                     }
                     RegistrationStatus::Registered { .. } => {}
                     RegistrationStatus::Pending => {}
@@ -261,8 +455,8 @@ pub mod rendezous {
         }
 
         fn inject_disconnected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Disconnected;
+            if let Some(node) = self.node_mut(peer_id) {
+                node.set_connection(ConnectionStatus::Disconnected);
             }
         }
 
@@ -281,8 +475,8 @@ pub mod rendezous {
             _handler: Self::ProtocolsHandler,
             _error: DialError,
         ) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Disconnected;
+            if let Some(node) = self.node_mut(peer_id) {
+                node.set_connection(ConnectionStatus::Disconnected);
             }
         }
 
@@ -292,59 +486,74 @@ pub mod rendezous {
             cx: &mut std::task::Context<'_>,
             params: &mut impl PollParameters,
         ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
-            match &mut self.registration_status {
-                RegistrationStatus::RegisterOnNextConnection => match self.connection_status {
-                    ConnectionStatus::Disconnected => {
-                        self.connection_status = ConnectionStatus::Dialling;
-
-                        return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                            peer_id: self.rendezvous_peer_id,
-                            condition: DialPeerCondition::Disconnected,
-                            handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                        });
-                    }
-                    ConnectionStatus::Dialling => {}
-                    ConnectionStatus::Connected => {
-                        self.registration_status = RegistrationStatus::Pending;
-                        self.register();
-                    }
-                },
-                RegistrationStatus::Registered { re_register_in } => {
-                    if let Poll::Ready(()) = re_register_in.poll_unpin(cx) {
-                        match self.connection_status {
-                            ConnectionStatus::Connected => {
-                                self.registration_status = RegistrationStatus::Pending;
-                                self.register();
-                            }
-                            ConnectionStatus::Disconnected => {
-                                self.registration_status =
-                                    RegistrationStatus::RegisterOnNextConnection;
-
-                                return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                                    peer_id: self.rendezvous_peer_id,
-                                    condition: DialPeerCondition::Disconnected,
-                                    handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                                });
+            for node_index in 0..self.rendezvous_nodes.len() {
+                let node = &mut self.rendezvous_nodes[node_index];
+                let peer_id = node.peer_id;
+
+                match &mut node.registration_status {
+                    RegistrationStatus::RegisterOnNextConnection => match node.connection_status {
+                        ConnectionStatus::Disconnected => {
+                            node.set_connection(ConnectionStatus::Dialling);
+
+                            return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                                peer_id,
+                                condition: DialPeerCondition::Disconnected,
+                                handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
+                            });
+                        }
+                        ConnectionStatus::Dialling => {}
+                        ConnectionStatus::Connected => {
+                            node.set_registration(RegistrationStatus::Pending);
+                            self.register(node_index);
+                        }
+                    },
+                    RegistrationStatus::Registered { re_register_in } => {
+                        if let Poll::Ready(()) = re_register_in.poll_unpin(cx) {
+                            match node.connection_status {
+                                ConnectionStatus::Connected => {
+                                    node.set_registration(RegistrationStatus::Pending);
+                                    self.register(node_index);
+                                }
+                                ConnectionStatus::Disconnected => {
+                                    node.set_registration(
+                                        RegistrationStatus::RegisterOnNextConnection,
+                                    );
+
+                                    return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                                        peer_id,
+                                        condition: DialPeerCondition::Disconnected,
+                                        handler: Self::ProtocolsHandler::new(Duration::from_secs(
+                                            30,
+                                        )),
+                                    });
+                                }
+                                ConnectionStatus::Dialling => {}
                             }
-                            ConnectionStatus::Dialling => {}
                         }
                     }
+                    RegistrationStatus::Pending => {}
                 }
-                RegistrationStatus::Pending => {}
             }
 
             let inner_poll = self.inner.poll(cx, params);
 
-            // reset the timer if we successfully registered
+            // reset the timer for the specific rendezvous node if we successfully
+            // registered
             if let Poll::Ready(NetworkBehaviourAction::GenerateEvent(
-                libp2p::rendezvous::client::Event::Registered { ttl, .. },
+                libp2p::rendezvous::client::Event::Registered {
+                    ttl,
+                    rendezvous_node,
+                    ..
+                },
             )) = &inner_poll
             {
-                let half_of_ttl = Duration::from_secs(*ttl) / 2;
+                if let Some(node) = self.node_mut(rendezvous_node) {
+                    let half_of_ttl = Duration::from_secs(*ttl) / 2;
 
-                self.registration_status = RegistrationStatus::Registered {
-                    re_register_in: Box::pin(tokio::time::sleep(half_of_ttl)),
-                };
+                    node.set_registration(RegistrationStatus::Registered {
+                        re_register_in: Box::pin(tokio::time::sleep(half_of_ttl)),
+                    });
+                }
             }
 
             inner_poll
@@ -358,6 +567,7 @@ pub mod rendezous {
         use futures::StreamExt;
         use libp2p::rendezvous;
         use libp2p::swarm::SwarmEvent;
+        use std::collections::HashMap;
 
         #[tokio::test]
         async fn given_no_initial_connection_when_constructed_asb_connects_and_registers_with_rendezvous_node(
@@ -370,9 +580,8 @@ pub mod rendezous {
             let mut asb = new_swarm(|_, identity| {
                 rendezous::Behaviour::new(
                     identity,
-                    *rendezvous_node.local_peer_id(),
-                    rendezvous_address,
-                    XmrBtcNamespace::Testnet,
+                    vec![(*rendezvous_node.local_peer_id(), rendezvous_address)],
+                    vec![RendezvousNamespace::testnet()],
                     None,
                 )
             });
@@ -399,6 +608,55 @@ pub mod rendezous {
                 .unwrap();
         }
 
+        #[tokio::test]
+        async fn asb_registers_in_all_namespaces() {
+            let mut rendezvous_node = new_swarm(|_, _| {
+                rendezvous::server::Behaviour::new(rendezvous::server::Config::default())
+            });
+            let rendezvous_address = rendezvous_node.listen_on_random_memory_address().await;
+            let namespaces = vec![
+                RendezvousNamespace::testnet(),
+                "xmr-btc-swap-stagenet".parse().unwrap(),
+            ];
+
+            let mut asb = new_swarm(|_, identity| {
+                rendezous::Behaviour::new(
+                    identity,
+                    vec![(*rendezvous_node.local_peer_id(), rendezvous_address)],
+                    namespaces.clone(),
+                    None,
+                )
+            });
+            asb.listen_on_random_memory_address().await; // this adds an external address
+
+            tokio::spawn(async move {
+                loop {
+                    rendezvous_node.next().await;
+                }
+            });
+            let asb_registered_everywhere = tokio::spawn(async move {
+                let mut pending = namespaces
+                    .iter()
+                    .map(libp2p::rendezvous::Namespace::from)
+                    .collect::<Vec<_>>();
+
+                while !pending.is_empty() {
+                    if let SwarmEvent::Behaviour(rendezvous::client::Event::Registered {
+                        namespace,
+                        ..
+                    }) = asb.select_next_some().await
+                    {
+                        pending.retain(|pending| pending != &namespace);
+                    }
+                }
+            });
+
+            tokio::time::timeout(Duration::from_secs(10), asb_registered_everywhere)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
         #[tokio::test]
         async fn asb_automatically_re_registers() {
             let mut rendezvous_node = new_swarm(|_, _| {
@@ -411,9 +669,8 @@ pub mod rendezous {
             let mut asb = new_swarm(|_, identity| {
                 rendezous::Behaviour::new(
                     identity,
-                    *rendezvous_node.local_peer_id(),
-                    rendezvous_address,
-                    XmrBtcNamespace::Testnet,
+                    vec![(*rendezvous_node.local_peer_id(), rendezvous_address)],
+                    vec![RendezvousNamespace::testnet()],
                     Some(5),
                 )
             });
@@ -445,5 +702,63 @@ pub mod rendezous {
                 .unwrap()
                 .unwrap();
         }
+
+        #[tokio::test]
+        async fn asb_registers_multiple() {
+            let registration_ttl = Some(10);
+            let mut rendezvous_nodes = Vec::new();
+            let mut registrations = HashMap::new();
+
+            // register with 5 rendezvous nodes
+            for _ in 0..5 {
+                let mut rendezvous = new_swarm(|_, _| {
+                    rendezvous::server::Behaviour::new(
+                        rendezvous::server::Config::default().with_min_ttl(2),
+                    )
+                });
+                let address = rendezvous.listen_on_random_memory_address().await;
+                let id = *rendezvous.local_peer_id();
+                registrations.insert(id, 0);
+                rendezvous_nodes.push((id, address));
+                tokio::spawn(async move {
+                    loop {
+                        rendezvous.next().await;
+                    }
+                });
+            }
+
+            let mut asb = new_swarm(|_, identity| {
+                rendezous::Behaviour::new(
+                    identity,
+                    rendezvous_nodes,
+                    vec![RendezvousNamespace::testnet()],
+                    registration_ttl,
+                )
+            });
+            asb.listen_on_random_memory_address().await; // this adds an external address
+
+            let handle = tokio::spawn(async move {
+                loop {
+                    if let SwarmEvent::Behaviour(rendezvous::client::Event::Registered {
+                        rendezvous_node,
+                        ..
+                    }) = asb.select_next_some().await
+                    {
+                        registrations
+                            .entry(rendezvous_node)
+                            .and_modify(|counter| *counter += 1);
+                    }
+
+                    if registrations.iter().all(|(_, &count)| count >= 4) {
+                        break;
+                    }
+                }
+            });
+
+            tokio::time::timeout(Duration::from_secs(30), handle)
+                .await
+                .unwrap()
+                .unwrap();
+        }
     }
 }
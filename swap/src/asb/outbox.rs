@@ -0,0 +1,160 @@
+//! Delivers the messages of a swap that the peer must acknowledge, even if the
+//! peer is offline when they are sent.
+//!
+//! A message stays in the outbox until the peer acknowledges it and is sent
+//! again on every new connection of the peer until then. The messages are
+//! also persisted in the database, so they survive a restart of the ASB.
+
+use crate::network::{encrypted_signature, transfer_proof};
+use libp2p::request_response::RequestId;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hash;
+use uuid::Uuid;
+
+/// A message of a swap that is persisted until the peer acknowledged it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum OutboundMessage {
+    TransferProof(transfer_proof::Request),
+    EncryptedSignature(encrypted_signature::Request),
+}
+
+impl OutboundMessage {
+    pub fn swap_id(&self) -> Uuid {
+        match self {
+            OutboundMessage::TransferProof(request) => request.swap_id,
+            OutboundMessage::EncryptedSignature(request) => request.swap_id,
+        }
+    }
+}
+
+/// The unacknowledged messages of one protocol, at most one per swap.
+///
+/// The `Id` identifies a request that was sent, it is only generic so the
+/// outbox can be tested without a swarm.
+#[derive(Debug)]
+pub struct Outbox<T, Id = RequestId> {
+    pending: HashMap<Uuid, Pending<T>>,
+    /// The swaps of the requests that were sent and not answered yet. A
+    /// message may have been sent several times, each of these requests
+    /// acknowledges it.
+    inflight: HashMap<Id, Uuid>,
+}
+
+#[derive(Debug)]
+struct Pending<T> {
+    peer: PeerId,
+    request: T,
+    /// Lets the swap know that the message was acknowledged, messages loaded
+    /// from the database have none until their swap sends them again.
+    responder: Option<bmrng::Responder<()>>,
+}
+
+impl<T, Id> Outbox<T, Id>
+where
+    T: Clone,
+    Id: Eq + Hash,
+{
+    /// Queues the message of the swap, replacing an earlier message of the
+    /// same swap.
+    pub fn insert(
+        &mut self,
+        swap_id: Uuid,
+        peer: PeerId,
+        request: T,
+        responder: Option<bmrng::Responder<()>>,
+    ) {
+        self.pending.insert(swap_id, Pending {
+            peer,
+            request,
+            responder,
+        });
+    }
+
+    /// The unacknowledged messages to the peer, including the ones that were
+    /// already sent.
+    pub fn to_send(&self, peer: PeerId) -> Vec<(Uuid, T)> {
+        self.pending
+            .iter()
+            .filter(|(_, pending)| pending.peer == peer)
+            .map(|(swap_id, pending)| (*swap_id, pending.request.clone()))
+            .collect()
+    }
+
+    /// Records that the message of the swap was sent with the request `id`.
+    pub fn sent(&mut self, id: Id, swap_id: Uuid) {
+        self.inflight.insert(id, swap_id);
+    }
+
+    /// Removes the message acknowledged with the response to request `id` and
+    /// lets its swap know, returns the swap of the message.
+    ///
+    /// Returns `None` if the message was already acknowledged.
+    pub fn acknowledged(&mut self, id: &Id) -> Option<Uuid> {
+        let swap_id = self.inflight.remove(id)?;
+        self.inflight.retain(|_, inflight| *inflight != swap_id);
+
+        let pending = self.pending.remove(&swap_id)?;
+        if let Some(responder) = pending.responder {
+            let _ = responder.respond(());
+        }
+
+        Some(swap_id)
+    }
+}
+
+impl<T, Id> Default for Outbox<T, Id> {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::default(),
+            inflight: HashMap::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_sent_until_acknowledged() {
+        let mut outbox = Outbox::<&str, u64>::default();
+        let peer = PeerId::random();
+        let swap_id = Uuid::new_v4();
+
+        outbox.insert(swap_id, peer, "proof", None);
+        assert_eq!(outbox.to_send(peer), vec![(swap_id, "proof")]);
+        assert!(outbox.to_send(PeerId::random()).is_empty());
+
+        // the first request got lost with the connection, the message is sent again
+        outbox.sent(1, swap_id);
+        assert_eq!(outbox.to_send(peer), vec![(swap_id, "proof")]);
+        outbox.sent(2, swap_id);
+
+        assert_eq!(outbox.acknowledged(&2), Some(swap_id));
+        assert!(outbox.to_send(peer).is_empty());
+        assert_eq!(outbox.acknowledged(&1), None);
+    }
+
+    #[tokio::test]
+    async fn swap_learns_about_acknowledgement() {
+        let mut outbox = Outbox::<&str, u64>::default();
+        let peer = PeerId::random();
+        let swap_id = Uuid::new_v4();
+        let (sender, mut receiver) = bmrng::channel::<(), ()>(1);
+
+        let swap = tokio::spawn(async move { sender.send_receive(()).await });
+        let ((), responder) = receiver.recv().await.unwrap();
+
+        // the message loaded from the database is replaced once the swap sends it again
+        outbox.insert(swap_id, peer, "persisted proof", None);
+        outbox.insert(swap_id, peer, "proof", Some(responder));
+        assert_eq!(outbox.to_send(peer), vec![(swap_id, "proof")]);
+
+        outbox.sent(1, swap_id);
+        assert_eq!(outbox.acknowledged(&1), Some(swap_id));
+
+        swap.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+//! Adjusts the spread of a swap to the circumstances it is requested in.
+//!
+//! The configured `ask_spread` is applied to every swap. A pricing strategy
+//! adds to it per swap, based on the XMR inventory left, the size of the swap
+//! and the volume of the recent swaps. The spread of every swap is logged
+//! together with what it was based on.
+
+use crate::{bitcoin, monero};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+/// The window in which the volume of the recent swaps is counted.
+pub const VOLUME_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What the spread of a swap is based on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricingInput {
+    /// The BTC the taker swaps.
+    pub btc: bitcoin::Amount,
+    /// The XMR that is not reserved for other swaps.
+    pub inventory: monero::Amount,
+    /// The BTC of the swaps set up within the [`VOLUME_WINDOW`].
+    pub recent_volume: bitcoin::Amount,
+}
+
+/// The spread chosen for a swap, including the configured one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub input: PricingInput,
+    pub spread: Decimal,
+}
+
+pub trait PricingStrategy: Debug + Send {
+    /// The spread added to the configured spread for a swap.
+    fn additional_spread(&self, input: &PricingInput) -> Decimal;
+}
+
+/// Applies the configured spread to every swap.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedSpread;
+
+impl PricingStrategy for FixedSpread {
+    fn additional_spread(&self, _: &PricingInput) -> Decimal {
+        Decimal::ZERO
+    }
+}
+
+/// Adds to the spread once a threshold is crossed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tier<A> {
+    pub threshold: A,
+    pub add_spread: Decimal,
+}
+
+/// Widens the spread in tiers.
+///
+/// Of every kind of tier the one adding the most counts, the spreads of the
+/// different kinds add up.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TieredPricing {
+    /// Apply once the inventory is below the threshold.
+    pub inventory: Vec<Tier<monero::Amount>>,
+    /// Apply to swaps of at least the threshold.
+    pub size: Vec<Tier<bitcoin::Amount>>,
+    /// Apply once the recent volume reached the threshold.
+    pub volume: Vec<Tier<bitcoin::Amount>>,
+}
+
+impl PricingStrategy for TieredPricing {
+    fn additional_spread(&self, input: &PricingInput) -> Decimal {
+        let inventory = max_spread(&self.inventory, |threshold| input.inventory < threshold);
+        let size = max_spread(&self.size, |threshold| input.btc >= threshold);
+        let volume = max_spread(&self.volume, |threshold| input.recent_volume >= threshold);
+
+        inventory + size + volume
+    }
+}
+
+fn max_spread<A: Copy>(tiers: &[Tier<A>], applies: impl Fn(A) -> bool) -> Decimal {
+    tiers
+        .iter()
+        .filter(|tier| applies(tier.threshold))
+        .map(|tier| tier.add_spread)
+        .max()
+        .unwrap_or(Decimal::ZERO)
+}
+
+/// The BTC of the swaps set up within the [`VOLUME_WINDOW`].
+///
+/// The volume is only kept in memory, it starts from zero when the ASB
+/// starts.
+#[derive(Debug, Default)]
+pub struct RecentVolume {
+    swaps: VecDeque<(Instant, bitcoin::Amount)>,
+}
+
+impl RecentVolume {
+    pub fn record(&mut self, btc: bitcoin::Amount) {
+        self.record_at(Instant::now(), btc)
+    }
+
+    pub fn total(&mut self) -> bitcoin::Amount {
+        self.total_at(Instant::now())
+    }
+
+    fn record_at(&mut self, now: Instant, btc: bitcoin::Amount) {
+        self.swaps.push_back((now, btc));
+    }
+
+    fn total_at(&mut self, now: Instant) -> bitcoin::Amount {
+        while let Some((set_up_at, _)) = self.swaps.front() {
+            if now.duration_since(*set_up_at) < VOLUME_WINDOW {
+                break;
+            }
+            self.swaps.pop_front();
+        }
+
+        self.swaps
+            .iter()
+            .fold(bitcoin::Amount::ZERO, |total, (_, btc)| total + *btc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_PERCENT: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+    const THREE_PERCENT: Decimal = Decimal::from_parts(3, 0, 0, false, 2);
+
+    fn input(btc: f64, inventory: f64, recent_volume: f64) -> PricingInput {
+        PricingInput {
+            btc: bitcoin::Amount::from_btc(btc).unwrap(),
+            inventory: monero::Amount::from_monero(inventory).unwrap(),
+            recent_volume: bitcoin::Amount::from_btc(recent_volume).unwrap(),
+        }
+    }
+
+    fn pricing() -> TieredPricing {
+        TieredPricing {
+            inventory: vec![
+                Tier {
+                    threshold: monero::Amount::from_monero(10.0).unwrap(),
+                    add_spread: ONE_PERCENT,
+                },
+                Tier {
+                    threshold: monero::Amount::from_monero(2.0).unwrap(),
+                    add_spread: THREE_PERCENT,
+                },
+            ],
+            size: vec![Tier {
+                threshold: bitcoin::Amount::from_btc(0.5).unwrap(),
+                add_spread: ONE_PERCENT,
+            }],
+            volume: vec![Tier {
+                threshold: bitcoin::Amount::from_btc(2.0).unwrap(),
+                add_spread: ONE_PERCENT,
+            }],
+        }
+    }
+
+    #[test]
+    fn spread_widens_as_inventory_depletes() {
+        let pricing = pricing();
+
+        assert_eq!(
+            pricing.additional_spread(&input(0.1, 20.0, 0.0)),
+            Decimal::ZERO
+        );
+        assert_eq!(
+            pricing.additional_spread(&input(0.1, 5.0, 0.0)),
+            ONE_PERCENT
+        );
+        assert_eq!(
+            pricing.additional_spread(&input(0.1, 1.0, 0.0)),
+            THREE_PERCENT
+        );
+    }
+
+    #[test]
+    fn spreads_of_different_tiers_add_up() {
+        let pricing = pricing();
+
+        assert_eq!(
+            pricing.additional_spread(&input(0.5, 1.0, 2.0)),
+            THREE_PERCENT + ONE_PERCENT + ONE_PERCENT
+        );
+    }
+
+    #[test]
+    fn fixed_spread_adds_nothing() {
+        assert_eq!(
+            FixedSpread.additional_spread(&input(1.0, 0.0, 10.0)),
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn volume_only_counts_swaps_within_window() {
+        let mut volume = RecentVolume::default();
+        let start = Instant::now();
+
+        volume.record_at(start, bitcoin::Amount::from_sat(1_000));
+        volume.record_at(
+            start + Duration::from_secs(60),
+            bitcoin::Amount::from_sat(2_000),
+        );
+
+        assert_eq!(
+            volume.total_at(start + Duration::from_secs(120)),
+            bitcoin::Amount::from_sat(3_000)
+        );
+        assert_eq!(
+            volume.total_at(start + VOLUME_WINDOW),
+            bitcoin::Amount::from_sat(2_000)
+        );
+        assert_eq!(
+            volume.total_at(start + VOLUME_WINDOW + Duration::from_secs(60)),
+            bitcoin::Amount::ZERO
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Punishes the swaps that miss the punish window because they do not run.
+//!
+//! A swap publishes the punish transaction itself once the punish timelock
+//! expired, but only as long as its task is running. A swap that failed, e.g.
+//! because the Electrum server was unreachable, is only resumed on the next
+//! start and the peer might refund in the meantime. The scheduler checks the
+//! swaps that no task is running shortly after the start and periodically
+//! thereafter. It punishes the ones whose punish timelock expired unless the
+//! peer refunded, and records the outcome in the database.
+
+use crate::asb::reputation::{Misbehaviour, Reputation};
+use crate::asb::EventLoopController;
+use crate::bitcoin::wallet::{ScriptStatus, Watchable};
+use crate::bitcoin::{self, PunishTimelock, Txid};
+use crate::monero::TransferProof;
+use crate::protocol::alice::{AliceState, State3};
+use crate::protocol::{Database, State};
+use anyhow::Result;
+use monero_rpc::wallet::BlockHeight;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How long after the start the swaps are checked for the first time, the
+/// swaps that are resumed on start are running by then.
+const STARTUP_DELAY: Duration = Duration::from_secs(60);
+
+/// How often the swaps are checked after the first time.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// What the scheduler found out about a swap that no task is running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The punish timelock did not expire yet.
+    NotPunishable,
+    /// The peer refunded, the Monero is refunded once the swap is resumed.
+    Refunded,
+    /// The punish transaction was published, by the scheduler or by the swap
+    /// before it stopped.
+    Punished(Txid),
+}
+
+pub struct PunishScheduler {
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+    reputation: Reputation,
+    /// The event loops of the current and the previous identities, the
+    /// scheduler leaves the swaps they are running to their task.
+    event_loops: Vec<EventLoopController>,
+}
+
+impl PunishScheduler {
+    pub fn new(
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        db: Arc<dyn Database + Send + Sync>,
+        reputation: Reputation,
+        event_loops: Vec<EventLoopController>,
+    ) -> Self {
+        Self {
+            bitcoin_wallet,
+            db,
+            reputation,
+            event_loops,
+        }
+    }
+
+    /// Checks the swaps on every interval, swaps that fail to be checked are
+    /// checked again on the next one.
+    pub async fn run(self) {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.check_all().await {
+                tracing::warn!("Failed to check swaps for missed punishes: {:#}", error);
+            }
+        }
+    }
+
+    async fn check_all(&self) -> Result<()> {
+        let mut running_swaps = HashSet::new();
+        for event_loop in &self.event_loops {
+            running_swaps.extend(event_loop.running_swaps().await?);
+        }
+
+        for (swap_id, state) in self.db.all().await? {
+            let state = match state {
+                State::Alice(state) => state,
+                State::Bob(_) => continue,
+            };
+            if running_swaps.contains(&swap_id) {
+                continue;
+            }
+            let (monero_wallet_restore_blockheight, transfer_proof, state3) =
+                match xmr_locked(state) {
+                    Some(parts) => parts,
+                    None => continue,
+                };
+
+            match self
+                .check(
+                    swap_id,
+                    monero_wallet_restore_blockheight,
+                    transfer_proof,
+                    *state3,
+                )
+                .await
+            {
+                Ok(Outcome::NotPunishable) => {}
+                Ok(Outcome::Refunded) => {
+                    tracing::warn!(%swap_id, "Peer refunded the Bitcoin while the swap was not running, the Monero is refunded once the swap is resumed")
+                }
+                Ok(Outcome::Punished(txid)) => {
+                    tracing::info!(%swap_id, %txid, "Punished swap that was not running")
+                }
+                Err(error) => {
+                    tracing::warn!(%swap_id, "Failed to check swap for a missed punish: {:#}", error)
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Punishes the swap after making sure on the blockchain that the peer did
+    /// not refund.
+    async fn check(
+        &self,
+        swap_id: Uuid,
+        monero_wallet_restore_blockheight: BlockHeight,
+        transfer_proof: TransferProof,
+        state3: State3,
+    ) -> Result<Outcome> {
+        let tx_cancel = state3.tx_cancel();
+        let tx_refund = state3.tx_refund();
+        let tx_punish = state3.tx_punish();
+
+        let action = next_action(
+            state3.punish_timelock,
+            self.bitcoin_wallet.status_of_script(&tx_cancel).await?,
+            self.bitcoin_wallet.status_of_script(&tx_refund).await?,
+            self.bitcoin_wallet.status_of_script(&tx_punish).await?,
+        );
+
+        let txid = match action {
+            Action::Wait => return Ok(Outcome::NotPunishable),
+            Action::RecordRefund => {
+                let published_refund_tx = state3.fetch_tx_refund(&self.bitcoin_wallet).await?;
+                let spend_key = state3.extract_monero_private_key(published_refund_tx)?;
+
+                let state = AliceState::BtcRefunded {
+                    monero_wallet_restore_blockheight,
+                    transfer_proof,
+                    spend_key,
+                    state3: Box::new(state3),
+                };
+                self.db.insert_latest_state(swap_id, state.into()).await?;
+
+                return Ok(Outcome::Refunded);
+            }
+            Action::RecordPunish => tx_punish.id(),
+            Action::Punish => {
+                tracing::info!(%swap_id, "Punish timelock expired while the swap was not running, punishing");
+
+                state3.punish_btc(&self.bitcoin_wallet).await?
+            }
+        };
+
+        self.db
+            .insert_latest_state(swap_id, AliceState::BtcPunished.into())
+            .await?;
+
+        let peer_id = self.db.get_peer_id(swap_id).await?;
+        if let Some(ban) = self
+            .reputation
+            .record(peer_id, Misbehaviour::Punished)
+            .await?
+        {
+            tracing::warn!(%peer_id, reason = %ban.reason, "Banning peer");
+        }
+
+        Ok(Outcome::Punished(txid))
+    }
+}
+
+/// The parts of a swap in which the Monero is locked, the ones in which a
+/// missed punish loses the Monero.
+fn xmr_locked(state: AliceState) -> Option<(BlockHeight, TransferProof, Box<State3>)> {
+    match state {
+        AliceState::XmrLockTransactionSent {
+            monero_wallet_restore_blockheight,
+            transfer_proof,
+            state3,
+        }
+        | AliceState::XmrLocked {
+            monero_wallet_restore_blockheight,
+            transfer_proof,
+            state3,
+        }
+        | AliceState::XmrLockTransferProofSent {
+            monero_wallet_restore_blockheight,
+            transfer_proof,
+            state3,
+        }
+        | AliceState::EncSigLearned {
+            monero_wallet_restore_blockheight,
+            transfer_proof,
+            state3,
+            ..
+        }
+        | AliceState::CancelTimelockExpired {
+            monero_wallet_restore_blockheight,
+            transfer_proof,
+            state3,
+        }
+        | AliceState::BtcCancelled {
+            monero_wallet_restore_blockheight,
+            transfer_proof,
+            state3,
+        }
+        | AliceState::BtcPunishable {
+            monero_wallet_restore_blockheight,
+            transfer_proof,
+            state3,
+        } => Some((monero_wallet_restore_blockheight, transfer_proof, state3)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Wait,
+    Punish,
+    RecordRefund,
+    RecordPunish,
+}
+
+/// Decides what to do about a swap based on the transactions that spend its
+/// cancel output.
+fn next_action(
+    punish_timelock: PunishTimelock,
+    tx_cancel: ScriptStatus,
+    tx_refund: ScriptStatus,
+    tx_punish: ScriptStatus,
+) -> Action {
+    // refund and punish spend the same output, only one of them can be seen
+    if tx_refund.has_been_seen() {
+        return Action::RecordRefund;
+    }
+    if tx_punish.has_been_seen() {
+        return Action::RecordPunish;
+    }
+    if tx_cancel.is_confirmed_with(punish_timelock) {
+        return Action::Punish;
+    }
+
+    Action::Wait
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUNISH_TIMELOCK: PunishTimelock = PunishTimelock::new(10);
+
+    #[test]
+    fn given_punish_timelock_expired_then_punish() {
+        let action = next_action(
+            PUNISH_TIMELOCK,
+            ScriptStatus::from_confirmations(10),
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+        );
+
+        assert_eq!(action, Action::Punish);
+    }
+
+    #[test]
+    fn given_punish_timelock_not_expired_then_wait() {
+        let not_cancelled = next_action(
+            PUNISH_TIMELOCK,
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+        );
+        let cancelled = next_action(
+            PUNISH_TIMELOCK,
+            ScriptStatus::from_confirmations(9),
+            ScriptStatus::Unseen,
+            ScriptStatus::Unseen,
+        );
+
+        assert_eq!(not_cancelled, Action::Wait);
+        assert_eq!(cancelled, Action::Wait);
+    }
+
+    #[test]
+    fn given_refund_in_mempool_then_never_punish() {
+        let action = next_action(
+            PUNISH_TIMELOCK,
+            ScriptStatus::from_confirmations(20),
+            ScriptStatus::InMempool,
+            ScriptStatus::Unseen,
+        );
+
+        assert_eq!(action, Action::RecordRefund);
+    }
+
+    #[test]
+    fn given_punish_already_published_then_only_record_it() {
+        let action = next_action(
+            PUNISH_TIMELOCK,
+            ScriptStatus::from_confirmations(20),
+            ScriptStatus::Unseen,
+            ScriptStatus::from_confirmations(1),
+        );
+
+        assert_eq!(action, Action::RecordPunish);
+    }
+}
@@ -25,6 +25,18 @@ impl Rate {
         Self { ask, ask_spread }
     }
 
+    /// The rate with the given spread added to its spread.
+    pub fn with_additional_spread(self, spread: Decimal) -> Self {
+        Self {
+            ask: self.ask,
+            ask_spread: self.ask_spread + spread,
+        }
+    }
+
+    pub fn ask_spread(&self) -> Decimal {
+        self.ask_spread
+    }
+
     /// Computes the asking price at which we are willing to sell 1 XMR.
     ///
     /// This applies the spread to the market asking price.
@@ -42,11 +54,42 @@ impl Rate {
         Ok(self.ask + additional_sats)
     }
 
+    /// Computes the price at which we are willing to buy 1 XMR.
+    ///
+    /// The price feeds only provide the market asking price, hence the spread
+    /// is subtracted from it.
+    pub fn bid(&self) -> Result<bitcoin::Amount> {
+        let sats = Decimal::from(self.ask.as_sat());
+
+        let bid_sats = sats - sats * self.ask_spread;
+        let bid_sats = bid_sats
+            .to_u64()
+            .context("Failed to fit bid price into u64")?;
+
+        Ok(bitcoin::Amount::from_sat(bid_sats))
+    }
+
     /// Calculate a sell quote for a given BTC amount.
     pub fn sell_quote(&self, quote: bitcoin::Amount) -> Result<monero::Amount> {
         Self::quote(self.ask()?, quote)
     }
 
+    /// Calculate the BTC we pay when buying the given XMR amount.
+    pub fn buy_quote(&self, xmr: monero::Amount) -> Result<bitcoin::Amount> {
+        let bid_in_sats = Decimal::from(self.bid()?.as_sat());
+
+        let xmr = xmr
+            .as_piconero_decimal()
+            .checked_div(Decimal::from(monero::Amount::ONE_XMR.as_piconero()))
+            .context("Division overflow")?;
+
+        let sats = (xmr * bid_in_sats)
+            .to_u64()
+            .context("Failed to fit satoshi amount into a u64")?;
+
+        Ok(bitcoin::Amount::from_sat(sats))
+    }
+
     fn quote(rate: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
         // quote (btc) = rate * base (xmr)
         // base = quote / rate
@@ -130,4 +173,35 @@ mod tests {
                                                          // it is really close
                                                          // to two percent
     }
+
+    #[test]
+    fn subtracts_spread_from_asking_price_for_bid() {
+        let asking_price = bitcoin::Amount::from_sat(100);
+        let rate = Rate::new(asking_price, TWO_PERCENT);
+
+        let amount = rate.bid().unwrap();
+
+        assert_eq!(amount.as_sat(), 98);
+    }
+
+    #[test]
+    fn additional_spread_adds_to_spread() {
+        let asking_price = bitcoin::Amount::from_sat(100);
+        let rate = Rate::new(asking_price, TWO_PERCENT).with_additional_spread(TWO_PERCENT);
+
+        assert_eq!(rate.ask_spread(), TWO_PERCENT + TWO_PERCENT);
+        assert_eq!(rate.ask().unwrap().as_sat(), 104);
+    }
+
+    #[test]
+    fn buy_quote() {
+        let asking_price = bitcoin::Amount::from_btc(0.002_500).unwrap();
+        let rate = Rate::new(asking_price, TWO_PERCENT);
+
+        let btc_amount = rate
+            .buy_quote(monero::Amount::from_monero(1000.0).unwrap())
+            .unwrap();
+
+        assert_eq!(btc_amount, bitcoin::Amount::from_btc(2.45).unwrap())
+    }
 }
@@ -0,0 +1,176 @@
+//! Checks which of the advertised addresses peers actually reach the ASB on.
+//!
+//! Peers that run the identify protocol tell the ASB the address they see it
+//! on. A peer that connected to an advertised address confirms that the
+//! address is reachable. A peer the ASB connected to, e.g. a rendezvous
+//! point, only confirms the IP address, as the ASB dials from another port.
+//!
+//! Peers can report any address, the result is a hint for the operator and not
+//! used to decide what is advertised.
+
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The number of observed addresses that are kept, so peers cannot grow the
+/// set without bounds.
+const MAX_OBSERVED_ADDRESSES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// A peer reached the ASB on the address.
+    Reachable,
+    /// Peers saw the ASB on the IP address of the address, but not on its port.
+    IpObserved,
+    /// No peer saw the ASB on the address yet.
+    Unconfirmed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AddressStatus {
+    pub address: Multiaddr,
+    pub status: Status,
+}
+
+#[derive(Debug, Default)]
+pub struct Reachability {
+    observed: HashSet<Multiaddr>,
+}
+
+impl Reachability {
+    /// Records the address a peer saw us on, returns whether it is new.
+    pub fn observe(&mut self, address: Multiaddr) -> bool {
+        if self.observed.len() >= MAX_OBSERVED_ADDRESSES {
+            return false;
+        }
+
+        self.observed.insert(address)
+    }
+
+    /// The status of every advertised address based on what peers observed.
+    pub fn report<'a>(
+        &self,
+        advertised: impl IntoIterator<Item = &'a Multiaddr>,
+    ) -> Vec<AddressStatus> {
+        advertised
+            .into_iter()
+            .map(|address| AddressStatus {
+                address: address.clone(),
+                status: self.status_of(address),
+            })
+            .collect()
+    }
+
+    fn status_of(&self, address: &Multiaddr) -> Status {
+        if self.observed.contains(address) {
+            return Status::Reachable;
+        }
+
+        let ip = ip_of(address);
+        if ip.is_some() && self.observed.iter().any(|observed| ip_of(observed) == ip) {
+            return Status::IpObserved;
+        }
+
+        Status::Unconfirmed
+    }
+}
+
+/// Whether the address is an IP address that is reachable from the internet,
+/// as opposed to e.g. a loopback or private address.
+pub fn is_global(address: &Multiaddr) -> bool {
+    match ip_of(address) {
+        Some(IpAddr::V4(ip)) => is_global_v4(ip),
+        Some(IpAddr::V6(ip)) => is_global_v6(ip),
+        None => false,
+    }
+}
+
+fn ip_of(address: &Multiaddr) -> Option<IpAddr> {
+    match address.iter().next()? {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    }
+}
+
+fn is_global_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    // 100.64.0.0/10 is used for carrier-grade NAT
+    let shared = first == 100 && (second & 0b1100_0000) == 64;
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || shared)
+}
+
+fn is_global_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    // fc00::/7 are unique local and fe80::/10 link-local addresses
+    let unique_local = (first & 0xfe00) == 0xfc00;
+    let link_local = (first & 0xffc0) == 0xfe80;
+
+    !(ip.is_unspecified() || ip.is_loopback() || unique_local || link_local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn addr(address: &str) -> Multiaddr {
+        Multiaddr::from_str(address).unwrap()
+    }
+
+    #[test]
+    fn only_public_ip_addresses_are_global() {
+        assert!(is_global(&addr("/ip4/203.0.114.7/tcp/9939")));
+        assert!(is_global(&addr("/ip6/2a01:4f8::1/tcp/9939")));
+
+        assert!(!is_global(&addr("/ip4/0.0.0.0/tcp/9939")));
+        assert!(!is_global(&addr("/ip4/127.0.0.1/tcp/9939")));
+        assert!(!is_global(&addr("/ip4/192.168.1.10/tcp/9939")));
+        assert!(!is_global(&addr("/ip4/100.64.0.1/tcp/9939")));
+        assert!(!is_global(&addr("/ip6/::/tcp/9939")));
+        assert!(!is_global(&addr("/ip6/::1/tcp/9939")));
+        assert!(!is_global(&addr("/ip6/fe80::1/tcp/9939")));
+        assert!(!is_global(&addr("/ip6/fd00::1/tcp/9939")));
+        assert!(!is_global(&addr("/dns4/example.com/tcp/9939")));
+    }
+
+    #[test]
+    fn observed_addresses_confirm_the_advertised_addresses() {
+        let reachable = addr("/ip4/203.0.114.7/tcp/9939");
+        let same_ip = addr("/ip4/203.0.114.7/tcp/9940/ws");
+        let unconfirmed = addr("/ip6/2a01:4f8::1/tcp/9939");
+
+        let mut reachability = Reachability::default();
+        assert!(reachability.observe(reachable.clone()));
+        assert!(!reachability.observe(reachable.clone()));
+        reachability.observe(addr("/ip4/203.0.114.7/tcp/51234"));
+
+        let report =
+            reachability.report(&[reachable.clone(), same_ip.clone(), unconfirmed.clone()]);
+
+        assert_eq!(report, vec![
+            AddressStatus {
+                address: reachable,
+                status: Status::Reachable
+            },
+            AddressStatus {
+                address: same_ip,
+                status: Status::IpObserved
+            },
+            AddressStatus {
+                address: unconfirmed,
+                status: Status::Unconfirmed
+            },
+        ]);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::bitcoin::{self};
 use crate::monero;
+use crate::protocol::alice::swap::refund_xmr;
 use crate::protocol::alice::AliceState;
 use crate::protocol::Database;
 use anyhow::{bail, Result};
@@ -71,15 +72,16 @@ pub async fn refund(
         bail!(Error::RefundTransactionNotPublishedYet(bob_peer_id),);
     };
 
-    state3
-        .refund_xmr(
-            &monero_wallet,
-            monero_wallet_restore_blockheight,
-            swap_id.to_string(),
-            spend_key,
-            transfer_proof,
-        )
-        .await?;
+    refund_xmr(
+        swap_id,
+        db.as_ref(),
+        &monero_wallet,
+        &state3,
+        monero_wallet_restore_blockheight,
+        spend_key,
+        transfer_proof,
+    )
+    .await?;
 
     let state = AliceState::XmrRefunded;
     db.insert_latest_state(swap_id, state.clone().into())
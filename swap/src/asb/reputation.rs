@@ -0,0 +1,214 @@
+//! Records the misbehaviour of peers and bans peers that misbehave
+//! repeatedly.
+//!
+//! Misbehaviours and bans are stored in the database, so they survive
+//! restarts and bans can be managed with `asb ban` and `asb unban` while the
+//! ASB is running. The event loop enforces the bans at the swarm level,
+//! connections of banned peers are closed and denied.
+
+use crate::protocol::Database;
+use anyhow::{bail, Result};
+use libp2p::PeerId;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+
+/// Behaviour of a peer that counts against its reputation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Misbehaviour {
+    /// The peer started a swap setup but did not complete it.
+    AbortedSwapSetup,
+    /// A swap with the peer ended with us punishing the peer.
+    Punished,
+    /// The peer sent a message it was not supposed to send.
+    ProtocolViolation,
+}
+
+impl Misbehaviour {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Misbehaviour::AbortedSwapSetup => "aborted_swap_setup",
+            Misbehaviour::Punished => "punished",
+            Misbehaviour::ProtocolViolation => "protocol_violation",
+        }
+    }
+}
+
+impl FromStr for Misbehaviour {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "aborted_swap_setup" => Ok(Misbehaviour::AbortedSwapSetup),
+            "punished" => Ok(Misbehaviour::Punished),
+            "protocol_violation" => Ok(Misbehaviour::ProtocolViolation),
+            _ => bail!("Unknown misbehaviour `{}`", s),
+        }
+    }
+}
+
+impl fmt::Display for Misbehaviour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Misbehaviour::AbortedSwapSetup => write!(f, "aborted swap setup"),
+            Misbehaviour::Punished => write!(f, "punished"),
+            Misbehaviour::ProtocolViolation => write!(f, "protocol violation"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ban {
+    pub peer_id: PeerId,
+    /// `None` if the ban lasts until the peer is unbanned.
+    pub until: Option<OffsetDateTime>,
+    pub reason: String,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        match self.until {
+            Some(until) => now < until,
+            None => true,
+        }
+    }
+}
+
+/// When to ban peers automatically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BanPolicy {
+    /// The number of misbehaviours within `window` at which a peer is banned.
+    pub max_misbehaviours: usize,
+    pub window: Duration,
+    pub ban_duration: Duration,
+}
+
+impl BanPolicy {
+    /// Returns the ban for a peer given its misbehaviours within the window,
+    /// if it reached the limit.
+    fn ban(&self, peer_id: PeerId, recent: &[Misbehaviour], now: OffsetDateTime) -> Option<Ban> {
+        if recent.len() < self.max_misbehaviours {
+            return None;
+        }
+
+        let last = recent.last()?;
+
+        Some(Ban {
+            peer_id,
+            until: Some(now + self.ban_duration),
+            reason: format!(
+                "{} misbehaviours within {} hours, last: {}",
+                recent.len(),
+                self.window.whole_hours(),
+                last
+            ),
+        })
+    }
+}
+
+/// Records misbehaviours and bans peers according to the ban policy.
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct Reputation {
+    db: Arc<dyn Database + Send + Sync>,
+    policy: Option<BanPolicy>,
+}
+
+impl Reputation {
+    /// Without a policy, misbehaviours are recorded but peers are only banned
+    /// manually.
+    pub fn new(db: Arc<dyn Database + Send + Sync>, policy: Option<BanPolicy>) -> Self {
+        Self { db, policy }
+    }
+
+    /// Records the misbehaviour of the peer and returns the ban if the peer
+    /// reached the limit of the ban policy.
+    pub async fn record(&self, peer_id: PeerId, misbehaviour: Misbehaviour) -> Result<Option<Ban>> {
+        let now = OffsetDateTime::now_utc();
+
+        tracing::debug!(%peer_id, %misbehaviour, "Recording misbehaviour of peer");
+        self.db
+            .insert_misbehaviour(peer_id, misbehaviour, now)
+            .await?;
+
+        let policy = match self.policy {
+            Some(policy) => policy,
+            None => return Ok(None),
+        };
+
+        let recent = self
+            .db
+            .get_misbehaviours_since(peer_id, now - policy.window)
+            .await?;
+
+        match policy.ban(peer_id, &recent, now) {
+            Some(ban) => {
+                self.db.insert_ban(ban.clone()).await?;
+
+                Ok(Some(ban))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: BanPolicy = BanPolicy {
+        max_misbehaviours: 3,
+        window: Duration::hours(24),
+        ban_duration: Duration::hours(48),
+    };
+
+    #[test]
+    fn given_fewer_misbehaviours_than_limit_then_not_banned() {
+        let recent = [
+            Misbehaviour::AbortedSwapSetup,
+            Misbehaviour::AbortedSwapSetup,
+        ];
+
+        assert_eq!(
+            POLICY.ban(PeerId::random(), &recent, OffsetDateTime::now_utc()),
+            None
+        );
+    }
+
+    #[test]
+    fn given_misbehaviours_reach_limit_then_banned_for_ban_duration() {
+        let peer_id = PeerId::random();
+        let now = OffsetDateTime::now_utc();
+        let recent = [
+            Misbehaviour::AbortedSwapSetup,
+            Misbehaviour::ProtocolViolation,
+            Misbehaviour::Punished,
+        ];
+
+        let ban = POLICY.ban(peer_id, &recent, now).unwrap();
+
+        assert_eq!(ban.peer_id, peer_id);
+        assert_eq!(ban.until, Some(now + Duration::hours(48)));
+        assert_eq!(
+            ban.reason,
+            "3 misbehaviours within 24 hours, last: punished"
+        );
+        assert!(ban.is_active(now + Duration::hours(47)));
+        assert!(!ban.is_active(now + Duration::hours(48)));
+    }
+
+    #[test]
+    fn misbehaviour_roundtrips_through_str() {
+        for misbehaviour in &[
+            Misbehaviour::AbortedSwapSetup,
+            Misbehaviour::Punished,
+            Misbehaviour::ProtocolViolation,
+        ] {
+            assert_eq!(
+                misbehaviour.as_str().parse::<Misbehaviour>().unwrap(),
+                *misbehaviour
+            );
+        }
+    }
+}
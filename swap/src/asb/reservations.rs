@@ -0,0 +1,157 @@
+//! Reserves the funds of swaps that were agreed upon but not locked yet.
+//!
+//! The wallets only account for the funds of a swap once they are locked. Until
+//! then they are reserved, so that concurrent swap setups don't agree to swap
+//! the same funds.
+//!
+//! Likewise a swap only counts towards the concurrency limits once it runs.
+//! Every swap setup reserves a slot until then, so that concurrent swap setups
+//! don't exceed the limits.
+
+use crate::network::swap_setup::alice::OngoingSwaps;
+use crate::{bitcoin, monero};
+use libp2p::PeerId;
+use std::sync::{Arc, Weak};
+
+#[derive(Debug, Default)]
+pub struct Reservations {
+    xmr: Vec<(monero::Amount, Weak<()>)>,
+    btc: Vec<(bitcoin::Amount, Weak<()>)>,
+    slots: Vec<(PeerId, Weak<()>)>,
+}
+
+/// Releases the reserved funds when dropped.
+#[derive(Debug)]
+pub struct Reservation(Arc<()>);
+
+impl Reservations {
+    pub fn reserve_xmr(&mut self, amount: monero::Amount) -> Reservation {
+        let reservation = Reservation(Arc::new(()));
+        self.xmr.push((amount, Arc::downgrade(&reservation.0)));
+
+        reservation
+    }
+
+    pub fn reserve_btc(&mut self, amount: bitcoin::Amount) -> Reservation {
+        let reservation = Reservation(Arc::new(()));
+        self.btc.push((amount, Arc::downgrade(&reservation.0)));
+
+        reservation
+    }
+
+    /// Reserves a slot for a swap with the peer that is being set up.
+    pub fn reserve_slot(&mut self, peer: PeerId) -> Reservation {
+        let reservation = Reservation(Arc::new(()));
+        self.slots.push((peer, Arc::downgrade(&reservation.0)));
+
+        reservation
+    }
+
+    /// The XMR of swaps that did not lock it yet.
+    pub fn reserved_xmr(&mut self) -> monero::Amount {
+        self.xmr
+            .retain(|(_, reservation)| reservation.strong_count() > 0);
+
+        self.xmr
+            .iter()
+            .fold(monero::Amount::ZERO, |sum, (amount, _)| sum + *amount)
+    }
+
+    /// The BTC of swaps in which we buy XMR that did not lock it yet.
+    pub fn reserved_btc(&mut self) -> bitcoin::Amount {
+        self.btc
+            .retain(|(_, reservation)| reservation.strong_count() > 0);
+
+        self.btc
+            .iter()
+            .fold(bitcoin::Amount::ZERO, |sum, (amount, _)| sum + *amount)
+    }
+
+    /// The swaps that are being set up, in total and with the given peer.
+    pub fn reserved_slots(&mut self, peer: PeerId) -> OngoingSwaps {
+        self.slots
+            .retain(|(_, reservation)| reservation.strong_count() > 0);
+
+        OngoingSwaps {
+            total: self.slots.len(),
+            with_peer: self
+                .slots
+                .iter()
+                .filter(|(slot_peer, _)| *slot_peer == peer)
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::swap_setup::alice::{ConcurrencyLimits, Error};
+
+    #[test]
+    fn reservations_add_up() {
+        let mut reservations = Reservations::default();
+
+        let _first = reservations.reserve_xmr(monero::Amount::from_piconero(1_000));
+        let _second = reservations.reserve_xmr(monero::Amount::from_piconero(500));
+        let _btc = reservations.reserve_btc(bitcoin::Amount::from_sat(100));
+
+        assert_eq!(
+            reservations.reserved_xmr(),
+            monero::Amount::from_piconero(1_500)
+        );
+        assert_eq!(reservations.reserved_btc(), bitcoin::Amount::from_sat(100));
+    }
+
+    #[test]
+    fn dropped_reservations_are_released() {
+        let mut reservations = Reservations::default();
+
+        let first = reservations.reserve_xmr(monero::Amount::from_piconero(1_000));
+        let _second = reservations.reserve_xmr(monero::Amount::from_piconero(500));
+        drop(first);
+
+        assert_eq!(
+            reservations.reserved_xmr(),
+            monero::Amount::from_piconero(500)
+        );
+    }
+
+    #[test]
+    fn concurrent_swap_setups_cannot_exceed_the_concurrency_limits() {
+        let limits = ConcurrencyLimits {
+            max_swaps: Some(3),
+            max_swaps_per_peer: Some(2),
+        };
+        let mut reservations = Reservations::default();
+        let peer = PeerId::random();
+
+        // Ten swap requests arrive before any of the swap setups completes, the event
+        // loop handles them one after the other.
+        let mut setups = Vec::new();
+        let mut declined = Vec::new();
+        for index in 0..10 {
+            let requesting_peer = if index < 5 { peer } else { PeerId::random() };
+
+            match limits.check(reservations.reserved_slots(requesting_peer)) {
+                Ok(()) => setups.push(reservations.reserve_slot(requesting_peer)),
+                Err(error) => declined.push(error),
+            }
+        }
+
+        assert_eq!(setups.len(), 3);
+        assert_eq!(declined.len(), 7);
+        assert!(declined[..3]
+            .iter()
+            .all(|error| matches!(error, Error::TooManySwapsWithPeer { max: 2 })));
+        assert!(declined[3..]
+            .iter()
+            .all(|error| matches!(error, Error::TooManySwaps { max: 3 })));
+
+        // a failed swap setup releases its slot
+        setups.pop();
+        assert!(limits
+            .check(reservations.reserved_slots(PeerId::random()))
+            .is_ok());
+    }
+}
@@ -0,0 +1,621 @@
+//! A JSON-RPC 2.0 server over HTTP for controlling a running ASB.
+//!
+//! Like the cookie authentication of bitcoind, the server writes a random
+//! token to a cookie file in the data directory on startup. Only requests that
+//! carry the token as bearer token are served. Requests have to be sent as
+//! `application/json` to a loopback host, so that web pages opened by the
+//! operator cannot send requests to the server without a CORS preflight or
+//! through DNS rebinding.
+
+use crate::asb::event_loop::{Control, EventLoopController};
+use crate::protocol::Database;
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context as _, Result};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, HOST, WWW_AUTHENTICATE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// The name of the file in the data directory the token of the RPC server is
+/// written to.
+pub const COOKIE_FILE_NAME: &str = ".rpc-cookie";
+
+/// Everything the RPC server needs to serve requests.
+#[allow(missing_debug_implementations)]
+pub struct Context {
+    pub bitcoin_wallet: Arc<bitcoin::Wallet>,
+    pub monero_wallet: Arc<monero::Wallet>,
+    pub db: Arc<dyn Database + Send + Sync>,
+    pub event_loop: EventLoopController,
+}
+
+/// Serves JSON-RPC requests on the given loopback address until the server
+/// fails.
+///
+/// A new token is written to the cookie file in the given data directory
+/// every time the server is started.
+pub async fn serve(address: SocketAddr, data_dir: &Path, context: Context) -> Result<()> {
+    if !address.ip().is_loopback() {
+        bail!(
+            "The RPC server can only be bound to a loopback address, got {}",
+            address
+        );
+    }
+
+    let cookie_file = data_dir.join(COOKIE_FILE_NAME);
+    let token = write_cookie(&cookie_file)
+        .with_context(|| format!("Failed to write RPC cookie to {}", cookie_file.display()))?;
+    let context = Arc::new(context);
+    let token = Arc::new(token);
+
+    let make_service = make_service_fn(move |_| {
+        let context = context.clone();
+        let token = token.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, context.clone(), token.clone())
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)
+        .with_context(|| format!("Failed to bind RPC server to {}", address))?
+        .serve(make_service);
+
+    tracing::info!(%address, cookie_file = %cookie_file.display(), "RPC server listening");
+
+    server.await.context("RPC server failed")?;
+
+    Ok(())
+}
+
+/// Generates a new token and writes it to the cookie file, readable by the
+/// owner only.
+fn write_cookie(cookie_file: &Path) -> Result<String> {
+    let token = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(cookie_file)?;
+    std::io::Write::write_all(&mut file, token.as_bytes())?;
+
+    Ok(token)
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    context: Arc<Context>,
+    token: Arc<String>,
+) -> Result<Response<Body>, Infallible> {
+    if let Err(status) = authorize(&request, &token) {
+        let mut response = Response::builder().status(status);
+        if status == StatusCode::UNAUTHORIZED {
+            response = response.header(WWW_AUTHENTICATE, "Bearer");
+        }
+
+        return Ok(response
+            .body(Body::empty())
+            .expect("static response to be valid"));
+    }
+
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(error) => {
+            let response = RpcResponse::error(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, format!("Failed to read body: {}", error)),
+            );
+
+            return Ok(response.into_http());
+        }
+    };
+
+    let response = match serde_json::from_slice::<RpcRequest>(&body) {
+        Ok(RpcRequest { id, method, params }) => match RpcMethod::parse(&method, params) {
+            Ok(method) => match method.execute(&context).await {
+                Ok(result) => RpcResponse::success(id, result),
+                Err(error) => {
+                    RpcResponse::error(id, RpcError::new(SERVER_ERROR, format!("{:#}", error)))
+                }
+            },
+            Err(error) => RpcResponse::error(id, error),
+        },
+        Err(error) => RpcResponse::error(
+            Value::Null,
+            RpcError::new(PARSE_ERROR, format!("Invalid JSON-RPC request: {}", error)),
+        ),
+    };
+
+    Ok(response.into_http())
+}
+
+/// Checks that the request is a JSON POST to a loopback host that carries the
+/// token of the server.
+fn authorize(request: &Request<Body>, token: &str) -> Result<(), StatusCode> {
+    if request.method() != Method::POST {
+        return Err(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let host = request
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .ok_or(StatusCode::FORBIDDEN)?;
+    if !is_loopback_host(host) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?;
+    let mime_type = content_type.split(';').next().unwrap_or_default().trim();
+    if !mime_type.eq_ignore_ascii_case("application/json") {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if !constant_time_eq(bearer.trim().as_bytes(), token.as_bytes()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(())
+}
+
+/// Whether the host of the `Host` header, with or without port, is
+/// `localhost` or a loopback IP address.
+fn is_loopback_host(host: &str) -> bool {
+    let host = match host.strip_prefix('[') {
+        // an IPv6 address, e.g. `[::1]:9944`
+        Some(rest) => match rest.split_once(']') {
+            Some((host, _)) => host,
+            None => return false,
+        },
+        None => host.split(':').next().unwrap_or_default(),
+    };
+
+    host.eq_ignore_ascii_case("localhost")
+        || IpAddr::from_str(host).map_or(false, |ip| ip.is_loopback())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+
+    fn into_http(self) -> Response<Body> {
+        let body = serde_json::to_vec(&self).expect("rpc response to be serializable");
+
+        Response::builder()
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .expect("rpc response to be valid")
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_params(error: impl std::fmt::Display) -> Self {
+        Self::new(INVALID_PARAMS, format!("Invalid params: {}", error))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum RpcMethod {
+    ListSwaps,
+    GetBalance,
+    GetStatus,
+    PauseQuotes,
+    ResumeQuotes,
+    EnableQuotesOnly,
+    DisableQuotesOnly,
+    SetLimits {
+        min_buy: bitcoin::Amount,
+        max_buy: bitcoin::Amount,
+    },
+    WithdrawBtc {
+        address: bitcoin::Address,
+        amount: Option<bitcoin::Amount>,
+    },
+    WithdrawXmr {
+        address: monero::Address,
+        amount: monero::Amount,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetLimitsParams {
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    min_buy_btc: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    max_buy_btc: bitcoin::Amount,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WithdrawBtcParams {
+    address: bitcoin::Address,
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_btc::opt")]
+    amount_btc: Option<bitcoin::Amount>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WithdrawXmrParams {
+    address: String,
+    amount_xmr: String,
+}
+
+impl RpcMethod {
+    fn parse(method: &str, params: Value) -> Result<Self, RpcError> {
+        let method = match method {
+            "list_swaps" => RpcMethod::ListSwaps,
+            "get_balance" => RpcMethod::GetBalance,
+            "get_status" => RpcMethod::GetStatus,
+            "pause_quotes" => RpcMethod::PauseQuotes,
+            "resume_quotes" => RpcMethod::ResumeQuotes,
+            "enable_quotes_only" => RpcMethod::EnableQuotesOnly,
+            "disable_quotes_only" => RpcMethod::DisableQuotesOnly,
+            "set_limits" => {
+                let SetLimitsParams {
+                    min_buy_btc,
+                    max_buy_btc,
+                } = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+
+                if min_buy_btc > max_buy_btc {
+                    return Err(RpcError::invalid_params(
+                        "min_buy_btc must not be greater than max_buy_btc",
+                    ));
+                }
+
+                RpcMethod::SetLimits {
+                    min_buy: min_buy_btc,
+                    max_buy: max_buy_btc,
+                }
+            }
+            "withdraw_btc" => {
+                let WithdrawBtcParams {
+                    address,
+                    amount_btc,
+                } = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+
+                RpcMethod::WithdrawBtc {
+                    address,
+                    amount: amount_btc,
+                }
+            }
+            "withdraw_xmr" => {
+                let WithdrawXmrParams {
+                    address,
+                    amount_xmr,
+                } = serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+
+                RpcMethod::WithdrawXmr {
+                    address: monero::Address::from_str(&address)
+                        .map_err(RpcError::invalid_params)?,
+                    amount: monero::Amount::parse_monero(&amount_xmr)
+                        .map_err(RpcError::invalid_params)?,
+                }
+            }
+            other => {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("Method not found: {}", other),
+                ))
+            }
+        };
+
+        Ok(method)
+    }
+
+    async fn execute(self, context: &Context) -> Result<Value> {
+        let result = match self {
+            RpcMethod::ListSwaps => {
+                let mut swaps = Vec::new();
+
+                for (swap_id, state) in context.db.all().await? {
+                    if state.swap_finished() {
+                        continue;
+                    }
+
+                    let peer_id = context
+                        .db
+                        .get_peer_id(swap_id)
+                        .await
+                        .ok()
+                        .map(|peer_id| peer_id.to_string());
+
+                    swaps.push(json!({
+                        "swap_id": swap_id,
+                        "peer_id": peer_id,
+                        "state": state.to_string(),
+                    }));
+                }
+
+                Value::from(swaps)
+            }
+            RpcMethod::GetBalance => {
+                context.bitcoin_wallet.sync().await?;
+
+                let bitcoin_balance = context.bitcoin_wallet.balance().await?;
+                let monero_balance = context.monero_wallet.get_balance().await?;
+
+                json!({
+                    "bitcoin": bitcoin_balance.as_btc(),
+                    "monero": monero_balance.to_string(),
+                })
+            }
+            RpcMethod::GetStatus => {
+                serde_json::to_value(context.event_loop.send(Control::Status).await?)?
+            }
+            RpcMethod::PauseQuotes => {
+                serde_json::to_value(context.event_loop.send(Control::PauseQuotes).await?)?
+            }
+            RpcMethod::ResumeQuotes => {
+                serde_json::to_value(context.event_loop.send(Control::ResumeQuotes).await?)?
+            }
+            RpcMethod::EnableQuotesOnly => {
+                serde_json::to_value(context.event_loop.send(Control::EnableQuotesOnly).await?)?
+            }
+            RpcMethod::DisableQuotesOnly => {
+                serde_json::to_value(context.event_loop.send(Control::DisableQuotesOnly).await?)?
+            }
+            RpcMethod::SetLimits { min_buy, max_buy } => serde_json::to_value(
+                context
+                    .event_loop
+                    .send(Control::SetLimits { min_buy, max_buy })
+                    .await?,
+            )?,
+            RpcMethod::WithdrawBtc { address, amount } => {
+                let txid = context.event_loop.withdraw_btc(address, amount).await?;
+
+                json!({ "txid": txid.to_string() })
+            }
+            RpcMethod::WithdrawXmr { address, amount } => {
+                let tx_hash = context.event_loop.withdraw_xmr(address, amount).await?;
+
+                json!({ "tx_hash": tx_hash.to_string() })
+            }
+        };
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "e3b0c44298fc1c149afbf4c8996fb924";
+
+    fn request(host: &str, content_type: &str, authorization: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .header(HOST, host)
+            .header(CONTENT_TYPE, content_type)
+            .header(AUTHORIZATION, authorization)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn accepts_json_request_to_loopback_host_with_token() {
+        let bearer = format!("Bearer {}", TOKEN);
+
+        for host in &[
+            "127.0.0.1:9944",
+            "localhost:9944",
+            "[::1]:9944",
+            "localhost",
+        ] {
+            assert_eq!(
+                authorize(&request(host, "application/json", &bearer), TOKEN),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            authorize(
+                &request("127.0.0.1", "application/json; charset=utf-8", &bearer),
+                TOKEN
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn given_wrong_or_missing_token_then_unauthorized() {
+        let wrong = request("127.0.0.1:9944", "application/json", "Bearer nope");
+        let basic = request("127.0.0.1:9944", "application/json", TOKEN);
+
+        assert_eq!(authorize(&wrong, TOKEN), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(authorize(&basic, TOKEN), Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn given_simple_request_of_a_web_page_then_unsupported_media_type() {
+        let bearer = format!("Bearer {}", TOKEN);
+
+        for content_type in &[
+            "text/plain",
+            "application/x-www-form-urlencoded",
+            "multipart/form-data",
+        ] {
+            assert_eq!(
+                authorize(&request("127.0.0.1:9944", content_type, &bearer), TOKEN),
+                Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            );
+        }
+    }
+
+    #[test]
+    fn given_non_loopback_host_then_forbidden() {
+        let bearer = format!("Bearer {}", TOKEN);
+
+        for host in &[
+            "attacker.example:9944",
+            "192.168.1.2:9944",
+            "[fe80::1]:9944",
+        ] {
+            assert_eq!(
+                authorize(&request(host, "application/json", &bearer), TOKEN),
+                Err(StatusCode::FORBIDDEN)
+            );
+        }
+    }
+
+    #[test]
+    fn given_get_request_then_method_not_allowed() {
+        let mut request = request("127.0.0.1:9944", "application/json", TOKEN);
+        *request.method_mut() = Method::GET;
+
+        assert_eq!(
+            authorize(&request, TOKEN),
+            Err(StatusCode::METHOD_NOT_ALLOWED)
+        );
+    }
+
+    #[test]
+    fn parses_methods_without_params() {
+        assert_eq!(
+            RpcMethod::parse("list_swaps", Value::Null).unwrap(),
+            RpcMethod::ListSwaps
+        );
+        assert_eq!(
+            RpcMethod::parse("pause_quotes", Value::Null).unwrap(),
+            RpcMethod::PauseQuotes
+        );
+        assert_eq!(
+            RpcMethod::parse("enable_quotes_only", Value::Null).unwrap(),
+            RpcMethod::EnableQuotesOnly
+        );
+    }
+
+    #[test]
+    fn given_unknown_method_then_method_not_found() {
+        let error = RpcMethod::parse("shutdown", Value::Null).unwrap_err();
+
+        assert_eq!(error.code, METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn parses_set_limits() {
+        let method = RpcMethod::parse(
+            "set_limits",
+            json!({ "min_buy_btc": 0.001, "max_buy_btc": 0.1 }),
+        )
+        .unwrap();
+
+        assert_eq!(method, RpcMethod::SetLimits {
+            min_buy: bitcoin::Amount::from_sat(100_000),
+            max_buy: bitcoin::Amount::from_sat(10_000_000),
+        });
+    }
+
+    #[test]
+    fn given_min_above_max_then_invalid_params() {
+        let error = RpcMethod::parse(
+            "set_limits",
+            json!({ "min_buy_btc": 0.2, "max_buy_btc": 0.1 }),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn given_missing_params_then_invalid_params() {
+        let error = RpcMethod::parse("withdraw_btc", Value::Null).unwrap_err();
+
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn error_response_omits_result() {
+        let response = RpcResponse::error(json!(1), RpcError::new(METHOD_NOT_FOUND, "nope"));
+
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": { "code": METHOD_NOT_FOUND, "message": "nope" }
+            })
+        );
+    }
+}
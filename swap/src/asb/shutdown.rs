@@ -0,0 +1,144 @@
+//! Shuts the ASB down without leaving takers hanging in the middle of a swap.
+//!
+//! Once a shutdown is requested, the swaps stop at their next safe checkpoint,
+//! that is between two state transitions or while they only wait for the
+//! blockchain or the peer. Their state is persisted at that point, so they are
+//! resumed on the next start. The ASB exits once all swaps stopped or the drain
+//! timeout passed, whichever comes first.
+
+use anyhow::Result;
+use std::future;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+/// Requests the shutdown and waits for the swaps to stop.
+#[derive(Debug)]
+pub struct ShutdownController {
+    requested: watch::Sender<bool>,
+    running_sender: mpsc::Sender<()>,
+    running_receiver: mpsc::Receiver<()>,
+}
+
+impl ShutdownController {
+    pub fn new() -> Self {
+        let (requested, _) = watch::channel(false);
+        let (running_sender, running_receiver) = mpsc::channel(1);
+
+        Self {
+            requested,
+            running_sender,
+            running_receiver,
+        }
+    }
+
+    /// A handle for a swap, the shutdown waits for all handles to be dropped.
+    pub fn shutdown(&self) -> Shutdown {
+        Shutdown {
+            requested: self.requested.subscribe(),
+            _running: self.running_sender.clone(),
+        }
+    }
+
+    /// Requests the shutdown and waits for the handles to be dropped, until
+    /// the timeout passes.
+    ///
+    /// Returns whether all handles were dropped in time.
+    pub async fn drain(self, timeout: Duration) -> bool {
+        let Self {
+            requested,
+            running_sender,
+            mut running_receiver,
+        } = self;
+
+        let _ = requested.send(true);
+        drop(running_sender);
+
+        // `recv` only returns once all senders are dropped, nothing is ever sent
+        tokio::time::timeout(timeout, running_receiver.recv())
+            .await
+            .is_ok()
+    }
+}
+
+impl Default for ShutdownController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The handle of a swap to learn about a requested shutdown.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    requested: watch::Receiver<bool>,
+    _running: mpsc::Sender<()>,
+}
+
+impl Shutdown {
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Completes once the shutdown is requested.
+    pub async fn requested(&mut self) {
+        while !self.is_requested() {
+            if self.requested.changed().await.is_err() {
+                // the controller is gone, the shutdown can no longer be requested
+                return future::pending().await;
+            }
+        }
+    }
+}
+
+/// Completes once the process is asked to terminate, with SIGTERM or SIGINT.
+#[cfg(unix)]
+pub async fn signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    tokio::select! {
+        _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+        _ = interrupt.recv() => tracing::info!("Received SIGINT"),
+    }
+
+    Ok(())
+}
+
+/// Completes once the process is asked to terminate, with Ctrl-C.
+#[cfg(not(unix))]
+pub async fn signal() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+    tracing::info!("Received Ctrl-C");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drain_completes_once_all_swaps_stopped() {
+        let controller = ShutdownController::new();
+        let mut shutdown = controller.shutdown();
+
+        let swap = tokio::spawn(async move {
+            shutdown.requested().await;
+            drop(shutdown);
+        });
+
+        assert!(controller.drain(Duration::from_secs(10)).await);
+        swap.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn drain_times_out_if_a_swap_does_not_stop() {
+        let controller = ShutdownController::new();
+        let shutdown = controller.shutdown();
+        assert!(!shutdown.is_requested());
+
+        assert!(!controller.drain(Duration::from_millis(10)).await);
+        assert!(shutdown.is_requested());
+    }
+}
@@ -0,0 +1,264 @@
+//! Sweeps the redeemed Bitcoin from the hot wallet to a cold wallet.
+//!
+//! The Bitcoin of every redeemed swap ends up in the internal wallet of the
+//! ASB. A sweep policy moves it to an address the operator controls once
+//! enough accumulated or regularly. Every sweep is recorded in the database.
+
+use crate::bitcoin::{self, Address, Amount, Txid};
+use crate::protocol::Database;
+use anyhow::{bail, Context, Result};
+use bdk::database::MemoryDatabase;
+use bdk::wallet::AddressIndex;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+
+/// How often the balance is checked against the sweep policy.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Where the Bitcoin is swept to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    Address(Address),
+    /// Every sweep goes to the next address of the descriptor.
+    Descriptor(String),
+}
+
+impl Destination {
+    /// The address of the sweep with the given index.
+    pub fn address(&self, index: u32, network: bitcoin::Network) -> Result<Address> {
+        match self {
+            Destination::Address(address) => Ok(address.clone()),
+            Destination::Descriptor(descriptor) => {
+                let wallet = bdk::Wallet::new_offline(
+                    descriptor.as_str(),
+                    None,
+                    network,
+                    MemoryDatabase::default(),
+                )
+                .context("Failed to parse sweep descriptor")?;
+                let address = wallet
+                    .get_address(AddressIndex::Peek(index))
+                    .context("Failed to derive sweep address from descriptor")?
+                    .address;
+
+                Ok(address)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub destination: Destination,
+    /// Sweep once the balance reaches this amount.
+    pub threshold: Option<Amount>,
+    /// Sweep if this much time passed since the last sweep.
+    pub interval: Option<Duration>,
+    /// The amount left in the hot wallet, e.g. to buy XMR with.
+    pub keep: Amount,
+}
+
+impl Policy {
+    pub fn is_due(
+        &self,
+        balance: Amount,
+        last_sweep: Option<OffsetDateTime>,
+        now: OffsetDateTime,
+    ) -> bool {
+        if balance <= self.keep {
+            return false;
+        }
+
+        let threshold_reached = self
+            .threshold
+            .map_or(false, |threshold| balance >= threshold);
+        let interval_passed = self.interval.map_or(false, |interval| {
+            last_sweep.map_or(true, |last_sweep| now - last_sweep >= interval)
+        });
+
+        threshold_reached || interval_passed
+    }
+
+    /// Checks that the Bitcoin is swept to addresses on the network of the
+    /// wallet.
+    pub fn validate(&self, network: bitcoin::Network) -> Result<()> {
+        let address = self.destination.address(0, network)?;
+
+        if address.network != network {
+            bail!(
+                "Sweep address {} is on network {} but the wallet is on network {}",
+                address,
+                address.network,
+                network
+            )
+        }
+
+        Ok(())
+    }
+}
+
+/// A sweep of the hot wallet, as recorded in the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    pub txid: Txid,
+    pub address: Address,
+    pub amount: Amount,
+    pub swept_at: OffsetDateTime,
+}
+
+pub struct Sweeper {
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+    policy: Policy,
+}
+
+impl Sweeper {
+    pub fn new(
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        db: Arc<dyn Database + Send + Sync>,
+        policy: Policy,
+    ) -> Self {
+        Self {
+            bitcoin_wallet,
+            db,
+            policy,
+        }
+    }
+
+    /// Sweeps whenever the policy is due, failed sweeps are retried on the
+    /// next check.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            match self.sweep_if_due().await {
+                Ok(Some(sweep)) => {
+                    tracing::info!(txid = %sweep.txid, address = %sweep.address, amount = %sweep.amount, "Swept Bitcoin to cold wallet")
+                }
+                Ok(None) => {}
+                Err(error) => tracing::warn!("Failed to sweep Bitcoin: {:#}", error),
+            }
+        }
+    }
+
+    async fn sweep_if_due(&self) -> Result<Option<Sweep>> {
+        self.bitcoin_wallet.sync().await?;
+
+        let balance = self.bitcoin_wallet.balance().await?;
+        let sweeps = self.db.get_sweeps().await?;
+        let last_sweep = sweeps.last().map(|sweep| sweep.swept_at);
+
+        if !self
+            .policy
+            .is_due(balance, last_sweep, OffsetDateTime::now_utc())
+        {
+            return Ok(None);
+        }
+
+        let index = u32::try_from(sweeps.len())?;
+
+        self.sweep(index).await
+    }
+
+    async fn sweep(&self, index: u32) -> Result<Option<Sweep>> {
+        let address = self
+            .policy
+            .destination
+            .address(index, self.bitcoin_wallet.get_network())?;
+
+        let max_giveable = self
+            .bitcoin_wallet
+            .max_giveable(address.script_pubkey().len())
+            .await?;
+        let amount = match max_giveable.checked_sub(self.policy.keep) {
+            Some(amount) if amount > Amount::ZERO => amount,
+            _ => return Ok(None),
+        };
+
+        let psbt = self
+            .bitcoin_wallet
+            .send_to_address(address.clone(), amount, None)
+            .await?;
+        let transaction = self.bitcoin_wallet.sign_and_finalize(psbt).await?;
+        let (txid, _) = self.bitcoin_wallet.broadcast(transaction, "sweep").await?;
+
+        let sweep = Sweep {
+            txid,
+            address,
+            amount,
+            swept_at: OffsetDateTime::now_utc(),
+        };
+        self.db.insert_sweep(sweep.clone()).await?;
+
+        Ok(Some(sweep))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn policy(threshold: Option<u64>, interval_hours: Option<i64>) -> Policy {
+        Policy {
+            destination: Destination::Address(
+                Address::from_str("bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw").unwrap(),
+            ),
+            threshold: threshold.map(Amount::from_sat),
+            interval: interval_hours.map(Duration::hours),
+            keep: Amount::from_sat(10_000),
+        }
+    }
+
+    #[test]
+    fn sweep_is_due_once_threshold_is_reached() {
+        let now = OffsetDateTime::now_utc();
+        let policy = policy(Some(1_000_000), None);
+
+        assert!(!policy.is_due(Amount::from_sat(999_999), None, now));
+        assert!(policy.is_due(Amount::from_sat(1_000_000), Some(now), now));
+    }
+
+    #[test]
+    fn sweep_is_due_once_interval_passed_since_last_sweep() {
+        let now = OffsetDateTime::now_utc();
+        let policy = policy(None, Some(24));
+
+        assert!(policy.is_due(Amount::from_sat(20_000), None, now));
+        assert!(!policy.is_due(
+            Amount::from_sat(20_000),
+            Some(now - Duration::hours(23)),
+            now
+        ));
+        assert!(policy.is_due(
+            Amount::from_sat(20_000),
+            Some(now - Duration::hours(24)),
+            now
+        ));
+    }
+
+    #[test]
+    fn sweep_is_not_due_if_only_kept_amount_is_left() {
+        let now = OffsetDateTime::now_utc();
+        let policy = policy(Some(0), Some(0));
+
+        assert!(!policy.is_due(Amount::from_sat(10_000), None, now));
+    }
+
+    #[test]
+    fn every_sweep_goes_to_the_next_address_of_the_descriptor() {
+        let destination = Destination::Descriptor("wpkh(tpubD6NzVbkrYhZ4Y2T5diX3fWTxxTY8ahSRd6LUm3zPLzxE7HvrBk6f5m3WPctwyXixoUpyZFKP1JgPZRw3Seqi6gok8MCZ73VZwJC8ZepLvJs/0/*)".to_string());
+
+        let first = destination.address(0, bitcoin::Network::Regtest).unwrap();
+        let second = destination.address(1, bitcoin::Network::Regtest).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(
+            first,
+            destination.address(0, bitcoin::Network::Regtest).unwrap()
+        );
+    }
+}
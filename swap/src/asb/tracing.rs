@@ -1,30 +1,39 @@
+use crate::logging::{LogFormat, SwapLogFiles};
 use anyhow::Result;
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::fmt::time::ChronoLocal;
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Registry};
+
+/// Logs to stderr and every swap to its own file, once the directory of these
+/// files is set on the returned [`SwapLogFiles`].
+pub fn init(level: LevelFilter, format: LogFormat, timestamp: bool) -> Result<SwapLogFiles> {
+    let swap_logs = SwapLogFiles::new(format);
 
-pub fn init(level: LevelFilter, json_format: bool, timestamp: bool) -> Result<()> {
     if level == LevelFilter::OFF {
-        return Ok(());
+        return Ok(swap_logs);
     }
 
     let is_terminal = atty::is(atty::Stream::Stderr);
 
-    let builder = FmtSubscriber::builder()
-        .with_env_filter(format!("asb={},swap={}", level, level))
+    let registry = Registry::default()
+        .with(EnvFilter::try_new(format!("asb={},swap={}", level, level))?)
+        .with(swap_logs.clone());
+    let layer = fmt::layer()
         .with_writer(std::io::stderr)
         .with_ansi(is_terminal)
         .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
         .with_target(false);
 
-    match (json_format, timestamp) {
-        (true, true) => builder.json().init(),
-        (true, false) => builder.json().without_time().init(),
-        (false, true) => builder.init(),
-        (false, false) => builder.without_time().init(),
+    match (format, timestamp) {
+        (LogFormat::Json, true) => registry.with(layer.json()).try_init()?,
+        (LogFormat::Json, false) => registry.with(layer.json().without_time()).try_init()?,
+        (LogFormat::Text, true) => registry.with(layer).try_init()?,
+        (LogFormat::Text, false) => registry.with(layer.without_time()).try_init()?,
     }
 
-    tracing::info!(%level, "Initialized tracing");
+    tracing::info!(%level, %format, "Initialized tracing");
 
-    Ok(())
+    Ok(swap_logs)
 }
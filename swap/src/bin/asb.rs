@@ -17,27 +17,43 @@ use comfy_table::Table;
 use libp2p::core::multiaddr::Protocol;
 use libp2p::core::Multiaddr;
 use libp2p::swarm::AddressScore;
-use libp2p::Swarm;
-use std::convert::TryInto;
+use libp2p::{PeerId, Swarm};
+use std::convert::TryFrom;
 use std::env;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::clap;
 use structopt::clap::ErrorKind;
-use swap::asb::command::{parse_args, Arguments, Command};
+use swap::asb::accounting::{self, SwapReport};
+use swap::asb::command::{parse_args, Arguments, Command, PASSPHRASE_ENV_VAR};
 use swap::asb::config::{
     initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
 };
-use swap::asb::{cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate};
-use swap::database::open_db;
+use swap::asb::identity::{self, IdentityRotation, Retirement};
+use swap::asb::punish_scheduler::PunishScheduler;
+use swap::asb::reputation::{Ban, Misbehaviour, Reputation};
+use swap::asb::shutdown::{self, Shutdown, ShutdownController};
+use swap::asb::sweep::Sweeper;
+use swap::asb::{
+    cancel, metrics, punish, redeem, refund, rpc, safely_abort, Control, EventLoop, Finality,
+    PriceFeedRate,
+};
+use swap::database::{open_db, sled_import};
 use swap::monero::Amount;
-use swap::network::rendezvous::XmrBtcNamespace;
 use swap::network::swarm;
-use swap::protocol::alice::{run, AliceState};
+use swap::notifications::Notifier;
+use swap::price_feed::PriceFeed;
+use swap::protocol::alice::{
+    run_until_shutdown, AliceState, LocalRedeemSigner, RedeemSigner, Swap,
+};
+use swap::protocol::Database;
 use swap::seed::Seed;
 use swap::tor::AuthenticatedClient;
-use swap::{asb, bitcoin, kraken, monero, tor};
+use swap::{asb, binance, bitcoin, encryption, kraken, monero, monitor, price_feed, tor};
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
 use tracing_subscriber::filter::LevelFilter;
+use uuid::Uuid;
 
 const DEFAULT_WALLET_NAME: &str = "asb-wallet";
 
@@ -45,8 +61,9 @@ const DEFAULT_WALLET_NAME: &str = "asb-wallet";
 async fn main() -> Result<()> {
     let Arguments {
         testnet,
-        json,
+        log_format,
         disable_timestamp,
+        passphrase,
         config_path,
         env_config,
         cmd,
@@ -68,7 +85,8 @@ async fn main() -> Result<()> {
         }
     };
 
-    asb::tracing::init(LevelFilter::DEBUG, json, !disable_timestamp).expect("initialize tracing");
+    let swap_logs = asb::tracing::init(LevelFilter::DEBUG, log_format, !disable_timestamp)
+        .expect("initialize tracing");
 
     let config = match read_config(config_path.clone())? {
         Ok(config) => config,
@@ -91,13 +109,40 @@ async fn main() -> Result<()> {
         ));
     }
 
-    let db = open_db(config.data.dir.join("sqlite")).await?;
+    swap_logs.write_to(config.data.dir.join("logs"));
+
+    let cipher = encryption::unlock(&config.data.dir, passphrase, PASSPHRASE_ENV_VAR)?;
 
-    let seed =
-        Seed::from_file_or_generate(&config.data.dir).expect("Could not retrieve/initialize seed");
+    let db = open_db(config.data.dir.join("sqlite"), cipher.clone()).await?;
+
+    let seed = Seed::from_file_or_generate(&config.data.dir, cipher.as_ref())
+        .expect("Could not retrieve/initialize seed");
 
     match cmd {
-        Command::Start { resume_only } => {
+        Command::Start {
+            resume_only,
+            rpc_bind_address,
+            monitor_bind_address,
+            metrics_bind_address,
+            drain_timeout,
+        } => {
+            let rotations = db.get_identity_rotations().await?;
+            let identity_generation = identity::current_generation(&rotations);
+            let previous_identities = identity::previous_identities(
+                &rotations,
+                &unfinished_swap_identities(db.as_ref()).await?,
+                OffsetDateTime::now_utc(),
+            );
+            for previous in &previous_identities {
+                if let Some(listen) = previous
+                    .listen
+                    .iter()
+                    .find(|listen| config.network.listen.contains(listen))
+                {
+                    bail!("The previous identity of generation {} still listens on {}, change `network.listen` in the config so the current identity listens on other addresses", previous.generation, listen)
+                }
+            }
+
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
@@ -105,6 +150,24 @@ async fn main() -> Result<()> {
             let bitcoin_balance = bitcoin_wallet.balance().await?;
             tracing::info!(%bitcoin_balance, "Initialized Bitcoin wallet");
 
+            let sweep_policy = config
+                .bitcoin
+                .sweep
+                .as_ref()
+                .map(|sweep| sweep.policy())
+                .transpose()?;
+            if let Some(policy) = &sweep_policy {
+                policy.validate(bitcoin_wallet.get_network())?;
+            }
+
+            let redeem_signer: Arc<dyn RedeemSigner> = match &config.bitcoin.redeem_signer {
+                Some(redeem_signer) => {
+                    tracing::info!(url = %redeem_signer.url, "Signing redeem transactions with external signer");
+                    Arc::new(redeem_signer.signer()?)
+                }
+                None => Arc::new(LocalRedeemSigner),
+            };
+
             let monero_balance = monero_wallet.get_balance().await?;
             if monero_balance == Amount::ZERO {
                 let monero_address = monero_wallet.get_main_address();
@@ -116,53 +179,87 @@ async fn main() -> Result<()> {
                 tracing::info!(%monero_balance, "Initialized Monero wallet");
             }
 
-            let kraken_price_updates = kraken::connect(config.maker.price_ticker_ws_url.clone())?;
+            let price_updates =
+                price_feed::connect(init_price_feeds(&config), config.maker.rate_freshness())?;
 
             // setup Tor hidden services
             let tor_client =
                 tor::Client::new(config.tor.socks5_port).with_control_port(config.tor.control_port);
-            let _ac = match tor_client.assert_tor_running().await {
+            let (_ac, onion_addresses) = match tor_client.assert_tor_running().await {
                 Ok(_) => {
                     tracing::info!("Setting up Tor hidden service");
-                    let ac =
+                    let (ac, onion_addresses) =
                         register_tor_services(config.network.clone().listen, tor_client, &seed)
                             .await?;
-                    Some(ac)
+                    (Some(ac), onion_addresses)
                 }
                 Err(_) => {
                     tracing::warn!("Tor not found. Running on clear net");
-                    None
+                    (None, vec![])
                 }
             };
 
-            let kraken_rate = KrakenRate::new(config.maker.ask_spread, kraken_price_updates);
-            let mut swarm = swarm::asb(
-                &seed,
-                config.maker.min_buy_btc,
-                config.maker.max_buy_btc,
-                kraken_rate.clone(),
-                resume_only,
-                env_config,
-                config.network.rendezvous_point.map(|rendezvous_point| {
-                    (
-                        rendezvous_point,
-                        if testnet {
-                            XmrBtcNamespace::Testnet
-                        } else {
-                            XmrBtcNamespace::Mainnet
-                        },
+            let rate = PriceFeedRate::new(config.maker.ask_spread, price_updates);
+            let namespaces = config.network.rendezvous_namespaces(testnet)?;
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let confirmation_schedule = config.monero.confirmation_schedule(env_config)?;
+            let new_swarm =
+                |identity_generation, resume_only, rendezvous_points, relay_nodes: &[Multiaddr]| {
+                    swarm::asb(
+                        &seed,
+                        identity_generation,
+                        config.maker.min_buy_btc,
+                        config.maker.max_buy_btc,
+                        rate.clone(),
+                        resume_only,
+                        config.maker.concurrency_limits(),
+                        config.maker.buy_xmr,
+                        bitcoin_wallet.clone(),
+                        env_config,
+                        config.bitcoin.timelock_ranges(env_config),
+                        confirmation_schedule.clone(),
+                        namespaces.clone(),
+                        rendezvous_points,
+                        config.network.reachability_check,
+                        relay_nodes,
                     )
-                }),
+                };
+            let mut swarm = new_swarm(
+                identity_generation,
+                resume_only,
+                config.network.rendezvous_point.clone(),
+                &config.network.relay_nodes,
             )?;
 
             for listen in config.network.listen.clone() {
                 Swarm::listen_on(&mut swarm, listen.clone())
                     .with_context(|| format!("Failed to listen on network interface {}", listen))?;
             }
+            // Once connected to a relay node, the event loop advertises the
+            // address takers reach us on through it.
+            for relay_node in &config.network.relay_nodes {
+                Swarm::listen_on(&mut swarm, relay_node.clone().with(Protocol::P2pCircuit))
+                    .with_context(|| {
+                        format!("Failed to listen through relay node {}", relay_node)
+                    })?;
+            }
 
-            tracing::info!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
-
-            for external_address in config.network.external_addresses {
+            tracing::info!(peer_id = %swarm.local_peer_id(), %identity_generation, "Network layer initialized");
+
+            // Without configured external addresses or a hidden service, the listen
+            // addresses that are reachable from the internet are announced instead.
+            let advertise_listen_addresses =
+                config.network.external_addresses.is_empty() && onion_addresses.is_empty();
+
+            // The onion addresses are announced like any other external
+            // address, hence they are also registered at the rendezvous nodes.
+            for external_address in config
+                .network
+                .external_addresses
+                .clone()
+                .into_iter()
+                .chain(onion_addresses)
+            {
                 let _ = Swarm::add_external_address(
                     &mut swarm,
                     external_address,
@@ -170,49 +267,277 @@ async fn main() -> Result<()> {
                 );
             }
 
+            let monero_wallet = Arc::new(monero_wallet);
+
             let (event_loop, mut swap_receiver) = EventLoop::new(
                 swarm,
                 env_config,
-                Arc::new(bitcoin_wallet),
-                Arc::new(monero_wallet),
-                db,
-                kraken_rate.clone(),
+                bitcoin_wallet.clone(),
+                monero_wallet.clone(),
+                db.clone(),
+                rate.clone(),
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
+                config.maker.pricing_strategy()?,
+                config.maker.ban_policy(),
+                identity_generation,
             )
             .unwrap();
+            let event_loop = event_loop
+                .with_redeem_signer(redeem_signer.clone())
+                .with_quotes_only(config.maker.quotes_only)
+                .with_advertised_listen_addresses(advertise_listen_addresses);
+            if config.maker.quotes_only {
+                tracing::info!("Only serving quotes, new swaps are declined");
+            }
 
-            tokio::spawn(async move {
-                while let Some(swap) = swap_receiver.recv().await {
-                    let rate = kraken_rate.clone();
+            // The previous identities run next to the current one until they
+            // are retired, each with a swarm and event loop of its own.
+            let mut previous_event_loops = Vec::new();
+            for previous in previous_identities {
+                let (resume_only, rendezvous_points) = match previous.retirement {
+                    Retirement::Overlapping { .. } => {
+                        (resume_only, config.network.rendezvous_point.clone())
+                    }
+                    Retirement::Draining => (true, vec![]),
+                };
+                let mut swarm =
+                    new_swarm(previous.generation, resume_only, rendezvous_points, &[])?;
+
+                for listen in previous.listen {
+                    Swarm::listen_on(&mut swarm, listen.clone()).with_context(|| {
+                        format!("Failed to listen on network interface {}", listen)
+                    })?;
+                }
+                let advertise_listen_addresses =
+                    advertise_listen_addresses && previous.external_addresses.is_empty();
+                for external_address in previous.external_addresses {
+                    let _ = Swarm::add_external_address(
+                        &mut swarm,
+                        external_address,
+                        AddressScore::Infinite,
+                    );
+                }
+
+                tracing::info!(peer_id = %swarm.local_peer_id(), identity_generation = %previous.generation, retirement = ?previous.retirement, "Network layer of previous identity initialized");
+
+                let (event_loop, swap_receiver) = EventLoop::new(
+                    swarm,
+                    env_config,
+                    bitcoin_wallet.clone(),
+                    monero_wallet.clone(),
+                    db.clone(),
+                    rate.clone(),
+                    config.maker.min_buy_btc,
+                    config.maker.max_buy_btc,
+                    config.maker.pricing_strategy()?,
+                    config.maker.ban_policy(),
+                    previous.generation,
+                )?;
+                let event_loop = event_loop
+                    .with_redeem_signer(redeem_signer.clone())
+                    .with_quotes_only(config.maker.quotes_only)
+                    .with_advertised_listen_addresses(advertise_listen_addresses);
+
+                if let Retirement::Overlapping { until } = previous.retirement {
+                    let controller = event_loop.controller();
                     tokio::spawn(async move {
-                        let swap_id = swap.swap_id;
-                        match run(swap, rate).await {
-                            Ok(state) => {
-                                tracing::debug!(%swap_id, final_state=%state, "Swap completed")
-                            }
-                            Err(error) => {
-                                tracing::error!(%swap_id, "Swap failed: {:#}", error)
-                            }
+                        let remaining = (until - OffsetDateTime::now_utc()).whole_seconds();
+                        tokio::time::sleep(Duration::from_secs(
+                            u64::try_from(remaining).unwrap_or(0),
+                        ))
+                        .await;
+
+                        if let Err(error) = controller.send(Control::Retire).await {
+                            tracing::warn!("Failed to retire previous identity: {:#}", error);
                         }
                     });
                 }
-            });
 
-            event_loop.run().await;
+                previous_event_loops.push((event_loop, swap_receiver));
+            }
+
+            if let Some(monitor_bind_address) = monitor_bind_address {
+                let db = db.clone();
+
+                tokio::spawn(async move {
+                    if let Err(error) = monitor::serve(monitor_bind_address, db).await {
+                        tracing::error!("Swap monitor stopped: {:#}", error);
+                    }
+                });
+            }
+
+            if let Some(notifications) = &config.notifications {
+                let notifier = Notifier::new(notifications.webhook_url.clone());
+                let thresholds = notifications.balance_thresholds()?;
+
+                let db = db.clone();
+                let swaps_notifier = notifier.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = swaps_notifier.watch_swaps(db).await {
+                        tracing::error!("Swap notifications stopped: {:#}", error);
+                    }
+                });
+
+                let bitcoin_wallet = bitcoin_wallet.clone();
+                let monero_wallet = monero_wallet.clone();
+                tokio::spawn(async move {
+                    notifier
+                        .watch_balances(bitcoin_wallet, monero_wallet, thresholds)
+                        .await
+                });
+            }
+
+            if let Some(policy) = sweep_policy {
+                let sweeper = Sweeper::new(bitcoin_wallet.clone(), db.clone(), policy);
+                tokio::spawn(sweeper.run());
+            }
+
+            let mut event_loops = vec![event_loop.controller()];
+            event_loops.extend(
+                previous_event_loops
+                    .iter()
+                    .map(|(event_loop, _)| event_loop.controller()),
+            );
+            let punish_scheduler = PunishScheduler::new(
+                bitcoin_wallet.clone(),
+                db.clone(),
+                event_loop.reputation(),
+                event_loops,
+            );
+            tokio::spawn(punish_scheduler.run());
+
+            if let Some(metrics_bind_address) = metrics_bind_address {
+                let context = metrics::Context {
+                    metrics: event_loop.metrics(),
+                    bitcoin_wallet: bitcoin_wallet.clone(),
+                    monero_wallet: monero_wallet.clone(),
+                    db: db.clone(),
+                };
+
+                tokio::spawn(async move {
+                    if let Err(error) = metrics::serve(metrics_bind_address, context).await {
+                        tracing::error!("Metrics endpoint stopped: {:#}", error);
+                    }
+                });
+            }
+
+            let event_loop_controller = event_loop.controller();
+
+            if let Some(rpc_bind_address) = rpc_bind_address {
+                let context = rpc::Context {
+                    bitcoin_wallet,
+                    monero_wallet,
+                    db,
+                    event_loop: event_loop_controller.clone(),
+                };
+
+                let data_dir = config.data.dir.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = rpc::serve(rpc_bind_address, &data_dir, context).await {
+                        tracing::error!("RPC server stopped: {:#}", error);
+                    }
+                });
+            }
+
+            let shutdown_controller = ShutdownController::new();
+
+            spawn_swaps(
+                swap_receiver,
+                rate.clone(),
+                event_loop.reputation(),
+                shutdown_controller.shutdown(),
+            );
+            let mut previous_controllers = Vec::new();
+            for (event_loop, swap_receiver) in previous_event_loops {
+                spawn_swaps(
+                    swap_receiver,
+                    rate.clone(),
+                    event_loop.reputation(),
+                    shutdown_controller.shutdown(),
+                );
+                previous_controllers.push(event_loop.controller());
+                tokio::spawn(event_loop.run());
+            }
+
+            let event_loop = tokio::spawn(event_loop.run());
+
+            tokio::select! {
+                result = event_loop => {
+                    result.context("EventLoop panicked")?;
+                    return Ok(());
+                }
+                result = shutdown::signal() => {
+                    result.context("Failed to listen for shutdown signals")?;
+                }
+            }
+
+            tracing::info!(timeout_secs = %drain_timeout.as_secs(), "Shutting down, waiting for the running swaps to stop");
+            event_loop_controller.send(Control::Shutdown).await?;
+            for controller in previous_controllers {
+                if let Err(error) = controller.send(Control::Shutdown).await {
+                    tracing::warn!("Failed to shut down previous identity: {:#}", error);
+                }
+            }
+
+            if shutdown_controller.drain(drain_timeout).await {
+                tracing::info!("All swaps stopped, exiting");
+            } else {
+                tracing::warn!("Drain timeout passed, exiting with swaps still running. They are resumed on the next start.");
+            }
         }
-        Command::History => {
+        Command::History { period, csv } => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+
+            let reports =
+                accounting::report(db.as_ref(), &bitcoin_wallet, &monero_wallet, period).await?;
+
+            if csv {
+                print!("{}", accounting::to_csv(&reports));
+            } else {
+                let mut table = Table::new();
+
+                table.set_header(
+                    SwapReport::HEADER
+                        .iter()
+                        .map(|field| field.replace('_', " ").to_uppercase())
+                        .collect::<Vec<_>>(),
+                );
+                for report in reports {
+                    table.add_row(report.row());
+                }
+
+                println!("{}", table);
+            }
+        }
+        Command::Sweeps => {
             let mut table = Table::new();
 
-            table.set_header(vec!["SWAP ID", "STATE"]);
+            table.set_header(vec!["SWEPT AT", "TXID", "ADDRESS", "AMOUNT"]);
 
-            for (swap_id, state) in db.all().await? {
-                let state: AliceState = state.try_into()?;
-                table.add_row(vec![swap_id.to_string(), state.to_string()]);
+            for sweep in db.get_sweeps().await? {
+                table.add_row(vec![
+                    sweep.swept_at.to_string(),
+                    sweep.txid.to_string(),
+                    sweep.address.to_string(),
+                    sweep.amount.to_string(),
+                ]);
             }
 
             println!("{}", table);
         }
+        Command::ImportSled { sled_dir } => {
+            let sled_dir = sled_dir.unwrap_or_else(|| config.data.dir.join("database"));
+            let imported = sled_import::import(&sled_dir, db.as_ref()).await?;
+
+            tracing::info!(
+                imported = imported.imported.len(),
+                skipped = imported.skipped.len(),
+                unreadable = imported.unreadable.len(),
+                "Imported the swaps of the sled database"
+            );
+        }
         Command::Config => {
             let config_json = serde_json::to_string_pretty(&config)?;
             println!("{}", config_json);
@@ -302,6 +627,127 @@ async fn main() -> Result<()> {
             let wallet_export = bitcoin_wallet.wallet_export("asb").await?;
             println!("{}", wallet_export.to_string())
         }
+        Command::Ban {
+            peer_id,
+            hours,
+            reason,
+        } => {
+            let until = hours
+                .map(|hours| OffsetDateTime::now_utc() + time::Duration::hours(i64::from(hours)));
+
+            db.insert_ban(Ban {
+                peer_id,
+                until,
+                reason,
+            })
+            .await?;
+
+            match until {
+                Some(until) => tracing::info!(%peer_id, %until, "Banned peer"),
+                None => tracing::info!(%peer_id, "Banned peer until it is unbanned"),
+            }
+        }
+        Command::Unban { peer_id } => {
+            if !db.remove_ban(peer_id).await? {
+                bail!("Peer {} is not banned", peer_id)
+            }
+
+            tracing::info!(%peer_id, "Lifted ban of peer");
+        }
+        Command::RotateIdentity { overlap_hours } => {
+            let rotations = db.get_identity_rotations().await?;
+            let previous_peer_id = PeerId::from(
+                seed.derive_libp2p_identity_at(identity::current_generation(&rotations))
+                    .public(),
+            );
+
+            let rotation = IdentityRotation::next(
+                &rotations,
+                time::Duration::hours(i64::from(overlap_hours)),
+                config.network.listen.clone(),
+                config.network.external_addresses.clone(),
+                OffsetDateTime::now_utc(),
+            )?;
+            let peer_id =
+                PeerId::from(seed.derive_libp2p_identity_at(rotation.generation).public());
+            let overlap_until = rotation.overlap_until;
+            db.insert_identity_rotation(rotation).await?;
+
+            tracing::info!(%previous_peer_id, %peer_id, %overlap_until, "Rotated identity, the previous identity keeps listening on the addresses in `network.listen`");
+            tracing::warn!("Change `network.listen` in the config to addresses for the new identity and restart the ASB");
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the swaps the event loop sets up or resumes, until shutdown is
+/// requested.
+fn spawn_swaps(
+    mut swap_receiver: mpsc::Receiver<Swap>,
+    rate: PriceFeedRate,
+    reputation: Reputation,
+    mut shutdown: Shutdown,
+) {
+    tokio::spawn(async move {
+        loop {
+            // Swaps that are set up during the shutdown are resumed on the next start.
+            let swap = tokio::select! {
+                swap = swap_receiver.recv() => swap,
+                _ = shutdown.requested() => None,
+            };
+            let swap = match swap {
+                Some(swap) => swap,
+                None => break,
+            };
+
+            let rate = rate.clone();
+            let reputation = reputation.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                let swap_id = swap.swap_id;
+                let db = swap.db.clone();
+                match run_until_shutdown(swap, rate, shutdown).await {
+                    Ok(AliceState::BtcPunished) => {
+                        tracing::debug!(%swap_id, "Swap completed with punishing the peer");
+
+                        if let Err(error) = record_punished(swap_id, db, reputation).await {
+                            tracing::warn!(%swap_id, "Failed to record punished peer: {:#}", error);
+                        }
+                    }
+                    Ok(state) => {
+                        tracing::debug!(%swap_id, final_state=%state, "Swap stopped")
+                    }
+                    Err(error) => {
+                        tracing::error!(%swap_id, "Swap failed: {:#}", error)
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Returns the identity generations of the unfinished swaps.
+async fn unfinished_swap_identities(db: &(dyn Database + Send + Sync)) -> Result<Vec<u32>> {
+    let mut generations = Vec::new();
+    for (swap_id, state) in db.all().await? {
+        if !state.swap_finished() {
+            generations.push(db.get_swap_identity(swap_id).await?);
+        }
+    }
+
+    Ok(generations)
+}
+
+async fn record_punished(
+    swap_id: Uuid,
+    db: Arc<dyn Database + Send + Sync>,
+    reputation: Reputation,
+) -> Result<()> {
+    let peer_id = db.get_peer_id(swap_id).await?;
+
+    if let Some(ban) = reputation.record(peer_id, Misbehaviour::Punished).await? {
+        tracing::warn!(%peer_id, reason = %ban.reason, "Banning peer");
     }
 
     Ok(())
@@ -315,14 +761,42 @@ async fn init_bitcoin_wallet(
     tracing::debug!("Opening Bitcoin wallet");
     let wallet_dir = config.data.dir.join("wallet");
 
-    let wallet = bitcoin::Wallet::new(
-        config.bitcoin.electrum_rpc_url.clone(),
-        &wallet_dir,
-        seed.derive_extended_private_key(env_config.bitcoin_network)?,
-        env_config,
-        config.bitcoin.target_block,
-    )
-    .await
+    let key = seed.derive_extended_private_key(env_config.bitcoin_network)?;
+
+    let wallet = match &config.bitcoin.bitcoind {
+        Some(bitcoind) => {
+            tracing::info!(rpc_url = %bitcoind.rpc_url, "Using bitcoind instead of Electrum");
+
+            let backend = bitcoin::Bitcoind::connect(
+                &bitcoind.rpc_url,
+                bitcoind.auth()?,
+                &bitcoind.wallet_name,
+                env_config.bitcoin_network,
+                bitcoind.rescan_from_height,
+            )?;
+
+            bitcoin::Wallet::with_bitcoind(
+                backend,
+                &wallet_dir,
+                key,
+                env_config,
+                config.bitcoin.target_block,
+                config.bitcoin.confirmation_targets(),
+            )
+            .await
+        }
+        None => {
+            bitcoin::Wallet::new(
+                &config.bitcoin.electrum_rpc_urls(),
+                &wallet_dir,
+                key,
+                env_config,
+                config.bitcoin.target_block,
+                config.bitcoin.confirmation_targets(),
+            )
+            .await
+        }
+    }
     .context("Failed to initialize Bitcoin wallet")?;
 
     wallet.sync().await?;
@@ -345,45 +819,70 @@ async fn init_monero_wallet(
     Ok(wallet)
 }
 
+fn init_price_feeds(config: &Config) -> Vec<Box<dyn PriceFeed>> {
+    config
+        .maker
+        .price_feeds
+        .iter()
+        .map(|price_feed| -> Box<dyn PriceFeed> {
+            match price_feed {
+                asb::config::PriceFeed::Kraken => Box::new(kraken::Kraken::new(
+                    config.maker.price_ticker_ws_url.clone(),
+                )),
+                asb::config::PriceFeed::Binance { ws_url } => {
+                    Box::new(binance::Binance::new(ws_url.clone()))
+                }
+                asb::config::PriceFeed::Static { ask_btc } => {
+                    Box::new(price_feed::Static::new(*ask_btc))
+                }
+                asb::config::PriceFeed::File { path } => {
+                    Box::new(price_feed::File::new(path.clone()))
+                }
+            }
+        })
+        .collect()
+}
+
 /// Registers a hidden service for each network.
+///
+/// Returns the onion addresses of the hidden service alongside the client.
 /// Note: Once ac goes out of scope, the services will be de-registered.
 async fn register_tor_services(
     networks: Vec<Multiaddr>,
     tor_client: tor::Client,
     seed: &Seed,
-) -> Result<AuthenticatedClient> {
+) -> Result<(AuthenticatedClient, Vec<Multiaddr>)> {
     let mut ac = tor_client.into_authenticated_client().await?;
 
-    let hidden_services_details = networks
-        .iter()
-        .flat_map(|network| {
-            network.iter().map(|protocol| match protocol {
-                Protocol::Tcp(port) => Some((
-                    port,
-                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), port),
-                )),
-                _ => {
-                    // We only care for Tcp for now.
-                    None
-                }
-            })
-        })
-        .flatten()
-        .collect::<Vec<_>>();
-
+    // The key is derived from the seed, hence the onion address stays the
+    // same across restarts.
     let key = seed.derive_torv3_key();
 
-    ac.add_services(&hidden_services_details, &key).await?;
-
     let onion_address = key
         .public()
         .get_onion_address()
         .get_address_without_dot_onion();
 
-    hidden_services_details.iter().for_each(|(port, _)| {
-        let onion_address = format!("/onion3/{}:{}", onion_address, port);
+    let ports = tor::hidden_service_ports(&networks, &onion_address);
+    if ports.is_empty() {
+        bail!("None of the listen addresses can be reached over Tor, only TCP addresses are supported")
+    }
+
+    let hidden_services_details = ports
+        .iter()
+        .map(|port| (port.onion_port, port.target))
+        .collect::<Vec<_>>();
+
+    ac.add_services(&hidden_services_details, &key).await?;
+
+    let onion_addresses = ports
+        .into_iter()
+        .map(|port| port.address)
+        .collect::<Vec<_>>();
+
+    for onion_address in &onion_addresses {
         tracing::info!(%onion_address, "Successfully created hidden service");
-    });
+    }
 
-    Ok(ac)
+    Ok((ac, onion_addresses))
 }
@@ -1,4 +1,6 @@
 use anyhow::{Context, Result};
+use swap::kraken::Kraken;
+use swap::price_feed;
 use url::Url;
 
 #[tokio::main]
@@ -8,8 +10,11 @@ async fn main() -> Result<()> {
     )?;
 
     let price_ticker_ws_url = Url::parse("wss://ws.kraken.com")?;
-    let mut ticker =
-        swap::kraken::connect(price_ticker_ws_url).context("Failed to connect to kraken")?;
+    let mut ticker = price_feed::connect(
+        vec![Box::new(Kraken::new(price_ticker_ws_url))],
+        price_feed::Freshness::default(),
+    )
+    .context("Failed to connect to kraken")?;
 
     loop {
         match ticker.wait_for_next_update().await? {
@@ -12,32 +12,58 @@
 #![forbid(unsafe_code)]
 #![allow(non_snake_case)]
 
-use anyhow::{Context, Result};
+use ::bitcoin::util::bip32::Fingerprint;
+use anyhow::{bail, Context, Result};
 use comfy_table::Table;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Password;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use libp2p::{identity, Multiaddr, PeerId};
 use qrcode::render::unicode;
 use qrcode::QrCode;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use std::cmp::min;
-use std::convert::TryInto;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::env;
 use std::future::Future;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
-use swap::bitcoin::TxLock;
-use swap::cli::command::{parse_args_and_apply_defaults, Arguments, Command, ParseResult};
-use swap::cli::{list_sellers, EventLoop, SellerStatus};
-use swap::database::open_db;
+use std::time::{Duration, SystemTime};
+use swap::asb::{FixedRate, Rate};
+use swap::bitcoin::{ConfirmationTargets, TxLock};
+use swap::cli::command::{
+    parse_args_and_apply_defaults, Arguments, Command, ParseResult, SelectBest, PASSPHRASE_ENV_VAR,
+};
+use swap::cli::external_wallet::{ExternalWallet, Hwi, PsbtPrompt};
+use swap::cli::{
+    list_sellers, list_sellers_with_latency, progress, select_seller, split, watch_sellers,
+    EventLoop, Seller, SellerStatus, SwapClient, SwapRequest,
+};
+use swap::database::{open_db, sled_import};
 use swap::env::Config;
 use swap::libp2p_ext::MultiAddrExt;
 use swap::network::quote::BidQuote;
+use swap::network::swap_setup::receipt::Receipt;
+use swap::network::swap_setup::sell_xmr::alice::NewSwap;
+use swap::network::swap_setup::{ConfirmationSchedule, Timelocks};
 use swap::network::swarm;
-use swap::protocol::bob;
-use swap::protocol::bob::{BobState, Swap};
+use swap::protocol::alice::{AliceState, LocalRedeemSigner};
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob, state_machine, Database, State};
 use swap::seed::Seed;
-use swap::{bitcoin, cli, monero};
+use swap::{bitcoin, cli, encryption, monero, monitor};
 use url::Url;
 use uuid::Uuid;
 
+/// How often `list-sellers --watch` refreshes the list of sellers.
+const LIST_SELLERS_WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often `sell-xmr` checks the internal Monero wallet for a deposit.
+const MONERO_DEPOSIT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let Arguments {
@@ -45,6 +71,7 @@ async fn main() -> Result<()> {
         data_dir,
         debug,
         json,
+        passphrase,
         cmd,
     } = match parse_args_and_apply_defaults(env::args_os())? {
         ParseResult::Arguments(args) => args,
@@ -54,42 +81,242 @@ async fn main() -> Result<()> {
         }
     };
 
+    let cipher = encryption::unlock(&data_dir, passphrase, PASSPHRASE_ENV_VAR)?;
+
     match cmd {
         Command::BuyXmr {
-            seller,
-            bitcoin_electrum_rpc_url,
+            sellers,
+            select_best,
+            split,
+            cancel_timelock,
+            punish_timelock,
+            confirmation_schedule,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
-            bitcoin_change_address,
+            bitcoin_confirmation_targets,
+            bitcoin_wallet_file,
+            hardware_wallet,
+            bitcoin_refund_address,
             monero_receive_address,
             monero_daemon_address,
+            monero_light_wallet,
+            monero_auto_update,
+            monero_verify_daemons,
+            monero_verify_quorum,
+            tor_socks5_port,
+            json_progress,
+        } => {
+            let swap_id = Uuid::new_v4();
+
+            // the swaps of `--split` each log to the file of their own swap
+            let log_swap_id = if split == 1 { Some(swap_id) } else { None };
+            cli::tracing::init(debug, json, data_dir.join("logs"), log_swap_id)?;
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let monero_receive_address = monero_receive_address
+                .resolve(db.as_ref(), &env_config)
+                .await?;
+            let bitcoin_refund_address = match bitcoin_refund_address {
+                Some(address) => Some(address.resolve(db.as_ref(), &env_config).await?),
+                None => None,
+            };
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
+                .context("Failed to read in seed file")?;
+            let (sellers, selected_btc_amount) = match select_best {
+                Some(select_best) => {
+                    let btc_amount = select_best.btc_amount;
+                    let seller = select_best_seller(
+                        select_best,
+                        seed.derive_libp2p_identity(),
+                        tor_socks5_port,
+                        cancel_timelock,
+                        punish_timelock,
+                        &env_config,
+                    )
+                    .await?;
+
+                    (vec![seller], Some(btc_amount))
+                }
+                None => (sellers, None),
+            };
+            let external_wallet = match bitcoin_wallet_file {
+                Some(path) => Some(ExternalWallet::read(&path).await?),
+                None => None,
+            };
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                data_dir.clone(),
+                env_config,
+                bitcoin_target_block,
+                bitcoin_confirmation_targets,
+                external_wallet,
+                hardware_wallet,
+            )
+            .await?;
+            let (monero_wallet, _process) = init_monero_wallet(
+                data_dir,
+                monero_daemon_address,
+                monero_light_wallet,
+                monero_auto_update,
+                monero_verify_daemons,
+                monero_verify_quorum,
+                env_config,
+            )
+            .await?;
+            let bitcoin_refund_address = match bitcoin_refund_address {
+                Some(address) => address,
+                None => bitcoin_wallet.new_address().await?,
+            };
+            tracing::info!(address = %bitcoin_refund_address, "Bitcoin is refunded to this address if the swap is cancelled");
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let (client, _events) = SwapClient::new(db.clone(), bitcoin_wallet.clone(), env_config);
+            let client = client.with_network(seed.derive_libp2p_identity(), tor_socks5_port);
+
+            if split > 1 {
+                return buy_xmr_split(
+                    sellers,
+                    split,
+                    cancel_timelock,
+                    punish_timelock,
+                    confirmation_schedule,
+                    json,
+                    client,
+                    bitcoin_wallet,
+                    monero_wallet,
+                    env_config,
+                    monero_receive_address,
+                    bitcoin_refund_address,
+                )
+                .await;
+            }
+
+            let client = client.with_monero_wallet(monero_wallet.clone());
+            let seller = sellers
+                .into_iter()
+                .next()
+                .context("At least one seller is required")?;
+            let mut connection = client.connect(swap_id, seller).await?;
+
+            tracing::debug!("Requesting quote");
+            let bid_quote = connection.request_quote().await?;
+            // the seller was selected for swapping at most this amount
+            let bid_quote = match selected_btc_amount {
+                Some(btc_amount) if btc_amount < bid_quote.min_quantity => bail!(
+                    "The seller raised its minimum amount to {} since it was selected for {}",
+                    bid_quote.min_quantity,
+                    btc_amount
+                ),
+                Some(btc_amount) => BidQuote {
+                    max_quantity: min(bid_quote.max_quantity, btc_amount),
+                    ..bid_quote
+                },
+                None => bid_quote,
+            };
+            let timelocks = Timelocks::propose(
+                cancel_timelock,
+                punish_timelock,
+                bid_quote.timelock_ranges(),
+                &env_config,
+            )?;
+
+            let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
+            let (amount, fees) = determine_btc_to_swap(
+                json,
+                async move { Ok(bid_quote) },
+                bitcoin_wallet.new_address(),
+                || bitcoin_wallet.balance(),
+                max_givable,
+                || bitcoin_wallet.sync(),
+            )
+            .await?;
+
+            tracing::info!(%amount, %fees,  "Determined swap amount");
+
+            // Waiting for a deposit can take longer than the seller guarantees the price
+            // for.
+            if bid_quote.is_expired(SystemTime::now()) {
+                bail!("The quote of the seller expired, run buy-xmr again to request a new quote")
+            }
+
+            let progress = report_progress(
+                swap_id,
+                db,
+                bitcoin_wallet,
+                monero_wallet,
+                env_config,
+                json,
+                json_progress,
+            );
+            let request = SwapRequest {
+                btc_amount: amount,
+                monero_receive_address,
+                bitcoin_refund_address,
+                timelocks,
+                confirmation_schedule,
+            };
+
+            tokio::select! {
+                result = client.start(connection, request) => {
+                    let state = result.context("Failed to complete swap")?;
+                    print_redeemed_monero_wallet(swap_id, &state, env_config, json)?;
+                },
+                _ = progress => {}
+            }
+        }
+        Command::SellXmr {
+            seller,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+            bitcoin_receive_address,
+            monero_refund_address,
+            monero_daemon_address,
+            monero_auto_update,
             tor_socks5_port,
         } => {
             let swap_id = Uuid::new_v4();
 
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
+            )
+            .await?;
+            let (monero_wallet, _process) = init_monero_wallet_rpc(
+                data_dir,
+                monero_daemon_address,
+                monero_auto_update,
+                env_config,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let monero_wallet = Arc::new(monero_wallet);
             let seller_peer_id = seller
                 .extract_peer_id()
                 .context("Seller address must contain peer ID")?;
             db.insert_address(seller_peer_id, seller.clone()).await?;
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
-            let mut swarm =
-                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
+            let identity = seed.derive_libp2p_identity();
+            let mut swarm = swarm::cli(identity.clone(), tor_socks5_port, |relay| {
+                cli::Behaviour::new(
+                    seller_peer_id,
+                    env_config,
+                    bitcoin_wallet.clone(),
+                    identity.public(),
+                    relay,
+                )
+            })
+            .await?;
             swarm.behaviour_mut().add_address(seller_peer_id, seller);
 
             tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
@@ -98,57 +325,155 @@ async fn main() -> Result<()> {
                 EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
             let event_loop = tokio::spawn(event_loop.run());
 
-            let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
-            let (amount, fees) = determine_btc_to_swap(
+            tracing::debug!("Requesting quote");
+            let bid_quote = event_loop_handle.request_quote().await?;
+            let buy_price = bid_quote
+                .details
+                .and_then(|details| details.buy_price)
+                .context("The seller does not buy XMR")?;
+            tracing::info!(
+                price = %buy_price,
+                minimum_amount = %bid_quote.min_quantity,
+                maximum_amount = %bid_quote.max_quantity,
+                "Received quote",
+            );
+
+            let rate = Rate::new(buy_price, Decimal::ZERO);
+            let xmr = determine_xmr_to_swap(
                 json,
-                event_loop_handle.request_quote(),
-                bitcoin_wallet.new_address(),
-                || bitcoin_wallet.balance(),
-                max_givable,
-                || bitcoin_wallet.sync(),
+                monero_wallet.get_main_address(),
+                rate.sell_quote(bid_quote.min_quantity)?,
+                rate.sell_quote(bid_quote.max_quantity)?,
+                || async {
+                    monero_wallet.refresh().await?;
+                    monero_wallet.get_balance().await
+                },
             )
             .await?;
 
-            tracing::info!(%amount, %fees,  "Determined swap amount");
+            let btc = rate.buy_quote(xmr)?;
+            tracing::info!(%xmr, %btc, "Determined swap amount");
+
+            // Waiting for a deposit can take longer than the seller guarantees the price
+            // for.
+            if bid_quote.is_expired(SystemTime::now()) {
+                bail!("The quote of the seller expired, run sell-xmr again to request a new quote")
+            }
 
             db.insert_peer_id(swap_id, seller_peer_id).await?;
-            db.insert_monero_address(swap_id, monero_receive_address)
+            if let Some(monero_refund_address) = monero_refund_address {
+                db.insert_monero_address(swap_id, monero_refund_address)
+                    .await?;
+            }
+
+            let state3 = event_loop_handle
+                .setup_sell_xmr(NewSwap {
+                    swap_id,
+                    xmr,
+                    btc,
+                    bitcoin_redeem_address: bitcoin_receive_address,
+                })
+                .await?;
+            let state = AliceState::Started {
+                state3: Box::new(state3),
+            };
+            db.insert_latest_state(swap_id, state.clone().into())
                 .await?;
 
-            let swap = Swap::new(
-                db,
-                swap_id,
+            let swap = alice::Swap {
+                state,
+                event_loop_handle: event_loop_handle.into_alice_handle(),
                 bitcoin_wallet,
-                Arc::new(monero_wallet),
+                monero_wallet,
                 env_config,
-                event_loop_handle,
-                monero_receive_address,
-                bitcoin_change_address,
-                amount,
-            );
+                swap_id,
+                db,
+                redeem_signer: Arc::new(LocalRedeemSigner),
+            };
 
             tokio::select! {
                 result = event_loop => {
                     result
-                        .context("EventLoop panicked")?;
+                        .context("EventLoop panicked")??;
                 },
-                result = bob::run(swap) => {
+                result = alice::run(swap, FixedRate::new(rate)) => {
                     result.context("Failed to complete swap")?;
                 }
             }
         }
         Command::History => {
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let mut table = Table::new();
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+
+            if json {
+                for swap in cli::history(db.as_ref()).await? {
+                    println!("{}", serde_json::to_string(&swap)?);
+                }
+            } else {
+                let mut table = Table::new();
+
+                table.set_header(vec!["SWAP ID", "STATE"]);
+
+                for (swap_id, state) in db.all().await? {
+                    table.add_row(vec![swap_id.to_string(), state.to_string()]);
+                }
+
+                println!("{}", table);
+            }
+        }
+        Command::AddressBookAdd { entry } => {
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
 
-            table.set_header(vec!["SWAP ID", "STATE"]);
+            db.insert_address_book_entry(entry.clone()).await?;
+            println!(
+                "Stored {} address {} as `{}`",
+                entry.address.kind(),
+                entry.address,
+                entry.label
+            );
+        }
+        Command::AddressBookRemove { label } => {
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+
+            if !db.remove_address_book_entries(&label).await? {
+                bail!("No address is stored as `{}`", label)
+            }
+            println!("Removed the addresses stored as `{}`", label);
+        }
+        Command::AddressBookList => {
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
 
-            for (swap_id, state) in db.all().await? {
-                let state: BobState = state.try_into()?;
-                table.add_row(vec![swap_id.to_string(), state.to_string()]);
+            if json {
+                for entry in db.get_address_book().await? {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "label": entry.label,
+                            "kind": entry.address.kind().to_string(),
+                            "address": entry.address.to_string(),
+                        })
+                    );
+                }
+            } else {
+                let mut table = Table::new();
+
+                table.set_header(vec!["LABEL", "KIND", "ADDRESS"]);
+
+                for entry in db.get_address_book().await? {
+                    table.add_row(vec![
+                        entry.label,
+                        entry.address.kind().to_string(),
+                        entry.address.to_string(),
+                    ]);
+                }
+
+                println!("{}", table);
             }
+        }
+        Command::Monitor { bind_address } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
 
-            println!("{}", table);
+            monitor::serve(bind_address, db).await?;
         }
         Command::Config => {
             println!("Data directory: {}", data_dir.display());
@@ -174,20 +499,23 @@ async fn main() -> Result<()> {
             );
         }
         Command::WithdrawBtc {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
             amount,
             address,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
             )
             .await?;
 
@@ -209,18 +537,21 @@ async fn main() -> Result<()> {
         }
 
         Command::Balance {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
             )
             .await?;
 
@@ -229,217 +560,1042 @@ async fn main() -> Result<()> {
         }
         Command::Resume {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
+            bitcoin_confirmation_targets,
+            bitcoin_wallet_file,
+            hardware_wallet,
             monero_daemon_address,
+            monero_light_wallet,
+            monero_auto_update,
+            monero_verify_daemons,
+            monero_verify_quorum,
             tor_socks5_port,
+            json_progress,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
+            let external_wallet = match bitcoin_wallet_file {
+                Some(path) => Some(ExternalWallet::read(&path).await?),
+                None => None,
+            };
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                bitcoin_confirmation_targets,
+                external_wallet,
+                hardware_wallet,
+            )
+            .await?;
+            let (monero_wallet, _process) = init_monero_wallet(
+                data_dir,
+                monero_daemon_address,
+                monero_light_wallet,
+                monero_auto_update,
+                monero_verify_daemons,
+                monero_verify_quorum,
+                env_config,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
 
-            let seller_peer_id = db.get_peer_id(swap_id).await?;
-            let seller_addresses = db.get_addresses(seller_peer_id).await?;
-
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
-            let mut swarm =
-                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
-            let our_peer_id = swarm.local_peer_id();
-            tracing::debug!(peer_id = %our_peer_id, "Network layer initialized");
+            match db.get_state(swap_id).await? {
+                State::Alice(state) => {
+                    let monero_wallet = match monero_wallet {
+                        bob::MoneroWallet::Rpc(wallet) => wallet,
+                        _ => bail!("Swaps in which XMR is sold cannot be resumed with --monero-light-wallet"),
+                    };
+                    let rate = sell_xmr_rate(&state)?;
+
+                    let seller_peer_id = db.get_peer_id(swap_id).await?;
+                    let seller_addresses = db.get_addresses(seller_peer_id).await?;
+
+                    let identity = seed.derive_libp2p_identity();
+                    let mut swarm = swarm::cli(identity.clone(), tor_socks5_port, |relay| {
+                        cli::Behaviour::new(
+                            seller_peer_id,
+                            env_config,
+                            bitcoin_wallet.clone(),
+                            identity.public(),
+                            relay,
+                        )
+                    })
+                    .await?;
+                    let our_peer_id = swarm.local_peer_id();
+                    tracing::debug!(peer_id = %our_peer_id, "Network layer initialized");
+
+                    for seller_address in seller_addresses {
+                        swarm
+                            .behaviour_mut()
+                            .add_address(seller_peer_id, seller_address);
+                    }
+
+                    let (event_loop, event_loop_handle) =
+                        EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
+                    let handle = tokio::spawn(event_loop.run());
+
+                    let swap = alice::Swap {
+                        state,
+                        event_loop_handle: event_loop_handle.into_alice_handle(),
+                        bitcoin_wallet,
+                        monero_wallet,
+                        env_config,
+                        swap_id,
+                        db,
+                        redeem_signer: Arc::new(LocalRedeemSigner),
+                    };
 
-            for seller_address in seller_addresses {
-                swarm
-                    .behaviour_mut()
-                    .add_address(seller_peer_id, seller_address);
+                    tokio::select! {
+                        event_loop_result = handle => {
+                            event_loop_result??;
+                        },
+                        swap_result = alice::run(swap, FixedRate::new(rate)) => {
+                            swap_result?;
+                        }
+                    }
+                }
+                State::Bob(_) => {
+                    let (client, _events) =
+                        SwapClient::new(db.clone(), bitcoin_wallet.clone(), env_config);
+                    let client = client
+                        .with_monero_wallet(monero_wallet.clone())
+                        .with_network(seed.derive_libp2p_identity(), tor_socks5_port);
+                    let progress = report_progress(
+                        swap_id,
+                        db,
+                        bitcoin_wallet,
+                        monero_wallet,
+                        env_config,
+                        json,
+                        json_progress,
+                    );
+
+                    tokio::select! {
+                        swap_result = client.resume(swap_id) => {
+                            let state = swap_result?;
+                            print_redeemed_monero_wallet(swap_id, &state, env_config, json)?;
+                        },
+                        _ = progress => {}
+                    }
+                }
             }
+        }
+        Command::Abort {
+            swap_id,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+            tor_socks5_port,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
+                .context("Failed to read in seed file")?;
 
-            let (event_loop, event_loop_handle) =
-                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
-            let handle = tokio::spawn(event_loop.run());
+            let bitcoin_wallet = Arc::new(
+                init_bitcoin_wallet(
+                    bitcoin_electrum_rpc_urls,
+                    &seed,
+                    data_dir,
+                    env_config,
+                    bitcoin_target_block,
+                    ConfirmationTargets::uniform(bitcoin_target_block),
+                    None,
+                    None,
+                )
+                .await?,
+            );
 
-            let monero_receive_address = db.get_monero_address(swap_id).await?;
-            let swap = Swap::from_db(
-                db,
-                swap_id,
-                bitcoin_wallet,
-                Arc::new(monero_wallet),
-                env_config,
-                event_loop_handle,
-                monero_receive_address,
-            )
+            cli::abort(swap_id, bitcoin_wallet.clone(), db.clone()).await?;
+
+            // The swap is aborted regardless of whether we reach the peer, the peer aborts
+            // on its own once it timed out waiting for the Bitcoin to be locked.
+            let peer_id = db.get_peer_id(swap_id).await?;
+            let peer_addresses = db.get_addresses(peer_id).await?;
+
+            let identity = seed.derive_libp2p_identity();
+            let mut swarm = swarm::cli(identity.clone(), tor_socks5_port, |relay| {
+                cli::Behaviour::new(
+                    peer_id,
+                    env_config,
+                    bitcoin_wallet,
+                    identity.public(),
+                    relay,
+                )
+            })
             .await?;
+            for peer_address in peer_addresses {
+                swarm.behaviour_mut().add_address(peer_id, peer_address);
+            }
 
-            tokio::select! {
-                event_loop_result = handle => {
-                    event_loop_result?;
-                },
-                swap_result = bob::run(swap) => {
-                    swap_result?;
-                }
+            let (event_loop, mut event_loop_handle) =
+                EventLoop::new(swap_id, swarm, peer_id, env_config)?;
+            let event_loop = tokio::spawn(event_loop.run());
+
+            match event_loop_handle.send_abort().await {
+                Ok(()) => tracing::info!(%swap_id, "Told the peer that the swap was aborted"),
+                Err(error) => tracing::warn!(
+                    %swap_id,
+                    "Failed to tell the peer that the swap was aborted: {:#}",
+                    error
+                ),
             }
+
+            event_loop.abort();
         }
         Command::Cancel {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
             )
             .await?;
 
-            let (txid, _) = cli::cancel(swap_id, Arc::new(bitcoin_wallet), db).await?;
+            let (client, _events) = SwapClient::new(db, Arc::new(bitcoin_wallet), env_config);
+            let (txid, _) = client.cancel(swap_id).await?;
             tracing::debug!("Cancel transaction successfully published with id {}", txid);
         }
         Command::Refund {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
             )
             .await?;
 
-            cli::refund(swap_id, Arc::new(bitcoin_wallet), db).await?;
+            let (client, _events) = SwapClient::new(db, Arc::new(bitcoin_wallet), env_config);
+            client.refund(swap_id).await?;
         }
-        Command::ListSellers {
-            rendezvous_point,
-            namespace,
-            tor_socks5_port,
+        Command::CancelAndRefund {
+            swap_id,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
         } => {
-            let rendezvous_node_peer_id = rendezvous_point
-                .extract_peer_id()
-                .context("Rendezvous node address must contain peer ID")?;
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
+            )
+            .await?;
+
+            cli::cancel_and_refund(swap_id, Arc::new(bitcoin_wallet), db).await?;
+        }
+        Command::BumpFee {
+            swap_id,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
+            )
+            .await?;
+
+            cli::bump_fee(swap_id, Arc::new(bitcoin_wallet), db).await?;
+        }
+        Command::ExportPsbt { swap_id, tx } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+
+            let exported = cli::export_psbt(swap_id, tx, db).await?;
 
+            if json {
+                println!("{}", serde_json::to_string(&exported)?);
+            } else {
+                println!("Transaction id: {}", exported.txid);
+                println!("PSBT: {}", exported.psbt);
+                println!("Raw transaction: {}", exported.hex);
+            }
+        }
+        Command::ExportSwap {
+            swap_id,
+            archive,
+            archive_passphrase_file,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+            let archive_cipher = archive_cipher(archive_passphrase_file, true)?;
+
+            let exported = cli::archive::export(swap_id, db.as_ref(), &archive_cipher).await?;
+            tokio::fs::write(&archive, exported)
+                .await
+                .with_context(|| format!("Failed to write archive to {}", archive.display()))?;
+
+            tracing::info!(%swap_id, archive = %archive.display(), "Exported swap, do not resume it on this machine anymore");
+        }
+        Command::ImportSwap {
+            archive,
+            archive_passphrase_file,
+        } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+            let archive_cipher = archive_cipher(archive_passphrase_file, false)?;
+
+            let encrypted = tokio::fs::read(&archive)
+                .await
+                .with_context(|| format!("Failed to read archive from {}", archive.display()))?;
+            let swap_id = cli::archive::import(&encrypted, db.as_ref(), &archive_cipher).await?;
+
+            tracing::info!(%swap_id, "Imported swap, run `swap resume --swap-id {}` to resume it", swap_id);
+        }
+        Command::ImportSled { sled_dir } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+
+            let sled_dir = sled_dir.unwrap_or_else(|| data_dir.join("database"));
+            let imported = sled_import::import(&sled_dir, db.as_ref()).await?;
+
+            tracing::info!(
+                imported = imported.imported.len(),
+                skipped = imported.skipped.len(),
+                unreadable = imported.unreadable.len(),
+                "Imported the swaps of the sled database, run `swap history` to list them"
+            );
+        }
+        Command::VerifyReceipt { swap_id } => {
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+
+            let seller = db.get_peer_id(swap_id).await?;
+            let receipts = db.get_receipts(swap_id).await?;
+            if receipts.is_empty() {
+                bail!("The seller signed no receipts for swap {}", swap_id)
+            }
+
+            let mut table = Table::new();
+            table.set_header(vec![
+                "RECEIPT",
+                "BTC",
+                "XMR",
+                "CANCEL TIMELOCK",
+                "PUNISH TIMELOCK",
+                "SIGNED AT",
+            ]);
+
+            let mut invalid = Vec::new();
+            for receipt in receipts {
+                let (kind, btc, xmr, timelocks, signed_at) = match receipt.verify(seller) {
+                    Ok(Receipt::Quote(quote)) => (
+                        "quote",
+                        quote.btc,
+                        quote.xmr,
+                        quote.timelocks,
+                        quote.quoted_at,
+                    ),
+                    Ok(Receipt::Swap(swap)) => {
+                        ("swap", swap.btc, swap.xmr, swap.timelocks, swap.set_up_at)
+                    }
+                    Err(error) => {
+                        invalid.push(format!("{:#}", error));
+                        continue;
+                    }
+                };
+                let signed_at =
+                    time::OffsetDateTime::from_unix_timestamp(i64::try_from(signed_at)?)?;
+
+                if json {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "receipt": kind,
+                            "btc": btc.as_sat(),
+                            "xmr": xmr.as_piconero(),
+                            "cancel_timelock": u32::from(timelocks.cancel),
+                            "punish_timelock": u32::from(timelocks.punish),
+                            "signed_at": signed_at.to_string(),
+                        })
+                    );
+                } else {
+                    table.add_row(vec![
+                        kind.to_string(),
+                        btc.to_string(),
+                        xmr.to_string(),
+                        timelocks.cancel.to_string(),
+                        timelocks.punish.to_string(),
+                        signed_at.to_string(),
+                    ]);
+                }
+            }
+
+            if !json {
+                println!("{}", table);
+            }
+            if !invalid.is_empty() {
+                bail!(
+                    "{} of the receipts of swap {} are invalid: {}",
+                    invalid.len(),
+                    swap_id,
+                    invalid.join(", ")
+                )
+            }
+        }
+        Command::Audit {
+            swap_id,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+            monero_daemon_address,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = open_db(data_dir.join("sqlite"), cipher.clone()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
-            let identity = seed.derive_libp2p_identity();
 
-            let sellers = list_sellers(
-                rendezvous_node_peer_id,
-                rendezvous_point,
-                namespace,
-                tor_socks5_port,
-                identity,
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
             )
             .await?;
+            let monero_wallet =
+                monero::LightWallet::connect(monero_daemon_address.as_str(), env_config).await?;
+
+            let findings =
+                cli::audit(swap_id, db.as_ref(), &bitcoin_wallet, &monero_wallet).await?;
 
             if json {
-                for seller in sellers {
-                    println!("{}", serde_json::to_string(&seller)?);
+                for finding in &findings {
+                    println!("{}", serde_json::to_string(finding)?);
                 }
             } else {
                 let mut table = Table::new();
+                table.set_header(vec!["CHECK", "EXPECTED", "ACTUAL", "STATUS"]);
+                for finding in &findings {
+                    table.add_row(vec![
+                        finding.check.to_string(),
+                        finding.expected.clone(),
+                        finding.actual.clone().unwrap_or_else(|| "-".to_string()),
+                        finding.status.to_string(),
+                    ]);
+                }
+                println!("{}", table);
+            }
 
-                table.set_header(vec![
-                    "PRICE",
-                    "MIN_QUANTITY",
-                    "MAX_QUANTITY",
-                    "STATUS",
-                    "ADDRESS",
-                ]);
-
-                for seller in sellers {
-                    let row = match seller.status {
-                        SellerStatus::Online(quote) => {
-                            vec![
-                                quote.price.to_string(),
-                                quote.min_quantity.to_string(),
-                                quote.max_quantity.to_string(),
-                                "Online".to_owned(),
-                                seller.multiaddr.to_string(),
-                            ]
-                        }
-                        SellerStatus::Unreachable => {
-                            vec![
-                                "???".to_owned(),
-                                "???".to_owned(),
-                                "???".to_owned(),
-                                "Unreachable".to_owned(),
-                                seller.multiaddr.to_string(),
-                            ]
-                        }
-                    };
+            let discrepancies = findings
+                .iter()
+                .filter(|finding| finding.status == cli::audit::Status::Discrepancy)
+                .count();
+            if discrepancies > 0 {
+                bail!(
+                    "Found {} discrepancies in the amounts of swap {}",
+                    discrepancies,
+                    swap_id
+                )
+            }
+        }
+        Command::ListSellers {
+            rendezvous_points,
+            namespace,
+            tor_socks5_port,
+            watch,
+        } => {
+            let rendezvous_points = rendezvous_points
+                .into_iter()
+                .map(|address| {
+                    let peer_id = address
+                        .extract_peer_id()
+                        .context("Rendezvous node address must contain peer ID")?;
 
-                    table.add_row(row);
+                    Ok((peer_id, address))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
+                .context("Failed to read in seed file")?;
+            let identity = seed.derive_libp2p_identity();
+
+            if watch {
+                let mut updates = watch_sellers(
+                    rendezvous_points,
+                    namespace,
+                    tor_socks5_port,
+                    identity,
+                    LIST_SELLERS_WATCH_INTERVAL,
+                )
+                .await?;
+
+                while let Some(sellers) = updates.next().await {
+                    if json {
+                        print_sellers_json(&sellers)?;
+                    } else {
+                        // clear the terminal before printing the refreshed table
+                        print!("\x1B[2J\x1B[1;1H");
+                        println!("{}", sellers_table(sellers));
+                    }
                 }
+            } else {
+                let sellers =
+                    list_sellers(rendezvous_points, namespace, tor_socks5_port, identity).await?;
 
-                println!("{}", table);
+                if json {
+                    print_sellers_json(&sellers)?;
+                } else {
+                    println!("{}", sellers_table(sellers));
+                }
             }
         }
         Command::ExportBitcoinWallet {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                ConfirmationTargets::uniform(bitcoin_target_block),
+                None,
+                None,
             )
             .await?;
             let wallet_export = bitcoin_wallet.wallet_export("cli").await?;
             println!("{}", wallet_export.to_string())
         }
+        Command::ExportSeed => {
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), cipher.as_ref())
+                .context("Failed to read in seed file")?;
+            let mnemonic = seed.to_mnemonic().to_string();
+
+            if json {
+                println!("{}", serde_json::json!({ "mnemonic": mnemonic }));
+            } else {
+                println!("{}", mnemonic);
+            }
+        }
+        Command::ImportSeed { mnemonic_file } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            let seed = Seed::from_mnemonic(&read_mnemonic(mnemonic_file)?)?;
+            seed.write_new(data_dir.as_path(), cipher.as_ref())
+                .context("Failed to write seed file")?;
+
+            let peer_id = PeerId::from(seed.derive_libp2p_identity().public());
+            tracing::info!(%peer_id, data_dir = %data_dir.display(), "Restored seed, run `swap balance` to sync the Bitcoin wallet");
+        }
+        Command::StateMachine { role, format } => {
+            print!("{}", state_machine::render(role, format));
+        }
+        Command::SimulateSwap {
+            swap_id,
+            lock_height,
+        } => {
+            let db = open_db(data_dir.join("sqlite"), cipher).await?;
+            let state = db.get_state(swap_id).await?;
+
+            print!("{}", state_machine::Simulation::new(&state, lock_height));
+        }
+    };
+    Ok(())
+}
+
+/// The cipher of a swap archive, the passphrase is asked for if no file is
+/// given.
+fn archive_cipher(passphrase_file: Option<PathBuf>, new: bool) -> Result<encryption::Cipher> {
+    let passphrase = match passphrase_file {
+        Some(path) => encryption::Passphrase::from_file(&path)?,
+        None => encryption::Passphrase::prompt_with("Enter the passphrase of the archive", new)?,
+    };
+
+    encryption::Cipher::new(passphrase)
+}
+
+/// The mnemonic of `import-seed`, it is asked for without echoing it if no file
+/// is given.
+fn read_mnemonic(mnemonic_file: Option<PathBuf>) -> Result<String> {
+    let mnemonic = match mnemonic_file {
+        Some(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read mnemonic from {}", path.display()))?
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        None => Password::with_theme(&ColorfulTheme::default())
+            .with_prompt("Enter the 24 words of the seed")
+            .interact()?,
+    };
+
+    Ok(mnemonic)
+}
+
+/// Discovers the sellers of `buy-xmr --select-best` and returns the address of
+/// the best one that accepts the amount and the timelocks.
+async fn select_best_seller(
+    select_best: SelectBest,
+    identity: identity::Keypair,
+    tor_socks5_port: u16,
+    cancel_timelock: Option<u32>,
+    punish_timelock: Option<u32>,
+    env_config: &Config,
+) -> Result<Multiaddr> {
+    let SelectBest {
+        rendezvous_points,
+        namespace,
+        btc_amount,
+    } = select_best;
+    let rendezvous_points = rendezvous_points
+        .into_iter()
+        .map(|address| {
+            let peer_id = address
+                .extract_peer_id()
+                .context("Rendezvous node address must contain peer ID")?;
+
+            Ok((peer_id, address))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let sellers =
+        list_sellers_with_latency(rendezvous_points, namespace, tor_socks5_port, identity).await?;
+    let discovered = sellers.len();
+
+    let candidates = select_seller::rank(sellers, btc_amount)
+        .into_iter()
+        .filter(|candidate| {
+            Timelocks::propose(
+                cancel_timelock,
+                punish_timelock,
+                candidate.quote.timelock_ranges(),
+                env_config,
+            )
+            .is_ok()
+        })
+        .collect::<Vec<_>>();
+
+    for candidate in &candidates {
+        tracing::debug!(
+            seller = %candidate.multiaddr,
+            price = %candidate.quote.price,
+            latency = ?candidate.latency,
+            "Seller accepts the amount"
+        );
+    }
+
+    let best = match candidates.into_iter().next() {
+        Some(best) => best,
+        None => bail!(
+            "None of the {} discovered sellers accepts to swap {} with the given timelocks",
+            discovered,
+            btc_amount
+        ),
     };
+
+    tracing::info!(
+        seller = %best.multiaddr,
+        price = %best.quote.price,
+        latency = ?best.latency,
+        "Selected seller"
+    );
+
+    Ok(best.multiaddr)
+}
+
+/// Runs the swaps of `buy-xmr --split`.
+///
+/// The swaps are set up and their Bitcoin is locked one swap after another, so
+/// the lock transactions don't spend the same outputs. Once its Bitcoin is
+/// locked, a swap continues concurrently to the others.
+#[allow(clippy::too_many_arguments)]
+async fn buy_xmr_split(
+    sellers: Vec<Multiaddr>,
+    split: usize,
+    cancel_timelock: Option<u32>,
+    punish_timelock: Option<u32>,
+    confirmation_schedule: Option<ConfirmationSchedule>,
+    json: bool,
+    client: SwapClient,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: bob::MoneroWallet,
+    env_config: Config,
+    monero_receive_address: monero::Address,
+    bitcoin_refund_address: bitcoin::Address,
+) -> Result<()> {
+    let sellers = sellers
+        .into_iter()
+        .map(|seller| {
+            let peer_id = seller
+                .extract_peer_id()
+                .context("Seller address must contain peer ID")?;
+
+            Ok((peer_id, seller))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut quotes = HashMap::new();
+    for (peer_id, address) in &sellers {
+        let mut connection = client.connect(Uuid::new_v4(), address.clone()).await?;
+
+        tracing::debug!(seller = %peer_id, "Requesting quote");
+        let quote = connection.request_quote().await;
+        connection.close();
+
+        let quote =
+            quote.with_context(|| format!("Failed to request quote from seller {}", peer_id))?;
+        Timelocks::propose(
+            cancel_timelock,
+            punish_timelock,
+            quote.timelock_ranges(),
+            &env_config,
+        )
+        .with_context(|| format!("Cannot swap with seller {}", peer_id))?;
+
+        quotes.insert(*peer_id, quote);
+    }
+
+    let swaps = split::assign_sellers(&sellers, split);
+    let swap_quotes = swaps
+        .iter()
+        .map(|(peer_id, _)| quotes[peer_id])
+        .collect::<Vec<_>>();
+
+    let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
+    let (amount, fees) = determine_btc_to_swap(
+        json,
+        async { Ok(split::combined_quote(&swap_quotes)) },
+        bitcoin_wallet.new_address(),
+        || bitcoin_wallet.balance(),
+        max_givable,
+        || bitcoin_wallet.sync(),
+    )
+    .await?;
+
+    tracing::info!(%amount, %fees, swaps = split, "Determined amount to split");
+
+    // the swaps share the wallet of monero-wallet-rpc
+    let client = client.with_monero_wallet(match monero_wallet {
+        bob::MoneroWallet::Rpc(wallet) => bob::MoneroWallet::Shared(wallet),
+        wallet => wallet,
+    });
+
+    let mut remaining = amount;
+    let mut running = FuturesUnordered::new();
+    let mut outcomes = Vec::new();
+
+    for (index, (peer_id, address)) in swaps.into_iter().enumerate() {
+        let swap_id = Uuid::new_v4();
+
+        bitcoin_wallet.sync().await?;
+        let max_giveable = bitcoin_wallet.max_giveable(TxLock::script_size()).await?;
+
+        let mut connection = client.connect(swap_id, address).await?;
+
+        // the quote requested before waiting for the deposit might have expired
+        let quote = connection.request_quote().await?;
+        let btc_amount = match split::next_swap_amount(
+            min(max_giveable, remaining),
+            split - index,
+            &quote,
+        ) {
+            Some(btc_amount) => btc_amount,
+            None => {
+                connection.close();
+                tracing::warn!(
+                        started = index,
+                        swaps = split,
+                        "Not enough Bitcoin left to start the remaining swaps within the limits of their sellers"
+                    );
+                break;
+            }
+        };
+
+        let timelocks = match Timelocks::propose(
+            cancel_timelock,
+            punish_timelock,
+            quote.timelock_ranges(),
+            &env_config,
+        ) {
+            Ok(timelocks) => timelocks,
+            Err(error) => {
+                connection.close();
+                tracing::warn!(
+                    started = index,
+                    swaps = split,
+                    "Cannot start the remaining swaps: {:#}",
+                    error
+                );
+                break;
+            }
+        };
+
+        let request = SwapRequest {
+            btc_amount,
+            monero_receive_address,
+            bitcoin_refund_address: bitcoin_refund_address.clone(),
+            timelocks,
+            confirmation_schedule: confirmation_schedule.clone(),
+        };
+
+        tracing::info!(%swap_id, seller = %peer_id, amount = %btc_amount, "Starting swap {} of {}", index + 1, split);
+
+        let locked = client
+            .start_until(connection, request, |state| {
+                matches!(state, BobState::BtcLocked(..)) || bob::swap::is_complete(state)
+            })
+            .await;
+
+        remaining = remaining
+            .checked_sub(btc_amount)
+            .unwrap_or(bitcoin::Amount::ZERO);
+
+        match locked {
+            Ok(state) if bob::swap::is_complete(&state) => {
+                outcomes.push((swap_id, peer_id, btc_amount, Ok(state)));
+            }
+            Ok(_) => {
+                // continue the swap over a connection of its own
+                let client = client.clone();
+                running.push(tokio::spawn(async move {
+                    (swap_id, peer_id, btc_amount, client.resume(swap_id).await)
+                }));
+            }
+            Err(error) => {
+                tracing::warn!(%swap_id, "Failed to lock the Bitcoin of swap, resume it to continue: {:#}", error);
+                outcomes.push((swap_id, peer_id, btc_amount, Err(error)));
+            }
+        }
+    }
+
+    let total = running.len() + outcomes.len();
+    while let Some(outcome) = running.next().await {
+        let (swap_id, peer_id, btc_amount, result) = outcome.context("Swap panicked")?;
+
+        match &result {
+            Ok(state) => tracing::info!(
+                %swap_id,
+                %state,
+                finished = outcomes.len() + 1,
+                swaps = total,
+                "Swap finished"
+            ),
+            Err(error) => {
+                tracing::warn!(%swap_id, finished = outcomes.len() + 1, swaps = total, "Swap failed, resume it to continue: {:#}", error)
+            }
+        }
+
+        outcomes.push((swap_id, peer_id, btc_amount, result));
+    }
+
+    let mut table = Table::new();
+    table.set_header(vec!["SWAP ID", "SELLER", "AMOUNT", "STATE"]);
+
+    for (swap_id, peer_id, btc_amount, result) in outcomes {
+        let state = match result {
+            Ok(state) => {
+                print_redeemed_monero_wallet(swap_id, &state, env_config, json)?;
+                state.to_string()
+            }
+            Err(error) => format!("failed: {:#}", error),
+        };
+
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "swap_id": swap_id,
+                    "seller": peer_id.to_string(),
+                    "amount": btc_amount.as_btc(),
+                    "state": state,
+                })
+            );
+        } else {
+            table.add_row(vec![
+                swap_id.to_string(),
+                peer_id.to_string(),
+                btc_amount.to_string(),
+                state,
+            ]);
+        }
+    }
+
+    if !json {
+        println!("{}", table);
+    }
+
     Ok(())
 }
 
+fn print_sellers_json(sellers: &[Seller]) -> Result<()> {
+    for seller in sellers {
+        println!("{}", serde_json::to_string(seller)?);
+    }
+
+    Ok(())
+}
+
+fn sellers_table(sellers: Vec<Seller>) -> Table {
+    let mut table = Table::new();
+
+    table.set_header(vec![
+        "PRICE",
+        "MIN_QUANTITY",
+        "MAX_QUANTITY",
+        "STATUS",
+        "ADDRESS",
+    ]);
+
+    for seller in sellers {
+        let row = match seller.status {
+            SellerStatus::Online(quote) => {
+                vec![
+                    quote.price.to_string(),
+                    quote.min_quantity.to_string(),
+                    quote.max_quantity.to_string(),
+                    "Online".to_owned(),
+                    seller.multiaddr.to_string(),
+                ]
+            }
+            SellerStatus::Unreachable => {
+                vec![
+                    "???".to_owned(),
+                    "???".to_owned(),
+                    "???".to_owned(),
+                    "Unreachable".to_owned(),
+                    seller.multiaddr.to_string(),
+                ]
+            }
+        };
+
+        table.add_row(row);
+    }
+
+    table
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments)]
 async fn init_bitcoin_wallet(
-    electrum_rpc_url: Url,
+    electrum_rpc_urls: Vec<Url>,
     seed: &Seed,
     data_dir: PathBuf,
     env_config: Config,
     bitcoin_target_block: usize,
+    bitcoin_confirmation_targets: ConfirmationTargets,
+    external_wallet: Option<ExternalWallet>,
+    hardware_wallet: Option<Fingerprint>,
 ) -> Result<bitcoin::Wallet> {
-    let wallet_dir = data_dir.join("wallet");
-
-    let wallet = bitcoin::Wallet::new(
-        electrum_rpc_url.clone(),
-        &wallet_dir,
-        seed.derive_extended_private_key(env_config.bitcoin_network)?,
-        env_config,
-        bitcoin_target_block,
-    )
-    .await
+    let wallet = match external_wallet {
+        None => {
+            bitcoin::Wallet::new(
+                &electrum_rpc_urls,
+                &data_dir.join("wallet"),
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+                bitcoin_target_block,
+                bitcoin_confirmation_targets,
+            )
+            .await
+        }
+        Some(external_wallet) => {
+            let wallet_dir = data_dir.join("external-wallets").join(external_wallet.id());
+
+            match external_wallet {
+                ExternalWallet::Xprv(xprv) => {
+                    bitcoin::Wallet::new(
+                        &electrum_rpc_urls,
+                        &wallet_dir,
+                        xprv,
+                        env_config,
+                        bitcoin_target_block,
+                        bitcoin_confirmation_targets,
+                    )
+                    .await
+                }
+                ExternalWallet::Descriptors {
+                    descriptor,
+                    change_descriptor,
+                } => {
+                    bitcoin::Wallet::from_descriptors(
+                        &electrum_rpc_urls,
+                        &wallet_dir,
+                        &descriptor,
+                        change_descriptor.as_deref(),
+                        env_config,
+                        bitcoin_target_block,
+                        bitcoin_confirmation_targets,
+                    )
+                    .await
+                }
+            }
+        }
+    }
     .context("Failed to initialize Bitcoin wallet")?;
 
+    let wallet = match (wallet.is_watch_only(), hardware_wallet) {
+        (true, Some(fingerprint)) => {
+            tracing::info!(%fingerprint, "Signing Bitcoin transactions with hardware wallet");
+            wallet.with_signer(Arc::new(Hwi::new(fingerprint, env_config.bitcoin_network)))
+        }
+        (true, None) => {
+            tracing::info!(
+                "Using watch-only Bitcoin wallet, transactions have to be signed externally"
+            );
+            wallet.with_signer(Arc::new(PsbtPrompt))
+        }
+        (false, Some(_)) => {
+            bail!("The Bitcoin wallet file of a hardware wallet must not contain private keys")
+        }
+        (false, None) => wallet,
+    };
+
     wallet.sync().await?;
 
     Ok(wallet)
@@ -448,13 +1604,64 @@ async fn init_bitcoin_wallet(
 async fn init_monero_wallet(
     data_dir: PathBuf,
     monero_daemon_address: String,
+    monero_light_wallet: bool,
+    monero_auto_update: bool,
+    monero_verify_daemons: Vec<String>,
+    monero_verify_quorum: Option<usize>,
+    env_config: Config,
+) -> Result<(bob::MoneroWallet, Option<monero::WalletRpcProcess>)> {
+    let lock_verifier = if monero_verify_daemons.is_empty() {
+        None
+    } else {
+        let quorum =
+            monero::MonerodQuorum::new(&monero_verify_daemons, monero_verify_quorum, env_config)?;
+        tracing::info!(daemons = ?monero_verify_daemons, "Cross-checking the Monero lock transaction against other monero daemons");
+
+        Some(quorum)
+    };
+
+    if monero_light_wallet {
+        let mut light_wallet =
+            monero::LightWallet::connect(monero_daemon_address.as_str(), env_config).await?;
+        if let Some(lock_verifier) = lock_verifier {
+            light_wallet = light_wallet.with_lock_verifier(lock_verifier);
+        }
+        tracing::info!(
+            "Using Monero light wallet, the Monero will not be transferred to the receive address"
+        );
+
+        return Ok((Arc::new(light_wallet).into(), None));
+    }
+
+    let (mut monero_wallet, monero_wallet_rpc_process) = init_monero_wallet_rpc(
+        data_dir,
+        monero_daemon_address,
+        monero_auto_update,
+        env_config,
+    )
+    .await?;
+    if let Some(lock_verifier) = lock_verifier {
+        monero_wallet = monero_wallet.with_lock_verifier(lock_verifier);
+    }
+
+    Ok((
+        Arc::new(monero_wallet).into(),
+        Some(monero_wallet_rpc_process),
+    ))
+}
+
+async fn init_monero_wallet_rpc(
+    data_dir: PathBuf,
+    monero_daemon_address: String,
+    monero_auto_update: bool,
     env_config: Config,
 ) -> Result<(monero::Wallet, monero::WalletRpcProcess)> {
     let network = env_config.monero_network;
 
     const MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
 
-    let monero_wallet_rpc = monero::WalletRpc::new(data_dir.join("monero")).await?;
+    let monero_wallet_rpc =
+        monero::WalletRpc::new(data_dir.join("monero"), monero_auto_update).await?;
 
     let monero_wallet_rpc_process = monero_wallet_rpc
         .run(network, monero_daemon_address.as_str())
@@ -470,6 +1677,108 @@ async fn init_monero_wallet(
     Ok((monero_wallet, monero_wallet_rpc_process))
 }
 
+/// Prints the keys of the wallet holding the Monero of a swap that was run with
+/// the light wallet, which cannot transfer the Monero to the receive address.
+///
+/// The keys are printed to stdout only, to keep them out of the log files.
+/// Reports the progress of the swap on the terminal, or as JSON if
+/// `--json-progress` is given. No progress is shown if the logs are printed as
+/// JSON, to not mix it into them.
+///
+/// Never completes, the reporting ends with the swap.
+async fn report_progress(
+    swap_id: Uuid,
+    db: Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: bob::MoneroWallet,
+    env_config: Config,
+    json: bool,
+    json_progress: bool,
+) {
+    let output = match (json_progress, json) {
+        (true, _) => progress::Output::Json,
+        (false, false) => progress::Output::Spinner,
+        (false, true) => return std::future::pending().await,
+    };
+
+    if let Err(error) = progress::report(
+        swap_id,
+        db,
+        bitcoin_wallet,
+        monero_wallet,
+        env_config,
+        output,
+    )
+    .await
+    {
+        tracing::warn!("Stopped reporting the progress of the swap: {:#}", error);
+    }
+
+    std::future::pending().await
+}
+
+fn print_redeemed_monero_wallet(
+    swap_id: Uuid,
+    state: &BobState,
+    env_config: Config,
+    json: bool,
+) -> Result<()> {
+    let state5 = match state {
+        BobState::BtcRedeemed(state5) => state5,
+        _ => return Ok(()),
+    };
+
+    let (spend_key, view_key) = state5.xmr_keys();
+    let view_key = monero::PrivateKey::from(view_key);
+    let address = monero::Address::standard(
+        env_config.monero_network,
+        monero::PublicKey::from_private_key(&spend_key),
+        monero::PublicKey::from_private_key(&view_key),
+    );
+    let restore_height = state5.monero_wallet_restore_blockheight.height;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "swap_id": swap_id,
+                "address": address.to_string(),
+                "spend_key": spend_key.to_string(),
+                "view_key": view_key.to_string(),
+                "restore_height": restore_height,
+            })
+        );
+    } else {
+        println!(
+            "The Monero of swap {} has been redeemed. Restore the wallet holding it from these keys in a Monero wallet of your choice, or resume the swap without --monero-light-wallet to transfer it to the receive address:",
+            swap_id
+        );
+        println!("Address:        {}", address);
+        println!("Spend key:      {}", spend_key);
+        println!("View key:       {}", view_key);
+        println!("Restore height: {}", restore_height);
+    }
+
+    Ok(())
+}
+
+/// The price per XMR of a swap in which we sell XMR, computed from the amounts
+/// of the swap because the CLI has no price feed. The swap only logs it.
+fn sell_xmr_rate(state: &AliceState) -> Result<Rate> {
+    let (btc, xmr) = match (state.btc_amount(), state.xmr_amount()) {
+        (Some(btc), Some(xmr)) => (btc, xmr),
+        _ => return Ok(Rate::ZERO),
+    };
+
+    let price = Decimal::from(btc.as_sat())
+        .checked_mul(monero::Amount::ONE_XMR.as_piconero_decimal())
+        .and_then(|sats| sats.checked_div(xmr.as_piconero_decimal()))
+        .and_then(|price| price.to_u64())
+        .context("Failed to compute the price of the swap")?;
+
+    Ok(Rate::new(bitcoin::Amount::from_sat(price), Decimal::ZERO))
+}
+
 fn qr_code(value: &impl ToString) -> Result<String> {
     let code = QrCode::new(value.to_string())?;
     let qr_code = code
@@ -496,7 +1805,6 @@ where
     TS: Future<Output = Result<()>>,
     FS: Fn() -> TS,
 {
-    tracing::debug!("Requesting quote");
     let bid_quote = bid_quote.await?;
     tracing::info!(
         price = %bid_quote.price,
@@ -558,6 +1866,70 @@ where
     Ok((btc_swap_amount, fees))
 }
 
+/// Waits until the internal Monero wallet holds enough Monero to sell at
+/// least the minimum amount and returns the amount to sell.
+///
+/// The Monero fee of the lock transfer is paid on top of the amount, hence it
+/// is kept back from the balance.
+async fn determine_xmr_to_swap<FB, TB>(
+    json: bool,
+    deposit_address: monero::Address,
+    min_quantity: monero::Amount,
+    max_quantity: monero::Amount,
+    balance: FB,
+) -> Result<monero::Amount>
+where
+    TB: Future<Output = Result<monero::Amount>>,
+    FB: Fn() -> TB,
+{
+    let sellable = |balance: monero::Amount| {
+        if balance > monero::MONERO_FEE {
+            balance - monero::MONERO_FEE
+        } else {
+            monero::Amount::ZERO
+        }
+    };
+
+    let mut max_sellable = sellable(balance().await?);
+
+    if max_sellable == monero::Amount::ZERO || max_sellable < min_quantity {
+        if !json {
+            eprintln!("{}", qr_code(&deposit_address)?);
+        }
+
+        loop {
+            tracing::info!(
+                %deposit_address,
+                %max_sellable,
+                minimum_amount = %min_quantity,
+                maximum_amount = %max_quantity,
+                "Waiting for Monero deposit",
+            );
+
+            max_sellable = loop {
+                let new_max_sellable = sellable(balance().await?);
+
+                if new_max_sellable > max_sellable {
+                    break new_max_sellable;
+                }
+
+                tokio::time::sleep(MONERO_DEPOSIT_POLL_INTERVAL).await;
+            };
+
+            tracing::info!(%max_sellable, "Received Monero");
+
+            if max_sellable < min_quantity {
+                tracing::info!("Deposited amount is less than `min_quantity`");
+                continue;
+            }
+
+            break;
+        }
+    }
+
+    Ok(min(max_sellable, max_quantity))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -857,6 +2229,68 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn given_enough_monero_then_sells_balance_minus_fee_up_to_max_quantity() {
+        let xmr = determine_xmr_to_swap(
+            true,
+            dummy_monero_address(),
+            monero::Amount::ZERO,
+            monero::Amount::from_monero(1.0).unwrap(),
+            || async { Ok(monero::Amount::from_monero(0.5).unwrap()) },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            xmr,
+            monero::Amount::from_monero(0.5).unwrap() - monero::MONERO_FEE
+        );
+
+        let xmr = determine_xmr_to_swap(
+            true,
+            dummy_monero_address(),
+            monero::Amount::ZERO,
+            monero::Amount::from_monero(1.0).unwrap(),
+            || async { Ok(monero::Amount::from_monero(2.0).unwrap()) },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(xmr, monero::Amount::from_monero(1.0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn given_deposit_below_min_quantity_then_waits_for_another_deposit() {
+        let writer = capture_logs(LevelFilter::INFO);
+        let balances = Arc::new(Mutex::new(vec![
+            monero::Amount::ZERO,
+            monero::Amount::from_monero(0.05).unwrap(),
+            monero::Amount::from_monero(0.2).unwrap(),
+        ]));
+
+        let xmr = determine_xmr_to_swap(
+            true,
+            dummy_monero_address(),
+            monero::Amount::from_monero(0.1).unwrap(),
+            monero::Amount::from_monero(1.0).unwrap(),
+            || async { Ok(balances.lock().unwrap().remove(0)) },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            xmr,
+            monero::Amount::from_monero(0.2).unwrap() - monero::MONERO_FEE
+        );
+        assert_eq!(
+            writer
+                .captured()
+                .matches("Waiting for Monero deposit")
+                .count(),
+            2
+        );
+    }
+
     struct MaxGiveable {
         amounts: Vec<Amount>,
         call_counter: usize,
@@ -884,6 +2318,7 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::from_btc(btc).unwrap(),
             min_quantity: Amount::ZERO,
+            details: None,
         }
     }
 
@@ -892,10 +2327,17 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::max_value(),
             min_quantity: Amount::from_btc(btc).unwrap(),
+            details: None,
         }
     }
 
     async fn get_dummy_address() -> Result<bitcoin::Address> {
         Ok("1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6".parse()?)
     }
+
+    fn dummy_monero_address() -> monero::Address {
+        "44Ato7HveWidJYUAVw5QffEcEtSH1DwzSP3FPPkHxNAS4LX9CqgucphTisH978FLHE34YNEx7FcbBfQLQUU8m3NUC4VqsRa"
+            .parse()
+            .unwrap()
+    }
 }
@@ -0,0 +1,168 @@
+use crate::price_feed::{PriceFeed, PriceUpdate};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use url::Url;
+
+/// Price feed backed by the Binance websocket API.
+///
+/// ws_url must point to a websocket server that streams `bookTicker` updates
+/// of the XMR/BTC pair following the Binance protocol.
+/// See: https://binance-docs.github.io/apidocs/spot/en/#individual-symbol-book-ticker-streams
+#[derive(Clone, Debug)]
+pub struct Binance {
+    ws_url: Url,
+}
+
+impl Binance {
+    pub fn new(ws_url: Url) -> Self {
+        Self { ws_url }
+    }
+}
+
+#[async_trait]
+impl PriceFeed for Binance {
+    fn name(&self) -> String {
+        "Binance".to_string()
+    }
+
+    async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+        let stream = connection::new(self.ws_url.clone())
+            .await?
+            .map_ok(|update| update.map(|update| PriceUpdate { ask: update.ask }))
+            .err_into()
+            .boxed();
+
+        Ok(stream)
+    }
+}
+
+/// Binance websocket connection module.
+///
+/// Responsible for establishing a connection to the Binance websocket API and
+/// transforming the received websocket frames into a stream of rate updates.
+/// The connection may fail in which case it is simply terminated and the stream
+/// ends.
+mod connection {
+    use super::*;
+    use crate::binance::wire;
+    use tokio_tungstenite::tungstenite;
+
+    /// Connects to the book ticker stream.
+    ///
+    /// Ping frames sent by Binance are forwarded as `None`.
+    pub async fn new(
+        ws_url: Url,
+    ) -> Result<BoxStream<'static, Result<Option<wire::PriceUpdate>, Error>>> {
+        let (rate_stream, _) = tokio_tungstenite::connect_async(ws_url)
+            .await
+            .context("Failed to connect to Binance websocket API")?;
+
+        let stream = rate_stream.err_into().try_filter_map(parse_message).boxed();
+
+        Ok(stream)
+    }
+
+    /// Parse a websocket message into a [`wire::PriceUpdate`].
+    ///
+    /// Pings result in `Some(None)`. All other messages which are not ticker
+    /// updates are ignored and result in `None` being returned. In the context
+    /// of a [`TryStream`], these will simply be filtered out.
+    async fn parse_message(
+        msg: tungstenite::Message,
+    ) -> Result<Option<Option<wire::PriceUpdate>>, Error> {
+        let msg = match msg {
+            tungstenite::Message::Text(msg) => msg,
+            tungstenite::Message::Ping(_) => {
+                tracing::trace!("Received ping message");
+
+                return Ok(Some(None));
+            }
+            tungstenite::Message::Close(close_frame) => {
+                if let Some(tungstenite::protocol::CloseFrame { code, reason }) = close_frame {
+                    tracing::debug!(
+                        "Binance rate stream was closed with code {} and reason: {}",
+                        code,
+                        reason
+                    );
+                } else {
+                    tracing::debug!("Binance rate stream was closed without code and reason");
+                }
+
+                return Err(Error::ConnectionClosed);
+            }
+            msg => {
+                tracing::trace!(
+                    "Binance rate stream returned non text message that will be ignored: {}",
+                    msg
+                );
+
+                return Ok(None);
+            }
+        };
+
+        match serde_json::from_str::<wire::PriceUpdate>(&msg) {
+            Ok(update) => Ok(Some(Some(update))),
+            Err(error) => {
+                tracing::warn!(%msg, "Failed to deserialize message as ticker update. Error {:#}", error);
+                Ok(None)
+            }
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum Error {
+        #[error("The Binance server closed the websocket connection")]
+        ConnectionClosed,
+        #[error("Failed to read message from websocket stream")]
+        WebSocket(#[from] tungstenite::Error),
+    }
+}
+
+/// Binance websocket API wire module.
+///
+/// Responsible for parsing websocket text messages to rate updates.
+mod wire {
+    use super::*;
+    use bitcoin::util::amount::ParseAmountError;
+    use std::convert::TryFrom;
+
+    /// Represents an update within the book ticker.
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(try_from = "BookTicker")]
+    pub struct PriceUpdate {
+        pub ask: bitcoin::Amount,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct BookTicker {
+        #[serde(rename = "a")]
+        ask: String,
+    }
+
+    impl TryFrom<BookTicker> for PriceUpdate {
+        type Error = ParseAmountError;
+
+        fn try_from(value: BookTicker) -> Result<Self, ParseAmountError> {
+            let ask = bitcoin::Amount::from_str_in(&value.ask, ::bitcoin::Denomination::Bitcoin)?;
+
+            Ok(PriceUpdate { ask })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn deserialize_book_ticker_update() {
+            let message = r#"{"u":400900217,"s":"XMRBTC","b":"0.00440000","B":"31.21000000","a":"0.00441000","A":"40.66000000"}"#;
+
+            let update = serde_json::from_str::<PriceUpdate>(message).unwrap();
+
+            assert_eq!(update.ask, bitcoin::Amount::from_sat(441_000));
+        }
+    }
+}
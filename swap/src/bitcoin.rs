@@ -1,17 +1,25 @@
+pub mod signer;
 pub mod wallet;
 
+mod backend;
+mod bitcoind;
 mod cancel;
+mod electrum;
 mod lock;
 mod punish;
 mod redeem;
 mod refund;
 mod timelocks;
 
+pub use crate::bitcoin::backend::Backend;
+pub use crate::bitcoin::bitcoind::{Auth as BitcoindAuth, Bitcoind};
 pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
+pub use crate::bitcoin::electrum::Electrum;
 pub use crate::bitcoin::lock::TxLock;
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
 pub use crate::bitcoin::refund::TxRefund;
+pub use crate::bitcoin::signer::Signer;
 pub use crate::bitcoin::timelocks::{BlockHeight, ExpiredTimelocks};
 pub use ::bitcoin::util::amount::Amount;
 pub use ::bitcoin::util::psbt::PartiallySignedTransaction;
@@ -19,7 +27,7 @@ pub use ::bitcoin::{Address, Network, Transaction, Txid};
 pub use ecdsa_fun::adaptor::EncryptedSignature;
 pub use ecdsa_fun::fun::Scalar;
 pub use ecdsa_fun::Signature;
-pub use wallet::Wallet;
+pub use wallet::{ConfirmationTargets, TxKind, Wallet};
 
 #[cfg(test)]
 pub use wallet::WalletBuilder;
@@ -51,6 +59,16 @@ pub enum network {
     Regtest,
 }
 
+/// The fees of the transactions that spend the locked Bitcoin, as agreed upon
+/// in the swap setup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxFees {
+    pub redeem: Amount,
+    pub cancel: Amount,
+    pub refund: Amount,
+    pub punish: Amount,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct SecretKey {
     inner: Scalar,
@@ -380,11 +398,11 @@ mod tests {
         let xmr_amount = crate::monero::Amount::from_piconero(10000);
 
         let tx_redeem_fee = alice_wallet
-            .estimate_fee(TxRedeem::weight(), btc_amount)
+            .estimate_fee(TxKind::Redeem, TxRedeem::weight(), btc_amount)
             .await
             .unwrap();
         let tx_punish_fee = alice_wallet
-            .estimate_fee(TxPunish::weight(), btc_amount)
+            .estimate_fee(TxKind::Punish, TxPunish::weight(), btc_amount)
             .await
             .unwrap();
         let redeem_address = alice_wallet.new_address().await.unwrap();
@@ -394,7 +412,8 @@ mod tests {
         let alice_state0 = alice::State0::new(
             btc_amount,
             xmr_amount,
-            config,
+            config.bitcoin_cancel_timelock,
+            config.bitcoin_punish_timelock,
             redeem_address,
             punish_address,
             tx_redeem_fee,
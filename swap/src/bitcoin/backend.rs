@@ -0,0 +1,110 @@
+use crate::bitcoin::{Bitcoind, BlockHeight, Electrum};
+use ::bitcoin::{Script, Transaction, Txid};
+use anyhow::{Context, Result};
+use bdk::blockchain::{Blockchain, Capability, Progress};
+use bdk::database::BatchDatabase;
+use bdk::electrum_client::GetHistoryRes;
+use bdk::FeeRate;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// Where the wallet gets its view of the blockchain from.
+#[derive(Clone)]
+pub enum Backend {
+    Electrum(Electrum),
+    Bitcoind(Bitcoind),
+}
+
+impl Backend {
+    pub fn latest_block(&self) -> Result<BlockHeight> {
+        match self {
+            Backend::Electrum(electrum) => electrum.latest_block(),
+            Backend::Bitcoind(bitcoind) => bitcoind.latest_block(),
+        }
+    }
+
+    /// Fetches the history of each of the given scripts, in order.
+    ///
+    /// Electrum servers index all scripts, bitcoind only knows about the
+    /// transactions we are watching for.
+    pub fn script_histories(
+        &self,
+        watched: &BTreeMap<Script, BTreeSet<Txid>>,
+        latest_block: BlockHeight,
+    ) -> Result<Vec<Vec<GetHistoryRes>>> {
+        match self {
+            Backend::Electrum(electrum) => Ok(electrum
+                .batch_script_get_history(watched.keys())
+                .context("Failed to get script histories")?),
+            Backend::Bitcoind(bitcoind) => watched
+                .values()
+                .map(|txids| bitcoind.script_history(txids.iter(), latest_block))
+                .collect(),
+        }
+    }
+
+    /// Returns the estimated fee rate in BTC/kvB, or `-1` if it cannot be
+    /// estimated.
+    pub fn estimate_fee(&self, target_block: usize) -> Result<f64> {
+        match self {
+            Backend::Electrum(electrum) => Ok(electrum.estimate_fee(target_block)?),
+            Backend::Bitcoind(bitcoind) => bitcoind.estimate_fee(target_block),
+        }
+    }
+
+    /// Returns the minimum relay fee in BTC/kvB.
+    pub fn relay_fee(&self) -> Result<f64> {
+        match self {
+            Backend::Electrum(electrum) => Ok(electrum.relay_fee()?),
+            Backend::Bitcoind(bitcoind) => bitcoind.relay_fee(),
+        }
+    }
+}
+
+impl Blockchain for Backend {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        match self {
+            Backend::Electrum(electrum) => electrum.get_capabilities(),
+            Backend::Bitcoind(bitcoind) => bitcoind.get_capabilities(),
+        }
+    }
+
+    fn setup<D: BatchDatabase, P: 'static + Progress>(
+        &self,
+        stop_gap: Option<usize>,
+        database: &mut D,
+        progress_update: P,
+    ) -> Result<(), bdk::Error> {
+        match self {
+            Backend::Electrum(electrum) => electrum.setup(stop_gap, database, progress_update),
+            Backend::Bitcoind(bitcoind) => bitcoind.setup(stop_gap, database, progress_update),
+        }
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        match self {
+            Backend::Electrum(electrum) => electrum.get_tx(txid),
+            Backend::Bitcoind(bitcoind) => bitcoind.get_tx(txid),
+        }
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        match self {
+            Backend::Electrum(electrum) => electrum.broadcast(tx),
+            Backend::Bitcoind(bitcoind) => bitcoind.broadcast(tx),
+        }
+    }
+
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        match self {
+            Backend::Electrum(electrum) => electrum.get_height(),
+            Backend::Bitcoind(bitcoind) => bitcoind.get_height(),
+        }
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        match self {
+            Backend::Electrum(electrum) => Blockchain::estimate_fee(electrum, target),
+            Backend::Bitcoind(bitcoind) => Blockchain::estimate_fee(bitcoind, target),
+        }
+    }
+}
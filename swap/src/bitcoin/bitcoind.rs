@@ -0,0 +1,222 @@
+//! Syncing the wallet and watching transactions through a bitcoind node
+//! instead of an Electrum server.
+//!
+//! The wallet is synced through a watch-only wallet bdk creates in bitcoind.
+//! Transactions that don't belong to the wallet, such as the cancel or redeem
+//! transaction of a swap, are looked up by their txid, hence bitcoind has to
+//! run with `-txindex`.
+
+use crate::bitcoin::BlockHeight;
+use ::bitcoin::{Network, Transaction, Txid};
+use anyhow::{Context, Result};
+use bdk::bitcoincore_rpc::jsonrpc::error::Error as JsonRpcError;
+use bdk::bitcoincore_rpc::{self, Client, RpcApi};
+use bdk::blockchain::rpc::{RpcBlockchain, RpcConfig};
+use bdk::blockchain::{Blockchain, Capability, ConfigurableBlockchain, Progress};
+use bdk::database::BatchDatabase;
+use bdk::electrum_client::GetHistoryRes;
+use bdk::FeeRate;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+pub use bdk::bitcoincore_rpc::Auth;
+
+/// The error code bitcoind returns for transactions it does not know about.
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+/// A connection to a bitcoind node.
+#[derive(Clone)]
+pub struct Bitcoind {
+    /// Used by bdk to sync the wallet and broadcast transactions.
+    blockchain: Arc<RpcBlockchain>,
+    /// Used for the requests of the wallet's [`Client`](super::wallet::Client).
+    client: Arc<Client>,
+}
+
+impl Bitcoind {
+    /// Connects to bitcoind and loads the watch-only wallet with the given
+    /// name, creating it if it does not exist yet.
+    ///
+    /// When the wallet is created bitcoind rescans the blockchain for
+    /// transactions of the wallet, starting at `rescan_from_height` or the
+    /// genesis block.
+    pub fn connect(
+        rpc_url: &Url,
+        auth: Auth,
+        wallet_name: &str,
+        network: Network,
+        rescan_from_height: Option<u32>,
+    ) -> Result<Self> {
+        // bdk appends the wallet path to the URL
+        let url = rpc_url.as_str().trim_end_matches('/').to_string();
+
+        let blockchain = RpcBlockchain::from_config(&RpcConfig {
+            url: url.clone(),
+            auth: auth.clone(),
+            network,
+            wallet_name: wallet_name.to_string(),
+            skip_blocks: rescan_from_height,
+        })
+        .context("Failed to load wallet in bitcoind")?;
+        let client = Client::new(url, auth).context("Failed to initialize bitcoind RPC client")?;
+
+        Ok(Self {
+            blockchain: Arc::new(blockchain),
+            client: Arc::new(client),
+        })
+    }
+
+    pub fn latest_block(&self) -> Result<BlockHeight> {
+        let block_count: u32 = self
+            .client
+            .call("getblockcount", &[])
+            .context("Failed to get block count from bitcoind")?;
+
+        Ok(BlockHeight::from(block_count))
+    }
+
+    /// Builds the history of a script from the status of the given
+    /// transactions, in the format Electrum servers return it.
+    pub fn script_history<'t>(
+        &self,
+        txids: impl Iterator<Item = &'t Txid>,
+        latest_block: BlockHeight,
+    ) -> Result<Vec<GetHistoryRes>> {
+        let mut history = Vec::new();
+
+        for txid in txids {
+            let info = match self
+                .client
+                .call::<RawTransactionInfo>("getrawtransaction", &[json!(txid), json!(true)])
+            {
+                Ok(info) => info,
+                Err(error) if is_unknown_transaction(&error) => continue,
+                Err(error) => return Err(error).context("Failed to get transaction from bitcoind"),
+            };
+
+            history.push(GetHistoryRes {
+                height: inclusion_height(info.confirmations, latest_block)?,
+                tx_hash: *txid,
+                fee: None,
+            });
+        }
+
+        Ok(history)
+    }
+
+    /// Returns the estimated fee rate in BTC/kvB, or `-1` if bitcoind cannot
+    /// estimate the fee rate, like Electrum servers do.
+    pub fn estimate_fee(&self, target_block: usize) -> Result<f64> {
+        let estimate: SmartFeeEstimate = self
+            .client
+            .call("estimatesmartfee", &[json!(target_block)])
+            .context("Failed to estimate fee rate with bitcoind")?;
+
+        Ok(estimate.feerate.unwrap_or(-1.0))
+    }
+
+    /// Returns the minimum relay fee in BTC/kvB.
+    pub fn relay_fee(&self) -> Result<f64> {
+        let info: NetworkInfo = self
+            .client
+            .call("getnetworkinfo", &[])
+            .context("Failed to get network info from bitcoind")?;
+
+        Ok(info.relayfee)
+    }
+}
+
+impl Blockchain for Bitcoind {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        self.blockchain.get_capabilities()
+    }
+
+    fn setup<D: BatchDatabase, P: 'static + Progress>(
+        &self,
+        stop_gap: Option<usize>,
+        database: &mut D,
+        progress_update: P,
+    ) -> Result<(), bdk::Error> {
+        self.blockchain.setup(stop_gap, database, progress_update)
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        self.blockchain.get_tx(txid)
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        self.blockchain.broadcast(tx)
+    }
+
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        self.blockchain.get_height()
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        self.blockchain.estimate_fee(target)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTransactionInfo {
+    /// Missing for transactions in the mempool.
+    #[serde(default)]
+    confirmations: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SmartFeeEstimate {
+    /// Missing if there is not enough data to estimate the fee rate.
+    feerate: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NetworkInfo {
+    relayfee: f64,
+}
+
+fn is_unknown_transaction(error: &bitcoincore_rpc::Error) -> bool {
+    matches!(
+        error,
+        bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(error)) if error.code == RPC_INVALID_ADDRESS_OR_KEY
+    )
+}
+
+/// Electrum reports the height of the block a transaction was included in, or
+/// `0` for transactions in the mempool.
+fn inclusion_height(confirmations: u32, latest_block: BlockHeight) -> Result<i32> {
+    let height = match confirmations {
+        0 => 0,
+        confirmations => (u32::from(latest_block) + 1).saturating_sub(confirmations),
+    };
+
+    Ok(i32::try_from(height)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::Confirmed;
+
+    #[test]
+    fn transaction_in_mempool_has_height_zero() {
+        assert_eq!(inclusion_height(0, BlockHeight::from(700_000)).unwrap(), 0);
+    }
+
+    #[test]
+    fn inclusion_height_roundtrips_to_confirmations() {
+        let latest_block = BlockHeight::from(700_000);
+
+        let height = inclusion_height(3, latest_block).unwrap();
+        let confirmed = Confirmed::from_inclusion_and_latest_block(
+            u32::try_from(height).unwrap(),
+            u32::from(latest_block),
+        );
+
+        assert_eq!(confirmed.confirmations(), 3);
+    }
+}
@@ -0,0 +1,365 @@
+//! Failover between several Electrum servers.
+//!
+//! All requests go to the active server. If it fails with a connection error,
+//! the remaining servers are tried in the order they were configured in and
+//! the first one that answers becomes the active server. When probing the
+//! servers for the latest block, a server that fell behind the best known tip
+//! is replaced as well.
+
+use crate::bitcoin::BlockHeight;
+use ::bitcoin::{Script, Transaction, Txid};
+use anyhow::{bail, Context, Result};
+use bdk::blockchain::{noop_progress, Blockchain, Capability, ElectrumBlockchain, Progress};
+use bdk::database::BatchDatabase;
+use bdk::electrum_client::{self, ElectrumApi, GetHistoryRes};
+use bdk::FeeRate;
+use reqwest::Url;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// How many blocks the active server may lag behind the best known tip before
+/// we switch to another server.
+const MAX_BLOCKS_BEHIND: u32 = 1;
+
+/// Timeout for requests to an Electrum server, in seconds.
+const TIMEOUT_SECS: u8 = 10;
+
+/// A set of Electrum servers of which one is active at a time.
+#[derive(Clone)]
+pub struct Electrum {
+    servers: Arc<Vec<Server>>,
+    active: Arc<AtomicUsize>,
+}
+
+struct Server {
+    url: Url,
+    /// Used for the requests of the wallet's [`Client`](super::wallet::Client).
+    client: electrum_client::Client,
+    /// Used by bdk to sync the wallet and broadcast transactions.
+    blockchain: ElectrumBlockchain,
+}
+
+impl Electrum {
+    /// Connects to the given Electrum servers, the first one that can be
+    /// connected to becomes the active server.
+    ///
+    /// Servers that cannot be connected to are skipped. Fails if none of the
+    /// servers can be connected to.
+    pub fn connect(urls: &[Url]) -> Result<Self> {
+        if urls.is_empty() {
+            bail!("At least one Electrum server has to be configured")
+        }
+
+        // With other servers to fail over to, don't waste time retrying a
+        // failing server.
+        let retries = if urls.len() > 1 { 1 } else { 5 };
+
+        let mut servers = Vec::new();
+        for url in urls {
+            match Server::connect(url, retries) {
+                Ok(server) => servers.push(server),
+                Err(error) => {
+                    tracing::warn!(electrum_rpc_url = %url, "Failed to connect to Electrum server: {:#}", error)
+                }
+            }
+        }
+
+        if servers.is_empty() {
+            bail!("Failed to connect to any of the configured Electrum servers")
+        }
+
+        Ok(Self {
+            servers: Arc::new(servers),
+            active: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    fn active_server(&self) -> &Server {
+        &self.servers[self.active.load(Ordering::SeqCst)]
+    }
+
+    fn switch_to(&self, index: usize) {
+        let previous = self.active.swap(index, Ordering::SeqCst);
+
+        if previous != index {
+            tracing::info!(
+                from = %self.servers[previous].url,
+                to = %self.servers[index].url,
+                "Switched Electrum server"
+            );
+        }
+    }
+
+    /// Runs the given request against the active server, failing over to the
+    /// other servers if it fails with a connection error.
+    ///
+    /// Other errors, such as a transaction being rejected, are returned as is.
+    fn request<T, E>(
+        &self,
+        mut request: impl FnMut(&Server) -> Result<T, E>,
+        is_connection_error: fn(&E) -> bool,
+    ) -> Result<T, E>
+    where
+        E: fmt::Display,
+    {
+        let active = self.active.load(Ordering::SeqCst);
+        let mut result = request(&self.servers[active]);
+
+        for offset in 1..self.servers.len() {
+            match &result {
+                Err(error) if is_connection_error(error) => {
+                    let failed = &self.servers[(active + offset - 1) % self.servers.len()];
+                    tracing::warn!(electrum_rpc_url = %failed.url, "Request to Electrum server failed, trying next server: {}", error);
+                }
+                _ => break,
+            }
+
+            let index = (active + offset) % self.servers.len();
+            result = request(&self.servers[index]);
+
+            if result.is_ok() {
+                self.switch_to(index);
+            }
+        }
+
+        result
+    }
+
+    /// Fetches the latest block from all servers and returns the best known
+    /// tip.
+    ///
+    /// If the active server is unreachable or lags behind the best known tip,
+    /// the first server that is at the tip becomes the active server.
+    pub fn latest_block(&self) -> Result<BlockHeight> {
+        let heights = self
+            .servers
+            .iter()
+            .map(|server| match server.latest_block() {
+                Ok(height) => Some(height),
+                Err(error) => {
+                    tracing::debug!(electrum_rpc_url = %server.url, "Failed to fetch latest block: {:#}", error);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let active = self.active.load(Ordering::SeqCst);
+        let block_numbers = heights
+            .iter()
+            .map(|height| height.map(u32::from))
+            .collect::<Vec<_>>();
+        if let Some(index) = select_server(active, &block_numbers) {
+            if index != active {
+                tracing::warn!(
+                    electrum_rpc_url = %self.servers[active].url,
+                    "Electrum server is unreachable or lags behind the best known tip"
+                );
+            }
+            self.switch_to(index);
+        }
+
+        heights
+            .into_iter()
+            .flatten()
+            .max()
+            .context("Failed to fetch the latest block from any Electrum server")
+    }
+
+    pub fn batch_script_get_history<'s>(
+        &self,
+        scripts: impl Iterator<Item = &'s Script> + Clone,
+    ) -> Result<Vec<Vec<GetHistoryRes>>, electrum_client::Error> {
+        self.request(
+            |server| server.client.batch_script_get_history(scripts.clone()),
+            is_connection_error,
+        )
+    }
+
+    pub fn estimate_fee(&self, target_block: usize) -> Result<f64, electrum_client::Error> {
+        self.request(
+            |server| server.client.estimate_fee(target_block),
+            is_connection_error,
+        )
+    }
+
+    pub fn relay_fee(&self) -> Result<f64, electrum_client::Error> {
+        self.request(|server| server.client.relay_fee(), is_connection_error)
+    }
+}
+
+impl Server {
+    fn connect(url: &Url, retries: u8) -> Result<Self> {
+        let connect = || -> Result<electrum_client::Client> {
+            let config = electrum_client::ConfigBuilder::default()
+                .retry(retries)
+                .timeout(Some(TIMEOUT_SECS))?
+                .build();
+
+            electrum_client::Client::from_config(url.as_str(), config)
+                .context("Failed to initialize Electrum RPC client")
+        };
+        let blockchain_client = connect()?;
+        let client = connect()?;
+
+        Ok(Self {
+            url: url.clone(),
+            client,
+            blockchain: ElectrumBlockchain::from(blockchain_client),
+        })
+    }
+
+    fn latest_block(&self) -> Result<BlockHeight> {
+        // We do not act on this subscription, as we cannot rely on
+        // subscription push notifications because eventually the Electrum
+        // server will close the connection and subscriptions are not
+        // automatically renewed upon renewing the connection.
+        let latest_block = self
+            .client
+            .block_headers_subscribe()
+            .context("Failed to subscribe to header notifications")?;
+
+        BlockHeight::try_from(latest_block)
+    }
+}
+
+impl Blockchain for Electrum {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        self.active_server().blockchain.get_capabilities()
+    }
+
+    fn setup<D: BatchDatabase, P: 'static + Progress>(
+        &self,
+        stop_gap: Option<usize>,
+        database: &mut D,
+        progress_update: P,
+    ) -> Result<(), bdk::Error> {
+        // The progress can only be reported once, syncs against the servers
+        // we fail over to are not reported.
+        let mut progress_update = Some(progress_update);
+
+        self.request(
+            |server| match progress_update.take() {
+                Some(progress_update) => {
+                    server
+                        .blockchain
+                        .setup(stop_gap, &mut *database, progress_update)
+                }
+                None => server
+                    .blockchain
+                    .setup(stop_gap, &mut *database, noop_progress()),
+            },
+            is_bdk_connection_error,
+        )
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        self.request(
+            |server| server.blockchain.get_tx(txid),
+            is_bdk_connection_error,
+        )
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        self.request(
+            |server| server.blockchain.broadcast(tx),
+            is_bdk_connection_error,
+        )
+    }
+
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        self.request(
+            |server| server.blockchain.get_height(),
+            is_bdk_connection_error,
+        )
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        self.request(
+            |server| server.blockchain.estimate_fee(target),
+            is_bdk_connection_error,
+        )
+    }
+}
+
+/// Protocol errors are responses of the server, e.g. to a transaction that was
+/// rejected, and would be the same on any other server.
+fn is_connection_error(error: &electrum_client::Error) -> bool {
+    !matches!(error, electrum_client::Error::Protocol(_))
+}
+
+fn is_bdk_connection_error(error: &bdk::Error) -> bool {
+    matches!(error, bdk::Error::Electrum(error) if is_connection_error(error))
+}
+
+/// Returns the server that should be active given the latest block height
+/// reported by each server, or `None` if none of them answered.
+///
+/// The active server is kept as long as it is not more than
+/// [`MAX_BLOCKS_BEHIND`] blocks behind the best known tip, otherwise the first
+/// server at the tip is selected.
+fn select_server(active: usize, heights: &[Option<u32>]) -> Option<usize> {
+    let best = heights.iter().flatten().max().copied()?;
+
+    match heights.get(active) {
+        Some(Some(height)) if height + MAX_BLOCKS_BEHIND >= best => Some(active),
+        _ => heights.iter().position(|height| *height == Some(best)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_active_server_is_at_tip_then_keeps_it() {
+        let heights = [Some(100), Some(100)];
+
+        assert_eq!(select_server(1, &heights), Some(1));
+    }
+
+    #[test]
+    fn given_active_server_lags_one_block_behind_then_keeps_it() {
+        let heights = [Some(101), Some(100)];
+
+        assert_eq!(select_server(1, &heights), Some(1));
+    }
+
+    #[test]
+    fn given_active_server_lags_behind_then_switches_to_server_at_tip() {
+        let heights = [Some(100), Some(105), Some(105)];
+
+        assert_eq!(select_server(0, &heights), Some(1));
+    }
+
+    #[test]
+    fn given_active_server_is_unreachable_then_switches_to_reachable_server() {
+        let heights = [None, Some(100)];
+
+        assert_eq!(select_server(0, &heights), Some(1));
+    }
+
+    #[test]
+    fn given_no_server_is_reachable_then_selects_none() {
+        let heights = [None, None];
+
+        assert_eq!(select_server(0, &heights), None);
+    }
+
+    #[test]
+    fn protocol_errors_are_no_connection_errors() {
+        let rejected = electrum_client::Error::Protocol(serde_json::Value::String(
+            "sendrawtransaction RPC error".to_string(),
+        ));
+        let disconnected = electrum_client::Error::IOError(std::io::Error::from(
+            std::io::ErrorKind::ConnectionReset,
+        ));
+
+        assert!(!is_connection_error(&rejected));
+        assert!(is_connection_error(&disconnected));
+        assert!(!is_bdk_connection_error(&bdk::Error::Electrum(rejected)));
+        assert!(is_bdk_connection_error(&bdk::Error::Electrum(disconnected)));
+    }
+}
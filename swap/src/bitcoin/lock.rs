@@ -1,4 +1,4 @@
-use crate::bitcoin::wallet::{EstimateFeeRate, Watchable};
+use crate::bitcoin::wallet::{EstimateFeeRate, TxKind, Watchable};
 use crate::bitcoin::{
     build_shared_output_descriptor, Address, Amount, PublicKey, Transaction, Wallet,
 };
@@ -36,7 +36,12 @@ impl TxLock {
             .expect("can derive address from descriptor");
 
         let psbt = wallet
-            .send_to_address(address, amount, Some(change))
+            .send_to_address_within(
+                address,
+                amount,
+                Some(change),
+                wallet.confirmation_target(TxKind::Lock),
+            )
             .await?;
 
         Ok(Self {
@@ -53,11 +53,26 @@ impl TxRedeem {
     }
 
     pub fn complete(
-        mut self,
+        self,
         encrypted_signature: EncryptedSignature,
         a: SecretKey,
         s_a: Scalar,
         B: PublicKey,
+    ) -> Result<Transaction> {
+        let sig_a = a.sign(self.digest());
+
+        self.complete_with_signature(encrypted_signature, a.public(), sig_a, s_a, B)
+    }
+
+    /// Completes the transaction with a signature of Alice that was made
+    /// elsewhere, e.g. by an external signer.
+    pub fn complete_with_signature(
+        mut self,
+        encrypted_signature: EncryptedSignature,
+        A: PublicKey,
+        sig_a: Signature,
+        s_a: Scalar,
+        B: PublicKey,
     ) -> Result<Transaction> {
         verify_encsig(
             B,
@@ -67,7 +82,6 @@ impl TxRedeem {
         )
         .context("Invalid encrypted signature received")?;
 
-        let sig_a = a.sign(self.digest());
         let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
         let sig_b = adaptor.decrypt_signature(&s_a, encrypted_signature);
 
@@ -76,7 +90,7 @@ impl TxRedeem {
 
             let A = ::bitcoin::PublicKey {
                 compressed: true,
-                key: a.public.into(),
+                key: A.0.into(),
             };
             let B = ::bitcoin::PublicKey {
                 compressed: true,
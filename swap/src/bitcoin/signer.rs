@@ -0,0 +1,28 @@
+use crate::bitcoin::Txid;
+use ::bitcoin::util::psbt::PartiallySignedTransaction;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+
+/// Signs the inputs of a transaction that spend from a wallet whose keys are
+/// not known to us, e.g. because they are kept on a hardware wallet.
+///
+/// The signed PSBT has to spend and pay exactly what the unsigned one does, it
+/// is finalized by the wallet.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction>;
+}
+
+pub fn ensure_same_transaction(txid: Txid, signed: &PartiallySignedTransaction) -> Result<()> {
+    let signed_txid = signed.global.unsigned_tx.txid();
+
+    if signed_txid != txid {
+        bail!(
+            "Signed PSBT is for transaction {} but expected transaction {}",
+            signed_txid,
+            txid
+        );
+    }
+
+    Ok(())
+}
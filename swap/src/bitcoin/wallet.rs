@@ -1,13 +1,15 @@
+use crate::bitcoin::signer::{ensure_same_transaction, Signer};
 use crate::bitcoin::timelocks::BlockHeight;
-use crate::bitcoin::{Address, Amount, Transaction};
+use crate::bitcoin::{Address, Amount, Backend, Bitcoind, Electrum, Transaction};
 use crate::env;
+use ::bitcoin::secp256k1::Secp256k1;
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
-use ::bitcoin::Txid;
+use ::bitcoin::{OutPoint, Txid};
 use anyhow::{bail, Context, Result};
-use bdk::blockchain::{noop_progress, Blockchain, ElectrumBlockchain};
+use bdk::blockchain::{noop_progress, Blockchain};
 use bdk::database::BatchDatabase;
-use bdk::descriptor::Segwitv0;
-use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
+use bdk::descriptor::{IntoWalletDescriptor, Segwitv0};
+use bdk::electrum_client::GetHistoryRes;
 use bdk::keys::DerivableKey;
 use bdk::wallet::export::WalletExport;
 use bdk::wallet::AddressIndex;
@@ -17,7 +19,7 @@ use reqwest::Url;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::fmt;
 use std::path::Path;
@@ -33,52 +35,180 @@ const MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
 const MAX_ABSOLUTE_TX_FEE: Decimal = dec!(100_000);
 const DUST_AMOUNT: u64 = 546;
 
-pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
+/// The fee rate in sat/vB used on test networks if the Electrum server cannot
+/// estimate the fee rate, which happens frequently because there is not
+/// enough fee data on these networks.
+const TESTNET_FALLBACK_FEE_RATE: f32 = 1.0;
+
+/// The kinds of transactions published during a swap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TxKind {
+    Lock,
+    Cancel,
+    Refund,
+    Redeem,
+    Punish,
+}
+
+/// The number of blocks within which each kind of swap transaction should be
+/// confirmed, used to estimate its fee.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfirmationTargets {
+    pub lock: usize,
+    pub cancel: usize,
+    pub refund: usize,
+    pub redeem: usize,
+    pub punish: usize,
+}
+
+impl ConfirmationTargets {
+    /// Uses the same confirmation target for all kinds of transactions.
+    pub fn uniform(target_block: usize) -> Self {
+        Self {
+            lock: target_block,
+            cancel: target_block,
+            refund: target_block,
+            redeem: target_block,
+            punish: target_block,
+        }
+    }
+
+    pub fn of(&self, kind: TxKind) -> usize {
+        match kind {
+            TxKind::Lock => self.lock,
+            TxKind::Cancel => self.cancel,
+            TxKind::Refund => self.refund,
+            TxKind::Redeem => self.redeem,
+            TxKind::Punish => self.punish,
+        }
+    }
+}
+
+pub struct Wallet<B = Backend, D = bdk::sled::Tree, C = Client> {
     client: Arc<Mutex<C>>,
     wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
     finality_confirmations: u32,
     network: Network,
     target_block: usize,
+    confirmation_targets: ConfirmationTargets,
+    watch_only: bool,
+    /// Signs the transactions of a watch-only wallet.
+    signer: Option<Arc<dyn Signer>>,
 }
 
 impl Wallet {
     pub async fn new(
-        electrum_rpc_url: Url,
+        electrum_rpc_urls: &[Url],
         wallet_dir: &Path,
         key: impl DerivableKey<Segwitv0> + Clone,
         env_config: env::Config,
         target_block: usize,
+        confirmation_targets: ConfirmationTargets,
     ) -> Result<Self> {
-        let config = bdk::electrum_client::ConfigBuilder::default()
-            .retry(5)
-            .build();
-        let client = bdk::electrum_client::Client::from_config(electrum_rpc_url.as_str(), config)
-            .context("Failed to initialize Electrum RPC client")?;
+        Self::with_descriptors(
+            Backend::Electrum(Electrum::connect(electrum_rpc_urls)?),
+            wallet_dir,
+            bdk::template::Bip84(key.clone(), KeychainKind::External),
+            Some(bdk::template::Bip84(key, KeychainKind::Internal)),
+            env_config,
+            target_block,
+            confirmation_targets,
+        )
+        .await
+    }
 
-        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+    /// Creates a wallet from the given output descriptors for receive and
+    /// change addresses.
+    ///
+    /// If the descriptors don't contain any private keys the wallet is
+    /// watch-only, i.e. transactions have to be signed elsewhere.
+    pub async fn from_descriptors(
+        electrum_rpc_urls: &[Url],
+        wallet_dir: &Path,
+        descriptor: &str,
+        change_descriptor: Option<&str>,
+        env_config: env::Config,
+        target_block: usize,
+        confirmation_targets: ConfirmationTargets,
+    ) -> Result<Self> {
+        let (_, keys) = descriptor
+            .into_wallet_descriptor(&Secp256k1::new(), env_config.bitcoin_network)
+            .context("Failed to parse output descriptor")?;
+
+        let mut wallet = Self::with_descriptors(
+            Backend::Electrum(Electrum::connect(electrum_rpc_urls)?),
+            wallet_dir,
+            descriptor,
+            change_descriptor,
+            env_config,
+            target_block,
+            confirmation_targets,
+        )
+        .await?;
+        wallet.watch_only = keys.is_empty();
 
-        let wallet = bdk::Wallet::new(
+        Ok(wallet)
+    }
+
+    /// Creates a wallet like [`Wallet::new`] that uses bitcoind instead of
+    /// Electrum servers.
+    pub async fn with_bitcoind(
+        bitcoind: Bitcoind,
+        wallet_dir: &Path,
+        key: impl DerivableKey<Segwitv0> + Clone,
+        env_config: env::Config,
+        target_block: usize,
+        confirmation_targets: ConfirmationTargets,
+    ) -> Result<Self> {
+        Self::with_descriptors(
+            Backend::Bitcoind(bitcoind),
+            wallet_dir,
             bdk::template::Bip84(key.clone(), KeychainKind::External),
             Some(bdk::template::Bip84(key, KeychainKind::Internal)),
+            env_config,
+            target_block,
+            confirmation_targets,
+        )
+        .await
+    }
+
+    async fn with_descriptors<E>(
+        backend: Backend,
+        wallet_dir: &Path,
+        descriptor: E,
+        change_descriptor: Option<E>,
+        env_config: env::Config,
+        target_block: usize,
+        confirmation_targets: ConfirmationTargets,
+    ) -> Result<Self>
+    where
+        E: IntoWalletDescriptor,
+    {
+        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+
+        let wallet = bdk::Wallet::new(
+            descriptor,
+            change_descriptor,
             env_config.bitcoin_network,
             db,
-            ElectrumBlockchain::from(client),
+            backend.clone(),
         )?;
 
-        let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
-
         let network = wallet.network();
 
         Ok(Self {
             client: Arc::new(Mutex::new(Client::new(
-                electrum,
+                backend,
                 env_config.bitcoin_sync_interval(),
+                fallback_fee_rate(network),
             )?)),
             wallet: Arc::new(Mutex::new(wallet)),
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
             target_block,
+            confirmation_targets,
+            watch_only: false,
+            signer: None,
         })
     }
 
@@ -148,8 +278,10 @@ impl Wallet {
                         let new_status = match client.lock().await.status_of_script(&tx) {
                             Ok(new_status) => new_status,
                             Err(error) => {
+                                // All Electrum servers failed, keep the subscription alive
+                                // until one of them is back.
                                 tracing::warn!(%txid, "Failed to get status of script: {:#}", error);
-                                return;
+                                continue;
                             }
                         };
 
@@ -176,6 +308,17 @@ impl Wallet {
         sub
     }
 
+    /// Measures the round trip time of a single request to the Electrum
+    /// server or bitcoind node.
+    pub async fn backend_latency(&self) -> Result<Duration> {
+        let client = self.client.lock().await;
+
+        let start = Instant::now();
+        client.backend.relay_fee()?;
+
+        Ok(start.elapsed())
+    }
+
     pub async fn wallet_export(&self, role: &str) -> Result<WalletExport> {
         let wallet = self.wallet.lock().await;
         match bdk::wallet::export::WalletExport::export_wallet(
@@ -194,6 +337,11 @@ fn print_status_change(txid: Txid, old: Option<ScriptStatus>, new: ScriptStatus)
         (None, new_status) => {
             tracing::debug!(%txid, status = %new_status, "Found relevant Bitcoin transaction");
         }
+        (Some(old_status), new_status)
+            if new_status.confirmations() < old_status.confirmations() =>
+        {
+            tracing::warn!(%txid, %new_status, %old_status, "Bitcoin transaction lost confirmations in a chain reorganization");
+        }
         (Some(old_status), new_status) if old_status != new_status => {
             tracing::debug!(%txid, %new_status, %old_status, "Bitcoin transaction status changed");
         }
@@ -224,7 +372,7 @@ impl Subscription {
             ScriptStatus::Confirmed(inner) => {
                 let confirmations = inner.confirmations();
 
-                if confirmations > seen_confirmations {
+                if confirmations != seen_confirmations {
                     tracing::info!(%txid,
                         seen_confirmations = %confirmations,
                         needed_confirmations = %conf_target,
@@ -234,7 +382,12 @@ impl Subscription {
 
                 inner.meets_target(conf_target)
             }
-            _ => false,
+            _ => {
+                // not included in a block (anymore, after a reorg), report
+                // the confirmations from the start once it is included again
+                seen_confirmations = 0;
+                false
+            }
         })
         .await
     }
@@ -271,10 +424,28 @@ where
     C: EstimateFeeRate,
     D: BatchDatabase,
 {
-    pub async fn sign_and_finalize(
-        &self,
-        mut psbt: PartiallySignedTransaction,
-    ) -> Result<Transaction> {
+    pub async fn sign_and_finalize(&self, psbt: PartiallySignedTransaction) -> Result<Transaction> {
+        let mut psbt = match &self.signer {
+            Some(signer) => {
+                let txid = psbt.global.unsigned_tx.txid();
+                let signed = signer.sign(psbt).await?;
+                ensure_same_transaction(txid, &signed)?;
+
+                signed
+            }
+            None => psbt,
+        };
+
+        // External signers may already have finalized the inputs and dropped
+        // the partial signatures the wallet would finalize them from.
+        let already_finalized = !psbt.inputs.is_empty()
+            && psbt.inputs.iter().all(|input| {
+                input.final_script_witness.is_some() || input.final_script_sig.is_some()
+            });
+        if already_finalized {
+            return Ok(psbt.extract_tx());
+        }
+
         let finalized = self
             .wallet
             .lock()
@@ -337,6 +508,19 @@ where
         address: Address,
         amount: Amount,
         change_override: Option<Address>,
+    ) -> Result<PartiallySignedTransaction> {
+        self.send_to_address_within(address, amount, change_override, self.target_block)
+            .await
+    }
+
+    /// Like [`Wallet::send_to_address`], but with the fee chosen such
+    /// that the transaction is confirmed within `target_block` blocks.
+    pub async fn send_to_address_within(
+        &self,
+        address: Address,
+        amount: Amount,
+        change_override: Option<Address>,
+        target_block: usize,
     ) -> Result<PartiallySignedTransaction> {
         if self.network != address.network {
             bail!("Cannot build PSBT because network of given address is {} but wallet is on network {}", address.network, self.network);
@@ -350,7 +534,7 @@ where
 
         let wallet = self.wallet.lock().await;
         let client = self.client.lock().await;
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let fee_rate = client.estimate_feerate(target_block)?;
         let script = address.script_pubkey();
 
         let mut tx_builder = wallet.build_tx();
@@ -388,6 +572,87 @@ where
         Ok(psbt)
     }
 
+    /// Builds a transaction that spends output `vout` of the given unconfirmed
+    /// transaction back into this wallet.
+    ///
+    /// The fee of the child transaction is chosen such that parent and child
+    /// together pay the fee rate required for confirmation within the target
+    /// block (child pays for parent). Returns `None` if the parent already
+    /// pays this fee rate on its own.
+    pub async fn child_pays_for_parent(
+        &self,
+        parent: &Transaction,
+        parent_fee: Amount,
+        vout: u32,
+    ) -> Result<Option<PartiallySignedTransaction>> {
+        let wallet = self.wallet.lock().await;
+        let client = self.client.lock().await;
+        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let sats_per_vbyte =
+            Decimal::from_f32(fee_rate.as_sat_vb()).context("Failed to parse fee rate")?;
+
+        let parent_vbytes = Decimal::from(parent.get_weight()) / dec!(4.0);
+        let parent_fee = Decimal::from(parent_fee.as_sat());
+        if parent_fee >= parent_vbytes * sats_per_vbyte {
+            return Ok(None);
+        }
+
+        let outpoint = OutPoint::new(parent.txid(), vout);
+        let drain_script = wallet
+            .get_address(AddressIndex::New)
+            .context("Failed to get new Bitcoin address")?
+            .address
+            .script_pubkey();
+
+        // Build the child at the target fee rate first to learn its size.
+        let mut tx_builder = wallet.build_tx();
+        tx_builder
+            .add_utxo(outpoint)
+            .with_context(|| format!("Output {} is not owned by the wallet", outpoint))?;
+        tx_builder.manually_selected_only();
+        tx_builder.drain_to(drain_script.clone());
+        tx_builder.fee_rate(fee_rate);
+        let (_, details) = tx_builder.finish()?;
+        let child_fee = details
+            .fee
+            .expect("fees are always present with Electrum backend");
+        let child_vbytes = Decimal::from(child_fee) / sats_per_vbyte;
+
+        let package_fee = (parent_vbytes + child_vbytes) * sats_per_vbyte;
+        let fee = (package_fee - parent_fee)
+            .ceil()
+            .to_u64()
+            .context("Failed to fit fee into u64")?;
+
+        let mut tx_builder = wallet.build_tx();
+        tx_builder.add_utxo(outpoint)?;
+        tx_builder.manually_selected_only();
+        tx_builder.drain_to(drain_script);
+        tx_builder.fee_absolute(fee);
+        let (psbt, _) = tx_builder.finish().with_context(|| {
+            format!(
+                "Failed to build child transaction paying {} sats in fees",
+                fee
+            )
+        })?;
+
+        Ok(Some(psbt))
+    }
+
+    /// Returns the index of the first output of the given transaction that
+    /// pays to this wallet, if any.
+    pub async fn owned_output(&self, tx: &Transaction) -> Result<Option<u32>> {
+        let wallet = self.wallet.lock().await;
+
+        for (vout, output) in tx.output.iter().enumerate() {
+            if wallet.is_mine(&output.script_pubkey)? {
+                return Ok(Some(u32::try_from(vout)?));
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Calculates the maximum "giveable" amount of this wallet.
     ///
     /// We define this as the maximum amount we can pay to a single output,
@@ -429,16 +694,17 @@ where
         }
     }
 
-    /// Estimate total tx fee for a pre-defined target block based on the
-    /// transaction weight. The max fee cannot be more than MAX_PERCENTAGE_FEE
-    /// of amount
+    /// Estimate total tx fee for the confirmation target of the given kind of
+    /// transaction based on the transaction weight. The max fee cannot be
+    /// more than MAX_PERCENTAGE_FEE of amount
     pub async fn estimate_fee(
         &self,
+        kind: TxKind,
         weight: usize,
         transfer_amount: bitcoin::Amount,
     ) -> Result<bitcoin::Amount> {
         let client = self.client.lock().await;
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let fee_rate = client.estimate_feerate(self.confirmation_targets.of(kind))?;
         let min_relay_fee = client.min_relay_fee()?;
 
         estimate_fee(weight, transfer_amount, fee_rate, min_relay_fee)
@@ -542,6 +808,22 @@ impl<B, D, C> Wallet<B, D, C> {
     pub fn get_network(&self) -> bitcoin::Network {
         self.network
     }
+
+    pub fn confirmation_target(&self, kind: TxKind) -> usize {
+        self.confirmation_targets.of(kind)
+    }
+
+    /// Whether the wallet lacks the keys to sign its transactions.
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only
+    }
+
+    /// Signs the transactions of the wallet with the given signer before
+    /// finalizing them.
+    pub fn with_signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
 }
 
 pub trait EstimateFeeRate {
@@ -648,6 +930,9 @@ impl WalletBuilder {
             finality_confirmations: 1,
             network: Network::Regtest,
             target_block: 1,
+            confirmation_targets: ConfirmationTargets::uniform(1),
+            watch_only: false,
+            signer: None,
         }
     }
 }
@@ -674,29 +959,35 @@ impl Watchable for (Txid, Script) {
 }
 
 pub struct Client {
-    electrum: bdk::electrum_client::Client,
+    backend: Backend,
     latest_block_height: BlockHeight,
     last_sync: Instant,
     sync_interval: Duration,
+    /// The transactions we are watching for, by the script they pay to.
+    watched: BTreeMap<Script, BTreeSet<Txid>>,
     script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
     subscriptions: HashMap<(Txid, Script), Subscription>,
+    fallback_fee_rate: Option<FeeRate>,
 }
 
 impl Client {
-    fn new(electrum: bdk::electrum_client::Client, interval: Duration) -> Result<Self> {
+    fn new(
+        backend: Backend,
+        interval: Duration,
+        fallback_fee_rate: Option<FeeRate>,
+    ) -> Result<Self> {
         // Initially fetch the latest block for storing the height.
-        // We do not act on this subscription after this call.
-        let latest_block = electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
+        let latest_block_height = backend.latest_block()?;
 
         Ok(Self {
-            electrum,
-            latest_block_height: BlockHeight::try_from(latest_block)?,
+            backend,
+            latest_block_height,
             last_sync: Instant::now(),
             sync_interval: interval,
+            watched: Default::default(),
             script_history: Default::default(),
             subscriptions: Default::default(),
+            fallback_fee_rate,
         })
     }
 
@@ -720,9 +1011,7 @@ impl Client {
         let txid = tx.id();
         let script = tx.script();
 
-        if !self.script_history.contains_key(&script) {
-            self.script_history.insert(script.clone(), vec![]);
-        }
+        self.watched.entry(script.clone()).or_default().insert(txid);
 
         self.update_state()?;
 
@@ -755,43 +1044,44 @@ impl Client {
     }
 
     fn update_latest_block(&mut self) -> Result<()> {
-        // Fetch the latest block for storing the height.
-        // We do not act on this subscription after this call, as we cannot rely on
-        // subscription push notifications because eventually the Electrum server will
-        // close the connection and subscriptions are not automatically renewed
-        // upon renewing the connection.
-        let latest_block = self
-            .electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
-        let latest_block_height = BlockHeight::try_from(latest_block)?;
+        // Fetching the latest block from all Electrum servers also switches
+        // away from a server that lags behind.
+        let latest_block_height = self.backend.latest_block()?;
 
         if latest_block_height > self.latest_block_height {
             tracing::debug!(
                 block_height = u32::from(latest_block_height),
                 "Got notification for new block"
             );
-            self.latest_block_height = latest_block_height;
+        } else if latest_block_height < self.latest_block_height {
+            // None of the backends knows the previous tip anymore, the chain
+            // was reorganized to a shorter one. The confirmations are
+            // computed against the current tip, so they are rolled back.
+            tracing::warn!(
+                block_height = u32::from(latest_block_height),
+                previous_block_height = u32::from(self.latest_block_height),
+                "Bitcoin chain tip moved back in a chain reorganization"
+            );
         }
+        self.latest_block_height = latest_block_height;
 
         Ok(())
     }
 
     fn update_script_histories(&mut self) -> Result<()> {
         let histories = self
-            .electrum
-            .batch_script_get_history(self.script_history.keys())
-            .context("Failed to get script histories")?;
+            .backend
+            .script_histories(&self.watched, self.latest_block_height)?;
 
-        if histories.len() != self.script_history.len() {
+        if histories.len() != self.watched.len() {
             bail!(
                 "Expected {} history entries, received {}",
-                self.script_history.len(),
+                self.watched.len(),
                 histories.len()
             );
         }
 
-        let scripts = self.script_history.keys().cloned();
+        let scripts = self.watched.keys().cloned();
         let histories = histories.into_iter();
 
         self.script_history = scripts.zip(histories).collect::<BTreeMap<_, _>>();
@@ -804,20 +1094,56 @@ impl EstimateFeeRate for Client {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L213
         // Returned estimated fees are per BTC/kb.
-        let fee_per_byte = self.electrum.estimate_fee(target_block)?;
-        // we do not expect fees being that high.
-        #[allow(clippy::cast_possible_truncation)]
-        Ok(FeeRate::from_btc_per_kvb(fee_per_byte as f32))
+        let fee_per_byte = self.backend.estimate_fee(target_block)?;
+
+        fee_rate_from_estimate(fee_per_byte, self.fallback_fee_rate)
     }
 
     fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L219
         // Returned fee is in BTC/kb
-        let relay_fee = bitcoin::Amount::from_btc(self.electrum.relay_fee()?)?;
+        let relay_fee = bitcoin::Amount::from_btc(self.backend.relay_fee()?)?;
         Ok(relay_fee)
     }
 }
 
+fn fallback_fee_rate(network: Network) -> Option<FeeRate> {
+    match network {
+        Network::Bitcoin => None,
+        Network::Testnet | Network::Signet | Network::Regtest => {
+            Some(FeeRate::from_sat_per_vb(TESTNET_FALLBACK_FEE_RATE))
+        }
+    }
+}
+
+/// Converts a fee estimate in BTC/kvB as returned by Electrum into a fee rate.
+///
+/// Electrum servers return `-1` if the backing node cannot estimate the fee
+/// rate. In this case, or if the estimate is otherwise invalid, the fallback
+/// fee rate is used if there is one.
+fn fee_rate_from_estimate(btc_per_kvb: f64, fallback: Option<FeeRate>) -> Result<FeeRate> {
+    if btc_per_kvb.is_finite() && btc_per_kvb > 0.0 {
+        // we do not expect fees being that high.
+        #[allow(clippy::cast_possible_truncation)]
+        return Ok(FeeRate::from_btc_per_kvb(btc_per_kvb as f32));
+    }
+
+    match fallback {
+        Some(fallback) => {
+            tracing::warn!(
+                "Electrum server returned invalid fee estimate {}, falling back to {} sat/vB",
+                btc_per_kvb,
+                fallback.as_sat_vb()
+            );
+            Ok(fallback)
+        }
+        None => bail!(
+            "Electrum server returned invalid fee estimate {}",
+            btc_per_kvb
+        ),
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ScriptStatus {
     Unseen,
@@ -891,6 +1217,15 @@ impl ScriptStatus {
     pub fn has_been_seen(&self) -> bool {
         matches!(self, ScriptStatus::InMempool | ScriptStatus::Confirmed(_))
     }
+
+    /// The number of confirmations, zero if the script is not included in a
+    /// block.
+    pub fn confirmations(&self) -> u32 {
+        match self {
+            ScriptStatus::Confirmed(inner) => inner.confirmations(),
+            ScriptStatus::Unseen | ScriptStatus::InMempool => 0,
+        }
+    }
 }
 
 impl fmt::Display for ScriptStatus {
@@ -913,6 +1248,169 @@ mod tests {
     use proptest::prelude::*;
     use tracing::level_filters::LevelFilter;
 
+    #[tokio::test]
+    async fn given_parent_pays_target_fee_rate_then_no_child_is_built() {
+        let wallet = WalletBuilder::new(50_000).with_fees(1.0, 1000).build();
+        let (parent, vout) = first_utxo(&wallet).await;
+
+        let psbt = wallet
+            .child_pays_for_parent(&parent, Amount::from_sat(10_000), vout)
+            .await
+            .unwrap();
+
+        assert!(psbt.is_none());
+    }
+
+    #[tokio::test]
+    async fn given_parent_pays_no_fees_then_child_pays_for_both() {
+        let wallet = WalletBuilder::new(50_000).with_fees(10.0, 1000).build();
+        let (parent, vout) = first_utxo(&wallet).await;
+
+        let psbt = wallet
+            .child_pays_for_parent(&parent, Amount::ZERO, vout)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let child = psbt.global.unsigned_tx;
+        let child_output: u64 = child.output.iter().map(|output| output.value).sum();
+        let child_fee = 50_000 - child_output;
+        // 10 sat/vB equal 2.5 sat per weight unit
+        let parent_fee = parent.get_weight() * 10 / 4;
+        let child_fee_alone = child.get_weight() * 10 / 4;
+
+        assert_eq!(child.input.len(), 1);
+        assert_eq!(
+            child.input[0].previous_output,
+            OutPoint::new(parent.txid(), vout)
+        );
+        assert!(child_fee > u64::try_from(parent_fee + child_fee_alone).unwrap());
+    }
+
+    #[test]
+    fn given_valid_fee_estimate_then_uses_estimate() {
+        let fee_rate = fee_rate_from_estimate(0.0001, Some(FeeRate::from_sat_per_vb(5.0))).unwrap();
+
+        assert!((fee_rate.as_sat_vb() - 10.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn given_invalid_fee_estimate_then_falls_back() {
+        let fallback = FeeRate::from_sat_per_vb(5.0);
+
+        assert_eq!(
+            fee_rate_from_estimate(-1.0, Some(fallback)).unwrap(),
+            fallback
+        );
+        assert_eq!(
+            fee_rate_from_estimate(0.0, Some(fallback)).unwrap(),
+            fallback
+        );
+        assert_eq!(
+            fee_rate_from_estimate(f64::NAN, Some(fallback)).unwrap(),
+            fallback
+        );
+    }
+
+    #[test]
+    fn given_invalid_fee_estimate_without_fallback_then_fails() {
+        assert!(fee_rate_from_estimate(-1.0, None).is_err());
+    }
+
+    #[test]
+    fn confirmation_targets_are_looked_up_by_kind() {
+        let targets = ConfirmationTargets {
+            redeem: 2,
+            punish: 6,
+            ..ConfirmationTargets::uniform(3)
+        };
+
+        assert_eq!(targets.of(TxKind::Lock), 3);
+        assert_eq!(targets.of(TxKind::Redeem), 2);
+        assert_eq!(targets.of(TxKind::Punish), 6);
+    }
+
+    #[tokio::test]
+    async fn finds_change_output_paying_to_wallet() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let change = wallet.new_address().await.unwrap();
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B, change)
+            .await
+            .unwrap();
+        let tx = wallet.sign_and_finalize(tx_lock.into()).await.unwrap();
+
+        let vout = wallet.owned_output(&tx).await.unwrap();
+
+        assert_eq!(vout, Some(1));
+    }
+
+    struct TamperingSigner;
+
+    #[async_trait::async_trait]
+    impl Signer for TamperingSigner {
+        async fn sign(
+            &self,
+            mut psbt: PartiallySignedTransaction,
+        ) -> Result<PartiallySignedTransaction> {
+            psbt.global.unsigned_tx.output[0].value -= 1;
+
+            Ok(psbt)
+        }
+    }
+
+    #[tokio::test]
+    async fn given_signer_changes_transaction_then_signing_fails() {
+        let wallet = WalletBuilder::new(50_000)
+            .build()
+            .with_signer(Arc::new(TamperingSigner));
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let change = wallet.new_address().await.unwrap();
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B, change)
+            .await
+            .unwrap();
+
+        let result = wallet.sign_and_finalize(tx_lock.into()).await;
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Signed PSBT is for transaction"));
+    }
+
+    #[tokio::test]
+    async fn given_change_is_sent_elsewhere_then_no_output_is_owned() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let change = "bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw"
+            .parse::<Address>()
+            .unwrap();
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B, change)
+            .await
+            .unwrap();
+        let tx = wallet.sign_and_finalize(tx_lock.into()).await.unwrap();
+
+        let vout = wallet.owned_output(&tx).await.unwrap();
+
+        assert_eq!(vout, None);
+    }
+
+    async fn first_utxo(
+        wallet: &Wallet<(), bdk::database::MemoryDatabase, StaticFeeRate>,
+    ) -> (Transaction, u32) {
+        let wallet = wallet.wallet.lock().await;
+        let utxo = wallet.list_unspent().unwrap().remove(0);
+        let parent = wallet
+            .list_transactions(true)
+            .unwrap()
+            .into_iter()
+            .find(|tx| tx.txid == utxo.outpoint.txid)
+            .and_then(|tx| tx.transaction)
+            .unwrap();
+
+        (parent, utxo.outpoint.vout)
+    }
+
     #[test]
     fn given_depth_0_should_meet_confirmation_target_one() {
         let script = ScriptStatus::Confirmed(Confirmed { depth: 0 });
@@ -1216,6 +1714,48 @@ DEBUG swap::bitcoin::wallet: Bitcoin transaction status changed txid=00000000000
         )
     }
 
+    #[test]
+    fn given_confirmations_decrease_then_reorg_is_logged() {
+        let writer = capture_logs(LevelFilter::WARN);
+
+        let tx = Txid::default();
+        let mut old = None;
+        old = Some(print_status_change(tx, old, confs(2)));
+        old = Some(print_status_change(tx, old, confs(3)));
+        old = Some(print_status_change(tx, old, ScriptStatus::InMempool));
+        old = Some(print_status_change(tx, old, ScriptStatus::Unseen));
+        print_status_change(tx, old, confs(1));
+
+        assert_eq!(
+            writer.captured(),
+            r" WARN swap::bitcoin::wallet: Bitcoin transaction lost confirmations in a chain reorganization txid=0000000000000000000000000000000000000000000000000000000000000000 new_status=in mempool old_status=confirmed with 3 blocks
+"
+        )
+    }
+
+    #[tokio::test]
+    async fn given_reorg_then_waiting_for_confirmations_continues_until_reconfirmed() {
+        let (sender, receiver) = watch::channel(confs(1));
+        let subscription = Subscription {
+            receiver,
+            finality_confirmations: 3,
+            txid: Txid::default(),
+        };
+        let mut wait = tokio::spawn(async move { subscription.wait_until_final().await });
+
+        sender.send(confs(2)).unwrap();
+        sender.send(ScriptStatus::InMempool).unwrap();
+        let still_waiting = tokio::time::timeout(Duration::from_millis(10), &mut wait).await;
+        assert!(still_waiting.is_err());
+
+        sender.send(confs(3)).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), wait)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
     fn confs(confirmations: u32) -> ScriptStatus {
         ScriptStatus::from_confirmations(confirmations)
     }
@@ -1,27 +1,49 @@
+pub mod abort;
+pub mod address_book;
+pub mod archive;
+pub mod audit;
 mod behaviour;
+pub mod bump_fee;
 pub mod cancel;
+pub mod cancel_and_refund;
+pub mod client;
 pub mod command;
 mod event_loop;
+pub mod export_psbt;
+pub mod external_wallet;
+pub mod history;
 mod list_sellers;
+pub mod progress;
 pub mod refund;
+pub mod select_seller;
+pub mod split;
 pub mod tracing;
 pub mod transport;
 
+pub use abort::abort;
+pub use audit::audit;
 pub use behaviour::{Behaviour, OutEvent};
+pub use bump_fee::bump_fee;
 pub use cancel::cancel;
-pub use event_loop::{EventLoop, EventLoopHandle};
-pub use list_sellers::{list_sellers, Seller, Status as SellerStatus};
+pub use cancel_and_refund::cancel_and_refund;
+pub use client::{SwapClient, SwapEvent, SwapRequest};
+pub use event_loop::{EventLoop, EventLoopHandle, PeerUnreachable};
+pub use export_psbt::export_psbt;
+pub use history::{history, swap_details, SwapDetails};
+pub use list_sellers::{
+    list_sellers, list_sellers_with_latency, watch_sellers, Seller, Status as SellerStatus,
+};
 pub use refund::refund;
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::asb;
     use crate::cli::list_sellers::{Seller, Status};
     use crate::network::quote;
-    use crate::network::quote::BidQuote;
-    use crate::network::rendezvous::XmrBtcNamespace;
+    use crate::network::quote::{BidQuote, FeeAssumptions, QuoteDetails};
+    use crate::network::rendezvous::{Asset, AssetPair, RendezvousNamespace};
     use crate::network::test::{new_swarm, SwarmExt};
+    use crate::{asb, monero};
     use futures::StreamExt;
     use libp2p::multiaddr::Protocol;
     use libp2p::request_response::RequestResponseEvent;
@@ -33,16 +55,21 @@ mod tests {
 
     #[tokio::test]
     async fn list_sellers_should_report_all_registered_asbs_with_a_quote() {
-        let namespace = XmrBtcNamespace::Mainnet;
+        let namespace = RendezvousNamespace::mainnet();
         let (rendezvous_address, rendezvous_peer_id) = setup_rendezvous_point().await;
-        let expected_seller_1 =
-            setup_asb(rendezvous_peer_id, rendezvous_address.clone(), namespace).await;
-        let expected_seller_2 =
-            setup_asb(rendezvous_peer_id, rendezvous_address.clone(), namespace).await;
+        let expected_seller_1 = setup_asb(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace.clone(),
+        )
+        .await;
+        let expected_seller_2 = setup_asb(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace.clone(),
+        )
+        .await;
 
         let list_sellers = list_sellers(
-            rendezvous_peer_id,
-            rendezvous_address,
+            vec![(rendezvous_peer_id, rendezvous_address)],
             namespace,
             0,
             identity::Keypair::generate_ed25519(),
@@ -58,6 +85,118 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn list_sellers_should_report_asb_registered_at_multiple_rendezvous_points_once() {
+        let namespace = RendezvousNamespace::mainnet();
+        let rendezvous_point_1 = setup_rendezvous_point().await;
+        let rendezvous_point_2 = setup_rendezvous_point().await;
+        let rendezvous_points = vec![
+            (rendezvous_point_1.1, rendezvous_point_1.0),
+            (rendezvous_point_2.1, rendezvous_point_2.0),
+        ];
+        let expected_seller = setup_asb(rendezvous_points.clone(), namespace.clone()).await;
+
+        let list_sellers = list_sellers(
+            rendezvous_points,
+            namespace,
+            0,
+            identity::Keypair::generate_ed25519(),
+        );
+        let sellers = tokio::time::timeout(Duration::from_secs(15), list_sellers)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(sellers, vec![expected_seller])
+    }
+
+    #[tokio::test]
+    async fn watch_sellers_should_report_asbs_registered_after_the_first_round() {
+        let namespace = RendezvousNamespace::mainnet();
+        let (rendezvous_address, rendezvous_peer_id) = setup_rendezvous_point().await;
+        let expected_seller_1 = setup_asb(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace.clone(),
+        )
+        .await;
+
+        let mut sellers = watch_sellers(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace.clone(),
+            0,
+            identity::Keypair::generate_ed25519(),
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap();
+
+        let first_round = tokio::time::timeout(Duration::from_secs(15), sellers.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first_round, vec![expected_seller_1.clone()]);
+
+        let expected_seller_2 =
+            setup_asb(vec![(rendezvous_peer_id, rendezvous_address)], namespace).await;
+
+        let second_round = tokio::time::timeout(Duration::from_secs(15), sellers.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            HashSet::<Seller>::from_iter(second_round),
+            HashSet::<Seller>::from_iter([expected_seller_1, expected_seller_2])
+        )
+    }
+
+    #[tokio::test]
+    async fn list_sellers_should_leave_out_asbs_quoting_a_different_pair() {
+        let namespace = RendezvousNamespace::mainnet();
+        let (rendezvous_address, rendezvous_peer_id) = setup_rendezvous_point().await;
+        let expected_seller = setup_asb(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace.clone(),
+        )
+        .await;
+        let _other_pair_seller = setup_asb_with_quote(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace.clone(),
+            BidQuote {
+                details: Some(QuoteDetails {
+                    pair: AssetPair {
+                        base: Asset::Btc,
+                        quote: Asset::Xmr,
+                    },
+                    expires_at: u64::MAX,
+                    min_quantity_xmr: monero::Amount::ZERO,
+                    max_quantity_xmr: monero::Amount::ZERO,
+                    fees: FeeAssumptions {
+                        bitcoin_redeem: bitcoin::Amount::ZERO,
+                        bitcoin_punish: bitcoin::Amount::ZERO,
+                        monero_lock: monero::Amount::ZERO,
+                    },
+                    buy_price: None,
+                    timelocks: None,
+                }),
+                ..static_quote()
+            },
+        )
+        .await;
+
+        let list_sellers = list_sellers(
+            vec![(rendezvous_peer_id, rendezvous_address)],
+            namespace,
+            0,
+            identity::Keypair::generate_ed25519(),
+        );
+        let sellers = tokio::time::timeout(Duration::from_secs(15), list_sellers)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(sellers, vec![expected_seller])
+    }
+
     async fn setup_rendezvous_point() -> (Multiaddr, PeerId) {
         let mut rendezvous_node = new_swarm(|_, _| RendezvousPointBehaviour::default());
         let rendezvous_address = rendezvous_node.listen_on_tcp_localhost().await;
@@ -72,29 +211,38 @@ mod tests {
         (rendezvous_address, rendezvous_peer_id)
     }
 
-    async fn setup_asb(
-        rendezvous_peer_id: PeerId,
-        rendezvous_address: Multiaddr,
-        namespace: XmrBtcNamespace,
-    ) -> Seller {
-        let static_quote = BidQuote {
+    fn static_quote() -> BidQuote {
+        BidQuote {
             price: bitcoin::Amount::from_sat(1337),
             min_quantity: bitcoin::Amount::from_sat(42),
             max_quantity: bitcoin::Amount::from_sat(9001),
-        };
+            details: None,
+        }
+    }
+
+    async fn setup_asb(
+        rendezvous_points: Vec<(PeerId, Multiaddr)>,
+        namespace: RendezvousNamespace,
+    ) -> Seller {
+        setup_asb_with_quote(rendezvous_points, namespace, static_quote()).await
+    }
 
+    async fn setup_asb_with_quote(
+        rendezvous_points: Vec<(PeerId, Multiaddr)>,
+        namespace: RendezvousNamespace,
+        static_quote: BidQuote,
+    ) -> Seller {
         let mut asb = new_swarm(|_, identity| StaticQuoteAsbBehaviour {
             rendezvous: asb::rendezous::Behaviour::new(
                 identity,
-                rendezvous_peer_id,
-                rendezvous_address,
-                namespace,
+                rendezvous_points.clone(),
+                vec![namespace],
                 None,
             ),
             ping: Default::default(),
             quote: quote::asb(),
             static_quote,
-            registered: false,
+            registrations: 0,
         });
 
         let asb_address = asb.listen_on_tcp_localhost().await;
@@ -104,7 +252,7 @@ mod tests {
 
         // avoid race condition where `list_sellers` tries to discover before we are
         // registered block this function until we are registered
-        while !asb.behaviour().registered {
+        while asb.behaviour().registrations < rendezvous_points.len() {
             asb.next().await;
         }
 
@@ -131,12 +279,12 @@ mod tests {
         #[behaviour(ignore)]
         static_quote: BidQuote,
         #[behaviour(ignore)]
-        registered: bool,
+        registrations: usize,
     }
     impl NetworkBehaviourEventProcess<rendezvous::client::Event> for StaticQuoteAsbBehaviour {
         fn inject_event(&mut self, event: rendezvous::client::Event) {
             if let rendezvous::client::Event::Registered { .. } = event {
-                self.registered = true;
+                self.registrations += 1;
             }
         }
     }
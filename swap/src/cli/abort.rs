@@ -0,0 +1,55 @@
+use crate::bitcoin::Wallet;
+use crate::protocol::alice::AliceState;
+use crate::protocol::bob::BobState;
+use crate::protocol::{Database, State};
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Aborts a swap whose Bitcoin has not been locked yet.
+///
+/// Only the swap in the database is aborted, it is up to the caller to tell
+/// the peer. Fails if the Bitcoin lock transaction was already published.
+pub async fn abort(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+) -> Result<State> {
+    let state = db.get_state(swap_id).await?;
+
+    let tx_lock = match &state {
+        State::Bob(state @ (BobState::Started { .. } | BobState::SwapSetupCompleted(_))) => {
+            state.tx_lock()
+        }
+        State::Alice(AliceState::Started { state3 }) => Some(&state3.tx_lock),
+        _ => bail!(
+            "Cannot abort swap {} because it is in state {}, only swaps whose Bitcoin is not locked yet can be aborted.",
+            swap_id,
+            state
+        ),
+    };
+
+    // We might have published the lock transaction without recording it, e.g.
+    // if we were stopped right after publishing it.
+    if let Some(tx_lock) = tx_lock {
+        let status = bitcoin_wallet.status_of_script(tx_lock).await?;
+
+        if status.has_been_seen() {
+            bail!(
+                "Cannot abort swap {} because its Bitcoin lock transaction {} was already published.",
+                swap_id,
+                tx_lock.txid()
+            )
+        }
+    }
+
+    tracing::info!(%swap_id, "Manually aborting swap");
+
+    let state = match state {
+        State::Alice(_) => State::Alice(AliceState::SafelyAborted),
+        State::Bob(_) => State::Bob(BobState::SafelyAborted),
+    };
+    db.insert_latest_state(swap_id, state.clone()).await?;
+
+    Ok(state)
+}
@@ -0,0 +1,289 @@
+//! Addresses the CLI refunds Bitcoin to and pays Monero out to, stored under a
+//! label.
+//!
+//! `buy-xmr` takes the label of an address in the address book wherever it
+//! takes an address. Labels are resolved and checked against the network of
+//! the swap before any money moves.
+
+use crate::protocol::Database;
+use crate::{bitcoin, env, monero};
+use anyhow::{bail, Result};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AddressKind {
+    Bitcoin,
+    Monero,
+}
+
+impl fmt::Display for AddressKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressKind::Bitcoin => write!(f, "bitcoin"),
+            AddressKind::Monero => write!(f, "monero"),
+        }
+    }
+}
+
+impl FromStr for AddressKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bitcoin" => Ok(AddressKind::Bitcoin),
+            "monero" => Ok(AddressKind::Monero),
+            other => bail!("Unknown kind of address `{}`", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookAddress {
+    /// Refunded Bitcoin is sent to this address.
+    Bitcoin(bitcoin::Address),
+    /// Redeemed Monero is sent to this address.
+    Monero(monero::Address),
+}
+
+impl BookAddress {
+    pub fn kind(&self) -> AddressKind {
+        match self {
+            BookAddress::Bitcoin(_) => AddressKind::Bitcoin,
+            BookAddress::Monero(_) => AddressKind::Monero,
+        }
+    }
+}
+
+impl FromStr for BookAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(address) = bitcoin::Address::from_str(s) {
+            return Ok(BookAddress::Bitcoin(address));
+        }
+        if let Ok(address) = monero::Address::from_str(s) {
+            return Ok(BookAddress::Monero(address));
+        }
+
+        bail!("{} is neither a Bitcoin nor a Monero address", s)
+    }
+}
+
+impl fmt::Display for BookAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookAddress::Bitcoin(address) => write!(f, "{}", address),
+            BookAddress::Monero(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+/// An address stored under a label. A label can hold one address of each
+/// kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressBookEntry {
+    pub label: String,
+    pub address: BookAddress,
+}
+
+impl AddressBookEntry {
+    pub fn new(label: String, address: BookAddress) -> Result<Self> {
+        if label.trim().is_empty() {
+            bail!("The label of an address must not be empty")
+        }
+        // otherwise the label could not be told apart from an address on the command
+        // line
+        if BookAddress::from_str(&label).is_ok() {
+            bail!("The label `{}` is an address itself", label)
+        }
+
+        Ok(Self { label, address })
+    }
+}
+
+/// An address given on the command line, either as is or by its label in the
+/// address book.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressOrLabel<A> {
+    Address(A),
+    Label(String),
+}
+
+impl<A: FromStr> FromStr for AddressOrLabel<A> {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Infallible> {
+        Ok(match A::from_str(s) {
+            Ok(address) => AddressOrLabel::Address(address),
+            Err(_) => AddressOrLabel::Label(s.to_string()),
+        })
+    }
+}
+
+impl<A> AddressOrLabel<A> {
+    /// Checks the address if it was given as is, labels are checked once they
+    /// are resolved.
+    pub fn validate<E>(self, validate: impl FnOnce(A) -> Result<A, E>) -> Result<Self, E> {
+        match self {
+            AddressOrLabel::Address(address) => validate(address).map(AddressOrLabel::Address),
+            AddressOrLabel::Label(label) => Ok(AddressOrLabel::Label(label)),
+        }
+    }
+}
+
+impl AddressOrLabel<bitcoin::Address> {
+    pub async fn resolve(
+        self,
+        db: &(dyn Database + Send + Sync),
+        env_config: &env::Config,
+    ) -> Result<bitcoin::Address> {
+        let label = match self {
+            AddressOrLabel::Address(address) => return Ok(address),
+            AddressOrLabel::Label(label) => label,
+        };
+
+        let address = match lookup(db, &label, AddressKind::Bitcoin).await? {
+            Some(BookAddress::Bitcoin(address)) => address,
+            _ => bail!(
+                "{} is neither a Bitcoin address nor the label of one in the address book",
+                label
+            ),
+        };
+        if address.network != env_config.bitcoin_network {
+            bail!(
+                "The Bitcoin address labelled `{}` is on network {} but the swap is on {}",
+                label,
+                address.network,
+                env_config.bitcoin_network
+            )
+        }
+
+        Ok(address)
+    }
+}
+
+impl AddressOrLabel<monero::Address> {
+    pub async fn resolve(
+        self,
+        db: &(dyn Database + Send + Sync),
+        env_config: &env::Config,
+    ) -> Result<monero::Address> {
+        let label = match self {
+            AddressOrLabel::Address(address) => return Ok(address),
+            AddressOrLabel::Label(label) => label,
+        };
+
+        let address = match lookup(db, &label, AddressKind::Monero).await? {
+            Some(BookAddress::Monero(address)) => address,
+            _ => bail!(
+                "{} is neither a Monero address nor the label of one in the address book",
+                label
+            ),
+        };
+        if address.network != env_config.monero_network {
+            bail!(
+                "The Monero address labelled `{}` is on network {:?} but the swap is on {:?}",
+                label,
+                address.network,
+                env_config.monero_network
+            )
+        }
+
+        Ok(address)
+    }
+}
+
+async fn lookup(
+    db: &(dyn Database + Send + Sync),
+    label: &str,
+    kind: AddressKind,
+) -> Result<Option<BookAddress>> {
+    let address = db
+        .get_address_book()
+        .await?
+        .into_iter()
+        .find(|entry| entry.label == label && entry.address.kind() == kind)
+        .map(|entry| entry.address);
+
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SqliteDatabase;
+    use crate::env::{GetConfig, Mainnet, Testnet};
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    const BITCOIN_TESTNET_ADDRESS: &str = "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv";
+    const MONERO_STAGENET_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
+
+    #[test]
+    fn arguments_that_are_no_address_are_labels() {
+        assert_eq!(
+            AddressOrLabel::<bitcoin::Address>::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(),
+            AddressOrLabel::Address(BITCOIN_TESTNET_ADDRESS.parse().unwrap())
+        );
+        assert_eq!(
+            AddressOrLabel::<bitcoin::Address>::from_str("cold wallet").unwrap(),
+            AddressOrLabel::Label("cold wallet".to_string())
+        );
+    }
+
+    #[test]
+    fn labels_must_not_be_addresses() {
+        let address = BookAddress::from_str(MONERO_STAGENET_ADDRESS).unwrap();
+        assert_eq!(address.kind(), AddressKind::Monero);
+
+        assert!(
+            AddressBookEntry::new(MONERO_STAGENET_ADDRESS.to_string(), address.clone()).is_err()
+        );
+        assert!(AddressBookEntry::new(" ".to_string(), address.clone()).is_err());
+        assert!(AddressBookEntry::new("savings".to_string(), address).is_ok());
+    }
+
+    #[tokio::test]
+    async fn labels_are_resolved_on_the_network_of_the_swap() -> Result<()> {
+        let temp_db = tempdir().unwrap().into_path().join("tempdb");
+        File::create(temp_db.clone()).unwrap();
+        let db = SqliteDatabase::open(temp_db, None).await?;
+
+        db.insert_address_book_entry(AddressBookEntry::new(
+            "me".to_string(),
+            BookAddress::from_str(BITCOIN_TESTNET_ADDRESS)?,
+        )?)
+        .await?;
+        db.insert_address_book_entry(AddressBookEntry::new(
+            "me".to_string(),
+            BookAddress::from_str(MONERO_STAGENET_ADDRESS)?,
+        )?)
+        .await?;
+
+        let refund_address = AddressOrLabel::<bitcoin::Address>::Label("me".to_string());
+        assert_eq!(
+            refund_address
+                .clone()
+                .resolve(&db, &Testnet::get_config())
+                .await?,
+            BITCOIN_TESTNET_ADDRESS.parse()?
+        );
+        assert!(refund_address
+            .resolve(&db, &Mainnet::get_config())
+            .await
+            .is_err());
+
+        let receive_address = AddressOrLabel::<monero::Address>::Label("me".to_string());
+        assert_eq!(
+            receive_address.resolve(&db, &Testnet::get_config()).await?,
+            MONERO_STAGENET_ADDRESS.parse()?
+        );
+
+        let unknown = AddressOrLabel::<monero::Address>::Label("someone else".to_string());
+        assert!(unknown.resolve(&db, &Testnet::get_config()).await.is_err());
+
+        Ok(())
+    }
+}
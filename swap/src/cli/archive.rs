@@ -0,0 +1,227 @@
+//! Moves a swap of `buy-xmr` to the CLI on another machine.
+//!
+//! The archive holds everything the database knows about the swap: every state
+//! it has been in, the seller and its addresses, the Monero receive address and
+//! the parameters the swap was set up with, as well as the receipts the seller
+//! signed. The states contain the keys of the swap, including the ones of the
+//! Monero wallet the Monero is redeemed to, hence the archive is encrypted with
+//! a passphrase of its own.
+
+use crate::database::Swap;
+use crate::encryption::Cipher;
+use crate::monero;
+use crate::network::swap_setup::receipt::SignedReceipt;
+use crate::protocol::bob::SwapSetupParameters;
+use crate::protocol::{Database, State};
+use anyhow::{bail, Context, Result};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use uuid::Uuid;
+
+/// Increased whenever the archive changes in a way older versions of the CLI
+/// cannot import.
+const ARCHIVE_VERSION: u32 = 1;
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SwapArchive {
+    version: u32,
+    swap_id: Uuid,
+    #[serde_as(as = "DisplayFromStr")]
+    peer_id: PeerId,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    peer_addresses: Vec<Multiaddr>,
+    #[serde_as(as = "DisplayFromStr")]
+    monero_receive_address: monero::Address,
+    setup_parameters: Option<SwapSetupParameters>,
+    /// The receipts the seller signed for the swap, archives of older
+    /// versions of the CLI hold none.
+    #[serde(default)]
+    receipts: Vec<SignedReceipt>,
+    /// Every state of the swap with the time it was entered, from the first
+    /// to the latest state.
+    states: Vec<(String, Swap)>,
+}
+
+/// Returns the encrypted archive of the swap.
+pub async fn export(
+    swap_id: Uuid,
+    db: &(dyn Database + Send + Sync),
+    cipher: &Cipher,
+) -> Result<Vec<u8>> {
+    let states = db.get_states(swap_id).await?;
+    if states.is_empty() {
+        bail!("No swap with id {} in the database", swap_id)
+    }
+    if states
+        .iter()
+        .any(|(_, state)| matches!(state, State::Alice(_)))
+    {
+        bail!("Only swaps of buy-xmr can be exported")
+    }
+
+    let peer_id = db.get_peer_id(swap_id).await?;
+    let archive = SwapArchive {
+        version: ARCHIVE_VERSION,
+        swap_id,
+        peer_id,
+        peer_addresses: db.get_addresses(peer_id).await?,
+        monero_receive_address: db.get_monero_address(swap_id).await?,
+        setup_parameters: db.get_swap_setup_parameters(swap_id).await?,
+        receipts: db.get_receipts(swap_id).await?,
+        states: states
+            .into_iter()
+            .map(|(entered_at, state)| (entered_at, Swap::from(state)))
+            .collect(),
+    };
+
+    Ok(cipher.encrypt(serde_json::to_string(&archive)?.as_bytes()))
+}
+
+/// Stores the swap of the encrypted archive in the database, returns the id
+/// of the swap.
+///
+/// Fails if the database already knows the swap.
+pub async fn import(
+    encrypted: &[u8],
+    db: &(dyn Database + Send + Sync),
+    cipher: &Cipher,
+) -> Result<Uuid> {
+    let archive: SwapArchive = serde_json::from_slice(&cipher.decrypt(encrypted)?)
+        .context("Failed to read the archive")?;
+    if archive.version != ARCHIVE_VERSION {
+        bail!(
+            "The archive has version {}, this version of the CLI only imports version {}",
+            archive.version,
+            ARCHIVE_VERSION
+        )
+    }
+
+    let swap_id = archive.swap_id;
+    if !db.get_states(swap_id).await?.is_empty() {
+        bail!("Swap {} is already in the database", swap_id)
+    }
+
+    db.insert_peer_id(swap_id, archive.peer_id).await?;
+    let known_addresses = db.get_addresses(archive.peer_id).await?;
+    for address in archive.peer_addresses {
+        if !known_addresses.contains(&address) {
+            db.insert_address(archive.peer_id, address).await?;
+        }
+    }
+    db.insert_monero_address(swap_id, archive.monero_receive_address)
+        .await?;
+    if let Some(parameters) = archive.setup_parameters {
+        db.insert_swap_setup_parameters(swap_id, parameters).await?;
+    }
+    for receipt in archive.receipts {
+        db.insert_receipt(swap_id, receipt).await?;
+    }
+    for (entered_at, swap) in archive.states {
+        db.insert_recorded_state(swap_id, entered_at, swap.into())
+            .await?;
+    }
+
+    Ok(swap_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin;
+    use crate::database::SqliteDatabase;
+    use crate::encryption::Passphrase;
+    use crate::protocol::bob::BobState;
+    use std::fs::File;
+    use std::str::FromStr;
+    use tempfile::tempdir;
+
+    async fn new_db() -> Result<SqliteDatabase> {
+        let temp_db = tempdir().unwrap().into_path().join("tempdb");
+        File::create(temp_db.clone()).unwrap();
+
+        SqliteDatabase::open(temp_db, None).await
+    }
+
+    fn cipher(passphrase: &str) -> Cipher {
+        Cipher::new(Passphrase::new(passphrase.to_string()).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn imported_swap_equals_exported_swap() -> Result<()> {
+        let old_machine = new_db().await?;
+        let new_machine = new_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+        let address = Multiaddr::from_str("/ip4/127.0.0.1/tcp/9939")?;
+        let monero_address = monero::Address::from_str("53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a")?;
+        let parameters = SwapSetupParameters {
+            xmr: monero::Amount::from_piconero(1_000_000),
+            tx_refund_fee: bitcoin::Amount::from_sat(2_000),
+            tx_cancel_fee: bitcoin::Amount::from_sat(3_000),
+        };
+        let receipt = SignedReceipt {
+            receipt: vec![1, 2, 3],
+            public_key: vec![4; 32],
+            signature: vec![5; 64],
+        };
+
+        old_machine.insert_peer_id(swap_id, peer_id).await?;
+        old_machine.insert_address(peer_id, address.clone()).await?;
+        old_machine
+            .insert_monero_address(swap_id, monero_address)
+            .await?;
+        old_machine
+            .insert_swap_setup_parameters(swap_id, parameters)
+            .await?;
+        old_machine.insert_receipt(swap_id, receipt.clone()).await?;
+        old_machine
+            .insert_latest_state(
+                swap_id,
+                State::Bob(BobState::Started {
+                    btc_amount: bitcoin::Amount::from_sat(100_000),
+                    change_address: "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv".parse()?,
+                    timelocks: None,
+                    confirmation_schedule: None,
+                }),
+            )
+            .await?;
+        old_machine
+            .insert_latest_state(swap_id, State::Bob(BobState::SafelyAborted))
+            .await?;
+
+        let archive = export(swap_id, &old_machine, &cipher("archive")).await?;
+
+        assert!(import(&archive, &new_machine, &cipher("wrong"))
+            .await
+            .is_err());
+        assert_eq!(
+            import(&archive, &new_machine, &cipher("archive")).await?,
+            swap_id
+        );
+
+        assert_eq!(
+            new_machine.get_states(swap_id).await?,
+            old_machine.get_states(swap_id).await?
+        );
+        assert_eq!(new_machine.get_peer_id(swap_id).await?, peer_id);
+        assert_eq!(new_machine.get_addresses(peer_id).await?, vec![address]);
+        assert_eq!(
+            new_machine.get_monero_address(swap_id).await?,
+            monero_address
+        );
+        assert_eq!(
+            new_machine.get_swap_setup_parameters(swap_id).await?,
+            Some(parameters)
+        );
+        assert_eq!(new_machine.get_receipts(swap_id).await?, vec![receipt]);
+
+        assert!(import(&archive, &new_machine, &cipher("archive"))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
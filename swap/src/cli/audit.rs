@@ -0,0 +1,311 @@
+use crate::bitcoin::wallet::Watchable;
+use crate::bitcoin::{Amount, TxCancel, TxLock, TxRedeem, TxRefund, Wallet};
+use crate::monero::wallet::WatchRequest;
+use crate::network::swap_setup::receipt::Receipt;
+use crate::protocol::bob::BobState;
+use crate::protocol::{Database, State};
+use crate::{bitcoin, monero};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt;
+use uuid::Uuid;
+
+/// The outcome of comparing an amount of a swap against what it should be.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub check: &'static str,
+    pub expected: String,
+    /// `None` if the transaction is not on the blockchain (yet).
+    pub actual: Option<String>,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Discrepancy,
+    NotFound,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Discrepancy => write!(f, "DISCREPANCY"),
+            Status::NotFound => write!(f, "not found"),
+        }
+    }
+}
+
+impl Finding {
+    fn compare<T>(check: &'static str, expected: T, actual: Option<T>) -> Self
+    where
+        T: PartialEq + fmt::Display,
+    {
+        let status = match &actual {
+            Some(actual) if *actual == expected => Status::Ok,
+            Some(_) => Status::Discrepancy,
+            None => Status::NotFound,
+        };
+
+        Self {
+            check,
+            expected: expected.to_string(),
+            actual: actual.map(|actual| actual.to_string()),
+            status,
+        }
+    }
+}
+
+/// Recomputes the amounts of a swap in which we bought XMR from the stored
+/// states and compares them against the receipts of the seller and against
+/// both blockchains.
+///
+/// Transactions that were not published (yet) are reported as not found, only
+/// amounts that differ from what was agreed upon are discrepancies.
+pub async fn audit(
+    swap_id: Uuid,
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &Wallet,
+    monero_wallet: &monero::LightWallet,
+) -> Result<Vec<Finding>> {
+    let mut terms = Terms::default();
+    for (_, state) in db.get_states(swap_id).await? {
+        match state {
+            State::Bob(state) => terms.learn(state),
+            State::Alice(_) => bail!(
+                "Swap {} sold XMR, only swaps that bought XMR can be audited",
+                swap_id
+            ),
+        }
+    }
+
+    let tx_lock = match terms.tx_lock {
+        Some(tx_lock) => tx_lock,
+        None => bail!(
+            "The swap setup of swap {} did not complete, there is nothing to audit",
+            swap_id
+        ),
+    };
+    let lock_amount = tx_lock.lock_amount();
+
+    let mut findings = Vec::new();
+
+    if let Some(requested) = terms.requested_btc {
+        findings.push(Finding::compare(
+            "BTC lock amount",
+            requested,
+            Some(lock_amount),
+        ));
+    }
+
+    if let Ok(seller) = db.get_peer_id(swap_id).await {
+        for receipt in db.get_receipts(swap_id).await? {
+            if let Ok(Receipt::Swap(receipt)) = receipt.verify(seller) {
+                findings.push(Finding::compare(
+                    "BTC of the swap receipt",
+                    receipt.btc,
+                    Some(lock_amount),
+                ));
+                findings.push(Finding::compare(
+                    "XMR of the swap receipt",
+                    receipt.xmr,
+                    terms.xmr,
+                ));
+            }
+        }
+    }
+
+    findings.push(Finding::compare(
+        "BTC lock output",
+        lock_amount,
+        output_value(bitcoin_wallet, &tx_lock).await?,
+    ));
+
+    if let (Some(request), Some(xmr)) = (&terms.xmr_lock, terms.xmr) {
+        let transfer = monero_wallet.transfer(request).await?;
+
+        findings.push(Finding::compare(
+            "XMR lock amount",
+            xmr,
+            transfer.map(|transfer| transfer.received),
+        ));
+        findings.push(Finding::compare(
+            "XMR lock unlock time",
+            0,
+            transfer.map(|transfer| transfer.unlock_time),
+        ));
+    }
+
+    if let (Some(tx_redeem), Some(fees)) = (&terms.tx_redeem, terms.fees) {
+        findings.extend(spend_findings(
+            ("BTC redeem output", "BTC redeem fee"),
+            lock_amount,
+            fees.redeem,
+            output_value(bitcoin_wallet, tx_redeem).await?,
+        ));
+    }
+
+    if let (Some((tx_cancel, tx_refund)), Some(fees)) = (&terms.tx_cancel_and_refund, terms.fees) {
+        findings.extend(spend_findings(
+            ("BTC cancel output", "BTC cancel fee"),
+            lock_amount,
+            fees.cancel,
+            output_value(bitcoin_wallet, tx_cancel).await?,
+        ));
+        findings.extend(spend_findings(
+            ("BTC refund output", "BTC refund fee"),
+            lock_amount.checked_sub(fees.cancel).unwrap_or(Amount::ZERO),
+            fees.refund,
+            output_value(bitcoin_wallet, tx_refund).await?,
+        ));
+    }
+
+    Ok(findings)
+}
+
+/// What the stored states of a swap tell about its amounts, each state only
+/// knows about some of them.
+#[derive(Default)]
+struct Terms {
+    requested_btc: Option<Amount>,
+    xmr: Option<monero::Amount>,
+    tx_lock: Option<TxLock>,
+    fees: Option<bitcoin::TxFees>,
+    tx_redeem: Option<TxRedeem>,
+    tx_cancel_and_refund: Option<(TxCancel, TxRefund)>,
+    xmr_lock: Option<WatchRequest>,
+}
+
+impl Terms {
+    fn learn(&mut self, state: BobState) {
+        if let BobState::Started { btc_amount, .. } = state {
+            self.requested_btc.get_or_insert(btc_amount);
+        }
+        if let Some(xmr) = state.xmr_amount() {
+            self.xmr.get_or_insert(xmr);
+        }
+        if let Some(tx_lock) = state.tx_lock() {
+            self.tx_lock.get_or_insert_with(|| tx_lock.clone());
+        }
+        if let Some(fees) = state.tx_fees() {
+            self.fees.get_or_insert(fees);
+        }
+        if self.tx_redeem.is_none() {
+            self.tx_redeem = state.tx_redeem();
+        }
+        if self.tx_cancel_and_refund.is_none() {
+            self.tx_cancel_and_refund = state.tx_cancel_and_refund();
+        }
+        if self.xmr_lock.is_none() {
+            self.xmr_lock = state.lock_xmr_watch_request();
+        }
+    }
+}
+
+/// Checks the output and the fee of a transaction that spends `input` to a
+/// single output of ours and should pay `fee`.
+fn spend_findings(
+    (output_check, fee_check): (&'static str, &'static str),
+    input: Amount,
+    fee: Amount,
+    actual_output: Option<Amount>,
+) -> Vec<Finding> {
+    // A fee that exceeds the input cannot be paid, any output is wrong then
+    let expected_output = input.checked_sub(fee).unwrap_or(Amount::ZERO);
+    let actual_fee = actual_output.map(|output| input.checked_sub(output).unwrap_or(Amount::ZERO));
+
+    vec![
+        Finding::compare(output_check, expected_output, actual_output),
+        Finding::compare(fee_check, fee, actual_fee),
+    ]
+}
+
+/// The value of the outputs of the transaction that pay to its watched
+/// script, `None` if the transaction was not seen on the blockchain.
+async fn output_value<T>(bitcoin_wallet: &Wallet, tx: &T) -> Result<Option<Amount>>
+where
+    T: Watchable,
+{
+    if !bitcoin_wallet.status_of_script(tx).await?.has_been_seen() {
+        return Ok(None);
+    }
+
+    let transaction = bitcoin_wallet.get_raw_transaction(tx.id()).await?;
+    let script = tx.script();
+    let value = transaction
+        .output
+        .iter()
+        .filter(|output| output.script_pubkey == script)
+        .map(|output| output.value)
+        .sum();
+
+    Ok(Some(Amount::from_sat(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_are_compared_to_the_expected_amount() {
+        let expected = Amount::from_sat(100_000);
+
+        let ok = Finding::compare("check", expected, Some(Amount::from_sat(100_000)));
+        let discrepancy = Finding::compare("check", expected, Some(Amount::from_sat(99_999)));
+        let not_found = Finding::compare("check", expected, None);
+
+        assert_eq!(ok.status, Status::Ok);
+        assert_eq!(discrepancy.status, Status::Discrepancy);
+        assert_eq!(
+            discrepancy.actual,
+            Some(Amount::from_sat(99_999).to_string())
+        );
+        assert_eq!(not_found.status, Status::NotFound);
+        assert_eq!(not_found.actual, None);
+    }
+
+    #[test]
+    fn spending_transaction_that_pays_the_agreed_fee_is_ok() {
+        let findings = spend_findings(
+            ("output", "fee"),
+            Amount::from_sat(100_000),
+            Amount::from_sat(1_000),
+            Some(Amount::from_sat(99_000)),
+        );
+
+        assert!(findings.iter().all(|finding| finding.status == Status::Ok));
+    }
+
+    #[test]
+    fn spending_transaction_with_a_short_output_is_a_discrepancy_of_output_and_fee() {
+        let findings = spend_findings(
+            ("output", "fee"),
+            Amount::from_sat(100_000),
+            Amount::from_sat(1_000),
+            Some(Amount::from_sat(98_999)),
+        );
+
+        assert_eq!(findings[0].status, Status::Discrepancy);
+        assert_eq!(findings[1].status, Status::Discrepancy);
+        assert_eq!(
+            findings[1].actual,
+            Some(Amount::from_sat(1_001).to_string())
+        );
+    }
+
+    #[test]
+    fn unpublished_spending_transaction_is_not_found() {
+        let findings = spend_findings(
+            ("output", "fee"),
+            Amount::from_sat(100_000),
+            Amount::from_sat(1_000),
+            None,
+        );
+
+        assert!(findings
+            .iter()
+            .all(|finding| finding.status == Status::NotFound));
+    }
+}
@@ -1,15 +1,18 @@
 use crate::network::quote::BidQuote;
-use crate::network::swap_setup::bob;
-use crate::network::{encrypted_signature, quote, redial, transfer_proof};
+use crate::network::swap_setup::{bob, sell_xmr};
+use crate::network::{
+    abort, connection_manager, encrypted_signature, identify, quote, transfer_proof,
+};
+use crate::protocol::alice::State3;
 use crate::protocol::bob::State2;
 use crate::{bitcoin, env};
 use anyhow::{anyhow, Error, Result};
 use libp2p::core::Multiaddr;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::relay::Relay;
 use libp2p::request_response::{RequestId, ResponseChannel};
-use libp2p::{NetworkBehaviour, PeerId};
+use libp2p::{identity, NetworkBehaviour, PeerId};
 use std::sync::Arc;
-use std::time::Duration;
 
 #[derive(Debug)]
 pub enum OutEvent {
@@ -18,6 +21,7 @@ pub enum OutEvent {
         response: BidQuote,
     },
     SwapSetupCompleted(Box<Result<State2>>),
+    SellXmrSetupCompleted(Box<Result<State3>>),
     TransferProofReceived {
         msg: Box<transfer_proof::Request>,
         channel: ResponseChannel<()>,
@@ -26,7 +30,26 @@ pub enum OutEvent {
     EncryptedSignatureAcknowledged {
         id: RequestId,
     },
-    AllRedialAttemptsExhausted {
+    /// Received in swaps in which we sell XMR.
+    TransferProofAcknowledged {
+        id: RequestId,
+    },
+    /// Received in swaps in which we sell XMR.
+    EncryptedSignatureReceived {
+        msg: encrypted_signature::Request,
+        channel: ResponseChannel<()>,
+        peer: PeerId,
+    },
+    AbortReceived {
+        msg: abort::Request,
+        channel: ResponseChannel<()>,
+        peer: PeerId,
+    },
+    AbortAcknowledged {
+        id: RequestId,
+    },
+    /// Alice could not be reached again after losing the connection.
+    PeerUnreachable {
         peer: PeerId,
     },
     Failure {
@@ -54,16 +77,24 @@ impl OutEvent {
     }
 }
 
-/// A `NetworkBehaviour` that represents an XMR/BTC swap node as Bob.
+/// A `NetworkBehaviour` that represents an XMR/BTC swap node as Bob, or as
+/// Alice when selling XMR.
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "OutEvent", event_process = false)]
 #[allow(missing_debug_implementations)]
 pub struct Behaviour {
     pub quote: quote::Behaviour,
     pub swap_setup: bob::Behaviour,
+    pub sell_xmr_setup: sell_xmr::alice::Behaviour,
     pub transfer_proof: transfer_proof::Behaviour,
     pub encrypted_signature: encrypted_signature::Behaviour,
-    pub redial: redial::Behaviour,
+    pub abort: abort::Behaviour,
+    pub connection_manager: connection_manager::Behaviour,
+    /// Tells the seller the address we see it on, for its reachability check.
+    identify: identify::Behaviour,
+    /// Dials the seller through a relay node if it is only reachable on a
+    /// relayed address.
+    relay: Relay,
 
     /// Ping behaviour that ensures that the underlying network connection is
     /// still alive. If the ping fails a connection close event will be
@@ -76,14 +107,20 @@ impl Behaviour {
         alice: PeerId,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
+        local_public_key: identity::PublicKey,
+        relay: Relay,
     ) -> Self {
         Self {
             quote: quote::cli(),
-            swap_setup: bob::Behaviour::new(env_config, bitcoin_wallet),
-            transfer_proof: transfer_proof::bob(),
-            encrypted_signature: encrypted_signature::bob(),
-            redial: redial::Behaviour::new(alice, Duration::from_secs(2)),
-            ping: Ping::new(PingConfig::new().with_keep_alive(true)),
+            swap_setup: bob::Behaviour::new(env_config, bitcoin_wallet.clone()),
+            sell_xmr_setup: sell_xmr::alice::Behaviour::new(env_config, bitcoin_wallet),
+            transfer_proof: transfer_proof::alice_and_bob(),
+            encrypted_signature: encrypted_signature::alice_and_bob(),
+            abort: abort::new(),
+            connection_manager: connection_manager::cli(alice),
+            identify: identify::new(local_public_key),
+            relay,
+            ping: Ping::new(PingConfig::new()),
         }
     }
 
@@ -91,7 +128,9 @@ impl Behaviour {
     pub fn add_address(&mut self, peer_id: PeerId, address: Multiaddr) {
         self.quote.add_address(&peer_id, address.clone());
         self.transfer_proof.add_address(&peer_id, address.clone());
-        self.encrypted_signature.add_address(&peer_id, address);
+        self.encrypted_signature
+            .add_address(&peer_id, address.clone());
+        self.abort.add_address(&peer_id, address);
     }
 }
 
@@ -100,3 +139,16 @@ impl From<PingEvent> for OutEvent {
         OutEvent::Other
     }
 }
+
+/// The relay behaviour does not emit any events.
+impl From<()> for OutEvent {
+    fn from(_: ()) -> Self {
+        OutEvent::Other
+    }
+}
+
+impl From<identify::OutEvent> for OutEvent {
+    fn from(_: identify::OutEvent) -> Self {
+        OutEvent::Other
+    }
+}
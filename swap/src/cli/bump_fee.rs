@@ -0,0 +1,84 @@
+use crate::bitcoin::{Txid, Wallet};
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use anyhow::{bail, Context, Result};
+use std::convert::TryInto;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Bumps the fee of the Bitcoin lock transaction of a swap that is stuck in
+/// the mempool.
+///
+/// The lock transaction cannot be replaced because the cancel and refund
+/// transactions Alice and Bob signed during the swap setup spend it by its
+/// txid. Instead, the change output of the lock transaction is spent in a child
+/// transaction that pays enough fees for both (child pays for parent). This
+/// requires the change of the lock transaction to be sent to the internal
+/// wallet.
+///
+/// Returns the txid of the child transaction.
+pub async fn bump_fee(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+) -> Result<Txid> {
+    let state = db.get_state(swap_id).await?.try_into()?;
+
+    let state3 = match state {
+        BobState::BtcLocked(state3) => state3,
+        BobState::Started { .. } | BobState::SwapSetupCompleted(_) => bail!(
+            "Cannot bump the fee of swap {} because the Bitcoin lock transaction has not been published yet",
+            swap_id
+        ),
+        state => bail!(
+            "Cannot bump the fee of swap {} because it is in state {} in which the Bitcoin lock transaction is already confirmed",
+            swap_id,
+            state
+        ),
+    };
+
+    bitcoin_wallet.sync().await?;
+
+    let tx_lock_status = bitcoin_wallet.status_of_script(&state3.tx_lock).await?;
+    if tx_lock_status.is_confirmed() {
+        bail!(
+            "The Bitcoin lock transaction of swap {} has already been confirmed",
+            swap_id
+        );
+    }
+    if !tx_lock_status.has_been_seen() {
+        bail!(
+            "The Bitcoin lock transaction of swap {} is not known to the network, resume the swap to publish it",
+            swap_id
+        );
+    }
+
+    let txid = state3.tx_lock_id();
+    let tx_lock = bitcoin_wallet.get_raw_transaction(txid).await?;
+    let tx_lock_fee = bitcoin_wallet.transaction_fee(txid).await?;
+
+    let vout = bitcoin_wallet.owned_output(&tx_lock).await?.with_context(|| {
+        format!(
+            "Cannot bump the fee of swap {} because the Bitcoin lock transaction has no change output paying to the internal wallet",
+            swap_id
+        )
+    })?;
+
+    let psbt = bitcoin_wallet
+        .child_pays_for_parent(&tx_lock, tx_lock_fee, vout)
+        .await
+        .context("Failed to bump the fee of the Bitcoin lock transaction")?
+        .with_context(|| {
+            format!(
+                "The Bitcoin lock transaction of swap {} already pays the current fee rate",
+                swap_id
+            )
+        })?;
+
+    let child = bitcoin_wallet.sign_and_finalize(psbt).await?;
+    let (child_txid, _) = bitcoin_wallet.broadcast(child, "lock fee bump").await?;
+
+    tracing::info!(%swap_id, %txid, %child_txid, "Bumped the fee of the Bitcoin lock transaction");
+
+    Ok(child_txid)
+}
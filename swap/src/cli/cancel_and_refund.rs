@@ -0,0 +1,160 @@
+use crate::bitcoin::{parse_rpc_error_code, ExpiredTimelocks, RpcErrorCode, Transaction, Wallet};
+use crate::protocol::bob::{BobState, State6};
+use crate::protocol::Database;
+use anyhow::{bail, Context, Result};
+use std::convert::TryInto;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Brings a swap to the point where Bob's Bitcoin is refunded, independently
+/// of the state the swap is recorded in.
+///
+/// The on-chain state of the cancel and the refund transaction is inspected
+/// and only the transactions that are still missing are rebuilt and published.
+/// If the refund transaction is stuck in the mempool because it pays less than
+/// the current fee rate, its fee is bumped with a child transaction.
+pub async fn cancel_and_refund(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+) -> Result<BobState> {
+    let state6 = latest_refundable_state(swap_id, db.as_ref()).await?;
+
+    let tx_cancel_status = bitcoin_wallet.status_of_script(&state6.tx_cancel()).await?;
+    let tx_refund_status = bitcoin_wallet.status_of_script(&state6.tx_refund()).await?;
+
+    tracing::info!(%swap_id, %tx_cancel_status, %tx_refund_status, "Inspected cancel and refund transaction");
+
+    if tx_refund_status.is_confirmed() {
+        tracing::info!(%swap_id, "Refund transaction has already been confirmed");
+        return record_refunded(swap_id, state6, db.as_ref()).await;
+    }
+
+    if !tx_cancel_status.has_been_seen() {
+        if let ExpiredTimelocks::None = state6.expired_timelock(bitcoin_wallet.as_ref()).await? {
+            bail!(
+                "Cannot cancel swap {} because the cancel timelock has not expired yet",
+                swap_id
+            );
+        }
+
+        match state6.submit_tx_cancel(bitcoin_wallet.as_ref()).await {
+            Ok(_) => {}
+            Err(err) if is_rpc_error(&err, RpcErrorCode::RpcVerifyAlreadyInChain) => {
+                tracing::info!(%swap_id, "Cancel transaction has already been confirmed on chain")
+            }
+            Err(err) => return Err(err),
+        }
+
+        db.insert_latest_state(swap_id, BobState::BtcCancelled(state6.clone()).into())
+            .await?;
+    }
+
+    let tx_refund = state6.signed_refund_transaction()?;
+
+    if !tx_refund_status.has_been_seen() {
+        match bitcoin_wallet.broadcast(tx_refund.clone(), "refund").await {
+            Ok(_) => {}
+            Err(err) if is_rpc_error(&err, RpcErrorCode::RpcVerifyError) => {
+                return Err(err.context(format!(
+                    "The output of the cancel transaction of swap {} has already been spent, the Bitcoin was most likely punished",
+                    swap_id
+                )));
+            }
+            Err(err) if is_rpc_error(&err, RpcErrorCode::RpcVerifyRejected) => {
+                return Err(err.context(
+                    "The refund transaction was rejected. Its fee is fixed when the swap is set up and may be below the current mempool minimum fee, try again once the mempool has cleared",
+                ));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    // The refund is published at this point, failing to bump its fee must not
+    // keep the swap from being recorded as refunded once it is final.
+    if let Err(error) = bump_refund_fee(bitcoin_wallet.as_ref(), &tx_refund, &state6).await {
+        tracing::warn!(%swap_id, "Failed to bump the fee of the refund transaction, waiting for it to confirm at its current fee: {:#}", error);
+    }
+
+    bitcoin_wallet
+        .subscribe_to(state6.tx_refund())
+        .await
+        .wait_until_final()
+        .await?;
+
+    record_refunded(swap_id, state6, db.as_ref()).await
+}
+
+/// Bumps the fee of the refund transaction with a child transaction if it pays
+/// less than the current fee rate.
+///
+/// The child spends the refund output, hence this is only possible if the
+/// Bitcoin is refunded to this wallet and not to an external address.
+async fn bump_refund_fee(
+    bitcoin_wallet: &Wallet,
+    tx_refund: &Transaction,
+    state6: &State6,
+) -> Result<()> {
+    bitcoin_wallet.sync().await?;
+    let vout = bitcoin_wallet.owned_output(tx_refund).await?.context(
+        "The refund output is not owned by the wallet, e.g. because it goes to an external address",
+    )?;
+
+    let child = bitcoin_wallet
+        .child_pays_for_parent(tx_refund, state6.tx_refund_fee, vout)
+        .await?;
+
+    if let Some(psbt) = child {
+        let tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+        bitcoin_wallet.broadcast(tx, "refund fee bump").await?;
+    }
+
+    Ok(())
+}
+
+/// Finds the most recent state of the swap from which the cancel and refund
+/// transactions can be rebuilt.
+///
+/// States such as [`BobState::BtcPunished`] don't hold the data needed to
+/// build these transactions, hence we look through the history of the swap.
+pub(crate) async fn latest_refundable_state(swap_id: Uuid, db: &dyn Database) -> Result<State6> {
+    for (_, state) in db.get_states(swap_id).await?.into_iter().rev() {
+        let state6 = match state.try_into()? {
+            BobState::BtcLocked(state3) => state3.cancel(),
+            BobState::XmrLockProofReceived { state, .. } => state.cancel(),
+            BobState::XmrLocked(state4) => state4.cancel(),
+            BobState::EncSigSent(state4) => state4.cancel(),
+            BobState::CancelTimelockExpired(state6) => state6,
+            BobState::BtcCancelled(state6) => state6,
+            BobState::BtcRefunded(state6) => state6,
+            state @ BobState::BtcRedeemed(_) | state @ BobState::XmrRedeemed { .. } => bail!(
+                "Cannot refund swap {} because it is in state {} which is not refundable.",
+                swap_id,
+                state
+            ),
+            BobState::Started { .. }
+            | BobState::SwapSetupCompleted(_)
+            | BobState::BtcPunished { .. }
+            | BobState::SafelyAborted => continue,
+        };
+
+        return Ok(state6);
+    }
+
+    bail!(
+        "Cannot refund swap {} because no Bitcoin was locked",
+        swap_id
+    )
+}
+
+async fn record_refunded(swap_id: Uuid, state6: State6, db: &dyn Database) -> Result<BobState> {
+    let state = BobState::BtcRefunded(state6);
+    db.insert_latest_state(swap_id, state.clone().into())
+        .await?;
+
+    Ok(state)
+}
+
+fn is_rpc_error(error: &anyhow::Error, code: RpcErrorCode) -> bool {
+    matches!(parse_rpc_error_code(error), Ok(actual) if actual == i64::from(code))
+}
@@ -0,0 +1,341 @@
+//! Drive swaps in which we buy XMR from another application, e.g. a GUI.
+//!
+//! The [`SwapClient`] sets up the connection to the seller and runs the state
+//! machine of Bob. Every state a swap enters is stored in the database and
+//! sent as a [`SwapEvent`] to the receiver returned by [`SwapClient::new`].
+
+use crate::bitcoin::{Txid, Wallet};
+use crate::cli::{Behaviour, EventLoop, EventLoopHandle, PeerUnreachable};
+use crate::libp2p_ext::MultiAddrExt;
+use crate::network::quote::BidQuote;
+use crate::network::swap_setup::{ConfirmationSchedule, Timelocks};
+use crate::network::swarm;
+use crate::protocol::bob::{BobState, MoneroWallet, Swap};
+use crate::protocol::{bob, Database, State};
+use crate::{bitcoin, cli, env, monero};
+use anyhow::{bail, Context, Result};
+use libp2p::{identity, Multiaddr, PeerId};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub enum SwapEvent {
+    /// The swap entered a new state, the state is already stored in the
+    /// database.
+    StateChanged { swap_id: Uuid, state: BobState },
+    /// The client stopped running the swap in this state, because the swap is
+    /// complete or reached the state it was run until.
+    Stopped { swap_id: Uuid, state: BobState },
+    /// Running the swap failed, resume it to continue.
+    Failed { swap_id: Uuid, error: String },
+}
+
+/// The terms of a new swap, usually determined from the quote of the seller.
+#[derive(Debug, Clone)]
+pub struct SwapRequest {
+    pub btc_amount: bitcoin::Amount,
+    pub monero_receive_address: monero::Address,
+    pub bitcoin_refund_address: bitcoin::Address,
+    pub timelocks: Option<Timelocks>,
+    pub confirmation_schedule: Option<ConfirmationSchedule>,
+}
+
+/// A connection to the seller of a swap, made by [`SwapClient::connect`].
+pub struct Connection {
+    swap_id: Uuid,
+    seller: PeerId,
+    seller_addresses: Vec<Multiaddr>,
+    event_loop: JoinHandle<Result<(), PeerUnreachable>>,
+    event_loop_handle: EventLoopHandle,
+}
+
+impl Connection {
+    pub fn swap_id(&self) -> Uuid {
+        self.swap_id
+    }
+
+    pub fn seller(&self) -> PeerId {
+        self.seller
+    }
+
+    pub async fn request_quote(&mut self) -> Result<BidQuote> {
+        self.event_loop_handle.request_quote().await
+    }
+
+    /// Closes the connection without starting a swap.
+    pub fn close(self) {
+        self.event_loop.abort();
+    }
+}
+
+#[derive(Clone)]
+pub struct SwapClient {
+    db: Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: Arc<Wallet>,
+    monero_wallet: Option<MoneroWallet>,
+    network: Option<(identity::Keypair, u16)>,
+    env_config: env::Config,
+    events: mpsc::UnboundedSender<SwapEvent>,
+}
+
+impl SwapClient {
+    /// A client that can cancel and refund swaps, use
+    /// [`SwapClient::with_monero_wallet`] and [`SwapClient::with_network`] to
+    /// start and resume swaps as well.
+    ///
+    /// Events are dropped once the returned receiver is dropped.
+    pub fn new(
+        db: Arc<dyn Database + Send + Sync>,
+        bitcoin_wallet: Arc<Wallet>,
+        env_config: env::Config,
+    ) -> (Self, mpsc::UnboundedReceiver<SwapEvent>) {
+        let (events, receiver) = mpsc::unbounded_channel();
+
+        let client = Self {
+            db,
+            bitcoin_wallet,
+            monero_wallet: None,
+            network: None,
+            env_config,
+            events,
+        };
+
+        (client, receiver)
+    }
+
+    /// The wallet that receives the Monero of the swaps.
+    pub fn with_monero_wallet(self, monero_wallet: MoneroWallet) -> Self {
+        Self {
+            monero_wallet: Some(monero_wallet),
+            ..self
+        }
+    }
+
+    /// The identity the client connects to sellers with, and the port of the
+    /// Tor SOCKS5 proxy to connect to onion addresses through.
+    pub fn with_network(self, identity: identity::Keypair, tor_socks5_port: u16) -> Self {
+        Self {
+            network: Some((identity, tor_socks5_port)),
+            ..self
+        }
+    }
+
+    /// Connects to the seller for the swap with the given id, e.g. to request
+    /// a quote before starting the swap.
+    pub async fn connect(&self, swap_id: Uuid, seller: Multiaddr) -> Result<Connection> {
+        let seller_peer_id = seller
+            .extract_peer_id()
+            .context("Seller address must contain peer ID")?;
+
+        self.connect_to(swap_id, seller_peer_id, vec![seller]).await
+    }
+
+    /// Starts a new swap over the connection and runs it until it is complete.
+    pub async fn start(&self, connection: Connection, request: SwapRequest) -> Result<BobState> {
+        let is_target_state = bob::swap::is_target_state_of(self.monero_wallet()?);
+
+        self.start_until(connection, request, is_target_state).await
+    }
+
+    /// Starts a new swap over the connection and runs it until it reaches a
+    /// state for which `is_target_state` returns true.
+    pub async fn start_until(
+        &self,
+        connection: Connection,
+        request: SwapRequest,
+        is_target_state: fn(&BobState) -> bool,
+    ) -> Result<BobState> {
+        let swap_id = connection.swap_id;
+
+        // the addresses of the seller are needed to resume the swap
+        for seller_address in connection.seller_addresses {
+            self.db
+                .insert_address(connection.seller, seller_address)
+                .await?;
+        }
+        self.db.insert_peer_id(swap_id, connection.seller).await?;
+        self.db
+            .insert_monero_address(swap_id, request.monero_receive_address)
+            .await?;
+
+        let swap = Swap::new(
+            self.db.clone(),
+            swap_id,
+            self.bitcoin_wallet.clone(),
+            self.monero_wallet()?.clone(),
+            self.env_config,
+            connection.event_loop_handle,
+            request.monero_receive_address,
+            request.bitcoin_refund_address,
+            request.btc_amount,
+            request.timelocks,
+            request.confirmation_schedule,
+        );
+
+        // Record the swap before setting it up so that it can be resumed if we
+        // stop before Bitcoin was locked.
+        self.db
+            .insert_latest_state(swap_id, swap.state.clone().into())
+            .await?;
+        self.report_state(swap_id, &swap.state);
+
+        self.run(
+            swap,
+            connection.seller,
+            connection.event_loop,
+            is_target_state,
+        )
+        .await
+    }
+
+    /// Reconnects to the seller of a swap and runs the swap from its latest
+    /// state until it is complete.
+    pub async fn resume(&self, swap_id: Uuid) -> Result<BobState> {
+        if let State::Alice(_) = self.db.get_state(swap_id).await? {
+            bail!(
+                "Swap {} sold XMR, only swaps that buy XMR can be resumed by the client",
+                swap_id
+            )
+        }
+
+        let seller = self.db.get_peer_id(swap_id).await?;
+        let seller_addresses = self.db.get_addresses(seller).await?;
+        let monero_receive_address = self.db.get_monero_address(swap_id).await?;
+        let monero_wallet = self.monero_wallet()?.clone();
+        let is_target_state = bob::swap::is_target_state_of(&monero_wallet);
+
+        let connection = self.connect_to(swap_id, seller, seller_addresses).await?;
+        let swap = Swap::from_db(
+            self.db.clone(),
+            swap_id,
+            self.bitcoin_wallet.clone(),
+            monero_wallet,
+            self.env_config,
+            connection.event_loop_handle,
+            monero_receive_address,
+        )
+        .await?;
+
+        self.run(swap, seller, connection.event_loop, is_target_state)
+            .await
+    }
+
+    /// Publishes the cancel transaction of a swap, see [`cli::cancel`].
+    pub async fn cancel(&self, swap_id: Uuid) -> Result<(Txid, BobState)> {
+        let (txid, state) = cli::cancel(swap_id, self.bitcoin_wallet.clone(), self.db.clone())
+            .await
+            .map_err(|error| self.report_failure(swap_id, error))?;
+        self.report_state(swap_id, &state);
+
+        Ok((txid, state))
+    }
+
+    /// Publishes the refund transaction of a cancelled swap, see
+    /// [`cli::refund`].
+    pub async fn refund(&self, swap_id: Uuid) -> Result<BobState> {
+        let state = cli::refund(swap_id, self.bitcoin_wallet.clone(), self.db.clone())
+            .await
+            .map_err(|error| self.report_failure(swap_id, error))?;
+        self.report_state(swap_id, &state);
+
+        Ok(state)
+    }
+
+    async fn connect_to(
+        &self,
+        swap_id: Uuid,
+        seller: PeerId,
+        seller_addresses: Vec<Multiaddr>,
+    ) -> Result<Connection> {
+        let (identity, tor_socks5_port) = self
+            .network
+            .clone()
+            .context("The client needs a network identity to connect to sellers")?;
+
+        let mut swarm = swarm::cli(identity.clone(), tor_socks5_port, |relay| {
+            Behaviour::new(
+                seller,
+                self.env_config,
+                self.bitcoin_wallet.clone(),
+                identity.public(),
+                relay,
+            )
+        })
+        .await?;
+        for seller_address in &seller_addresses {
+            swarm
+                .behaviour_mut()
+                .add_address(seller, seller_address.clone());
+        }
+
+        tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
+
+        let (event_loop, event_loop_handle) =
+            EventLoop::new(swap_id, swarm, seller, self.env_config)?;
+
+        Ok(Connection {
+            swap_id,
+            seller,
+            seller_addresses,
+            event_loop: tokio::spawn(event_loop.run()),
+            event_loop_handle,
+        })
+    }
+
+    async fn run(
+        &self,
+        swap: Swap,
+        seller: PeerId,
+        mut event_loop: JoinHandle<Result<(), PeerUnreachable>>,
+        is_target_state: fn(&BobState) -> bool,
+    ) -> Result<BobState> {
+        let swap_id = swap.id;
+        let on_state = |state: &BobState| self.report_state(swap_id, state);
+
+        let result = tokio::select! {
+            result = &mut event_loop => match result.context("EventLoop panicked") {
+                Ok(Ok(())) => Err(anyhow::anyhow!("Lost connection to seller {}", seller)),
+                Ok(Err(error)) => Err(error.into()),
+                Err(error) => Err(error),
+            },
+            result = bob::run_until_and_report(swap, is_target_state, on_state) => result,
+        };
+        event_loop.abort();
+
+        match result {
+            Ok(state) => {
+                let _ = self.events.send(SwapEvent::Stopped {
+                    swap_id,
+                    state: state.clone(),
+                });
+
+                Ok(state)
+            }
+            Err(error) => Err(self.report_failure(swap_id, error)),
+        }
+    }
+
+    fn monero_wallet(&self) -> Result<&MoneroWallet> {
+        self.monero_wallet
+            .as_ref()
+            .context("The client needs a Monero wallet to run swaps")
+    }
+
+    fn report_state(&self, swap_id: Uuid, state: &BobState) {
+        let _ = self.events.send(SwapEvent::StateChanged {
+            swap_id,
+            state: state.clone(),
+        });
+    }
+
+    fn report_failure(&self, swap_id: Uuid, error: anyhow::Error) -> anyhow::Error {
+        let _ = self.events.send(SwapEvent::Failed {
+            swap_id,
+            error: format!("{:#}", error),
+        });
+
+        error
+    }
+}
@@ -1,13 +1,21 @@
-use crate::bitcoin::Amount;
+use crate::bitcoin::{Amount, ConfirmationTargets};
+use crate::cli::address_book::{AddressBookEntry, AddressOrLabel, BookAddress};
+use crate::cli::export_psbt::ExportableTx;
+use crate::encryption::PassphraseSource;
 use crate::env::GetConfig;
 use crate::fs::system_data_dir;
-use crate::network::rendezvous::XmrBtcNamespace;
+use crate::logging::LogFormat;
+use crate::network::rendezvous::RendezvousNamespace;
+use crate::network::swap_setup::{ConfirmationSchedule, ConfirmationTier};
+use crate::protocol::state_machine::{GraphFormat, Role};
 use crate::{env, monero};
 use anyhow::{bail, Context, Result};
+use bitcoin::util::bip32::Fingerprint;
 use bitcoin::{Address, AddressType};
 use libp2p::core::Multiaddr;
 use serde::Serialize;
 use std::ffi::OsString;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
 use structopt::{clap, StructOpt};
@@ -28,11 +36,18 @@ const DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET: usize = 1;
 
 const DEFAULT_TOR_SOCKS5_PORT: &str = "9050";
 
+const DEFAULT_MONITOR_BIND_ADDRESS: &str = "127.0.0.1:9945";
+
+pub const PASSPHRASE_ENV_VAR: &str = "SWAP_PASSPHRASE";
+
 #[derive(Debug, PartialEq)]
 pub struct Arguments {
     pub env_config: env::Config,
     pub debug: bool,
     pub json: bool,
+    /// Where to read the passphrase from that encrypts the seed and the
+    /// database, if it is not given in the environment.
+    pub passphrase: Option<PassphraseSource>,
     pub data_dir: PathBuf,
     pub cmd: Command,
 }
@@ -65,39 +80,150 @@ where
     };
 
     let debug = args.debug;
-    let json = args.json;
+    let json = args.json || args.log_format == LogFormat::Json;
     let is_testnet = args.testnet;
+    let is_regtest = args.regtest;
     let data = args.data;
+    let passphrase = match (args.passphrase_file, args.encrypt) {
+        (Some(path), _) => Some(PassphraseSource::File(path)),
+        (None, true) => Some(PassphraseSource::Prompt),
+        (None, false) => None,
+    };
 
-    let arguments = match args.cmd {
+    let mut arguments = match args.cmd {
         RawCommand::BuyXmr {
-            seller: Seller { seller },
+            sellers,
+            select_best,
+            rendezvous_points,
+            amount,
+            split,
+            cancel_timelock,
+            punish_timelock,
+            monero_confirmations,
             bitcoin,
-            bitcoin_change_address,
+            bitcoin_confirmation_targets,
+            bitcoin_wallet_file,
+            hardware_wallet,
+            bitcoin_refund_address,
             monero,
+            monero_lock_verification,
             monero_receive_address,
             tor: Tor { tor_socks5_port },
+            json_progress,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
-            let monero_daemon_address = monero.apply_defaults(is_testnet);
-            let monero_receive_address =
-                validate_monero_address(monero_receive_address, is_testnet)?;
-            let bitcoin_change_address =
-                validate_bitcoin_address(bitcoin_change_address, is_testnet)?;
+            let bitcoin_confirmation_targets =
+                bitcoin_confirmation_targets.apply_defaults(bitcoin_target_block);
+            let (monero_daemon_address, monero_light_wallet, monero_auto_update) =
+                monero.apply_defaults(is_testnet);
+            let (monero_verify_daemons, monero_verify_quorum) =
+                monero_lock_verification.validate()?;
+            let monero_receive_address = monero_receive_address
+                .validate(|address| validate_monero_address(address, is_testnet))?;
+            let bitcoin_refund_address = bitcoin_refund_address
+                .map(|address| {
+                    address.validate(|address| validate_bitcoin_address(address, is_testnet))
+                })
+                .transpose()?;
+            let env_config = env_config_from(is_testnet);
+            let confirmation_schedule = if monero_confirmations.is_empty() {
+                None
+            } else {
+                Some(ConfirmationSchedule::new(
+                    monero_confirmations,
+                    env_config.monero_finality_confirmations,
+                )?)
+            };
+            if split == 0 {
+                bail!("--split has to be at least 1")
+            }
+            if select_best && split != 1 {
+                bail!(
+                    "--select-best swaps with a single seller and cannot be combined with --split"
+                )
+            }
+            let select_best = match (select_best, amount) {
+                (true, Some(btc_amount)) => Some(SelectBest {
+                    rendezvous_points,
+                    namespace: rendezvous_namespace_from(is_testnet),
+                    btc_amount,
+                }),
+                _ => None,
+            };
+            if sellers.len() > split {
+                bail!(
+                    "Got {} sellers for {} swaps, pass --split to spread the swaps over several sellers",
+                    sellers.len(),
+                    split
+                )
+            }
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
+                passphrase,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::BuyXmr {
-                    seller,
-                    bitcoin_electrum_rpc_url,
+                    sellers,
+                    select_best,
+                    split,
+                    cancel_timelock,
+                    punish_timelock,
+                    confirmation_schedule,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
-                    bitcoin_change_address,
+                    bitcoin_confirmation_targets,
+                    bitcoin_wallet_file,
+                    hardware_wallet,
+                    bitcoin_refund_address,
                     monero_receive_address,
                     monero_daemon_address,
+                    monero_light_wallet,
+                    monero_auto_update,
+                    monero_verify_daemons,
+                    monero_verify_quorum,
+                    tor_socks5_port,
+                    json_progress,
+                },
+            }
+        }
+        RawCommand::SellXmr {
+            seller: Seller { seller },
+            bitcoin,
+            bitcoin_receive_address,
+            monero,
+            monero_refund_address,
+            tor: Tor { tor_socks5_port },
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+            let (monero_daemon_address, monero_light_wallet, monero_auto_update) =
+                monero.apply_defaults(is_testnet);
+            if monero_light_wallet {
+                bail!("The Monero light wallet cannot send Monero, sell-xmr requires monero-wallet-rpc")
+            }
+            let bitcoin_receive_address =
+                validate_bitcoin_address(bitcoin_receive_address, is_testnet)?;
+            let monero_refund_address = monero_refund_address
+                .map(|address| validate_monero_address(address, is_testnet))
+                .transpose()?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                passphrase,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::SellXmr {
+                    seller,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    bitcoin_receive_address,
+                    monero_refund_address,
+                    monero_daemon_address,
+                    monero_auto_update,
                     tor_socks5_port,
                 },
             }
@@ -106,33 +232,44 @@ where
             env_config: env_config_from(is_testnet),
             debug,
             json,
+            passphrase,
             data_dir: data::data_dir_from(data, is_testnet)?,
             cmd: Command::History,
         },
+        RawCommand::Monitor { bind_address } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::Monitor { bind_address },
+        },
         RawCommand::Config => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
+            passphrase,
             data_dir: data::data_dir_from(data, is_testnet)?,
             cmd: Command::Config,
         },
         RawCommand::Balance {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
         } => {
             let bitcoin = Bitcoin {
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 bitcoin_target_block: None,
             };
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                passphrase,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Balance {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
@@ -142,16 +279,17 @@ where
             amount,
             address,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                passphrase,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::WithdrawBtc {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                     amount,
                     address: bitcoin_address(address, is_testnet)?,
@@ -161,23 +299,64 @@ where
         RawCommand::Resume {
             swap_id: SwapId { swap_id },
             bitcoin,
+            bitcoin_confirmation_targets,
+            bitcoin_wallet_file,
+            hardware_wallet,
             monero,
+            monero_lock_verification,
             tor: Tor { tor_socks5_port },
+            json_progress,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
-            let monero_daemon_address = monero.apply_defaults(is_testnet);
+            let bitcoin_confirmation_targets =
+                bitcoin_confirmation_targets.apply_defaults(bitcoin_target_block);
+            let (monero_daemon_address, monero_light_wallet, monero_auto_update) =
+                monero.apply_defaults(is_testnet);
+            let (monero_verify_daemons, monero_verify_quorum) =
+                monero_lock_verification.validate()?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                passphrase,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Resume {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
+                    bitcoin_confirmation_targets,
+                    bitcoin_wallet_file,
+                    hardware_wallet,
                     monero_daemon_address,
+                    monero_light_wallet,
+                    monero_auto_update,
+                    monero_verify_daemons,
+                    monero_verify_quorum,
+                    tor_socks5_port,
+                    json_progress,
+                },
+            }
+        }
+        RawCommand::Abort {
+            swap_id: SwapId { swap_id },
+            bitcoin,
+            tor: Tor { tor_socks5_port },
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                passphrase,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::Abort {
+                    swap_id,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
                     tor_socks5_port,
                 },
             }
@@ -186,17 +365,18 @@ where
             swap_id: SwapId { swap_id },
             bitcoin,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                passphrase,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Cancel {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
@@ -205,104 +385,427 @@ where
             swap_id: SwapId { swap_id },
             bitcoin,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                passphrase,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Refund {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                },
+            }
+        }
+        RawCommand::CancelAndRefund {
+            swap_id: SwapId { swap_id },
+            bitcoin,
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                passphrase,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::CancelAndRefund {
+                    swap_id,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                },
+            }
+        }
+        RawCommand::BumpFee {
+            swap_id: SwapId { swap_id },
+            bitcoin,
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                passphrase,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::BumpFee {
+                    swap_id,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                },
+            }
+        }
+        RawCommand::ExportPsbt {
+            swap_id: SwapId { swap_id },
+            tx,
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::ExportPsbt { swap_id, tx },
+        },
+        RawCommand::Export {
+            swap_id: SwapId { swap_id },
+            archive: Archive {
+                path,
+                passphrase_file,
+            },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::ExportSwap {
+                swap_id,
+                archive: path,
+                archive_passphrase_file: passphrase_file,
+            },
+        },
+        RawCommand::Import {
+            archive: Archive {
+                path,
+                passphrase_file,
+            },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::ImportSwap {
+                archive: path,
+                archive_passphrase_file: passphrase_file,
+            },
+        },
+        RawCommand::ImportSled { sled_dir } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::ImportSled { sled_dir },
+        },
+        RawCommand::VerifyReceipt {
+            swap_id: SwapId { swap_id },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::VerifyReceipt { swap_id },
+        },
+        RawCommand::Audit {
+            swap_id: SwapId { swap_id },
+            bitcoin,
+            monero_daemon_address,
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                passphrase,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::Audit {
+                    swap_id,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
+                    monero_daemon_address: monero_daemon_address
+                        .unwrap_or_else(|| default_monero_daemon_address(is_testnet)),
                 },
             }
         }
         RawCommand::ListSellers {
             rendezvous_point,
+            namespace,
+            watch,
             tor: Tor { tor_socks5_port },
         } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
+            passphrase,
             data_dir: data::data_dir_from(data, is_testnet)?,
             cmd: Command::ListSellers {
-                rendezvous_point,
-                namespace: rendezvous_namespace_from(is_testnet),
+                rendezvous_points: rendezvous_point,
+                namespace: namespace.unwrap_or_else(|| rendezvous_namespace_from(is_testnet)),
                 tor_socks5_port,
+                watch,
             },
         },
         RawCommand::ExportBitcoinWallet { bitcoin } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                passphrase,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::ExportBitcoinWallet {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
         }
+        RawCommand::ExportSeed => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::ExportSeed,
+        },
+        RawCommand::ImportSeed { mnemonic_file } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::ImportSeed { mnemonic_file },
+        },
+        RawCommand::Debug(DebugCommand::StateMachine {
+            role,
+            format,
+            swap_id,
+            lock_height,
+        }) => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: match swap_id {
+                Some(swap_id) => Command::SimulateSwap {
+                    swap_id,
+                    lock_height,
+                },
+                None => Command::StateMachine { role, format },
+            },
+        },
+        RawCommand::AddressBook(AddressBookCommand::Add { label, address }) => {
+            let address = match address {
+                BookAddress::Bitcoin(address) => {
+                    BookAddress::Bitcoin(validate_bitcoin_address(address, is_testnet)?)
+                }
+                BookAddress::Monero(address) => {
+                    BookAddress::Monero(validate_monero_address(address, is_testnet)?)
+                }
+            };
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                passphrase,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::AddressBookAdd {
+                    entry: AddressBookEntry::new(label, address)?,
+                },
+            }
+        }
+        RawCommand::AddressBook(AddressBookCommand::Remove { label }) => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::AddressBookRemove { label },
+        },
+        RawCommand::AddressBook(AddressBookCommand::List) => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            passphrase,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::AddressBookList,
+        },
     };
 
+    // everything but the blockchain parameters keeps the mainnet defaults
+    if is_regtest {
+        arguments.env_config = env::Regtest::get_config();
+    }
+
     Ok(ParseResult::Arguments(arguments))
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
     BuyXmr {
+        /// Empty if the seller is selected from the rendezvous points.
+        sellers: Vec<Multiaddr>,
+        select_best: Option<SelectBest>,
+        split: usize,
+        cancel_timelock: Option<u32>,
+        punish_timelock: Option<u32>,
+        confirmation_schedule: Option<ConfirmationSchedule>,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        bitcoin_confirmation_targets: ConfirmationTargets,
+        bitcoin_wallet_file: Option<PathBuf>,
+        hardware_wallet: Option<Fingerprint>,
+        /// Defaults to an address of the Bitcoin wallet.
+        bitcoin_refund_address: Option<AddressOrLabel<bitcoin::Address>>,
+        monero_receive_address: AddressOrLabel<monero::Address>,
+        monero_daemon_address: String,
+        monero_light_wallet: bool,
+        monero_auto_update: bool,
+        /// The daemons the Monero lock transaction is cross-checked against.
+        monero_verify_daemons: Vec<String>,
+        /// How many of the verifying daemons have to agree, a majority if not
+        /// given.
+        monero_verify_quorum: Option<usize>,
+        tor_socks5_port: u16,
+        json_progress: bool,
+    },
+    SellXmr {
         seller: Multiaddr,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
-        bitcoin_change_address: bitcoin::Address,
-        monero_receive_address: monero::Address,
+        bitcoin_receive_address: bitcoin::Address,
+        monero_refund_address: Option<monero::Address>,
         monero_daemon_address: String,
+        monero_auto_update: bool,
         tor_socks5_port: u16,
     },
     History,
+    Monitor {
+        bind_address: SocketAddr,
+    },
     Config,
     WithdrawBtc {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
         amount: Option<Amount>,
         address: Address,
     },
     Balance {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     Resume {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
+        bitcoin_confirmation_targets: ConfirmationTargets,
+        bitcoin_wallet_file: Option<PathBuf>,
+        hardware_wallet: Option<Fingerprint>,
         monero_daemon_address: String,
+        monero_light_wallet: bool,
+        monero_auto_update: bool,
+        /// The daemons the Monero lock transaction is cross-checked against.
+        monero_verify_daemons: Vec<String>,
+        /// How many of the verifying daemons have to agree, a majority if not
+        /// given.
+        monero_verify_quorum: Option<usize>,
+        tor_socks5_port: u16,
+        json_progress: bool,
+    },
+    Abort {
+        swap_id: Uuid,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
         tor_socks5_port: u16,
     },
     Cancel {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     Refund {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+    },
+    CancelAndRefund {
+        swap_id: Uuid,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+    },
+    BumpFee {
+        swap_id: Uuid,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+    },
+    ExportPsbt {
+        swap_id: Uuid,
+        tx: ExportableTx,
+    },
+    ExportSwap {
+        swap_id: Uuid,
+        archive: PathBuf,
+        archive_passphrase_file: Option<PathBuf>,
+    },
+    ImportSwap {
+        archive: PathBuf,
+        archive_passphrase_file: Option<PathBuf>,
+    },
+    ImportSled {
+        /// The `database` directory in the data directory if not given.
+        sled_dir: Option<PathBuf>,
+    },
+    VerifyReceipt {
+        swap_id: Uuid,
+    },
+    Audit {
+        swap_id: Uuid,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
+        monero_daemon_address: String,
     },
     ListSellers {
-        rendezvous_point: Multiaddr,
-        namespace: XmrBtcNamespace,
+        rendezvous_points: Vec<Multiaddr>,
+        namespace: RendezvousNamespace,
         tor_socks5_port: u16,
+        watch: bool,
     },
     ExportBitcoinWallet {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
+    ExportSeed,
+    ImportSeed {
+        /// Asked for on the terminal if not given.
+        mnemonic_file: Option<PathBuf>,
+    },
+    StateMachine {
+        role: Role,
+        format: GraphFormat,
+    },
+    SimulateSwap {
+        swap_id: Uuid,
+        lock_height: Option<u32>,
+    },
+    AddressBookAdd {
+        entry: AddressBookEntry,
+    },
+    AddressBookRemove {
+        label: String,
+    },
+    AddressBookList,
+}
+
+/// Where `buy-xmr --select-best` discovers the sellers and the amount of
+/// Bitcoin they have to accept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectBest {
+    pub rendezvous_points: Vec<Multiaddr>,
+    pub namespace: RendezvousNamespace,
+    pub btc_amount: Amount,
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -321,6 +824,15 @@ struct RawArguments {
     )]
     testnet: bool,
 
+    #[structopt(
+        long,
+        hidden = true,
+        global = true,
+        conflicts_with = "testnet",
+        help = "Swap on regtest, for local test environments like swap-testbed. Uses the mainnet defaults for everything but the blockchain related parameters."
+    )]
+    regtest: bool,
+
     #[structopt(
         long = "--data-base-dir",
         help = "The base data directory to be used for mainnet / testnet specific data like database, wallets etc"
@@ -337,6 +849,28 @@ struct RawArguments {
     )]
     json: bool,
 
+    #[structopt(
+        long = "log-format",
+        help = "The format of the logs, either text or json. The json format is the same as `--json`.",
+        default_value = "text",
+        possible_values = &["text", "json"]
+    )]
+    log_format: LogFormat,
+
+    #[structopt(
+        long = "passphrase-file",
+        help = "Read the passphrase that encrypts the seed and the database from the first line of this file, e.g. /dev/fd/3. The passphrase can also be given in the SWAP_PASSPHRASE environment variable.",
+        parse(from_os_str)
+    )]
+    passphrase_file: Option<PathBuf>,
+
+    #[structopt(
+        long = "encrypt",
+        conflicts_with = "passphrase-file",
+        help = "Ask for a passphrase to encrypt the seed and the database with. Once they are encrypted, the passphrase is asked for on every start unless it is given otherwise."
+    )]
+    encrypt: bool,
+
     #[structopt(subcommand)]
     cmd: RawCommand,
 }
@@ -345,51 +879,171 @@ struct RawArguments {
 enum RawCommand {
     /// Start a BTC for XMR swap
     BuyXmr {
-        #[structopt(flatten)]
-        seller: Seller,
+        #[structopt(
+            long = "seller",
+            required_unless = "select-best",
+            conflicts_with = "select-best",
+            number_of_values = 1,
+            help = "The seller's address. Must include a peer ID part, i.e. `/p2p/`. Can be given multiple times to spread the swaps of --split over several sellers."
+        )]
+        sellers: Vec<Multiaddr>,
 
-        #[structopt(flatten)]
-        bitcoin: Bitcoin,
+        #[structopt(
+            long = "select-best",
+            requires_all = &["rendezvous-point", "amount"],
+            help = "Discover the sellers at the rendezvous points and swap with the one that accepts the --amount at the lowest price, preferring the one with the lowest latency among equal prices"
+        )]
+        select_best: bool,
 
         #[structopt(
-            long = "change-address",
-            help = "The bitcoin address where any form of change or excess funds should be sent to"
+            long = "rendezvous-point",
+            requires = "select-best",
+            number_of_values = 1,
+            help = "Address of a rendezvous point to discover the sellers of --select-best at. Can be given multiple times."
         )]
-        bitcoin_change_address: bitcoin::Address,
+        rendezvous_points: Vec<Multiaddr>,
 
-        #[structopt(flatten)]
-        monero: Monero,
+        #[structopt(
+            long = "amount",
+            requires = "select-best",
+            help = "The amount of Bitcoin to swap with the seller of --select-best, e.g. `0.01 BTC`. Less is swapped if the wallet holds less."
+        )]
+        amount: Option<Amount>,
 
-        #[structopt(long = "receive-address",
-            help = "The monero address where you would like to receive monero",
-            parse(try_from_str = parse_monero_address)
+        #[structopt(
+            long = "split",
+            default_value = "1",
+            help = "Split the Bitcoin into this many swaps, which are assigned to the given sellers in turn and run concurrently"
         )]
-        monero_receive_address: monero::Address,
+        split: usize,
 
-        #[structopt(flatten)]
-        tor: Tor,
-    },
-    /// Show a list of past, ongoing and completed swaps
-    History,
-    #[structopt(about = "Prints the current config")]
-    Config,
-    #[structopt(about = "Allows withdrawing BTC from the internal Bitcoin wallet.")]
-    WithdrawBtc {
-        #[structopt(flatten)]
-        bitcoin: Bitcoin,
+        #[structopt(
+            long = "cancel-timelock",
+            help = "The number of blocks after which the swap can be cancelled once the Bitcoin is locked. Defaults to the timelock of the network, other values have to be accepted by the seller."
+        )]
+        cancel_timelock: Option<u32>,
 
         #[structopt(
-            long = "amount",
-            help = "Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet will be drained."
+            long = "punish-timelock",
+            help = "The number of blocks after the swap was cancelled within which the Bitcoin has to be refunded. Defaults to the timelock of the network, other values have to be accepted by the seller."
         )]
-        amount: Option<Amount>,
-        #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
+        punish_timelock: Option<u32>,
+
+        #[structopt(
+            long = "monero-confirmations",
+            number_of_values = 1,
+            help = "Wait for this many confirmations of the Monero lock transaction in swaps of at least this much XMR, given as <from-xmr>:<confirmations>, e.g. 5:20. Can be given multiple times, smaller swaps wait for the confirmations of the network. The seller only accepts confirmations that fit into the cancel timelock."
+        )]
+        monero_confirmations: Vec<ConfirmationTier>,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        bitcoin_confirmation_targets: BitcoinConfirmationTargets,
+
+        #[structopt(
+            long = "bitcoin-wallet-file",
+            help = "Use the Bitcoin wallet given as an xprv or output descriptors in this file instead of the internal wallet. If the descriptors contain no private keys, the lock transaction has to be signed with an external wallet.",
+            parse(from_os_str)
+        )]
+        bitcoin_wallet_file: Option<PathBuf>,
+
+        #[structopt(
+            long = "hardware-wallet",
+            requires = "bitcoin-wallet-file",
+            help = "Sign the Bitcoin transactions with the hardware wallet whose master key has this fingerprint, using HWI. The descriptors in the --bitcoin-wallet-file have to belong to the hardware wallet."
+        )]
+        hardware_wallet: Option<Fingerprint>,
+
+        #[structopt(
+            long = "refund-address",
+            alias = "change-address",
+            help = "The bitcoin address, or the label of one in the address book, where the Bitcoin is refunded to if the swap is cancelled. Defaults to an address of the Bitcoin wallet."
+        )]
+        bitcoin_refund_address: Option<AddressOrLabel<bitcoin::Address>>,
+
+        #[structopt(flatten)]
+        monero: Monero,
+
+        #[structopt(flatten)]
+        monero_lock_verification: MoneroLockVerification,
+
+        #[structopt(
+            long = "receive-address",
+            help = "The monero address, or the label of one in the address book, where you would like to receive monero"
+        )]
+        monero_receive_address: AddressOrLabel<monero::Address>,
+
+        #[structopt(flatten)]
+        tor: Tor,
+
+        #[structopt(
+            long = "json-progress",
+            help = "Stream the progress of the swap as one JSON object per line on stdout instead of showing it on the terminal"
+        )]
+        json_progress: bool,
+    },
+    /// Start an XMR for BTC swap
+    SellXmr {
+        #[structopt(flatten)]
+        seller: Seller,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(
+            long = "receive-address",
+            help = "The bitcoin address where you would like to receive bitcoin"
+        )]
+        bitcoin_receive_address: bitcoin::Address,
+
+        #[structopt(flatten)]
+        monero: Monero,
+
+        #[structopt(long = "refund-address",
+            help = "The monero address the monero is refunded to if the swap is aborted, defaults to the internal Monero wallet",
+            parse(try_from_str = parse_monero_address)
+        )]
+        monero_refund_address: Option<monero::Address>,
+
+        #[structopt(flatten)]
+        tor: Tor,
+    },
+    /// Show a list of past, ongoing and completed swaps
+    History,
+    /// Serve the state transitions of all swaps as server-sent events
+    Monitor {
+        #[structopt(
+            long = "bind-address",
+            help = "The address to serve the event stream on. The stream is unauthenticated, only bind it to a local interface.",
+            default_value = DEFAULT_MONITOR_BIND_ADDRESS
+        )]
+        bind_address: SocketAddr,
+    },
+    #[structopt(about = "Prints the current config")]
+    Config,
+    #[structopt(about = "Allows withdrawing BTC from the internal Bitcoin wallet.")]
+    WithdrawBtc {
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(
+            long = "amount",
+            help = "Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet will be drained."
+        )]
+        amount: Option<Amount>,
+        #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
     },
     #[structopt(about = "Prints the Bitcoin balance.")]
     Balance {
-        #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
-        bitcoin_electrum_rpc_url: Option<Url>,
+        #[structopt(
+            long = "electrum-rpc",
+            number_of_values = 1,
+            help = "Provide the Bitcoin Electrum RPC URL. Can be given multiple times to fail over to the next server if one is unreachable or lags behind."
+        )]
+        bitcoin_electrum_rpc_urls: Vec<Url>,
     },
     /// Resume a swap
     Resume {
@@ -399,9 +1053,47 @@ enum RawCommand {
         #[structopt(flatten)]
         bitcoin: Bitcoin,
 
+        #[structopt(flatten)]
+        bitcoin_confirmation_targets: BitcoinConfirmationTargets,
+
+        #[structopt(
+            long = "bitcoin-wallet-file",
+            help = "Use the Bitcoin wallet given as an xprv or output descriptors in this file instead of the internal wallet. If the descriptors contain no private keys, the lock transaction has to be signed with an external wallet.",
+            parse(from_os_str)
+        )]
+        bitcoin_wallet_file: Option<PathBuf>,
+
+        #[structopt(
+            long = "hardware-wallet",
+            requires = "bitcoin-wallet-file",
+            help = "Sign the Bitcoin transactions with the hardware wallet whose master key has this fingerprint, using HWI. The descriptors in the --bitcoin-wallet-file have to belong to the hardware wallet."
+        )]
+        hardware_wallet: Option<Fingerprint>,
+
         #[structopt(flatten)]
         monero: Monero,
 
+        #[structopt(flatten)]
+        monero_lock_verification: MoneroLockVerification,
+
+        #[structopt(flatten)]
+        tor: Tor,
+
+        #[structopt(
+            long = "json-progress",
+            help = "Stream the progress of the swap as one JSON object per line on stdout instead of showing it on the terminal"
+        )]
+        json_progress: bool,
+    },
+    /// Abort a swap whose Bitcoin has not been locked yet and tell the peer
+    /// about it, instead of waiting for the peer to time out
+    Abort {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
         #[structopt(flatten)]
         tor: Tor,
     },
@@ -423,13 +1115,103 @@ enum RawCommand {
         #[structopt(flatten)]
         bitcoin: Bitcoin,
     },
+    /// Inspect the on-chain state of the cancel and refund transactions and
+    /// publish whatever is missing to get the Bitcoin refunded, bumping the fee
+    /// of a stuck refund transaction if necessary (expert users only)
+    CancelAndRefund {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+    },
+    /// Bump the fee of a Bitcoin lock transaction that is stuck in the mempool
+    /// by spending its change output in a child transaction
+    BumpFee {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+    },
+    /// Print the signed cancel or refund transaction of a swap instead of
+    /// publishing it, to broadcast it through other means
+    ExportPsbt {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(
+            long = "tx",
+            help = "The transaction to export, either `cancel` or `refund`."
+        )]
+        tx: ExportableTx,
+    },
+    /// Write a swap to an encrypted archive, to resume it on another machine
+    Export {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        archive: Archive,
+    },
+    /// Store the swap of an archive written by export in the database, to
+    /// resume it on this machine
+    Import {
+        #[structopt(flatten)]
+        archive: Archive,
+    },
+    /// Store the swaps of the sled database of releases before 0.10.0 in the
+    /// sqlite database, requires a build with the sled-import feature
+    ImportSled {
+        #[structopt(
+            long = "sled-dir",
+            help = "The directory of the sled database, the database directory in the data directory by default",
+            parse(from_os_str)
+        )]
+        sled_dir: Option<PathBuf>,
+    },
+    /// Verify the receipts the seller signed for the quote and the terms of a
+    /// swap, without connecting to the seller
+    VerifyReceipt {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+    },
+    /// Recompute the amounts and fees of a swap from its stored states and
+    /// compare them against the receipts of the seller and both blockchains
+    Audit {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(
+            long = "monero-daemon-address",
+            help = "Specify to connect to a monero daemon of your choice: <host>:<port>"
+        )]
+        monero_daemon_address: Option<String>,
+    },
     /// Discover and list sellers (i.e. ASB providers)
     ListSellers {
         #[structopt(
             long,
-            help = "Address of the rendezvous point you want to use to discover ASBs"
+            required = true,
+            number_of_values = 1,
+            help = "Address of the rendezvous point you want to use to discover ASBs. Can be given multiple times to discover ASBs at several rendezvous points."
+        )]
+        rendezvous_point: Vec<Multiaddr>,
+
+        #[structopt(
+            long,
+            help = "The namespace to discover ASBs in, e.g. xmr-btc-swap-stagenet. Only ASBs that quote the asset pair of the namespace are listed. Defaults to the namespace of the network."
         )]
-        rendezvous_point: Multiaddr,
+        namespace: Option<RendezvousNamespace>,
+
+        #[structopt(
+            long,
+            help = "Keep discovering ASBs and refresh the list of sellers periodically"
+        )]
+        watch: bool,
 
         #[structopt(flatten)]
         tor: Tor,
@@ -439,6 +1221,80 @@ enum RawCommand {
         #[structopt(flatten)]
         bitcoin: Bitcoin,
     },
+    /// Print the seed as a mnemonic of 24 words, to restore the Bitcoin wallet
+    /// and the identity with import-seed after losing the data directory
+    ExportSeed,
+    /// Restore the seed from the mnemonic printed by export-seed into a data
+    /// directory without a seed
+    ImportSeed {
+        #[structopt(
+            long = "mnemonic-file",
+            help = "Read the mnemonic from the first line of this file instead of asking for it, e.g. /dev/stdin",
+            parse(from_os_str)
+        )]
+        mnemonic_file: Option<PathBuf>,
+    },
+    /// Tools to inspect the swap protocol
+    Debug(DebugCommand),
+    /// Manage the addresses that buy-xmr takes by their label
+    AddressBook(AddressBookCommand),
+}
+
+#[derive(structopt::StructOpt, Debug)]
+enum AddressBookCommand {
+    /// Store an address under a label, replacing the address of the same kind
+    /// stored under it
+    Add {
+        #[structopt(long = "label", help = "The label to give the address")]
+        label: String,
+
+        #[structopt(
+            long = "address",
+            help = "A bitcoin address to refund to or a monero address to receive monero at"
+        )]
+        address: BookAddress,
+    },
+    /// Remove the addresses stored under a label
+    Remove {
+        #[structopt(long = "label", help = "The label of the addresses to remove")]
+        label: String,
+    },
+    /// Print the addresses in the address book
+    List,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+enum DebugCommand {
+    /// Print the state machine of Alice or Bob as a graph, or simulate which
+    /// outcomes a stored swap can still reach and when
+    StateMachine {
+        #[structopt(
+            long = "role",
+            default_value = "bob",
+            help = "The role whose state machine to print, either `alice` or `bob`."
+        )]
+        role: Role,
+
+        #[structopt(
+            long = "format",
+            default_value = "dot",
+            help = "The format of the graph, either `dot` or `mermaid`."
+        )]
+        format: GraphFormat,
+
+        #[structopt(
+            long = "swap-id",
+            help = "Simulate the swap with this id forward from its current state instead of printing the graph. The role is taken from the swap."
+        )]
+        swap_id: Option<Uuid>,
+
+        #[structopt(
+            long = "lock-height",
+            requires = "swap-id",
+            help = "The block height at which the Bitcoin lock transaction confirmed, to show absolute instead of relative block heights."
+        )]
+        lock_height: Option<u32>,
+    },
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -448,24 +1304,82 @@ struct Monero {
         help = "Specify to connect to a monero daemon of your choice: <host>:<port>"
     )]
     monero_daemon_address: Option<String>,
+
+    #[structopt(
+        long = "monero-light-wallet",
+        help = "Watch for the Monero lock transaction through the monero daemon instead of running monero-wallet-rpc. The Monero is not transferred to the receive address, instead the keys of the wallet holding it are printed once the swap is complete."
+    )]
+    monero_light_wallet: bool,
+
+    #[structopt(
+        long = "no-auto-update",
+        help = "Do not update monero-wallet-rpc if the installed version is older than the minimum supported version."
+    )]
+    no_auto_update: bool,
 }
 
-impl Monero {
-    fn apply_defaults(self, testnet: bool) -> String {
-        if let Some(address) = self.monero_daemon_address {
-            address
-        } else if testnet {
-            DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string()
-        } else {
-            DEFAULT_MONERO_DAEMON_ADDRESS.to_string()
+#[derive(structopt::StructOpt, Debug)]
+struct MoneroLockVerification {
+    #[structopt(
+        long = "monero-verify-daemon",
+        number_of_values = 1,
+        help = "Cross-check the Monero lock transaction against this monero daemon: <host>:<port>. Can be given multiple times, the lock transaction is only considered confirmed once a quorum of these daemons agrees on its amount, its unlock time and its confirmations."
+    )]
+    daemons: Vec<String>,
+
+    #[structopt(
+        long = "monero-verify-quorum",
+        requires = "monero-verify-daemon",
+        help = "How many of the daemons given with --monero-verify-daemon have to agree on the Monero lock transaction. Defaults to a majority of them."
+    )]
+    quorum: Option<usize>,
+}
+
+impl MoneroLockVerification {
+    fn validate(self) -> Result<(Vec<String>, Option<usize>)> {
+        if let Some(quorum) = self.quorum {
+            if quorum == 0 || quorum > self.daemons.len() {
+                bail!(
+                    "--monero-verify-quorum has to be between 1 and the {} daemons given with --monero-verify-daemon",
+                    self.daemons.len()
+                )
+            }
         }
+
+        Ok((self.daemons, self.quorum))
+    }
+}
+
+impl Monero {
+    fn apply_defaults(self, testnet: bool) -> (String, bool, bool) {
+        let monero_daemon_address = self
+            .monero_daemon_address
+            .unwrap_or_else(|| default_monero_daemon_address(testnet));
+
+        (
+            monero_daemon_address,
+            self.monero_light_wallet,
+            !self.no_auto_update,
+        )
+    }
+}
+
+fn default_monero_daemon_address(testnet: bool) -> String {
+    if testnet {
+        DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string()
+    } else {
+        DEFAULT_MONERO_DAEMON_ADDRESS.to_string()
     }
 }
 
 #[derive(structopt::StructOpt, Debug)]
 struct Bitcoin {
-    #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
-    bitcoin_electrum_rpc_url: Option<Url>,
+    #[structopt(
+        long = "electrum-rpc",
+        number_of_values = 1,
+        help = "Provide the Bitcoin Electrum RPC URL. Can be given multiple times to fail over to the next server if one is unreachable or lags behind."
+    )]
+    bitcoin_electrum_rpc_urls: Vec<Url>,
 
     #[structopt(
         long = "bitcoin-target-block",
@@ -475,13 +1389,13 @@ struct Bitcoin {
 }
 
 impl Bitcoin {
-    fn apply_defaults(self, testnet: bool) -> Result<(Url, usize)> {
-        let bitcoin_electrum_rpc_url = if let Some(url) = self.bitcoin_electrum_rpc_url {
-            url
+    fn apply_defaults(self, testnet: bool) -> Result<(Vec<Url>, usize)> {
+        let bitcoin_electrum_rpc_urls = if !self.bitcoin_electrum_rpc_urls.is_empty() {
+            self.bitcoin_electrum_rpc_urls
         } else if testnet {
-            Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?
+            vec![Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?]
         } else {
-            Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?
+            vec![Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?]
         };
 
         let bitcoin_target_block = if let Some(target_block) = self.bitcoin_target_block {
@@ -492,7 +1406,39 @@ impl Bitcoin {
             DEFAULT_BITCOIN_CONFIRMATION_TARGET
         };
 
-        Ok((bitcoin_electrum_rpc_url, bitcoin_target_block))
+        Ok((bitcoin_electrum_rpc_urls, bitcoin_target_block))
+    }
+}
+
+#[derive(structopt::StructOpt, Debug)]
+struct BitcoinConfirmationTargets {
+    #[structopt(
+        long = "bitcoin-lock-target-block",
+        help = "Overrides --bitcoin-target-block for the Bitcoin lock transaction"
+    )]
+    lock: Option<usize>,
+
+    #[structopt(
+        long = "bitcoin-cancel-target-block",
+        help = "Overrides --bitcoin-target-block for the Bitcoin cancel transaction"
+    )]
+    cancel: Option<usize>,
+
+    #[structopt(
+        long = "bitcoin-refund-target-block",
+        help = "Overrides --bitcoin-target-block for the Bitcoin refund transaction"
+    )]
+    refund: Option<usize>,
+}
+
+impl BitcoinConfirmationTargets {
+    fn apply_defaults(self, target_block: usize) -> ConfirmationTargets {
+        ConfirmationTargets {
+            lock: self.lock.unwrap_or(target_block),
+            cancel: self.cancel.unwrap_or(target_block),
+            refund: self.refund.unwrap_or(target_block),
+            ..ConfirmationTargets::uniform(target_block)
+        }
     }
 }
 
@@ -515,6 +1461,19 @@ struct SwapId {
     swap_id: Uuid,
 }
 
+#[derive(structopt::StructOpt, Debug)]
+struct Archive {
+    #[structopt(long = "archive", help = "The file of the archive", parse(from_os_str))]
+    path: PathBuf,
+
+    #[structopt(
+        long = "archive-passphrase-file",
+        help = "Read the passphrase that encrypts the archive from the first line of this file instead of asking for it",
+        parse(from_os_str)
+    )]
+    passphrase_file: Option<PathBuf>,
+}
+
 #[derive(structopt::StructOpt, Debug)]
 struct Seller {
     #[structopt(
@@ -543,11 +1502,11 @@ mod data {
     }
 }
 
-fn rendezvous_namespace_from(is_testnet: bool) -> XmrBtcNamespace {
+fn rendezvous_namespace_from(is_testnet: bool) -> RendezvousNamespace {
     if is_testnet {
-        XmrBtcNamespace::Testnet
+        RendezvousNamespace::testnet()
     } else {
-        XmrBtcNamespace::Mainnet
+        RendezvousNamespace::mainnet()
     }
 }
 
@@ -659,168 +1618,1304 @@ mod tests {
     const BITCOIN_MAINNET_ADDRESS: &str = "bc1qe4epnfklcaa0mun26yz5g8k24em5u9f92hy325";
     const MULTI_ADDRESS: &str =
         "/ip4/127.0.0.1/tcp/9939/p2p/12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
+    const OTHER_MULTI_ADDRESS: &str =
+        "/dns4/rendezvous.example.com/tcp/8888/p2p/12D3KooWQUt9DkNZxEn2R5ymJzWj15MpG6mTW84kyd8vDaRZi46o";
     const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
 
     #[test]
-    fn given_buy_xmr_on_mainnet_then_defaults_to_mainnet() {
+    fn given_buy_xmr_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let expected_args = ParseResult::Arguments(Arguments::buy_xmr_mainnet_defaults());
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_regtest_then_regtest_env_config_is_used() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--regtest",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let env_config = match parse_args_and_apply_defaults(raw_ars).unwrap() {
+            ParseResult::Arguments(Arguments { env_config, .. }) => env_config,
+            result => panic!("unexpected parse result {:?}", result),
+        };
+        assert_eq!(env_config, env::Regtest::get_config());
+
+        let raw_ars = vec![BINARY_NAME, "--regtest", "--testnet", "history"];
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_buy_xmr_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--change-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::buy_xmr_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_lock_target_block_then_only_lock_target_is_overridden() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--bitcoin-target-block",
+            "6",
+            "--bitcoin-lock-target-block",
+            "1",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let confirmation_targets = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::BuyXmr {
+                        bitcoin_confirmation_targets,
+                        ..
+                    },
+                ..
+            }) => bitcoin_confirmation_targets,
+            _ => panic!("expected buy-xmr arguments"),
+        };
+        assert_eq!(confirmation_targets, ConfirmationTargets {
+            lock: 1,
+            ..ConfirmationTargets::uniform(6)
+        });
+    }
+
+    #[test]
+    fn given_bitcoin_wallet_file_then_it_is_passed_on_when_resuming() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--bitcoin-wallet-file",
+            "/path/to/wallet",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let bitcoin_wallet_file = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::Resume {
+                        bitcoin_wallet_file,
+                        ..
+                    },
+                ..
+            }) => bitcoin_wallet_file,
+            _ => panic!("expected resume arguments"),
+        };
+        assert_eq!(bitcoin_wallet_file, Some(PathBuf::from("/path/to/wallet")));
+    }
+
+    #[test]
+    fn given_json_progress_then_it_is_passed_on_when_resuming() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--json-progress",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let json_progress = match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::Resume { json_progress, .. },
+                ..
+            }) => json_progress,
+            _ => panic!("expected resume arguments"),
+        };
+        assert!(json_progress);
+    }
+
+    #[test]
+    fn given_hardware_wallet_then_fingerprint_is_passed_on_when_resuming() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--bitcoin-wallet-file",
+            "/path/to/wallet",
+            "--hardware-wallet",
+            "d34db33f",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let hardware_wallet = match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::Resume {
+                    hardware_wallet, ..
+                },
+                ..
+            }) => hardware_wallet,
+            _ => panic!("expected resume arguments"),
+        };
+        assert_eq!(hardware_wallet, Some("d34db33f".parse().unwrap()));
+    }
+
+    #[test]
+    fn given_hardware_wallet_without_bitcoin_wallet_file_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--hardware-wallet",
+            "d34db33f",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_monero_light_wallet_then_it_is_passed_on_when_resuming() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--monero-light-wallet",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let monero_light_wallet = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::Resume {
+                        monero_light_wallet,
+                        monero_auto_update,
+                        ..
+                    },
+                ..
+            }) => monero_light_wallet,
+            _ => panic!("expected resume arguments"),
+        };
+        assert!(monero_light_wallet);
+    }
+
+    #[test]
+    fn given_monero_verify_daemons_then_quorum_is_passed_on_when_resuming() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--monero-verify-daemon",
+            "node1.example.com:18081",
+            "--monero-verify-daemon",
+            "node2.example.com:18081",
+            "--monero-verify-quorum",
+            "2",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let (monero_verify_daemons, monero_verify_quorum) = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::Resume {
+                        monero_verify_daemons,
+                        monero_verify_quorum,
+                        ..
+                    },
+                ..
+            }) => (monero_verify_daemons, monero_verify_quorum),
+            _ => panic!("expected resume arguments"),
+        };
+        assert_eq!(monero_verify_daemons, vec![
+            "node1.example.com:18081".to_string(),
+            "node2.example.com:18081".to_string()
+        ]);
+        assert_eq!(monero_verify_quorum, Some(2));
+    }
+
+    #[test]
+    fn given_monero_verify_quorum_above_daemons_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--monero-verify-daemon",
+            "node1.example.com:18081",
+            "--monero-verify-quorum",
+            "2",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_no_auto_update_then_monero_wallet_rpc_is_not_updated() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--no-auto-update",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let monero_auto_update = match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::BuyXmr {
+                    monero_auto_update, ..
+                },
+                ..
+            }) => monero_auto_update,
+            _ => panic!("expected buy-xmr arguments"),
+        };
+        assert!(!monero_auto_update);
+    }
+
+    #[test]
+    fn given_split_and_multiple_sellers_then_all_sellers_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--split",
+            "3",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let (sellers, split) = match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::BuyXmr { sellers, split, .. },
+                ..
+            }) => (sellers, split),
+            _ => panic!("expected buy-xmr arguments"),
+        };
+        assert_eq!(sellers.len(), 2);
+        assert_eq!(split, 3);
+    }
+
+    #[test]
+    fn given_timelocks_then_they_are_passed_on() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--cancel-timelock",
+            "144",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let (cancel_timelock, punish_timelock) = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::BuyXmr {
+                        cancel_timelock,
+                        punish_timelock,
+                        ..
+                    },
+                ..
+            }) => (cancel_timelock, punish_timelock),
+            _ => panic!("expected buy-xmr arguments"),
+        };
+        assert_eq!(cancel_timelock, Some(144));
+        assert_eq!(punish_timelock, None);
+    }
+
+    #[test]
+    fn given_select_best_then_sellers_are_discovered_at_the_rendezvous_points() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--select-best",
+            "--rendezvous-point",
+            MULTI_ADDRESS,
+            "--amount",
+            "0.01 BTC",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let (sellers, select_best) = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::BuyXmr {
+                        sellers,
+                        select_best,
+                        ..
+                    },
+                ..
+            }) => (sellers, select_best),
+            _ => panic!("expected buy-xmr arguments"),
+        };
+        assert!(sellers.is_empty());
+        assert_eq!(
+            select_best,
+            Some(SelectBest {
+                rendezvous_points: vec![Multiaddr::from_str(MULTI_ADDRESS).unwrap()],
+                namespace: RendezvousNamespace::testnet(),
+                btc_amount: Amount::from_btc(0.01).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn given_select_best_without_amount_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--select-best",
+            "--rendezvous-point",
+            MULTI_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_select_best_and_seller_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--select-best",
+            "--rendezvous-point",
+            MULTI_ADDRESS,
+            "--amount",
+            "0.01 BTC",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_monero_confirmations_then_smaller_swaps_wait_for_the_network() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--monero-confirmations",
+            "5:20",
+            "--monero-confirmations",
+            "0.1:15",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let schedule = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::BuyXmr {
+                        confirmation_schedule: Some(schedule),
+                        ..
+                    },
+                ..
+            }) => schedule,
+            _ => panic!("expected buy-xmr arguments with a confirmation schedule"),
+        };
+        let xmr = |xmr: f64| monero::Amount::from_monero(xmr).unwrap();
+        assert_eq!(schedule.confirmations(xmr(0.01)), 10);
+        assert_eq!(schedule.confirmations(xmr(1.0)), 15);
+        assert_eq!(schedule.confirmations(xmr(5.0)), 20);
+    }
+
+    #[test]
+    fn given_more_sellers_than_swaps_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_split_of_zero_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--split",
+            "0",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_buy_xmr_on_mainnet_with_testnet_address_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--change-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
+            &MoneroAddressNetworkMismatch {
+                expected: monero::Network::Mainnet,
+                actual: monero::Network::Stagenet
+            }
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_on_testnet_with_mainnet_address_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
+            &MoneroAddressNetworkMismatch {
+                expected: monero::Network::Stagenet,
+                actual: monero::Network::Mainnet
+            }
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_labels_then_they_are_passed_on() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            "savings",
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let (bitcoin_refund_address, monero_receive_address) = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::BuyXmr {
+                        bitcoin_refund_address,
+                        monero_receive_address,
+                        ..
+                    },
+                ..
+            }) => (bitcoin_refund_address, monero_receive_address),
+            _ => panic!("expected buy-xmr arguments"),
+        };
+        assert_eq!(bitcoin_refund_address, None);
+        assert_eq!(
+            monero_receive_address,
+            AddressOrLabel::Label("savings".to_string())
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_refund_address_of_other_network_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--refund-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_address_book_add_then_kind_of_address_is_detected() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "address-book",
+            "add",
+            "--label",
+            "savings",
+            "--address",
+            MONERO_STAGENET_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::AddressBookAdd {
+                    entry: AddressBookEntry {
+                        label: "savings".to_string(),
+                        address: BookAddress::Monero(
+                            monero::Address::from_str(MONERO_STAGENET_ADDRESS).unwrap()
+                        ),
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_address_book_add_with_address_of_other_network_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "address-book",
+            "add",
+            "--label",
+            "savings",
+            "--address",
+            BITCOIN_TESTNET_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_sell_xmr_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "sell-xmr",
+            "--receive-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let expected_args = ParseResult::Arguments(Arguments::sell_xmr_mainnet_defaults());
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_sell_xmr_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "sell-xmr",
+            "--receive-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let expected_args = ParseResult::Arguments(Arguments::sell_xmr_testnet_defaults());
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_sell_xmr_with_refund_address_then_refund_address_is_used() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "sell-xmr",
+            "--receive-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--refund-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(
+                Arguments::sell_xmr_mainnet_defaults()
+                    .with_monero_refund_address(MONERO_MAINNET_ADDRESS)
+            )
+        );
+    }
+
+    #[test]
+    fn given_sell_xmr_with_light_wallet_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "sell-xmr",
+            "--receive-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--monero-light-wallet",
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_resume_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "resume", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_resume_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "resume", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_abort_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "abort", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::abort_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_abort_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "abort", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::abort_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_cancel_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::cancel_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_cancel_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "cancel", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::cancel_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_refund_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "refund", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::refund_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_refund_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "refund", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::refund_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_bump_fee_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "bump-fee", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::bump_fee_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_bump_fee_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "bump-fee", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::bump_fee_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_multiple_electrum_servers_then_keeps_them_in_order() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "balance",
+            "--electrum-rpc",
+            "tcp://localhost:50001",
+            "--electrum-rpc",
+            "tcp://localhost:50002",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let bitcoin_electrum_rpc_urls = match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::Balance {
+                        bitcoin_electrum_rpc_urls,
+                        ..
+                    },
+                ..
+            }) => bitcoin_electrum_rpc_urls,
+            _ => panic!("expected balance arguments"),
+        };
+        assert_eq!(bitcoin_electrum_rpc_urls, vec![
+            Url::from_str("tcp://localhost:50001").unwrap(),
+            Url::from_str("tcp://localhost:50002").unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn given_export_psbt_then_parses_transaction() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "export-psbt",
+            "--swap-id",
+            SWAP_ID,
+            "--tx",
+            "refund",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::ExportPsbt {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    tx: ExportableTx::Refund,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_export_psbt_of_redeem_transaction_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "export-psbt",
+            "--swap-id",
+            SWAP_ID,
+            "--tx",
+            "redeem",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_export_then_archive_and_passphrase_file_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "export",
+            "--swap-id",
+            SWAP_ID,
+            "--archive",
+            "/tmp/swap.archive",
+            "--archive-passphrase-file",
+            "/dev/fd/3",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::ExportSwap {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    archive: PathBuf::from("/tmp/swap.archive"),
+                    archive_passphrase_file: Some(PathBuf::from("/dev/fd/3")),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_import_sled_then_sled_dir_defaults_to_data_dir() {
+        let raw_ars = vec![BINARY_NAME, "import-sled"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::ImportSled { sled_dir: None },
+            })
+        );
+    }
+
+    #[test]
+    fn given_import_seed_with_mnemonic_file_then_mnemonic_file_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "import-seed",
+            "--mnemonic-file",
+            "/dev/stdin",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::ImportSeed {
+                    mnemonic_file: Some(PathBuf::from("/dev/stdin")),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_verify_receipt_then_swap_id_set() {
+        let raw_ars = vec![BINARY_NAME, "verify-receipt", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::VerifyReceipt {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_audit_on_testnet_then_defaults_applied() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "audit", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::Audit {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET
+                    )
+                    .unwrap()],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_debug_state_machine_without_swap_id_then_graph_of_role_printed() {
         let raw_ars = vec![
             BINARY_NAME,
-            "buy-xmr",
-            "--receive-address",
-            MONERO_MAINNET_ADDRESS,
-            "--change-address",
-            BITCOIN_MAINNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
+            "debug",
+            "state-machine",
+            "--role",
+            "alice",
+            "--format",
+            "mermaid",
         ];
 
-        let expected_args = ParseResult::Arguments(Arguments::buy_xmr_mainnet_defaults());
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
-        assert_eq!(expected_args, args);
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::StateMachine {
+                    role: Role::Alice,
+                    format: GraphFormat::Mermaid,
+                },
+            })
+        );
     }
 
     #[test]
-    fn given_buy_xmr_on_testnet_then_defaults_to_testnet() {
+    fn given_debug_state_machine_with_swap_id_then_swap_simulated() {
         let raw_ars = vec![
             BINARY_NAME,
-            "--testnet",
-            "buy-xmr",
-            "--receive-address",
-            MONERO_STAGENET_ADDRESS,
-            "--change-address",
-            BITCOIN_TESTNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
+            "debug",
+            "state-machine",
+            "--swap-id",
+            SWAP_ID,
+            "--lock-height",
+            "700000",
         ];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::buy_xmr_testnet_defaults())
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::SimulateSwap {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    lock_height: Some(700_000),
+                },
+            })
         );
     }
 
     #[test]
-    fn given_buy_xmr_on_mainnet_with_testnet_address_then_fails() {
+    fn given_debug_state_machine_with_lock_height_but_no_swap_id_then_fails() {
         let raw_ars = vec![
             BINARY_NAME,
-            "buy-xmr",
-            "--receive-address",
-            MONERO_STAGENET_ADDRESS,
-            "--change-address",
-            BITCOIN_TESTNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
+            "debug",
+            "state-machine",
+            "--lock-height",
+            "700000",
         ];
 
-        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_cancel_and_refund_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "cancel-and-refund", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
-            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
-            &MoneroAddressNetworkMismatch {
-                expected: monero::Network::Mainnet,
-                actual: monero::Network::Stagenet
-            }
+            args,
+            ParseResult::Arguments(Arguments::cancel_and_refund_mainnet_defaults())
         );
     }
 
     #[test]
-    fn given_buy_xmr_on_testnet_with_mainnet_address_then_fails() {
+    fn given_cancel_and_refund_on_testnet_then_defaults_to_testnet() {
         let raw_ars = vec![
             BINARY_NAME,
             "--testnet",
-            "buy-xmr",
-            "--receive-address",
-            MONERO_MAINNET_ADDRESS,
-            "--change-address",
-            BITCOIN_MAINNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
+            "cancel-and-refund",
+            "--swap-id",
+            SWAP_ID,
         ];
 
-        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
-            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
-            &MoneroAddressNetworkMismatch {
-                expected: monero::Network::Stagenet,
-                actual: monero::Network::Mainnet
-            }
+            args,
+            ParseResult::Arguments(Arguments::cancel_and_refund_testnet_defaults())
         );
     }
 
     #[test]
-    fn given_resume_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "resume", "--swap-id", SWAP_ID];
+    fn given_passphrase_file_then_passphrase_read_from_file() {
+        let raw_ars = vec![BINARY_NAME, "--passphrase-file", "/dev/fd/3", "history"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::resume_mainnet_defaults())
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: Some(PassphraseSource::File(PathBuf::from("/dev/fd/3"))),
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::History,
+            })
         );
     }
 
     #[test]
-    fn given_resume_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "resume", "--swap-id", SWAP_ID];
+    fn given_encrypt_then_passphrase_prompted() {
+        let raw_ars = vec![BINARY_NAME, "--encrypt", "history"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::resume_testnet_defaults())
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: Some(PassphraseSource::Prompt),
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::History,
+            })
         );
     }
 
     #[test]
-    fn given_cancel_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID];
+    fn given_encrypt_and_passphrase_file_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--encrypt",
+            "--passphrase-file",
+            "/dev/fd/3",
+            "history",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_monitor_with_bind_address_then_bind_address_set() {
+        let raw_ars = vec![BINARY_NAME, "monitor", "--bind-address", "127.0.0.1:8080"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::cancel_mainnet_defaults())
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Monitor {
+                    bind_address: "127.0.0.1:8080".parse().unwrap()
+                },
+            })
         );
     }
 
     #[test]
-    fn given_cancel_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "cancel", "--swap-id", SWAP_ID];
+    fn given_list_sellers_with_multiple_rendezvous_points_and_watch_then_all_points_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "list-sellers",
+            "--rendezvous-point",
+            MULTI_ADDRESS,
+            "--rendezvous-point",
+            OTHER_MULTI_ADDRESS,
+            "--watch",
+        ];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::cancel_testnet_defaults())
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::ListSellers {
+                    rendezvous_points: vec![
+                        MULTI_ADDRESS.parse().unwrap(),
+                        OTHER_MULTI_ADDRESS.parse().unwrap()
+                    ],
+                    namespace: RendezvousNamespace::mainnet(),
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    watch: true,
+                },
+            })
         );
     }
 
     #[test]
-    fn given_refund_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "refund", "--swap-id", SWAP_ID];
+    fn given_list_sellers_on_testnet_with_namespace_then_namespace_overrides_default() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "list-sellers",
+            "--rendezvous-point",
+            MULTI_ADDRESS,
+            "--namespace",
+            "xmr-btc-swap-stagenet",
+        ];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::refund_mainnet_defaults())
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::ListSellers {
+                    rendezvous_points: vec![MULTI_ADDRESS.parse().unwrap()],
+                    namespace: "xmr-btc-swap-stagenet".parse().unwrap(),
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    watch: false,
+                },
+            })
         );
     }
 
     #[test]
-    fn given_refund_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "refund", "--swap-id", SWAP_ID];
+    fn given_list_sellers_without_rendezvous_point_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "list-sellers"];
 
-        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        let result = parse_args_and_apply_defaults(raw_ars);
 
-        assert_eq!(
-            args,
-            ParseResult::Arguments(Arguments::refund_testnet_defaults())
-        );
+        assert!(result.is_err());
     }
 
     #[test]
@@ -1038,6 +3133,21 @@ mod tests {
             args,
             ParseResult::Arguments(Arguments::resume_testnet_defaults().with_json())
         );
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--log-format",
+            "json",
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_mainnet_defaults().with_json())
+        );
     }
 
     #[test]
@@ -1145,17 +3255,38 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::BuyXmr {
-                    seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    sellers: vec![Multiaddr::from_str(MULTI_ADDRESS).unwrap()],
+                    select_best: None,
+                    split: 1,
+                    cancel_timelock: None,
+                    punish_timelock: None,
+                    confirmation_schedule: None,
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
-                    bitcoin_change_address: BITCOIN_TESTNET_ADDRESS.parse().unwrap(),
-                    monero_receive_address: monero::Address::from_str(MONERO_STAGENET_ADDRESS)
-                        .unwrap(),
+                    bitcoin_confirmation_targets: ConfirmationTargets::uniform(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    ),
+                    bitcoin_wallet_file: None,
+                    hardware_wallet: None,
+                    bitcoin_refund_address: Some(AddressOrLabel::Address(
+                        BITCOIN_TESTNET_ADDRESS.parse().unwrap(),
+                    )),
+                    monero_receive_address: AddressOrLabel::Address(
+                        monero::Address::from_str(MONERO_STAGENET_ADDRESS).unwrap(),
+                    ),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    monero_light_wallet: false,
+                    monero_auto_update: true,
+                    monero_verify_daemons: vec![],
+                    monero_verify_quorum: None,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    json_progress: false,
                 },
             }
         }
@@ -1165,15 +3296,81 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::BuyXmr {
+                    sellers: vec![Multiaddr::from_str(MULTI_ADDRESS).unwrap()],
+                    select_best: None,
+                    split: 1,
+                    cancel_timelock: None,
+                    punish_timelock: None,
+                    confirmation_schedule: None,
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_confirmation_targets: ConfirmationTargets::uniform(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    ),
+                    bitcoin_wallet_file: None,
+                    hardware_wallet: None,
+                    bitcoin_refund_address: Some(AddressOrLabel::Address(
+                        BITCOIN_MAINNET_ADDRESS.parse().unwrap(),
+                    )),
+                    monero_receive_address: AddressOrLabel::Address(
+                        monero::Address::from_str(MONERO_MAINNET_ADDRESS).unwrap(),
+                    ),
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    monero_light_wallet: false,
+                    monero_auto_update: true,
+                    monero_verify_daemons: vec![],
+                    monero_verify_quorum: None,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    json_progress: false,
+                },
+            }
+        }
+
+        pub fn sell_xmr_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::SellXmr {
+                    seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_receive_address: BITCOIN_TESTNET_ADDRESS.parse().unwrap(),
+                    monero_refund_address: None,
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    monero_auto_update: true,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                },
+            }
+        }
+
+        pub fn sell_xmr_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::SellXmr {
                     seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
-                    bitcoin_change_address: BITCOIN_MAINNET_ADDRESS.parse().unwrap(),
-                    monero_receive_address: monero::Address::from_str(MONERO_MAINNET_ADDRESS)
-                        .unwrap(),
+                    bitcoin_receive_address: BITCOIN_MAINNET_ADDRESS.parse().unwrap(),
+                    monero_refund_address: None,
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    monero_auto_update: true,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
                 },
             }
@@ -1184,14 +3381,27 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_confirmation_targets: ConfirmationTargets::uniform(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    ),
+                    bitcoin_wallet_file: None,
+                    hardware_wallet: None,
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    monero_light_wallet: false,
+                    monero_auto_update: true,
+                    monero_verify_daemons: vec![],
+                    monero_verify_quorum: None,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    json_progress: false,
                 },
             }
         }
@@ -1201,12 +3411,62 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_confirmation_targets: ConfirmationTargets::uniform(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    ),
+                    bitcoin_wallet_file: None,
+                    hardware_wallet: None,
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    monero_light_wallet: false,
+                    monero_auto_update: true,
+                    monero_verify_daemons: vec![],
+                    monero_verify_quorum: None,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    json_progress: false,
+                },
+            }
+        }
+
+        pub fn abort_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::Abort {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                },
+            }
+        }
+
+        pub fn abort_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Abort {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
                 },
             }
@@ -1217,11 +3477,14 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
                 },
             }
@@ -1232,10 +3495,13 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                 },
             }
@@ -1246,11 +3512,14 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
                 },
             }
@@ -1261,10 +3530,83 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                passphrase: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                },
+            }
+        }
+
+        pub fn cancel_and_refund_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::CancelAndRefund {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                },
+            }
+        }
+
+        pub fn cancel_and_refund_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::CancelAndRefund {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                },
+            }
+        }
+
+        pub fn bump_fee_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::BumpFee {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                },
+            }
+        }
+
+        pub fn bump_fee_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                passphrase: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::BumpFee {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                 },
             }
@@ -1284,6 +3626,17 @@ mod tests {
             self.json = true;
             self
         }
+
+        pub fn with_monero_refund_address(mut self, address: &str) -> Self {
+            if let Command::SellXmr {
+                monero_refund_address,
+                ..
+            } = &mut self.cmd
+            {
+                *monero_refund_address = Some(monero::Address::from_str(address).unwrap());
+            }
+            self
+        }
     }
 
     fn data_dir_path_cli() -> PathBuf {
@@ -1,10 +1,13 @@
 use crate::bitcoin::EncryptedSignature;
 use crate::cli::behaviour::{Behaviour, OutEvent};
-use crate::network::encrypted_signature;
+use crate::network::connection_manager::UNREACHABLE_AFTER;
 use crate::network::quote::BidQuote;
 use crate::network::swap_setup::bob::NewSwap;
+use crate::network::swap_setup::sell_xmr;
+use crate::network::{abort, encrypted_signature, transfer_proof};
+use crate::protocol::alice::State3;
 use crate::protocol::bob::State2;
-use crate::{env, monero};
+use crate::{asb, env, monero};
 use anyhow::{Context, Result};
 use futures::future::{BoxFuture, OptionFuture};
 use futures::{FutureExt, StreamExt};
@@ -15,6 +18,13 @@ use std::collections::HashMap;
 use std::time::Duration;
 use uuid::Uuid;
 
+/// The connection to Alice was lost and could not be re-established.
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Alice ({peer}) could not be reached for {} minutes, resume the swap once she is back online", UNREACHABLE_AFTER.as_secs() / 60)]
+pub struct PeerUnreachable {
+    pub peer: PeerId,
+}
+
 #[allow(missing_debug_implementations)]
 pub struct EventLoop {
     swap_id: Uuid,
@@ -25,6 +35,9 @@ pub struct EventLoop {
     quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
     encrypted_signatures: bmrng::RequestReceiverStream<EncryptedSignature, ()>,
     swap_setup_requests: bmrng::RequestReceiverStream<NewSwap, Result<State2>>,
+    sell_xmr_setup_requests: bmrng::RequestReceiverStream<sell_xmr::alice::NewSwap, Result<State3>>,
+    transfer_proofs: bmrng::RequestReceiverStream<monero::TransferProof, ()>,
+    aborts: bmrng::RequestReceiverStream<(), ()>,
 
     // these represents requests that are currently in-flight.
     // once we get a response to a matching [`RequestId`], we will use the responder to relay the
@@ -32,6 +45,9 @@ pub struct EventLoop {
     inflight_quote_requests: HashMap<RequestId, bmrng::Responder<BidQuote>>,
     inflight_encrypted_signature_requests: HashMap<RequestId, bmrng::Responder<()>>,
     inflight_swap_setup: Option<bmrng::Responder<Result<State2>>>,
+    inflight_sell_xmr_setup: Option<bmrng::Responder<Result<State3>>>,
+    inflight_transfer_proof_requests: HashMap<RequestId, bmrng::Responder<()>>,
+    inflight_abort_requests: HashMap<RequestId, bmrng::Responder<()>>,
 
     /// The sender we will use to relay incoming transfer proofs.
     transfer_proof: bmrng::RequestSender<monero::TransferProof, ()>,
@@ -43,6 +59,19 @@ pub struct EventLoop {
     /// resolves, we use the `ResponseChannel` returned from it to send an ACK
     /// to Alice that we have successfully processed the transfer proof.
     pending_transfer_proof: OptionFuture<BoxFuture<'static, ResponseChannel<()>>>,
+
+    /// The sender we will use to relay incoming encrypted signatures, in swaps
+    /// in which we sell XMR.
+    encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
+    /// The future representing the successful handling of an incoming
+    /// encrypted signature, like [`EventLoop::pending_transfer_proof`].
+    pending_encrypted_signature: OptionFuture<BoxFuture<'static, ResponseChannel<()>>>,
+
+    /// The sender we will use to relay an abort by Alice.
+    abort: bmrng::RequestSender<(), ()>,
+    /// The future representing the successful handling of an incoming abort,
+    /// like [`EventLoop::pending_transfer_proof`].
+    pending_abort: OptionFuture<BoxFuture<'static, ResponseChannel<()>>>,
 }
 
 impl EventLoop {
@@ -53,28 +82,52 @@ impl EventLoop {
         env_config: env::Config,
     ) -> Result<(Self, EventLoopHandle)> {
         let execution_setup = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let sell_xmr_setup = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let transfer_proof = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let encrypted_signature = bmrng::channel(1);
         let quote = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        // like in the ASB there are no timeouts on these channels because the swap
+        // races them against a timelock
+        let outgoing_transfer_proof = bmrng::channel(1);
+        let incoming_encrypted_signature = bmrng::channel(1);
+        // the swap only listens for an abort until the Bitcoin is locked
+        let incoming_abort = bmrng::channel(1);
+        let outgoing_abort = bmrng::channel_with_timeout(1, Duration::from_secs(60));
 
         let event_loop = EventLoop {
             swap_id,
             swarm,
             alice_peer_id,
             swap_setup_requests: execution_setup.1.into(),
+            sell_xmr_setup_requests: sell_xmr_setup.1.into(),
+            transfer_proofs: outgoing_transfer_proof.1.into(),
+            aborts: outgoing_abort.1.into(),
             transfer_proof: transfer_proof.0,
             encrypted_signatures: encrypted_signature.1.into(),
             quote_requests: quote.1.into(),
             inflight_quote_requests: HashMap::default(),
             inflight_swap_setup: None,
+            inflight_sell_xmr_setup: None,
             inflight_encrypted_signature_requests: HashMap::default(),
+            inflight_transfer_proof_requests: HashMap::default(),
+            inflight_abort_requests: HashMap::default(),
             pending_transfer_proof: OptionFuture::from(None),
+            encrypted_signature: incoming_encrypted_signature.0,
+            pending_encrypted_signature: OptionFuture::from(None),
+            abort: incoming_abort.0,
+            pending_abort: OptionFuture::from(None),
         };
 
         let handle = EventLoopHandle {
+            peer: alice_peer_id,
             swap_setup: execution_setup.0,
+            sell_xmr_setup: sell_xmr_setup.0,
             transfer_proof: transfer_proof.1,
             encrypted_signature: encrypted_signature.0,
+            send_transfer_proof: outgoing_transfer_proof.0,
+            recv_encrypted_signature: incoming_encrypted_signature.1,
+            recv_abort: incoming_abort.1,
+            send_abort: outgoing_abort.0,
             quote: quote.0,
             env_config,
         };
@@ -82,12 +135,13 @@ impl EventLoop {
         Ok((event_loop, handle))
     }
 
-    pub async fn run(mut self) {
+    #[tracing::instrument(name = "event_loop", skip(self), fields(swap_id = %self.swap_id, peer_id = %self.alice_peer_id))]
+    pub async fn run(mut self) -> Result<(), PeerUnreachable> {
         match self.swarm.dial(&self.alice_peer_id) {
             Ok(()) => {}
             Err(e) => {
                 tracing::error!("Failed to initiate dial to Alice: {}", e);
-                return;
+                return Ok(());
             }
         }
 
@@ -106,6 +160,11 @@ impl EventLoop {
                                 let _ = responder.respond(*response);
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::SellXmrSetupCompleted(response)) => {
+                            if let Some(responder) = self.inflight_sell_xmr_setup.take() {
+                                let _ = responder.respond(*response);
+                            }
+                        }
                         SwarmEvent::Behaviour(OutEvent::TransferProofReceived { msg, channel, peer }) => {
                             let swap_id = msg.swap_id;
 
@@ -147,13 +206,85 @@ impl EventLoop {
                                 let _ = responder.respond(());
                             }
                         }
-                        SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer }) if peer == self.alice_peer_id => {
+                        SwarmEvent::Behaviour(OutEvent::TransferProofAcknowledged { id }) => {
+                            if let Some(responder) = self.inflight_transfer_proof_requests.remove(&id) {
+                                let _ = responder.respond(());
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::EncryptedSignatureReceived { msg, channel, peer }) => {
+                            let swap_id = msg.swap_id;
+
+                            if peer != self.alice_peer_id {
+                                tracing::warn!(
+                                    %swap_id,
+                                    "Ignoring malicious encrypted signature from {}, expected to receive it from {}",
+                                    peer,
+                                    self.alice_peer_id);
+                                continue;
+                            }
+
+                            if swap_id != self.swap_id {
+                                tracing::warn!("Received unexpected encrypted signature for swap {} while running swap {}. This encrypted signature will be ignored", swap_id, self.swap_id);
+                                continue;
+                            }
+
+                            let mut responder = match self.encrypted_signature.send(msg.tx_redeem_encsig).await {
+                                Ok(responder) => responder,
+                                Err(e) => {
+                                    tracing::warn!("Failed to pass on encrypted signature: {:#}", e);
+                                    continue;
+                                }
+                            };
+
+                            self.pending_encrypted_signature = OptionFuture::from(Some(async move {
+                                let _ = responder.recv().await;
+
+                                channel
+                            }.boxed()));
+                        }
+                        SwarmEvent::Behaviour(OutEvent::AbortReceived { msg, channel, peer }) => {
+                            let swap_id = msg.swap_id;
+
+                            if peer != self.alice_peer_id {
+                                tracing::warn!(
+                                    %swap_id,
+                                    "Ignoring malicious abort from {}, expected to receive it from {}",
+                                    peer,
+                                    self.alice_peer_id);
+                                continue;
+                            }
+
+                            if swap_id != self.swap_id {
+                                tracing::warn!("Received unexpected abort for swap {} while running swap {}. This abort will be ignored", swap_id, self.swap_id);
+                                continue;
+                            }
+
+                            let mut responder = match self.abort.send(()).await {
+                                Ok(responder) => responder,
+                                Err(e) => {
+                                    tracing::warn!("Failed to pass on abort: {:#}", e);
+                                    continue;
+                                }
+                            };
+
+                            self.pending_abort = OptionFuture::from(Some(async move {
+                                let _ = responder.recv().await;
+
+                                channel
+                            }.boxed()));
+                        }
+                        SwarmEvent::Behaviour(OutEvent::AbortAcknowledged { id }) => {
+                            if let Some(responder) = self.inflight_abort_requests.remove(&id) {
+                                let _ = responder.respond(());
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::PeerUnreachable { peer }) if peer == self.alice_peer_id => {
                             tracing::error!("Exhausted all re-dial attempts to Alice");
-                            return;
+                            return Err(PeerUnreachable { peer });
                         }
                         SwarmEvent::Behaviour(OutEvent::Failure { peer, error }) => {
-                            tracing::warn!(%peer, "Communication error: {:#}", error);
-                            return;
+                            tracing::warn!(peer_id = %peer, "Communication error: {:#}", error);
+                            return Ok(());
                         }
                         SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } if peer_id == self.alice_peer_id => {
                             tracing::info!("Connected to Alice at {}", endpoint.get_remote_address());
@@ -167,12 +298,12 @@ impl EventLoop {
                         SwarmEvent::ConnectionClosed { peer_id, num_established, cause: None, .. } if peer_id == self.alice_peer_id && num_established == 0 => {
                             // no error means the disconnection was requested
                             tracing::info!("Successfully closed connection to Alice");
-                            return;
+                            return Ok(());
                         }
                         SwarmEvent::UnreachableAddr { peer_id, address, attempts_remaining, error } if peer_id == self.alice_peer_id && attempts_remaining == 0 => {
                             tracing::warn!(%address, "Failed to dial Alice: {}", error);
 
-                            if let Some(duration) = self.swarm.behaviour_mut().redial.until_next_redial() {
+                            if let Some(duration) = self.swarm.behaviour().connection_manager.until_next_redial(&peer_id) {
                                 tracing::info!("Next redial attempt in {}s", duration.as_secs());
                             }
                         }
@@ -190,6 +321,19 @@ impl EventLoop {
                     self.swarm.behaviour_mut().swap_setup.start(self.alice_peer_id, swap).await;
                     self.inflight_swap_setup = Some(responder);
                 },
+                Some((swap, responder)) = self.sell_xmr_setup_requests.next().fuse(), if self.is_connected_to_alice() => {
+                    self.swarm.behaviour_mut().sell_xmr_setup.start(self.alice_peer_id, swap).await;
+                    self.inflight_sell_xmr_setup = Some(responder);
+                },
+                Some((tx_lock_proof, responder)) = self.transfer_proofs.next().fuse(), if self.is_connected_to_alice() => {
+                    let request = transfer_proof::Request {
+                        swap_id: self.swap_id,
+                        tx_lock_proof
+                    };
+
+                    let id = self.swarm.behaviour_mut().transfer_proof.send_request(&self.alice_peer_id, request);
+                    self.inflight_transfer_proof_requests.insert(id, responder);
+                },
                 Some((tx_redeem_encsig, responder)) = self.encrypted_signatures.next().fuse(), if self.is_connected_to_alice() => {
                     let request = encrypted_signature::Request {
                         swap_id: self.swap_id,
@@ -199,12 +343,32 @@ impl EventLoop {
                     let id = self.swarm.behaviour_mut().encrypted_signature.send_request(&self.alice_peer_id, request);
                     self.inflight_encrypted_signature_requests.insert(id, responder);
                 },
+                Some(((), responder)) = self.aborts.next().fuse(), if self.is_connected_to_alice() => {
+                    let request = abort::Request {
+                        swap_id: self.swap_id,
+                    };
+
+                    let id = self.swarm.behaviour_mut().abort.send_request(&self.alice_peer_id, request);
+                    self.inflight_abort_requests.insert(id, responder);
+                },
 
                 Some(response_channel) = &mut self.pending_transfer_proof => {
                     let _ = self.swarm.behaviour_mut().transfer_proof.send_response(response_channel, ());
 
                     self.pending_transfer_proof = OptionFuture::from(None);
                 }
+
+                Some(response_channel) = &mut self.pending_encrypted_signature => {
+                    let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
+
+                    self.pending_encrypted_signature = OptionFuture::from(None);
+                }
+
+                Some(response_channel) = &mut self.pending_abort => {
+                    let _ = self.swarm.behaviour_mut().abort.send_response(response_channel, ());
+
+                    self.pending_abort = OptionFuture::from(None);
+                }
             }
         }
     }
@@ -216,18 +380,75 @@ impl EventLoop {
 
 #[derive(Debug)]
 pub struct EventLoopHandle {
+    peer: PeerId,
     swap_setup: bmrng::RequestSender<NewSwap, Result<State2>>,
+    sell_xmr_setup: bmrng::RequestSender<sell_xmr::alice::NewSwap, Result<State3>>,
     transfer_proof: bmrng::RequestReceiver<monero::TransferProof, ()>,
     encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
+    send_transfer_proof: bmrng::RequestSender<monero::TransferProof, ()>,
+    recv_encrypted_signature: bmrng::RequestReceiver<EncryptedSignature, ()>,
+    recv_abort: bmrng::RequestReceiver<(), ()>,
+    send_abort: bmrng::RequestSender<(), ()>,
     quote: bmrng::RequestSender<(), BidQuote>,
     env_config: env::Config,
 }
 
 impl EventLoopHandle {
+    /// Creates a handle for a swap in which we take the role of Bob but the
+    /// swap was set up by Alice, like the ASB does when buying XMR.
+    ///
+    /// Only the transfer proof, the encrypted signature and an abort are
+    /// relayed through the handle, setting up a swap or requesting a quote
+    /// fails.
+    pub(crate) fn for_swap_set_up_by_peer(
+        peer: PeerId,
+        transfer_proof: bmrng::RequestReceiver<monero::TransferProof, ()>,
+        encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
+        recv_abort: bmrng::RequestReceiver<(), ()>,
+        send_abort: bmrng::RequestSender<(), ()>,
+        env_config: env::Config,
+    ) -> Self {
+        Self {
+            peer,
+            swap_setup: bmrng::channel(1).0,
+            sell_xmr_setup: bmrng::channel(1).0,
+            transfer_proof,
+            encrypted_signature,
+            send_transfer_proof: bmrng::channel(1).0,
+            recv_encrypted_signature: bmrng::channel(1).1,
+            recv_abort,
+            send_abort,
+            quote: bmrng::channel(1).0,
+            env_config,
+        }
+    }
+
+    /// The peer the swap is executed with.
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
     pub async fn setup_swap(&mut self, swap: NewSwap) -> Result<State2> {
         self.swap_setup.send_receive(swap).await?
     }
 
+    /// Sets up a swap in which we sell XMR, taking the role of Alice.
+    pub async fn setup_sell_xmr(&mut self, swap: sell_xmr::alice::NewSwap) -> Result<State3> {
+        self.sell_xmr_setup.send_receive(swap).await?
+    }
+
+    /// Turns this handle into the handle the swap in the role of Alice is
+    /// executed with.
+    pub fn into_alice_handle(self) -> asb::EventLoopHandle {
+        asb::EventLoopHandle::new(
+            self.peer,
+            self.recv_encrypted_signature,
+            self.send_transfer_proof,
+            self.recv_abort,
+            self.send_abort,
+        )
+    }
+
     pub async fn recv_transfer_proof(&mut self) -> Result<monero::TransferProof> {
         let (transfer_proof, responder) = self
             .transfer_proof
@@ -241,6 +462,28 @@ impl EventLoopHandle {
         Ok(transfer_proof)
     }
 
+    /// Resolves once Alice told us that she will not proceed with the swap.
+    pub async fn recv_abort(&mut self) -> Result<()> {
+        let ((), responder) = self
+            .recv_abort
+            .recv()
+            .await
+            .context("Failed to receive abort")?;
+        responder
+            .respond(())
+            .context("Failed to acknowledge receipt of abort")?;
+
+        Ok(())
+    }
+
+    /// Tells Alice that we will not proceed with the swap.
+    pub async fn send_abort(&mut self) -> Result<()> {
+        self.send_abort
+            .send_receive(())
+            .await
+            .context("Failed to send abort")
+    }
+
     pub async fn request_quote(&mut self) -> Result<BidQuote> {
         Ok(self.quote.send_receive(()).await?)
     }
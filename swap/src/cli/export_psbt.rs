@@ -0,0 +1,167 @@
+use crate::bitcoin::{Transaction, Txid};
+use crate::cli::cancel_and_refund::latest_refundable_state;
+use crate::cli::external_wallet::encode_psbt;
+use crate::protocol::Database;
+use ::bitcoin::consensus::encode::serialize_hex;
+use ::bitcoin::util::psbt::PartiallySignedTransaction;
+use ::bitcoin::TxOut;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The transactions of a swap that can be exported instead of being published
+/// by the CLI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportableTx {
+    Cancel,
+    Refund,
+}
+
+impl FromStr for ExportableTx {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cancel" => Ok(ExportableTx::Cancel),
+            "refund" => Ok(ExportableTx::Refund),
+            "redeem" => bail!(
+                "The Bitcoin redeem transaction is signed and published by the seller, only the cancel and refund transaction can be exported"
+            ),
+            _ => bail!("Unknown transaction `{}`, expected `cancel` or `refund`", s),
+        }
+    }
+}
+
+impl fmt::Display for ExportableTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportableTx::Cancel => write!(f, "cancel"),
+            ExportableTx::Refund => write!(f, "refund"),
+        }
+    }
+}
+
+/// A fully signed transaction, ready to be broadcast through any means.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportedTx {
+    pub txid: Txid,
+    /// The transaction as base64 encoded PSBT with finalized inputs.
+    pub psbt: String,
+    /// The raw transaction as hex.
+    pub hex: String,
+}
+
+/// Rebuilds and signs the given transaction of a swap from the data stored in
+/// the database without publishing it.
+///
+/// This does not require a connection to the Electrum server, hence allows to
+/// recover the Bitcoin of a swap by broadcasting the transaction through other
+/// means. The cancel transaction is only accepted by the network once the
+/// cancel timelock expired, the refund transaction only once the cancel
+/// transaction is confirmed.
+pub async fn export_psbt(
+    swap_id: Uuid,
+    tx: ExportableTx,
+    db: Arc<dyn Database>,
+) -> Result<ExportedTx> {
+    let state6 = latest_refundable_state(swap_id, db.as_ref()).await?;
+
+    let tx_cancel = state6.signed_cancel_transaction()?;
+    let (signed, spent_output) = match tx {
+        ExportableTx::Cancel => {
+            let tx_lock = state6.tx_lock();
+            let spent_output = TxOut {
+                value: tx_lock.lock_amount().as_sat(),
+                script_pubkey: tx_lock.script_pubkey(),
+            };
+
+            (tx_cancel, spent_output)
+        }
+        ExportableTx::Refund => {
+            let spent_output = tx_cancel
+                .output
+                .first()
+                .cloned()
+                .context("Cancel transaction has no output")?;
+
+            (state6.signed_refund_transaction()?, spent_output)
+        }
+    };
+
+    tracing::info!(%swap_id, txid = %signed.txid(), "Exporting {} transaction", tx);
+
+    Ok(ExportedTx {
+        txid: signed.txid(),
+        psbt: encode_psbt(&finalized_psbt(&signed, spent_output)?),
+        hex: serialize_hex(&signed),
+    })
+}
+
+/// Wraps a signed transaction spending a single output into a PSBT whose
+/// input is already finalized.
+fn finalized_psbt(signed: &Transaction, spent_output: TxOut) -> Result<PartiallySignedTransaction> {
+    if signed.input.len() != 1 {
+        bail!(
+            "Expected transaction {} to have exactly one input",
+            signed.txid()
+        );
+    }
+
+    let mut unsigned = signed.clone();
+    for input in &mut unsigned.input {
+        input.witness.clear();
+    }
+
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned)?;
+    psbt.inputs[0].witness_utxo = Some(spent_output);
+    psbt.inputs[0].final_script_witness = Some(signed.input[0].witness.clone());
+
+    Ok(psbt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::external_wallet::decode_psbt;
+    use ::bitcoin::{OutPoint, Script, TxIn};
+
+    #[test]
+    fn finalized_psbt_extracts_the_signed_transaction() {
+        let signed = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: 0xFFFF_FFFF,
+                witness: vec![vec![1; 72], vec![2; 33]],
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Script::new(),
+            }],
+        };
+        let spent_output = TxOut {
+            value: 11_000,
+            script_pubkey: Script::new(),
+        };
+
+        let psbt = finalized_psbt(&signed, spent_output.clone()).unwrap();
+        let decoded = decode_psbt(&encode_psbt(&psbt)).unwrap();
+
+        assert_eq!(decoded.inputs[0].witness_utxo, Some(spent_output));
+        assert_eq!(decoded.extract_tx(), signed);
+    }
+
+    #[test]
+    fn redeem_transaction_cannot_be_exported() {
+        assert_eq!(
+            "refund".parse::<ExportableTx>().unwrap(),
+            ExportableTx::Refund
+        );
+        assert!("redeem".parse::<ExportableTx>().is_err());
+    }
+}
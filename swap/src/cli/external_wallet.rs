@@ -0,0 +1,273 @@
+//! Funding swaps from a Bitcoin wallet that is not derived from the seed of
+//! the CLI.
+
+use crate::bitcoin::Signer;
+use ::bitcoin::consensus::encode::{deserialize, serialize};
+use ::bitcoin::hashes::hex::ToHex;
+use ::bitcoin::hashes::{sha256, Hash};
+use ::bitcoin::util::bip32::{ExtendedPrivKey, Fingerprint};
+use ::bitcoin::util::psbt::PartiallySignedTransaction;
+use ::bitcoin::Network;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A Bitcoin wallet given to the CLI instead of its internal wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternalWallet {
+    /// An extended private key, used with BIP84 derivation like the internal
+    /// wallet.
+    Xprv(ExtendedPrivKey),
+    /// Output descriptors for receive and optionally change addresses.
+    Descriptors {
+        descriptor: String,
+        change_descriptor: Option<String>,
+    },
+}
+
+impl ExternalWallet {
+    /// Reads the wallet from a file that contains either an xprv or an output
+    /// descriptor, optionally followed by a change descriptor on the next
+    /// line. Empty lines and lines starting with `#` are ignored.
+    pub async fn read(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read Bitcoin wallet from {}", path.display()))?;
+
+        content.parse()
+    }
+
+    /// Identifies the wallet without revealing its keys, used to keep the
+    /// databases of different external wallets apart.
+    pub fn id(&self) -> String {
+        let hash = match self {
+            ExternalWallet::Xprv(xprv) => sha256::Hash::hash(xprv.to_string().as_bytes()),
+            ExternalWallet::Descriptors { descriptor, .. } => {
+                sha256::Hash::hash(descriptor.as_bytes())
+            }
+        };
+
+        hash.to_hex()[..16].to_string()
+    }
+}
+
+impl FromStr for ExternalWallet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect::<Vec<_>>();
+
+        match lines.as_slice() {
+            [line] => match line.parse::<ExtendedPrivKey>() {
+                Ok(xprv) => Ok(ExternalWallet::Xprv(xprv)),
+                Err(_) => Ok(ExternalWallet::Descriptors {
+                    descriptor: line.to_string(),
+                    change_descriptor: None,
+                }),
+            },
+            [descriptor, change_descriptor] => Ok(ExternalWallet::Descriptors {
+                descriptor: descriptor.to_string(),
+                change_descriptor: Some(change_descriptor.to_string()),
+            }),
+            [] => bail!("Bitcoin wallet file is empty"),
+            _ => bail!("Expected an xprv or up to two output descriptors in Bitcoin wallet file"),
+        }
+    }
+}
+
+/// Has the user sign transactions with their external wallet.
+///
+/// Prints the unsigned PSBT and reads the signed PSBT from stdin.
+#[derive(Debug, Clone, Copy)]
+pub struct PsbtPrompt;
+
+#[async_trait]
+impl Signer for PsbtPrompt {
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction> {
+        println!(
+            "Sign the Bitcoin transaction {} with your wallet and paste the signed PSBT:\n{}",
+            psbt.global.unsigned_tx.txid(),
+            encode_psbt(&psbt)
+        );
+
+        let line = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+
+            Ok::<_, std::io::Error>(line)
+        })
+        .await??;
+
+        decode_psbt(line.trim())
+    }
+}
+
+/// Signs transactions with a hardware wallet through
+/// [HWI](https://github.com/bitcoin-core/HWI).
+///
+/// The `hwi` binary has to be on the `PATH`. The device is selected by the
+/// fingerprint of its master key, the user confirms every transaction on the
+/// device.
+#[derive(Debug, Clone, Copy)]
+pub struct Hwi {
+    fingerprint: Fingerprint,
+    network: Network,
+}
+
+impl Hwi {
+    pub fn new(fingerprint: Fingerprint, network: Network) -> Self {
+        Self {
+            fingerprint,
+            network,
+        }
+    }
+
+    fn chain(&self) -> &'static str {
+        match self.network {
+            Network::Bitcoin => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for Hwi {
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction> {
+        println!(
+            "Confirm the Bitcoin transaction {} on your hardware wallet",
+            psbt.global.unsigned_tx.txid()
+        );
+
+        let output = tokio::process::Command::new("hwi")
+            .arg("--fingerprint")
+            .arg(self.fingerprint.to_string())
+            .arg("--chain")
+            .arg(self.chain())
+            .arg("signtx")
+            .arg(encode_psbt(&psbt))
+            .output()
+            .await
+            .context("Failed to run hwi, make sure it is installed")?;
+
+        match serde_json::from_slice::<SignTxResponse>(&output.stdout) {
+            Ok(response) => response.into_psbt(),
+            Err(_) => bail!(
+                "hwi failed to sign the transaction: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        }
+    }
+}
+
+/// The output of `hwi signtx`, either the signed PSBT or an error.
+#[derive(Debug, Deserialize)]
+struct SignTxResponse {
+    psbt: Option<String>,
+    error: Option<String>,
+}
+
+impl SignTxResponse {
+    fn into_psbt(self) -> Result<PartiallySignedTransaction> {
+        match self {
+            SignTxResponse {
+                error: Some(error), ..
+            } => bail!("Hardware wallet failed to sign the transaction: {}", error),
+            SignTxResponse {
+                psbt: Some(psbt), ..
+            } => decode_psbt(&psbt),
+            SignTxResponse { .. } => bail!("hwi returned neither a PSBT nor an error"),
+        }
+    }
+}
+
+pub fn encode_psbt(psbt: &PartiallySignedTransaction) -> String {
+    base64::encode(serialize(psbt))
+}
+
+pub fn decode_psbt(encoded: &str) -> Result<PartiallySignedTransaction> {
+    let bytes = base64::decode(encoded).context("PSBT is not base64 encoded")?;
+    let psbt = deserialize(&bytes).context("Failed to deserialize PSBT")?;
+
+    Ok(psbt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{Amount, PublicKey, TxLock, WalletBuilder};
+
+    const XPRV: &str = "tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m";
+    const DESCRIPTOR: &str = "wpkh(tpubD6NzVbkrYhZ4Y2T5diX3fWTxxTY8ahSRd6LUm3zPLzxE7HvrBk6f5m3WPctwyXixoUpyZFKP1JgPZRw3Seqi6gok8MCZ73VZwJC8ZepLvJs/0/*)";
+    const CHANGE_DESCRIPTOR: &str = "wpkh(tpubD6NzVbkrYhZ4Y2T5diX3fWTxxTY8ahSRd6LUm3zPLzxE7HvrBk6f5m3WPctwyXixoUpyZFKP1JgPZRw3Seqi6gok8MCZ73VZwJC8ZepLvJs/1/*)";
+
+    #[test]
+    fn parses_xprv() {
+        let wallet = XPRV.parse::<ExternalWallet>().unwrap();
+
+        assert_eq!(wallet, ExternalWallet::Xprv(XPRV.parse().unwrap()));
+    }
+
+    #[test]
+    fn parses_descriptors_and_ignores_comments() {
+        let content = format!(
+            "# my hardware wallet\n{}\n\n{}\n",
+            DESCRIPTOR, CHANGE_DESCRIPTOR
+        );
+
+        let wallet = content.parse::<ExternalWallet>().unwrap();
+
+        assert_eq!(wallet, ExternalWallet::Descriptors {
+            descriptor: DESCRIPTOR.to_string(),
+            change_descriptor: Some(CHANGE_DESCRIPTOR.to_string()),
+        });
+    }
+
+    #[test]
+    fn given_more_than_two_descriptors_then_fails() {
+        let content = format!("{}\n{}\n{}", DESCRIPTOR, CHANGE_DESCRIPTOR, DESCRIPTOR);
+
+        assert!(content.parse::<ExternalWallet>().is_err());
+    }
+
+    #[tokio::test]
+    async fn psbt_roundtrips_through_base64() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let change = wallet.new_address().await.unwrap();
+        let tx_lock = TxLock::new(
+            &wallet,
+            Amount::from_sat(10_000),
+            PublicKey::random(),
+            PublicKey::random(),
+            change,
+        )
+        .await
+        .unwrap();
+        let psbt = PartiallySignedTransaction::from(tx_lock);
+
+        let decoded = decode_psbt(&encode_psbt(&psbt)).unwrap();
+
+        assert_eq!(decoded, psbt);
+    }
+
+    #[test]
+    fn hwi_error_is_reported() {
+        let response = serde_json::from_str::<SignTxResponse>(
+            r#"{"error": "Could not find device with specified fingerprint", "code": -3}"#,
+        )
+        .unwrap();
+
+        let error = response.into_psbt().unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("Could not find device with specified fingerprint"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::bitcoin::Txid;
+use crate::protocol::{Database, State};
+use crate::{bitcoin, monero};
+use anyhow::Result;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Everything we know about a swap, assembled from all the states the swap
+/// went through.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapDetails {
+    pub swap_id: Uuid,
+    pub peer_id: Option<String>,
+    pub state: String,
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc::opt")]
+    pub btc_amount: Option<bitcoin::Amount>,
+    pub xmr_amount: Option<String>,
+    pub state_transitions: Vec<StateTransition>,
+    pub txids: TxIds,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateTransition {
+    pub entered_at: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TxIds {
+    pub btc_lock: Option<Txid>,
+    pub btc_cancel: Option<Txid>,
+    pub btc_refund: Option<Txid>,
+    pub xmr_lock: Option<monero::TxHash>,
+}
+
+/// Loads the details of all swaps in the database.
+pub async fn history(db: &(dyn Database + Send + Sync)) -> Result<Vec<SwapDetails>> {
+    let mut swaps = Vec::new();
+
+    for (swap_id, _) in db.all().await? {
+        swaps.push(swap_details(db, swap_id).await?);
+    }
+
+    Ok(swaps)
+}
+
+/// Loads the details of a single swap from the database.
+pub async fn swap_details(db: &(dyn Database + Send + Sync), swap_id: Uuid) -> Result<SwapDetails> {
+    let peer_id = db.get_peer_id(swap_id).await.ok().map(|p| p.to_string());
+
+    let states = db.get_states(swap_id).await?;
+
+    Ok(aggregate(swap_id, peer_id, states))
+}
+
+/// What a single state reveals about a swap, regardless of whether we bought
+/// or sold XMR in it.
+struct Facts {
+    btc_amount: Option<bitcoin::Amount>,
+    xmr_amount: Option<monero::Amount>,
+    tx_lock_id: Option<Txid>,
+    tx_cancel_id: Option<Txid>,
+    tx_refund_id: Option<Txid>,
+    xmr_lock_tx_hash: Option<monero::TxHash>,
+}
+
+impl From<&State> for Facts {
+    fn from(state: &State) -> Self {
+        match state {
+            State::Alice(state) => Facts {
+                btc_amount: state.btc_amount(),
+                xmr_amount: state.xmr_amount(),
+                tx_lock_id: state.tx_lock_id(),
+                tx_cancel_id: state.tx_cancel_id(),
+                tx_refund_id: state.tx_refund_id(),
+                xmr_lock_tx_hash: state.xmr_lock_tx_hash(),
+            },
+            State::Bob(state) => Facts {
+                btc_amount: state.btc_amount(),
+                xmr_amount: state.xmr_amount(),
+                tx_lock_id: state.tx_lock_id(),
+                tx_cancel_id: state.tx_cancel_id(),
+                tx_refund_id: state.tx_refund_id(),
+                xmr_lock_tx_hash: state.xmr_lock_tx_hash(),
+            },
+        }
+    }
+}
+
+fn aggregate(swap_id: Uuid, peer_id: Option<String>, states: Vec<(String, State)>) -> SwapDetails {
+    let mut btc_amount = None;
+    let mut xmr_amount: Option<monero::Amount> = None;
+    let mut txids = TxIds::default();
+    let mut state_transitions = Vec::with_capacity(states.len());
+
+    for (entered_at, state) in &states {
+        let facts = Facts::from(state);
+
+        // The lock amount is only known precisely once the lock transaction
+        // was built, hence later states take precedence.
+        btc_amount = facts.btc_amount.or(btc_amount);
+        xmr_amount = xmr_amount.or(facts.xmr_amount);
+
+        txids.btc_lock = facts.tx_lock_id.or(txids.btc_lock);
+        txids.btc_cancel = facts.tx_cancel_id.or(txids.btc_cancel);
+        txids.btc_refund = facts.tx_refund_id.or(txids.btc_refund);
+        if let Some(tx_hash) = facts.xmr_lock_tx_hash {
+            txids.xmr_lock = Some(tx_hash);
+        }
+
+        state_transitions.push(StateTransition {
+            entered_at: entered_at.clone(),
+            state: state.to_string(),
+        });
+    }
+
+    let state = states
+        .last()
+        .map(|(_, state)| state.to_string())
+        .unwrap_or_default();
+
+    SwapDetails {
+        swap_id,
+        peer_id,
+        state,
+        btc_amount,
+        xmr_amount: xmr_amount.map(|amount| amount.to_string()),
+        state_transitions,
+        txids,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::alice::AliceState;
+    use crate::protocol::bob::BobState;
+
+    #[test]
+    fn given_no_lock_transaction_then_only_amount_and_transitions_are_reported() {
+        let swap_id = Uuid::new_v4();
+        let change_address: bitcoin::Address = "bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw"
+            .parse()
+            .unwrap();
+
+        let details = aggregate(swap_id, None, vec![
+            (
+                "2021-10-01 10:00:00.0 +00:00:00".to_string(),
+                State::Bob(BobState::Started {
+                    btc_amount: bitcoin::Amount::from_sat(100_000),
+                    change_address,
+                    timelocks: None,
+                    confirmation_schedule: None,
+                }),
+            ),
+            (
+                "2021-10-01 10:00:05.0 +00:00:00".to_string(),
+                State::Bob(BobState::SafelyAborted),
+            ),
+        ]);
+
+        assert_eq!(details, SwapDetails {
+            swap_id,
+            peer_id: None,
+            state: "safely aborted".to_string(),
+            btc_amount: Some(bitcoin::Amount::from_sat(100_000)),
+            xmr_amount: None,
+            state_transitions: vec![
+                StateTransition {
+                    entered_at: "2021-10-01 10:00:00.0 +00:00:00".to_string(),
+                    state: "quote has been requested".to_string(),
+                },
+                StateTransition {
+                    entered_at: "2021-10-01 10:00:05.0 +00:00:00".to_string(),
+                    state: "safely aborted".to_string(),
+                },
+            ],
+            txids: TxIds::default(),
+        });
+    }
+
+    #[test]
+    fn txid_of_final_state_is_reported() {
+        let tx_lock_id = Txid::default();
+
+        let details = aggregate(Uuid::new_v4(), None, vec![(
+            "2021-10-01 10:00:00.0 +00:00:00".to_string(),
+            State::Bob(BobState::XmrRedeemed { tx_lock_id }),
+        )]);
+
+        assert_eq!(details.txids.btc_lock, Some(tx_lock_id));
+        assert_eq!(details.state, "xmr is redeemed");
+    }
+
+    #[test]
+    fn state_of_swap_in_which_xmr_was_sold_is_reported() {
+        let details = aggregate(Uuid::new_v4(), None, vec![(
+            "2021-10-01 10:00:00.0 +00:00:00".to_string(),
+            State::Alice(AliceState::BtcRedeemed),
+        )]);
+
+        assert_eq!(details.btc_amount, None);
+        assert_eq!(details.txids, TxIds::default());
+        assert_eq!(details.state, "btc is redeemed");
+    }
+}
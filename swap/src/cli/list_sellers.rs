@@ -1,64 +1,106 @@
+use crate::libp2p_ext::MultiAddrExt;
 use crate::network::quote::BidQuote;
-use crate::network::rendezvous::XmrBtcNamespace;
+use crate::network::rendezvous::RendezvousNamespace;
 use crate::network::{quote, swarm};
-use anyhow::{Context, Result};
+use anyhow::Result;
+use futures::stream::BoxStream;
 use futures::StreamExt;
 use libp2p::multiaddr::Protocol;
-use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::ping::{Ping, PingConfig, PingEvent, PingSuccess};
+use libp2p::relay::Relay;
 use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
 use libp2p::swarm::SwarmEvent;
 use libp2p::{identity, rendezvous, Multiaddr, PeerId, Swarm};
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
 /// Returns sorted list of sellers, with [Online](Status::Online) listed first.
 ///
-/// First uses the rendezvous node to discover peers in the given namespace,
+/// First uses the rendezvous nodes to discover peers in the given namespace,
 /// then fetches a quote from each peer that was discovered. If fetching a quote
 /// from a discovered peer fails the seller's status will be
-/// [Unreachable](Status::Unreachable).
+/// [Unreachable](Status::Unreachable). Sellers registered at several
+/// rendezvous nodes are only listed once, sellers that quote a different asset
+/// pair than the one of the namespace are left out.
 pub async fn list_sellers(
-    rendezvous_node_peer_id: PeerId,
-    rendezvous_node_addr: Multiaddr,
-    namespace: XmrBtcNamespace,
+    rendezvous_points: Vec<(PeerId, Multiaddr)>,
+    namespace: RendezvousNamespace,
     tor_socks5_port: u16,
     identity: identity::Keypair,
 ) -> Result<Vec<Seller>> {
-    let behaviour = Behaviour {
-        rendezvous: rendezvous::client::Behaviour::new(identity.clone()),
-        quote: quote::cli(),
-        ping: Ping::new(
-            PingConfig::new()
-                .with_keep_alive(false)
-                .with_interval(Duration::from_secs(86_400)),
-        ),
-    };
-    let mut swarm = swarm::cli(identity, tor_socks5_port, behaviour).await?;
-
-    swarm
-        .behaviour_mut()
-        .quote
-        .add_address(&rendezvous_node_peer_id, rendezvous_node_addr.clone());
-    swarm
-        .dial(&rendezvous_node_peer_id)
-        .context("Failed to dial rendezvous node")?;
-
-    let event_loop = EventLoop::new(
-        swarm,
-        rendezvous_node_peer_id,
-        rendezvous_node_addr,
-        namespace,
-    );
+    let mut event_loop =
+        EventLoop::new(rendezvous_points, namespace, tor_socks5_port, identity).await?;
     let sellers = event_loop.run().await;
 
     Ok(sellers)
 }
 
+/// Like [`list_sellers`], but also returns the round trip time of a ping to
+/// every seller.
+///
+/// The ping is sent as soon as we are connected to a seller, sellers that did
+/// not answer it by the time all quotes were fetched have no latency.
+pub async fn list_sellers_with_latency(
+    rendezvous_points: Vec<(PeerId, Multiaddr)>,
+    namespace: RendezvousNamespace,
+    tor_socks5_port: u16,
+    identity: identity::Keypair,
+) -> Result<Vec<(Seller, Option<Duration>)>> {
+    let mut event_loop =
+        EventLoop::new(rendezvous_points, namespace, tor_socks5_port, identity).await?;
+    let sellers = event_loop.run().await;
+
+    let sellers = sellers
+        .into_iter()
+        .map(|seller| {
+            let latency = seller
+                .multiaddr
+                .extract_peer_id()
+                .and_then(|peer_id| event_loop.latencies.get(&peer_id).copied());
+
+            (seller, latency)
+        })
+        .collect();
+
+    Ok(sellers)
+}
+
+/// Like [`list_sellers`], but keeps discovering sellers and fetching their
+/// quotes every `interval`.
+///
+/// The stream yields the sorted list of sellers after every round.
+pub async fn watch_sellers(
+    rendezvous_points: Vec<(PeerId, Multiaddr)>,
+    namespace: RendezvousNamespace,
+    tor_socks5_port: u16,
+    identity: identity::Keypair,
+    interval: Duration,
+) -> Result<BoxStream<'static, Vec<Seller>>> {
+    let event_loop =
+        EventLoop::new(rendezvous_points, namespace, tor_socks5_port, identity).await?;
+
+    let stream = futures::stream::unfold(
+        (event_loop, true),
+        move |(mut event_loop, first_round)| async move {
+            if !first_round {
+                tokio::time::sleep(interval).await;
+            }
+
+            let sellers = event_loop.run().await;
+
+            Some((sellers, (event_loop, false)))
+        },
+    )
+    .boxed();
+
+    Ok(stream)
+}
+
 #[serde_as]
-#[derive(Debug, Serialize, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct Seller {
     pub status: Status,
     #[serde_as(as = "DisplayFromStr")]
@@ -76,6 +118,8 @@ enum OutEvent {
     Rendezvous(rendezvous::client::Event),
     Quote(quote::OutEvent),
     Ping(PingEvent),
+    /// The relay behaviour does not emit any events.
+    Other,
 }
 
 impl From<rendezvous::client::Event> for OutEvent {
@@ -84,6 +128,12 @@ impl From<rendezvous::client::Event> for OutEvent {
     }
 }
 
+impl From<()> for OutEvent {
+    fn from(_: ()) -> Self {
+        OutEvent::Other
+    }
+}
+
 impl From<quote::OutEvent> for OutEvent {
     fn from(event: quote::OutEvent) -> Self {
         OutEvent::Quote(event)
@@ -96,6 +146,7 @@ impl From<quote::OutEvent> for OutEvent {
 struct Behaviour {
     rendezvous: rendezvous::client::Behaviour,
     quote: quote::Behaviour,
+    relay: Relay,
     ping: Ping,
 }
 
@@ -105,75 +156,134 @@ enum QuoteStatus {
     Received(Status),
 }
 
-#[derive(Debug)]
-enum State {
-    WaitForDiscovery,
-    WaitForQuoteCompletion,
-}
-
 struct EventLoop {
     swarm: Swarm<Behaviour>,
-    rendezvous_peer_id: PeerId,
-    rendezvous_addr: Multiaddr,
-    namespace: XmrBtcNamespace,
+    rendezvous_points: Vec<(PeerId, Multiaddr)>,
+    namespace: RendezvousNamespace,
     reachable_asb_address: HashMap<PeerId, Multiaddr>,
     unreachable_asb_address: HashMap<PeerId, Multiaddr>,
     asb_quote_status: HashMap<PeerId, QuoteStatus>,
-    state: State,
+    /// The round trip times of the pings to the sellers in the current round.
+    latencies: HashMap<PeerId, Duration>,
+    /// The rendezvous nodes that have not yet answered our discovery request
+    /// in the current round.
+    pending_discoveries: HashSet<PeerId>,
 }
 
 impl EventLoop {
-    fn new(
-        swarm: Swarm<Behaviour>,
-        rendezvous_peer_id: PeerId,
-        rendezvous_addr: Multiaddr,
-        namespace: XmrBtcNamespace,
-    ) -> Self {
-        Self {
+    async fn new(
+        rendezvous_points: Vec<(PeerId, Multiaddr)>,
+        namespace: RendezvousNamespace,
+        tor_socks5_port: u16,
+        identity: identity::Keypair,
+    ) -> Result<Self> {
+        let mut swarm = swarm::cli(identity.clone(), tor_socks5_port, |relay| Behaviour {
+            rendezvous: rendezvous::client::Behaviour::new(identity),
+            quote: quote::cli(),
+            relay,
+            ping: Ping::new(
+                PingConfig::new()
+                    .with_keep_alive(false)
+                    .with_interval(Duration::from_secs(86_400)),
+            ),
+        })
+        .await?;
+
+        for (peer_id, address) in &rendezvous_points {
+            swarm
+                .behaviour_mut()
+                .quote
+                .add_address(peer_id, address.clone());
+        }
+
+        Ok(Self {
             swarm,
-            rendezvous_peer_id,
-            rendezvous_addr,
+            rendezvous_points,
             namespace,
             reachable_asb_address: Default::default(),
             unreachable_asb_address: Default::default(),
             asb_quote_status: Default::default(),
-            state: State::WaitForDiscovery,
+            latencies: Default::default(),
+            pending_discoveries: Default::default(),
+        })
+    }
+
+    fn is_rendezvous_point(&self, peer_id: &PeerId) -> bool {
+        self.rendezvous_points
+            .iter()
+            .any(|(rendezvous_peer_id, _)| rendezvous_peer_id == peer_id)
+    }
+
+    fn discover(&mut self, rendezvous_node: PeerId) {
+        tracing::info!(
+            %rendezvous_node,
+            "Connected to rendezvous point, discovering nodes in '{}' namespace ...",
+            self.namespace
+        );
+
+        self.swarm.behaviour_mut().rendezvous.discover(
+            Some((&self.namespace).into()),
+            None,
+            None,
+            rendezvous_node,
+        );
+    }
+
+    /// Starts a new round of discovering sellers at all rendezvous points.
+    ///
+    /// Rendezvous points we are still connected to from a previous round are
+    /// asked right away, all others are dialed first.
+    fn start_round(&mut self) {
+        self.asb_quote_status.clear();
+        self.latencies.clear();
+        self.pending_discoveries = self
+            .rendezvous_points
+            .iter()
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+
+        for (peer_id, _) in self.rendezvous_points.clone() {
+            if self.swarm.is_connected(&peer_id) {
+                self.discover(peer_id);
+            } else if let Err(error) = self.swarm.dial(&peer_id) {
+                tracing::error!(rendezvous_node = %peer_id, "Failed to dial rendezvous node: {}", error);
+                self.pending_discoveries.remove(&peer_id);
+            }
         }
     }
 
-    async fn run(mut self) -> Vec<Seller> {
+    /// Runs a round of discovery and returns the sellers found.
+    async fn run(&mut self) -> Vec<Seller> {
+        self.start_round();
+
         loop {
+            if let Some(sellers) = self.round_completed() {
+                break sellers;
+            }
+
             tokio::select! {
                 swarm_event = self.swarm.select_next_some() => {
                     match swarm_event {
                         SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
-                            if peer_id == self.rendezvous_peer_id{
-                                tracing::info!(
-                                    "Connected to rendezvous point, discovering nodes in '{}' namespace ...",
-                                    self.namespace
-                                );
-
-                                self.swarm.behaviour_mut().rendezvous.discover(
-                                    Some(rendezvous::Namespace::new(self.namespace.to_string()).expect("our namespace to be a correct string")),
-                                    None,
-                                    None,
-                                    self.rendezvous_peer_id,
-                                );
+                            if self.is_rendezvous_point(&peer_id) {
+                                if self.pending_discoveries.contains(&peer_id) {
+                                    self.discover(peer_id);
+                                }
                             } else {
                                 let address = endpoint.get_remote_address();
                                 self.reachable_asb_address.insert(peer_id, address.clone());
                             }
                         }
                         SwarmEvent::UnreachableAddr { peer_id, error, address, .. } => {
-                            if address == self.rendezvous_addr {
+                            if self.is_rendezvous_point(&peer_id) {
                                 tracing::error!(
                                     "Failed to connect to rendezvous point at {}: {}",
                                     address,
                                     error
                                 );
 
-                                // if the rendezvous node is unreachable we just stop
-                                return Vec::new();
+                                // if a rendezvous node is unreachable we continue with the others
+                                self.pending_discoveries.remove(&peer_id);
                             } else {
                                 tracing::debug!(
                                     "Failed to connect to peer at {}: {}",
@@ -193,12 +303,18 @@ impl EventLoop {
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(
-                                                  libp2p::rendezvous::client::Event::Discovered { registrations, .. },
+                                                  libp2p::rendezvous::client::Event::Discovered { rendezvous_node, registrations, .. },
                                               )) => {
-                            self.state = State::WaitForQuoteCompletion;
+                            self.pending_discoveries.remove(&rendezvous_node);
 
                             for registration in registrations {
                                 let peer = registration.record.peer_id();
+
+                                // the same seller may be registered at several rendezvous points
+                                if self.asb_quote_status.contains_key(&peer) {
+                                    continue;
+                                }
+
                                 for address in registration.record.addresses() {
                                     tracing::info!("Discovered peer {} at {}", peer, address);
 
@@ -221,12 +337,21 @@ impl EventLoop {
                                 let _request_id = self.swarm.behaviour_mut().quote.send_request(&peer, ());
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::Rendezvous(
+                                                  libp2p::rendezvous::client::Event::DiscoverFailed { rendezvous_node, error, .. },
+                                              )) => {
+                            tracing::error!(%rendezvous_node, "Failed to discover sellers: {:?}", error);
+                            self.pending_discoveries.remove(&rendezvous_node);
+                        }
                         SwarmEvent::Behaviour(OutEvent::Quote(quote_response)) => {
                             match quote_response {
                                 RequestResponseEvent::Message { peer, message } => {
                                     match message {
                                         RequestResponseMessage::Response { response, .. } => {
-                                            if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response))).is_none() {
+                                            if response.pair() != self.namespace.pair() {
+                                                tracing::debug!(%peer, pair = %response.pair(), "Ignoring seller, because it quotes a different asset pair");
+                                                self.asb_quote_status.remove(&peer);
+                                            } else if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response))).is_none() {
                                                 tracing::error!(%peer, "Received bid quote from unexpected peer, this record will be removed!");
                                                 self.asb_quote_status.remove(&peer);
                                             }
@@ -235,7 +360,7 @@ impl EventLoop {
                                     }
                                 }
                                 RequestResponseEvent::OutboundFailure { peer, error, .. } => {
-                                    if peer == self.rendezvous_peer_id {
+                                    if self.is_rendezvous_point(&peer) {
                                         tracing::debug!(%peer, "Outbound failure when communicating with rendezvous node: {:#}", error);
                                     } else {
                                         tracing::debug!(%peer, "Ignoring seller, because unable to request quote: {:#}", error);
@@ -243,7 +368,7 @@ impl EventLoop {
                                     }
                                 }
                                 RequestResponseEvent::InboundFailure { peer, error, .. } => {
-                                    if peer == self.rendezvous_peer_id {
+                                    if self.is_rendezvous_point(&peer) {
                                         tracing::debug!(%peer, "Inbound failure when communicating with rendezvous node: {:#}", error);
                                     } else {
                                         tracing::debug!(%peer, "Ignoring seller, because unable to request quote: {:#}", error);
@@ -253,55 +378,61 @@ impl EventLoop {
                                 RequestResponseEvent::ResponseSent { .. } => unreachable!()
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::Ping(PingEvent { peer, result: Ok(PingSuccess::Ping { rtt }) })) => {
+                            if !self.is_rendezvous_point(&peer) {
+                                self.latencies.insert(peer, rtt);
+                            }
+                        }
                         _ => {}
                     }
                 }
             }
+        }
+    }
 
-            match self.state {
-                State::WaitForDiscovery => {
-                    continue;
-                }
-                State::WaitForQuoteCompletion => {
-                    let all_quotes_fetched = self
-                        .asb_quote_status
-                        .iter()
-                        .map(|(peer_id, quote_status)| match quote_status {
-                            QuoteStatus::Pending => Err(StillPending {}),
-                            QuoteStatus::Received(Status::Online(quote)) => {
-                                let address = self
-                                    .reachable_asb_address
-                                    .get(&peer_id)
-                                    .expect("if we got a quote we must have stored an address");
-
-                                Ok(Seller {
-                                    multiaddr: address.clone(),
-                                    status: Status::Online(*quote),
-                                })
-                            }
-                            QuoteStatus::Received(Status::Unreachable) => {
-                                let address = self
-                                    .unreachable_asb_address
-                                    .get(&peer_id)
-                                    .expect("if we got a quote we must have stored an address");
-
-                                Ok(Seller {
-                                    multiaddr: address.clone(),
-                                    status: Status::Unreachable,
-                                })
-                            }
-                        })
-                        .collect::<Result<Vec<_>, _>>();
+    /// Returns the sorted list of sellers once all rendezvous points answered
+    /// and the quotes of all discovered sellers have been fetched.
+    fn round_completed(&self) -> Option<Vec<Seller>> {
+        if !self.pending_discoveries.is_empty() {
+            return None;
+        }
 
-                    match all_quotes_fetched {
-                        Ok(mut sellers) => {
-                            sellers.sort();
-                            break sellers;
-                        }
-                        Err(StillPending {}) => continue,
-                    }
+        let all_quotes_fetched = self
+            .asb_quote_status
+            .iter()
+            .map(|(peer_id, quote_status)| match quote_status {
+                QuoteStatus::Pending => Err(StillPending {}),
+                QuoteStatus::Received(Status::Online(quote)) => {
+                    let address = self
+                        .reachable_asb_address
+                        .get(&peer_id)
+                        .expect("if we got a quote we must have stored an address");
+
+                    Ok(Seller {
+                        multiaddr: address.clone(),
+                        status: Status::Online(*quote),
+                    })
+                }
+                QuoteStatus::Received(Status::Unreachable) => {
+                    let address = self
+                        .unreachable_asb_address
+                        .get(&peer_id)
+                        .expect("if we got a quote we must have stored an address");
+
+                    Ok(Seller {
+                        multiaddr: address.clone(),
+                        status: Status::Unreachable,
+                    })
                 }
+            })
+            .collect::<Result<Vec<_>, _>>();
+
+        match all_quotes_fetched {
+            Ok(mut sellers) => {
+                sellers.sort();
+                Some(sellers)
             }
+            Err(StillPending {}) => None,
         }
     }
 }
@@ -336,6 +467,7 @@ mod tests {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
+                    details: None,
                 }),
             },
         ];
@@ -349,6 +481,7 @@ mod tests {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
+                    details: None,
                 })
             },
             Seller {
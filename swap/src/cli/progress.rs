@@ -0,0 +1,350 @@
+//! Reports the progress of a swap of Bob while it runs.
+//!
+//! The progress is derived from the latest state in the database and the
+//! confirmations of the lock transactions. It is either shown as a spinner on
+//! the terminal or streamed as one JSON object per line, for GUIs.
+
+use crate::bitcoin;
+use crate::bitcoin::wallet::ScriptStatus;
+use crate::env::Config;
+use crate::protocol::bob::{BobState, MoneroWallet};
+use crate::protocol::{Database, State};
+use anyhow::Result;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::cmp::min;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How often the state and the confirmations are checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the spinner is redrawn.
+const SPINNER_TICK_MILLIS: u64 = 100;
+
+/// How the progress is reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    /// A spinner on the terminal, with the progress as its message.
+    Spinner,
+    /// One JSON object per line on stdout whenever the progress changes.
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    pub swap_id: Uuid,
+    pub state: String,
+    /// `None` until the Bitcoin lock transaction was published.
+    pub btc_lock_confirmations: Option<u32>,
+    pub btc_lock_confirmations_required: u32,
+    /// `None` until the Monero lock transaction is known.
+    pub xmr_lock_confirmations: Option<u64>,
+    pub xmr_lock_confirmations_required: u64,
+    /// `None` if the Bitcoin lock transaction was not published yet or the
+    /// swap is past the point where it can be cancelled.
+    pub blocks_until_cancel: Option<u32>,
+    pub seconds_until_cancel: Option<u64>,
+}
+
+impl Progress {
+    pub fn new(
+        swap_id: Uuid,
+        state: &BobState,
+        btc_lock_status: Option<ScriptStatus>,
+        xmr_lock_confirmations: Option<u64>,
+        xmr_lock_confirmations_required: u64,
+        env_config: &Config,
+    ) -> Self {
+        let btc_lock_confirmations = btc_lock_status
+            .filter(|status| status.has_been_seen())
+            .map(|status| confirmations(&status));
+
+        let xmr_lock_confirmations = match state {
+            BobState::XmrLocked(..)
+            | BobState::EncSigSent(..)
+            | BobState::BtcRedeemed(..)
+            | BobState::XmrRedeemed { .. } => Some(xmr_lock_confirmations_required),
+            _ => xmr_lock_confirmations,
+        };
+
+        let blocks_until_cancel = match (state.timelocks(), btc_lock_status) {
+            (Some(timelocks), Some(status)) => {
+                blocks_until_cancel(u32::from(timelocks.cancel), &status)
+            }
+            _ => None,
+        };
+        let seconds_until_cancel = blocks_until_cancel
+            .map(|blocks| u64::from(blocks) * env_config.bitcoin_avg_block_time.as_secs());
+
+        Self {
+            swap_id,
+            state: state.to_string(),
+            btc_lock_confirmations,
+            btc_lock_confirmations_required: env_config.bitcoin_finality_confirmations,
+            xmr_lock_confirmations,
+            xmr_lock_confirmations_required,
+            blocks_until_cancel,
+            seconds_until_cancel,
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Swap {}: {}", self.swap_id, self.state)?;
+
+        if let Some(confirmations) = self.btc_lock_confirmations {
+            write!(
+                f,
+                ", BTC lock {}/{} confirmations",
+                min(confirmations, self.btc_lock_confirmations_required),
+                self.btc_lock_confirmations_required
+            )?;
+        }
+        if let Some(confirmations) = self.xmr_lock_confirmations {
+            write!(
+                f,
+                ", XMR lock {}/{} confirmations",
+                min(confirmations, self.xmr_lock_confirmations_required),
+                self.xmr_lock_confirmations_required
+            )?;
+        }
+        if let (Some(blocks), Some(seconds)) = (self.blocks_until_cancel, self.seconds_until_cancel)
+        {
+            write!(
+                f,
+                ", cancel possible in {} blocks (~{})",
+                blocks,
+                humanize(seconds)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reports the progress of the swap whenever it changes, until reading the
+/// database fails.
+///
+/// Failing to query the confirmations is not fatal, the confirmations are
+/// reported as unknown until the next successful query.
+pub async fn report(
+    swap_id: Uuid,
+    db: Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: MoneroWallet,
+    env_config: Config,
+    output: Output,
+) -> Result<()> {
+    let spinner = match output {
+        Output::Spinner => {
+            let spinner = ProgressBar::new_spinner();
+            spinner.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}"));
+            spinner.enable_steady_tick(SPINNER_TICK_MILLIS);
+            Some(spinner)
+        }
+        Output::Json => None,
+    };
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    let mut last_progress = None;
+
+    loop {
+        interval.tick().await;
+
+        let state = match db.get_state(swap_id).await? {
+            State::Bob(state) => state,
+            State::Alice(_) => return Ok(()),
+        };
+        let progress = progress(
+            swap_id,
+            &state,
+            &bitcoin_wallet,
+            &monero_wallet,
+            &env_config,
+        )
+        .await;
+
+        if last_progress.as_ref() == Some(&progress) {
+            continue;
+        }
+
+        match &spinner {
+            Some(spinner) => spinner.set_message(progress.to_string()),
+            None => println!("{}", serde_json::to_string(&progress)?),
+        }
+
+        last_progress = Some(progress);
+    }
+}
+
+async fn progress(
+    swap_id: Uuid,
+    state: &BobState,
+    bitcoin_wallet: &bitcoin::Wallet,
+    monero_wallet: &MoneroWallet,
+    env_config: &Config,
+) -> Progress {
+    let btc_lock_status = match state.tx_lock() {
+        Some(tx_lock) => bitcoin_wallet
+            .status_of_script(tx_lock)
+            .await
+            .map_err(|error| {
+                tracing::debug!(
+                    "Failed to get status of Bitcoin lock transaction: {:#}",
+                    error
+                )
+            })
+            .ok(),
+        None => None,
+    };
+
+    let (xmr_lock_confirmations, xmr_lock_confirmations_required) = match state {
+        BobState::XmrLockProofReceived {
+            state: state3,
+            lock_transfer_proof,
+            ..
+        } => {
+            let request = state3.lock_xmr_watch_request(lock_transfer_proof.clone());
+            let confirmations = monero_wallet
+                .transfer_confirmations(&request)
+                .await
+                .unwrap_or_else(|error| {
+                    tracing::debug!(
+                        "Failed to get confirmations of Monero lock transaction: {:#}",
+                        error
+                    );
+                    None
+                });
+
+            (confirmations, request.conf_target)
+        }
+        _ => (
+            None,
+            state
+                .monero_confirmations()
+                .unwrap_or(env_config.monero_finality_confirmations),
+        ),
+    };
+
+    Progress::new(
+        swap_id,
+        state,
+        btc_lock_status,
+        xmr_lock_confirmations,
+        xmr_lock_confirmations_required,
+        env_config,
+    )
+}
+
+fn confirmations(status: &ScriptStatus) -> u32 {
+    match status {
+        ScriptStatus::Confirmed(inner) => inner.confirmations(),
+        ScriptStatus::Unseen | ScriptStatus::InMempool => 0,
+    }
+}
+
+/// The relative cancel timelock starts once the lock transaction is
+/// confirmed.
+fn blocks_until_cancel(cancel_timelock: u32, btc_lock_status: &ScriptStatus) -> Option<u32> {
+    if !btc_lock_status.has_been_seen() {
+        return None;
+    }
+
+    Some(cancel_timelock.saturating_sub(confirmations(btc_lock_status)))
+}
+
+fn humanize(seconds: u64) -> String {
+    let minutes = seconds / 60;
+
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{GetConfig, Testnet};
+
+    #[test]
+    fn cancel_is_counted_from_the_confirmation_of_the_lock_transaction() {
+        assert_eq!(blocks_until_cancel(72, &ScriptStatus::Unseen), None);
+        assert_eq!(blocks_until_cancel(72, &ScriptStatus::InMempool), Some(72));
+        assert_eq!(
+            blocks_until_cancel(72, &ScriptStatus::from_confirmations(10)),
+            Some(62)
+        );
+        assert_eq!(
+            blocks_until_cancel(72, &ScriptStatus::from_confirmations(100)),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn confirmations_are_only_known_once_the_bitcoin_is_locked() {
+        let swap_id = Uuid::new_v4();
+        let env_config = Testnet::get_config();
+
+        let progress = Progress::new(
+            swap_id,
+            &BobState::SafelyAborted,
+            None,
+            None,
+            env_config.monero_finality_confirmations,
+            &env_config,
+        );
+
+        assert_eq!(progress.btc_lock_confirmations, None);
+        assert_eq!(progress.xmr_lock_confirmations, None);
+        assert_eq!(progress.blocks_until_cancel, None);
+        assert_eq!(
+            progress.to_string(),
+            format!("Swap {}: safely aborted", swap_id)
+        );
+    }
+
+    #[test]
+    fn progress_is_shown_on_one_line() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let progress = Progress {
+            swap_id,
+            state: "XMR lock transaction transfer proof received".to_string(),
+            btc_lock_confirmations: Some(3),
+            btc_lock_confirmations_required: 1,
+            xmr_lock_confirmations: Some(4),
+            xmr_lock_confirmations_required: 10,
+            blocks_until_cancel: Some(9),
+            seconds_until_cancel: Some(9 * 600),
+        };
+
+        assert_eq!(
+            progress.to_string(),
+            "Swap ea030832-3be9-454f-bb98-5ea9a788406b: XMR lock transaction transfer proof received, BTC lock 1/1 confirmations, XMR lock 4/10 confirmations, cancel possible in 9 blocks (~1h 30m)"
+        );
+    }
+
+    #[test]
+    fn progress_is_streamed_as_json() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let progress = Progress {
+            swap_id,
+            state: "btc is locked".to_string(),
+            btc_lock_confirmations: Some(0),
+            btc_lock_confirmations_required: 1,
+            xmr_lock_confirmations: None,
+            xmr_lock_confirmations_required: 10,
+            blocks_until_cancel: Some(72),
+            seconds_until_cancel: Some(72 * 600),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&progress).unwrap(),
+            "{\"swap_id\":\"ea030832-3be9-454f-bb98-5ea9a788406b\",\"state\":\"btc is locked\",\"btc_lock_confirmations\":0,\"btc_lock_confirmations_required\":1,\"xmr_lock_confirmations\":null,\"xmr_lock_confirmations_required\":10,\"blocks_until_cancel\":72,\"seconds_until_cancel\":43200}"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Choosing the seller of `buy-xmr --select-best` among the sellers that are
+//! registered at the rendezvous points.
+//!
+//! Only the sellers that accept the amount to swap are considered. The seller
+//! with the lowest price wins, sellers with the same price are ranked by the
+//! round trip time of a ping to them.
+
+use crate::bitcoin;
+use crate::cli::list_sellers::{Seller, Status};
+use crate::network::quote::BidQuote;
+use libp2p::Multiaddr;
+use std::time::Duration;
+
+/// A seller that accepts the amount to swap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub multiaddr: Multiaddr,
+    pub quote: BidQuote,
+    /// `None` if the seller did not answer the ping in time.
+    pub latency: Option<Duration>,
+}
+
+/// Ranks the sellers that accept to swap `btc_amount`, the best seller first.
+///
+/// The price of the quote is the effective price, the seller pays the fees of
+/// its own transactions out of it. Sellers without a latency are ranked after
+/// the ones with the same price that answered the ping.
+pub fn rank(
+    sellers: Vec<(Seller, Option<Duration>)>,
+    btc_amount: bitcoin::Amount,
+) -> Vec<Candidate> {
+    let mut candidates = sellers
+        .into_iter()
+        .filter_map(|(seller, latency)| match seller.status {
+            Status::Online(quote)
+                if quote.min_quantity <= btc_amount && btc_amount <= quote.max_quantity =>
+            {
+                Some(Candidate {
+                    multiaddr: seller.multiaddr,
+                    quote,
+                    latency,
+                })
+            }
+            Status::Online(_) | Status::Unreachable => None,
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_by_key(|candidate| {
+        (
+            candidate.quote.price,
+            candidate.latency.is_none(),
+            candidate.latency,
+        )
+    });
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn btc(amount: f64) -> bitcoin::Amount {
+        bitcoin::Amount::from_btc(amount).unwrap()
+    }
+
+    fn seller(port: u16, price: f64, min: f64, max: f64) -> Seller {
+        Seller {
+            multiaddr: format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap(),
+            status: Status::Online(BidQuote {
+                price: btc(price),
+                min_quantity: btc(min),
+                max_quantity: btc(max),
+                details: None,
+            }),
+        }
+    }
+
+    fn addresses(candidates: &[Candidate]) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|candidate| candidate.multiaddr.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn cheapest_seller_ranks_first_regardless_of_latency() {
+        let ranked = rank(
+            vec![
+                (
+                    seller(1, 0.0070, 0.001, 0.1),
+                    Some(Duration::from_millis(10)),
+                ),
+                (
+                    seller(2, 0.0065, 0.001, 0.1),
+                    Some(Duration::from_millis(900)),
+                ),
+            ],
+            btc(0.01),
+        );
+
+        assert_eq!(addresses(&ranked), vec![
+            "/ip4/127.0.0.1/tcp/2",
+            "/ip4/127.0.0.1/tcp/1"
+        ]);
+    }
+
+    #[test]
+    fn sellers_with_the_same_price_are_ranked_by_latency() {
+        let ranked = rank(
+            vec![
+                (seller(1, 0.0070, 0.001, 0.1), None),
+                (
+                    seller(2, 0.0070, 0.001, 0.1),
+                    Some(Duration::from_millis(300)),
+                ),
+                (
+                    seller(3, 0.0070, 0.001, 0.1),
+                    Some(Duration::from_millis(30)),
+                ),
+            ],
+            btc(0.01),
+        );
+
+        assert_eq!(addresses(&ranked), vec![
+            "/ip4/127.0.0.1/tcp/3",
+            "/ip4/127.0.0.1/tcp/2",
+            "/ip4/127.0.0.1/tcp/1"
+        ]);
+    }
+
+    #[test]
+    fn sellers_that_do_not_accept_the_amount_are_left_out() {
+        let unreachable = Seller {
+            multiaddr: "/ip4/127.0.0.1/tcp/4".parse().unwrap(),
+            status: Status::Unreachable,
+        };
+
+        let ranked = rank(
+            vec![
+                (seller(1, 0.0060, 0.02, 0.1), None),
+                (seller(2, 0.0060, 0.001, 0.005), None),
+                (seller(3, 0.0070, 0.01, 0.01), None),
+                (unreachable, None),
+            ],
+            btc(0.01),
+        );
+
+        assert_eq!(addresses(&ranked), vec!["/ip4/127.0.0.1/tcp/3"]);
+    }
+}
@@ -0,0 +1,129 @@
+//! Splitting the Bitcoin of a single `buy-xmr` invocation into several swaps.
+//!
+//! The swaps are assigned to the sellers in turn. Their Bitcoin is locked one
+//! swap after another so that the lock transactions don't spend the same
+//! outputs, once locked the swaps run concurrently.
+
+use crate::bitcoin;
+use crate::network::quote::BidQuote;
+use std::cmp::min;
+use std::convert::TryFrom;
+
+/// Assigns the given number of swaps to the sellers in turn.
+pub fn assign_sellers<T>(sellers: &[T], swaps: usize) -> Vec<T>
+where
+    T: Clone,
+{
+    sellers.iter().cycle().take(swaps).cloned().collect()
+}
+
+/// The quote for all swaps together, given the quotes of the sellers the swaps
+/// are assigned to.
+///
+/// The Bitcoin has to cover the minimum amount of every swap and can be at most
+/// the sum of the maximum amounts. The price is the highest price of the
+/// sellers.
+pub fn combined_quote(quotes: &[BidQuote]) -> BidQuote {
+    BidQuote {
+        price: quotes
+            .iter()
+            .map(|quote| quote.price)
+            .max()
+            .unwrap_or(bitcoin::Amount::ZERO),
+        min_quantity: saturating_sum(quotes.iter().map(|quote| quote.min_quantity)),
+        max_quantity: saturating_sum(quotes.iter().map(|quote| quote.max_quantity)),
+        details: None,
+    }
+}
+
+/// Sellers without a maximum amount quote the maximum value.
+fn saturating_sum(amounts: impl Iterator<Item = bitcoin::Amount>) -> bitcoin::Amount {
+    amounts.fold(bitcoin::Amount::ZERO, |sum, amount| {
+        sum.checked_add(amount)
+            .unwrap_or_else(bitcoin::Amount::max_value)
+    })
+}
+
+/// The amount of the next swap, out of the Bitcoin that is left for the
+/// remaining swaps including the next one.
+///
+/// Returns `None` if the share of the next swap is below the minimum amount of
+/// its seller.
+pub fn next_swap_amount(
+    available: bitcoin::Amount,
+    remaining_swaps: usize,
+    quote: &BidQuote,
+) -> Option<bitcoin::Amount> {
+    let remaining_swaps = u64::try_from(remaining_swaps).ok()?;
+    let share = available.checked_div(remaining_swaps)?;
+    let amount = min(share, quote.max_quantity);
+
+    if amount == bitcoin::Amount::ZERO || amount < quote.min_quantity {
+        return None;
+    }
+
+    Some(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swaps_are_assigned_to_sellers_in_turn() {
+        assert_eq!(assign_sellers(&["a", "b"], 5), vec![
+            "a", "b", "a", "b", "a"
+        ]);
+        assert_eq!(assign_sellers(&["a", "b", "c"], 2), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn combined_quote_covers_the_limits_of_all_swaps() {
+        let combined = combined_quote(&[quote(0.001, 0.01, 0.1), quote(0.002, 0.02, 0.2)]);
+
+        assert_eq!(combined.price, btc(0.002));
+        assert_eq!(combined.min_quantity, btc(0.03));
+        assert_eq!(combined.max_quantity, btc(0.3));
+    }
+
+    #[test]
+    fn combined_quote_of_unlimited_sellers_does_not_overflow() {
+        let unlimited = BidQuote {
+            max_quantity: bitcoin::Amount::max_value(),
+            ..quote(0.001, 0.0, 0.0)
+        };
+
+        let combined = combined_quote(&[unlimited, unlimited]);
+
+        assert_eq!(combined.max_quantity, bitcoin::Amount::max_value());
+    }
+
+    #[test]
+    fn next_swap_gets_an_even_share_up_to_the_maximum() {
+        let quote = quote(0.001, 0.01, 0.1);
+
+        assert_eq!(next_swap_amount(btc(0.09), 3, &quote), Some(btc(0.03)));
+        assert_eq!(next_swap_amount(btc(0.9), 3, &quote), Some(btc(0.1)));
+    }
+
+    #[test]
+    fn next_swap_below_minimum_is_not_started() {
+        let quote = quote(0.001, 0.01, 0.1);
+
+        assert_eq!(next_swap_amount(btc(0.02), 3, &quote), None);
+        assert_eq!(next_swap_amount(bitcoin::Amount::ZERO, 1, &quote), None);
+    }
+
+    fn quote(price: f64, min: f64, max: f64) -> BidQuote {
+        BidQuote {
+            price: btc(price),
+            min_quantity: btc(min),
+            max_quantity: btc(max),
+            details: None,
+        }
+    }
+
+    fn btc(amount: f64) -> bitcoin::Amount {
+        bitcoin::Amount::from_btc(amount).unwrap()
+    }
+}
@@ -1,67 +1,42 @@
+use crate::logging::{LogFormat, SwapLogFiles};
 use anyhow::Result;
-use std::option::Option::Some;
 use std::path::Path;
 use tracing::subscriber::set_global_default;
 use tracing::{Event, Level, Subscriber};
 use tracing_subscriber::fmt::format::{DefaultFields, Format};
 use tracing_subscriber::fmt::time::ChronoLocal;
 use tracing_subscriber::layer::{Context, SubscriberExt};
-use tracing_subscriber::{fmt, EnvFilter, FmtSubscriber, Layer, Registry};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
 use uuid::Uuid;
 
+/// Logs to stderr and the debug logs of every swap to its own file in `dir`.
+///
+/// The log messages that don't belong to a swap are written to the file of
+/// `swap_id`, if given.
 pub fn init(debug: bool, json: bool, dir: impl AsRef<Path>, swap_id: Option<Uuid>) -> Result<()> {
-    if json {
-        let level = if debug { Level::DEBUG } else { Level::INFO };
-
-        let is_terminal = atty::is(atty::Stream::Stderr);
-
-        FmtSubscriber::builder()
-            .with_env_filter(format!("swap={}", level))
-            .with_writer(std::io::stderr)
-            .with_ansi(is_terminal)
-            .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
-            .with_target(false)
-            .json()
-            .init();
-
-        Ok(())
-    } else if let Some(swap_id) = swap_id {
-        let level_filter = EnvFilter::try_new("swap=debug")?;
-
-        let registry = Registry::default().with(level_filter);
-
-        let appender = tracing_appender::rolling::never(dir, format!("swap-{}.log", swap_id));
-        let (appender, guard) = tracing_appender::non_blocking(appender);
-
-        std::mem::forget(guard);
-
-        let file_logger = fmt::layer()
-            .with_ansi(false)
-            .with_target(false)
-            .with_writer(appender);
-
-        if debug {
-            set_global_default(registry.with(file_logger).with(debug_terminal_printer()))?;
-        } else {
-            set_global_default(registry.with(file_logger).with(info_terminal_printer()))?;
-        }
-
-        Ok(())
+    let format = if json {
+        LogFormat::Json
     } else {
-        let level = if debug { Level::DEBUG } else { Level::INFO };
-        let is_terminal = atty::is(atty::Stream::Stderr);
+        LogFormat::Text
+    };
+    let mut swap_logs = SwapLogFiles::new(format);
+    swap_logs.write_to(dir.as_ref());
+    if let Some(swap_id) = swap_id {
+        swap_logs = swap_logs.with_fallback(swap_id);
+    }
 
-        FmtSubscriber::builder()
-            .with_env_filter(format!("swap={}", level))
-            .with_writer(std::io::stderr)
-            .with_ansi(is_terminal)
-            .with_level(false)
-            .without_time()
-            .with_target(false)
-            .init();
+    let level_filter = EnvFilter::try_new("swap=debug")?;
+    let registry = Registry::default().with(level_filter).with(swap_logs);
+    let level = if debug { Level::DEBUG } else { Level::INFO };
 
-        Ok(())
+    match (format, debug) {
+        (LogFormat::Json, _) => set_global_default(registry.with(json_terminal_printer(level)))?,
+        (LogFormat::Text, true) => set_global_default(registry.with(debug_terminal_printer()))?,
+        (LogFormat::Text, false) => set_global_default(registry.with(info_terminal_printer()))?,
     }
+
+    Ok(())
 }
 
 pub struct StdErrPrinter<L> {
@@ -88,6 +63,22 @@ fn debug_terminal_printer<S>() -> StdErrPrinter<StdErrLayer<S, ChronoLocal>> {
     }
 }
 
+fn json_terminal_printer<S>(level: Level) -> StdErrPrinter<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let is_terminal = atty::is(atty::Stream::Stderr);
+    StdErrPrinter {
+        inner: fmt::layer()
+            .with_ansi(is_terminal)
+            .with_target(false)
+            .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
+            .with_writer(std::io::stderr)
+            .json(),
+        level,
+    }
+}
+
 fn info_terminal_printer<S>() -> StdErrPrinter<StdErrLayer<S, ()>> {
     let is_terminal = atty::is(atty::Stream::Stderr);
     StdErrPrinter {
@@ -104,7 +95,7 @@ fn info_terminal_printer<S>() -> StdErrPrinter<StdErrLayer<S, ()>> {
 impl<L, S> Layer<S> for StdErrPrinter<L>
 where
     L: 'static + Layer<S>,
-    S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    S: Subscriber + for<'a> LookupSpan<'a>,
 {
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
         if self.level.ge(event.metadata().level()) {
@@ -4,7 +4,9 @@ use anyhow::Result;
 use libp2p::core::muxing::StreamMuxerBox;
 use libp2p::core::transport::{Boxed, OptionalTransport};
 use libp2p::dns::TokioDnsConfig;
+use libp2p::relay::{Relay, RelayConfig};
 use libp2p::tcp::TokioTcpConfig;
+use libp2p::websocket::WsConfig;
 use libp2p::{identity, PeerId, Transport};
 
 /// Creates the libp2p transport for the swap CLI.
@@ -15,10 +17,22 @@ use libp2p::{identity, PeerId, Transport};
 /// - Dial onion-addresses through a running Tor daemon by connecting to the
 ///   socks5 port. If the port is not given, we will fall back to the regular
 ///   TCP transport.
+/// - Dial websocket addresses, i.e. addresses ending in `/ws`, over TCP or Tor
+/// - Dial relayed addresses, i.e. addresses containing `/p2p-circuit`, through
+///   a circuit relay v1 node. This only works with the returned [`Relay`]
+///   behaviour in the swarm.
+///
+/// The transport is chosen by the address that is dialed. If a seller is
+/// known under several addresses, e.g. because its `/dnsaddr` resolves to a
+/// websocket and a TCP address, the next address is dialed if establishing
+/// the connection over one of them fails.
+///
+/// QUIC is not supported, the version of libp2p in use does not provide a
+/// QUIC transport.
 pub fn new(
     identity: &identity::Keypair,
     maybe_tor_socks5_port: Option<u16>,
-) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+) -> Result<(Boxed<(PeerId, StreamMuxerBox)>, Relay)> {
     let tcp = TokioTcpConfig::new().nodelay(true);
     let tcp_with_dns = TokioDnsConfig::system(tcp)?;
     let maybe_tor_transport = match maybe_tor_socks5_port {
@@ -26,7 +40,16 @@ pub fn new(
         None => OptionalTransport::none(),
     };
 
-    let transport = maybe_tor_transport.or_transport(tcp_with_dns).boxed();
+    let tcp_or_tor = maybe_tor_transport.or_transport(tcp_with_dns);
+
+    // The Tor transport also accepts websocket addresses, so the websocket
+    // transport has to come first.
+    let transport = WsConfig::new(tcp_or_tor.clone()).or_transport(tcp_or_tor);
+    let (transport, relay) =
+        libp2p::relay::new_transport_and_behaviour(RelayConfig::default(), transport);
 
-    authenticate_and_multiplex(transport, identity)
+    Ok((
+        authenticate_and_multiplex(transport.boxed(), identity)?,
+        relay,
+    ))
 }
@@ -1,7 +1,9 @@
 pub use alice::Alice;
 pub use bob::Bob;
+pub use memory::MemoryDatabase;
 pub use sqlite::SqliteDatabase;
 
+use crate::encryption::Cipher;
 use crate::fs::ensure_directory_exists;
 use crate::protocol::{Database, State};
 use anyhow::{bail, Result};
@@ -12,6 +14,8 @@ use std::sync::Arc;
 
 mod alice;
 mod bob;
+mod memory;
+pub mod sled_import;
 mod sqlite;
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -83,16 +87,29 @@ impl Swap {
     }
 }
 
-pub async fn open_db(sqlite_path: impl AsRef<Path>) -> Result<Arc<dyn Database + Send + Sync>> {
+pub async fn open_db(
+    sqlite_path: impl AsRef<Path>,
+    cipher: Option<Cipher>,
+) -> Result<Arc<dyn Database + Send + Sync>> {
     if sqlite_path.as_ref().exists() {
         tracing::debug!("Using existing sqlite database.");
-        let sqlite = SqliteDatabase::open(sqlite_path).await?;
+        let sqlite = SqliteDatabase::open(sqlite_path, cipher).await?;
         Ok(Arc::new(sqlite))
     } else {
+        // Releases before 0.10.0 stored swaps in a sled database in the same
+        // directory. These swaps are not migrated automatically anymore.
+        let sled_path = sqlite_path.as_ref().with_file_name("database");
+        if sled_path.exists() {
+            tracing::warn!(
+                path = %sled_path.display(),
+                "Found an old sled database, its swaps are not available until they are imported with the `import-sled` command"
+            );
+        }
+
         tracing::debug!("Creating and using new sqlite database.");
         ensure_directory_exists(sqlite_path.as_ref())?;
         tokio::fs::File::create(&sqlite_path).await?;
-        let sqlite = SqliteDatabase::open(sqlite_path).await?;
+        let sqlite = SqliteDatabase::open(sqlite_path, cipher).await?;
         Ok(Arc::new(sqlite))
     }
 }
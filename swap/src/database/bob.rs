@@ -1,4 +1,5 @@
 use crate::monero::TransferProof;
+use crate::network::swap_setup::{ConfirmationSchedule, Timelocks};
 use crate::protocol::bob;
 use crate::protocol::bob::BobState;
 use monero_rpc::wallet::BlockHeight;
@@ -14,6 +15,10 @@ pub enum Bob {
         btc_amount: bitcoin::Amount,
         #[serde_as(as = "DisplayFromStr")]
         change_address: bitcoin::Address,
+        #[serde(default)]
+        timelocks: Option<Timelocks>,
+        #[serde(default)]
+        confirmation_schedule: Option<ConfirmationSchedule>,
     },
     ExecutionSetupDone {
         state2: bob::State2,
@@ -52,9 +57,13 @@ impl From<BobState> for Bob {
             BobState::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                confirmation_schedule,
             } => Bob::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                confirmation_schedule,
             },
             BobState::SwapSetupCompleted(state2) => Bob::ExecutionSetupDone { state2 },
             BobState::BtcLocked(state3) => Bob::BtcLocked { state3 },
@@ -90,9 +99,13 @@ impl From<Bob> for BobState {
             Bob::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                confirmation_schedule,
             } => BobState::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                confirmation_schedule,
             },
             Bob::ExecutionSetupDone { state2 } => BobState::SwapSetupCompleted(state2),
             Bob::BtcLocked { state3 } => BobState::BtcLocked(state3),
@@ -0,0 +1,494 @@
+use crate::asb::identity::IdentityRotation;
+use crate::asb::outbox::OutboundMessage;
+use crate::asb::reputation::{Ban, Misbehaviour};
+use crate::asb::sweep::Sweep;
+use crate::cli::address_book::{AddressBookEntry, AddressKind};
+use crate::monero;
+use crate::network::swap_setup::receipt::SignedReceipt;
+use crate::protocol::bob::SwapSetupParameters;
+use crate::protocol::{Database, RecordedState, State};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use libp2p::{Multiaddr, PeerId};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A database that keeps everything in memory and forgets it once dropped.
+///
+/// Meant for applications that embed the swap protocol and do not need the
+/// swaps to survive a restart, or that persist them on their own through
+/// [`Database::get_states_after`]. It behaves like the
+/// [`SqliteDatabase`](crate::database::SqliteDatabase), except that nothing is
+/// encrypted.
+#[derive(Debug, Default)]
+pub struct MemoryDatabase {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    peers: HashMap<Uuid, PeerId>,
+    monero_addresses: HashMap<Uuid, monero::Address>,
+    peer_addresses: Vec<(PeerId, Multiaddr)>,
+    states: Vec<RecordedState>,
+    swap_setup_parameters: HashMap<Uuid, SwapSetupParameters>,
+    misbehaviours: Vec<(PeerId, OffsetDateTime, Misbehaviour)>,
+    bans: HashMap<PeerId, Ban>,
+    sweeps: Vec<Sweep>,
+    xmr_refund_sweeps: Vec<(Uuid, monero::wallet::Sweep)>,
+    outbound_messages: HashMap<Uuid, (PeerId, OutboundMessage)>,
+    address_book: BTreeMap<(String, AddressKind), AddressBookEntry>,
+    identity_rotations: BTreeMap<u32, IdentityRotation>,
+    swap_identities: HashMap<Uuid, u32>,
+    receipts: Vec<(Uuid, SignedReceipt)>,
+}
+
+impl MemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables
+            .lock()
+            .expect("no panic while holding the lock of the memory database")
+    }
+}
+
+#[async_trait]
+impl Database for MemoryDatabase {
+    async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
+        let mut tables = self.tables();
+        if tables.peers.contains_key(&swap_id) {
+            bail!("A peer id is already stored for swap {}", swap_id)
+        }
+        tables.peers.insert(swap_id, peer_id);
+
+        Ok(())
+    }
+
+    async fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId> {
+        self.tables()
+            .peers
+            .get(&swap_id)
+            .copied()
+            .with_context(|| format!("No peer id in database for swap: {}", swap_id))
+    }
+
+    async fn insert_monero_address(&self, swap_id: Uuid, address: monero::Address) -> Result<()> {
+        let mut tables = self.tables();
+        if tables.monero_addresses.contains_key(&swap_id) {
+            bail!("A Monero address is already stored for swap {}", swap_id)
+        }
+        tables.monero_addresses.insert(swap_id, address);
+
+        Ok(())
+    }
+
+    async fn get_monero_address(&self, swap_id: Uuid) -> Result<monero::Address> {
+        self.tables()
+            .monero_addresses
+            .get(&swap_id)
+            .cloned()
+            .with_context(|| format!("No Monero address in database for swap: {}", swap_id))
+    }
+
+    async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()> {
+        self.tables().peer_addresses.push((peer_id, address));
+
+        Ok(())
+    }
+
+    async fn get_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>> {
+        let addresses = self
+            .tables()
+            .peer_addresses
+            .iter()
+            .filter(|(peer, _)| *peer == peer_id)
+            .map(|(_, address)| address.clone())
+            .collect();
+
+        Ok(addresses)
+    }
+
+    async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()> {
+        let entered_at = OffsetDateTime::now_utc();
+
+        self.insert_recorded_state(swap_id, entered_at.to_string(), state)
+            .await
+    }
+
+    async fn insert_recorded_state(
+        &self,
+        swap_id: Uuid,
+        entered_at: String,
+        state: State,
+    ) -> Result<()> {
+        let mut tables = self.tables();
+        let id = tables.states.last().map_or(1, |recorded| recorded.id + 1);
+        tables.states.push(RecordedState {
+            id,
+            swap_id,
+            entered_at,
+            state,
+        });
+
+        Ok(())
+    }
+
+    async fn get_state(&self, swap_id: Uuid) -> Result<State> {
+        self.tables()
+            .states
+            .iter()
+            .rev()
+            .find(|recorded| recorded.swap_id == swap_id)
+            .map(|recorded| recorded.state.clone())
+            .with_context(|| format!("No state in database for swap: {}", swap_id))
+    }
+
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<(String, State)>> {
+        let states = self
+            .tables()
+            .states
+            .iter()
+            .filter(|recorded| recorded.swap_id == swap_id)
+            .map(|recorded| (recorded.entered_at.clone(), recorded.state.clone()))
+            .collect();
+
+        Ok(states)
+    }
+
+    async fn all(&self) -> Result<Vec<(Uuid, State)>> {
+        let mut latest = Vec::<(Uuid, State)>::new();
+        for recorded in self.tables().states.iter() {
+            match latest
+                .iter_mut()
+                .find(|(swap_id, _)| *swap_id == recorded.swap_id)
+            {
+                Some((_, state)) => *state = recorded.state.clone(),
+                None => latest.push((recorded.swap_id, recorded.state.clone())),
+            }
+        }
+
+        Ok(latest)
+    }
+
+    async fn get_latest_state_id(&self) -> Result<Option<i64>> {
+        Ok(self.tables().states.last().map(|recorded| recorded.id))
+    }
+
+    async fn get_states_after(&self, id: i64) -> Result<Vec<RecordedState>> {
+        let states = self
+            .tables()
+            .states
+            .iter()
+            .filter(|recorded| recorded.id > id)
+            .cloned()
+            .collect();
+
+        Ok(states)
+    }
+
+    async fn insert_swap_setup_parameters(
+        &self,
+        swap_id: Uuid,
+        parameters: SwapSetupParameters,
+    ) -> Result<()> {
+        self.tables()
+            .swap_setup_parameters
+            .insert(swap_id, parameters);
+
+        Ok(())
+    }
+
+    async fn get_swap_setup_parameters(
+        &self,
+        swap_id: Uuid,
+    ) -> Result<Option<SwapSetupParameters>> {
+        Ok(self.tables().swap_setup_parameters.get(&swap_id).copied())
+    }
+
+    async fn insert_misbehaviour(
+        &self,
+        peer_id: PeerId,
+        misbehaviour: Misbehaviour,
+        recorded_at: OffsetDateTime,
+    ) -> Result<()> {
+        self.tables()
+            .misbehaviours
+            .push((peer_id, recorded_at, misbehaviour));
+
+        Ok(())
+    }
+
+    async fn get_misbehaviours_since(
+        &self,
+        peer_id: PeerId,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Misbehaviour>> {
+        // Compared in seconds, like the timestamps stored by sqlite
+        let misbehaviours = self
+            .tables()
+            .misbehaviours
+            .iter()
+            .filter(|(peer, recorded_at, _)| {
+                *peer == peer_id && recorded_at.unix_timestamp() >= since.unix_timestamp()
+            })
+            .map(|(_, _, misbehaviour)| *misbehaviour)
+            .collect();
+
+        Ok(misbehaviours)
+    }
+
+    async fn insert_ban(&self, ban: Ban) -> Result<()> {
+        self.tables().bans.insert(ban.peer_id, ban);
+
+        Ok(())
+    }
+
+    async fn remove_ban(&self, peer_id: PeerId) -> Result<bool> {
+        Ok(self.tables().bans.remove(&peer_id).is_some())
+    }
+
+    async fn get_bans(&self) -> Result<Vec<Ban>> {
+        Ok(self.tables().bans.values().cloned().collect())
+    }
+
+    async fn insert_sweep(&self, sweep: Sweep) -> Result<()> {
+        self.tables().sweeps.push(sweep);
+
+        Ok(())
+    }
+
+    async fn get_sweeps(&self) -> Result<Vec<Sweep>> {
+        Ok(self.tables().sweeps.clone())
+    }
+
+    async fn insert_xmr_refund_sweep(
+        &self,
+        swap_id: Uuid,
+        sweep: monero::wallet::Sweep,
+    ) -> Result<()> {
+        self.tables().xmr_refund_sweeps.push((swap_id, sweep));
+
+        Ok(())
+    }
+
+    async fn get_xmr_refund_sweeps(&self, swap_id: Uuid) -> Result<Vec<monero::wallet::Sweep>> {
+        let sweeps = self
+            .tables()
+            .xmr_refund_sweeps
+            .iter()
+            .filter(|(id, _)| *id == swap_id)
+            .map(|(_, sweep)| sweep.clone())
+            .collect();
+
+        Ok(sweeps)
+    }
+
+    async fn insert_outbound_message(
+        &self,
+        peer_id: PeerId,
+        message: OutboundMessage,
+    ) -> Result<()> {
+        self.tables()
+            .outbound_messages
+            .insert(message.swap_id(), (peer_id, message));
+
+        Ok(())
+    }
+
+    async fn remove_outbound_message(&self, swap_id: Uuid) -> Result<()> {
+        self.tables().outbound_messages.remove(&swap_id);
+
+        Ok(())
+    }
+
+    async fn get_outbound_messages(&self) -> Result<Vec<(PeerId, OutboundMessage)>> {
+        Ok(self.tables().outbound_messages.values().cloned().collect())
+    }
+
+    async fn insert_address_book_entry(&self, entry: AddressBookEntry) -> Result<()> {
+        self.tables()
+            .address_book
+            .insert((entry.label.clone(), entry.address.kind()), entry);
+
+        Ok(())
+    }
+
+    async fn remove_address_book_entries(&self, label: &str) -> Result<bool> {
+        let mut tables = self.tables();
+        let entries = tables.address_book.len();
+        tables
+            .address_book
+            .retain(|(entry_label, _), _| entry_label != label);
+
+        Ok(tables.address_book.len() < entries)
+    }
+
+    async fn get_address_book(&self) -> Result<Vec<AddressBookEntry>> {
+        Ok(self.tables().address_book.values().cloned().collect())
+    }
+
+    async fn insert_identity_rotation(&self, rotation: IdentityRotation) -> Result<()> {
+        let mut tables = self.tables();
+        if tables.identity_rotations.contains_key(&rotation.generation) {
+            bail!(
+                "A rotation to generation {} is already stored",
+                rotation.generation
+            )
+        }
+        tables
+            .identity_rotations
+            .insert(rotation.generation, rotation);
+
+        Ok(())
+    }
+
+    async fn get_identity_rotations(&self) -> Result<Vec<IdentityRotation>> {
+        Ok(self.tables().identity_rotations.values().cloned().collect())
+    }
+
+    async fn insert_swap_identity(&self, swap_id: Uuid, generation: u32) -> Result<()> {
+        self.tables().swap_identities.insert(swap_id, generation);
+
+        Ok(())
+    }
+
+    async fn get_swap_identity(&self, swap_id: Uuid) -> Result<u32> {
+        Ok(self
+            .tables()
+            .swap_identities
+            .get(&swap_id)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn insert_receipt(&self, swap_id: Uuid, receipt: SignedReceipt) -> Result<()> {
+        self.tables().receipts.push((swap_id, receipt));
+
+        Ok(())
+    }
+
+    async fn get_receipts(&self, swap_id: Uuid) -> Result<Vec<SignedReceipt>> {
+        let receipts = self
+            .tables()
+            .receipts
+            .iter()
+            .filter(|(id, _)| *id == swap_id)
+            .map(|(_, receipt)| receipt.clone())
+            .collect();
+
+        Ok(receipts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::alice::AliceState;
+    use crate::protocol::bob::BobState;
+
+    #[tokio::test]
+    async fn latest_states_and_states_after_id_are_loaded_in_order() -> Result<()> {
+        let db = MemoryDatabase::new();
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        assert_eq!(db.get_latest_state_id().await?, None);
+
+        db.insert_latest_state(swap_id_1, State::Alice(AliceState::SafelyAborted))
+            .await?;
+        let first_id = db.get_latest_state_id().await?.unwrap();
+        db.insert_latest_state(swap_id_2, State::Bob(BobState::SafelyAborted))
+            .await?;
+        db.insert_latest_state(swap_id_1, State::Alice(AliceState::BtcPunished))
+            .await?;
+
+        assert_eq!(
+            db.get_state(swap_id_1).await?,
+            State::Alice(AliceState::BtcPunished)
+        );
+        assert_eq!(db.all().await?, vec![
+            (swap_id_1, State::Alice(AliceState::BtcPunished)),
+            (swap_id_2, State::Bob(BobState::SafelyAborted)),
+        ]);
+        assert_eq!(
+            db.get_states(swap_id_1)
+                .await?
+                .into_iter()
+                .map(|(_, state)| state)
+                .collect::<Vec<_>>(),
+            vec![
+                State::Alice(AliceState::SafelyAborted),
+                State::Alice(AliceState::BtcPunished)
+            ]
+        );
+        assert_eq!(
+            db.get_states_after(first_id)
+                .await?
+                .into_iter()
+                .map(|recorded| (recorded.swap_id, recorded.state))
+                .collect::<Vec<_>>(),
+            vec![
+                (swap_id_2, State::Bob(BobState::SafelyAborted)),
+                (swap_id_1, State::Alice(AliceState::BtcPunished))
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_state_is_an_error() {
+        let db = MemoryDatabase::new();
+
+        assert!(db.get_state(Uuid::new_v4()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn peer_id_of_a_swap_cannot_be_replaced() -> Result<()> {
+        let db = MemoryDatabase::new();
+        let swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+
+        db.insert_peer_id(swap_id, peer_id).await?;
+
+        assert!(db.insert_peer_id(swap_id, PeerId::random()).await.is_err());
+        assert_eq!(db.get_peer_id(swap_id).await?, peer_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn address_book_entries_are_replaced_and_removed() -> Result<()> {
+        let db = MemoryDatabase::new();
+
+        let entry = |label: &str, address: &str| -> Result<AddressBookEntry> {
+            AddressBookEntry::new(label.to_string(), address.parse()?)
+        };
+        let bitcoin = entry("me", "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv")?;
+        let monero = entry("me", "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a")?;
+        let other_bitcoin = entry("me", "tb1q958vfh3wkdp232pktq8zzvmttyxeqnj80zkz3v")?;
+        let savings = entry("savings", "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv")?;
+
+        db.insert_address_book_entry(savings.clone()).await?;
+        db.insert_address_book_entry(bitcoin).await?;
+        db.insert_address_book_entry(monero.clone()).await?;
+        db.insert_address_book_entry(other_bitcoin.clone()).await?;
+
+        assert_eq!(db.get_address_book().await?, vec![
+            other_bitcoin,
+            monero,
+            savings.clone()
+        ]);
+
+        assert!(db.remove_address_book_entries("me").await?);
+        assert!(!db.remove_address_book_entries("me").await?);
+        assert_eq!(db.get_address_book().await?, vec![savings]);
+
+        Ok(())
+    }
+}
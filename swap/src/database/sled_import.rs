@@ -0,0 +1,266 @@
+//! Imports the swaps of the sled database that releases before 0.10.0 stored
+//! their data in.
+//!
+//! The sled database is the `database` directory next to the sqlite database.
+//! It held the latest state of every swap but none of the states before, the
+//! peer of every swap, the addresses of the peers and, for the CLI, the Monero
+//! address of every swap. Reading it requires the `sled-import` feature.
+
+use crate::protocol::Database;
+use anyhow::Result;
+use std::path::Path;
+use uuid::Uuid;
+
+/// What [`import`] did with the swaps of the sled database.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Imported {
+    /// The swaps that were stored in the database.
+    pub imported: Vec<Uuid>,
+    /// The swaps the database already knew, they are left as they are.
+    pub skipped: Vec<Uuid>,
+    /// The swaps whose state could not be read, e.g. because a very early
+    /// release stored it.
+    pub unreadable: Vec<Uuid>,
+}
+
+/// Stores the swaps of the sled database at the given path in the database.
+///
+/// Swaps the database already knows are skipped, importing the same sled
+/// database again does not change anything.
+#[cfg(feature = "sled-import")]
+pub async fn import(sled_path: &Path, db: &(dyn Database + Send + Sync)) -> Result<Imported> {
+    let (swaps, unreadable) = sled_db::read(sled_path)?;
+
+    let mut imported = Imported {
+        unreadable,
+        ..Imported::default()
+    };
+    for swap in swaps {
+        let swap_id = swap.swap_id;
+        if !db.get_states(swap_id).await?.is_empty() {
+            tracing::debug!(%swap_id, "Skipping swap that is already in the database");
+            imported.skipped.push(swap_id);
+            continue;
+        }
+
+        if let Some(peer_id) = swap.peer_id {
+            db.insert_peer_id(swap_id, peer_id).await?;
+
+            let known_addresses = db.get_addresses(peer_id).await?;
+            for address in swap.peer_addresses {
+                if !known_addresses.contains(&address) {
+                    db.insert_address(peer_id, address).await?;
+                }
+            }
+        }
+        if let Some(address) = swap.monero_address {
+            db.insert_monero_address(swap_id, address).await?;
+        }
+        tracing::info!(%swap_id, state = %swap.state, "Importing swap");
+        db.insert_latest_state(swap_id, swap.state.into()).await?;
+
+        imported.imported.push(swap_id);
+    }
+
+    Ok(imported)
+}
+
+#[cfg(not(feature = "sled-import"))]
+pub async fn import(_: &Path, _: &(dyn Database + Send + Sync)) -> Result<Imported> {
+    anyhow::bail!(
+        "This build cannot read sled databases, build it with `--features sled-import` to import them"
+    )
+}
+
+#[cfg(feature = "sled-import")]
+mod sled_db {
+    use crate::database::Swap;
+    use crate::monero;
+    use anyhow::{bail, Context, Result};
+    use libp2p::{Multiaddr, PeerId};
+    use std::path::Path;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    /// A swap as the sled database stored it.
+    #[derive(Debug)]
+    pub struct SledSwap {
+        pub swap_id: Uuid,
+        pub state: Swap,
+        pub peer_id: Option<PeerId>,
+        pub peer_addresses: Vec<Multiaddr>,
+        pub monero_address: Option<monero::Address>,
+    }
+
+    /// Reads the swaps of the sled database, returns the ids of the swaps
+    /// whose state could not be read separately.
+    ///
+    /// The keys and values are encoded the way releases before 0.10.0 wrote
+    /// them: the swaps and peers are keyed by the CBOR encoded swap id, the
+    /// addresses by the bytes of the peer id and the Monero addresses by the
+    /// bytes of the swap id.
+    pub fn read(sled_path: &Path) -> Result<(Vec<SledSwap>, Vec<Uuid>)> {
+        // sled creates a new database if there is none
+        if !sled_path.exists() {
+            bail!("No sled database at {}", sled_path.display())
+        }
+
+        let db = ::sled::open(sled_path)
+            .with_context(|| format!("Failed to open sled database at {}", sled_path.display()))?;
+        let swaps = db.open_tree("swaps")?;
+        let peers = db.open_tree("peers")?;
+        let addresses = db.open_tree("addresses")?;
+        let monero_addresses = db.open_tree("monero_addresses")?;
+
+        let mut readable = Vec::new();
+        let mut unreadable = Vec::new();
+        for entry in swaps.iter() {
+            let (key, value) = entry.context("Failed to read swap from sled database")?;
+            let swap_id: Uuid =
+                serde_cbor::from_slice(&key).context("Failed to read id of swap")?;
+
+            let state = match serde_cbor::from_slice::<Swap>(&value) {
+                Ok(state) => state,
+                Err(error) => {
+                    tracing::warn!(%swap_id, "Failed to read state of swap: {:#}", error);
+                    unreadable.push(swap_id);
+                    continue;
+                }
+            };
+
+            let peer_id = match peers.get(&key)? {
+                Some(encoded) => {
+                    let peer_id: String = serde_cbor::from_slice(&encoded)
+                        .with_context(|| format!("Failed to read peer id of swap {}", swap_id))?;
+                    Some(PeerId::from_str(&peer_id)?)
+                }
+                None => None,
+            };
+            let peer_addresses = match peer_id {
+                Some(peer_id) => match addresses.get(peer_id.to_bytes())? {
+                    Some(encoded) => serde_cbor::from_slice(&encoded).with_context(|| {
+                        format!("Failed to read addresses of peer of swap {}", swap_id)
+                    })?,
+                    None => Vec::new(),
+                },
+                None => Vec::new(),
+            };
+            let monero_address = match monero_addresses.get(swap_id.as_bytes())? {
+                Some(encoded) => Some(
+                    String::from_utf8(encoded.to_vec())?
+                        .parse::<monero::Address>()
+                        .with_context(|| {
+                            format!("Failed to read Monero address of swap {}", swap_id)
+                        })?,
+                ),
+                None => None,
+            };
+
+            readable.push(SledSwap {
+                swap_id,
+                state,
+                peer_id,
+                peer_addresses,
+                monero_address,
+            });
+        }
+
+        Ok((readable, unreadable))
+    }
+}
+
+#[cfg(all(test, feature = "sled-import"))]
+mod tests {
+    use super::*;
+    use crate::database::{SqliteDatabase, Swap};
+    use crate::monero;
+    use crate::protocol::bob::BobState;
+    use crate::protocol::State;
+    use libp2p::{Multiaddr, PeerId};
+    use std::fs::File;
+    use std::str::FromStr;
+    use tempfile::tempdir;
+
+    async fn new_db() -> Result<SqliteDatabase> {
+        let temp_db = tempdir().unwrap().into_path().join("tempdb");
+        File::create(temp_db.clone()).unwrap();
+
+        SqliteDatabase::open(temp_db, None).await
+    }
+
+    /// Writes the swap the way releases before 0.10.0 did.
+    fn write_sled_swap(
+        db: &::sled::Db,
+        swap_id: Uuid,
+        state: &Swap,
+        peer_id: PeerId,
+        peer_addresses: &[Multiaddr],
+        monero_address: &monero::Address,
+    ) -> Result<()> {
+        let key = serde_cbor::to_vec(&swap_id)?;
+        db.open_tree("swaps")?
+            .insert(&key, serde_cbor::to_vec(state)?)?;
+        db.open_tree("peers")?
+            .insert(&key, serde_cbor::to_vec(&peer_id.to_string())?)?;
+        db.open_tree("addresses")?
+            .insert(peer_id.to_bytes(), serde_cbor::to_vec(peer_addresses)?)?;
+        db.open_tree("monero_addresses")?
+            .insert(swap_id.as_bytes(), monero_address.to_string().as_bytes())?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn imports_swaps_of_sled_database_once() -> Result<()> {
+        let sled_path = tempdir().unwrap().into_path().join("database");
+        let db = new_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+        let address = Multiaddr::from_str("/ip4/127.0.0.1/tcp/9939")?;
+        let monero_address = monero::Address::from_str("53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a")?;
+        let state = State::Bob(BobState::SafelyAborted);
+
+        {
+            let sled = ::sled::open(&sled_path)?;
+            write_sled_swap(
+                &sled,
+                swap_id,
+                &Swap::from(state.clone()),
+                peer_id,
+                &[address.clone()],
+                &monero_address,
+            )?;
+            let unreadable = serde_cbor::to_vec(&Uuid::new_v4())?;
+            sled.open_tree("swaps")?.insert(unreadable, vec![0xffu8])?;
+        }
+
+        let imported = import(&sled_path, &db).await?;
+
+        assert_eq!(imported.imported, vec![swap_id]);
+        assert_eq!(imported.unreadable.len(), 1);
+        assert_eq!(db.get_state(swap_id).await?, state);
+        assert_eq!(db.get_peer_id(swap_id).await?, peer_id);
+        assert_eq!(db.get_addresses(peer_id).await?, vec![address]);
+        assert_eq!(db.get_monero_address(swap_id).await?, monero_address);
+
+        let imported_again = import(&sled_path, &db).await?;
+
+        assert!(imported_again.imported.is_empty());
+        assert_eq!(imported_again.skipped, vec![swap_id]);
+        assert_eq!(db.get_states(swap_id).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_no_sled_database_then_import_fails() -> Result<()> {
+        let sled_path = tempdir().unwrap().into_path().join("database");
+        let db = new_db().await?;
+
+        assert!(import(&sled_path, &db).await.is_err());
+        assert!(!sled_path.exists());
+
+        Ok(())
+    }
+}
@@ -1,29 +1,46 @@
+use crate::asb::identity::IdentityRotation;
+use crate::asb::outbox::OutboundMessage;
+use crate::asb::reputation::{Ban, Misbehaviour};
+use crate::asb::sweep::Sweep;
+use crate::cli::address_book::{AddressBookEntry, AddressKind, BookAddress};
 use crate::database::Swap;
+use crate::encryption::Cipher;
 use crate::monero::Address;
-use crate::protocol::{Database, State};
+use crate::network::swap_setup::receipt::SignedReceipt;
+use crate::protocol::bob::SwapSetupParameters;
+use crate::protocol::{Database, RecordedState, State};
+use crate::{bitcoin, monero};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use libp2p::{Multiaddr, PeerId};
 use sqlx::sqlite::Sqlite;
 use sqlx::{Pool, SqlitePool};
+use std::convert::TryFrom;
 use std::path::Path;
 use std::str::FromStr;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+/// Prefix of the values that are stored encrypted.
+const ENCRYPTED_PREFIX: &str = "encrypted:";
+
 pub struct SqliteDatabase {
     pool: Pool<Sqlite>,
+    /// Encrypts the swap states and setup parameters, which contain secret
+    /// keys.
+    cipher: Option<Cipher>,
 }
 
 impl SqliteDatabase {
-    pub async fn open(path: impl AsRef<Path>) -> Result<Self>
+    pub async fn open(path: impl AsRef<Path>, cipher: Option<Cipher>) -> Result<Self>
     where
         Self: std::marker::Sized,
     {
         let path_str = format!("sqlite:{}", path.as_ref().display());
         let pool = SqlitePool::connect(&path_str).await?;
-        let mut sqlite = Self { pool };
+        let mut sqlite = Self { pool, cipher };
         sqlite.run_migrations().await?;
+        sqlite.encrypt_plaintext_values().await?;
         Ok(sqlite)
     }
 
@@ -31,6 +48,139 @@ impl SqliteDatabase {
         sqlx::migrate!("./migrations").run(&self.pool).await?;
         Ok(())
     }
+
+    /// Encrypts the values that were stored before encryption was enabled.
+    async fn encrypt_plaintext_values(&self) -> Result<()> {
+        if self.cipher.is_none() {
+            return Ok(());
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let mut encrypted = 0;
+
+        let states = sqlx::query!(
+            r#"
+           SELECT id, state
+           FROM swap_states
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        for row in states {
+            if row.state.starts_with(ENCRYPTED_PREFIX) {
+                continue;
+            }
+
+            let state = self.seal(row.state);
+            sqlx::query!(
+                r#"
+            UPDATE swap_states
+            SET state = ?
+            WHERE id = ?
+        "#,
+                state,
+                row.id
+            )
+            .execute(&mut conn)
+            .await?;
+            encrypted += 1;
+        }
+
+        let parameters = sqlx::query!(
+            r#"
+        SELECT swap_id, parameters
+        FROM swap_setup_parameters
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        for row in parameters {
+            if row.parameters.starts_with(ENCRYPTED_PREFIX) {
+                continue;
+            }
+
+            let parameters = self.seal(row.parameters);
+            sqlx::query!(
+                r#"
+        UPDATE swap_setup_parameters
+        SET parameters = ?
+        WHERE swap_id = ?
+        "#,
+                parameters,
+                row.swap_id
+            )
+            .execute(&mut conn)
+            .await?;
+            encrypted += 1;
+        }
+
+        let messages = sqlx::query!(
+            r#"
+        SELECT swap_id, message
+        FROM outbound_messages
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        for row in messages {
+            if row.message.starts_with(ENCRYPTED_PREFIX) {
+                continue;
+            }
+
+            let message = self.seal(row.message);
+            sqlx::query!(
+                r#"
+        UPDATE outbound_messages
+        SET message = ?
+        WHERE swap_id = ?
+        "#,
+                message,
+                row.swap_id
+            )
+            .execute(&mut conn)
+            .await?;
+            encrypted += 1;
+        }
+
+        if encrypted > 0 {
+            // Deleted pages of the database file still contain the plaintext.
+            sqlx::query("VACUUM").execute(&mut conn).await?;
+            tracing::info!(%encrypted, "Encrypted values stored before encryption was enabled");
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts the value to be stored if encryption is enabled.
+    fn seal(&self, value: String) -> String {
+        match &self.cipher {
+            Some(cipher) => format!(
+                "{}{}",
+                ENCRYPTED_PREFIX,
+                base64::encode(cipher.encrypt(value.as_bytes()))
+            ),
+            None => value,
+        }
+    }
+
+    /// Decrypts a stored value, values stored before encryption was enabled
+    /// are returned as they are.
+    fn unseal(&self, value: &str) -> Result<String> {
+        let encrypted = match value.strip_prefix(ENCRYPTED_PREFIX) {
+            Some(encrypted) => encrypted,
+            None => return Ok(value.to_string()),
+        };
+        let cipher = self
+            .cipher
+            .as_ref()
+            .context("The database is encrypted, a passphrase is required to read it")?;
+        let decrypted = cipher.decrypt(&base64::decode(encrypted)?)?;
+
+        Ok(String::from_utf8(decrypted)?)
+    }
 }
 
 #[async_trait]
@@ -170,12 +320,22 @@ impl Database for SqliteDatabase {
     }
 
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()> {
-        let mut conn = self.pool.acquire().await?;
         let entered_at = OffsetDateTime::now_utc();
 
+        self.insert_recorded_state(swap_id, entered_at.to_string(), state)
+            .await
+    }
+
+    async fn insert_recorded_state(
+        &self,
+        swap_id: Uuid,
+        entered_at: String,
+        state: State,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
         let swap_id = swap_id.to_string();
-        let swap = serde_json::to_string(&Swap::from(state))?;
-        let entered_at = entered_at.to_string();
+        let swap = self.seal(serde_json::to_string(&Swap::from(state))?);
 
         sqlx::query!(
             r#"
@@ -215,11 +375,37 @@ impl Database for SqliteDatabase {
         let row = row
             .first()
             .context(format!("No state in database for swap: {}", swap_id))?;
-        let swap: Swap = serde_json::from_str(&row.state)?;
+        let swap: Swap = serde_json::from_str(&self.unseal(&row.state)?)?;
 
         Ok(swap.into())
     }
 
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<(String, State)>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let rows = sqlx::query!(
+            r#"
+           SELECT entered_at, state
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let result = rows
+            .iter()
+            .map(|row| {
+                let swap: Swap = serde_json::from_str(&self.unseal(&row.state)?)?;
+                Ok((row.entered_at.clone(), State::from(swap)))
+            })
+            .collect::<Result<Vec<(String, State)>>>();
+
+        result
+    }
+
     async fn all(&self) -> Result<Vec<(Uuid, State)>> {
         let mut conn = self.pool.acquire().await?;
         let rows = sqlx::query!(
@@ -239,7 +425,7 @@ impl Database for SqliteDatabase {
             .iter()
             .map(|row| {
                 let swap_id = Uuid::from_str(&row.swap_id)?;
-                let state = match serde_json::from_str::<Swap>(&row.state) {
+                let state = match serde_json::from_str::<Swap>(&self.unseal(&row.state)?) {
                     Ok(a) => Ok(State::from(a)),
                     Err(e) => Err(e),
                 }?;
@@ -249,123 +435,1126 @@ impl Database for SqliteDatabase {
 
         result
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::protocol::alice::AliceState;
-    use crate::protocol::bob::BobState;
-    use std::fs::File;
-    use tempfile::tempdir;
+    async fn get_latest_state_id(&self) -> Result<Option<i64>> {
+        let mut conn = self.pool.acquire().await?;
+        let row = sqlx::query!(
+            r#"
+           SELECT id
+           FROM swap_states
+           ORDER BY id desc
+           LIMIT 1;
+        "#
+        )
+        .fetch_optional(&mut conn)
+        .await?;
 
-    #[tokio::test]
-    async fn test_insert_and_load_state() {
-        let db = setup_test_db().await.unwrap();
+        Ok(row.map(|row| row.id))
+    }
 
-        let state_1 = State::Alice(AliceState::BtcRedeemed);
-        let swap_id_1 = Uuid::new_v4();
+    async fn get_states_after(&self, id: i64) -> Result<Vec<RecordedState>> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+           SELECT id, swap_id, entered_at, state
+           FROM swap_states
+           WHERE id > ?
+           ORDER BY id
+        "#,
+            id
+        )
+        .fetch_all(&mut conn)
+        .await?;
 
-        db.insert_latest_state(swap_id_1, state_1).await.unwrap();
+        let result = rows
+            .iter()
+            .map(|row| {
+                let swap: Swap = serde_json::from_str(&self.unseal(&row.state)?)?;
+                Ok(RecordedState {
+                    id: row.id,
+                    swap_id: Uuid::from_str(&row.swap_id)?,
+                    entered_at: row.entered_at.clone(),
+                    state: State::from(swap),
+                })
+            })
+            .collect::<Result<Vec<RecordedState>>>();
 
-        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        result
+    }
 
-        db.insert_latest_state(swap_id_1, state_1.clone())
-            .await
-            .unwrap();
+    async fn insert_swap_setup_parameters(
+        &self,
+        swap_id: Uuid,
+        parameters: SwapSetupParameters,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
 
-        let state_1_loaded = db.get_state(swap_id_1).await.unwrap();
+        let swap_id = swap_id.to_string();
+        let parameters = self.seal(serde_json::to_string(&parameters)?);
 
-        assert_eq!(state_1, state_1_loaded);
+        sqlx::query!(
+            r#"
+        insert or replace into swap_setup_parameters (
+            swap_id,
+            parameters
+            ) values (?, ?);
+        "#,
+            swap_id,
+            parameters
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
     }
 
-    #[tokio::test]
-    async fn test_retrieve_all_latest_states() {
-        let db = setup_test_db().await.unwrap();
+    async fn get_swap_setup_parameters(
+        &self,
+        swap_id: Uuid,
+    ) -> Result<Option<SwapSetupParameters>> {
+        let mut conn = self.pool.acquire().await?;
 
-        let state_1 = State::Alice(AliceState::BtcRedeemed);
-        let state_2 = State::Alice(AliceState::BtcPunished);
-        let state_3 = State::Alice(AliceState::SafelyAborted);
-        let state_4 = State::Bob(BobState::SafelyAborted);
-        let swap_id_1 = Uuid::new_v4();
-        let swap_id_2 = Uuid::new_v4();
+        let swap_id = swap_id.to_string();
 
-        db.insert_latest_state(swap_id_1, state_1.clone())
-            .await
-            .unwrap();
-        db.insert_latest_state(swap_id_1, state_2.clone())
-            .await
-            .unwrap();
-        db.insert_latest_state(swap_id_1, state_3.clone())
-            .await
-            .unwrap();
-        db.insert_latest_state(swap_id_2, state_4.clone())
-            .await
-            .unwrap();
+        let row = sqlx::query!(
+            r#"
+        SELECT parameters
+        FROM swap_setup_parameters
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
 
-        let latest_loaded = db.all().await.unwrap();
+        let parameters = match row {
+            Some(row) => Some(serde_json::from_str(&self.unseal(&row.parameters)?)?),
+            None => None,
+        };
 
-        assert_eq!(latest_loaded.len(), 2);
+        Ok(parameters)
+    }
 
-        assert!(latest_loaded.contains(&(swap_id_1, state_3)));
-        assert!(latest_loaded.contains(&(swap_id_2, state_4)));
+    async fn insert_misbehaviour(
+        &self,
+        peer_id: PeerId,
+        misbehaviour: Misbehaviour,
+        recorded_at: OffsetDateTime,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
 
-        assert!(!latest_loaded.contains(&(swap_id_1, state_1)));
-        assert!(!latest_loaded.contains(&(swap_id_1, state_2)));
+        let peer_id = peer_id.to_string();
+        let recorded_at = recorded_at.unix_timestamp();
+        let kind = misbehaviour.as_str();
+
+        sqlx::query!(
+            r#"
+        insert into peer_misbehaviours (
+            peer_id,
+            recorded_at,
+            kind
+            ) values (?, ?, ?);
+        "#,
+            peer_id,
+            recorded_at,
+            kind
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
     }
 
-    #[tokio::test]
-    async fn test_insert_load_monero_address() -> Result<()> {
-        let db = setup_test_db().await?;
+    async fn get_misbehaviours_since(
+        &self,
+        peer_id: PeerId,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Misbehaviour>> {
+        let mut conn = self.pool.acquire().await?;
 
-        let swap_id = Uuid::new_v4();
-        let monero_address = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?;
+        let peer_id = peer_id.to_string();
+        let since = since.unix_timestamp();
 
-        db.insert_monero_address(swap_id, monero_address).await?;
+        let rows = sqlx::query!(
+            r#"
+        SELECT kind
+        FROM peer_misbehaviours
+        WHERE peer_id = ? AND recorded_at >= ?
+        ORDER BY id ASC
+        "#,
+            peer_id,
+            since
+        )
+        .fetch_all(&mut conn)
+        .await?;
 
-        let loaded_monero_address = db.get_monero_address(swap_id).await?;
+        rows.iter().map(|row| row.kind.parse()).collect()
+    }
 
-        assert_eq!(monero_address, loaded_monero_address);
+    async fn insert_ban(&self, ban: Ban) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = ban.peer_id.to_string();
+        let banned_until = ban.until.map(|until| until.unix_timestamp());
+        let reason = ban.reason;
+
+        sqlx::query!(
+            r#"
+        insert or replace into banned_peers (
+            peer_id,
+            banned_until,
+            reason
+            ) values (?, ?, ?);
+        "#,
+            peer_id,
+            banned_until,
+            reason
+        )
+        .execute(&mut conn)
+        .await?;
 
         Ok(())
     }
 
-    #[tokio::test]
-    async fn test_insert_and_load_multiaddr() -> Result<()> {
-        let db = setup_test_db().await?;
+    async fn remove_ban(&self, peer_id: PeerId) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
 
-        let peer_id = PeerId::random();
-        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
-        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+        let peer_id = peer_id.to_string();
 
-        db.insert_address(peer_id, multiaddr1.clone()).await?;
-        db.insert_address(peer_id, multiaddr2.clone()).await?;
+        let result = sqlx::query!(
+            r#"
+        DELETE FROM banned_peers
+        WHERE peer_id = ?
+        "#,
+            peer_id
+        )
+        .execute(&mut conn)
+        .await?;
 
-        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+        Ok(result.rows_affected() > 0)
+    }
 
-        assert!(loaded_multiaddr.contains(&multiaddr1));
-        assert!(loaded_multiaddr.contains(&multiaddr2));
-        assert_eq!(loaded_multiaddr.len(), 2);
+    async fn get_bans(&self) -> Result<Vec<Ban>> {
+        let mut conn = self.pool.acquire().await?;
 
-        Ok(())
+        let rows = sqlx::query!(
+            r#"
+        SELECT peer_id, banned_until, reason
+        FROM banned_peers
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| -> Result<Ban> {
+                let until = row
+                    .banned_until
+                    .map(OffsetDateTime::from_unix_timestamp)
+                    .transpose()?;
+
+                Ok(Ban {
+                    peer_id: PeerId::from_str(&row.peer_id)?,
+                    until,
+                    reason: row.reason,
+                })
+            })
+            .collect()
     }
 
-    #[tokio::test]
-    async fn test_insert_and_load_peer_id() -> Result<()> {
-        let db = setup_test_db().await?;
+    async fn insert_sweep(&self, sweep: Sweep) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
 
-        let peer_id = PeerId::random();
-        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
-        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+        let txid = sweep.txid.to_string();
+        let address = sweep.address.to_string();
+        let amount = i64::try_from(sweep.amount.as_sat())?;
+        let swept_at = sweep.swept_at.unix_timestamp();
 
-        db.insert_address(peer_id, multiaddr1.clone()).await?;
-        db.insert_address(peer_id, multiaddr2.clone()).await?;
+        sqlx::query!(
+            r#"
+        insert into sweeps (
+            txid,
+            address,
+            amount,
+            swept_at
+            ) values (?, ?, ?, ?);
+        "#,
+            txid,
+            address,
+            amount,
+            swept_at
+        )
+        .execute(&mut conn)
+        .await?;
 
-        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+        Ok(())
+    }
 
-        assert!(loaded_multiaddr.contains(&multiaddr1));
-        assert!(loaded_multiaddr.contains(&multiaddr2));
-        assert_eq!(loaded_multiaddr.len(), 2);
+    async fn get_sweeps(&self) -> Result<Vec<Sweep>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT txid, address, amount, swept_at
+        FROM sweeps
+        ORDER BY id ASC
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| -> Result<Sweep> {
+                Ok(Sweep {
+                    txid: row.txid.parse()?,
+                    address: row.address.parse()?,
+                    amount: bitcoin::Amount::from_sat(u64::try_from(row.amount)?),
+                    swept_at: OffsetDateTime::from_unix_timestamp(row.swept_at)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_xmr_refund_sweep(
+        &self,
+        swap_id: Uuid,
+        sweep: monero::wallet::Sweep,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let txid = sweep.txid.to_string();
+        let address = sweep.address.to_string();
+        let amount = i64::try_from(sweep.amount.as_piconero())?;
+        let fee = i64::try_from(sweep.fee.as_piconero())?;
+
+        sqlx::query!(
+            r#"
+        insert into xmr_refund_sweeps (
+            swap_id,
+            txid,
+            address,
+            amount,
+            fee
+            ) values (?, ?, ?, ?, ?);
+        "#,
+            swap_id,
+            txid,
+            address,
+            amount,
+            fee
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_xmr_refund_sweeps(&self, swap_id: Uuid) -> Result<Vec<monero::wallet::Sweep>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT txid, address, amount, fee
+        FROM xmr_refund_sweeps
+        WHERE swap_id = ?
+        ORDER BY id ASC
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| -> Result<monero::wallet::Sweep> {
+                Ok(monero::wallet::Sweep {
+                    txid: monero::TxHash(row.txid),
+                    address: row.address.parse()?,
+                    amount: monero::Amount::from_piconero(u64::try_from(row.amount)?),
+                    fee: monero::Amount::from_piconero(u64::try_from(row.fee)?),
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_outbound_message(
+        &self,
+        peer_id: PeerId,
+        message: OutboundMessage,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = message.swap_id().to_string();
+        let peer_id = peer_id.to_string();
+        let message = self.seal(serde_json::to_string(&message)?);
+
+        sqlx::query!(
+            r#"
+        insert or replace into outbound_messages (
+            swap_id,
+            peer_id,
+            message
+            ) values (?, ?, ?);
+        "#,
+            swap_id,
+            peer_id,
+            message
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_outbound_message(&self, swap_id: Uuid) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM outbound_messages
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_outbound_messages(&self) -> Result<Vec<(PeerId, OutboundMessage)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT peer_id, message
+        FROM outbound_messages
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| -> Result<(PeerId, OutboundMessage)> {
+                let peer_id = PeerId::from_str(&row.peer_id)?;
+                let message = serde_json::from_str(&self.unseal(&row.message)?)?;
+
+                Ok((peer_id, message))
+            })
+            .collect()
+    }
+
+    async fn insert_address_book_entry(&self, entry: AddressBookEntry) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let label = entry.label;
+        let kind = entry.address.kind().to_string();
+        let address = entry.address.to_string();
+
+        sqlx::query!(
+            r#"
+        insert or replace into address_book (
+            label,
+            kind,
+            address
+            ) values (?, ?, ?);
+        "#,
+            label,
+            kind,
+            address
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_address_book_entries(&self, label: &str) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+
+        let result = sqlx::query!(
+            r#"
+        DELETE FROM address_book
+        WHERE label = ?
+        "#,
+            label
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_address_book(&self) -> Result<Vec<AddressBookEntry>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT label, kind, address
+        FROM address_book
+        ORDER BY label ASC, kind ASC
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| -> Result<AddressBookEntry> {
+                let address = match row.kind.parse()? {
+                    AddressKind::Bitcoin => BookAddress::Bitcoin(row.address.parse()?),
+                    AddressKind::Monero => BookAddress::Monero(row.address.parse()?),
+                };
+
+                Ok(AddressBookEntry {
+                    label: row.label,
+                    address,
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_identity_rotation(&self, rotation: IdentityRotation) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let generation = i64::from(rotation.generation);
+        let rotated_at = rotation.rotated_at.unix_timestamp();
+        let overlap_until = rotation.overlap_until.unix_timestamp();
+        let previous_listen = addresses_to_json(&rotation.previous_listen)?;
+        let previous_external_addresses = addresses_to_json(&rotation.previous_external_addresses)?;
+
+        sqlx::query!(
+            r#"
+        insert into identity_rotations (
+            generation,
+            rotated_at,
+            overlap_until,
+            previous_listen,
+            previous_external_addresses
+            ) values (?, ?, ?, ?, ?);
+        "#,
+            generation,
+            rotated_at,
+            overlap_until,
+            previous_listen,
+            previous_external_addresses
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_identity_rotations(&self) -> Result<Vec<IdentityRotation>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT generation, rotated_at, overlap_until, previous_listen, previous_external_addresses
+        FROM identity_rotations
+        ORDER BY generation ASC
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| -> Result<IdentityRotation> {
+                Ok(IdentityRotation {
+                    generation: u32::try_from(row.generation)?,
+                    rotated_at: OffsetDateTime::from_unix_timestamp(row.rotated_at)?,
+                    overlap_until: OffsetDateTime::from_unix_timestamp(row.overlap_until)?,
+                    previous_listen: addresses_from_json(&row.previous_listen)?,
+                    previous_external_addresses: addresses_from_json(
+                        &row.previous_external_addresses,
+                    )?,
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_swap_identity(&self, swap_id: Uuid, generation: u32) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let generation = i64::from(generation);
+
+        sqlx::query!(
+            r#"
+        insert or replace into swap_identities (
+            swap_id,
+            generation
+            ) values (?, ?);
+        "#,
+            swap_id,
+            generation
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_swap_identity(&self, swap_id: Uuid) -> Result<u32> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT generation
+        FROM swap_identities
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        match row {
+            Some(row) => Ok(u32::try_from(row.generation)?),
+            None => Ok(0),
+        }
+    }
+
+    async fn insert_receipt(&self, swap_id: Uuid, receipt: SignedReceipt) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let receipt = serde_json::to_string(&receipt)?;
+
+        sqlx::query!(
+            r#"
+        insert into receipts (
+            swap_id,
+            receipt
+            ) values (?, ?);
+        "#,
+            swap_id,
+            receipt
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_receipts(&self, swap_id: Uuid) -> Result<Vec<SignedReceipt>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT receipt
+        FROM receipts
+        WHERE swap_id = ?
+        ORDER BY id ASC
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(serde_json::from_str(&row.receipt)?))
+            .collect()
+    }
+}
+
+fn addresses_to_json(addresses: &[Multiaddr]) -> Result<String> {
+    let addresses = addresses
+        .iter()
+        .map(|address| address.to_string())
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::to_string(&addresses)?)
+}
+
+fn addresses_from_json(json: &str) -> Result<Vec<Multiaddr>> {
+    serde_json::from_str::<Vec<String>>(json)?
+        .iter()
+        .map(|address| Ok(Multiaddr::from_str(address)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::Passphrase;
+    use crate::network::transfer_proof;
+    use crate::protocol::alice::AliceState;
+    use crate::protocol::bob::BobState;
+    use crate::{bitcoin, monero};
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_insert_and_load_state() {
+        let db = setup_test_db().await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        let swap_id_1 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, state_1).await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+
+        db.insert_latest_state(swap_id_1, state_1.clone())
+            .await
+            .unwrap();
+
+        let state_1_loaded = db.get_state(swap_id_1).await.unwrap();
+
+        assert_eq!(state_1, state_1_loaded);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_all_latest_states() {
+        let db = setup_test_db().await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        let state_2 = State::Alice(AliceState::BtcPunished);
+        let state_3 = State::Alice(AliceState::SafelyAborted);
+        let state_4 = State::Bob(BobState::SafelyAborted);
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, state_1.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, state_2.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, state_3.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_2, state_4.clone())
+            .await
+            .unwrap();
+
+        let latest_loaded = db.all().await.unwrap();
+
+        assert_eq!(latest_loaded.len(), 2);
+
+        assert!(latest_loaded.contains(&(swap_id_1, state_3)));
+        assert!(latest_loaded.contains(&(swap_id_2, state_4)));
+
+        assert!(!latest_loaded.contains(&(swap_id_1, state_1)));
+        assert!(!latest_loaded.contains(&(swap_id_1, state_2)));
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_all_states_of_swap_in_order() {
+        let db = setup_test_db().await.unwrap();
+
+        let state_1 = State::Bob(BobState::SafelyAborted);
+        let state_2 = State::Alice(AliceState::BtcRedeemed);
+        let state_3 = State::Alice(AliceState::BtcPunished);
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, state_2.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_2, state_1.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, state_3.clone())
+            .await
+            .unwrap();
+
+        let states = db
+            .get_states(swap_id_1)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, state)| state)
+            .collect::<Vec<_>>();
+
+        assert_eq!(states, vec![state_2, state_3]);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_states_recorded_after_id() {
+        let db = setup_test_db().await.unwrap();
+
+        assert_eq!(db.get_latest_state_id().await.unwrap(), None);
+
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, State::Alice(AliceState::SafelyAborted))
+            .await
+            .unwrap();
+        let first_id = db.get_latest_state_id().await.unwrap().unwrap();
+
+        db.insert_latest_state(swap_id_2, State::Bob(BobState::SafelyAborted))
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, State::Alice(AliceState::BtcPunished))
+            .await
+            .unwrap();
+
+        let states = db
+            .get_states_after(first_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|recorded| (recorded.swap_id, recorded.state))
+            .collect::<Vec<_>>();
+
+        assert_eq!(states, vec![
+            (swap_id_2, State::Bob(BobState::SafelyAborted)),
+            (swap_id_1, State::Alice(AliceState::BtcPunished))
+        ]);
+        assert!(db
+            .get_states_after(db.get_latest_state_id().await.unwrap().unwrap())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_insert_load_monero_address() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let monero_address = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?;
+
+        db.insert_monero_address(swap_id, monero_address).await?;
+
+        let loaded_monero_address = db.get_monero_address(swap_id).await?;
+
+        assert_eq!(monero_address, loaded_monero_address);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_multiaddr() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
+        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+
+        db.insert_address(peer_id, multiaddr1.clone()).await?;
+        db.insert_address(peer_id, multiaddr2.clone()).await?;
+
+        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+
+        assert!(loaded_multiaddr.contains(&multiaddr1));
+        assert!(loaded_multiaddr.contains(&multiaddr2));
+        assert_eq!(loaded_multiaddr.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_peer_id() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
+        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+
+        db.insert_address(peer_id, multiaddr1.clone()).await?;
+        db.insert_address(peer_id, multiaddr2.clone()).await?;
+
+        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+
+        assert!(loaded_multiaddr.contains(&multiaddr1));
+        assert!(loaded_multiaddr.contains(&multiaddr2));
+        assert_eq!(loaded_multiaddr.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_swap_setup_parameters() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        assert_eq!(db.get_swap_setup_parameters(swap_id).await?, None);
+
+        let parameters = SwapSetupParameters {
+            xmr: monero::Amount::from_piconero(1_000_000_000),
+            tx_refund_fee: bitcoin::Amount::from_sat(1_000),
+            tx_cancel_fee: bitcoin::Amount::from_sat(2_000),
+        };
+        db.insert_swap_setup_parameters(swap_id, parameters).await?;
+
+        let updated = SwapSetupParameters {
+            xmr: monero::Amount::from_piconero(2_000_000_000),
+            ..parameters
+        };
+        db.insert_swap_setup_parameters(swap_id, updated).await?;
+
+        assert_eq!(db.get_swap_setup_parameters(swap_id).await?, Some(updated));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_misbehaviours_are_loaded_since_given_time() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let now = OffsetDateTime::now_utc();
+
+        db.insert_misbehaviour(
+            peer_id,
+            Misbehaviour::Punished,
+            now - time::Duration::hours(2),
+        )
+        .await?;
+        db.insert_misbehaviour(peer_id, Misbehaviour::AbortedSwapSetup, now)
+            .await?;
+        db.insert_misbehaviour(PeerId::random(), Misbehaviour::ProtocolViolation, now)
+            .await?;
+
+        let misbehaviours = db
+            .get_misbehaviours_since(peer_id, now - time::Duration::hours(1))
+            .await?;
+
+        assert_eq!(misbehaviours, vec![Misbehaviour::AbortedSwapSetup]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_replace_and_remove_ban() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let until = OffsetDateTime::from_unix_timestamp(1_700_000_000)?;
+
+        db.insert_ban(Ban {
+            peer_id,
+            until: Some(until),
+            reason: "3 misbehaviours within 24 hours, last: punished".to_string(),
+        })
+        .await?;
+        let permanent = Ban {
+            peer_id,
+            until: None,
+            reason: "banned by operator".to_string(),
+        };
+        db.insert_ban(permanent.clone()).await?;
+
+        assert_eq!(db.get_bans().await?, vec![permanent]);
+
+        assert!(db.remove_ban(peer_id).await?);
+        assert!(!db.remove_ban(peer_id).await?);
+        assert!(db.get_bans().await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sweeps_are_loaded_in_order() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let address: bitcoin::Address = "bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw".parse()?;
+        let first = Sweep {
+            txid: "0101010101010101010101010101010101010101010101010101010101010101".parse()?,
+            address: address.clone(),
+            amount: bitcoin::Amount::from_sat(1_000_000),
+            swept_at: OffsetDateTime::from_unix_timestamp(1_700_000_000)?,
+        };
+        let second = Sweep {
+            txid: "0202020202020202020202020202020202020202020202020202020202020202".parse()?,
+            address,
+            amount: bitcoin::Amount::from_sat(2_000_000),
+            swept_at: OffsetDateTime::from_unix_timestamp(1_700_086_400)?,
+        };
+
+        db.insert_sweep(first.clone()).await?;
+        db.insert_sweep(second.clone()).await?;
+
+        assert_eq!(db.get_sweeps().await?, vec![first, second]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xmr_refund_sweeps_are_loaded_per_swap() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let sweep = monero::wallet::Sweep {
+            txid: monero::TxHash(
+                "0101010101010101010101010101010101010101010101010101010101010101".to_string(),
+            ),
+            address: "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?,
+            amount: monero::Amount::from_piconero(999_000_000),
+            fee: monero::Amount::from_piconero(1_000_000),
+        };
+
+        db.insert_xmr_refund_sweep(swap_id, sweep.clone()).await?;
+
+        assert_eq!(db.get_xmr_refund_sweeps(swap_id).await?, vec![sweep]);
+        assert!(db.get_xmr_refund_sweeps(Uuid::new_v4()).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_identity_rotations_and_swap_identities() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let rotation = IdentityRotation {
+            generation: 1,
+            rotated_at: OffsetDateTime::from_unix_timestamp(1_700_000_000)?,
+            overlap_until: OffsetDateTime::from_unix_timestamp(1_700_259_200)?,
+            previous_listen: vec!["/ip4/0.0.0.0/tcp/9939".parse()?],
+            previous_external_addresses: vec![
+                "/dns4/example.com/tcp/9939".parse()?,
+                "/ip4/1.2.3.4/tcp/9939".parse()?,
+            ],
+        };
+        db.insert_identity_rotation(rotation.clone()).await?;
+        assert_eq!(db.get_identity_rotations().await?, vec![rotation.clone()]);
+        assert!(db.insert_identity_rotation(rotation).await.is_err());
+
+        let swap_id = Uuid::new_v4();
+        assert_eq!(db.get_swap_identity(swap_id).await?, 0);
+        db.insert_swap_identity(swap_id, 1).await?;
+        assert_eq!(db.get_swap_identity(swap_id).await?, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receipts_are_loaded_per_swap() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let receipt = |signature: u8| SignedReceipt {
+            receipt: vec![1, 2, 3],
+            public_key: vec![4; 32],
+            signature: vec![signature; 64],
+        };
+
+        db.insert_receipt(swap_id, receipt(1)).await?;
+        db.insert_receipt(swap_id, receipt(2)).await?;
+
+        assert_eq!(db.get_receipts(swap_id).await?, vec![
+            receipt(1),
+            receipt(2)
+        ]);
+        assert!(db.get_receipts(Uuid::new_v4()).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outbound_messages_are_kept_until_removed() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let swap_id = Uuid::new_v4();
+        let transfer_proof = |tx_hash: &str| {
+            OutboundMessage::TransferProof(transfer_proof::Request {
+                swap_id,
+                tx_lock_proof: monero::TransferProof::new(
+                    monero::TxHash(tx_hash.to_string()),
+                    monero::PrivateKey::from_scalar(
+                        monero::Scalar::random(&mut rand::thread_rng()),
+                    ),
+                ),
+            })
+        };
+
+        db.insert_outbound_message(peer_id, transfer_proof("first"))
+            .await?;
+        db.insert_outbound_message(peer_id, transfer_proof("second"))
+            .await?;
+
+        let messages = db.get_outbound_messages().await?;
+        assert_eq!(messages.len(), 1);
+        let (loaded_peer_id, message) = &messages[0];
+        assert_eq!(*loaded_peer_id, peer_id);
+        match message {
+            OutboundMessage::TransferProof(request) => {
+                assert_eq!(request.swap_id, swap_id);
+                assert_eq!(request.tx_lock_proof.tx_hash().0, "second");
+            }
+            OutboundMessage::EncryptedSignature(_) => panic!("expected transfer proof"),
+        }
+
+        db.remove_outbound_message(swap_id).await?;
+        assert!(db.get_outbound_messages().await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_address_book_entries_are_replaced_and_removed() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let entry = |label: &str, address: &str| -> Result<AddressBookEntry> {
+            AddressBookEntry::new(label.to_string(), address.parse()?)
+        };
+        let bitcoin = entry("me", "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv")?;
+        let monero = entry("me", "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a")?;
+        let other_bitcoin = entry("me", "tb1q958vfh3wkdp232pktq8zzvmttyxeqnj80zkz3v")?;
+        let savings = entry("savings", "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv")?;
+
+        db.insert_address_book_entry(savings.clone()).await?;
+        db.insert_address_book_entry(bitcoin).await?;
+        db.insert_address_book_entry(monero.clone()).await?;
+        db.insert_address_book_entry(other_bitcoin.clone()).await?;
+
+        assert_eq!(db.get_address_book().await?, vec![
+            other_bitcoin,
+            monero,
+            savings.clone()
+        ]);
+
+        assert!(db.remove_address_book_entries("me").await?);
+        assert!(!db.remove_address_book_entries("me").await?);
+        assert_eq!(db.get_address_book().await?, vec![savings]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_states_are_encrypted_including_existing_ones() -> Result<()> {
+        let temp_db = tempdir().unwrap().into_path().join("tempdb");
+        File::create(temp_db.clone()).unwrap();
+        let cipher = Cipher::new(Passphrase::new("passphrase".to_string())?)?;
+
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+        let plaintext = SqliteDatabase::open(&temp_db, None).await?;
+        plaintext
+            .insert_latest_state(swap_id_1, State::Bob(BobState::SafelyAborted))
+            .await?;
+        drop(plaintext);
+
+        let encrypted = SqliteDatabase::open(&temp_db, Some(cipher)).await?;
+        encrypted
+            .insert_latest_state(swap_id_2, State::Alice(AliceState::BtcPunished))
+            .await?;
+
+        assert_eq!(
+            encrypted.get_state(swap_id_1).await?,
+            State::Bob(BobState::SafelyAborted)
+        );
+        assert_eq!(
+            encrypted.get_state(swap_id_2).await?,
+            State::Alice(AliceState::BtcPunished)
+        );
+        drop(encrypted);
+
+        let without_passphrase = SqliteDatabase::open(&temp_db, None).await?;
+        assert!(without_passphrase.get_state(swap_id_1).await.is_err());
+        assert!(without_passphrase.get_state(swap_id_2).await.is_err());
 
         Ok(())
     }
@@ -376,7 +1565,7 @@ mod tests {
         // file has to exist in order to connect with sqlite
         File::create(temp_db.clone()).unwrap();
 
-        let db = SqliteDatabase::open(temp_db).await?;
+        let db = SqliteDatabase::open(temp_db, None).await?;
 
         Ok(db)
     }
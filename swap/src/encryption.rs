@@ -0,0 +1,234 @@
+//! Encrypts the seed and the secrets in the database with a passphrase.
+//!
+//! The key is derived from the passphrase with argon2. Every encrypted value
+//! carries the salt of its key and a random nonce, so values can be decrypted
+//! independently of each other and of the process that encrypted them.
+
+use crate::seed::Seed;
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Password;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+type Salt = [u8; SALT_LENGTH];
+
+#[derive(Clone, PartialEq)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: String) -> Result<Self> {
+        if passphrase.is_empty() {
+            bail!("The passphrase must not be empty")
+        }
+
+        Ok(Self(passphrase))
+    }
+
+    /// Reads the passphrase from the first line of the file.
+    ///
+    /// `/dev/stdin` or `/dev/fd/<n>` can be given to pass the passphrase
+    /// through a pipe instead of a file on disk.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read passphrase from {}", path.display()))?;
+        let passphrase = contents.lines().next().unwrap_or_default();
+
+        Self::new(passphrase.to_string())
+    }
+
+    /// Asks for the passphrase on the terminal, twice if it is a new one.
+    pub fn prompt(new: bool) -> Result<Self> {
+        Self::prompt_with("Enter the passphrase of the seed and the database", new)
+    }
+
+    /// Asks for a passphrase other than the one of the seed and the database.
+    pub fn prompt_with(text: &str, new: bool) -> Result<Self> {
+        let theme = ColorfulTheme::default();
+        let mut prompt = Password::with_theme(&theme);
+        prompt.with_prompt(text);
+
+        if new {
+            prompt.with_confirmation("Repeat the passphrase", "The passphrases do not match");
+        }
+
+        Self::new(prompt.interact()?)
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Passphrase([*****])")
+    }
+}
+
+/// Where the passphrase is read from, if it is not given in the environment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PassphraseSource {
+    File(PathBuf),
+    /// Ask for a new passphrase on the terminal.
+    Prompt,
+}
+
+/// Unlocks the encryption of the seed and the database in the data directory.
+///
+/// The passphrase is read from the given source, or else from the environment
+/// variable. If neither is given, the passphrase is only asked for if the seed
+/// is encrypted already. Returns `None` if nothing is to be encrypted.
+pub fn unlock(
+    data_dir: &Path,
+    source: Option<PassphraseSource>,
+    env_var: &str,
+) -> Result<Option<Cipher>> {
+    let passphrase = match source {
+        Some(PassphraseSource::File(path)) => Passphrase::from_file(&path)?,
+        Some(PassphraseSource::Prompt) => Passphrase::prompt(true)?,
+        None => match std::env::var(env_var) {
+            Ok(passphrase) => Passphrase::new(passphrase)?,
+            Err(_) if Seed::is_encrypted(data_dir)? => Passphrase::prompt(false)?,
+            Err(_) => return Ok(None),
+        },
+    };
+
+    Ok(Some(Cipher::new(passphrase)?))
+}
+
+/// Encrypts and decrypts values with the key derived from a passphrase.
+#[derive(Clone)]
+pub struct Cipher {
+    passphrase: Passphrase,
+    salt: Salt,
+    key: [u8; KEY_LENGTH],
+    /// The keys of values that were encrypted with a different salt.
+    other_keys: Arc<Mutex<HashMap<Salt, [u8; KEY_LENGTH]>>>,
+}
+
+impl Cipher {
+    pub fn new(passphrase: Passphrase) -> Result<Self> {
+        let mut salt = [0u8; SALT_LENGTH];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        let key = derive_key(&passphrase, &salt)?;
+
+        Ok(Self {
+            passphrase,
+            salt,
+            key,
+            other_keys: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .expect("encrypting a value in memory does not fail");
+
+        [&self.salt[..], &nonce[..], &ciphertext[..]].concat()
+    }
+
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+        if encrypted.len() < SALT_LENGTH + NONCE_LENGTH {
+            bail!("Encrypted value is too short")
+        }
+
+        let (salt, rest) = encrypted.split_at(SALT_LENGTH);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+        let key = self.key_for(salt)?;
+
+        XChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                anyhow!("Failed to decrypt, the passphrase is wrong or the data is corrupted")
+            })
+    }
+
+    fn key_for(&self, salt: &[u8]) -> Result<[u8; KEY_LENGTH]> {
+        if salt == self.salt {
+            return Ok(self.key);
+        }
+
+        let mut salt_array = [0u8; SALT_LENGTH];
+        salt_array.copy_from_slice(salt);
+
+        let mut other_keys = self
+            .other_keys
+            .lock()
+            .map_err(|_| anyhow!("Key cache is poisoned"))?;
+        if let Some(key) = other_keys.get(&salt_array) {
+            return Ok(*key);
+        }
+
+        let key = derive_key(&self.passphrase, &salt_array)?;
+        other_keys.insert(salt_array, key);
+
+        Ok(key)
+    }
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cipher([*****])")
+    }
+}
+
+fn derive_key(passphrase: &Passphrase, salt: &Salt) -> Result<[u8; KEY_LENGTH]> {
+    let mut key = [0u8; KEY_LENGTH];
+    Argon2::default()
+        .hash_password_into(passphrase.0.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive key from passphrase: {}", e))?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(passphrase: &str) -> Cipher {
+        Cipher::new(Passphrase::new(passphrase.to_string()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn encrypted_value_can_be_decrypted() {
+        let cipher = cipher("correct horse battery staple");
+
+        let encrypted = cipher.encrypt(b"secret");
+
+        assert_ne!(&encrypted[SALT_LENGTH + NONCE_LENGTH..], b"secret");
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn value_encrypted_by_other_process_can_be_decrypted_with_same_passphrase() {
+        let encrypted = cipher("correct horse battery staple").encrypt(b"secret");
+
+        let decrypted = cipher("correct horse battery staple").decrypt(&encrypted);
+
+        assert_eq!(decrypted.unwrap(), b"secret");
+    }
+
+    #[test]
+    fn value_cannot_be_decrypted_with_wrong_passphrase() {
+        let encrypted = cipher("correct horse battery staple").encrypt(b"secret");
+
+        assert!(cipher("wrong").decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn empty_passphrase_is_rejected() {
+        assert!(Passphrase::new(String::new()).is_err());
+    }
+}
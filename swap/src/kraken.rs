@@ -1,119 +1,44 @@
-use anyhow::{anyhow, Context, Result};
+use crate::price_feed::{PriceFeed, PriceUpdate};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
 use futures::{SinkExt, StreamExt, TryStreamExt};
 use serde::Deserialize;
-use std::convert::{Infallible, TryFrom};
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::watch;
+use std::convert::TryFrom;
 use url::Url;
 
-/// Connect to Kraken websocket API for a constant stream of rate updates.
-///
-/// If the connection fails, it will automatically be re-established.
+/// Price feed backed by the Kraken websocket API.
 ///
 /// price_ticker_ws_url must point to a websocket server that follows the kraken
 /// price ticker protocol
 /// See: https://docs.kraken.com/websockets/
-pub fn connect(price_ticker_ws_url: Url) -> Result<PriceUpdates> {
-    let (price_update, price_update_receiver) = watch::channel(Err(Error::NotYetAvailable));
-    let price_update = Arc::new(price_update);
-
-    tokio::spawn(async move {
-        // The default backoff config is fine for us apart from one thing:
-        // `max_elapsed_time`. If we don't get an error within this timeframe,
-        // backoff won't actually retry the operation.
-        let backoff = backoff::ExponentialBackoff {
-            max_elapsed_time: None,
-            ..backoff::ExponentialBackoff::default()
-        };
-
-        let result = backoff::future::retry_notify::<Infallible, _, _, _, _, _>(
-            backoff,
-            || {
-                let price_update = price_update.clone();
-                let price_ticker_ws_url = price_ticker_ws_url.clone();
-                async move {
-                    let mut stream = connection::new(price_ticker_ws_url).await?;
-
-                    while let Some(update) = stream.try_next().await.map_err(to_backoff)? {
-                        let send_result = price_update.send(Ok(update));
-
-                        if send_result.is_err() {
-                            return Err(backoff::Error::Permanent(anyhow!(
-                                "receiver disconnected"
-                            )));
-                        }
-                    }
-
-                    Err(backoff::Error::Transient(anyhow!("stream ended")))
-                }
-            },
-            |error, next: Duration| {
-                tracing::info!(
-                    "Kraken websocket connection failed, retrying in {}ms. Error {:#}",
-                    next.as_millis(),
-                    error
-                );
-            },
-        )
-        .await;
-
-        match result {
-            Err(e) => {
-                tracing::warn!("Rate updates incurred an unrecoverable error: {:#}", e);
-
-                // in case the retries fail permanently, let the subscribers know
-                price_update.send(Err(Error::PermanentFailure))
-            }
-            Ok(never) => match never {},
-        }
-    });
-
-    Ok(PriceUpdates {
-        inner: price_update_receiver,
-    })
-}
-
 #[derive(Clone, Debug)]
-pub struct PriceUpdates {
-    inner: watch::Receiver<PriceUpdate>,
+pub struct Kraken {
+    price_ticker_ws_url: Url,
 }
 
-impl PriceUpdates {
-    pub async fn wait_for_next_update(&mut self) -> Result<PriceUpdate> {
-        self.inner.changed().await?;
-
-        Ok(self.inner.borrow().clone())
-    }
-
-    pub fn latest_update(&mut self) -> PriceUpdate {
-        self.inner.borrow().clone()
+impl Kraken {
+    pub fn new(price_ticker_ws_url: Url) -> Self {
+        Self {
+            price_ticker_ws_url,
+        }
     }
 }
 
-#[derive(Clone, Debug, thiserror::Error)]
-pub enum Error {
-    #[error("Rate is not yet available")]
-    NotYetAvailable,
-    #[error("Permanently failed to retrieve rate from Kraken")]
-    PermanentFailure,
-}
-
-type PriceUpdate = Result<wire::PriceUpdate, Error>;
-
-/// Maps a [`connection::Error`] to a backoff error, effectively defining our
-/// retry strategy.
-fn to_backoff(e: connection::Error) -> backoff::Error<anyhow::Error> {
-    use backoff::Error::*;
+#[async_trait]
+impl PriceFeed for Kraken {
+    fn name(&self) -> String {
+        "Kraken".to_string()
+    }
 
-    match e {
-        // Connection closures and websocket errors will be retried
-        connection::Error::ConnectionClosed => Transient(anyhow::Error::from(e)),
-        connection::Error::WebSocket(_) => Transient(anyhow::Error::from(e)),
+    async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+        let stream = connection::new(self.price_ticker_ws_url.clone())
+            .await?
+            .map_ok(|update| update.map(|update| PriceUpdate { ask: update.ask }))
+            .err_into()
+            .boxed();
 
-        // Failures while parsing a message are permanent because they most likely present a
-        // programmer error
-        connection::Error::Parse(_) => Permanent(anyhow::Error::from(e)),
+        Ok(stream)
     }
 }
 
@@ -129,7 +54,12 @@ mod connection {
     use futures::stream::BoxStream;
     use tokio_tungstenite::tungstenite;
 
-    pub async fn new(ws_url: Url) -> Result<BoxStream<'static, Result<wire::PriceUpdate, Error>>> {
+    /// Connects to the ticker of the XMR/BTC pair.
+    ///
+    /// Heartbeats of the Kraken API are forwarded as `None`.
+    pub async fn new(
+        ws_url: Url,
+    ) -> Result<BoxStream<'static, Result<Option<wire::PriceUpdate>, Error>>> {
         let (mut rate_stream, _) = tokio_tungstenite::connect_async(ws_url)
             .await
             .context("Failed to connect to Kraken websocket API")?;
@@ -145,10 +75,12 @@ mod connection {
 
     /// Parse a websocket message into a [`wire::PriceUpdate`].
     ///
-    /// Messages which are not actually ticker updates are ignored and result in
-    /// `None` being returned. In the context of a [`TryStream`], these will
-    /// simply be filtered out.
-    async fn parse_message(msg: tungstenite::Message) -> Result<Option<wire::PriceUpdate>, Error> {
+    /// Heartbeats result in `Some(None)`. Messages which are neither ticker
+    /// updates nor heartbeats are ignored and result in `None` being returned.
+    /// In the context of a [`TryStream`], these will simply be filtered out.
+    async fn parse_message(
+        msg: tungstenite::Message,
+    ) -> Result<Option<Option<wire::PriceUpdate>>, Error> {
         let msg = match msg {
             tungstenite::Message::Text(msg) => msg,
             tungstenite::Message::Close(close_frame) => {
@@ -188,7 +120,7 @@ mod connection {
             Ok(wire::Event::Heartbeat) => {
                 tracing::trace!("Received heartbeat message");
 
-                return Ok(None);
+                return Ok(Some(None));
             }
             // if the message is not an event, it is a ticker update or an unknown event
             Err(_) => match serde_json::from_str::<wire::PriceUpdate>(&msg) {
@@ -200,7 +132,7 @@ mod connection {
             },
         };
 
-        Ok(Some(update))
+        Ok(Some(Some(update)))
     }
 
     #[derive(Debug, thiserror::Error)]
@@ -17,15 +17,21 @@
 )]
 
 pub mod asb;
+pub mod binance;
 pub mod bitcoin;
 pub mod cli;
 pub mod database;
+pub mod encryption;
 pub mod env;
 pub mod fs;
 pub mod kraken;
 pub mod libp2p_ext;
+pub mod logging;
 pub mod monero;
+pub mod monitor;
 pub mod network;
+pub mod notifications;
+pub mod price_feed;
 pub mod protocol;
 pub mod seed;
 pub mod tor;
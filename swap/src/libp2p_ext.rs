@@ -3,6 +3,8 @@ use libp2p::{Multiaddr, PeerId};
 
 pub trait MultiAddrExt {
     fn extract_peer_id(&self) -> Option<PeerId>;
+    /// Whether the address is reached through a circuit relay node.
+    fn is_relayed(&self) -> bool;
 }
 
 impl MultiAddrExt for Multiaddr {
@@ -12,4 +14,8 @@ impl MultiAddrExt for Multiaddr {
             _ => None,
         }
     }
+
+    fn is_relayed(&self) -> bool {
+        self.iter().any(|protocol| protocol == Protocol::P2pCircuit)
+    }
 }
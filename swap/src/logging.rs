@@ -0,0 +1,323 @@
+//! Log formats and the log files of the swaps, shared by the ASB and the CLI.
+//!
+//! A log message belongs to a swap if it has a `swap_id` field, or if one of
+//! the spans it is emitted in has one. The state machines of both roles run in
+//! a span with the `swap_id` and the `peer_id` of the swap, the messages of a
+//! swap are additionally written to a file of its own in the logs directory of
+//! the data dir. These files are rotated daily, as `swap-<id>.log.<date>`.
+
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_appender::rolling::{self, RollingFileAppender};
+use tracing_subscriber::fmt::time::{ChronoLocal, FormatTime};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+use uuid::Uuid;
+
+/// The log files of at most this many swaps are kept open, the others are
+/// reopened with their next log message.
+const MAX_OPEN_FILES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => bail!("Unknown log format {}, expected text or json", other),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// A [`Layer`] that writes the log messages of every swap to its own file.
+#[derive(Clone)]
+pub struct SwapLogFiles {
+    format: LogFormat,
+    fallback: Option<Uuid>,
+    files: Arc<Mutex<Files>>,
+}
+
+#[derive(Default)]
+struct Files {
+    dir: Option<PathBuf>,
+    open: HashMap<Uuid, RollingFileAppender>,
+}
+
+impl SwapLogFiles {
+    /// Nothing is written until the directory of the files is known, see
+    /// [`SwapLogFiles::write_to`].
+    pub fn new(format: LogFormat) -> Self {
+        Self {
+            format,
+            fallback: None,
+            files: Arc::new(Mutex::new(Files::default())),
+        }
+    }
+
+    /// Starts writing the files to the given directory.
+    ///
+    /// The ASB only knows its data dir once the config is read, at which point
+    /// logging is already set up.
+    pub fn write_to(&self, dir: impl Into<PathBuf>) {
+        if let Ok(mut files) = self.files.lock() {
+            files.dir = Some(dir.into());
+            files.open.clear();
+        }
+    }
+
+    /// Writes the log messages that don't belong to a swap to the file of the
+    /// given swap, for the CLI which runs a single swap.
+    pub fn with_fallback(self, swap_id: Uuid) -> Self {
+        Self {
+            fallback: Some(swap_id),
+            ..self
+        }
+    }
+
+    fn write(&self, swap_id: Uuid, line: String) {
+        let mut files = match self.files.lock() {
+            Ok(files) => files,
+            Err(_) => return,
+        };
+        let dir = match files.dir.clone() {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        if !files.open.contains_key(&swap_id) && files.open.len() >= MAX_OPEN_FILES {
+            files.open.clear();
+        }
+        let file = files
+            .open
+            .entry(swap_id)
+            .or_insert_with(|| rolling::daily(dir, format!("swap-{}.log", swap_id)));
+
+        // there is nowhere to report a failed write, logging it would recurse
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+impl<S> Layer<S> for SwapLogFiles
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            if let Some(fields) = extensions.get_mut::<Fields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // the fields of inner spans shadow the ones of outer spans
+        let mut span_fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<Fields>() {
+                    span_fields.extend(fields.0.clone());
+                }
+            }
+        }
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let swap_id = fields
+            .0
+            .get("swap_id")
+            .or_else(|| span_fields.get("swap_id"))
+            .and_then(Value::as_str)
+            .and_then(|swap_id| Uuid::from_str(swap_id).ok())
+            .or(self.fallback);
+        let swap_id = match swap_id {
+            Some(swap_id) => swap_id,
+            None => return,
+        };
+
+        let mut timestamp = String::new();
+        let _ = ChronoLocal::with_format("%F %T".to_owned()).format_time(&mut timestamp);
+        let level = event.metadata().level().to_string();
+
+        let line = match self.format {
+            LogFormat::Text => text_line(&timestamp, &level, &span_fields, fields.0),
+            LogFormat::Json => serde_json::json!({
+                "timestamp": timestamp,
+                "level": level,
+                "fields": fields.0,
+                "span": span_fields,
+            })
+            .to_string(),
+        };
+
+        self.write(swap_id, line);
+    }
+}
+
+fn text_line(
+    timestamp: &str,
+    level: &str,
+    span_fields: &Map<String, Value>,
+    mut fields: Map<String, Value>,
+) -> String {
+    let mut line = format!("{} {:>5}", timestamp, level);
+    for (name, value) in span_fields {
+        line.push_str(&format!(" {}={}", name, display(value)));
+    }
+    if !span_fields.is_empty() {
+        line.push(':');
+    }
+    if let Some(message) = fields.remove("message") {
+        line.push_str(&format!(" {}", display(&message)));
+    }
+    for (name, value) in fields {
+        line.push_str(&format!(" {}={}", name, display(&value)));
+    }
+
+    line
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// The fields of a span or an event.
+#[derive(Default)]
+struct Fields(Map<String, Value>);
+
+impl Visit for Fields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{:?}", value).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    fn read_logs(dir: &Path, swap_id: Uuid) -> String {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with(&format!("swap-{}.log", swap_id))
+            })
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn log_messages_are_written_to_the_file_of_their_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        let swap_logs = SwapLogFiles::new(LogFormat::Text);
+        swap_logs.write_to(dir.path());
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+        tracing::subscriber::with_default(Registry::default().with(swap_logs), || {
+            let span = tracing::info_span!("swap", swap_id = %first, peer_id = "peer");
+            span.in_scope(|| tracing::info!(state = "started", "Advancing state"));
+            tracing::info!(swap_id = %second, "Received transfer proof");
+            tracing::info!("Not about a swap");
+        });
+
+        let first_logs = read_logs(dir.path(), first);
+        assert!(first_logs.contains(&format!("swap_id={}: Advancing state state=started", first)));
+        assert!(first_logs.contains("peer_id=peer"));
+        assert!(read_logs(dir.path(), second).contains("Received transfer proof"));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn log_messages_without_swap_go_to_the_fallback_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        let swap_id = Uuid::new_v4();
+        let swap_logs = SwapLogFiles::new(LogFormat::Json).with_fallback(swap_id);
+        swap_logs.write_to(dir.path());
+
+        tracing::subscriber::with_default(Registry::default().with(swap_logs), || {
+            tracing::info!(address = "bc1q", "Waiting for Bitcoin deposit");
+        });
+
+        let line = read_logs(dir.path(), swap_id);
+        let json = serde_json::from_str::<Value>(line.trim()).unwrap();
+        assert_eq!(json["fields"]["message"], "Waiting for Bitcoin deposit");
+        assert_eq!(json["fields"]["address"], "bc1q");
+        assert_eq!(json["level"], "INFO");
+    }
+
+    #[test]
+    fn parses_log_formats() {
+        assert_eq!(LogFormat::from_str("json").unwrap(), LogFormat::Json);
+        assert_eq!(LogFormat::from_str("text").unwrap(), LogFormat::Text);
+        assert!(LogFormat::from_str("yaml").is_err());
+    }
+}
@@ -0,0 +1,584 @@
+//! Watching for the Monero lock transfer without monero-wallet-rpc.
+//!
+//! The light wallet talks to monerod directly and verifies the lock transfer
+//! the same way `check_tx_key` of monero-wallet-rpc does: the transaction key
+//! Alice sends in the transfer proof together with the public keys of the
+//! shared address is enough to find the outputs of the transaction that pay to
+//! the shared address and to decrypt their amounts. A decrypted amount only
+//! counts if it opens the Pedersen commitment of its output, because the
+//! encrypted amount is not covered by consensus and could claim anything.
+//!
+//! The light wallet cannot construct Monero transactions, hence it cannot
+//! sweep the redeemed Monero to the receive address. Instead, the keys of the
+//! redeemed wallet are handed to the user, see
+//! [`MoneroWallet::Light`](crate::protocol::bob::MoneroWallet::Light).
+//!
+//! The lock transfer can additionally be cross-checked against other daemons,
+//! see [`MonerodQuorum`].
+
+use crate::env::Config;
+use crate::monero::quorum::MonerodQuorum;
+use crate::monero::wallet::WatchRequest;
+use crate::monero::{Amount, InsufficientFunds, PrivateKey, PublicKey, Scalar, TransferProof};
+use ::monero::consensus::encode::{serialize, VarInt};
+use ::monero::cryptonote::hash::keccak_256;
+use anyhow::{bail, Context, Result};
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use monero_rpc::monerod::{self, MonerodRpc as _};
+use monero_rpc::wallet::BlockHeight;
+use serde::Deserialize;
+use std::convert::{TryFrom, TryInto};
+use std::time::Duration;
+
+/// The first RingCT type that encrypts amounts in 8 bytes instead of 32.
+const RCT_TYPE_BULLETPROOF2: u8 = 4;
+
+/// The generator `H` the amounts of Pedersen commitments are multiplied with.
+const H: CompressedEdwardsY = CompressedEdwardsY([
+    0x8b, 0x65, 0x59, 0x70, 0x15, 0x37, 0x99, 0xaf, 0x2a, 0xea, 0xdc, 0x9f, 0xf1, 0xad, 0xd0, 0xea,
+    0x6c, 0x72, 0x51, 0xd5, 0x41, 0x54, 0xcf, 0xa9, 0x2c, 0x17, 0x3a, 0x0d, 0xd3, 0x9c, 0x1f, 0x94,
+]);
+
+#[derive(Debug)]
+pub struct LightWallet {
+    client: monerod::Client,
+    sync_interval: Duration,
+    lock_verifier: Option<MonerodQuorum>,
+}
+
+impl LightWallet {
+    /// Connects to the monerod given as `<host>:<port>`.
+    pub async fn connect(daemon_address: &str, env_config: Config) -> Result<Self> {
+        let client = monerod::Client::from_daemon_address(daemon_address)?;
+
+        let block_count = client
+            .get_block_count()
+            .await
+            .with_context(|| format!("Failed to connect to monerod at {}", daemon_address))?;
+
+        tracing::debug!(%daemon_address, height = %block_count.count, "Connected to monerod");
+
+        Ok(Self {
+            client,
+            sync_interval: env_config.monero_sync_interval(),
+            lock_verifier: None,
+        })
+    }
+
+    /// Only considers the lock transfer confirmed once the quorum agrees.
+    pub fn with_lock_verifier(self, lock_verifier: MonerodQuorum) -> Self {
+        Self {
+            lock_verifier: Some(lock_verifier),
+            ..self
+        }
+    }
+
+    pub async fn block_height(&self) -> Result<BlockHeight> {
+        let block_count = self.client.get_block_count().await?;
+
+        Ok(BlockHeight {
+            height: block_count.count,
+        })
+    }
+
+    pub async fn watch_for_transfer(&self, request: WatchRequest) -> Result<(), InsufficientFunds> {
+        let WatchRequest {
+            conf_target,
+            public_view_key,
+            public_spend_key,
+            ref transfer_proof,
+            expected,
+        } = request;
+
+        let txid = transfer_proof.tx_hash();
+
+        tracing::info!(
+            %txid,
+            target_confirmations = %conf_target,
+            "Waiting for Monero transaction finality"
+        );
+
+        let mut check_interval = tokio::time::interval(self.sync_interval);
+        let mut seen_confirmations = 0u64;
+
+        while seen_confirmations < conf_target {
+            check_interval.tick().await;
+
+            let transfer = match check_transfer(
+                &self.client,
+                transfer_proof,
+                public_view_key.into(),
+                public_spend_key,
+            )
+            .await
+            {
+                Ok(Some(transfer)) => transfer,
+                Ok(None) => {
+                    tracing::debug!(%txid, "Monero lock transaction not found yet");
+                    continue;
+                }
+                Err(error) => {
+                    tracing::debug!(%txid, "Failed to check Monero lock transaction: {:#}", error);
+                    continue;
+                }
+            };
+
+            if transfer.unlock_time != 0 {
+                tracing::warn!(%txid, unlock_time = %transfer.unlock_time, "Monero lock transaction is time locked");
+                return Err(InsufficientFunds {
+                    expected,
+                    actual: Amount::ZERO,
+                });
+            }
+
+            if transfer.received != expected {
+                return Err(InsufficientFunds {
+                    expected,
+                    actual: transfer.received,
+                });
+            }
+
+            if transfer.confirmations > seen_confirmations {
+                seen_confirmations = transfer.confirmations;
+                tracing::info!(
+                    %txid,
+                    %seen_confirmations,
+                    needed_confirmations = %conf_target,
+                    "Received new confirmation for Monero lock tx"
+                );
+            }
+        }
+
+        if let Some(lock_verifier) = &self.lock_verifier {
+            lock_verifier.watch_for_transfer(&request).await?;
+        }
+
+        Ok(())
+    }
+
+    /// The confirmations of the transfer that is watched for, `None` if the
+    /// transfer is not known yet.
+    pub async fn transfer_confirmations(&self, request: &WatchRequest) -> Result<Option<u64>> {
+        if let Some(lock_verifier) = &self.lock_verifier {
+            return lock_verifier.transfer_confirmations(request).await;
+        }
+
+        let transfer = check_transfer(
+            &self.client,
+            &request.transfer_proof,
+            request.public_view_key.into(),
+            request.public_spend_key,
+        )
+        .await?;
+
+        Ok(transfer.map(|transfer| transfer.confirmations))
+    }
+
+    /// What the transfer that is watched for pays to the shared address,
+    /// `None` if monerod does not know the transfer yet.
+    pub(crate) async fn transfer(&self, request: &WatchRequest) -> Result<Option<Transfer>> {
+        check_transfer(
+            &self.client,
+            &request.transfer_proof,
+            request.public_view_key.into(),
+            request.public_spend_key,
+        )
+        .await
+    }
+}
+
+/// Returns what the transaction of the transfer proof pays to the given
+/// address and how often it is confirmed, or `None` if monerod does not know
+/// the transaction yet.
+pub(crate) async fn check_transfer(
+    client: &monerod::Client,
+    transfer_proof: &TransferProof,
+    public_view_key: PublicKey,
+    public_spend_key: PublicKey,
+) -> Result<Option<Transfer>> {
+    let txid = transfer_proof.tx_hash().0;
+
+    let response = client.get_transactions(vec![txid.clone()]).await?;
+    let entry = match response.txs.into_iter().find(|tx| tx.tx_hash == txid) {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let tx = serde_json::from_str::<DecodedTransaction>(&entry.as_json)
+        .context("Failed to parse transaction decoded by monerod")?;
+    let received = tx.received(transfer_proof.tx_key(), public_view_key, public_spend_key)?;
+
+    let confirmations = if entry.in_pool {
+        0
+    } else {
+        let block_count = client.get_block_count().await?;
+        u64::from(block_count.count).saturating_sub(entry.block_height)
+    };
+
+    Ok(Some(Transfer {
+        received,
+        confirmations,
+        unlock_time: tx.unlock_time,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Transfer {
+    pub received: Amount,
+    pub confirmations: u64,
+    /// The block height or time before which the outputs cannot be spent,
+    /// 0 if they can be spent right away.
+    pub unlock_time: u64,
+}
+
+/// The parts of a transaction decoded as JSON by monerod that are needed to
+/// find our outputs.
+#[derive(Debug, Deserialize)]
+struct DecodedTransaction {
+    #[serde(default)]
+    unlock_time: u64,
+    vout: Vec<Output>,
+    rct_signatures: RctSignatures,
+}
+
+#[derive(Debug, Deserialize)]
+struct Output {
+    target: OutputTarget,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OutputTarget {
+    Key(String),
+    TaggedKey { key: String },
+}
+
+impl OutputTarget {
+    fn key(&self) -> &str {
+        match self {
+            OutputTarget::Key(key) | OutputTarget::TaggedKey { key } => key,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RctSignatures {
+    #[serde(rename = "type")]
+    rct_type: u8,
+    #[serde(rename = "ecdhInfo", default)]
+    ecdh_info: Vec<EcdhInfo>,
+    /// The Pedersen commitments to the amounts of the outputs.
+    #[serde(rename = "outPk", default)]
+    out_pk: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EcdhInfo {
+    amount: String,
+}
+
+impl DecodedTransaction {
+    /// Sums up the amounts of all outputs that pay to the address with the
+    /// given public keys. Outputs whose decrypted amount does not open their
+    /// commitment are not counted.
+    fn received(
+        &self,
+        tx_key: PrivateKey,
+        public_view_key: PublicKey,
+        public_spend_key: PublicKey,
+    ) -> Result<Amount> {
+        if self.rct_signatures.rct_type < RCT_TYPE_BULLETPROOF2 {
+            bail!(
+                "Unsupported RingCT type {}, only transactions with Bulletproofs or CLSAG are supported",
+                self.rct_signatures.rct_type
+            );
+        }
+
+        let derivation = key_derivation(tx_key, public_view_key)?;
+
+        let mut received = 0u64;
+        for (index, output) in self.vout.iter().enumerate() {
+            let shared_secret = derivation_to_scalar(&derivation, u64::try_from(index)?);
+
+            if hex::decode(output.target.key())? != output_key(&shared_secret, public_spend_key) {
+                continue;
+            }
+
+            let ecdh_info = self
+                .rct_signatures
+                .ecdh_info
+                .get(index)
+                .context("Missing encrypted amount of output")?;
+            let encrypted_amount: [u8; 8] = hex::decode(&ecdh_info.amount)?
+                .as_slice()
+                .try_into()
+                .context("Encrypted amount must be 8 bytes")?;
+
+            let amount = decrypt_amount(encrypted_amount, &shared_secret);
+
+            let commitment = self
+                .rct_signatures
+                .out_pk
+                .get(index)
+                .context("Missing commitment of output")?;
+            if hex::decode(commitment)? != commit(amount, &shared_secret)?.compress().to_bytes() {
+                tracing::warn!(
+                    %index,
+                    "Ignoring output whose encrypted amount does not match its commitment"
+                );
+                continue;
+            }
+
+            received = received
+                .checked_add(amount)
+                .context("Received amount overflows")?;
+        }
+
+        Ok(Amount::from_piconero(received))
+    }
+}
+
+/// Computes `8rV`, the key derivation shared between the sender, who knows the
+/// transaction key `r`, and the recipient, who knows the private view key of
+/// `V`.
+fn key_derivation(tx_key: PrivateKey, public_view_key: PublicKey) -> Result<[u8; 32]> {
+    let public_view_key = public_view_key
+        .point
+        .decompress()
+        .context("Public view key is not a valid point")?;
+
+    Ok((tx_key.scalar * public_view_key)
+        .mul_by_cofactor()
+        .compress()
+        .to_bytes())
+}
+
+/// Computes `H_s(8rV || index)`, the shared secret of the output at the given
+/// index.
+fn derivation_to_scalar(derivation: &[u8; 32], index: u64) -> Scalar {
+    let mut bytes = derivation.to_vec();
+    bytes.extend(serialize(&VarInt(index)));
+
+    Scalar::from_bytes_mod_order(keccak_256(&bytes))
+}
+
+/// Computes `H_s(8rV || index)G + S`, the one-time key of the output at the
+/// given index.
+fn output_key(shared_secret: &Scalar, public_spend_key: PublicKey) -> Vec<u8> {
+    let shared_secret = PublicKey {
+        point: (shared_secret * &ED25519_BASEPOINT_TABLE).compress(),
+    };
+
+    (shared_secret + public_spend_key).as_bytes().to_vec()
+}
+
+fn decrypt_amount(encrypted_amount: [u8; 8], shared_secret: &Scalar) -> u64 {
+    let mut bytes = b"amount".to_vec();
+    bytes.extend_from_slice(shared_secret.as_bytes());
+    let mask = keccak_256(&bytes);
+
+    let mut amount = encrypted_amount;
+    for (byte, mask) in amount.iter_mut().zip(mask.iter()) {
+        *byte ^= mask;
+    }
+
+    u64::from_le_bytes(amount)
+}
+
+/// Computes `H_s("commitment_mask" || shared_secret)G + amount * H`, the
+/// commitment to the amount of the output with the given shared secret.
+fn commit(amount: u64, shared_secret: &Scalar) -> Result<EdwardsPoint> {
+    let mut bytes = b"commitment_mask".to_vec();
+    bytes.extend_from_slice(shared_secret.as_bytes());
+    let mask = Scalar::from_bytes_mod_order(keccak_256(&bytes));
+
+    let h = H.decompress().context("H is a valid point")?;
+
+    Ok(&mask * &ED25519_BASEPOINT_TABLE + Scalar::from(amount) * h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Keys {
+        tx_key: PrivateKey,
+        private_view_key: PrivateKey,
+        public_view_key: PublicKey,
+        public_spend_key: PublicKey,
+    }
+
+    impl Keys {
+        fn random() -> Self {
+            let mut rng = rand::thread_rng();
+            let private_view_key = PrivateKey::from_scalar(Scalar::random(&mut rng));
+            let private_spend_key = PrivateKey::from_scalar(Scalar::random(&mut rng));
+
+            Self {
+                tx_key: PrivateKey::from_scalar(Scalar::random(&mut rng)),
+                private_view_key,
+                public_view_key: PublicKey::from_private_key(&private_view_key),
+                public_spend_key: PublicKey::from_private_key(&private_spend_key),
+            }
+        }
+
+        /// Builds the output Alice's wallet creates for the given amount.
+        fn output(&self, index: u64, amount: u64) -> (Output, EcdhInfo, String) {
+            self.forged_output(index, amount, amount)
+        }
+
+        /// Builds an output that commits to a different amount than it
+        /// encrypts.
+        fn forged_output(
+            &self,
+            index: u64,
+            committed_amount: u64,
+            encrypted_amount: u64,
+        ) -> (Output, EcdhInfo, String) {
+            let derivation = key_derivation(self.tx_key, self.public_view_key).unwrap();
+            let shared_secret = derivation_to_scalar(&derivation, index);
+
+            let output = Output {
+                target: OutputTarget::Key(hex::encode(output_key(
+                    &shared_secret,
+                    self.public_spend_key,
+                ))),
+            };
+            let ecdh_info = EcdhInfo {
+                amount: hex::encode(
+                    decrypt_amount(encrypted_amount.to_le_bytes(), &shared_secret).to_le_bytes(),
+                ),
+            };
+            let commitment = hex::encode(
+                commit(committed_amount, &shared_secret)
+                    .unwrap()
+                    .compress()
+                    .to_bytes(),
+            );
+
+            (output, ecdh_info, commitment)
+        }
+    }
+
+    fn transaction(outputs: Vec<(Output, EcdhInfo, String)>) -> DecodedTransaction {
+        let mut vout = Vec::new();
+        let mut ecdh_info = Vec::new();
+        let mut out_pk = Vec::new();
+        for (output, info, commitment) in outputs {
+            vout.push(output);
+            ecdh_info.push(info);
+            out_pk.push(commitment);
+        }
+
+        DecodedTransaction {
+            unlock_time: 0,
+            vout,
+            rct_signatures: RctSignatures {
+                rct_type: 5,
+                ecdh_info,
+                out_pk,
+            },
+        }
+    }
+
+    #[test]
+    fn sender_and_recipient_share_the_key_derivation() {
+        let keys = Keys::random();
+        let tx_public_key = PublicKey::from_private_key(&keys.tx_key);
+
+        assert_eq!(
+            key_derivation(keys.tx_key, keys.public_view_key).unwrap(),
+            key_derivation(keys.private_view_key, tx_public_key).unwrap()
+        );
+    }
+
+    #[test]
+    fn finds_output_paying_to_address_and_decrypts_its_amount() {
+        let keys = Keys::random();
+        let change = Keys::random();
+        let tx = transaction(vec![change.output(0, 5_000), keys.output(1, 1_000_000)]);
+
+        let received = tx
+            .received(keys.tx_key, keys.public_view_key, keys.public_spend_key)
+            .unwrap();
+
+        assert_eq!(received, Amount::from_piconero(1_000_000));
+    }
+
+    #[test]
+    fn given_encrypted_amount_not_matching_commitment_then_output_is_not_counted() {
+        let keys = Keys::random();
+        let tx = transaction(vec![
+            keys.forged_output(0, 1, 1_000_000),
+            keys.output(1, 5_000),
+        ]);
+
+        let received = tx
+            .received(keys.tx_key, keys.public_view_key, keys.public_spend_key)
+            .unwrap();
+
+        assert_eq!(received, Amount::from_piconero(5_000));
+    }
+
+    #[test]
+    fn given_wrong_tx_key_then_nothing_is_received() {
+        let keys = Keys::random();
+        let tx = transaction(vec![keys.output(0, 1_000_000)]);
+
+        let received = tx
+            .received(
+                Keys::random().tx_key,
+                keys.public_view_key,
+                keys.public_spend_key,
+            )
+            .unwrap();
+
+        assert_eq!(received, Amount::ZERO);
+    }
+
+    #[test]
+    fn parses_transaction_decoded_by_monerod() {
+        let json = r#"{
+            "version": 2,
+            "unlock_time": 0,
+            "vin": [],
+            "vout": [
+                {"amount": 0, "target": {"key": "9b6f7ed2a5f1c3b4ff0d4b5e1cb3ab559b0e5bfa1c4c2a1ddbb0ec8b8e3a9c0f"}},
+                {"amount": 0, "target": {"tagged_key": {"key": "1c0e0c9d3b0a4b2c6e7f8a9b0c1d2e3f405162738495a6b7c8d9e0f1a2b3c4d5", "view_tag": "3c"}}}
+            ],
+            "extra": [1],
+            "rct_signatures": {
+                "type": 5,
+                "txnFee": 31400000,
+                "ecdhInfo": [{"amount": "a1b2c3d4e5f60718"}, {"amount": "0102030405060708"}],
+                "outPk": ["5bde3ac5b2a82c5e9ba1e6b751e5c8d76e7c0e2c1d1d8f3b3c0ca8b361b0a271", "e3c4b1a7f0d9c3b2a1908f7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a"]
+            }
+        }"#;
+
+        let tx = serde_json::from_str::<DecodedTransaction>(json).unwrap();
+
+        assert_eq!(tx.unlock_time, 0);
+        assert_eq!(tx.vout.len(), 2);
+        assert_eq!(
+            tx.vout[1].target.key(),
+            "1c0e0c9d3b0a4b2c6e7f8a9b0c1d2e3f405162738495a6b7c8d9e0f1a2b3c4d5"
+        );
+        assert_eq!(tx.rct_signatures.ecdh_info[0].amount, "a1b2c3d4e5f60718");
+        assert_eq!(
+            tx.rct_signatures.out_pk[1],
+            "e3c4b1a7f0d9c3b2a1908f7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a"
+        );
+    }
+
+    #[test]
+    fn rejects_transactions_without_compact_amounts() {
+        let keys = Keys::random();
+        let mut tx = transaction(vec![keys.output(0, 1_000_000)]);
+        tx.rct_signatures.rct_type = 1;
+
+        assert!(tx
+            .received(keys.tx_key, keys.public_view_key, keys.public_spend_key)
+            .is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Cross-checks the Monero lock transfer against several monerod.
+//!
+//! A single monerod that is malicious or eclipsed can claim that the lock
+//! transaction pays the shared address or that it is confirmed. With a quorum
+//! the lock transfer is only considered confirmed once enough independent
+//! daemons agree on the amount it pays, that it is not time locked and on its
+//! confirmations. Daemons that are unreachable or don't know the transaction
+//! count as abstaining.
+
+use crate::env::Config;
+use crate::monero::light_wallet::{check_transfer, Transfer};
+use crate::monero::wallet::WatchRequest;
+use crate::monero::{Amount, InsufficientFunds};
+use anyhow::{bail, Result};
+use monero_rpc::monerod;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct MonerodQuorum {
+    daemons: Vec<(String, monerod::Client)>,
+    quorum: usize,
+    sync_interval: Duration,
+}
+
+impl MonerodQuorum {
+    /// Requires `quorum` of the daemons given as `<host>:<port>` to agree,
+    /// defaults to a majority of them.
+    pub fn new(
+        daemon_addresses: &[String],
+        quorum: Option<usize>,
+        env_config: Config,
+    ) -> Result<Self> {
+        let quorum = quorum.unwrap_or(daemon_addresses.len() / 2 + 1);
+        if quorum == 0 || quorum > daemon_addresses.len() {
+            bail!(
+                "The quorum has to be between 1 and the {} monero daemons that verify the lock transaction",
+                daemon_addresses.len()
+            )
+        }
+
+        let daemons = daemon_addresses
+            .iter()
+            .map(|address| {
+                Ok((
+                    address.clone(),
+                    monerod::Client::from_daemon_address(address)?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            daemons,
+            quorum,
+            sync_interval: env_config.monero_sync_interval(),
+        })
+    }
+
+    /// Waits until the quorum agrees that the lock transfer pays the expected
+    /// amount and has the targeted confirmations.
+    pub async fn watch_for_transfer(
+        &self,
+        request: &WatchRequest,
+    ) -> Result<(), InsufficientFunds> {
+        let txid = request.transfer_proof.tx_hash();
+
+        tracing::info!(
+            %txid,
+            daemons = %self.daemons.len(),
+            quorum = %self.quorum,
+            "Verifying Monero lock transaction with a quorum of monero daemons"
+        );
+
+        let mut check_interval = tokio::time::interval(self.sync_interval);
+        let mut seen_confirmations = 0u64;
+
+        while seen_confirmations < request.conf_target {
+            check_interval.tick().await;
+
+            match decide(
+                &self.check_transfer(request).await,
+                self.quorum,
+                request.expected,
+            ) {
+                Verdict::Confirmed { confirmations } => {
+                    if confirmations > seen_confirmations {
+                        seen_confirmations = confirmations;
+                        tracing::info!(
+                            %txid,
+                            %seen_confirmations,
+                            needed_confirmations = %request.conf_target,
+                            "Quorum of monero daemons agrees on new confirmation of Monero lock tx"
+                        );
+                    }
+                }
+                Verdict::Rejected { actual } => {
+                    return Err(InsufficientFunds {
+                        expected: request.expected,
+                        actual,
+                    })
+                }
+                Verdict::Undecided => {
+                    tracing::debug!(%txid, "Monero daemons do not agree on the lock transaction yet");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The confirmations of the transfer the quorum agrees on, `None` if the
+    /// quorum does not agree on the transfer yet.
+    pub async fn transfer_confirmations(&self, request: &WatchRequest) -> Result<Option<u64>> {
+        Ok(
+            match decide(
+                &self.check_transfer(request).await,
+                self.quorum,
+                request.expected,
+            ) {
+                Verdict::Confirmed { confirmations } => Some(confirmations),
+                Verdict::Rejected { .. } | Verdict::Undecided => None,
+            },
+        )
+    }
+
+    /// Returns the transfer as seen by every daemon that knows it.
+    async fn check_transfer(&self, request: &WatchRequest) -> Vec<Transfer> {
+        let checks = self.daemons.iter().map(|(address, client)| async move {
+            match check_transfer(
+                client,
+                &request.transfer_proof,
+                request.public_view_key.into(),
+                request.public_spend_key,
+            )
+            .await
+            {
+                Ok(transfer) => transfer,
+                Err(error) => {
+                    tracing::debug!(daemon = %address, "Failed to check Monero lock transaction: {:#}", error);
+                    None
+                }
+            }
+        });
+
+        futures::future::join_all(checks)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Verdict {
+    /// The quorum agrees that the transfer pays the expected amount and is
+    /// not time locked, with at least the given confirmations.
+    Confirmed {
+        confirmations: u64,
+    },
+    /// The quorum agrees that the transfer pays a different amount or is time
+    /// locked, which makes the locked amount unspendable.
+    Rejected {
+        actual: Amount,
+    },
+    Undecided,
+}
+
+fn decide(transfers: &[Transfer], quorum: usize, expected: Amount) -> Verdict {
+    let mut agreeing = HashMap::<_, Vec<u64>>::new();
+    for transfer in transfers {
+        agreeing
+            .entry((transfer.received, transfer.unlock_time))
+            .or_default()
+            .push(transfer.confirmations);
+    }
+
+    if let Some(confirmations) = agreeing.get_mut(&(expected, 0)) {
+        if confirmations.len() >= quorum {
+            // every daemon of the quorum has seen at least this many confirmations
+            confirmations.sort_unstable_by(|a, b| b.cmp(a));
+            return Verdict::Confirmed {
+                confirmations: confirmations[quorum - 1],
+            };
+        }
+    }
+
+    agreeing
+        .into_iter()
+        .find(|(_, confirmations)| confirmations.len() >= quorum)
+        .map_or(Verdict::Undecided, |((received, unlock_time), _)| {
+            Verdict::Rejected {
+                actual: if unlock_time == 0 {
+                    received
+                } else {
+                    Amount::ZERO
+                },
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{GetConfig, Regtest};
+
+    const EXPECTED: Amount = Amount::from_piconero(1_000_000);
+
+    fn transfer(received: Amount, confirmations: u64) -> Transfer {
+        Transfer {
+            received,
+            confirmations,
+            unlock_time: 0,
+        }
+    }
+
+    #[test]
+    fn quorum_agrees_on_the_confirmations_all_of_its_daemons_have_seen() {
+        let transfers = vec![
+            transfer(EXPECTED, 10),
+            transfer(EXPECTED, 3),
+            transfer(EXPECTED, 7),
+        ];
+
+        assert_eq!(decide(&transfers, 2, EXPECTED), Verdict::Confirmed {
+            confirmations: 7
+        });
+        assert_eq!(decide(&transfers, 3, EXPECTED), Verdict::Confirmed {
+            confirmations: 3
+        });
+    }
+
+    #[test]
+    fn single_lying_daemon_cannot_confirm_the_transfer() {
+        let transfers = vec![transfer(EXPECTED, 10)];
+
+        assert_eq!(decide(&transfers, 2, EXPECTED), Verdict::Undecided);
+    }
+
+    #[test]
+    fn quorum_rejects_transfers_that_pay_too_little_or_are_time_locked() {
+        let too_little = Amount::from_piconero(1);
+        let transfers = vec![
+            transfer(too_little, 1),
+            transfer(too_little, 1),
+            transfer(EXPECTED, 10),
+        ];
+        assert_eq!(decide(&transfers, 2, EXPECTED), Verdict::Rejected {
+            actual: too_little
+        });
+
+        let time_locked = Transfer {
+            unlock_time: 3_000_000,
+            ..transfer(EXPECTED, 10)
+        };
+        assert_eq!(
+            decide(&[time_locked, time_locked], 2, EXPECTED),
+            Verdict::Rejected {
+                actual: Amount::ZERO
+            }
+        );
+    }
+
+    #[test]
+    fn quorum_defaults_to_a_majority_of_the_daemons() {
+        let daemons = vec![
+            "node1:18081".to_string(),
+            "node2:18081".to_string(),
+            "node3:18081".to_string(),
+        ];
+
+        let quorum = MonerodQuorum::new(&daemons, None, Regtest::get_config()).unwrap();
+        assert_eq!(quorum.quorum, 2);
+
+        assert!(MonerodQuorum::new(&daemons, Some(0), Regtest::get_config()).is_err());
+        assert!(MonerodQuorum::new(&daemons, Some(4), Regtest::get_config()).is_err());
+    }
+}
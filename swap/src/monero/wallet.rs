@@ -1,10 +1,10 @@
 use crate::env::Config;
 use crate::monero::{
-    Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
+    Amount, InsufficientFunds, MonerodQuorum, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
 };
 use ::monero::{Address, Network, PrivateKey, PublicKey};
 use anyhow::{Context, Result};
-use monero_rpc::wallet::{BlockHeight, MoneroWalletRpc as _, Refreshed};
+use monero_rpc::wallet::{BlockHeight, GetAddressIndex, MoneroWalletRpc as _, Refreshed};
 use monero_rpc::{jsonrpc, wallet};
 use std::str::FromStr;
 use std::time::Duration;
@@ -19,6 +19,7 @@ pub struct Wallet {
     name: String,
     main_address: monero::Address,
     sync_interval: Duration,
+    lock_verifier: Option<MonerodQuorum>,
 }
 
 impl Wallet {
@@ -50,9 +51,19 @@ impl Wallet {
             name,
             main_address,
             sync_interval: env_config.monero_sync_interval(),
+            lock_verifier: None,
         })
     }
 
+    /// Only considers a transfer that is watched for confirmed once the quorum
+    /// agrees, in addition to the daemon of the wallet RPC.
+    pub fn with_lock_verifier(self, lock_verifier: MonerodQuorum) -> Self {
+        Self {
+            lock_verifier: Some(lock_verifier),
+            ..self
+        }
+    }
+
     /// Re-open the wallet using the internally stored name.
     pub async fn re_open(&self) -> Result<()> {
         self.inner
@@ -109,7 +120,7 @@ impl Wallet {
 
     /// Close the wallet and open (load) another wallet by generating it from
     /// keys. The generated wallet will be opened, all funds sweeped to the
-    /// main_address and then the wallet will be re-loaded using the internally
+    /// given address and then the wallet will be re-loaded using the internally
     /// stored name.
     pub async fn create_from(
         &self,
@@ -117,6 +128,7 @@ impl Wallet {
         private_spend_key: PrivateKey,
         private_view_key: PrivateViewKey,
         restore_height: BlockHeight,
+        sweep_to: Address,
     ) -> Result<()> {
         let public_spend_key = PublicKey::from_private_key(&private_spend_key);
         let public_view_key = PublicKey::from_private_key(&private_view_key.into());
@@ -144,18 +156,18 @@ impl Wallet {
 
         // Try to send all the funds from the generated wallet to the default wallet
         match wallet.refresh().await {
-            Ok(_) => match wallet.sweep_all(self.main_address.to_string()).await {
+            Ok(_) => match wallet.sweep_all(sweep_to.to_string()).await {
                 Ok(sweep_all) => {
                     for tx in sweep_all.tx_hash_list {
                         tracing::info!(
                             %tx,
-                            monero_address = %self.main_address,
+                            monero_address = %sweep_to,
                             "Monero transferred back to default wallet");
                     }
                 }
                 Err(error) => {
                     tracing::warn!(
-                        address = %self.main_address,
+                        address = %sweep_to,
                         "Failed to transfer Monero to default wallet: {:#}", error
                     );
                 }
@@ -170,7 +182,101 @@ impl Wallet {
         Ok(())
     }
 
+    /// Sweeps the wallet generated from the keys to the given address, once
+    /// all of its balance is unlocked. The fee is paid from the balance.
+    ///
+    /// The default wallet is loaded again after every attempt, also while the
+    /// balance is still locked, so other swaps can use the wallet RPC
+    /// meanwhile. Returns no sweeps if the generated wallet is empty, because
+    /// it was swept before.
+    pub async fn sweep_from_keys(
+        &self,
+        file_name: String,
+        private_spend_key: PrivateKey,
+        private_view_key: PrivateViewKey,
+        restore_height: BlockHeight,
+        sweep_to: Address,
+    ) -> Result<Vec<Sweep>> {
+        let public_spend_key = PublicKey::from_private_key(&private_spend_key);
+        let public_view_key = PublicKey::from_private_key(&private_view_key.into());
+        let address = Address::standard(self.network, public_spend_key, public_view_key);
+
+        let mut check_interval = tokio::time::interval(self.sync_interval);
+
+        loop {
+            check_interval.tick().await;
+
+            let wallet = self.inner.lock().await;
+            let attempt = try_sweep(
+                &wallet,
+                file_name.clone(),
+                address,
+                private_spend_key,
+                private_view_key,
+                restore_height,
+                sweep_to,
+            )
+            .await;
+            wallet
+                .open_wallet(self.name.clone())
+                .await
+                .with_context(|| format!("Failed to open wallet {}", self.name))?;
+            drop(wallet);
+
+            if let Some(sweeps) = attempt? {
+                return Ok(sweeps);
+            }
+        }
+    }
+
+    /// Creates a new subaddress in the primary account of the wallet.
+    ///
+    /// The label is shown next to the subaddress by other wallet software.
+    pub async fn create_subaddress(&self, label: String) -> Result<Address> {
+        let inner = self.inner.lock().await;
+
+        inner
+            .open_wallet(self.name.clone())
+            .await
+            .with_context(|| format!("Failed to open wallet {}", self.name))?;
+
+        let response = inner
+            .create_address(0, label)
+            .await
+            .context("Failed to create subaddress")?;
+        let address = Address::from_str(&response.address)?;
+
+        tracing::debug!(%address, index = %response.address_index, "Created Monero subaddress");
+
+        Ok(address)
+    }
+
     pub async fn transfer(&self, request: TransferRequest) -> Result<TransferProof> {
+        self.transfer_from_subaddress(request, None).await
+    }
+
+    /// Transfers the Monero of a swap, spending only what the main address and
+    /// the given subaddress of the primary account received.
+    ///
+    /// Monero refunded to the subaddresses of other swaps is never spent, so
+    /// it stays attributed to those swaps. If the address is not in the primary
+    /// account, the whole account is spent from like [`Wallet::transfer`].
+    pub async fn transfer_from(
+        &self,
+        request: TransferRequest,
+        subaddress: Address,
+    ) -> Result<TransferProof> {
+        self.transfer_from_subaddress(request, Some(subaddress))
+            .await
+    }
+
+    /// Spends from all subaddresses of the primary account if no subaddress is
+    /// given.
+    async fn transfer_from_subaddress(
+        &self,
+        request: TransferRequest,
+        subaddress: Option<Address>,
+    ) -> Result<TransferProof> {
         let inner = self.inner.lock().await;
 
         inner
@@ -178,6 +284,24 @@ impl Wallet {
             .await
             .with_context(|| format!("Failed to open wallet {}", self.name))?;
 
+        let subaddr_indices = match subaddress {
+            Some(subaddress) => match inner.get_address_index(subaddress.to_string()).await {
+                Ok(GetAddressIndex { index }) if index.major == 0 => {
+                    let mut subaddr_indices = vec![0];
+                    if index.minor != 0 {
+                        subaddr_indices.push(index.minor);
+                    }
+                    subaddr_indices
+                }
+                // e.g. an address of another wallet that the user refunds to
+                _ => {
+                    tracing::debug!(%subaddress, "Address is not in the primary account of the wallet, spending from the whole account");
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+
         let TransferRequest {
             public_spend_key,
             public_view_key,
@@ -188,7 +312,12 @@ impl Wallet {
             Address::standard(self.network, public_spend_key, public_view_key.into());
 
         let res = inner
-            .transfer_single(0, amount.as_piconero(), &destination_address.to_string())
+            .transfer_from_subaddresses(
+                0,
+                subaddr_indices,
+                amount.as_piconero(),
+                &destination_address.to_string(),
+            )
             .await?;
 
         tracing::debug!(
@@ -210,7 +339,7 @@ impl Wallet {
             conf_target,
             public_view_key,
             public_spend_key,
-            transfer_proof,
+            ref transfer_proof,
             expected,
         } = request;
 
@@ -228,7 +357,7 @@ impl Wallet {
 
         wait_for_confirmations(
             &self.inner,
-            transfer_proof,
+            transfer_proof.clone(),
             address,
             expected,
             conf_target,
@@ -237,9 +366,40 @@ impl Wallet {
         )
         .await?;
 
+        if let Some(lock_verifier) = &self.lock_verifier {
+            lock_verifier.watch_for_transfer(&request).await?;
+        }
+
         Ok(())
     }
 
+    /// The confirmations of the transfer that is watched for, `None` if the
+    /// transfer is not known yet.
+    pub async fn transfer_confirmations(&self, request: &WatchRequest) -> Result<Option<u64>> {
+        if let Some(lock_verifier) = &self.lock_verifier {
+            return lock_verifier.transfer_confirmations(request).await;
+        }
+
+        let address = Address::standard(
+            self.network,
+            request.public_spend_key,
+            request.public_view_key.into(),
+        );
+
+        let tx = self
+            .inner
+            .lock()
+            .await
+            .check_tx_key(
+                request.transfer_proof.tx_hash().to_string(),
+                request.transfer_proof.tx_key.to_string(),
+                address.to_string(),
+            )
+            .await?;
+
+        Ok(Some(tx.confirmations))
+    }
+
     pub async fn sweep_all(&self, address: Address) -> Result<Vec<TxHash>> {
         let sweep_all = self
             .inner
@@ -252,9 +412,64 @@ impl Wallet {
         Ok(tx_hashes)
     }
 
+    /// The fee of a transaction of the wallet.
+    pub async fn transaction_fee(&self, tx_hash: &TxHash) -> Result<Amount> {
+        let transfer = self
+            .inner
+            .lock()
+            .await
+            .get_transfer_by_txid(tx_hash.0.clone())
+            .await
+            .with_context(|| format!("Could not find Monero transaction {} in wallet", tx_hash.0))?
+            .transfer;
+
+        Ok(Amount::from_piconero(transfer.fee))
+    }
+
+    /// Transfers the given amount from the primary account to the given
+    /// address.
+    pub async fn withdraw(&self, address: Address, amount: Amount) -> Result<TxHash> {
+        let res = self
+            .inner
+            .lock()
+            .await
+            .transfer_single(0, amount.as_piconero(), &address.to_string())
+            .await?;
+
+        tracing::info!(%amount, to = %address, tx_id = %res.tx_hash, "Withdrew Monero");
+
+        Ok(TxHash(res.tx_hash))
+    }
+
+    /// The balance that the Monero of new swaps is locked from, the one of the
+    /// main address.
+    ///
+    /// Unlike [`Wallet::get_balance`] it does not include the Monero refunded
+    /// to the subaddresses of earlier swaps.
+    pub async fn lockable_balance(&self) -> Result<Amount> {
+        let balance = self
+            .inner
+            .lock()
+            .await
+            .get_balance(0, vec![0])
+            .await?
+            .per_subaddress
+            .iter()
+            .find(|subaddress| subaddress.address_index == 0)
+            .map_or(0, |subaddress| subaddress.balance);
+
+        Ok(Amount::from_piconero(balance))
+    }
+
     /// Get the balance of the primary account.
     pub async fn get_balance(&self) -> Result<Amount> {
-        let amount = self.inner.lock().await.get_balance(0).await?.balance;
+        let amount = self
+            .inner
+            .lock()
+            .await
+            .get_balance(0, Vec::new())
+            .await?
+            .balance;
 
         Ok(Amount::from_piconero(amount))
     }
@@ -272,6 +487,16 @@ impl Wallet {
     }
 }
 
+/// A transaction that swept a wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    pub txid: TxHash,
+    pub address: Address,
+    /// What the address receives, the fee is already subtracted.
+    pub amount: Amount,
+    pub fee: Amount,
+}
+
 #[derive(Debug)]
 pub struct TransferRequest {
     pub public_spend_key: PublicKey,
@@ -288,6 +513,80 @@ pub struct WatchRequest {
     pub expected: Amount,
 }
 
+/// Loads the wallet of the keys and sweeps it if all of its balance is
+/// unlocked, returns `None` if some of it is still locked.
+async fn try_sweep(
+    client: &wallet::Client,
+    file_name: String,
+    address: Address,
+    private_spend_key: PrivateKey,
+    private_view_key: PrivateViewKey,
+    restore_height: BlockHeight,
+    sweep_to: Address,
+) -> Result<Option<Vec<Sweep>>> {
+    // Properly close the wallet before generating the other wallet to ensure that
+    // it saves its state correctly
+    let _ = client
+        .close_wallet()
+        .await
+        .context("Failed to close wallet")?;
+
+    if let Err(error) = client
+        .generate_from_keys(
+            file_name.clone(),
+            address.to_string(),
+            private_spend_key.to_string(),
+            PrivateKey::from(private_view_key).to_string(),
+            restore_height.height,
+            String::from(""),
+            true,
+        )
+        .await
+    {
+        // the wallet was generated by an earlier attempt
+        tracing::debug!(%file_name, "Opening wallet instead of generating it: {:#}", error);
+        client
+            .open_wallet(file_name.clone())
+            .await
+            .with_context(|| format!("Failed to open wallet {}", file_name))?;
+    }
+
+    client.refresh().await.context("Failed to refresh wallet")?;
+
+    let balance = client.get_balance(0, Vec::new()).await?;
+    if balance.balance == 0 {
+        return Ok(Some(Vec::new()));
+    }
+    if balance.unlocked_balance < balance.balance {
+        tracing::info!(
+            %address,
+            blocks_to_unlock = %balance.blocks_to_unlock,
+            "Waiting for Monero to unlock before sweeping it"
+        );
+        return Ok(None);
+    }
+
+    let sweep_all = client
+        .sweep_all(sweep_to.to_string())
+        .await
+        .with_context(|| format!("Failed to sweep Monero to {}", sweep_to))?;
+
+    let sweeps = sweep_all
+        .tx_hash_list
+        .into_iter()
+        .zip(sweep_all.amount_list)
+        .zip(sweep_all.fee_list)
+        .map(|((txid, amount), fee)| Sweep {
+            txid: TxHash(txid),
+            address: sweep_to,
+            amount: Amount::from_piconero(amount),
+            fee: Amount::from_piconero(fee),
+        })
+        .collect();
+
+    Ok(Some(sweeps))
+}
+
 async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync>(
     client: &Mutex<C>,
     transfer_proof: TransferProof,
@@ -1,34 +1,50 @@
 use ::monero::Network;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use big_bytes::BigByte;
-use futures::{StreamExt, TryStreamExt};
+use futures::StreamExt;
 use monero_rpc::wallet::{Client, MoneroWalletRpc as _};
 use reqwest::header::CONTENT_LENGTH;
 use reqwest::Url;
-use std::io::ErrorKind;
+use sha2::{Digest, Sha256};
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::str::FromStr;
 use tokio::fs::{remove_file, OpenOptions};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio_util::codec::{BytesCodec, FramedRead};
-use tokio_util::io::StreamReader;
 
 #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
 compile_error!("unsupported operating system");
 
+/// The oldest monero-wallet-rpc that works with the current network rules.
+///
+/// An installed monero-wallet-rpc that is older than this is replaced by the
+/// latest release, unless auto update is disabled.
+const MINIMUM_VERSION: Version = Version([0, 17, 2, 0]);
+
+/// Lists the SHA256 hashes of the archives of the latest Monero release.
+const HASHES_URL: &str = "https://www.getmonero.org/downloads/hashes.txt";
+
+const DOWNLOAD_BASE_URL: &str = "https://downloads.getmonero.org/cli/";
+
 #[cfg(target_os = "macos")]
-const DOWNLOAD_URL: &str = "http://downloads.getmonero.org/cli/monero-mac-x64-v0.17.2.0.tar.bz2";
+const ARCHIVE_PREFIX: &str = "monero-mac-x64-v";
 
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-linux-x64-v0.17.2.0.tar.bz2";
+const ARCHIVE_PREFIX: &str = "monero-linux-x64-v";
 
 #[cfg(all(target_os = "linux", target_arch = "arm"))]
-const DOWNLOAD_URL: &str =
-    "https://downloads.getmonero.org/cli/monero-linux-armv7-v0.17.2.0.tar.bz2";
+const ARCHIVE_PREFIX: &str = "monero-linux-armv7-v";
+
+#[cfg(target_os = "windows")]
+const ARCHIVE_PREFIX: &str = "monero-win-x64-v";
+
+#[cfg(not(target_os = "windows"))]
+const ARCHIVE_SUFFIX: &str = ".tar.bz2";
 
 #[cfg(target_os = "windows")]
-const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-win-x64-v0.17.2.0.zip";
+const ARCHIVE_SUFFIX: &str = ".zip";
 
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 const PACKED_FILE: &str = "monero-wallet-rpc";
@@ -57,7 +73,13 @@ pub struct WalletRpc {
 }
 
 impl WalletRpc {
-    pub async fn new(working_dir: impl AsRef<Path>) -> Result<WalletRpc> {
+    /// Installs monero-wallet-rpc into the working directory if it is missing.
+    ///
+    /// If `auto_update` is set, an installed monero-wallet-rpc that is older
+    /// than [`MINIMUM_VERSION`] is replaced by the latest release. The wallet
+    /// files are backed up before, because the new version converts them to a
+    /// format older versions cannot read.
+    pub async fn new(working_dir: impl AsRef<Path>, auto_update: bool) -> Result<WalletRpc> {
         let working_dir = working_dir.as_ref();
 
         if !working_dir.exists() {
@@ -73,51 +95,164 @@ impl WalletRpc {
         }
 
         if !monero_wallet_rpc.exec_path().exists() {
-            let mut options = OpenOptions::new();
-            let mut file = options
-                .read(true)
-                .write(true)
-                .create_new(true)
-                .open(monero_wallet_rpc.archive_path())
-                .await?;
-
-            let response = reqwest::get(DOWNLOAD_URL).await?;
-
-            let content_length = response.headers()[CONTENT_LENGTH]
-                .to_str()
-                .context("Failed to convert content-length to string")?
-                .parse::<u64>()?;
-
-            tracing::info!(
-                "Downloading monero-wallet-rpc ({}) from {}",
-                content_length.big_byte(2),
-                DOWNLOAD_URL
+            monero_wallet_rpc.install().await?;
+
+            return Ok(monero_wallet_rpc);
+        }
+
+        let installed_version = match monero_wallet_rpc.installed_version().await {
+            Ok(version) if version >= MINIMUM_VERSION => {
+                tracing::debug!(%version, "Found monero-wallet-rpc");
+
+                return Ok(monero_wallet_rpc);
+            }
+            Ok(version) => Some(version),
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to determine version of monero-wallet-rpc: {:#}",
+                    error
+                );
+
+                None
+            }
+        };
+
+        let installed = installed_version
+            .map(|version| version.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        if !auto_update {
+            tracing::warn!(
+                installed_version = %installed,
+                minimum_version = %MINIMUM_VERSION,
+                "monero-wallet-rpc is outdated and may not work after a network upgrade, not updating it because auto update is disabled"
             );
 
-            let byte_stream = response
-                .bytes_stream()
-                .map_err(|err| std::io::Error::new(ErrorKind::Other, err));
+            return Ok(monero_wallet_rpc);
+        }
+
+        let release = monero_wallet_rpc.download_latest_release().await?;
+        monero_wallet_rpc.backup_wallets(&installed).await?;
+        remove_file(monero_wallet_rpc.exec_path()).await?;
+        Self::extract_archive(&monero_wallet_rpc).await?;
+
+        tracing::info!(
+            from = %installed,
+            to = %release.version,
+            "Updated monero-wallet-rpc"
+        );
+
+        Ok(monero_wallet_rpc)
+    }
+
+    async fn install(&self) -> Result<()> {
+        let release = self.download_latest_release().await?;
+        Self::extract_archive(self).await?;
+
+        tracing::info!(version = %release.version, "Installed monero-wallet-rpc");
 
-            #[cfg(not(target_os = "windows"))]
-            let mut stream = FramedRead::new(
-                async_compression::tokio::bufread::BzDecoder::new(StreamReader::new(byte_stream)),
-                BytesCodec::new(),
+        Ok(())
+    }
+
+    /// Downloads the archive of the latest Monero release into the working
+    /// directory and verifies its hash against the published hashes.
+    async fn download_latest_release(&self) -> Result<Release> {
+        let hashes = reqwest::get(HASHES_URL)
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+            .context("Failed to fetch the hashes of the latest Monero release")?;
+        let release = find_release(&hashes)?;
+
+        if release.version < MINIMUM_VERSION {
+            bail!(
+                "The latest Monero release {} is older than the minimum version {}",
+                release.version,
+                MINIMUM_VERSION
             )
-            .map_ok(|bytes| bytes.freeze());
+        }
 
-            #[cfg(target_os = "windows")]
-            let mut stream = FramedRead::new(StreamReader::new(byte_stream), BytesCodec::new())
-                .map_ok(|bytes| bytes.freeze());
+        let download_url = format!("{}{}", DOWNLOAD_BASE_URL, release.file_name);
+        let response = reqwest::get(download_url.as_str())
+            .await?
+            .error_for_status()?;
+
+        let content_length = response.headers()[CONTENT_LENGTH]
+            .to_str()
+            .context("Failed to convert content-length to string")?
+            .parse::<u64>()?;
+
+        tracing::info!(
+            "Downloading monero-wallet-rpc {} ({}) from {}",
+            release.version,
+            content_length.big_byte(2),
+            download_url
+        );
 
-            while let Some(chunk) = stream.next().await {
-                file.write(&chunk?).await?;
-            }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(self.archive_path())
+            .await?;
 
-            file.flush().await?;
+        let mut hasher = Sha256::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        let sha256 = hex::encode(hasher.finalize());
+        if sha256 != release.sha256 {
+            remove_file(self.archive_path()).await?;
+            bail!(
+                "Hash of downloaded archive {} is {} but expected {}",
+                release.file_name,
+                sha256,
+                release.sha256
+            )
+        }
+
+        Ok(release)
+    }
+
+    async fn installed_version(&self) -> Result<Version> {
+        let output = Command::new(self.exec_path())
+            .arg("--version")
+            .output()
+            .await
+            .context("Failed to run monero-wallet-rpc")?;
+
+        parse_version_output(&String::from_utf8_lossy(&output.stdout))
+    }
 
-            Self::extract_archive(&monero_wallet_rpc).await?;
+    /// Copies the wallet files into a directory named after the version of
+    /// monero-wallet-rpc that wrote them.
+    async fn backup_wallets(&self, version: &str) -> Result<()> {
+        let wallet_dir = self.wallet_dir();
+        if !wallet_dir.exists() {
+            return Ok(());
         }
-        Ok(monero_wallet_rpc)
+
+        let backup_dir = self
+            .working_dir
+            .join(format!("monero-data-backup-{}", version));
+        tokio::fs::create_dir_all(&backup_dir).await?;
+
+        let mut entries = tokio::fs::read_dir(&wallet_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                tokio::fs::copy(entry.path(), backup_dir.join(entry.file_name())).await?;
+            }
+        }
+
+        tracing::info!(backup_dir = %backup_dir.display(), "Backed up Monero wallet files");
+
+        Ok(())
     }
 
     pub async fn run(&self, network: Network, daemon_address: &str) -> Result<WalletRpcProcess> {
@@ -154,7 +289,7 @@ impl WalletRpc {
             .arg(format!("{}", port))
             .arg("--disable-rpc-login")
             .arg("--wallet-dir")
-            .arg(self.working_dir.join("monero-data"))
+            .arg(self.wallet_dir())
             .spawn()?;
 
         let stdout = child
@@ -197,9 +332,13 @@ impl WalletRpc {
         self.working_dir.join(PACKED_FILE)
     }
 
+    fn wallet_dir(&self) -> PathBuf {
+        self.working_dir.join("monero-data")
+    }
+
     #[cfg(not(target_os = "windows"))]
     async fn extract_archive(monero_wallet_rpc: &Self) -> Result<()> {
-        use anyhow::bail;
+        use async_compression::tokio::bufread::BzDecoder;
         use tokio_tar::Archive;
 
         let mut options = OpenOptions::new();
@@ -208,7 +347,7 @@ impl WalletRpc {
             .open(monero_wallet_rpc.archive_path())
             .await?;
 
-        let mut ar = Archive::new(file);
+        let mut ar = Archive::new(BzDecoder::new(BufReader::new(file)));
         let mut entries = ar.entries()?;
 
         loop {
@@ -264,3 +403,134 @@ impl WalletRpc {
         Ok(())
     }
 }
+
+/// A version of Monero, e.g. `0.17.2.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Version([u32; 4]);
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = [0; 4];
+        let mut numbers = s.trim_start_matches('v').split('.');
+
+        for part in parts.iter_mut() {
+            if let Some(number) = numbers.next() {
+                *part = number
+                    .parse()
+                    .with_context(|| format!("Invalid Monero version {}", s))?;
+            }
+        }
+
+        if numbers.next().is_some() {
+            bail!("Invalid Monero version {}", s)
+        }
+
+        Ok(Version(parts))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [major, minor, patch, build] = self.0;
+        write!(f, "{}.{}.{}.{}", major, minor, patch, build)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Release {
+    version: Version,
+    file_name: String,
+    sha256: String,
+}
+
+/// Finds the archive for this platform in the list of hashes published with
+/// every Monero release, whose lines look like `<sha256>  <file name>`.
+fn find_release(hashes: &str) -> Result<Release> {
+    hashes
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let sha256 = columns.next()?;
+            let file_name = columns.next()?;
+            let version = file_name
+                .strip_prefix(ARCHIVE_PREFIX)?
+                .strip_suffix(ARCHIVE_SUFFIX)?
+                .parse()
+                .ok()?;
+
+            Some(Release {
+                version,
+                file_name: file_name.to_string(),
+                sha256: sha256.to_lowercase(),
+            })
+        })
+        .max_by_key(|release| release.version)
+        .with_context(|| {
+            format!(
+                "No archive matching {}*{} in the hashes of the latest Monero release",
+                ARCHIVE_PREFIX, ARCHIVE_SUFFIX
+            )
+        })
+}
+
+/// Parses the output of `monero-wallet-rpc --version`, e.g. `Monero 'Oxygen
+/// Orion' (v0.17.2.0-release)`.
+fn parse_version_output(output: &str) -> Result<Version> {
+    let version = output
+        .split("(v")
+        .nth(1)
+        .and_then(|rest| rest.split(|c: char| c == '-' || c == ')').next())
+        .with_context(|| format!("Unexpected output of monero-wallet-rpc: {}", output))?;
+
+    version.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_ordered_by_their_parts() {
+        let version = "0.17.2.0".parse::<Version>().unwrap();
+
+        assert!(version < "0.17.2.3".parse().unwrap());
+        assert!(version < "0.18.0.0".parse().unwrap());
+        assert!(version > "0.17.1.9".parse().unwrap());
+        assert_eq!("v0.17.2".parse::<Version>().unwrap(), version);
+        assert!("0.17.2.0.1".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn parses_version_of_monero_wallet_rpc() {
+        let version = parse_version_output("Monero 'Oxygen Orion' (v0.17.2.0-release)\n").unwrap();
+
+        assert_eq!(version, Version([0, 17, 2, 0]));
+    }
+
+    #[test]
+    fn finds_release_for_this_platform() {
+        let hashes = format!(
+            "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n## CLI\n{hash}  monero-android-armv8-v0.17.3.0.tar.bz2\n{hash}  {prefix}0.17.3.0{suffix}\n{hash}  monero-source-v0.17.3.0.tar.bz2\n",
+            hash = "A8D8273B14F31569F5B7AA3063FBD322E3CAEC3D63F9F51E287DFC539C7F7D61",
+            prefix = ARCHIVE_PREFIX,
+            suffix = ARCHIVE_SUFFIX,
+        );
+
+        let release = find_release(&hashes).unwrap();
+
+        assert_eq!(release, Release {
+            version: Version([0, 17, 3, 0]),
+            file_name: format!("{}0.17.3.0{}", ARCHIVE_PREFIX, ARCHIVE_SUFFIX),
+            sha256: "a8d8273b14f31569f5b7aa3063fbd322e3caec3d63f9f51e287dfc539c7f7d61".to_string(),
+        });
+    }
+
+    #[test]
+    fn given_no_archive_for_this_platform_then_fails() {
+        let hashes = "a8d8273b14f31569f5b7aa3063fbd322e3caec3d63f9f51e287dfc539c7f7d61  monero-source-v0.17.3.0.tar.bz2";
+
+        assert!(find_release(hashes).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Streams the state transitions of all swaps as server-sent events.
+//!
+//! The state transitions are read from the database, hence the monitor also
+//! reports transitions of swaps that are executed by another process using
+//! the same database. Like the ASB's RPC server, the monitor does not provide
+//! any authentication and is meant to be bound to a local interface only.
+
+use crate::protocol::alice::AliceState;
+use crate::protocol::bob::BobState;
+use crate::protocol::{Database, RecordedState, State};
+use anyhow::{Context as _, Result};
+use futures::stream::{self, Stream};
+use futures::{StreamExt, TryStreamExt};
+use hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How often the database is checked for new state transitions.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// After how many polls without a state transition a comment is sent to keep
+/// the connection alive.
+const KEEP_ALIVE_POLLS: u32 = 15;
+
+/// A state transition of a swap.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    /// Identifies the event, clients can resume the stream after this event
+    /// by sending it in the `Last-Event-ID` header.
+    pub id: i64,
+    pub swap_id: Uuid,
+    pub role: &'static str,
+    /// The name of the state the swap entered, e.g. `BtcLocked`.
+    pub event: &'static str,
+    /// A human readable description of the state the swap entered.
+    pub state: String,
+    pub entered_at: String,
+}
+
+impl From<RecordedState> for Event {
+    fn from(recorded: RecordedState) -> Self {
+        let (role, event) = match &recorded.state {
+            State::Alice(state) => ("alice", alice_event(state)),
+            State::Bob(state) => ("bob", bob_event(state)),
+        };
+        let state = match &recorded.state {
+            State::Alice(state) => state.to_string(),
+            State::Bob(state) => state.to_string(),
+        };
+
+        Event {
+            id: recorded.id,
+            swap_id: recorded.swap_id,
+            role,
+            event,
+            state,
+            entered_at: recorded.entered_at,
+        }
+    }
+}
+
+impl Event {
+    fn to_sse(&self) -> Result<String> {
+        Ok(format!(
+            "id: {}\ndata: {}\n\n",
+            self.id,
+            serde_json::to_string(self)?
+        ))
+    }
+}
+
+pub(crate) fn alice_event(state: &AliceState) -> &'static str {
+    match state {
+        AliceState::Started { .. } => "Started",
+        AliceState::BtcLockTransactionSeen { .. } => "BtcLockTransactionSeen",
+        AliceState::BtcLocked { .. } => "BtcLocked",
+        AliceState::XmrLockTransactionSent { .. } => "XmrLockTransactionSent",
+        AliceState::XmrLocked { .. } => "XmrLocked",
+        AliceState::XmrLockTransferProofSent { .. } => "XmrLockTransferProofSent",
+        AliceState::EncSigLearned { .. } => "EncSigLearned",
+        AliceState::BtcRedeemTransactionPublished { .. } => "BtcRedeemTransactionPublished",
+        AliceState::BtcRedeemed => "BtcRedeemed",
+        AliceState::BtcCancelled { .. } => "BtcCancelled",
+        AliceState::BtcRefunded { .. } => "BtcRefunded",
+        AliceState::BtcPunishable { .. } => "BtcPunishable",
+        AliceState::XmrRefunded => "XmrRefunded",
+        AliceState::CancelTimelockExpired { .. } => "CancelTimelockExpired",
+        AliceState::BtcPunished => "BtcPunished",
+        AliceState::SafelyAborted => "SafelyAborted",
+    }
+}
+
+pub(crate) fn bob_event(state: &BobState) -> &'static str {
+    match state {
+        BobState::Started { .. } => "Started",
+        BobState::SwapSetupCompleted(..) => "SwapSetupCompleted",
+        BobState::BtcLocked(..) => "BtcLocked",
+        BobState::XmrLockProofReceived { .. } => "XmrLockProofReceived",
+        BobState::XmrLocked(..) => "XmrLocked",
+        BobState::EncSigSent(..) => "EncSigSent",
+        BobState::BtcRedeemed(..) => "BtcRedeemed",
+        BobState::CancelTimelockExpired(..) => "CancelTimelockExpired",
+        BobState::BtcCancelled(..) => "BtcCancelled",
+        BobState::BtcRefunded(..) => "BtcRefunded",
+        BobState::XmrRedeemed { .. } => "XmrRedeemed",
+        BobState::BtcPunished { .. } => "BtcPunished",
+        BobState::SafelyAborted => "SafelyAborted",
+    }
+}
+
+/// Serves the event stream on the given address until the server fails.
+///
+/// Every `GET` request is answered with a `text/event-stream` of all state
+/// transitions recorded from then on.
+pub async fn serve(address: SocketAddr, db: Arc<dyn Database + Send + Sync>) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let db = db.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, db.clone())
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)
+        .with_context(|| format!("Failed to bind swap monitor to {}", address))?
+        .serve(make_service);
+
+    tracing::info!(%address, "Swap monitor listening");
+
+    server.await.context("Swap monitor failed")?;
+
+    Ok(())
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    db: Arc<dyn Database + Send + Sync>,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::GET {
+        return Ok(empty_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    // Clients that reconnect send the id of the last event they received.
+    let last_event_id = request
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<i64>().ok());
+
+    let after = match last_event_id {
+        Some(id) => id,
+        None => match db.get_latest_state_id().await {
+            Ok(id) => id.unwrap_or_default(),
+            Err(error) => {
+                tracing::warn!("Failed to load latest state from database: {:#}", error);
+                return Ok(empty_response(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+        },
+    };
+
+    let mut response = Response::new(Body::wrap_stream(event_stream(db, after)));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    Ok(response)
+}
+
+/// A stream of server-sent event chunks containing all states recorded
+/// after the state with the given id.
+fn event_stream(
+    db: Arc<dyn Database + Send + Sync>,
+    after: i64,
+) -> impl Stream<Item = Result<String>> + Send + 'static {
+    let interval = tokio::time::interval(POLL_INTERVAL);
+
+    stream::try_unfold(
+        (db, after, interval, 0),
+        |(db, after, mut interval, idle_polls)| async move {
+            interval.tick().await;
+
+            let recorded = db.get_states_after(after).await?;
+            let after = recorded.last().map(|recorded| recorded.id).unwrap_or(after);
+
+            let (chunks, idle_polls) = if recorded.is_empty() {
+                if idle_polls + 1 < KEEP_ALIVE_POLLS {
+                    (vec![], idle_polls + 1)
+                } else {
+                    (vec![": keep-alive\n\n".to_string()], 0)
+                }
+            } else {
+                let chunks = recorded
+                    .into_iter()
+                    .map(|recorded| Event::from(recorded).to_sse())
+                    .collect::<Result<Vec<_>>>()?;
+
+                (chunks, 0)
+            };
+
+            Ok::<_, anyhow::Error>(Some((chunks, (db, after, interval, idle_polls))))
+        },
+    )
+    .map_ok(|chunks| stream::iter(chunks.into_iter().map(Ok::<_, anyhow::Error>)))
+    .try_flatten()
+    .boxed()
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .expect("static response to be valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_is_encoded_as_server_sent_event() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+
+        let event = Event::from(RecordedState {
+            id: 42,
+            swap_id,
+            entered_at: "2021-10-01 10:00:00.0 +00:00:00".to_string(),
+            state: State::Bob(BobState::SafelyAborted),
+        });
+
+        assert_eq!(
+            event.to_sse().unwrap(),
+            "id: 42\ndata: {\"id\":42,\"swap_id\":\"ea030832-3be9-454f-bb98-5ea9a788406b\",\"role\":\"bob\",\"event\":\"SafelyAborted\",\"state\":\"safely aborted\",\"entered_at\":\"2021-10-01 10:00:00.0 +00:00:00\"}\n\n"
+        );
+    }
+}
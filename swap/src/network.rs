@@ -1,10 +1,12 @@
 mod impl_from_rr_event;
 
+pub mod abort;
 pub mod cbor_request_response;
+pub mod connection_manager;
 pub mod encrypted_signature;
+pub mod identify;
 pub mod json_pull_codec;
 pub mod quote;
-pub mod redial;
 pub mod rendezvous;
 pub mod swap_setup;
 pub mod swarm;
@@ -0,0 +1,75 @@
+use crate::network::cbor_request_response::CborCodec;
+use crate::{asb, cli};
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+    RequestResponseMessage,
+};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const PROTOCOL: &str = "/comit/xmr/btc/abort/1.0.0";
+type OutEvent = RequestResponseEvent<Request, ()>;
+type Message = RequestResponseMessage<Request, ()>;
+
+pub type Behaviour = RequestResponse<CborCodec<AbortProtocol, Request, ()>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AbortProtocol;
+
+impl ProtocolName for AbortProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+/// Tells the peer that we will not proceed with a swap whose Bitcoin has not
+/// been locked yet.
+///
+/// Either party can send it, Alice as long as she has not seen the Bitcoin lock
+/// transaction and Bob as long as he has not published it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub swap_id: Uuid,
+}
+
+pub fn new() -> Behaviour {
+    Behaviour::new(
+        CborCodec::default(),
+        vec![(AbortProtocol, ProtocolSupport::Full)],
+        RequestResponseConfig::default(),
+    )
+}
+
+impl From<(PeerId, Message)> for asb::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request {
+                request, channel, ..
+            } => Self::AbortReceived {
+                msg: request,
+                channel,
+                peer,
+            },
+            Message::Response { request_id, .. } => Self::AbortAcknowledged { id: request_id },
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+impl From<(PeerId, Message)> for cli::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request {
+                request, channel, ..
+            } => Self::AbortReceived {
+                msg: request,
+                channel,
+                peer,
+            },
+            Message::Response { request_id, .. } => Self::AbortAcknowledged { id: request_id },
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
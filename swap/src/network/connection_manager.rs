@@ -0,0 +1,370 @@
+//! Keeps the connections to the peers of the running swaps alive.
+//!
+//! A connection is closed once none of the protocols needs it any more, which
+//! in a swap can happen while waiting for a confirmation, for example between
+//! the confirmation of the Bitcoin lock transaction and the transfer proof.
+//! The connection manager keeps the connections to the peers of the running
+//! swaps open and lets all others idle out. If the connection to such a peer is
+//! lost anyway, the peer is redialled with an exponential backoff and reported
+//! as unreachable if the connection can't be re-established within
+//! [`UNREACHABLE_AFTER`].
+//!
+//! The ASB does not know the addresses of the peers that connect to it, so it
+//! only waits for them to reconnect.
+
+use crate::{asb, cli};
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use futures::future::FutureExt;
+use libp2p::core::connection::ConnectionId;
+use libp2p::core::upgrade::DeniedUpgrade;
+use libp2p::core::Multiaddr;
+use libp2p::swarm::{
+    DialPeerCondition, KeepAlive, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler,
+    PollParameters, ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr,
+    SubstreamProtocol,
+};
+use libp2p::PeerId;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+use void::Void;
+
+/// For how long a peer with running swaps may be disconnected before it is
+/// reported as unreachable.
+pub const UNREACHABLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+const INITIAL_REDIAL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutEvent {
+    /// The connection to a peer with running swaps was lost and could not be
+    /// re-established within [`UNREACHABLE_AFTER`].
+    PeerUnreachable { peer: PeerId },
+}
+
+/// The connection manager of the CLI, which keeps the connection to the
+/// seller alive and redials it.
+pub fn cli(alice: PeerId) -> Behaviour {
+    let mut behaviour = Behaviour::new(true);
+    behaviour.keep_connected_to(vec![alice]);
+
+    behaviour
+}
+
+/// The connection manager of the ASB, the peers of the running swaps are set
+/// with [`Behaviour::keep_connected_to`].
+pub fn asb() -> Behaviour {
+    Behaviour::new(false)
+}
+
+/// A [`NetworkBehaviour`] that keeps the connections to the peers with running
+/// swaps alive and tries to re-establish them if they are lost.
+#[allow(missing_debug_implementations)]
+pub struct Behaviour {
+    /// Whether we dial the peers we lost the connection to, or only wait for
+    /// them to reconnect.
+    redial: bool,
+    /// The peers we are currently connected to.
+    connected: HashSet<PeerId>,
+    /// The peers with running swaps and the state of our connection to them.
+    kept: HashMap<PeerId, Connection>,
+    notifications: VecDeque<(PeerId, bool)>,
+}
+
+enum Connection {
+    Connected,
+    /// The connection was lost, the peer is dialled again once `next_attempt`
+    /// elapses.
+    Lost {
+        next_attempt: Pin<Box<Sleep>>,
+        backoff: ExponentialBackoff,
+    },
+    /// The peer was reported as unreachable, we wait for it to reconnect.
+    Unreachable,
+}
+
+impl Behaviour {
+    fn new(redial: bool) -> Self {
+        Self {
+            redial,
+            connected: HashSet::default(),
+            kept: HashMap::default(),
+            notifications: VecDeque::default(),
+        }
+    }
+
+    /// Keeps the connections to the given peers alive and lets the ones to all
+    /// other peers idle out.
+    pub fn keep_connected_to(&mut self, peers: impl IntoIterator<Item = PeerId>) {
+        let peers = peers.into_iter().collect::<HashSet<_>>();
+
+        let released = self
+            .kept
+            .keys()
+            .filter(|peer| !peers.contains(peer))
+            .copied()
+            .collect::<Vec<_>>();
+        for peer in released {
+            self.kept.remove(&peer);
+            if self.connected.contains(&peer) {
+                self.notifications.push_back((peer, false));
+            }
+        }
+
+        for peer in peers {
+            if self.kept.contains_key(&peer) {
+                continue;
+            }
+
+            let connection = if self.connected.contains(&peer) {
+                self.notifications.push_back((peer, true));
+                Connection::Connected
+            } else {
+                self.lost()
+            };
+            self.kept.insert(peer, connection);
+        }
+    }
+
+    /// Returns for how long we wait until we dial the peer again, `None` if
+    /// we are connected to it or don't redial it.
+    pub fn until_next_redial(&self, peer: &PeerId) -> Option<Duration> {
+        match self.kept.get(peer)? {
+            Connection::Lost { next_attempt, .. } if self.redial => next_attempt
+                .deadline()
+                .checked_duration_since(Instant::now()),
+            _ => None,
+        }
+    }
+
+    fn lost(&self) -> Connection {
+        let initial_interval = if self.redial {
+            INITIAL_REDIAL_INTERVAL
+        } else {
+            UNREACHABLE_AFTER
+        };
+        let mut backoff = ExponentialBackoff {
+            initial_interval,
+            current_interval: initial_interval,
+            max_elapsed_time: Some(UNREACHABLE_AFTER),
+            ..ExponentialBackoff::default()
+        };
+        backoff.reset();
+
+        Connection::Lost {
+            next_attempt: Box::pin(tokio::time::sleep(initial_interval)),
+            backoff,
+        }
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ProtocolsHandler = Handler;
+    type OutEvent = OutEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        Handler::default()
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, peer_id: &PeerId) {
+        self.connected.insert(*peer_id);
+
+        if let Some(connection) = self.kept.get_mut(peer_id) {
+            *connection = Connection::Connected;
+            self.notifications.push_back((*peer_id, true));
+        }
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId) {
+        self.connected.remove(peer_id);
+
+        if self.kept.contains_key(peer_id) {
+            let lost = self.lost();
+            self.kept.insert(*peer_id, lost);
+        }
+    }
+
+    fn inject_event(&mut self, _: PeerId, _: ConnectionId, event: Void) {
+        void::unreachable(event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        _: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+        if let Some((peer, keep_alive)) = self.notifications.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
+                peer_id: peer,
+                // we only dial peers we are disconnected from, there is usually a single
+                // connection to a peer
+                handler: NotifyHandler::Any,
+                event: keep_alive,
+            });
+        }
+
+        for (peer, connection) in self.kept.iter_mut() {
+            let (next_attempt, backoff) = match connection {
+                Connection::Lost {
+                    next_attempt,
+                    backoff,
+                } => (next_attempt, backoff),
+                Connection::Connected | Connection::Unreachable => continue,
+            };
+
+            if next_attempt.poll_unpin(cx).is_pending() {
+                continue;
+            }
+
+            let next_dial_in = match backoff.next_backoff() {
+                Some(next_dial_in) if self.redial => next_dial_in,
+                _ => {
+                    let peer = *peer;
+                    *connection = Connection::Unreachable;
+
+                    return Poll::Ready(NetworkBehaviourAction::GenerateEvent(
+                        OutEvent::PeerUnreachable { peer },
+                    ));
+                }
+            };
+
+            *next_attempt = Box::pin(tokio::time::sleep(next_dial_in));
+
+            return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                peer_id: *peer,
+                condition: DialPeerCondition::Disconnected,
+                handler: Handler::default(),
+            });
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Keeps its connection alive while its peer has running swaps.
+#[derive(Debug, Default)]
+pub struct Handler {
+    keep_alive: bool,
+}
+
+impl ProtocolsHandler for Handler {
+    /// Whether the peer of the connection has running swaps.
+    type InEvent = bool;
+    type OutEvent = Void;
+    type Error = Void;
+    type InboundProtocol = DeniedUpgrade;
+    type OutboundProtocol = DeniedUpgrade;
+    type InboundOpenInfo = ();
+    type OutboundOpenInfo = Void;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(DeniedUpgrade, ())
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, protocol: Void, _: Self::InboundOpenInfo) {
+        void::unreachable(protocol)
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, protocol: Void, _: Self::OutboundOpenInfo) {
+        void::unreachable(protocol)
+    }
+
+    fn inject_event(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        info: Self::OutboundOpenInfo,
+        _: ProtocolsHandlerUpgrErr<Void>,
+    ) {
+        void::unreachable(info)
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        if self.keep_alive {
+            KeepAlive::Yes
+        } else {
+            KeepAlive::No
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll(
+        &mut self,
+        _: &mut Context<'_>,
+    ) -> Poll<
+        ProtocolsHandlerEvent<
+            Self::OutboundProtocol,
+            Self::OutboundOpenInfo,
+            Self::OutEvent,
+            Self::Error,
+        >,
+    > {
+        Poll::Pending
+    }
+}
+
+impl From<OutEvent> for cli::OutEvent {
+    fn from(event: OutEvent) -> Self {
+        match event {
+            OutEvent::PeerUnreachable { peer } => cli::OutEvent::PeerUnreachable { peer },
+        }
+    }
+}
+
+impl From<OutEvent> for asb::OutEvent {
+    fn from(event: OutEvent) -> Self {
+        match event {
+            OutEvent::PeerUnreachable { peer } => asb::OutEvent::PeerUnreachable { peer },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_connections_to_peers_with_running_swaps_are_kept_alive() {
+        let mut behaviour = asb();
+        let (bob, other) = (PeerId::random(), PeerId::random());
+        behaviour.inject_connected(&bob);
+        behaviour.inject_connected(&other);
+
+        behaviour.keep_connected_to(vec![bob]);
+        assert_eq!(behaviour.notifications.pop_front(), Some((bob, true)));
+        assert!(behaviour.notifications.is_empty());
+
+        // the swaps with the peer finished
+        behaviour.keep_connected_to(vec![]);
+        assert_eq!(behaviour.notifications.pop_front(), Some((bob, false)));
+        assert!(behaviour.kept.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lost_connections_to_peers_with_running_swaps_are_redialled() {
+        let alice = PeerId::random();
+        let mut behaviour = cli(alice);
+        assert!(behaviour.until_next_redial(&alice).is_some());
+
+        behaviour.inject_connected(&alice);
+        assert!(behaviour.until_next_redial(&alice).is_none());
+
+        behaviour.inject_disconnected(&alice);
+        let until_next_redial = behaviour.until_next_redial(&alice).unwrap();
+        assert!(until_next_redial <= INITIAL_REDIAL_INTERVAL);
+
+        // the ASB waits for the peer to reconnect instead
+        let mut behaviour = asb();
+        behaviour.keep_connected_to(vec![alice]);
+        assert!(behaviour.until_next_redial(&alice).is_none());
+    }
+}
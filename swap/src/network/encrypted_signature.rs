@@ -46,6 +46,16 @@ pub fn bob() -> Behaviour {
     )
 }
 
+/// For peers that take the role of Alice in some swaps and the role of Bob in
+/// others.
+pub fn alice_and_bob() -> Behaviour {
+    Behaviour::new(
+        CborCodec::default(),
+        vec![(EncryptedSignatureProtocol, ProtocolSupport::Full)],
+        RequestResponseConfig::default(),
+    )
+}
+
 impl From<(PeerId, Message)> for asb::OutEvent {
     fn from((peer, message): (PeerId, Message)) -> Self {
         match message {
@@ -56,7 +66,9 @@ impl From<(PeerId, Message)> for asb::OutEvent {
                 channel,
                 peer,
             },
-            Message::Response { .. } => Self::unexpected_response(peer),
+            Message::Response { request_id, .. } => {
+                Self::EncryptedSignatureAcknowledged { id: request_id }
+            }
         }
     }
 }
@@ -65,7 +77,13 @@ crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
 impl From<(PeerId, Message)> for cli::OutEvent {
     fn from((peer, message): (PeerId, Message)) -> Self {
         match message {
-            Message::Request { .. } => Self::unexpected_request(peer),
+            Message::Request {
+                request, channel, ..
+            } => Self::EncryptedSignatureReceived {
+                msg: request,
+                channel,
+                peer,
+            },
             Message::Response { request_id, .. } => {
                 Self::EncryptedSignatureAcknowledged { id: request_id }
             }
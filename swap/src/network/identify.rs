@@ -0,0 +1,17 @@
+//! The identify protocol tells a peer the address it is seen on, the ASB uses
+//! it to check which of its addresses takers actually reach it on.
+
+use libp2p::identify::{Identify, IdentifyConfig, IdentifyEvent};
+use libp2p::identity::PublicKey;
+
+const PROTOCOL_VERSION: &str = "/comit/xmr/btc/1.0.0";
+
+pub type Behaviour = Identify;
+pub type OutEvent = IdentifyEvent;
+
+pub fn new(local_public_key: PublicKey) -> Behaviour {
+    Identify::new(IdentifyConfig::new(
+        PROTOCOL_VERSION.to_string(),
+        local_public_key,
+    ))
+}
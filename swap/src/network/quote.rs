@@ -1,25 +1,40 @@
 use crate::network::json_pull_codec::JsonPullCodec;
-use crate::{asb, bitcoin, cli};
+use crate::network::rendezvous::AssetPair;
+use crate::network::swap_setup::TimelockRanges;
+use crate::{asb, bitcoin, cli, monero};
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncWrite};
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-    RequestResponseMessage,
+    ProtocolSupport, RequestResponse, RequestResponseCodec, RequestResponseConfig,
+    RequestResponseEvent, RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
+const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/2.0.0";
+const PROTOCOL_V1: &str = "/comit/xmr/btc/bid-quote/1.0.0";
 pub type OutEvent = RequestResponseEvent<(), BidQuote>;
 pub type Message = RequestResponseMessage<(), BidQuote>;
 
-pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, BidQuote>>;
+pub type Behaviour = RequestResponse<BidQuoteCodec>;
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct BidQuoteProtocol;
+/// Version 2 adds the [`QuoteDetails`] to the quote. Both versions are
+/// supported, so peers that only know version 1 keep working.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BidQuoteProtocol {
+    V1,
+    V2,
+}
 
 impl ProtocolName for BidQuoteProtocol {
     fn protocol_name(&self) -> &[u8] {
-        PROTOCOL.as_bytes()
+        match self {
+            BidQuoteProtocol::V1 => PROTOCOL_V1.as_bytes(),
+            BidQuoteProtocol::V2 => PROTOCOL.as_bytes(),
+        }
     }
 }
 
@@ -35,6 +50,150 @@ pub struct BidQuote {
     /// The maximum quantity the maker is willing to buy.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub max_quantity: bitcoin::Amount,
+    /// Only part of quotes sent over version 2 of the protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<QuoteDetails>,
+}
+
+impl BidQuote {
+    /// Quotes of makers that only support version 1 of the protocol don't
+    /// expire.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        let expires_at = match self.details {
+            Some(details) => UNIX_EPOCH + std::time::Duration::from_secs(details.expires_at),
+            None => return false,
+        };
+
+        now >= expires_at
+    }
+
+    /// The timelocks the maker accepts, `None` if it only accepts the
+    /// timelocks of the network.
+    pub fn timelock_ranges(&self) -> Option<TimelockRanges> {
+        self.details.and_then(|details| details.timelocks)
+    }
+
+    /// The assets the maker swaps, makers that only support version 1 of the
+    /// protocol only swap XMR for BTC.
+    pub fn pair(&self) -> AssetPair {
+        self.details.map(|details| details.pair).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct QuoteDetails {
+    /// Makers that don't tag their quotes with the pair swap XMR for BTC.
+    #[serde(default)]
+    pub pair: AssetPair,
+    /// Unix timestamp in seconds from which on the maker no longer guarantees
+    /// the price.
+    pub expires_at: u64,
+    /// The minimum quantity the maker is willing to sell.
+    pub min_quantity_xmr: monero::Amount,
+    /// The maximum quantity the maker is willing to sell.
+    pub max_quantity_xmr: monero::Amount,
+    pub fees: FeeAssumptions,
+    /// The price per XMR at which the maker buys XMR, if it does.
+    ///
+    /// The amount of BTC the maker pays is bound by the same limits as the
+    /// amount of BTC it accepts when selling XMR.
+    #[serde(
+        default,
+        with = "::bitcoin::util::amount::serde::as_sat::opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub buy_price: Option<bitcoin::Amount>,
+    /// The timelocks the maker accepts if the taker proposes its own. Makers
+    /// that don't advertise them only accept the timelocks of the network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelocks: Option<TimelockRanges>,
+}
+
+/// The network fees the maker assumes for its own transactions.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct FeeAssumptions {
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub bitcoin_redeem: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub bitcoin_punish: bitcoin::Amount,
+    pub monero_lock: monero::Amount,
+}
+
+/// Encodes quotes as JSON like [`JsonPullCodec`], but leaves out the details
+/// on version 1 of the protocol and requires them on version 2.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BidQuoteCodec {
+    inner: JsonPullCodec<BidQuoteProtocol, BidQuote>,
+}
+
+#[async_trait]
+impl RequestResponseCodec for BidQuoteCodec {
+    type Protocol = BidQuoteProtocol;
+    type Request = ();
+    type Response = BidQuote;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        self.inner.read_request(protocol, io).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let quote = self.inner.read_response(protocol, io).await?;
+
+        if *protocol == BidQuoteProtocol::V2 && quote.details.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Quote lacks the details required by version 2 of the protocol",
+            ));
+        }
+
+        Ok(quote)
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        request: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        self.inner.write_request(protocol, io, request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        quote: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let quote = match protocol {
+            BidQuoteProtocol::V1 => BidQuote {
+                details: None,
+                ..quote
+            },
+            BidQuoteProtocol::V2 => quote,
+        };
+
+        self.inner.write_response(protocol, io, quote).await
+    }
 }
 
 /// Constructs a new instance of the `quote` behaviour to be used by the ASB.
@@ -43,8 +202,11 @@ pub struct BidQuote {
 /// handing out quotes.
 pub fn asb() -> Behaviour {
     Behaviour::new(
-        JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Inbound)],
+        BidQuoteCodec::default(),
+        vec![
+            (BidQuoteProtocol::V2, ProtocolSupport::Inbound),
+            (BidQuoteProtocol::V1, ProtocolSupport::Inbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
@@ -55,8 +217,11 @@ pub fn asb() -> Behaviour {
 /// requesting quotes.
 pub fn cli() -> Behaviour {
     Behaviour::new(
-        JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Outbound)],
+        BidQuoteCodec::default(),
+        vec![
+            (BidQuoteProtocol::V2, ProtocolSupport::Outbound),
+            (BidQuoteProtocol::V1, ProtocolSupport::Outbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
@@ -86,3 +251,95 @@ impl From<(PeerId, Message)> for cli::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::swap_setup::TimelockRange;
+    use futures::io::Cursor;
+
+    fn quote() -> BidQuote {
+        BidQuote {
+            price: bitcoin::Amount::from_sat(700_000),
+            min_quantity: bitcoin::Amount::from_sat(100_000),
+            max_quantity: bitcoin::Amount::from_sat(1_000_000),
+            details: Some(QuoteDetails {
+                pair: AssetPair::XMR_BTC,
+                expires_at: 1_634_000_000,
+                min_quantity_xmr: monero::Amount::from_piconero(142_857_142_857),
+                max_quantity_xmr: monero::Amount::from_piconero(1_428_571_428_571),
+                fees: FeeAssumptions {
+                    bitcoin_redeem: bitcoin::Amount::from_sat(2_000),
+                    bitcoin_punish: bitcoin::Amount::from_sat(2_000),
+                    monero_lock: monero::MONERO_FEE,
+                },
+                buy_price: None,
+                timelocks: Some(TimelockRanges {
+                    cancel: TimelockRange { min: 72, max: 144 },
+                    punish: TimelockRange::exactly(72u32),
+                }),
+            }),
+        }
+    }
+
+    async fn roundtrip(write: BidQuoteProtocol, read: BidQuoteProtocol) -> io::Result<BidQuote> {
+        let mut codec = BidQuoteCodec::default();
+
+        let mut buffer = Cursor::new(Vec::new());
+        codec.write_response(&write, &mut buffer, quote()).await?;
+        buffer.set_position(0);
+
+        codec.read_response(&read, &mut buffer).await
+    }
+
+    #[tokio::test]
+    async fn details_are_sent_over_version_2() {
+        let received = roundtrip(BidQuoteProtocol::V2, BidQuoteProtocol::V2)
+            .await
+            .unwrap();
+
+        assert_eq!(received, quote());
+    }
+
+    #[tokio::test]
+    async fn details_are_left_out_over_version_1() {
+        let received = roundtrip(BidQuoteProtocol::V1, BidQuoteProtocol::V1)
+            .await
+            .unwrap();
+
+        assert_eq!(received, BidQuote {
+            details: None,
+            ..quote()
+        });
+    }
+
+    #[test]
+    fn quote_without_pair_swaps_xmr_for_btc() {
+        let mut json = serde_json::to_value(quote()).unwrap();
+        json["details"].as_object_mut().unwrap().remove("pair");
+
+        let quote = serde_json::from_value::<BidQuote>(json).unwrap();
+
+        assert_eq!(quote.pair(), AssetPair::XMR_BTC);
+    }
+
+    #[tokio::test]
+    async fn version_2_requires_details() {
+        let result = roundtrip(BidQuoteProtocol::V1, BidQuoteProtocol::V2).await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn quote_expires_at_expiry_timestamp() {
+        let expires_at = UNIX_EPOCH + std::time::Duration::from_secs(1_634_000_000);
+
+        assert!(!quote().is_expired(expires_at - std::time::Duration::from_secs(1)));
+        assert!(quote().is_expired(expires_at));
+        assert!(!BidQuote {
+            details: None,
+            ..quote()
+        }
+        .is_expired(expires_at));
+    }
+}
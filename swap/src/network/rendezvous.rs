@@ -1,29 +1,259 @@
+use anyhow::{bail, Context, Result};
 use libp2p::rendezvous::Namespace;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum XmrBtcNamespace {
-    Mainnet,
-    Testnet,
-}
+/// The longest namespace a rendezvous node accepts.
+const MAX_NAMESPACE_LENGTH: usize = 255;
+
+const MAINNET: &str = "mainnet";
+const TESTNET: &str = "testnet";
 
-const MAINNET: &str = "xmr-btc-swap-mainnet";
-const TESTNET: &str = "xmr-btc-swap-testnet";
+/// An asset that can be swapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub enum Asset {
+    Xmr,
+    Btc,
+}
 
-impl fmt::Display for XmrBtcNamespace {
+impl fmt::Display for Asset {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            XmrBtcNamespace::Mainnet => write!(f, "{}", MAINNET),
-            XmrBtcNamespace::Testnet => write!(f, "{}", TESTNET),
+            Asset::Xmr => write!(f, "xmr"),
+            Asset::Btc => write!(f, "btc"),
         }
     }
 }
 
-impl From<XmrBtcNamespace> for Namespace {
-    fn from(namespace: XmrBtcNamespace) -> Self {
-        match namespace {
-            XmrBtcNamespace::Mainnet => Namespace::from_static(MAINNET),
-            XmrBtcNamespace::Testnet => Namespace::from_static(TESTNET),
+impl FromStr for Asset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "xmr" => Ok(Asset::Xmr),
+            "btc" => Ok(Asset::Btc),
+            _ => bail!("Unknown asset '{}'", s),
         }
     }
 }
+
+/// The assets a maker swaps, the `base` asset is the one it sells for the
+/// `quote` asset.
+///
+/// Encoded as `<base>-<quote>`, e.g. `xmr-btc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AssetPair {
+    pub base: Asset,
+    pub quote: Asset,
+}
+
+impl AssetPair {
+    pub const XMR_BTC: AssetPair = AssetPair {
+        base: Asset::Xmr,
+        quote: Asset::Btc,
+    };
+}
+
+/// Makers that don't tag their quotes with the pair only swap XMR for BTC.
+impl Default for AssetPair {
+    fn default() -> Self {
+        AssetPair::XMR_BTC
+    }
+}
+
+impl fmt::Display for AssetPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.base, self.quote)
+    }
+}
+
+impl FromStr for AssetPair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (base, quote) = s
+            .split_once('-')
+            .with_context(|| format!("Asset pair '{}' is not of the form <base>-<quote>", s))?;
+        let pair = AssetPair {
+            base: base.parse()?,
+            quote: quote.parse()?,
+        };
+
+        if pair.base == pair.quote {
+            bail!("Asset pair '{}' swaps an asset for itself", s)
+        }
+
+        Ok(pair)
+    }
+}
+
+impl TryFrom<String> for AssetPair {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<AssetPair> for String {
+    fn from(pair: AssetPair) -> Self {
+        pair.to_string()
+    }
+}
+
+/// The namespace makers register in at rendezvous nodes and takers discover
+/// them in.
+///
+/// Encoded as `<base>-<quote>-swap-<network>`, e.g. `xmr-btc-swap-mainnet`. The
+/// network may be any name, so that test networks other than the public
+/// testnet don't mix with it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RendezvousNamespace {
+    pair: AssetPair,
+    network: String,
+}
+
+impl RendezvousNamespace {
+    pub fn new(pair: AssetPair, network: &str) -> Result<Self> {
+        if network.is_empty()
+            || !network
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            bail!(
+                "Network '{}' must only consist of lowercase letters, digits and dashes",
+                network
+            )
+        }
+
+        let namespace = Self {
+            pair,
+            network: network.to_string(),
+        };
+
+        if namespace.to_string().len() > MAX_NAMESPACE_LENGTH {
+            bail!(
+                "Namespace '{}' is longer than {} characters",
+                namespace,
+                MAX_NAMESPACE_LENGTH
+            )
+        }
+
+        Ok(namespace)
+    }
+
+    pub fn mainnet() -> Self {
+        Self {
+            pair: AssetPair::XMR_BTC,
+            network: MAINNET.to_string(),
+        }
+    }
+
+    pub fn testnet() -> Self {
+        Self {
+            pair: AssetPair::XMR_BTC,
+            network: TESTNET.to_string(),
+        }
+    }
+
+    pub fn pair(&self) -> AssetPair {
+        self.pair
+    }
+}
+
+impl fmt::Display for RendezvousNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-swap-{}", self.pair, self.network)
+    }
+}
+
+impl FromStr for RendezvousNamespace {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (pair, network) = s.split_once("-swap-").with_context(|| {
+            format!(
+                "Namespace '{}' is not of the form <base>-<quote>-swap-<network>",
+                s
+            )
+        })?;
+
+        Self::new(pair.parse()?, network)
+    }
+}
+
+impl TryFrom<String> for RendezvousNamespace {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<RendezvousNamespace> for String {
+    fn from(namespace: RendezvousNamespace) -> Self {
+        namespace.to_string()
+    }
+}
+
+impl From<&RendezvousNamespace> for Namespace {
+    fn from(namespace: &RendezvousNamespace) -> Self {
+        Namespace::new(namespace.to_string())
+            .expect("length of namespace to be checked on construction")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_namespaces_are_backwards_compatible() {
+        assert_eq!(
+            RendezvousNamespace::mainnet().to_string(),
+            "xmr-btc-swap-mainnet"
+        );
+        assert_eq!(
+            RendezvousNamespace::testnet().to_string(),
+            "xmr-btc-swap-testnet"
+        );
+    }
+
+    #[test]
+    fn namespace_roundtrips_through_string() {
+        let namespace = "xmr-btc-swap-my-test-net"
+            .parse::<RendezvousNamespace>()
+            .unwrap();
+
+        assert_eq!(namespace.pair(), AssetPair::XMR_BTC);
+        assert_eq!(namespace.to_string(), "xmr-btc-swap-my-test-net");
+    }
+
+    #[test]
+    fn invalid_namespaces_are_rejected() {
+        assert!("xmr-btc-mainnet".parse::<RendezvousNamespace>().is_err());
+        assert!("xmr-xmr-swap-mainnet"
+            .parse::<RendezvousNamespace>()
+            .is_err());
+        assert!("xmr-btc-swap-Main Net"
+            .parse::<RendezvousNamespace>()
+            .is_err());
+        assert!(RendezvousNamespace::new(AssetPair::XMR_BTC, &"a".repeat(250)).is_err());
+    }
+
+    #[test]
+    fn asset_pair_is_serialized_as_string() {
+        assert_eq!(
+            serde_json::to_string(&AssetPair::XMR_BTC).unwrap(),
+            "\"xmr-btc\""
+        );
+        assert_eq!(
+            serde_json::from_str::<AssetPair>("\"xmr-btc\"").unwrap(),
+            AssetPair::XMR_BTC
+        );
+    }
+}
@@ -1,12 +1,19 @@
-use crate::monero;
-use anyhow::{Context, Result};
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
+use crate::{env, monero};
+use anyhow::{bail, Context, Result};
 use libp2p::core::upgrade;
 use libp2p::swarm::NegotiatedSubstream;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
+pub mod receipt;
+pub mod sell_xmr;
 
 pub const BUF_SIZE: usize = 1024 * 1024;
 
@@ -24,6 +31,15 @@ pub mod protocol {
         )
     }
 
+    /// The swap setup for swaps in which the dialer sells XMR, i.e. the dialer
+    /// takes the role of Alice.
+    pub fn sell_xmr() -> SwapSetup {
+        from_fn(
+            b"/comit/xmr/btc/swap_setup/sell_xmr/1.0.0",
+            Box::new(|socket, _| future::ready(Ok(socket))),
+        )
+    }
+
     pub type SwapSetup = FromFnUpgrade<
         &'static [u8],
         Box<
@@ -50,11 +66,260 @@ pub struct SpotPriceRequest {
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub btc: bitcoin::Amount,
     pub blockchain_network: BlockchainNetwork,
+    /// The timelocks Bob proposes for the swap, if not given the timelocks of
+    /// the network are used.
+    #[serde(default)]
+    pub timelocks: Option<Timelocks>,
+    /// The confirmations of the Monero lock transaction Bob waits for, if not
+    /// given the confirmations of the network are used.
+    #[serde(default)]
+    pub confirmation_schedule: Option<ConfirmationSchedule>,
+    /// Whether Bob wants the seller to sign the quote and the swap, sellers
+    /// that don't sign receipts ignore it.
+    #[serde(default)]
+    pub receipts: bool,
+}
+
+/// The timelocks of a swap, negotiated when it is set up.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Timelocks {
+    pub cancel: CancelTimelock,
+    pub punish: PunishTimelock,
+}
+
+impl Timelocks {
+    pub fn of_network(env_config: &env::Config) -> Self {
+        Self {
+            cancel: env_config.bitcoin_cancel_timelock,
+            punish: env_config.bitcoin_punish_timelock,
+        }
+    }
+
+    /// The most confirmations of the Monero lock transaction Bob may wait
+    /// for, so that the swap can still be redeemed in the second half of the
+    /// cancel timelock.
+    pub fn max_monero_confirmations(&self, env_config: &env::Config) -> u64 {
+        let cancel =
+            u64::from(u32::from(self.cancel)) * env_config.bitcoin_avg_block_time.as_secs();
+        let monero_block = env_config.monero_avg_block_time.as_secs().max(1);
+
+        cancel / 2 / monero_block
+    }
+
+    /// The timelocks Bob proposes for a swap with a seller that advertised the
+    /// given ranges.
+    ///
+    /// Returns `None` if neither timelock was chosen, in that case the
+    /// timelocks of the network are used without proposing them so that
+    /// sellers which don't negotiate timelocks are still supported.
+    pub fn propose(
+        cancel: Option<u32>,
+        punish: Option<u32>,
+        advertised: Option<TimelockRanges>,
+        env_config: &env::Config,
+    ) -> Result<Option<Self>> {
+        if cancel.is_none() && punish.is_none() {
+            return Ok(None);
+        }
+
+        let ranges = match advertised {
+            Some(ranges) => ranges,
+            None => bail!("The seller does not support choosing the timelocks of a swap"),
+        };
+
+        let network = Self::of_network(env_config);
+        let timelocks = Self {
+            cancel: cancel.map(CancelTimelock::new).unwrap_or(network.cancel),
+            punish: punish.map(PunishTimelock::new).unwrap_or(network.punish),
+        };
+
+        if !ranges.contains(timelocks) {
+            bail!(
+                "The seller only accepts a cancel timelock of {} and a punish timelock of {}",
+                ranges.cancel,
+                ranges.punish
+            )
+        }
+
+        Ok(Some(timelocks))
+    }
+}
+
+/// A range of timelocks in blocks, including both ends.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct TimelockRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl TimelockRange {
+    pub fn exactly(blocks: impl Into<u32>) -> Self {
+        let blocks = blocks.into();
+
+        Self {
+            min: blocks,
+            max: blocks,
+        }
+    }
+
+    pub fn contains(&self, blocks: impl Into<u32>) -> bool {
+        let blocks = blocks.into();
+
+        self.min <= blocks && blocks <= self.max
+    }
+}
+
+impl fmt::Display for TimelockRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "{} blocks", self.min)
+        } else {
+            write!(f, "{} to {} blocks", self.min, self.max)
+        }
+    }
+}
+
+/// The timelocks Alice accepts, advertised in her quote.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct TimelockRanges {
+    pub cancel: TimelockRange,
+    pub punish: TimelockRange,
+}
+
+impl TimelockRanges {
+    /// Only accepts the given timelocks.
+    pub fn exactly(timelocks: Timelocks) -> Self {
+        Self {
+            cancel: TimelockRange::exactly(timelocks.cancel),
+            punish: TimelockRange::exactly(timelocks.punish),
+        }
+    }
+
+    pub fn contains(&self, timelocks: Timelocks) -> bool {
+        self.cancel.contains(timelocks.cancel) && self.punish.contains(timelocks.punish)
+    }
+}
+
+/// The confirmations of the Monero lock transaction Bob waits for before he
+/// sends the encrypted signature, depending on the XMR of the swap.
+///
+/// Small swaps can complete faster without weakening large ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "Vec<ConfirmationTier>", into = "Vec<ConfirmationTier>")]
+pub struct ConfirmationSchedule {
+    /// Ordered by amount, the first tier starts at zero.
+    tiers: Vec<ConfirmationTier>,
+}
+
+/// Applies to swaps of at least `from` XMR.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ConfirmationTier {
+    pub from: monero::Amount,
+    pub confirmations: u64,
+}
+
+impl ConfirmationSchedule {
+    /// Waits for the same confirmations in every swap.
+    pub fn fixed(confirmations: u64) -> Self {
+        Self {
+            tiers: vec![ConfirmationTier {
+                from: monero::Amount::ZERO,
+                confirmations,
+            }],
+        }
+    }
+
+    /// Swaps below the first of the tiers wait for the `default`
+    /// confirmations.
+    pub fn new(mut tiers: Vec<ConfirmationTier>, default: u64) -> Result<Self> {
+        tiers.sort_by_key(|tier| tier.from);
+        if tiers
+            .first()
+            .map_or(true, |tier| tier.from != monero::Amount::ZERO)
+        {
+            tiers.insert(0, ConfirmationTier {
+                from: monero::Amount::ZERO,
+                confirmations: default,
+            });
+        }
+
+        Self::try_from(tiers)
+    }
+
+    pub fn confirmations(&self, xmr: monero::Amount) -> u64 {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|tier| tier.from <= xmr)
+            .map_or(0, |tier| tier.confirmations)
+    }
+}
+
+impl TryFrom<Vec<ConfirmationTier>> for ConfirmationSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(tiers: Vec<ConfirmationTier>) -> Result<Self> {
+        if tiers.first().map(|tier| tier.from) != Some(monero::Amount::ZERO) {
+            bail!("The first tier of a confirmation schedule has to start at 0 XMR")
+        }
+        if tiers.windows(2).any(|pair| pair[0].from >= pair[1].from) {
+            bail!("The tiers of a confirmation schedule have to start at different amounts")
+        }
+        if tiers.iter().any(|tier| tier.confirmations == 0) {
+            bail!("A confirmation schedule has to wait for at least one confirmation")
+        }
+
+        Ok(Self { tiers })
+    }
+}
+
+impl From<ConfirmationSchedule> for Vec<ConfirmationTier> {
+    fn from(schedule: ConfirmationSchedule) -> Self {
+        schedule.tiers
+    }
+}
+
+impl FromStr for ConfirmationTier {
+    type Err = anyhow::Error;
+
+    /// Parses `<from-xmr>:<confirmations>`, e.g. `5:10`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (from, confirmations) = s
+            .split_once(':')
+            .with_context(|| format!("Expected <from-xmr>:<confirmations>, got `{}`", s))?;
+
+        Ok(Self {
+            from: monero::Amount::parse_monero(from)
+                .with_context(|| format!("Invalid amount of XMR `{}`", from))?,
+            confirmations: confirmations
+                .parse()
+                .with_context(|| format!("Invalid number of confirmations `{}`", confirmations))?,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum SpotPriceResponse {
     Xmr(monero::Amount),
+    /// Only sent if Bob asked for receipts.
+    SignedXmr {
+        xmr: monero::Amount,
+        receipt: receipt::SignedReceipt,
+    },
+    Error(SpotPriceError),
+}
+
+/// Sent by Alice to set up a swap in which she sells the given amount of XMR.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SellXmrRequest {
+    pub swap_id: Uuid,
+    pub xmr: monero::Amount,
+    pub blockchain_network: BlockchainNetwork,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SellXmrResponse {
+    Btc(#[serde(with = "::bitcoin::util::amount::serde::as_sat")] bitcoin::Amount),
     Error(SpotPriceError),
 }
 
@@ -81,11 +346,62 @@ pub enum SpotPriceError {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    /// The seller already executes as many swaps at the same time as it is
+    /// configured to.
+    Busy,
+    TimelocksNotAccepted {
+        cancel: TimelockRange,
+        punish: TimelockRange,
+    },
+    /// Bob waits for more confirmations of the Monero lock transaction than
+    /// fit into the cancel timelock.
+    MoneroConfirmationsNotAccepted {
+        max: u64,
+    },
+    /// The latest price of the seller is too old to quote a swap.
+    RateStale,
+    /// The seller only serves quotes and does not set up swaps, the amounts
+    /// are the ones the swap would have been set up with.
+    QuotesOnly {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+    },
     /// To be used for errors that cannot be explained on the CLI side (e.g.
     /// rate update problems on the seller side)
     Other,
 }
 
+/// The peer offered less than was agreed upon, in a quote or in an earlier
+/// attempt to set up the swap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OfferBelowAgreed<A> {
+    pub agreed: A,
+    pub offered: A,
+}
+
+impl<A: fmt::Display> fmt::Display for OfferBelowAgreed<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Peer offered {} instead of the agreed {}",
+            self.offered, self.agreed
+        )
+    }
+}
+
+impl<A: fmt::Debug + fmt::Display> std::error::Error for OfferBelowAgreed<A> {}
+
+/// Fails if the peer offers less than was agreed upon, a peer that offers
+/// more is taken up on it.
+pub fn check_offer<A: PartialOrd>(agreed: A, offered: A) -> Result<(), OfferBelowAgreed<A>> {
+    if offered < agreed {
+        return Err(OfferBelowAgreed { agreed, offered });
+    }
+
+    Ok(())
+}
+
 pub async fn read_cbor_message<T>(substream: &mut NegotiatedSubstream) -> Result<T>
 where
     T: DeserializeOwned,
@@ -112,3 +428,120 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{GetConfig, Mainnet, Regtest};
+
+    fn ranges() -> TimelockRanges {
+        TimelockRanges {
+            cancel: TimelockRange { min: 50, max: 200 },
+            punish: TimelockRange { min: 50, max: 100 },
+        }
+    }
+
+    #[test]
+    fn without_chosen_timelocks_nothing_is_proposed() {
+        let proposal = Timelocks::propose(None, None, None, &Regtest::get_config()).unwrap();
+
+        assert_eq!(proposal, None);
+    }
+
+    #[test]
+    fn timelocks_that_were_not_chosen_default_to_the_network() {
+        let proposal =
+            Timelocks::propose(Some(150), None, Some(ranges()), &Regtest::get_config()).unwrap();
+
+        assert_eq!(
+            proposal,
+            Some(Timelocks {
+                cancel: CancelTimelock::new(150),
+                punish: PunishTimelock::new(50),
+            })
+        );
+    }
+
+    #[test]
+    fn timelocks_outside_of_the_advertised_ranges_are_not_proposed() {
+        let result = Timelocks::propose(None, Some(101), Some(ranges()), &Regtest::get_config());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn timelocks_cannot_be_proposed_to_sellers_that_dont_advertise_ranges() {
+        let result = Timelocks::propose(Some(150), None, None, &Regtest::get_config());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn confirmations_scale_with_the_xmr_of_the_swap() {
+        let schedule =
+            ConfirmationSchedule::new(vec!["5:10".parse().unwrap(), "0.1:5".parse().unwrap()], 1)
+                .unwrap();
+
+        let xmr = |xmr: f64| monero::Amount::from_monero(xmr).unwrap();
+        assert_eq!(schedule.confirmations(xmr(0.05)), 1);
+        assert_eq!(schedule.confirmations(xmr(0.1)), 5);
+        assert_eq!(schedule.confirmations(xmr(4.9)), 5);
+        assert_eq!(schedule.confirmations(xmr(20.0)), 10);
+    }
+
+    #[test]
+    fn confirmation_schedules_have_to_be_complete() {
+        let tier = |from: u64, confirmations: u64| ConfirmationTier {
+            from: monero::Amount::from_piconero(from),
+            confirmations,
+        };
+
+        assert!(ConfirmationSchedule::try_from(vec![tier(1, 10)]).is_err());
+        assert!(ConfirmationSchedule::try_from(vec![tier(0, 1), tier(0, 10)]).is_err());
+        assert!(ConfirmationSchedule::try_from(vec![tier(0, 0)]).is_err());
+        assert!(ConfirmationSchedule::try_from(vec![tier(0, 1), tier(1, 10)]).is_ok());
+    }
+
+    #[test]
+    fn confirmations_have_to_fit_into_half_of_the_cancel_timelock() {
+        let env_config = Mainnet::get_config();
+
+        // 72 blocks of 10 minutes are 12 hours, the first half fits 180 Monero blocks
+        assert_eq!(
+            Timelocks::of_network(&env_config).max_monero_confirmations(&env_config),
+            180
+        );
+    }
+
+    #[test]
+    fn given_lower_offer_than_agreed_then_offer_below_agreed() {
+        let agreed = bitcoin::Amount::from_sat(1_000);
+        let offered = bitcoin::Amount::from_sat(999);
+
+        assert_eq!(
+            check_offer(agreed, offered),
+            Err(OfferBelowAgreed { agreed, offered })
+        );
+    }
+
+    #[test]
+    fn given_same_or_higher_offer_than_agreed_then_accepted() {
+        let agreed = monero::Amount::from_piconero(1_000);
+
+        assert_eq!(check_offer(agreed, agreed), Ok(()));
+        assert_eq!(
+            check_offer(agreed, monero::Amount::from_piconero(1_001)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn timelock_range_includes_both_ends() {
+        let range = TimelockRange { min: 50, max: 100 };
+
+        assert!(!range.contains(49u32));
+        assert!(range.contains(50u32));
+        assert!(range.contains(100u32));
+        assert!(!range.contains(101u32));
+    }
+}
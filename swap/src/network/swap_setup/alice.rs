@@ -1,11 +1,17 @@
-use crate::asb::LatestRate;
+use crate::asb::pricing::{Pricing, PricingInput, PricingStrategy};
+use crate::asb::reservations::{Reservation, Reservations};
+use crate::asb::{LatestRate, Rate};
 use crate::network::swap_setup;
+use crate::network::swap_setup::receipt::{
+    self, QuoteReceipt, Receipt, SignedReceipt, SwapReceipt,
+};
 use crate::network::swap_setup::{
-    protocol, BlockchainNetwork, SpotPriceError, SpotPriceRequest, SpotPriceResponse,
+    protocol, BlockchainNetwork, ConfirmationSchedule, SpotPriceError, SpotPriceRequest,
+    SpotPriceResponse, TimelockRanges, Timelocks,
 };
 use crate::protocol::alice::{State0, State3};
 use crate::protocol::{Message0, Message2, Message4};
-use crate::{asb, bitcoin, env, monero};
+use crate::{asb, bitcoin, env, monero, price_feed};
 use anyhow::{anyhow, Context, Result};
 use futures::future::{BoxFuture, OptionFuture};
 use futures::{AsyncWriteExt, FutureExt};
@@ -15,7 +21,7 @@ use libp2p::swarm::{
     KeepAlive, NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
     ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol,
 };
-use libp2p::{Multiaddr, PeerId};
+use libp2p::{identity, Multiaddr, PeerId};
 use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::task::Poll;
@@ -27,12 +33,16 @@ use void::Void;
 #[allow(clippy::large_enum_variant)]
 pub enum OutEvent {
     Initiated {
-        send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
+        peer_id: PeerId,
+        send_wallet_snapshot: bmrng::RequestReceiver<(bitcoin::Amount, Rate), WalletSnapshot>,
     },
     Completed {
         peer_id: PeerId,
         swap_id: Uuid,
         state3: State3,
+        reservation: Option<Reservation>,
+        slot: Option<Reservation>,
+        pricing: Pricing,
     },
     Error {
         peer_id: PeerId,
@@ -42,9 +52,14 @@ pub enum OutEvent {
 
 #[derive(Debug)]
 pub struct WalletSnapshot {
+    /// The balance that is not reserved for other swaps.
     balance: monero::Amount,
     lock_fee: monero::Amount,
 
+    /// The XMR quoted for the swap, priced with the balance at hand.
+    xmr: monero::Amount,
+    pricing: Pricing,
+
     // TODO: Consider using the same address for punish and redeem (they are mutually exclusive, so
     // effectively the address will only be used once)
     redeem_address: bitcoin::Address,
@@ -52,53 +67,217 @@ pub struct WalletSnapshot {
 
     redeem_fee: bitcoin::Amount,
     punish_fee: bitcoin::Amount,
+
+    ongoing_swaps: OngoingSwaps,
+
+    /// Reserves the XMR of the swap until it is locked, if the balance
+    /// suffices.
+    reservation: Option<Reservation>,
+    /// Counts the swap setup towards the concurrency limits until the swap is
+    /// started.
+    slot: Option<Reservation>,
+
+    /// Whether the swap is declined once it is priced.
+    quotes_only: bool,
+    /// Whether the swap is declined because quotes are paused.
+    quotes_paused: bool,
 }
 
 impl WalletSnapshot {
+    /// Prices the swap with the given strategy based on the balance and
+    /// captures the wallets for it.
+    #[allow(clippy::too_many_arguments)]
     pub async fn capture(
         bitcoin_wallet: &bitcoin::Wallet,
         monero_wallet: &monero::Wallet,
+        reservations: &mut Reservations,
         transfer_amount: bitcoin::Amount,
+        rate: Rate,
+        pricing_strategy: &dyn PricingStrategy,
+        recent_volume: bitcoin::Amount,
+        ongoing_swaps: OngoingSwaps,
     ) -> Result<Self> {
-        let balance = monero_wallet.get_balance().await?;
+        // The reservations are summed up before the balance is fetched, a swap that
+        // locks its XMR in between is counted twice rather than not at all.
+        let reserved = reservations.reserved_xmr();
+        let balance = monero::Amount::from_piconero(
+            monero_wallet
+                .lockable_balance()
+                .await?
+                .as_piconero()
+                .saturating_sub(reserved.as_piconero()),
+        );
+
+        let input = PricingInput {
+            btc: transfer_amount,
+            inventory: balance,
+            recent_volume,
+        };
+        let rate = rate.with_additional_spread(pricing_strategy.additional_spread(&input));
+        let xmr = rate
+            .sell_quote(transfer_amount)
+            .context("Failed to calculate quote")?;
+        let pricing = Pricing {
+            input,
+            spread: rate.ask_spread(),
+        };
+
         let redeem_address = bitcoin_wallet.new_address().await?;
         let punish_address = bitcoin_wallet.new_address().await?;
         let redeem_fee = bitcoin_wallet
-            .estimate_fee(bitcoin::TxRedeem::weight(), transfer_amount)
+            .estimate_fee(
+                bitcoin::TxKind::Redeem,
+                bitcoin::TxRedeem::weight(),
+                transfer_amount,
+            )
             .await?;
         let punish_fee = bitcoin_wallet
-            .estimate_fee(bitcoin::TxPunish::weight(), transfer_amount)
+            .estimate_fee(
+                bitcoin::TxKind::Punish,
+                bitcoin::TxPunish::weight(),
+                transfer_amount,
+            )
             .await?;
 
+        let lock_fee = monero::MONERO_FEE;
+        let reservation = if balance >= xmr + lock_fee {
+            Some(reservations.reserve_xmr(xmr + lock_fee))
+        } else {
+            None
+        };
+
         Ok(Self {
             balance,
-            lock_fee: monero::MONERO_FEE,
+            lock_fee,
+            xmr,
+            pricing,
             redeem_address,
             punish_address,
             redeem_fee,
             punish_fee,
+            ongoing_swaps,
+            reservation,
+            slot: None,
+            quotes_only: false,
+            quotes_paused: false,
         })
     }
+
+    /// Declines the swap with the amounts it would have been set up with,
+    /// regardless of the balance.
+    pub fn with_quotes_only(self, quotes_only: bool) -> Self {
+        Self {
+            quotes_only,
+            ..self
+        }
+    }
+
+    /// Declines the swap like a shut down ASB, a taker that got a quote before
+    /// quotes were paused must not start a swap with it.
+    pub fn with_quotes_paused(self, quotes_paused: bool) -> Self {
+        Self {
+            quotes_paused,
+            ..self
+        }
+    }
+
+    /// Holds on to the slot of the swap setup until the swap is started, or
+    /// until the setup fails.
+    pub fn with_slot(self, slot: Reservation) -> Self {
+        Self {
+            slot: Some(slot),
+            ..self
+        }
+    }
+
+    fn check(
+        &self,
+        btc: bitcoin::Amount,
+        concurrency_limits: ConcurrencyLimits,
+    ) -> Result<(), Error> {
+        if self.quotes_paused {
+            return Err(Error::QuotesPaused);
+        }
+
+        if self.quotes_only {
+            return Err(Error::QuotesOnly { btc, xmr: self.xmr });
+        }
+
+        concurrency_limits.check(self.ongoing_swaps)?;
+
+        if self.balance < self.xmr + self.lock_fee {
+            return Err(Error::BalanceTooLow {
+                balance: self.balance,
+                buy: btc,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of swaps the ASB is currently executing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OngoingSwaps {
+    pub total: usize,
+    /// The swaps executed with the peer requesting a new swap.
+    pub with_peer: usize,
+}
+
+/// Bounds the number of swaps that are executed at the same time.
+///
+/// Every swap locks up XMR until it is finished, limiting the number of
+/// concurrent swaps prevents a burst of takers from tying up the whole balance.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConcurrencyLimits {
+    pub max_swaps: Option<usize>,
+    pub max_swaps_per_peer: Option<usize>,
+}
+
+impl ConcurrencyLimits {
+    pub(crate) fn check(&self, ongoing_swaps: OngoingSwaps) -> Result<(), Error> {
+        if let Some(max) = self.max_swaps {
+            if ongoing_swaps.total >= max {
+                return Err(Error::TooManySwaps { max });
+            }
+        }
+
+        if let Some(max) = self.max_swaps_per_peer {
+            if ongoing_swaps.with_peer >= max {
+                return Err(Error::TooManySwapsWithPeer { max });
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl From<OutEvent> for asb::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
             OutEvent::Initiated {
+                peer_id,
                 send_wallet_snapshot,
             } => asb::OutEvent::SwapSetupInitiated {
+                peer_id,
                 send_wallet_snapshot,
             },
             OutEvent::Completed {
                 peer_id: bob_peer_id,
                 swap_id,
                 state3,
+                reservation,
+                slot,
+                pricing,
             } => asb::OutEvent::SwapSetupCompleted {
                 peer_id: bob_peer_id,
                 swap_id,
                 state3,
+                reservation,
+                slot,
+                pricing,
             },
-            OutEvent::Error { peer_id, error } => asb::OutEvent::Failure {
+            OutEvent::Error { peer_id, error } => asb::OutEvent::SwapSetupFailed {
                 peer: peer_id,
                 error: anyhow!(error),
             },
@@ -112,28 +291,55 @@ pub struct Behaviour<LR> {
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
     env_config: env::Config,
+    timelock_ranges: TimelockRanges,
 
     latest_rate: LR,
     resume_only: bool,
+    concurrency_limits: ConcurrencyLimits,
+    /// Signs the receipts Bob asks for.
+    identity: identity::Keypair,
 }
 
 impl<LR> Behaviour<LR> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
         env_config: env::Config,
+        timelock_ranges: TimelockRanges,
         latest_rate: LR,
         resume_only: bool,
+        concurrency_limits: ConcurrencyLimits,
+        identity: identity::Keypair,
     ) -> Self {
         Self {
             events: Default::default(),
             min_buy,
             max_buy,
             env_config,
+            timelock_ranges,
             latest_rate,
             resume_only,
+            concurrency_limits,
+            identity,
         }
     }
+
+    /// The timelocks accepted if Bob proposes his own, advertised in the
+    /// quote.
+    pub fn timelock_ranges(&self) -> TimelockRanges {
+        self.timelock_ranges
+    }
+
+    /// Updates the minimum and maximum amount of BTC accepted per swap.
+    ///
+    /// The limits are handed to the handler of each new connection. Peers
+    /// that are already connected keep negotiating with the limits that were
+    /// active when they connected.
+    pub fn set_limits(&mut self, min_buy: bitcoin::Amount, max_buy: bitcoin::Amount) {
+        self.min_buy = min_buy;
+        self.max_buy = max_buy;
+    }
 }
 
 impl<LR> NetworkBehaviour for Behaviour<LR>
@@ -148,8 +354,11 @@ where
             self.min_buy,
             self.max_buy,
             self.env_config,
+            self.timelock_ranges,
             self.latest_rate.clone(),
             self.resume_only,
+            self.concurrency_limits,
+            self.identity.clone(),
         )
     }
 
@@ -165,14 +374,18 @@ where
         match event {
             HandlerOutEvent::Initiated(send_wallet_snapshot) => {
                 self.events.push_back(OutEvent::Initiated {
+                    peer_id,
                     send_wallet_snapshot,
                 })
             }
-            HandlerOutEvent::Completed(Ok((swap_id, state3))) => {
+            HandlerOutEvent::Completed(Ok((swap_id, state3, reservation, slot, pricing))) => {
                 self.events.push_back(OutEvent::Completed {
                     peer_id,
                     swap_id,
                     state3,
+                    reservation,
+                    slot,
+                    pricing,
                 })
             }
             HandlerOutEvent::Completed(Err(error)) => {
@@ -194,7 +407,7 @@ where
     }
 }
 
-type InboundStream = BoxFuture<'static, Result<(Uuid, State3)>>;
+type InboundStream = BoxFuture<'static, Result<(Uuid, State3, Option<Reservation>, Pricing)>>;
 
 pub struct Handler<LR> {
     inbound_stream: OptionFuture<InboundStream>,
@@ -203,21 +416,28 @@ pub struct Handler<LR> {
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
     env_config: env::Config,
+    timelock_ranges: TimelockRanges,
 
     latest_rate: LR,
     resume_only: bool,
+    concurrency_limits: ConcurrencyLimits,
+    identity: identity::Keypair,
 
     timeout: Duration,
     keep_alive: KeepAlive,
 }
 
 impl<LR> Handler<LR> {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
         env_config: env::Config,
+        timelock_ranges: TimelockRanges,
         latest_rate: LR,
         resume_only: bool,
+        concurrency_limits: ConcurrencyLimits,
+        identity: identity::Keypair,
     ) -> Self {
         Self {
             inbound_stream: OptionFuture::from(None),
@@ -225,8 +445,11 @@ impl<LR> Handler<LR> {
             min_buy,
             max_buy,
             env_config,
+            timelock_ranges,
             latest_rate,
             resume_only,
+            concurrency_limits,
+            identity,
             timeout: Duration::from_secs(120),
             keep_alive: KeepAlive::Until(Instant::now() + Duration::from_secs(10)),
         }
@@ -236,8 +459,16 @@ impl<LR> Handler<LR> {
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum HandlerOutEvent {
-    Initiated(bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>),
-    Completed(Result<(Uuid, State3)>),
+    Initiated(bmrng::RequestReceiver<(bitcoin::Amount, Rate), WalletSnapshot>),
+    Completed(
+        Result<(
+            Uuid,
+            State3,
+            Option<Reservation>,
+            Option<Reservation>,
+            Pricing,
+        )>,
+    ),
 }
 
 impl<LR> ProtocolsHandler for Handler<LR>
@@ -263,29 +494,31 @@ where
     ) {
         self.keep_alive = KeepAlive::Yes;
 
-        let (sender, receiver) = bmrng::channel_with_timeout::<bitcoin::Amount, WalletSnapshot>(
-            1,
-            Duration::from_secs(5),
-        );
+        let (sender, receiver) = bmrng::channel_with_timeout::<
+            (bitcoin::Amount, Rate),
+            WalletSnapshot,
+        >(1, Duration::from_secs(5));
         let resume_only = self.resume_only;
+        let concurrency_limits = self.concurrency_limits;
         let min_buy = self.min_buy;
         let max_buy = self.max_buy;
         let latest_rate = self.latest_rate.latest_rate();
         let env_config = self.env_config;
+        let timelock_ranges = self.timelock_ranges;
+        let identity = self.identity.clone();
 
         let protocol = tokio::time::timeout(self.timeout, async move {
             let request = swap_setup::read_cbor_message::<SpotPriceRequest>(&mut substream)
                 .await
                 .context("Failed to read spot price request")?;
 
-            let wallet_snapshot = sender
-                .send_receive(request.btc)
-                .await
-                .context("Failed to receive wallet snapshot")?;
+            let timelocks = request
+                .timelocks
+                .unwrap_or_else(|| Timelocks::of_network(&env_config));
 
             // wrap all of these into another future so we can `return` from all the
             // different blocks
-            let validate = async {
+            let quote = async {
                 if resume_only {
                     return Err(Error::ResumeOnlyMode);
                 };
@@ -302,6 +535,13 @@ where
                     });
                 }
 
+                if !timelock_ranges.contains(timelocks) {
+                    return Err(Error::TimelocksNotAccepted {
+                        proposed: timelocks,
+                        accepted: timelock_ranges,
+                    });
+                }
+
                 let btc = request.btc;
 
                 if btc < min_buy {
@@ -318,36 +558,68 @@ where
                     });
                 }
 
-                let rate = latest_rate.map_err(|e| Error::LatestRateFetchFailed(Box::new(e)))?;
-                let xmr = rate
-                    .sell_quote(btc)
-                    .map_err(Error::SellQuoteCalculationFailed)?;
+                let rate = latest_rate.map_err(Error::from_latest_rate_error)?;
 
-                if wallet_snapshot.balance < xmr + wallet_snapshot.lock_fee {
-                    return Err(Error::BalanceTooLow {
-                        balance: wallet_snapshot.balance,
-                        buy: btc,
-                    });
-                }
+                Ok(rate)
+            };
 
-                Ok(xmr)
+            // The swap is priced with the balance at hand when the wallet snapshot is
+            // captured, which also reserves the XMR of the swap.
+            let result = match quote.await {
+                Ok(rate) => {
+                    let wallet_snapshot = sender
+                        .send_receive((request.btc, rate))
+                        .await
+                        .context("Failed to receive wallet snapshot")?;
+
+                    wallet_snapshot
+                        .check(request.btc, concurrency_limits)
+                        .and_then(|()| {
+                            check_monero_confirmations(
+                                request.confirmation_schedule.as_ref(),
+                                wallet_snapshot.xmr,
+                                timelocks,
+                                &env_config,
+                            )
+                        })
+                        .map(|()| (wallet_snapshot.xmr, wallet_snapshot))
+                }
+                Err(error) => Err(error),
             };
 
-            let result = validate.await;
+            let response = match &result {
+                Ok((xmr, _)) if request.receipts => {
+                    let receipt = Receipt::Quote(QuoteReceipt {
+                        btc: request.btc,
+                        xmr: *xmr,
+                        timelocks,
+                        blockchain_network: request.blockchain_network,
+                        quoted_at: receipt::now(),
+                    });
 
-            swap_setup::write_cbor_message(
-                &mut substream,
-                SpotPriceResponse::from_result_ref(&result),
-            )
-            .await
-            .context("Failed to write spot price response")?;
+                    SpotPriceResponse::SignedXmr {
+                        xmr: *xmr,
+                        receipt: SignedReceipt::sign(&receipt, &identity)?,
+                    }
+                }
+                _ => SpotPriceResponse::from_result_ref(&result),
+            };
+            let signs_receipts = matches!(response, SpotPriceResponse::SignedXmr { .. });
 
-            let xmr = result?;
+            swap_setup::write_cbor_message(&mut substream, response)
+                .await
+                .context("Failed to write spot price response")?;
+
+            let (xmr, mut wallet_snapshot) = result?;
+            let reservation = wallet_snapshot.reservation.take();
+            let slot = wallet_snapshot.slot.take();
+            let pricing = wallet_snapshot.pricing;
 
             let state0 = State0::new(
                 request.btc,
                 xmr,
-                env_config,
+                timelocks.cancel,
+                timelocks.punish,
                 wallet_snapshot.redeem_address,
                 wallet_snapshot.punish_address,
                 wallet_snapshot.redeem_fee,
@@ -355,34 +627,24 @@ where
                 &mut rand::thread_rng(),
             );
 
-            let message0 = swap_setup::read_cbor_message::<Message0>(&mut substream)
-                .await
-                .context("Failed to read message0")?;
-            let (swap_id, state1) = state0
-                .receive(message0)
-                .context("Failed to transition state0 -> state1 using message0")?;
+            let (swap_id, state3) = exchange_messages(&mut substream, state0).await?;
 
-            swap_setup::write_cbor_message(&mut substream, state1.next_message())
-                .await
-                .context("Failed to send message1")?;
-
-            let message2 = swap_setup::read_cbor_message::<Message2>(&mut substream)
-                .await
-                .context("Failed to read message2")?;
-            let state2 = state1
-                .receive(message2)
-                .context("Failed to transition state1 -> state2 using message2")?;
-
-            swap_setup::write_cbor_message(&mut substream, state2.next_message())
-                .await
-                .context("Failed to send message3")?;
-
-            let message4 = swap_setup::read_cbor_message::<Message4>(&mut substream)
+            if signs_receipts {
+                let receipt = Receipt::Swap(SwapReceipt {
+                    swap_id,
+                    btc: request.btc,
+                    xmr,
+                    timelocks,
+                    set_up_at: receipt::now(),
+                });
+
+                swap_setup::write_cbor_message(
+                    &mut substream,
+                    SignedReceipt::sign(&receipt, &identity)?,
+                )
                 .await
-                .context("Failed to read message4")?;
-            let state3 = state2
-                .receive(message4)
-                .context("Failed to transition state2 -> state3 using message4")?;
+                .context("Failed to send the receipt of the swap")?;
+            }
 
             substream
                 .flush()
@@ -393,7 +655,7 @@ where
                 .await
                 .context("Failed to close substream after all messages were sent")?;
 
-            Ok((swap_id, state3))
+            Ok((swap_id, state3, reservation, slot, pricing))
         });
 
         let max_seconds = self.timeout.as_secs();
@@ -456,10 +718,69 @@ where
     }
 }
 
+/// Only sets up swaps in which Bob sends the encrypted signature early enough
+/// to redeem the Bitcoin before the cancel timelock expires.
+fn check_monero_confirmations(
+    schedule: Option<&ConfirmationSchedule>,
+    xmr: monero::Amount,
+    timelocks: Timelocks,
+    env_config: &env::Config,
+) -> Result<(), Error> {
+    let proposed = match schedule {
+        Some(schedule) => schedule.confirmations(xmr),
+        None => return Ok(()),
+    };
+    let max = timelocks.max_monero_confirmations(env_config);
+
+    if proposed > max {
+        return Err(Error::MoneroConfirmationsNotAccepted { proposed, max });
+    }
+
+    Ok(())
+}
+
+/// Exchanges the messages that set up the swap with Bob once the amounts were
+/// agreed upon, regardless of which of the two opened the substream.
+pub(crate) async fn exchange_messages(
+    substream: &mut NegotiatedSubstream,
+    state0: State0,
+) -> Result<(Uuid, State3)> {
+    let message0 = swap_setup::read_cbor_message::<Message0>(substream)
+        .await
+        .context("Failed to read message0")?;
+    let (swap_id, state1) = state0
+        .receive(message0)
+        .context("Failed to transition state0 -> state1 using message0")?;
+
+    swap_setup::write_cbor_message(substream, state1.next_message())
+        .await
+        .context("Failed to send message1")?;
+
+    let message2 = swap_setup::read_cbor_message::<Message2>(substream)
+        .await
+        .context("Failed to read message2")?;
+    let state2 = state1
+        .receive(message2)
+        .context("Failed to transition state1 -> state2 using message2")?;
+
+    swap_setup::write_cbor_message(substream, state2.next_message())
+        .await
+        .context("Failed to send message3")?;
+
+    let message4 = swap_setup::read_cbor_message::<Message4>(substream)
+        .await
+        .context("Failed to read message4")?;
+    let state3 = state2
+        .receive(message4)
+        .context("Failed to transition state2 -> state3 using message4")?;
+
+    Ok((swap_id, state3))
+}
+
 impl SpotPriceResponse {
-    pub fn from_result_ref(result: &Result<monero::Amount, Error>) -> Self {
+    pub fn from_result_ref(result: &Result<(monero::Amount, WalletSnapshot), Error>) -> Self {
         match result {
-            Ok(amount) => SpotPriceResponse::Xmr(*amount),
+            Ok((xmr, _)) => SpotPriceResponse::Xmr(*xmr),
             Err(error) => SpotPriceResponse::Error(error.to_error_response()),
         }
     }
@@ -469,6 +790,13 @@ impl SpotPriceResponse {
 pub enum Error {
     #[error("ASB is running in resume-only mode")]
     ResumeOnlyMode,
+    #[error("Quotes are paused")]
+    QuotesPaused,
+    #[error("ASB only serves quotes, declined swapping {btc} for {xmr}")]
+    QuotesOnly {
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+    },
     #[error("Amount {buy} below minimum {min}")]
     AmountBelowMinimum {
         min: bitcoin::Amount,
@@ -479,6 +807,10 @@ pub enum Error {
         max: bitcoin::Amount,
         buy: bitcoin::Amount,
     },
+    #[error("Already executing the maximum of {max} concurrent swaps")]
+    TooManySwaps { max: usize },
+    #[error("Already executing the maximum of {max} concurrent swaps with this peer")]
+    TooManySwapsWithPeer { max: usize },
     #[error("Balance {balance} too low to fulfill swapping {buy}")]
     BalanceTooLow {
         balance: monero::Amount,
@@ -486,19 +818,32 @@ pub enum Error {
     },
     #[error("Failed to fetch latest rate")]
     LatestRateFetchFailed(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
-    #[error("Failed to calculate quote")]
-    SellQuoteCalculationFailed(#[source] anyhow::Error),
+    #[error("The latest rate is {}s old", age.as_secs())]
+    RateStale { age: Duration },
     #[error("Blockchain networks did not match, we are on {asb:?}, but request from {cli:?}")]
     BlockchainNetworkMismatch {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    #[error("Proposed timelocks {proposed:?} are not within the accepted ranges {accepted:?}")]
+    TimelocksNotAccepted {
+        proposed: Timelocks,
+        accepted: TimelockRanges,
+    },
+    #[error(
+        "Proposed {proposed} confirmations of the Monero lock transaction, accepting at most {max}"
+    )]
+    MoneroConfirmationsNotAccepted { proposed: u64, max: u64 },
 }
 
 impl Error {
     pub fn to_error_response(&self) -> SpotPriceError {
         match self {
-            Error::ResumeOnlyMode => SpotPriceError::NoSwapsAccepted,
+            Error::ResumeOnlyMode | Error::QuotesPaused => SpotPriceError::NoSwapsAccepted,
+            Error::QuotesOnly { btc, xmr } => SpotPriceError::QuotesOnly {
+                btc: *btc,
+                xmr: *xmr,
+            },
             Error::AmountBelowMinimum { min, buy } => SpotPriceError::AmountBelowMinimum {
                 min: *min,
                 buy: *buy,
@@ -507,6 +852,7 @@ impl Error {
                 max: *max,
                 buy: *buy,
             },
+            Error::TooManySwaps { .. } | Error::TooManySwapsWithPeer { .. } => SpotPriceError::Busy,
             Error::BalanceTooLow { buy, .. } => SpotPriceError::BalanceTooLow { buy: *buy },
             Error::BlockchainNetworkMismatch { cli, asb } => {
                 SpotPriceError::BlockchainNetworkMismatch {
@@ -514,9 +860,132 @@ impl Error {
                     asb: *asb,
                 }
             }
-            Error::LatestRateFetchFailed(_) | Error::SellQuoteCalculationFailed(_) => {
-                SpotPriceError::Other
+            Error::TimelocksNotAccepted { accepted, .. } => SpotPriceError::TimelocksNotAccepted {
+                cancel: accepted.cancel,
+                punish: accepted.punish,
+            },
+            Error::MoneroConfirmationsNotAccepted { max, .. } => {
+                SpotPriceError::MoneroConfirmationsNotAccepted { max: *max }
             }
+            Error::RateStale { .. } => SpotPriceError::RateStale,
+            Error::LatestRateFetchFailed(_) => SpotPriceError::Other,
         }
     }
+
+    fn from_latest_rate_error(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        match price_feed::stale_age(&error) {
+            Some(age) => Error::RateStale { age },
+            None => Error::LatestRateFetchFailed(Box::new(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_no_limits_then_any_number_of_swaps_is_accepted() {
+        let limits = ConcurrencyLimits::default();
+
+        let result = limits.check(OngoingSwaps {
+            total: 100,
+            with_peer: 100,
+        });
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn given_global_limit_reached_then_busy() {
+        let limits = ConcurrencyLimits {
+            max_swaps: Some(2),
+            max_swaps_per_peer: None,
+        };
+
+        let error = limits
+            .check(OngoingSwaps {
+                total: 2,
+                with_peer: 0,
+            })
+            .unwrap_err();
+
+        assert!(matches!(error, Error::TooManySwaps { max: 2 }));
+        assert!(matches!(error.to_error_response(), SpotPriceError::Busy));
+    }
+
+    #[test]
+    fn given_peer_limit_reached_then_busy() {
+        let limits = ConcurrencyLimits {
+            max_swaps: Some(10),
+            max_swaps_per_peer: Some(1),
+        };
+
+        let error = limits
+            .check(OngoingSwaps {
+                total: 3,
+                with_peer: 1,
+            })
+            .unwrap_err();
+
+        assert!(matches!(error, Error::TooManySwapsWithPeer { max: 1 }));
+        assert!(matches!(error.to_error_response(), SpotPriceError::Busy));
+    }
+
+    #[test]
+    fn given_below_limits_then_swap_is_accepted() {
+        let limits = ConcurrencyLimits {
+            max_swaps: Some(10),
+            max_swaps_per_peer: Some(2),
+        };
+
+        let result = limits.check(OngoingSwaps {
+            total: 9,
+            with_peer: 1,
+        });
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn given_stale_rate_then_rate_stale() {
+        let error = Error::from_latest_rate_error(price_feed::Error::Stale {
+            age: Duration::from_secs(600),
+        });
+
+        assert!(matches!(error, Error::RateStale { age } if age == Duration::from_secs(600)));
+        assert!(matches!(
+            error.to_error_response(),
+            SpotPriceError::RateStale
+        ));
+    }
+
+    #[test]
+    fn given_rate_unavailable_then_other() {
+        let error = Error::from_latest_rate_error(price_feed::Error::Unavailable);
+
+        assert!(matches!(error, Error::LatestRateFetchFailed(_)));
+        assert!(matches!(error.to_error_response(), SpotPriceError::Other));
+    }
+
+    #[test]
+    fn quotes_only_tells_bob_the_amounts_of_the_declined_swap() {
+        let btc = bitcoin::Amount::from_sat(1_000_000);
+        let xmr = monero::Amount::from_piconero(750_000_000_000);
+
+        let error = Error::QuotesOnly { btc, xmr };
+
+        assert!(matches!(
+            error.to_error_response(),
+            SpotPriceError::QuotesOnly { btc: b, xmr: x } if b == btc && x == xmr
+        ));
+    }
+
+    #[test]
+    fn given_quotes_paused_then_no_swaps_accepted() {
+        assert!(matches!(
+            Error::QuotesPaused.to_error_response(),
+            SpotPriceError::NoSwapsAccepted
+        ));
+    }
 }
@@ -1,6 +1,8 @@
+use crate::network::swap_setup::receipt::SignedReceipt;
 use crate::network::swap_setup::{
-    protocol, read_cbor_message, write_cbor_message, BlockchainNetwork, SpotPriceError,
-    SpotPriceRequest, SpotPriceResponse,
+    check_offer, protocol, read_cbor_message, write_cbor_message, BlockchainNetwork,
+    ConfirmationSchedule, OfferBelowAgreed, SpotPriceError, SpotPriceRequest, SpotPriceResponse,
+    TimelockRange, Timelocks,
 };
 use crate::protocol::bob::{State0, State2};
 use crate::protocol::{Message1, Message3};
@@ -20,6 +22,7 @@ use std::collections::VecDeque;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 use void::Void;
 
@@ -124,6 +127,21 @@ pub struct NewSwap {
     pub tx_refund_fee: bitcoin::Amount,
     pub tx_cancel_fee: bitcoin::Amount,
     pub bitcoin_refund_address: bitcoin::Address,
+    /// The spot price negotiated in an earlier attempt to set up this swap.
+    ///
+    /// If Alice quotes less XMR this time, the swap setup is aborted.
+    pub negotiated_xmr: Option<monero::Amount>,
+    /// The timelocks proposed to Alice, if not given the timelocks of the
+    /// network are used.
+    pub timelocks: Option<Timelocks>,
+    /// The confirmations of the Monero lock transaction proposed to Alice, if
+    /// not given the confirmations of the network are used.
+    pub confirmation_schedule: Option<ConfirmationSchedule>,
+    /// Receives the spot price as soon as Alice quoted it.
+    pub spot_price: oneshot::Sender<monero::Amount>,
+    /// Receives the receipts Alice signed for the quote and the swap, sellers
+    /// that don't sign receipts send none.
+    pub receipts: mpsc::UnboundedSender<SignedReceipt>,
 }
 
 #[derive(Debug)]
@@ -161,33 +179,64 @@ impl ProtocolsHandler for Handler {
                     bitcoin: env_config.bitcoin_network,
                     monero: env_config.monero_network,
                 },
+                timelocks: info.timelocks,
+                confirmation_schedule: info.confirmation_schedule.clone(),
+                receipts: true,
             })
             .await?;
 
-            let xmr = Result::from(read_cbor_message::<SpotPriceResponse>(&mut substream).await?)?;
+            let response = read_cbor_message::<SpotPriceResponse>(&mut substream).await?;
+            let quote_receipt = match &response {
+                SpotPriceResponse::SignedXmr { receipt, .. } => Some(receipt.clone()),
+                _ => None,
+            };
+            let xmr = Result::from(response)?;
+
+            // Alice must not lower the price she quoted in an earlier attempt to set up
+            // the swap.
+            if let Some(negotiated) = info.negotiated_xmr {
+                check_offer(negotiated, xmr).map_err(Error::SpotPriceChanged)?;
+            }
+
+            let _ = info.spot_price.send(xmr);
 
+            let timelocks = info
+                .timelocks
+                .unwrap_or_else(|| Timelocks::of_network(&env_config));
+            let monero_confirmations = info
+                .confirmation_schedule
+                .map_or(env_config.monero_finality_confirmations, |schedule| {
+                    schedule.confirmations(xmr)
+                });
             let state0 = State0::new(
                 info.swap_id,
                 &mut rand::thread_rng(),
                 info.btc,
                 xmr,
-                env_config.bitcoin_cancel_timelock,
-                env_config.bitcoin_punish_timelock,
+                timelocks.cancel,
+                timelocks.punish,
                 info.bitcoin_refund_address,
-                env_config.monero_finality_confirmations,
+                monero_confirmations,
                 info.tx_refund_fee,
                 info.tx_cancel_fee,
             );
 
-            write_cbor_message(&mut substream, state0.next_message()).await?;
-            let message1 = read_cbor_message::<Message1>(&mut substream).await?;
-            let state1 = state0.receive(bitcoin_wallet.as_ref(), message1).await?;
-
-            write_cbor_message(&mut substream, state1.next_message()).await?;
-            let message3 = read_cbor_message::<Message3>(&mut substream).await?;
-            let state2 = state1.receive(message3)?;
-
-            write_cbor_message(&mut substream, state2.next_message()).await?;
+            let state2 = exchange_messages(&mut substream, state0, bitcoin_wallet.as_ref()).await?;
+
+            // Alice signs the swap only if she signed the quote. The swap is set up either
+            // way, a missing receipt does not fail it.
+            if let Some(quote_receipt) = quote_receipt {
+                let _ = info.receipts.send(quote_receipt);
+
+                match read_cbor_message::<SignedReceipt>(&mut substream).await {
+                    Ok(swap_receipt) => {
+                        let _ = info.receipts.send(swap_receipt);
+                    }
+                    Err(error) => {
+                        tracing::warn!(swap_id = %info.swap_id, "Failed to receive the receipt of the swap: {:#}", error)
+                    }
+                }
+            }
 
             substream.flush().await?;
             substream.close().await?;
@@ -249,10 +298,31 @@ impl ProtocolsHandler for Handler {
     }
 }
 
+/// Exchanges the messages that set up the swap with Alice once the amounts
+/// were agreed upon, regardless of which of the two opened the substream.
+pub(crate) async fn exchange_messages(
+    substream: &mut NegotiatedSubstream,
+    state0: State0,
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> Result<State2> {
+    write_cbor_message(substream, state0.next_message()).await?;
+    let message1 = read_cbor_message::<Message1>(substream).await?;
+    let state1 = state0.receive(bitcoin_wallet, message1).await?;
+
+    write_cbor_message(substream, state1.next_message()).await?;
+    let message3 = read_cbor_message::<Message3>(substream).await?;
+    let state2 = state1.receive(message3)?;
+
+    write_cbor_message(substream, state2.next_message()).await?;
+
+    Ok(state2)
+}
+
 impl From<SpotPriceResponse> for Result<monero::Amount, Error> {
     fn from(response: SpotPriceResponse) -> Self {
         match response {
             SpotPriceResponse::Xmr(amount) => Ok(amount),
+            SpotPriceResponse::SignedXmr { xmr, .. } => Ok(xmr),
             SpotPriceResponse::Error(e) => Err(e.into()),
         }
     }
@@ -275,12 +345,36 @@ pub enum Error {
     #[error("Seller's XMR balance is currently too low to fulfill the swap request to buy {buy}, please try again later")]
     BalanceTooLow { buy: bitcoin::Amount },
 
+    #[error("Seller is currently processing too many swaps, please try again later")]
+    Busy,
+
     #[error("Seller blockchain network {asb:?} setup did not match your blockchain network setup {cli:?}")]
     BlockchainNetworkMismatch {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
 
+    #[error("Seller only accepts a cancel timelock of {cancel} and a punish timelock of {punish}")]
+    TimelocksNotAccepted {
+        cancel: TimelockRange,
+        punish: TimelockRange,
+    },
+
+    #[error("Seller only accepts swaps that wait for at most {max} confirmations of the Monero lock transaction")]
+    MoneroConfirmationsNotAccepted { max: u64 },
+
+    #[error("Seller's exchange rate is outdated, please try again later")]
+    RateStale,
+
+    #[error("Seller only serves quotes at the moment and does not accept swaps, it would have sent {xmr} for {btc}")]
+    QuotesOnly {
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+    },
+
+    #[error(transparent)]
+    SpotPriceChanged(OfferBelowAgreed<monero::Amount>),
+
     #[error("Failed to complete swap setup within {seconds}s")]
     Timeout { seconds: u64 },
 
@@ -304,6 +398,15 @@ impl From<SpotPriceError> for Error {
             SpotPriceError::BlockchainNetworkMismatch { cli, asb } => {
                 Error::BlockchainNetworkMismatch { cli, asb }
             }
+            SpotPriceError::Busy => Error::Busy,
+            SpotPriceError::TimelocksNotAccepted { cancel, punish } => {
+                Error::TimelocksNotAccepted { cancel, punish }
+            }
+            SpotPriceError::MoneroConfirmationsNotAccepted { max } => {
+                Error::MoneroConfirmationsNotAccepted { max }
+            }
+            SpotPriceError::RateStale => Error::RateStale,
+            SpotPriceError::QuotesOnly { btc, xmr } => Error::QuotesOnly { btc, xmr },
             SpotPriceError::Other => Error::Other,
         }
     }
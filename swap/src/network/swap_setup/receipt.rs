@@ -0,0 +1,157 @@
+//! Statements of the seller about a swap, signed with its libp2p identity.
+//!
+//! Bob asks for receipts when he sets up a swap. The seller signs the spot
+//! price it quotes and, once the swap is set up, the terms of the swap. Bob
+//! stores the receipts, with them he can prove the agreed rate without the
+//! seller being online.
+
+use crate::network::swap_setup::{BlockchainNetwork, Timelocks};
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use libp2p::identity::{self, ed25519};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Prefixed to every signed receipt, so a signature over a receipt can never
+/// be mistaken for a signature of another protocol.
+const DOMAIN: &[u8] = b"xmr-btc-swap/receipt/1";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Receipt {
+    Quote(QuoteReceipt),
+    Swap(SwapReceipt),
+}
+
+/// The spot price the seller quoted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuoteReceipt {
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc: bitcoin::Amount,
+    pub xmr: monero::Amount,
+    pub timelocks: Timelocks,
+    pub blockchain_network: BlockchainNetwork,
+    /// Unix timestamp in seconds.
+    pub quoted_at: u64,
+}
+
+/// The terms of the swap once it was set up.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SwapReceipt {
+    pub swap_id: Uuid,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc: bitcoin::Amount,
+    pub xmr: monero::Amount,
+    pub timelocks: Timelocks,
+    /// Unix timestamp in seconds.
+    pub set_up_at: u64,
+}
+
+/// A [`Receipt`] together with the signature of the seller.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedReceipt {
+    /// The receipt encoded as CBOR, exactly as it was signed.
+    pub receipt: Vec<u8>,
+    /// The ed25519 public key of the seller's libp2p identity.
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SignedReceipt {
+    pub fn sign(receipt: &Receipt, identity: &identity::Keypair) -> Result<Self> {
+        let public_key = match identity {
+            identity::Keypair::Ed25519(keypair) => keypair.public().encode().to_vec(),
+            _ => bail!("Only ed25519 identities can sign receipts"),
+        };
+        let receipt = serde_cbor::to_vec(receipt).context("Failed to encode receipt")?;
+        let signature = identity
+            .sign(&signed_message(&receipt))
+            .context("Failed to sign receipt")?;
+
+        Ok(Self {
+            receipt,
+            public_key,
+            signature,
+        })
+    }
+
+    /// Returns the receipt if it was signed by the seller with the given peer
+    /// id.
+    pub fn verify(&self, seller: PeerId) -> Result<Receipt> {
+        let public_key = identity::PublicKey::Ed25519(
+            ed25519::PublicKey::decode(&self.public_key)
+                .context("The receipt contains an invalid public key")?,
+        );
+        let signer = PeerId::from(public_key.clone());
+        if signer != seller {
+            bail!(
+                "The receipt was signed by {} instead of the seller {}",
+                signer,
+                seller
+            )
+        }
+        if !public_key.verify(&signed_message(&self.receipt), &self.signature) {
+            bail!("The signature of the receipt is invalid")
+        }
+
+        serde_cbor::from_slice(&self.receipt).context("Failed to decode receipt")
+    }
+}
+
+fn signed_message(receipt: &[u8]) -> Vec<u8> {
+    [DOMAIN, receipt].concat()
+}
+
+/// The current time as unix timestamp in seconds, as stated in receipts.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{GetConfig, Mainnet};
+
+    fn quote() -> Receipt {
+        let env_config = Mainnet::get_config();
+
+        Receipt::Quote(QuoteReceipt {
+            btc: bitcoin::Amount::from_sat(1_000_000),
+            xmr: monero::Amount::from_piconero(1_500_000_000_000),
+            timelocks: Timelocks::of_network(&env_config),
+            blockchain_network: BlockchainNetwork {
+                bitcoin: env_config.bitcoin_network,
+                monero: env_config.monero_network,
+            },
+            quoted_at: 1_700_000_000,
+        })
+    }
+
+    #[test]
+    fn receipts_verify_with_the_peer_id_of_the_seller() {
+        let seller = identity::Keypair::generate_ed25519();
+
+        let signed = SignedReceipt::sign(&quote(), &seller).unwrap();
+
+        assert_eq!(signed.verify(seller.public().into()).unwrap(), quote());
+        assert!(signed.verify(PeerId::random()).is_err());
+    }
+
+    #[test]
+    fn tampered_receipts_do_not_verify() {
+        let seller = identity::Keypair::generate_ed25519();
+        let mut signed = SignedReceipt::sign(&quote(), &seller).unwrap();
+
+        let mut other = quote();
+        if let Receipt::Quote(quote) = &mut other {
+            quote.xmr = monero::Amount::from_piconero(2_000_000_000_000);
+        }
+        signed.receipt = serde_cbor::to_vec(&other).unwrap();
+
+        assert!(signed.verify(seller.public().into()).is_err());
+    }
+}
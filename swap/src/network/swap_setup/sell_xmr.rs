@@ -0,0 +1,9 @@
+//! The swap setup for swaps in which the taker sells XMR.
+//!
+//! Alice always holds the XMR and Bob the BTC. In these swaps the CLI takes the
+//! role of Alice and dials the ASB, which takes the role of Bob. Once the
+//! amounts are agreed upon, the same messages are exchanged as in the swap
+//! setup in which the taker buys XMR.
+
+pub mod alice;
+pub mod bob;
@@ -0,0 +1,345 @@
+use crate::network::swap_setup::alice::exchange_messages;
+use crate::network::swap_setup::{
+    check_offer, protocol, read_cbor_message, write_cbor_message, BlockchainNetwork,
+    OfferBelowAgreed, SellXmrRequest, SellXmrResponse, SpotPriceError,
+};
+use crate::protocol::alice::{State0, State3};
+use crate::{bitcoin, cli, env, monero};
+use anyhow::{bail, Result};
+use futures::future::{BoxFuture, OptionFuture};
+use futures::{AsyncWriteExt, FutureExt};
+use libp2p::core::connection::ConnectionId;
+use libp2p::core::upgrade;
+use libp2p::swarm::{
+    KeepAlive, NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler,
+    PollParameters, ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr,
+    SubstreamProtocol,
+};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use uuid::Uuid;
+use void::Void;
+
+/// Sets up swaps in which we sell XMR to the peer we dial.
+#[allow(missing_debug_implementations)]
+pub struct Behaviour {
+    env_config: env::Config,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    new_swaps: VecDeque<(PeerId, NewSwap)>,
+    completed_swaps: VecDeque<(PeerId, Completed)>,
+}
+
+impl Behaviour {
+    pub fn new(env_config: env::Config, bitcoin_wallet: Arc<bitcoin::Wallet>) -> Self {
+        Self {
+            env_config,
+            bitcoin_wallet,
+            new_swaps: VecDeque::default(),
+            completed_swaps: VecDeque::default(),
+        }
+    }
+
+    pub async fn start(&mut self, bob: PeerId, swap: NewSwap) {
+        self.new_swaps.push_back((bob, swap))
+    }
+}
+
+impl From<Completed> for cli::OutEvent {
+    fn from(completed: Completed) -> Self {
+        cli::OutEvent::SellXmrSetupCompleted(Box::new(completed.0))
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ProtocolsHandler = Handler;
+    type OutEvent = Completed;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        Handler::new(self.env_config, self.bitcoin_wallet.clone())
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, _: &PeerId) {}
+
+    fn inject_disconnected(&mut self, _: &PeerId) {}
+
+    fn inject_event(&mut self, peer: PeerId, _: ConnectionId, completed: Completed) {
+        self.completed_swaps.push_back((peer, completed));
+    }
+
+    fn poll(
+        &mut self,
+        _cx: &mut Context<'_>,
+        _params: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+        if let Some((_, event)) = self.completed_swaps.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        if let Some((peer, event)) = self.new_swaps.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
+                peer_id: peer,
+                handler: NotifyHandler::Any,
+                event,
+            });
+        }
+
+        Poll::Pending
+    }
+}
+
+type OutboundStream = BoxFuture<'static, Result<State3>>;
+
+pub struct Handler {
+    outbound_stream: OptionFuture<OutboundStream>,
+    env_config: env::Config,
+    timeout: Duration,
+    new_swaps: VecDeque<NewSwap>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    keep_alive: KeepAlive,
+}
+
+impl Handler {
+    fn new(env_config: env::Config, bitcoin_wallet: Arc<bitcoin::Wallet>) -> Self {
+        Self {
+            env_config,
+            outbound_stream: OptionFuture::from(None),
+            timeout: Duration::from_secs(120),
+            new_swaps: VecDeque::default(),
+            bitcoin_wallet,
+            keep_alive: KeepAlive::Yes,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NewSwap {
+    pub swap_id: Uuid,
+    pub xmr: monero::Amount,
+    /// The BTC the peer quoted for the XMR, the swap is not set up if the peer
+    /// pays less.
+    pub btc: bitcoin::Amount,
+    /// Receives the BTC when we redeem, or when we punish the peer.
+    pub bitcoin_redeem_address: bitcoin::Address,
+}
+
+#[derive(Debug)]
+pub struct Completed(Result<State3>);
+
+impl ProtocolsHandler for Handler {
+    type InEvent = NewSwap;
+    type OutEvent = Completed;
+    type Error = Void;
+    type InboundProtocol = upgrade::DeniedUpgrade;
+    type OutboundProtocol = protocol::SwapSetup;
+    type InboundOpenInfo = ();
+    type OutboundOpenInfo = NewSwap;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(upgrade::DeniedUpgrade, ())
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, _: Void, _: Self::InboundOpenInfo) {
+        unreachable!("Alice does not support inbound substreams when selling XMR")
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        mut substream: NegotiatedSubstream,
+        info: Self::OutboundOpenInfo,
+    ) {
+        let bitcoin_wallet = self.bitcoin_wallet.clone();
+        let env_config = self.env_config;
+
+        let protocol = tokio::time::timeout(self.timeout, async move {
+            write_cbor_message(&mut substream, SellXmrRequest {
+                swap_id: info.swap_id,
+                xmr: info.xmr,
+                blockchain_network: BlockchainNetwork {
+                    bitcoin: env_config.bitcoin_network,
+                    monero: env_config.monero_network,
+                },
+            })
+            .await?;
+
+            let btc = Result::from(read_cbor_message::<SellXmrResponse>(&mut substream).await?)?;
+            check_offer(info.btc, btc).map_err(Error::OfferBelowQuote)?;
+
+            let tx_redeem_fee = bitcoin_wallet
+                .estimate_fee(bitcoin::TxKind::Redeem, bitcoin::TxRedeem::weight(), btc)
+                .await?;
+            let tx_punish_fee = bitcoin_wallet
+                .estimate_fee(bitcoin::TxKind::Punish, bitcoin::TxPunish::weight(), btc)
+                .await?;
+
+            let state0 = State0::new(
+                btc,
+                info.xmr,
+                env_config.bitcoin_cancel_timelock,
+                env_config.bitcoin_punish_timelock,
+                info.bitcoin_redeem_address.clone(),
+                info.bitcoin_redeem_address,
+                tx_redeem_fee,
+                tx_punish_fee,
+                &mut rand::thread_rng(),
+            );
+
+            let (swap_id, state3) = exchange_messages(&mut substream, state0).await?;
+
+            if swap_id != info.swap_id {
+                bail!(Error::SwapIdMismatch {
+                    expected: info.swap_id,
+                    received: swap_id
+                })
+            }
+
+            substream.flush().await?;
+            substream.close().await?;
+
+            Ok(state3)
+        });
+
+        let max_seconds = self.timeout.as_secs();
+        self.outbound_stream = OptionFuture::from(Some(
+            async move {
+                protocol.await.map_err(|_| Error::Timeout {
+                    seconds: max_seconds,
+                })?
+            }
+            .boxed(),
+        ));
+    }
+
+    fn inject_event(&mut self, new_swap: Self::InEvent) {
+        self.new_swaps.push_back(new_swap);
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        _: Self::OutboundOpenInfo,
+        _: ProtocolsHandlerUpgrErr<Void>,
+    ) {
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        ProtocolsHandlerEvent<
+            Self::OutboundProtocol,
+            Self::OutboundOpenInfo,
+            Self::OutEvent,
+            Self::Error,
+        >,
+    > {
+        if let Some(new_swap) = self.new_swaps.pop_front() {
+            self.keep_alive = KeepAlive::Yes;
+            return Poll::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(protocol::sell_xmr(), new_swap),
+            });
+        }
+
+        if let Some(result) = futures::ready!(self.outbound_stream.poll_unpin(cx)) {
+            self.outbound_stream = OptionFuture::from(None);
+            return Poll::Ready(ProtocolsHandlerEvent::Custom(Completed(result)));
+        }
+
+        Poll::Pending
+    }
+}
+
+impl From<SellXmrResponse> for Result<bitcoin::Amount, Error> {
+    fn from(response: SellXmrResponse) -> Self {
+        match response {
+            SellXmrResponse::Btc(amount) => Ok(amount),
+            SellXmrResponse::Error(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, thiserror::Error, PartialEq)]
+pub enum Error {
+    #[error("Peer currently does not buy XMR, please try again later")]
+    NoSwapsAccepted,
+    #[error("Peer refused to pay {buy} because the minimum configured limit is {min}")]
+    AmountBelowMinimum {
+        min: bitcoin::Amount,
+        buy: bitcoin::Amount,
+    },
+    #[error("Peer refused to pay {buy} because the maximum configured limit is {max}")]
+    AmountAboveMaximum {
+        max: bitcoin::Amount,
+        buy: bitcoin::Amount,
+    },
+    #[error("Peer's BTC balance is currently too low to pay {buy}, please try again later")]
+    BalanceTooLow { buy: bitcoin::Amount },
+
+    #[error("Peer is currently processing too many swaps, please try again later")]
+    Busy,
+
+    #[error(
+        "Peer blockchain network {asb:?} setup did not match your blockchain network setup {cli:?}"
+    )]
+    BlockchainNetworkMismatch {
+        cli: BlockchainNetwork,
+        asb: BlockchainNetwork,
+    },
+
+    #[error("Peer's exchange rate is outdated, please try again later")]
+    RateStale,
+
+    #[error("Peer only serves quotes at the moment and does not accept swaps, it would have paid {btc} for {xmr}")]
+    QuotesOnly {
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+    },
+
+    #[error(transparent)]
+    OfferBelowQuote(OfferBelowAgreed<bitcoin::Amount>),
+
+    #[error("Peer set up swap {received} instead of swap {expected}")]
+    SwapIdMismatch { expected: Uuid, received: Uuid },
+
+    #[error("Failed to complete swap setup within {seconds}s")]
+    Timeout { seconds: u64 },
+
+    /// To be used for errors that cannot be explained on the CLI side (e.g.
+    /// rate update problems on the peer's side)
+    #[error("Peer encountered a problem, please try again later.")]
+    Other,
+}
+
+impl From<SpotPriceError> for Error {
+    fn from(error: SpotPriceError) -> Self {
+        match error {
+            SpotPriceError::NoSwapsAccepted => Error::NoSwapsAccepted,
+            SpotPriceError::AmountBelowMinimum { min, buy } => {
+                Error::AmountBelowMinimum { min, buy }
+            }
+            SpotPriceError::AmountAboveMaximum { max, buy } => {
+                Error::AmountAboveMaximum { max, buy }
+            }
+            SpotPriceError::BalanceTooLow { buy } => Error::BalanceTooLow { buy },
+            SpotPriceError::BlockchainNetworkMismatch { cli, asb } => {
+                Error::BlockchainNetworkMismatch { cli, asb }
+            }
+            SpotPriceError::Busy => Error::Busy,
+            SpotPriceError::RateStale => Error::RateStale,
+            SpotPriceError::QuotesOnly { btc, xmr } => Error::QuotesOnly { btc, xmr },
+            SpotPriceError::TimelocksNotAccepted { .. }
+            | SpotPriceError::MoneroConfirmationsNotAccepted { .. }
+            | SpotPriceError::Other => Error::Other,
+        }
+    }
+}
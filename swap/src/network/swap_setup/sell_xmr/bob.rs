@@ -0,0 +1,690 @@
+use crate::asb::reservations::{Reservation, Reservations};
+use crate::asb::LatestRate;
+use crate::network::swap_setup;
+use crate::network::swap_setup::alice::{ConcurrencyLimits, OngoingSwaps};
+use crate::network::swap_setup::bob::exchange_messages;
+use crate::network::swap_setup::{
+    protocol, BlockchainNetwork, ConfirmationSchedule, SellXmrRequest, SellXmrResponse,
+    SpotPriceError,
+};
+use crate::protocol::bob::{State0, State2};
+use crate::{asb, bitcoin, env, monero, price_feed};
+use anyhow::{anyhow, Context, Result};
+use futures::future::{BoxFuture, OptionFuture};
+use futures::{AsyncWriteExt, FutureExt};
+use libp2p::core::connection::ConnectionId;
+use libp2p::core::upgrade;
+use libp2p::swarm::{
+    KeepAlive, NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+    ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol,
+};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use void::Void;
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum OutEvent {
+    Initiated {
+        peer_id: PeerId,
+        send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
+    },
+    Completed {
+        peer_id: PeerId,
+        swap_id: Uuid,
+        state2: State2,
+        reservation: Option<Reservation>,
+        slot: Option<Reservation>,
+    },
+    Error {
+        peer_id: PeerId,
+        error: anyhow::Error,
+    },
+}
+
+#[derive(Debug)]
+pub struct WalletSnapshot {
+    /// The amount that can be locked without the BTC reserved for other swaps.
+    max_giveable: bitcoin::Amount,
+    refund_address: bitcoin::Address,
+
+    tx_refund_fee: bitcoin::Amount,
+    tx_cancel_fee: bitcoin::Amount,
+
+    ongoing_swaps: OngoingSwaps,
+
+    /// Reserves the BTC of the swap until it is locked, if the balance
+    /// suffices.
+    reservation: Option<Reservation>,
+    /// Counts the swap setup towards the concurrency limits until the swap is
+    /// started.
+    slot: Option<Reservation>,
+
+    /// Whether the swap is declined once it is priced.
+    quotes_only: bool,
+    /// Whether the swap is declined because quotes are paused.
+    quotes_paused: bool,
+}
+
+impl WalletSnapshot {
+    pub async fn capture(
+        bitcoin_wallet: &bitcoin::Wallet,
+        reservations: &mut Reservations,
+        transfer_amount: bitcoin::Amount,
+        ongoing_swaps: OngoingSwaps,
+    ) -> Result<Self> {
+        // Like the XMR of swaps in which we sell XMR, the reservations are summed up
+        // before the balance is fetched.
+        let reserved = reservations.reserved_btc();
+        let max_giveable = bitcoin_wallet
+            .max_giveable(bitcoin::TxLock::script_size())
+            .await?
+            .checked_sub(reserved)
+            .unwrap_or(bitcoin::Amount::ZERO);
+        let refund_address = bitcoin_wallet.new_address().await?;
+        let tx_refund_fee = bitcoin_wallet
+            .estimate_fee(
+                bitcoin::TxKind::Refund,
+                bitcoin::TxRefund::weight(),
+                transfer_amount,
+            )
+            .await?;
+        let tx_cancel_fee = bitcoin_wallet
+            .estimate_fee(
+                bitcoin::TxKind::Cancel,
+                bitcoin::TxCancel::weight(),
+                transfer_amount,
+            )
+            .await?;
+
+        let reservation = if max_giveable >= transfer_amount {
+            Some(reservations.reserve_btc(transfer_amount))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            max_giveable,
+            refund_address,
+            tx_refund_fee,
+            tx_cancel_fee,
+            ongoing_swaps,
+            reservation,
+            slot: None,
+            quotes_only: false,
+            quotes_paused: false,
+        })
+    }
+
+    /// Like for swaps in which we sell XMR, declines the swap regardless of
+    /// the balance.
+    pub fn with_quotes_only(self, quotes_only: bool) -> Self {
+        Self {
+            quotes_only,
+            ..self
+        }
+    }
+
+    /// Like for swaps in which we sell XMR, declines the swap while quotes are
+    /// paused.
+    pub fn with_quotes_paused(self, quotes_paused: bool) -> Self {
+        Self {
+            quotes_paused,
+            ..self
+        }
+    }
+
+    /// Like for swaps in which we sell XMR, holds on to the slot of the swap
+    /// setup until the swap is started.
+    pub fn with_slot(self, slot: Reservation) -> Self {
+        Self {
+            slot: Some(slot),
+            ..self
+        }
+    }
+}
+
+impl From<OutEvent> for asb::OutEvent {
+    fn from(event: OutEvent) -> Self {
+        match event {
+            OutEvent::Initiated {
+                peer_id,
+                send_wallet_snapshot,
+            } => asb::OutEvent::SellXmrSetupInitiated {
+                peer_id,
+                send_wallet_snapshot,
+            },
+            OutEvent::Completed {
+                peer_id,
+                swap_id,
+                state2,
+                reservation,
+                slot,
+            } => asb::OutEvent::SellXmrSetupCompleted {
+                peer_id,
+                swap_id,
+                state2,
+                reservation,
+                slot,
+            },
+            OutEvent::Error { peer_id, error } => asb::OutEvent::SwapSetupFailed {
+                peer: peer_id,
+                error: anyhow!(error),
+            },
+        }
+    }
+}
+
+/// Sets up swaps in which we buy XMR from peers that dial us.
+#[allow(missing_debug_implementations)]
+pub struct Behaviour<LR> {
+    events: VecDeque<OutEvent>,
+    enabled: bool,
+    min_buy: bitcoin::Amount,
+    max_buy: bitcoin::Amount,
+    env_config: env::Config,
+    confirmation_schedule: ConfirmationSchedule,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+
+    latest_rate: LR,
+    resume_only: bool,
+    concurrency_limits: ConcurrencyLimits,
+}
+
+impl<LR> Behaviour<LR> {
+    /// If not enabled, all requests are declined with
+    /// [`SpotPriceError::NoSwapsAccepted`].
+    ///
+    /// The minimum and maximum limit the amount of BTC we pay for the XMR,
+    /// the schedule how long we wait for the Monero lock of the peer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        enabled: bool,
+        min_buy: bitcoin::Amount,
+        max_buy: bitcoin::Amount,
+        env_config: env::Config,
+        confirmation_schedule: ConfirmationSchedule,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        latest_rate: LR,
+        resume_only: bool,
+        concurrency_limits: ConcurrencyLimits,
+    ) -> Self {
+        Self {
+            events: Default::default(),
+            enabled,
+            min_buy,
+            max_buy,
+            env_config,
+            confirmation_schedule,
+            bitcoin_wallet,
+            latest_rate,
+            resume_only,
+            concurrency_limits,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Updates the minimum and maximum amount of BTC paid per swap.
+    ///
+    /// Like when selling XMR, peers that are already connected keep
+    /// negotiating with the limits that were active when they connected.
+    pub fn set_limits(&mut self, min_buy: bitcoin::Amount, max_buy: bitcoin::Amount) {
+        self.min_buy = min_buy;
+        self.max_buy = max_buy;
+    }
+}
+
+impl<LR> NetworkBehaviour for Behaviour<LR>
+where
+    LR: LatestRate + Send + 'static + Clone,
+{
+    type ProtocolsHandler = Handler<LR>;
+    type OutEvent = OutEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        Handler::new(
+            self.enabled,
+            self.min_buy,
+            self.max_buy,
+            self.env_config,
+            self.confirmation_schedule.clone(),
+            self.bitcoin_wallet.clone(),
+            self.latest_rate.clone(),
+            self.resume_only,
+            self.concurrency_limits,
+        )
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, _: &PeerId) {}
+
+    fn inject_disconnected(&mut self, _: &PeerId) {}
+
+    fn inject_event(&mut self, peer_id: PeerId, _: ConnectionId, event: HandlerOutEvent) {
+        match event {
+            HandlerOutEvent::Initiated(send_wallet_snapshot) => {
+                self.events.push_back(OutEvent::Initiated {
+                    peer_id,
+                    send_wallet_snapshot,
+                })
+            }
+            HandlerOutEvent::Completed(Ok((swap_id, state2, reservation, slot))) => {
+                self.events.push_back(OutEvent::Completed {
+                    peer_id,
+                    swap_id,
+                    state2,
+                    reservation,
+                    slot,
+                })
+            }
+            HandlerOutEvent::Completed(Err(error)) => {
+                self.events.push_back(OutEvent::Error { peer_id, error })
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+        _params: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        Poll::Pending
+    }
+}
+
+type InboundStream = BoxFuture<'static, Result<(Uuid, State2, Option<Reservation>)>>;
+
+pub struct Handler<LR> {
+    inbound_stream: OptionFuture<InboundStream>,
+    events: VecDeque<HandlerOutEvent>,
+
+    enabled: bool,
+    min_buy: bitcoin::Amount,
+    max_buy: bitcoin::Amount,
+    env_config: env::Config,
+    confirmation_schedule: ConfirmationSchedule,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+
+    latest_rate: LR,
+    resume_only: bool,
+    concurrency_limits: ConcurrencyLimits,
+
+    timeout: Duration,
+    keep_alive: KeepAlive,
+}
+
+impl<LR> Handler<LR> {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        enabled: bool,
+        min_buy: bitcoin::Amount,
+        max_buy: bitcoin::Amount,
+        env_config: env::Config,
+        confirmation_schedule: ConfirmationSchedule,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        latest_rate: LR,
+        resume_only: bool,
+        concurrency_limits: ConcurrencyLimits,
+    ) -> Self {
+        Self {
+            inbound_stream: OptionFuture::from(None),
+            events: Default::default(),
+            enabled,
+            min_buy,
+            max_buy,
+            env_config,
+            confirmation_schedule,
+            bitcoin_wallet,
+            latest_rate,
+            resume_only,
+            concurrency_limits,
+            timeout: Duration::from_secs(120),
+            keep_alive: KeepAlive::Until(Instant::now() + Duration::from_secs(10)),
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum HandlerOutEvent {
+    Initiated(bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>),
+    Completed(Result<(Uuid, State2, Option<Reservation>, Option<Reservation>)>),
+}
+
+impl<LR> ProtocolsHandler for Handler<LR>
+where
+    LR: LatestRate + Send + 'static,
+{
+    type InEvent = ();
+    type OutEvent = HandlerOutEvent;
+    type Error = Error;
+    type InboundProtocol = protocol::SwapSetup;
+    type OutboundProtocol = upgrade::DeniedUpgrade;
+    type InboundOpenInfo = ();
+    type OutboundOpenInfo = ();
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(protocol::sell_xmr(), ())
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        mut substream: NegotiatedSubstream,
+        _: Self::InboundOpenInfo,
+    ) {
+        self.keep_alive = KeepAlive::Yes;
+
+        let (sender, receiver) = bmrng::channel_with_timeout::<bitcoin::Amount, WalletSnapshot>(
+            1,
+            Duration::from_secs(5),
+        );
+        let enabled = self.enabled;
+        let resume_only = self.resume_only;
+        let concurrency_limits = self.concurrency_limits;
+        let min_buy = self.min_buy;
+        let max_buy = self.max_buy;
+        let latest_rate = self.latest_rate.latest_rate();
+        let env_config = self.env_config;
+        let confirmation_schedule = self.confirmation_schedule.clone();
+        let bitcoin_wallet = self.bitcoin_wallet.clone();
+
+        let protocol = tokio::time::timeout(self.timeout, async move {
+            let request = swap_setup::read_cbor_message::<SellXmrRequest>(&mut substream)
+                .await
+                .context("Failed to read sell XMR request")?;
+
+            // wrap all of these into another future so we can `return` from all the
+            // different blocks
+            let quote = async {
+                if !enabled {
+                    return Err(Error::BuyingXmrDisabled);
+                }
+
+                if resume_only {
+                    return Err(Error::ResumeOnlyMode);
+                };
+
+                let blockchain_network = BlockchainNetwork {
+                    bitcoin: env_config.bitcoin_network,
+                    monero: env_config.monero_network,
+                };
+
+                if request.blockchain_network != blockchain_network {
+                    return Err(Error::BlockchainNetworkMismatch {
+                        cli: request.blockchain_network,
+                        asb: blockchain_network,
+                    });
+                }
+
+                let rate = latest_rate.map_err(Error::from_latest_rate_error)?;
+                let btc = rate
+                    .buy_quote(request.xmr)
+                    .map_err(Error::BuyQuoteCalculationFailed)?;
+
+                if btc < min_buy {
+                    return Err(Error::AmountBelowMinimum {
+                        min: min_buy,
+                        buy: btc,
+                    });
+                }
+
+                if btc > max_buy {
+                    return Err(Error::AmountAboveMaximum {
+                        max: max_buy,
+                        buy: btc,
+                    });
+                }
+
+                Ok(btc)
+            };
+
+            // The wallet snapshot is only requested once the quote is known, if the
+            // request is declined before the event loop is left waiting for nothing.
+            let result = match quote.await {
+                Ok(btc) => {
+                    let wallet_snapshot = sender
+                        .send_receive(btc)
+                        .await
+                        .context("Failed to receive wallet snapshot")?;
+
+                    wallet_snapshot
+                        .check(btc, request.xmr, concurrency_limits)
+                        .map(|()| (btc, wallet_snapshot))
+                }
+                Err(error) => Err(error),
+            };
+
+            swap_setup::write_cbor_message(
+                &mut substream,
+                SellXmrResponse::from_result_ref(&result),
+            )
+            .await
+            .context("Failed to write sell XMR response")?;
+
+            let (btc, mut wallet_snapshot) = result?;
+            let reservation = wallet_snapshot.reservation.take();
+            let slot = wallet_snapshot.slot.take();
+
+            let state0 = State0::new(
+                request.swap_id,
+                &mut rand::thread_rng(),
+                btc,
+                request.xmr,
+                env_config.bitcoin_cancel_timelock,
+                env_config.bitcoin_punish_timelock,
+                wallet_snapshot.refund_address,
+                confirmation_schedule.confirmations(request.xmr),
+                wallet_snapshot.tx_refund_fee,
+                wallet_snapshot.tx_cancel_fee,
+            );
+
+            let state2 = exchange_messages(&mut substream, state0, bitcoin_wallet.as_ref()).await?;
+
+            substream
+                .flush()
+                .await
+                .context("Failed to flush substream after all messages were sent")?;
+            substream
+                .close()
+                .await
+                .context("Failed to close substream after all messages were sent")?;
+
+            Ok((request.swap_id, state2, reservation, slot))
+        });
+
+        let max_seconds = self.timeout.as_secs();
+        self.inbound_stream = OptionFuture::from(Some(
+            async move {
+                protocol.await.with_context(|| {
+                    format!("Failed to complete execution setup within {}s", max_seconds)
+                })?
+            }
+            .boxed(),
+        ));
+
+        self.events.push_back(HandlerOutEvent::Initiated(receiver));
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, _: Void, _: Self::OutboundOpenInfo) {
+        unreachable!("Bob does not support outbound in the handler")
+    }
+
+    fn inject_event(&mut self, _: Self::InEvent) {
+        unreachable!("Bob does not receive events from the Behaviour in the handler")
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        _: Self::OutboundOpenInfo,
+        _: ProtocolsHandlerUpgrErr<Void>,
+    ) {
+        unreachable!("Bob does not dial")
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<
+        ProtocolsHandlerEvent<
+            Self::OutboundProtocol,
+            Self::OutboundOpenInfo,
+            Self::OutEvent,
+            Self::Error,
+        >,
+    > {
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(ProtocolsHandlerEvent::Custom(event));
+        }
+
+        if let Some(result) = futures::ready!(self.inbound_stream.poll_unpin(cx)) {
+            self.inbound_stream = OptionFuture::from(None);
+            return Poll::Ready(ProtocolsHandlerEvent::Custom(HandlerOutEvent::Completed(
+                result,
+            )));
+        }
+
+        Poll::Pending
+    }
+}
+
+impl WalletSnapshot {
+    fn check(
+        &self,
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+        concurrency_limits: ConcurrencyLimits,
+    ) -> Result<(), Error> {
+        if self.quotes_paused {
+            return Err(Error::QuotesPaused);
+        }
+
+        if self.quotes_only {
+            return Err(Error::QuotesOnly { btc, xmr });
+        }
+
+        concurrency_limits
+            .check(self.ongoing_swaps)
+            .map_err(Error::ConcurrencyLimitReached)?;
+
+        if self.max_giveable < btc {
+            return Err(Error::BalanceTooLow {
+                balance: self.max_giveable,
+                buy: btc,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl SellXmrResponse {
+    pub fn from_result_ref(result: &Result<(bitcoin::Amount, WalletSnapshot), Error>) -> Self {
+        match result {
+            Ok((btc, _)) => SellXmrResponse::Btc(*btc),
+            Err(error) => SellXmrResponse::Error(error.to_error_response()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Buying XMR is not enabled")]
+    BuyingXmrDisabled,
+    #[error("ASB is running in resume-only mode")]
+    ResumeOnlyMode,
+    #[error("Quotes are paused")]
+    QuotesPaused,
+    #[error("ASB only serves quotes, declined paying {btc} for {xmr}")]
+    QuotesOnly {
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+    },
+    #[error("Amount {buy} below minimum {min}")]
+    AmountBelowMinimum {
+        min: bitcoin::Amount,
+        buy: bitcoin::Amount,
+    },
+    #[error("Amount {buy} above maximum {max}")]
+    AmountAboveMaximum {
+        max: bitcoin::Amount,
+        buy: bitcoin::Amount,
+    },
+    #[error(transparent)]
+    ConcurrencyLimitReached(swap_setup::alice::Error),
+    #[error("Balance {balance} too low to pay {buy}")]
+    BalanceTooLow {
+        balance: bitcoin::Amount,
+        buy: bitcoin::Amount,
+    },
+    #[error("Failed to fetch latest rate")]
+    LatestRateFetchFailed(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+    #[error("The latest rate is {}s old", age.as_secs())]
+    RateStale { age: Duration },
+    #[error("Failed to calculate quote")]
+    BuyQuoteCalculationFailed(#[source] anyhow::Error),
+    #[error("Blockchain networks did not match, we are on {asb:?}, but request from {cli:?}")]
+    BlockchainNetworkMismatch {
+        cli: BlockchainNetwork,
+        asb: BlockchainNetwork,
+    },
+}
+
+impl Error {
+    pub fn to_error_response(&self) -> SpotPriceError {
+        match self {
+            Error::BuyingXmrDisabled | Error::ResumeOnlyMode | Error::QuotesPaused => {
+                SpotPriceError::NoSwapsAccepted
+            }
+            Error::QuotesOnly { btc, xmr } => SpotPriceError::QuotesOnly {
+                btc: *btc,
+                xmr: *xmr,
+            },
+            Error::AmountBelowMinimum { min, buy } => SpotPriceError::AmountBelowMinimum {
+                min: *min,
+                buy: *buy,
+            },
+            Error::AmountAboveMaximum { max, buy } => SpotPriceError::AmountAboveMaximum {
+                max: *max,
+                buy: *buy,
+            },
+            Error::ConcurrencyLimitReached(_) => SpotPriceError::Busy,
+            Error::BalanceTooLow { buy, .. } => SpotPriceError::BalanceTooLow { buy: *buy },
+            Error::BlockchainNetworkMismatch { cli, asb } => {
+                SpotPriceError::BlockchainNetworkMismatch {
+                    cli: *cli,
+                    asb: *asb,
+                }
+            }
+            Error::RateStale { .. } => SpotPriceError::RateStale,
+            Error::LatestRateFetchFailed(_) | Error::BuyQuoteCalculationFailed(_) => {
+                SpotPriceError::Other
+            }
+        }
+    }
+
+    fn from_latest_rate_error(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        match price_feed::stale_age(&error) {
+            Some(age) => Error::RateStale { age },
+            None => Error::LatestRateFetchFailed(Box::new(error)),
+        }
+    }
+}
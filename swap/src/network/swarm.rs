@@ -1,36 +1,64 @@
 use crate::asb::LatestRate;
 use crate::libp2p_ext::MultiAddrExt;
-use crate::network::rendezvous::XmrBtcNamespace;
+use crate::network::rendezvous::RendezvousNamespace;
+use crate::network::swap_setup::alice::ConcurrencyLimits;
+use crate::network::swap_setup::{ConfirmationSchedule, TimelockRanges};
 use crate::seed::Seed;
 use crate::{asb, bitcoin, cli, env, tor};
 use anyhow::{Context, Result};
+use libp2p::relay::Relay;
 use libp2p::swarm::{NetworkBehaviour, SwarmBuilder};
 use libp2p::{identity, Multiaddr, Swarm};
 use std::fmt::Debug;
+use std::sync::Arc;
 
 #[allow(clippy::too_many_arguments)]
 pub fn asb<LR>(
     seed: &Seed,
+    identity_generation: u32,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
     latest_rate: LR,
     resume_only: bool,
+    concurrency_limits: ConcurrencyLimits,
+    buy_xmr: bool,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
     env_config: env::Config,
-    rendezvous_params: Option<(Multiaddr, XmrBtcNamespace)>,
+    timelock_ranges: TimelockRanges,
+    confirmation_schedule: ConfirmationSchedule,
+    namespaces: Vec<RendezvousNamespace>,
+    rendezvous_points: Vec<Multiaddr>,
+    reachability_check: bool,
+    relay_nodes: &[Multiaddr],
 ) -> Result<Swarm<asb::Behaviour<LR>>>
 where
     LR: LatestRate + Send + 'static + Debug + Clone,
 {
-    let identity = seed.derive_libp2p_identity();
+    let identity = seed.derive_libp2p_identity_at(identity_generation);
 
-    let rendezvous_params = if let Some((address, namespace)) = rendezvous_params {
-        let peer_id = address
+    let rendezvous_nodes = rendezvous_points
+        .into_iter()
+        .map(|addr| {
+            let peer_id = addr
+                .extract_peer_id()
+                .context("Rendezvous node address must contain peer ID")?;
+
+            Ok((peer_id, addr))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Listening through a relay node needs to know its peer ID
+    for relay_node in relay_nodes {
+        relay_node
             .extract_peer_id()
-            .context("Rendezvous node address must contain peer ID")?;
+            .with_context(|| format!("Relay node address {} must contain peer ID", relay_node))?;
+    }
 
-        Some((identity.clone(), peer_id, address, namespace))
-    } else {
+    let (transport, relay) = asb::transport::new(&identity)?;
+    let relay = if relay_nodes.is_empty() {
         None
+    } else {
+        Some(relay)
     };
 
     let behaviour = asb::Behaviour::new(
@@ -38,11 +66,19 @@ where
         max_buy,
         latest_rate,
         resume_only,
+        concurrency_limits,
+        buy_xmr,
+        bitcoin_wallet,
         env_config,
-        rendezvous_params,
+        timelock_ranges,
+        confirmation_schedule,
+        identity.clone(),
+        rendezvous_nodes,
+        namespaces,
+        reachability_check,
+        relay,
     );
 
-    let transport = asb::transport::new(&identity)?;
     let peer_id = identity.public().into();
 
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
@@ -54,10 +90,12 @@ where
     Ok(swarm)
 }
 
+/// Creates the swarm of the CLI, the behaviour is built with the [`Relay`]
+/// behaviour that dials `/p2p-circuit` addresses for the transport.
 pub async fn cli<T>(
     identity: identity::Keypair,
     tor_socks5_port: u16,
-    behaviour: T,
+    behaviour: impl FnOnce(Relay) -> T,
 ) -> Result<Swarm<T>>
 where
     T: NetworkBehaviour,
@@ -67,10 +105,10 @@ where
         Err(_) => None,
     };
 
-    let transport = cli::transport::new(&identity, maybe_tor_socks5_port)?;
+    let (transport, relay) = cli::transport::new(&identity, maybe_tor_socks5_port)?;
     let peer_id = identity.public().into();
 
-    let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
+    let swarm = SwarmBuilder::new(transport, behaviour(relay), peer_id)
         .executor(Box::new(|f| {
             tokio::spawn(f);
         }))
@@ -46,10 +46,26 @@ pub fn bob() -> Behaviour {
     )
 }
 
+/// For peers that take the role of Alice in some swaps and the role of Bob in
+/// others.
+pub fn alice_and_bob() -> Behaviour {
+    Behaviour::new(
+        CborCodec::default(),
+        vec![(TransferProofProtocol, ProtocolSupport::Full)],
+        RequestResponseConfig::default(),
+    )
+}
+
 impl From<(PeerId, Message)> for asb::OutEvent {
     fn from((peer, message): (PeerId, Message)) -> Self {
         match message {
-            Message::Request { .. } => Self::unexpected_request(peer),
+            Message::Request {
+                request, channel, ..
+            } => Self::TransferProofReceived {
+                msg: Box::new(request),
+                channel,
+                peer,
+            },
             Message::Response { request_id, .. } => Self::TransferProofAcknowledged {
                 peer,
                 id: request_id,
@@ -69,7 +85,9 @@ impl From<(PeerId, Message)> for cli::OutEvent {
                 channel,
                 peer,
             },
-            Message::Response { .. } => Self::unexpected_response(peer),
+            Message::Response { request_id, .. } => {
+                Self::TransferProofAcknowledged { id: request_id }
+            }
         }
     }
 }
@@ -0,0 +1,237 @@
+//! Notifies the operator when a swap needs attention.
+//!
+//! Notifications are posted as JSON to a webhook, which can forward them by
+//! mail or to a chat. Like the monitor, the state transitions are read from
+//! the database.
+
+use crate::protocol::alice::AliceState;
+use crate::protocol::bob::BobState;
+use crate::protocol::{Database, RecordedState, State};
+use crate::{bitcoin, monero};
+use anyhow::{Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+/// How often the database is checked for new state transitions.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the balances are checked against the thresholds.
+const BALANCE_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// The swap was cancelled, the punish timelock is running.
+    PunishWindowApproaching,
+    /// The Bitcoin of the swap was refunded to Bob.
+    RefundPublished,
+    /// The Bitcoin was refunded, Alice has to refund the Monero.
+    MoneroRefundRequired,
+    /// The balance of a wallet dropped below the configured threshold.
+    BalanceBelowThreshold,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub kind: Kind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap_id: Option<Uuid>,
+    pub message: String,
+}
+
+impl Notification {
+    /// The notification for a state a swap entered, if the state needs
+    /// attention.
+    pub fn for_state(swap_id: Uuid, state: &State) -> Option<Self> {
+        let (kind, message) = match state {
+            State::Alice(AliceState::BtcCancelled { .. }) => (
+                Kind::PunishWindowApproaching,
+                "The swap was cancelled, the peer can be punished once the punish timelock expires unless it refunds.",
+            ),
+            State::Alice(AliceState::BtcRefunded { .. }) => (
+                Kind::MoneroRefundRequired,
+                "The peer refunded the Bitcoin, the Monero has to be refunded.",
+            ),
+            State::Bob(BobState::BtcCancelled(..)) => (
+                Kind::PunishWindowApproaching,
+                "The swap was cancelled, the Bitcoin has to be refunded before the punish timelock expires.",
+            ),
+            State::Bob(BobState::BtcRefunded(..)) => (
+                Kind::RefundPublished,
+                "The refund transaction was published.",
+            ),
+            _ => return None,
+        };
+
+        Some(Notification {
+            kind,
+            swap_id: Some(swap_id),
+            message: message.to_string(),
+        })
+    }
+
+    fn balance_below_threshold(balance: impl fmt::Display, threshold: impl fmt::Display) -> Self {
+        Notification {
+            kind: Kind::BalanceBelowThreshold,
+            swap_id: None,
+            message: format!(
+                "The balance of {} dropped below the threshold of {}.",
+                balance, threshold
+            ),
+        }
+    }
+}
+
+/// Posts notifications to a webhook.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    client: reqwest::Client,
+    webhook_url: Url,
+}
+
+impl Notifier {
+    pub fn new(webhook_url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            webhook_url,
+        }
+    }
+
+    pub async fn send(&self, notification: &Notification) -> Result<()> {
+        self.client
+            .post(self.webhook_url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(notification)?)
+            .send()
+            .await
+            .context("Failed to post notification to webhook")?
+            .error_for_status()
+            .context("Webhook rejected notification")?;
+
+        Ok(())
+    }
+
+    /// Notifies about the states that need attention that swaps enter from
+    /// now on, until reading the database fails.
+    pub async fn watch_swaps(&self, db: Arc<dyn Database + Send + Sync>) -> Result<()> {
+        let mut after = db.get_latest_state_id().await?.unwrap_or_default();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let recorded = db.get_states_after(after).await?;
+            after = recorded.last().map(|recorded| recorded.id).unwrap_or(after);
+
+            for RecordedState { swap_id, state, .. } in recorded {
+                if let Some(notification) = Notification::for_state(swap_id, &state) {
+                    self.send_logged(&notification).await;
+                }
+            }
+        }
+    }
+
+    /// Notifies once the balance of a wallet drops below its threshold, and
+    /// again if it drops below after it was topped up.
+    pub async fn watch_balances(
+        &self,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        monero_wallet: Arc<monero::Wallet>,
+        thresholds: BalanceThresholds,
+    ) {
+        let mut bitcoin_below = false;
+        let mut monero_below = false;
+        let mut interval = tokio::time::interval(BALANCE_CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Some(threshold) = thresholds.bitcoin {
+                match bitcoin_wallet.balance().await {
+                    Ok(balance) => {
+                        if balance < threshold && !bitcoin_below {
+                            self.send_logged(&Notification::balance_below_threshold(
+                                balance, threshold,
+                            ))
+                            .await;
+                        }
+                        bitcoin_below = balance < threshold;
+                    }
+                    Err(error) => {
+                        tracing::warn!("Failed to check Bitcoin balance: {:#}", error)
+                    }
+                }
+            }
+
+            if let Some(threshold) = thresholds.monero {
+                match monero_wallet.get_balance().await {
+                    Ok(balance) => {
+                        if balance < threshold && !monero_below {
+                            self.send_logged(&Notification::balance_below_threshold(
+                                balance, threshold,
+                            ))
+                            .await;
+                        }
+                        monero_below = balance < threshold;
+                    }
+                    Err(error) => {
+                        tracing::warn!("Failed to check Monero balance: {:#}", error)
+                    }
+                }
+            }
+        }
+    }
+
+    async fn send_logged(&self, notification: &Notification) {
+        match self.send(notification).await {
+            Ok(()) => tracing::debug!(kind = ?notification.kind, "Sent notification"),
+            Err(error) => {
+                tracing::warn!(kind = ?notification.kind, "Failed to send notification: {:#}", error)
+            }
+        }
+    }
+}
+
+/// The balances below which a notification is sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BalanceThresholds {
+    pub bitcoin: Option<bitcoin::Amount>,
+    pub monero: Option<monero::Amount>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_states_that_need_attention_are_notified() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+
+        assert_eq!(
+            Notification::for_state(swap_id, &State::Bob(BobState::SafelyAborted)),
+            None
+        );
+        assert_eq!(
+            Notification::for_state(swap_id, &State::Alice(AliceState::BtcRedeemed)),
+            None
+        );
+    }
+
+    #[test]
+    fn notification_is_encoded_as_json() {
+        let notification = Notification::balance_below_threshold(
+            monero::Amount::from_piconero(1_000_000_000_000),
+            monero::Amount::from_piconero(2_000_000_000_000),
+        );
+
+        assert_eq!(
+            serde_json::to_string(&notification).unwrap(),
+            "{\"kind\":\"balance_below_threshold\",\"message\":\"The balance of 1.000000000000 XMR dropped below the threshold of 2.000000000000 XMR.\"}"
+        );
+    }
+}
@@ -0,0 +1,663 @@
+use crate::bitcoin;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use std::collections::VecDeque;
+use std::convert::{Infallible, TryFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Price feeds that did not show any sign of life for this long are
+/// considered stale.
+pub const MAX_PRICE_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// How many of the latest prices of a price feed are kept by default.
+pub const DEFAULT_CACHE_SIZE: usize = 10;
+
+/// For how long the cached prices of a disconnected price feed are used by
+/// default.
+pub const DEFAULT_MAX_INTERPOLATION: Duration = Duration::from_secs(30);
+
+/// How often the [`Static`] and [`File`] price feeds publish their price.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Represents the price of 1 XMR as published by a price feed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceUpdate {
+    pub ask: bitcoin::Amount,
+}
+
+/// A source of XMR/BTC prices, usually an exchange.
+#[async_trait]
+pub trait PriceFeed: Send + Sync + 'static {
+    /// A human readable name of the price feed, used for logging.
+    fn name(&self) -> String;
+
+    /// Connect to the price feed for a stream of price updates.
+    ///
+    /// The stream yields `None` if the feed signals that it is alive without
+    /// the price having changed. If the connection fails, the stream yields an
+    /// error or ends in which case it is re-established by calling this
+    /// function again.
+    async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>>;
+}
+
+/// When the prices of a price feed are too old to be used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Freshness {
+    /// The price of a connected price feed is stale once its latest update is
+    /// older than this.
+    pub max_age: Duration,
+    /// The number of latest prices kept for every price feed.
+    pub cache_size: usize,
+    /// For how long after the latest update of a disconnected price feed the
+    /// average of its cached prices is used, to bridge brief disconnects.
+    pub max_interpolation: Duration,
+}
+
+impl Default for Freshness {
+    fn default() -> Self {
+        Self {
+            max_age: MAX_PRICE_AGE,
+            cache_size: DEFAULT_CACHE_SIZE,
+            max_interpolation: DEFAULT_MAX_INTERPOLATION,
+        }
+    }
+}
+
+/// Connect to the given price feeds for a constant stream of rate updates.
+///
+/// The price feeds are given in order of preference. The latest update is
+/// taken from the first feed that is connected and not stale, failing over
+/// to the next feed if that is not the case. A feed that disconnected is
+/// still used within the interpolation window of `freshness`.
+///
+/// If a connection fails, it will automatically be re-established.
+pub fn connect(feeds: Vec<Box<dyn PriceFeed>>, freshness: Freshness) -> Result<PriceUpdates> {
+    if feeds.is_empty() {
+        bail!("At least one price feed has to be configured")
+    }
+    if freshness.cache_size == 0 {
+        bail!("At least one price has to be cached per price feed")
+    }
+
+    let feeds = feeds
+        .into_iter()
+        .map(|feed| Feed {
+            name: feed.name(),
+            updates: spawn_feed(Arc::from(feed), freshness.cache_size),
+        })
+        .collect();
+
+    Ok(PriceUpdates { feeds, freshness })
+}
+
+fn spawn_feed(feed: Arc<dyn PriceFeed>, cache_size: usize) -> watch::Receiver<FeedState> {
+    let (sender, feed_update_receiver) = watch::channel(FeedState::default());
+    let feed_update = Arc::new(Publisher {
+        sender,
+        state: Mutex::new(FeedState::default()),
+        cache_size,
+    });
+
+    tokio::spawn(async move {
+        // The default backoff config is fine for us apart from one thing:
+        // `max_elapsed_time`. If we don't get an error within this timeframe,
+        // backoff won't actually retry the operation.
+        let backoff = backoff::ExponentialBackoff {
+            max_elapsed_time: None,
+            ..backoff::ExponentialBackoff::default()
+        };
+
+        let name = feed.name();
+        let result = backoff::future::retry_notify::<Infallible, _, _, _, _, _>(
+            backoff,
+            || {
+                let feed_update = feed_update.clone();
+                let feed = feed.clone();
+                async move {
+                    let mut stream = feed.connect().await.map_err(backoff::Error::Transient)?;
+                    let mut latest = None;
+
+                    while let Some(update) =
+                        stream.try_next().await.map_err(backoff::Error::Transient)?
+                    {
+                        latest = update.or(latest);
+
+                        let price = match latest {
+                            Some(price) => price,
+                            None => continue,
+                        };
+
+                        if feed_update.tick(price).is_err() {
+                            return Err(backoff::Error::Permanent(anyhow!(
+                                "receiver disconnected"
+                            )));
+                        }
+                    }
+
+                    Err(backoff::Error::Transient(anyhow!("stream ended")))
+                }
+            },
+            |error, next: Duration| {
+                let _ = feed_update.fail(Error::Disconnected);
+
+                tracing::info!(
+                    "{} price feed connection failed, retrying in {}ms. Error {:#}",
+                    name,
+                    next.as_millis(),
+                    error
+                );
+            },
+        )
+        .await;
+
+        match result {
+            Err(e) => {
+                tracing::warn!(
+                    "{} price updates incurred an unrecoverable error: {:#}",
+                    name,
+                    e
+                );
+
+                // in case the retries fail permanently, let the subscribers know
+                feed_update.fail(Error::PermanentFailure)
+            }
+            Ok(never) => match never {},
+        }
+    });
+
+    feed_update_receiver
+}
+
+#[derive(Clone, Debug)]
+pub struct PriceUpdates {
+    feeds: Vec<Feed>,
+    freshness: Freshness,
+}
+
+#[derive(Clone, Debug)]
+struct Feed {
+    name: String,
+    updates: watch::Receiver<FeedState>,
+}
+
+impl PriceUpdates {
+    pub async fn wait_for_next_update(&mut self) -> Result<Result<PriceUpdate, Error>> {
+        let changes = self
+            .feeds
+            .iter_mut()
+            .map(|feed| Box::pin(feed.updates.changed()));
+        let (result, ..) = futures::future::select_all(changes).await;
+        result?;
+
+        Ok(self.latest_update())
+    }
+
+    /// The price of the first price feed that is fresh enough.
+    ///
+    /// Fails with [`Error::Stale`] if there are prices but all of them are too
+    /// old.
+    pub fn latest_update(&mut self) -> Result<PriceUpdate, Error> {
+        let Freshness {
+            max_age,
+            max_interpolation,
+            ..
+        } = self.freshness;
+        let now = Instant::now();
+        let mut youngest_age = None;
+
+        for (index, feed) in self.feeds.iter().enumerate() {
+            let state = feed.updates.borrow();
+            let latest = match state.ticks.back() {
+                Some(latest) => latest,
+                None => {
+                    if let Err(error) = &state.status {
+                        tracing::debug!(price_feed = %feed.name, "Price feed is unavailable: {}", error);
+                    }
+                    continue;
+                }
+            };
+            let age = now.saturating_duration_since(latest.received_at);
+            youngest_age = Some(youngest_age.map_or(age, |youngest: Duration| youngest.min(age)));
+
+            let update = match &state.status {
+                Ok(()) if age <= max_age => latest.price,
+                Ok(()) => {
+                    tracing::debug!(price_feed = %feed.name, age_secs = %age.as_secs(), "Price feed is stale");
+                    continue;
+                }
+                Err(error) if age <= max_interpolation.min(max_age) => {
+                    tracing::debug!(price_feed = %feed.name, "Price feed is unavailable, using the average of its cached prices: {}", error);
+                    state.average(now, max_age)
+                }
+                Err(error) => {
+                    tracing::debug!(price_feed = %feed.name, "Price feed is unavailable: {}", error);
+                    continue;
+                }
+            };
+
+            if index > 0 {
+                tracing::debug!(price_feed = %feed.name, "Using fallback price feed");
+            }
+
+            return Ok(update);
+        }
+
+        match youngest_age {
+            Some(age) => Err(Error::Stale { age }),
+            None => Err(Error::Unavailable),
+        }
+    }
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Rate is not yet available")]
+    NotYetAvailable,
+    #[error("Connection to the price feed was lost")]
+    Disconnected,
+    #[error("Permanently failed to retrieve rate from price feed")]
+    PermanentFailure,
+    #[error("None of the price feeds provides a rate")]
+    Unavailable,
+    #[error("The latest rate is {}s old", age.as_secs())]
+    Stale { age: Duration },
+}
+
+/// The age of the latest rate if `error` is an [`Error::Stale`], to tell stale
+/// rates apart from other errors of a rate source.
+pub fn stale_age(error: &(dyn std::error::Error + 'static)) -> Option<Duration> {
+    match error.downcast_ref::<Error>() {
+        Some(Error::Stale { age }) => Some(*age),
+        _ => None,
+    }
+}
+
+/// The latest prices of a price feed and whether it is connected.
+#[derive(Clone, Debug)]
+struct FeedState {
+    /// The latest prices, oldest first.
+    ticks: VecDeque<Tick>,
+    status: Result<(), Error>,
+}
+
+impl Default for FeedState {
+    fn default() -> Self {
+        Self {
+            ticks: VecDeque::new(),
+            status: Err(Error::NotYetAvailable),
+        }
+    }
+}
+
+impl FeedState {
+    /// The average of the cached prices that were not older than `max_age`
+    /// at `now`.
+    ///
+    /// Must only be called if there is such a price.
+    fn average(&self, now: Instant, max_age: Duration) -> PriceUpdate {
+        let asks = self
+            .ticks
+            .iter()
+            .filter(|tick| now.saturating_duration_since(tick.received_at) <= max_age)
+            .map(|tick| u128::from(tick.price.ask.as_sat()))
+            .collect::<Vec<_>>();
+        let average = asks.iter().sum::<u128>() / asks.len() as u128;
+
+        PriceUpdate {
+            ask: bitcoin::Amount::from_sat(
+                u64::try_from(average).expect("average of u64 values to fit into u64"),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Tick {
+    price: PriceUpdate,
+    received_at: Instant,
+}
+
+/// Publishes the state of a price feed to its subscribers.
+#[derive(Debug)]
+struct Publisher {
+    sender: watch::Sender<FeedState>,
+    state: Mutex<FeedState>,
+    cache_size: usize,
+}
+
+impl Publisher {
+    fn tick(&self, price: PriceUpdate) -> Result<(), watch::error::SendError<FeedState>> {
+        let mut state = self.state.lock().expect("publisher to not panic");
+        state.ticks.push_back(Tick {
+            price,
+            received_at: Instant::now(),
+        });
+        while state.ticks.len() > self.cache_size {
+            state.ticks.pop_front();
+        }
+        state.status = Ok(());
+
+        self.sender.send(state.clone())
+    }
+
+    fn fail(&self, error: Error) -> Result<(), watch::error::SendError<FeedState>> {
+        let mut state = self.state.lock().expect("publisher to not panic");
+        state.status = Err(error);
+
+        self.sender.send(state.clone())
+    }
+}
+
+/// A price feed that always publishes the same price.
+#[derive(Clone, Copy, Debug)]
+pub struct Static {
+    ask: bitcoin::Amount,
+}
+
+impl Static {
+    pub fn new(ask: bitcoin::Amount) -> Self {
+        Self { ask }
+    }
+}
+
+#[async_trait]
+impl PriceFeed for Static {
+    fn name(&self) -> String {
+        "Static".to_string()
+    }
+
+    async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+        let update = PriceUpdate { ask: self.ask };
+        let stream = interval_stream(PUBLISH_INTERVAL)
+            .map(move |_| Ok(Some(update)))
+            .boxed();
+
+        Ok(stream)
+    }
+}
+
+/// A price feed that periodically reads the price of 1 XMR in BTC from a
+/// file.
+///
+/// This allows to feed prices from arbitrary sources into the ASB by simply
+/// writing them to a file.
+#[derive(Clone, Debug)]
+pub struct File {
+    path: PathBuf,
+}
+
+impl File {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl PriceFeed for File {
+    fn name(&self) -> String {
+        format!("File {}", self.path.display())
+    }
+
+    async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+        let path = self.path.clone();
+        let stream = interval_stream(PUBLISH_INTERVAL)
+            .then(move |_| {
+                let path = path.clone();
+                async move {
+                    let content = tokio::fs::read_to_string(&path)
+                        .await
+                        .with_context(|| format!("Failed to read price from {}", path.display()))?;
+
+                    Ok(Some(parse_price(&content)?))
+                }
+            })
+            .boxed();
+
+        Ok(stream)
+    }
+}
+
+/// Parses the price of 1 XMR from a BTC amount such as `0.0065`.
+fn parse_price(content: &str) -> Result<PriceUpdate> {
+    let ask = bitcoin::Amount::from_str_in(content.trim(), ::bitcoin::Denomination::Bitcoin)
+        .with_context(|| format!("Failed to parse price `{}`", content.trim()))?;
+
+    Ok(PriceUpdate { ask })
+}
+
+/// A stream that yields immediately and then once every `period`.
+fn interval_stream(period: Duration) -> BoxStream<'static, ()> {
+    futures::stream::unfold(tokio::time::interval(period), |mut interval| async move {
+        interval.tick().await;
+        Some(((), interval))
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn given_primary_feed_is_available_then_uses_primary() {
+        let mut updates = connect(
+            vec![Box::new(Ticking(price(1))), Box::new(Ticking(price(2)))],
+            Freshness::default(),
+        )
+        .unwrap();
+
+        wait_until_available(&mut updates).await;
+
+        assert_eq!(updates.latest_update().unwrap(), price(1));
+    }
+
+    #[tokio::test]
+    async fn given_primary_feed_disconnects_then_fails_over_to_next_feed() {
+        let mut updates = connect(
+            vec![
+                Box::new(Disconnecting(vec![price(1)])),
+                Box::new(Ticking(price(2))),
+            ],
+            Freshness {
+                max_interpolation: Duration::ZERO,
+                ..Freshness::default()
+            },
+        )
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(updates.latest_update().unwrap(), price(2));
+    }
+
+    #[tokio::test]
+    async fn given_primary_feed_disconnects_within_interpolation_window_then_uses_average_of_cached_prices(
+    ) {
+        let mut updates = connect(
+            vec![
+                Box::new(Disconnecting(vec![price(100), price(300)])),
+                Box::new(Ticking(price(2))),
+            ],
+            Freshness::default(),
+        )
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(updates.latest_update().unwrap(), price(200));
+    }
+
+    #[tokio::test]
+    async fn given_cache_size_of_one_then_only_latest_price_is_interpolated() {
+        let mut updates = connect(
+            vec![Box::new(Disconnecting(vec![price(100), price(300)]))],
+            Freshness {
+                cache_size: 1,
+                ..Freshness::default()
+            },
+        )
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(updates.latest_update().unwrap(), price(300));
+    }
+
+    #[tokio::test]
+    async fn given_primary_feed_is_stale_then_fails_over_to_next_feed() {
+        let mut updates = connect(
+            vec![Box::new(Silent(price(1))), Box::new(Ticking(price(2)))],
+            Freshness {
+                max_age: Duration::from_millis(50),
+                ..Freshness::default()
+            },
+        )
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(updates.latest_update().unwrap(), price(2));
+    }
+
+    #[tokio::test]
+    async fn given_all_feeds_are_stale_then_rate_is_stale() {
+        let mut updates = connect(
+            vec![Box::new(Silent(price(1))), Box::new(Silent(price(2)))],
+            Freshness {
+                max_age: Duration::from_millis(50),
+                ..Freshness::default()
+            },
+        )
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let error = updates.latest_update().unwrap_err();
+        assert!(matches!(error, Error::Stale { age } if age >= Duration::from_millis(50)));
+    }
+
+    #[tokio::test]
+    async fn given_disconnected_feed_outside_interpolation_window_then_rate_is_stale() {
+        let mut updates = connect(vec![Box::new(Disconnecting(vec![price(1)]))], Freshness {
+            max_interpolation: Duration::from_millis(50),
+            ..Freshness::default()
+        })
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(matches!(updates.latest_update(), Err(Error::Stale { .. })));
+    }
+
+    #[tokio::test]
+    async fn given_no_feed_published_a_price_then_no_rate_is_available() {
+        let mut updates = connect(vec![Box::new(Pending)], Freshness::default()).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(matches!(updates.latest_update(), Err(Error::Unavailable)));
+    }
+
+    #[test]
+    fn given_cache_size_of_zero_then_fails_to_connect() {
+        let result = connect(vec![Box::new(Pending)], Freshness {
+            cache_size: 0,
+            ..Freshness::default()
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parses_price_from_file_content() {
+        let update = parse_price("0.00625000\n").unwrap();
+
+        assert_eq!(update, price(625_000));
+    }
+
+    fn price(sats: u64) -> PriceUpdate {
+        PriceUpdate {
+            ask: bitcoin::Amount::from_sat(sats),
+        }
+    }
+
+    async fn wait_until_available(updates: &mut PriceUpdates) {
+        while updates.latest_update().is_err() {
+            let _ = updates.wait_for_next_update().await.unwrap();
+        }
+    }
+
+    /// Publishes the same price every couple of milliseconds.
+    struct Ticking(PriceUpdate);
+
+    #[async_trait]
+    impl PriceFeed for Ticking {
+        fn name(&self) -> String {
+            "Ticking".to_string()
+        }
+
+        async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+            let update = self.0;
+
+            Ok(interval_stream(Duration::from_millis(10))
+                .map(move |_| Ok(Some(update)))
+                .boxed())
+        }
+    }
+
+    /// Publishes the prices and then loses the connection.
+    struct Disconnecting(Vec<PriceUpdate>);
+
+    #[async_trait]
+    impl PriceFeed for Disconnecting {
+        fn name(&self) -> String {
+            "Disconnecting".to_string()
+        }
+
+        async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+            let updates = self
+                .0
+                .iter()
+                .map(|update| Ok(Some(*update)))
+                .collect::<Vec<_>>();
+
+            Ok(stream::iter(updates).boxed())
+        }
+    }
+
+    /// Connects but never publishes a price.
+    struct Pending;
+
+    #[async_trait]
+    impl PriceFeed for Pending {
+        fn name(&self) -> String {
+            "Pending".to_string()
+        }
+
+        async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+            Ok(stream::pending().boxed())
+        }
+    }
+
+    /// Publishes a single price and then never shows a sign of life again.
+    struct Silent(PriceUpdate);
+
+    #[async_trait]
+    impl PriceFeed for Silent {
+        fn name(&self) -> String {
+            "Silent".to_string()
+        }
+
+        async fn connect(&self) -> Result<BoxStream<'static, Result<Option<PriceUpdate>>>> {
+            Ok(stream::iter(vec![Ok(Some(self.0))])
+                .chain(stream::pending())
+                .boxed())
+        }
+    }
+}
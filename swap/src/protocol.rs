@@ -1,7 +1,13 @@
+use crate::asb::identity::IdentityRotation;
+use crate::asb::outbox::OutboundMessage;
+use crate::asb::reputation::{Ban, Misbehaviour};
+use crate::asb::sweep::Sweep;
+use crate::cli::address_book::AddressBookEntry;
+use crate::network::swap_setup::receipt::SignedReceipt;
 use crate::protocol::alice::swap::is_complete as alice_is_complete;
 use crate::protocol::alice::AliceState;
 use crate::protocol::bob::swap::is_complete as bob_is_complete;
-use crate::protocol::bob::BobState;
+use crate::protocol::bob::{BobState, SwapSetupParameters};
 use crate::{bitcoin, monero};
 use anyhow::Result;
 use async_trait::async_trait;
@@ -13,10 +19,13 @@ use sha2::Sha256;
 use sigma_fun::ext::dl_secp256k1_ed25519_eq::{CrossCurveDLEQ, CrossCurveDLEQProof};
 use sigma_fun::HashTranscript;
 use std::convert::TryInto;
+use std::fmt;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
+pub mod state_machine;
 
 pub static CROSS_CURVE_PROOF_SYSTEM: Lazy<
     CrossCurveDLEQ<HashTranscript<Sha256, rand_chacha::ChaCha20Rng>>,
@@ -90,6 +99,15 @@ impl State {
     }
 }
 
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Alice(state) => write!(f, "{}", state),
+            State::Bob(state) => write!(f, "{}", state),
+        }
+    }
+}
+
 impl From<AliceState> for State {
     fn from(alice: AliceState) -> Self {
         Self::Alice(alice)
@@ -132,6 +150,16 @@ impl TryInto<AliceState> for State {
     }
 }
 
+/// A state a swap entered, as recorded in the database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedState {
+    /// Increases with every state that is recorded, across all swaps.
+    pub id: i64,
+    pub swap_id: Uuid,
+    pub entered_at: String,
+    pub state: State,
+}
+
 #[async_trait]
 pub trait Database {
     async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()>;
@@ -141,6 +169,92 @@ pub trait Database {
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()>;
     async fn get_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>>;
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()>;
+    /// Stores a state that was entered at the given time as the latest state,
+    /// e.g. one imported from another database.
+    async fn insert_recorded_state(
+        &self,
+        swap_id: Uuid,
+        entered_at: String,
+        state: State,
+    ) -> Result<()>;
     async fn get_state(&self, swap_id: Uuid) -> Result<State>;
+    /// Returns every state the swap has been in together with the time it
+    /// was entered, ordered from the first to the latest state.
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<(String, State)>>;
     async fn all(&self) -> Result<Vec<(Uuid, State)>>;
+    /// Returns the id of the state that was recorded last, if any.
+    async fn get_latest_state_id(&self) -> Result<Option<i64>>;
+    /// Returns the states of all swaps that were recorded after the state
+    /// with the given id, ordered from the first to the latest state.
+    async fn get_states_after(&self, id: i64) -> Result<Vec<RecordedState>>;
+    /// Stores the parameters a swap was set up with, replacing previously
+    /// stored ones.
+    async fn insert_swap_setup_parameters(
+        &self,
+        swap_id: Uuid,
+        parameters: SwapSetupParameters,
+    ) -> Result<()>;
+    async fn get_swap_setup_parameters(&self, swap_id: Uuid)
+        -> Result<Option<SwapSetupParameters>>;
+    async fn insert_misbehaviour(
+        &self,
+        peer_id: PeerId,
+        misbehaviour: Misbehaviour,
+        recorded_at: OffsetDateTime,
+    ) -> Result<()>;
+    /// Returns the misbehaviours of the peer recorded since the given time,
+    /// ordered from the first to the latest misbehaviour.
+    async fn get_misbehaviours_since(
+        &self,
+        peer_id: PeerId,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Misbehaviour>>;
+    /// Stores the ban, replacing an existing ban of the same peer.
+    async fn insert_ban(&self, ban: Ban) -> Result<()>;
+    /// Removes the ban of the peer, returns whether the peer was banned.
+    async fn remove_ban(&self, peer_id: PeerId) -> Result<bool>;
+    /// Returns all stored bans, including expired ones.
+    async fn get_bans(&self) -> Result<Vec<Ban>>;
+    async fn insert_sweep(&self, sweep: Sweep) -> Result<()>;
+    /// Returns all sweeps ordered from the first to the latest sweep.
+    async fn get_sweeps(&self) -> Result<Vec<Sweep>>;
+    async fn insert_xmr_refund_sweep(
+        &self,
+        swap_id: Uuid,
+        sweep: monero::wallet::Sweep,
+    ) -> Result<()>;
+    /// Returns the sweeps that refunded the Monero of the swap, ordered from
+    /// the first to the latest sweep.
+    async fn get_xmr_refund_sweeps(&self, swap_id: Uuid) -> Result<Vec<monero::wallet::Sweep>>;
+    /// Stores the message until the peer acknowledged it, replacing an
+    /// earlier message of the same swap.
+    async fn insert_outbound_message(
+        &self,
+        peer_id: PeerId,
+        message: OutboundMessage,
+    ) -> Result<()>;
+    async fn remove_outbound_message(&self, swap_id: Uuid) -> Result<()>;
+    /// Returns the messages that were not acknowledged yet.
+    async fn get_outbound_messages(&self) -> Result<Vec<(PeerId, OutboundMessage)>>;
+    /// Stores the entry, replacing the address of the same kind stored under
+    /// its label.
+    async fn insert_address_book_entry(&self, entry: AddressBookEntry) -> Result<()>;
+    /// Removes the addresses stored under the label, returns whether there
+    /// were any.
+    async fn remove_address_book_entries(&self, label: &str) -> Result<bool>;
+    /// Returns all entries of the address book ordered by their label.
+    async fn get_address_book(&self) -> Result<Vec<AddressBookEntry>>;
+    async fn insert_identity_rotation(&self, rotation: IdentityRotation) -> Result<()>;
+    /// Returns all rotations of the libp2p identity ordered from the first to
+    /// the latest rotation.
+    async fn get_identity_rotations(&self) -> Result<Vec<IdentityRotation>>;
+    /// Stores the generation of the libp2p identity the swap was set up with.
+    async fn insert_swap_identity(&self, swap_id: Uuid, generation: u32) -> Result<()>;
+    /// Returns the generation of the libp2p identity the swap was set up with,
+    /// swaps without a stored generation were set up with generation 0.
+    async fn get_swap_identity(&self, swap_id: Uuid) -> Result<u32>;
+    async fn insert_receipt(&self, swap_id: Uuid, receipt: SignedReceipt) -> Result<()>;
+    /// Returns the receipts the seller signed for the swap, ordered from the
+    /// first to the latest receipt.
+    async fn get_receipts(&self, swap_id: Uuid) -> Result<Vec<SignedReceipt>>;
 }
@@ -6,9 +6,11 @@ use crate::{asb, bitcoin, monero};
 use std::sync::Arc;
 use uuid::Uuid;
 
+pub use self::redeem_signer::{LocalRedeemSigner, RedeemSigner, RemoteRedeemSigner};
 pub use self::state::*;
-pub use self::swap::{run, run_until};
+pub use self::swap::{run, run_until, run_until_shutdown};
 
+pub mod redeem_signer;
 pub mod state;
 pub mod swap;
 
@@ -20,4 +22,5 @@ pub struct Swap {
     pub env_config: Config,
     pub swap_id: Uuid,
     pub db: Arc<dyn Database + Send + Sync>,
+    pub redeem_signer: Arc<dyn RedeemSigner>,
 }
@@ -0,0 +1,232 @@
+//! Signs Alice's half of the Bitcoin redeem transaction.
+//!
+//! By default the redeem transaction is signed in-process with the key Alice
+//! generated for the swap. Operators can have it signed by an external signing
+//! service instead, which holds the keys of the swaps it signs for, approves
+//! every redeem and can keep an audit trail of them. The signature returned by
+//! the service is verified before it is used, the ASB can fall back to the
+//! local key if the service fails.
+
+use crate::bitcoin::{self, verify_sig, Signature};
+use ::bitcoin::hashes::Hash;
+use ::bitcoin::SigHash;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ecdsa_fun::fun::Point;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+/// What is needed to sign the redeem transaction of a swap.
+#[derive(Debug, Clone, Copy)]
+pub struct RedeemRequest<'a> {
+    pub swap_id: Uuid,
+    /// The key Alice generated for the swap, external signers only use its
+    /// public key unless they fall back to signing locally.
+    pub local_key: &'a bitcoin::SecretKey,
+    pub digest: SigHash,
+    pub redeem_address: &'a bitcoin::Address,
+    pub btc: bitcoin::Amount,
+}
+
+#[async_trait]
+pub trait RedeemSigner: Send + Sync {
+    /// Returns the signature of Alice's key for the digest of the redeem
+    /// transaction.
+    async fn sign_redeem(&self, request: RedeemRequest<'_>) -> Result<Signature>;
+}
+
+/// Signs with the key of the swap in-process.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalRedeemSigner;
+
+#[async_trait]
+impl RedeemSigner for LocalRedeemSigner {
+    async fn sign_redeem(&self, request: RedeemRequest<'_>) -> Result<Signature> {
+        Ok(request.local_key.sign(request.digest))
+    }
+}
+
+/// Requests the signature from a signing service over HTTP.
+///
+/// The request is posted as JSON and contains the `swap_id`, the
+/// compressed `public_key` and the `digest` to sign as hex, the
+/// `redeem_address` and the swapped amount as `btc_sat`. The service answers
+/// with the 64 byte compact `signature` as hex.
+#[derive(Debug, Clone)]
+pub struct RemoteRedeemSigner {
+    client: reqwest::Client,
+    url: Url,
+    fallback_to_local: bool,
+}
+
+#[derive(Serialize)]
+struct SignatureRequest {
+    swap_id: Uuid,
+    public_key: String,
+    digest: String,
+    redeem_address: String,
+    btc_sat: u64,
+}
+
+#[derive(Deserialize)]
+struct SignatureResponse {
+    signature: String,
+}
+
+impl RemoteRedeemSigner {
+    /// Requests that take longer than `timeout` fail, with `fallback_to_local`
+    /// failed requests are signed with the local key instead.
+    pub fn new(url: Url, timeout: Duration, fallback_to_local: bool) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build HTTP client for redeem signer")?;
+
+        Ok(Self {
+            client,
+            url,
+            fallback_to_local,
+        })
+    }
+
+    async fn request_signature(&self, request: &RedeemRequest<'_>) -> Result<Signature> {
+        let public_key = request.local_key.public();
+        let body = SignatureRequest {
+            swap_id: request.swap_id,
+            public_key: hex::encode(Point::from(public_key).to_bytes()),
+            digest: hex::encode(request.digest.into_inner()),
+            redeem_address: request.redeem_address.to_string(),
+            btc_sat: request.btc.as_sat(),
+        };
+
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await
+            .context("Failed to request signature from redeem signer")?
+            .error_for_status()
+            .context("Redeem signer refused to sign")?
+            .bytes()
+            .await
+            .context("Failed to read response of redeem signer")?;
+        let response = serde_json::from_slice::<SignatureResponse>(&response)
+            .context("Failed to decode response of redeem signer")?;
+
+        let bytes = hex::decode(&response.signature)
+            .context("Redeem signer returned a signature that is not hex")?;
+        let bytes = <[u8; 64]>::try_from(bytes.as_slice())
+            .context("Redeem signer returned a signature that is not 64 bytes")?;
+        let signature =
+            Signature::from_bytes(bytes).context("Redeem signer returned an invalid signature")?;
+
+        verify_sig(&public_key, &request.digest, &signature)
+            .context("Redeem signer returned a signature for the wrong key or digest")?;
+
+        Ok(signature)
+    }
+}
+
+#[async_trait]
+impl RedeemSigner for RemoteRedeemSigner {
+    async fn sign_redeem(&self, request: RedeemRequest<'_>) -> Result<Signature> {
+        match self.request_signature(&request).await {
+            Ok(signature) => Ok(signature),
+            Err(error) if self.fallback_to_local => {
+                tracing::warn!(swap_id = %request.swap_id, "Signing redeem transaction with the local key: {:#}", error);
+                LocalRedeemSigner.sign_redeem(request).await
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use std::str::FromStr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves a single request with the given signature.
+    async fn serve_signature(signature: Signature) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/sign", listener.local_addr().unwrap())).unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request).await.unwrap();
+
+            let body =
+                serde_json::json!({ "signature": hex::encode(signature.to_bytes()) }).to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        url
+    }
+
+    fn request<'a>(
+        key: &'a bitcoin::SecretKey,
+        address: &'a bitcoin::Address,
+    ) -> RedeemRequest<'a> {
+        RedeemRequest {
+            swap_id: Uuid::new_v4(),
+            local_key: key,
+            digest: SigHash::hash(b"redeem"),
+            redeem_address: address,
+            btc: bitcoin::Amount::from_sat(100_000),
+        }
+    }
+
+    fn address() -> bitcoin::Address {
+        bitcoin::Address::from_str("bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw").unwrap()
+    }
+
+    #[tokio::test]
+    async fn remote_signatures_are_verified_before_they_are_used() {
+        let key = bitcoin::SecretKey::new_random(&mut OsRng);
+        let address = address();
+        let request = request(&key, &address);
+        let timeout = Duration::from_secs(5);
+
+        let url = serve_signature(key.sign(request.digest)).await;
+        let signer = RemoteRedeemSigner::new(url, timeout, false).unwrap();
+        let signature = signer.sign_redeem(request).await.unwrap();
+        assert!(verify_sig(&key.public(), &request.digest, &signature).is_ok());
+
+        let other_key = bitcoin::SecretKey::new_random(&mut OsRng);
+        let url = serve_signature(other_key.sign(request.digest)).await;
+        let signer = RemoteRedeemSigner::new(url, timeout, false).unwrap();
+        assert!(signer.sign_redeem(request).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_remote_signer_falls_back_to_the_local_key_if_allowed() {
+        let key = bitcoin::SecretKey::new_random(&mut OsRng);
+        let address = address();
+        let request = request(&key, &address);
+        // nothing listens on the discard port
+        let url = Url::parse("http://127.0.0.1:9/sign").unwrap();
+        let timeout = Duration::from_secs(5);
+
+        let strict = RemoteRedeemSigner::new(url.clone(), timeout, false).unwrap();
+        assert!(strict.sign_redeem(request).await.is_err());
+
+        let lenient = RemoteRedeemSigner::new(url, timeout, true).unwrap();
+        let signature = lenient.sign_redeem(request).await.unwrap();
+        assert!(verify_sig(&key.public(), &request.digest, &signature).is_ok());
+    }
+}
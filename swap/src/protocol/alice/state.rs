@@ -2,10 +2,11 @@ use crate::bitcoin::{
     current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
     TxPunish, TxRedeem, TxRefund, Txid,
 };
-use crate::env::Config;
-use crate::monero::wallet::{TransferRequest, WatchRequest};
+use crate::monero::wallet::{Sweep, TransferRequest, WatchRequest};
 use crate::monero::TransferProof;
 use crate::monero_ext::ScalarExt;
+use crate::network::swap_setup::Timelocks;
+use crate::protocol::alice::redeem_signer::{RedeemRequest, RedeemSigner};
 use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
 use crate::{bitcoin, monero};
 use anyhow::{anyhow, bail, Context, Result};
@@ -107,6 +108,99 @@ impl fmt::Display for AliceState {
     }
 }
 
+impl AliceState {
+    /// The amount of Bitcoin that is locked in this swap, if the state still
+    /// knows about it.
+    pub fn btc_amount(&self) -> Option<bitcoin::Amount> {
+        self.state3().map(|state3| state3.tx_lock.lock_amount())
+    }
+
+    /// The amount of Monero that was agreed upon for this swap, if the state
+    /// still knows about it.
+    pub fn xmr_amount(&self) -> Option<monero::Amount> {
+        self.state3().map(|state3| state3.xmr)
+    }
+
+    /// The timelocks that were agreed upon for this swap, if the state still
+    /// knows about them.
+    pub fn timelocks(&self) -> Option<Timelocks> {
+        self.state3().map(|state3| Timelocks {
+            cancel: state3.cancel_timelock,
+            punish: state3.punish_timelock,
+        })
+    }
+
+    pub fn tx_lock_id(&self) -> Option<bitcoin::Txid> {
+        self.state3().map(|state3| state3.tx_lock.txid())
+    }
+
+    /// The fees of the transactions spending the locked Bitcoin, if the state
+    /// still knows about them.
+    pub fn tx_fees(&self) -> Option<bitcoin::TxFees> {
+        self.state3().map(|state3| bitcoin::TxFees {
+            redeem: state3.tx_redeem_fee,
+            cancel: state3.tx_cancel_fee,
+            refund: state3.tx_refund_fee,
+            punish: state3.tx_punish_fee,
+        })
+    }
+
+    /// The id of the Bitcoin cancel transaction, if it was published.
+    pub fn tx_cancel_id(&self) -> Option<bitcoin::Txid> {
+        match self {
+            AliceState::BtcCancelled { state3, .. }
+            | AliceState::BtcRefunded { state3, .. }
+            | AliceState::BtcPunishable { state3, .. } => Some(state3.tx_cancel().txid()),
+            _ => None,
+        }
+    }
+
+    /// The id of the Bitcoin refund transaction, if Bob published it.
+    pub fn tx_refund_id(&self) -> Option<bitcoin::Txid> {
+        match self {
+            AliceState::BtcRefunded { state3, .. } => Some(state3.tx_refund().txid()),
+            _ => None,
+        }
+    }
+
+    pub fn xmr_lock_tx_hash(&self) -> Option<monero::TxHash> {
+        match self {
+            AliceState::XmrLockTransactionSent { transfer_proof, .. }
+            | AliceState::XmrLocked { transfer_proof, .. }
+            | AliceState::XmrLockTransferProofSent { transfer_proof, .. }
+            | AliceState::EncSigLearned { transfer_proof, .. }
+            | AliceState::BtcCancelled { transfer_proof, .. }
+            | AliceState::BtcRefunded { transfer_proof, .. }
+            | AliceState::BtcPunishable { transfer_proof, .. }
+            | AliceState::CancelTimelockExpired { transfer_proof, .. } => {
+                Some(transfer_proof.tx_hash())
+            }
+            _ => None,
+        }
+    }
+
+    fn state3(&self) -> Option<&State3> {
+        match self {
+            AliceState::Started { state3 }
+            | AliceState::BtcLockTransactionSeen { state3 }
+            | AliceState::BtcLocked { state3 }
+            | AliceState::XmrLockTransactionSent { state3, .. }
+            | AliceState::XmrLocked { state3, .. }
+            | AliceState::XmrLockTransferProofSent { state3, .. }
+            | AliceState::EncSigLearned { state3, .. }
+            | AliceState::BtcRedeemTransactionPublished { state3 }
+            | AliceState::BtcCancelled { state3, .. }
+            | AliceState::BtcRefunded { state3, .. }
+            | AliceState::BtcPunishable { state3, .. }
+            | AliceState::CancelTimelockExpired { state3, .. } => Some(state3.as_ref()),
+            AliceState::BtcRedeemed
+            | AliceState::XmrRefunded
+            | AliceState::BtcPunished
+            | AliceState::SafelyAborted => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     a: bitcoin::SecretKey,
@@ -130,7 +224,8 @@ impl State0 {
     pub fn new<R>(
         btc: bitcoin::Amount,
         xmr: monero::Amount,
-        env_config: Config,
+        cancel_timelock: CancelTimelock,
+        punish_timelock: PunishTimelock,
         redeem_address: bitcoin::Address,
         punish_address: bitcoin::Address,
         tx_redeem_fee: bitcoin::Amount,
@@ -159,8 +254,8 @@ impl State0 {
             punish_address,
             btc,
             xmr,
-            cancel_timelock: env_config.bitcoin_cancel_timelock,
-            punish_timelock: env_config.bitcoin_punish_timelock,
+            cancel_timelock,
+            punish_timelock,
             tx_redeem_fee,
             tx_punish_fee,
         }
@@ -505,7 +600,8 @@ impl State3 {
         file_name: String,
         spend_key: monero::PrivateKey,
         transfer_proof: TransferProof,
-    ) -> Result<()> {
+        refund_address: monero::Address,
+    ) -> Result<Vec<Sweep>> {
         let view_key = self.v;
 
         // Ensure that the XMR to be refunded are spendable by awaiting 10 confirmations
@@ -515,15 +611,14 @@ impl State3 {
             .await?;
 
         monero_wallet
-            .create_from(
+            .sweep_from_keys(
                 file_name,
                 spend_key,
                 view_key,
                 monero_wallet_restore_blockheight,
+                refund_address,
             )
-            .await?;
-
-        Ok(())
+            .await
     }
 
     pub async fn punish_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
@@ -544,6 +639,36 @@ impl State3 {
             .context("Failed to complete Bitcoin redeem transaction")
     }
 
+    /// Like [`State3::signed_redeem_transaction`], with the signature of Alice
+    /// made by the given signer.
+    pub async fn signed_redeem_transaction_with(
+        &self,
+        swap_id: Uuid,
+        signer: &dyn RedeemSigner,
+        sig: bitcoin::EncryptedSignature,
+    ) -> Result<bitcoin::Transaction> {
+        let tx_redeem = self.tx_redeem();
+        let sig_a = signer
+            .sign_redeem(RedeemRequest {
+                swap_id,
+                local_key: &self.a,
+                digest: tx_redeem.digest(),
+                redeem_address: &self.redeem_address,
+                btc: self.btc,
+            })
+            .await?;
+
+        tx_redeem
+            .complete_with_signature(
+                sig,
+                self.a.public(),
+                sig_a,
+                self.s_a.to_secpfun_scalar(),
+                self.B,
+            )
+            .context("Failed to complete Bitcoin redeem transaction")
+    }
+
     pub fn signed_cancel_transaction(&self) -> Result<bitcoin::Transaction> {
         self.tx_cancel()
             .complete_as_alice(self.a.clone(), self.B, self.tx_cancel_sig_bob.clone())
@@ -556,7 +681,7 @@ impl State3 {
             .context("Failed to complete Bitcoin punish transaction")
     }
 
-    fn tx_punish(&self) -> TxPunish {
+    pub fn tx_punish(&self) -> TxPunish {
         bitcoin::TxPunish::new(
             &self.tx_cancel(),
             &self.punish_address,
@@ -1,11 +1,14 @@
 //! Run an XMR/BTC swap in the role of Alice.
 //! Alice holds XMR and wishes receive BTC.
+use crate::asb::shutdown::Shutdown;
 use crate::asb::{EventLoopHandle, LatestRate};
 use crate::bitcoin::ExpiredTimelocks;
 use crate::env::Config;
-use crate::protocol::alice::{AliceState, Swap};
+use crate::protocol::alice::{AliceState, RedeemSigner, State3, Swap};
+use crate::protocol::Database;
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
+use monero_rpc::wallet::BlockHeight;
 use tokio::select;
 use tokio::time::timeout;
 use uuid::Uuid;
@@ -17,7 +20,7 @@ where
     run_until(swap, |_| false, rate_service).await
 }
 
-#[tracing::instrument(name = "swap", skip(swap,exit_early,rate_service), fields(id = %swap.swap_id), err)]
+#[tracing::instrument(name = "swap", skip(swap,exit_early,rate_service), fields(swap_id = %swap.swap_id, peer_id = %swap.event_loop_handle.peer()), err)]
 pub async fn run_until<LR>(
     mut swap: Swap,
     exit_early: fn(&AliceState) -> bool,
@@ -33,9 +36,11 @@ where
             swap.swap_id,
             current_state,
             &mut swap.event_loop_handle,
+            swap.db.as_ref(),
             swap.bitcoin_wallet.as_ref(),
             swap.monero_wallet.as_ref(),
             &swap.env_config,
+            swap.redeem_signer.as_ref(),
             rate_service.clone(),
         )
         .await?;
@@ -48,13 +53,73 @@ where
     Ok(current_state)
 }
 
+/// Runs the swap until it is complete or a shutdown is requested.
+///
+/// On shutdown the swap stops at the next state transition, unless the current
+/// transition only waits for the blockchain or the peer, then it is stopped
+/// right away. Either way the returned state is the one persisted last, the
+/// swap continues from it once it is resumed.
+#[tracing::instrument(name = "swap", skip(swap,rate_service,shutdown), fields(swap_id = %swap.swap_id, peer_id = %swap.event_loop_handle.peer()), err)]
+pub async fn run_until_shutdown<LR>(
+    mut swap: Swap,
+    rate_service: LR,
+    mut shutdown: Shutdown,
+) -> Result<AliceState>
+where
+    LR: LatestRate + Clone,
+{
+    let mut current_state = swap.state;
+
+    while !is_complete(&current_state) {
+        if shutdown.is_requested() {
+            tracing::info!(state = %current_state, "Stopping swap for shutdown");
+            return Ok(current_state);
+        }
+
+        let interruptible = is_interruptible(&current_state);
+        let state = current_state.clone();
+        let transition = next_state(
+            swap.swap_id,
+            current_state,
+            &mut swap.event_loop_handle,
+            swap.db.as_ref(),
+            swap.bitcoin_wallet.as_ref(),
+            swap.monero_wallet.as_ref(),
+            &swap.env_config,
+            swap.redeem_signer.as_ref(),
+            rate_service.clone(),
+        );
+
+        current_state = if interruptible {
+            select! {
+                next_state = transition => next_state?,
+                _ = shutdown.requested() => {
+                    tracing::info!(%state, "Stopping swap for shutdown");
+                    return Ok(state);
+                }
+            }
+        } else {
+            transition.await?
+        };
+
+        swap.db
+            .insert_latest_state(swap.swap_id, current_state.clone().into())
+            .await?;
+    }
+
+    Ok(current_state)
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn next_state<LR>(
     swap_id: Uuid,
     state: AliceState,
     event_loop_handle: &mut EventLoopHandle,
+    db: &(dyn Database + Send + Sync),
     bitcoin_wallet: &bitcoin::Wallet,
     monero_wallet: &monero::Wallet,
     env_config: &Config,
+    redeem_signer: &dyn RedeemSigner,
     mut rate_service: LR,
 ) -> Result<AliceState>
 where
@@ -69,23 +134,42 @@ where
     Ok(match state {
         AliceState::Started { state3 } => {
             let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
-            match timeout(
-                env_config.bitcoin_lock_mempool_timeout,
-                tx_lock_status.wait_until_seen(),
-            )
-            .await
-            {
-                Err(_) => {
+
+            // `None` if Bob aborted the swap before locking the Bitcoin
+            let tx_lock_seen = select! {
+                result = timeout(
+                    env_config.bitcoin_lock_mempool_timeout,
+                    tx_lock_status.wait_until_seen(),
+                ) => Some(result),
+                Ok(()) = event_loop_handle.recv_abort() => None,
+            };
+
+            match tx_lock_seen {
+                Some(Err(_)) => {
                     tracing::info!(
                         minutes = %env_config.bitcoin_lock_mempool_timeout.as_secs_f64() / 60.0,
                         "TxLock lock was not seen in mempool in time",
                     );
+
+                    // Bob is told so that he does not lock the Bitcoin after all, he is not
+                    // waiting for an answer if we cannot reach him.
+                    if let Err(error) = event_loop_handle.send_abort().await {
+                        tracing::debug!(
+                            "Failed to tell Bob that the swap was aborted: {:#}",
+                            error
+                        );
+                    }
+
                     AliceState::SafelyAborted
                 }
-                Ok(res) => {
+                Some(Ok(res)) => {
                     res?;
                     AliceState::BtcLockTransactionSeen { state3 }
                 }
+                None => {
+                    tracing::info!("Bob aborted the swap before locking the Bitcoin");
+                    AliceState::SafelyAborted
+                }
             }
         }
         AliceState::BtcLockTransactionSeen { state3 } => {
@@ -113,14 +197,40 @@ where
         AliceState::BtcLocked { state3 } => {
             match state3.expired_timelocks(bitcoin_wallet).await? {
                 ExpiredTimelocks::None => {
+                    // A reorg since TxLock was final, e.g. while the swap was
+                    // not running, can drop it from the chain again. The
+                    // Monero is only locked once TxLock is final again.
+                    let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
+                    match timeout(
+                        env_config.bitcoin_lock_confirmed_timeout,
+                        tx_lock_status.wait_until_final(),
+                    )
+                    .await
+                    {
+                        Err(_) => {
+                            tracing::info!(
+                                minutes = %env_config.bitcoin_lock_confirmed_timeout.as_secs_f64() / 60.0,
+                                "TxLock was reorganized out of the chain and did not get enough confirmations again in time",
+                            );
+                            return Ok(AliceState::SafelyAborted);
+                        }
+                        Ok(res) => res?,
+                    }
+
                     // Record the current monero wallet block height so we don't have to scan from
                     // block 0 for scenarios where we create a refund wallet.
                     let monero_wallet_restore_blockheight = monero_wallet.block_height().await?;
 
                     let transfer_proof = monero_wallet
-                        .transfer(state3.lock_xmr_transfer_request())
+                        .transfer_from(
+                            state3.lock_xmr_transfer_request(),
+                            xmr_refund_address(swap_id, db, monero_wallet).await,
+                        )
                         .await?;
 
+                    // The balance of the wallet no longer includes the locked XMR.
+                    event_loop_handle.release_reservation();
+
                     AliceState::XmrLockTransactionSent {
                         monero_wallet_restore_blockheight,
                         transfer_proof,
@@ -223,7 +333,10 @@ where
         } => match state3.expired_timelocks(bitcoin_wallet).await? {
             ExpiredTimelocks::None => {
                 let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
-                match state3.signed_redeem_transaction(*encrypted_signature) {
+                match state3
+                    .signed_redeem_transaction_with(swap_id, redeem_signer, *encrypted_signature)
+                    .await
+                {
                     Ok(tx) => match bitcoin_wallet.broadcast(tx, "redeem").await {
                         Ok((_, subscription)) => match subscription.wait_until_seen().await {
                             Ok(_) => AliceState::BtcRedeemTransactionPublished { state3 },
@@ -342,15 +455,16 @@ where
             spend_key,
             state3,
         } => {
-            state3
-                .refund_xmr(
-                    monero_wallet,
-                    monero_wallet_restore_blockheight,
-                    swap_id.to_string(),
-                    spend_key,
-                    transfer_proof,
-                )
-                .await?;
+            refund_xmr(
+                swap_id,
+                db,
+                monero_wallet,
+                &state3,
+                monero_wallet_restore_blockheight,
+                spend_key,
+                transfer_proof,
+            )
+            .await?;
 
             AliceState::XmrRefunded
         }
@@ -397,6 +511,90 @@ where
     })
 }
 
+/// The address the Monero of a swap is refunded to, the ASB also locks the
+/// Monero of the swap from it and the main address.
+///
+/// Swaps that were started before a subaddress was created for every swap
+/// have no address in the database and are refunded to the main address.
+pub async fn xmr_refund_address<D>(
+    swap_id: Uuid,
+    db: &D,
+    monero_wallet: &monero::Wallet,
+) -> monero::Address
+where
+    D: Database + ?Sized,
+{
+    match db.get_monero_address(swap_id).await {
+        Ok(address) => address,
+        Err(_) => monero_wallet.get_main_address(),
+    }
+}
+
+/// Sweeps the refunded Monero of the swap to its refund address and records
+/// the sweeps in the database.
+///
+/// Waits until the Monero is unlocked, the fee of the sweep is paid from the
+/// refunded Monero. If the Monero was already swept, e.g. before the swap was
+/// resumed, nothing is swept again.
+pub async fn refund_xmr<D>(
+    swap_id: Uuid,
+    db: &D,
+    monero_wallet: &monero::Wallet,
+    state3: &State3,
+    monero_wallet_restore_blockheight: BlockHeight,
+    spend_key: monero::PrivateKey,
+    transfer_proof: monero::TransferProof,
+) -> Result<()>
+where
+    D: Database + ?Sized,
+{
+    let refund_address = xmr_refund_address(swap_id, db, monero_wallet).await;
+
+    let sweeps = state3
+        .refund_xmr(
+            monero_wallet,
+            monero_wallet_restore_blockheight,
+            swap_id.to_string(),
+            spend_key,
+            transfer_proof,
+            refund_address,
+        )
+        .await?;
+
+    if sweeps.is_empty() {
+        tracing::warn!(%refund_address, "Refund wallet is empty, the Monero was swept before");
+    }
+
+    for sweep in sweeps {
+        tracing::info!(
+            txid = %sweep.txid,
+            address = %sweep.address,
+            amount = %sweep.amount,
+            fee = %sweep.fee,
+            "Refunded Monero"
+        );
+        db.insert_xmr_refund_sweep(swap_id, sweep).await?;
+    }
+
+    Ok(())
+}
+
+/// Whether the transition out of the state only waits for the blockchain or
+/// the peer, so that stopping it midway does not repeat or lose any action
+/// once the swap is resumed.
+fn is_interruptible(state: &AliceState) -> bool {
+    matches!(
+        state,
+        AliceState::Started { .. }
+            | AliceState::BtcLockTransactionSeen { .. }
+            | AliceState::XmrLockTransactionSent { .. }
+            | AliceState::XmrLocked { .. }
+            | AliceState::XmrLockTransferProofSent { .. }
+            | AliceState::BtcRedeemTransactionPublished { .. }
+            | AliceState::BtcCancelled { .. }
+    )
+}
+
 pub(crate) fn is_complete(state: &AliceState) -> bool {
     matches!(
         state,
@@ -1,13 +1,17 @@
 use std::sync::Arc;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::monero::wallet::WatchRequest;
+use crate::network::swap_setup::{ConfirmationSchedule, Timelocks};
 use crate::protocol::Database;
 use crate::{bitcoin, cli, env, monero};
+use monero_rpc::wallet::BlockHeight;
 
 pub use self::state::*;
-pub use self::swap::{run, run_until};
+pub use self::swap::{run, run_until, run_until_and_report};
 use std::convert::TryInto;
 
 pub mod state;
@@ -18,7 +22,7 @@ pub struct Swap {
     pub event_loop_handle: cli::EventLoopHandle,
     pub db: Arc<dyn Database + Send + Sync>,
     pub bitcoin_wallet: Arc<bitcoin::Wallet>,
-    pub monero_wallet: Arc<monero::Wallet>,
+    pub monero_wallet: MoneroWallet,
     pub env_config: env::Config,
     pub id: Uuid,
     pub monero_receive_address: monero::Address,
@@ -30,17 +34,21 @@ impl Swap {
         db: Arc<dyn Database + Send + Sync>,
         id: Uuid,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
-        monero_wallet: Arc<monero::Wallet>,
+        monero_wallet: MoneroWallet,
         env_config: env::Config,
         event_loop_handle: cli::EventLoopHandle,
         monero_receive_address: monero::Address,
         bitcoin_change_address: bitcoin::Address,
         btc_amount: bitcoin::Amount,
+        timelocks: Option<Timelocks>,
+        confirmation_schedule: Option<ConfirmationSchedule>,
     ) -> Self {
         Self {
             state: BobState::Started {
                 btc_amount,
                 change_address: bitcoin_change_address,
+                timelocks,
+                confirmation_schedule,
             },
             event_loop_handle,
             db,
@@ -57,7 +65,7 @@ impl Swap {
         db: Arc<dyn Database + Send + Sync>,
         id: Uuid,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
-        monero_wallet: Arc<monero::Wallet>,
+        monero_wallet: MoneroWallet,
         env_config: env::Config,
         event_loop_handle: cli::EventLoopHandle,
         monero_receive_address: monero::Address,
@@ -76,3 +84,77 @@ impl Swap {
         })
     }
 }
+
+/// The Monero wallet Bob uses to watch for the lock transfer and to redeem the
+/// Monero.
+#[derive(Clone, Debug)]
+pub enum MoneroWallet {
+    /// A wallet of monero-wallet-rpc, sweeps the redeemed Monero to the
+    /// receive address.
+    Rpc(Arc<monero::Wallet>),
+    /// A light wallet that talks to monerod directly, it cannot sweep the
+    /// redeemed Monero. The swap stops once the Bitcoin is redeemed and the
+    /// keys of the wallet holding the Monero are known.
+    Light(Arc<monero::LightWallet>),
+    /// A wallet of monero-wallet-rpc that also holds Monero of other swaps,
+    /// like the wallet of the ASB when it buys XMR. The redeemed Monero is
+    /// swept to the receive address from a temporary wallet, the shared wallet
+    /// is reopened afterwards.
+    Shared(Arc<monero::Wallet>),
+}
+
+impl MoneroWallet {
+    pub async fn block_height(&self) -> Result<BlockHeight> {
+        match self {
+            MoneroWallet::Rpc(wallet) | MoneroWallet::Shared(wallet) => wallet.block_height().await,
+            MoneroWallet::Light(wallet) => wallet.block_height().await,
+        }
+    }
+
+    pub async fn watch_for_transfer(
+        &self,
+        request: WatchRequest,
+    ) -> Result<(), monero::InsufficientFunds> {
+        match self {
+            MoneroWallet::Rpc(wallet) | MoneroWallet::Shared(wallet) => {
+                wallet.watch_for_transfer(request).await
+            }
+            MoneroWallet::Light(wallet) => wallet.watch_for_transfer(request).await,
+        }
+    }
+
+    pub async fn transfer_confirmations(&self, request: &WatchRequest) -> Result<Option<u64>> {
+        match self {
+            MoneroWallet::Rpc(wallet) | MoneroWallet::Shared(wallet) => {
+                wallet.transfer_confirmations(request).await
+            }
+            MoneroWallet::Light(wallet) => wallet.transfer_confirmations(request).await,
+        }
+    }
+}
+
+impl From<Arc<monero::Wallet>> for MoneroWallet {
+    fn from(wallet: Arc<monero::Wallet>) -> Self {
+        MoneroWallet::Rpc(wallet)
+    }
+}
+
+impl From<Arc<monero::LightWallet>> for MoneroWallet {
+    fn from(wallet: Arc<monero::LightWallet>) -> Self {
+        MoneroWallet::Light(wallet)
+    }
+}
+
+/// The spot price Alice quoted during the swap setup together with the fees
+/// Bob proposed for the cancel and refund transaction.
+///
+/// These are stored as soon as the spot price is known so that a swap that
+/// was interrupted during the setup can be set up again on the same terms.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SwapSetupParameters {
+    pub xmr: monero::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub tx_refund_fee: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub tx_cancel_fee: bitcoin::Amount,
+}
@@ -7,6 +7,7 @@ use crate::monero;
 use crate::monero::wallet::WatchRequest;
 use crate::monero::{monero_private_key, TransferProof};
 use crate::monero_ext::ScalarExt;
+use crate::network::swap_setup::{ConfirmationSchedule, Timelocks};
 use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
 use anyhow::{anyhow, bail, Context, Result};
 use bdk::database::BatchDatabase;
@@ -26,6 +27,12 @@ pub enum BobState {
     Started {
         btc_amount: bitcoin::Amount,
         change_address: bitcoin::Address,
+        /// The timelocks proposed to Alice, the timelocks of the network if
+        /// not given.
+        timelocks: Option<Timelocks>,
+        /// The confirmations of the Monero lock transaction proposed to Alice,
+        /// the confirmations of the network if not given.
+        confirmation_schedule: Option<ConfirmationSchedule>,
     },
     SwapSetupCompleted(State2),
     BtcLocked(State3),
@@ -71,6 +78,211 @@ impl fmt::Display for BobState {
     }
 }
 
+impl BobState {
+    /// The amount of Bitcoin that is locked (or about to be locked) in this
+    /// swap, if the state still knows about it.
+    pub fn btc_amount(&self) -> Option<bitcoin::Amount> {
+        match self {
+            BobState::Started { btc_amount, .. } => Some(*btc_amount),
+            _ => self.tx_lock().map(|tx_lock| tx_lock.lock_amount()),
+        }
+    }
+
+    /// The amount of Monero that was agreed upon for this swap, if the state
+    /// still knows about it.
+    pub fn xmr_amount(&self) -> Option<monero::Amount> {
+        match self {
+            BobState::SwapSetupCompleted(state2) => Some(state2.xmr),
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+                Some(state3.xmr)
+            }
+            _ => None,
+        }
+    }
+
+    /// The confirmations of the Monero lock transaction that were agreed upon
+    /// for this swap, if the state still knows about them.
+    pub fn monero_confirmations(&self) -> Option<u64> {
+        match self {
+            BobState::SwapSetupCompleted(state2) => Some(state2.min_monero_confirmations),
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+                Some(state3.min_monero_confirmations)
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                Some(state4.min_monero_confirmations)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn tx_lock_id(&self) -> Option<bitcoin::Txid> {
+        match self {
+            BobState::XmrRedeemed { tx_lock_id } | BobState::BtcPunished { tx_lock_id } => {
+                Some(*tx_lock_id)
+            }
+            _ => self.tx_lock().map(|tx_lock| tx_lock.txid()),
+        }
+    }
+
+    /// The id of the Bitcoin cancel transaction, if it was published.
+    pub fn tx_cancel_id(&self) -> Option<bitcoin::Txid> {
+        match self {
+            BobState::BtcCancelled(state6) | BobState::BtcRefunded(state6) => {
+                Some(state6.tx_cancel().txid())
+            }
+            _ => None,
+        }
+    }
+
+    /// The id of the Bitcoin refund transaction, if it was published.
+    pub fn tx_refund_id(&self) -> Option<bitcoin::Txid> {
+        match self {
+            BobState::BtcRefunded(state6) => Some(state6.tx_refund().txid()),
+            _ => None,
+        }
+    }
+
+    pub fn xmr_lock_tx_hash(&self) -> Option<monero::TxHash> {
+        match self {
+            BobState::XmrLockProofReceived {
+                lock_transfer_proof,
+                ..
+            } => Some(lock_transfer_proof.tx_hash()),
+            _ => None,
+        }
+    }
+
+    /// The timelocks of the swap, once they were agreed upon.
+    pub fn timelocks(&self) -> Option<Timelocks> {
+        let (cancel, punish) = match self {
+            BobState::SwapSetupCompleted(state2) => {
+                (state2.cancel_timelock, state2.punish_timelock)
+            }
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+                (state3.cancel_timelock, state3.punish_timelock)
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                (state4.cancel_timelock, state4.punish_timelock)
+            }
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => (state6.cancel_timelock, state6.punish_timelock),
+            BobState::Started { .. }
+            | BobState::BtcRedeemed(..)
+            | BobState::XmrRedeemed { .. }
+            | BobState::BtcPunished { .. }
+            | BobState::SafelyAborted => return None,
+        };
+
+        Some(Timelocks { cancel, punish })
+    }
+
+    /// The fees of the transactions spending the locked Bitcoin, only known
+    /// to the state right after the swap setup.
+    pub fn tx_fees(&self) -> Option<bitcoin::TxFees> {
+        match self {
+            BobState::SwapSetupCompleted(state2) => Some(bitcoin::TxFees {
+                redeem: state2.tx_redeem_fee,
+                cancel: state2.tx_cancel_fee,
+                refund: state2.tx_refund_fee,
+                punish: state2.tx_punish_fee,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The transaction Alice redeems the locked Bitcoin with, as agreed upon
+    /// in the swap setup, if the state still knows about it.
+    pub fn tx_redeem(&self) -> Option<bitcoin::TxRedeem> {
+        let (tx_lock, redeem_address, tx_redeem_fee) = match self {
+            BobState::SwapSetupCompleted(state2) => (
+                &state2.tx_lock,
+                &state2.redeem_address,
+                state2.tx_redeem_fee,
+            ),
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => (
+                &state3.tx_lock,
+                &state3.redeem_address,
+                state3.tx_redeem_fee,
+            ),
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => (
+                &state4.tx_lock,
+                &state4.redeem_address,
+                state4.tx_redeem_fee,
+            ),
+            _ => return None,
+        };
+
+        Some(bitcoin::TxRedeem::new(
+            tx_lock,
+            redeem_address,
+            tx_redeem_fee,
+        ))
+    }
+
+    /// The cancel and refund transactions as agreed upon in the swap setup,
+    /// regardless of whether they were published, if the state still knows
+    /// about them.
+    pub fn tx_cancel_and_refund(&self) -> Option<(TxCancel, bitcoin::TxRefund)> {
+        let state6 = match self {
+            BobState::SwapSetupCompleted(state2) => State6 {
+                A: state2.A,
+                b: state2.b.clone(),
+                s_b: state2.s_b,
+                cancel_timelock: state2.cancel_timelock,
+                punish_timelock: state2.punish_timelock,
+                refund_address: state2.refund_address.clone(),
+                tx_lock: state2.tx_lock.clone(),
+                tx_cancel_sig_a: state2.tx_cancel_sig_a.clone(),
+                tx_refund_encsig: state2.tx_refund_encsig.clone(),
+                tx_refund_fee: state2.tx_refund_fee,
+                tx_cancel_fee: state2.tx_cancel_fee,
+            },
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+                state3.cancel()
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => state4.clone().cancel(),
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => state6.clone(),
+            _ => return None,
+        };
+
+        Some((state6.tx_cancel(), state6.tx_refund()))
+    }
+
+    /// The request to watch for the Monero lock transaction, once Alice sent
+    /// the transfer proof.
+    pub fn lock_xmr_watch_request(&self) -> Option<WatchRequest> {
+        match self {
+            BobState::XmrLockProofReceived {
+                state,
+                lock_transfer_proof,
+                ..
+            } => Some(state.lock_xmr_watch_request(lock_transfer_proof.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn tx_lock(&self) -> Option<&bitcoin::TxLock> {
+        match self {
+            BobState::SwapSetupCompleted(state2) => Some(&state2.tx_lock),
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+                Some(&state3.tx_lock)
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => Some(&state4.tx_lock),
+            BobState::BtcRedeemed(state5) => Some(&state5.tx_lock),
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => Some(&state6.tx_lock),
+            BobState::Started { .. }
+            | BobState::XmrRedeemed { .. }
+            | BobState::BtcPunished { .. }
+            | BobState::SafelyAborted => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     swap_id: Uuid,
@@ -639,21 +851,19 @@ impl State6 {
     }
 
     pub async fn submit_tx_cancel(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
-        let transaction = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        )
-        .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
-        .context("Failed to complete Bitcoin cancel transaction")?;
+        let transaction = self.signed_cancel_transaction()?;
 
         let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
 
         Ok(tx_id)
     }
 
+    pub fn signed_cancel_transaction(&self) -> Result<Transaction> {
+        self.tx_cancel()
+            .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
+            .context("Failed to complete Bitcoin cancel transaction")
+    }
+
     pub async fn publish_refund_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
         let signed_tx_refund = self.signed_refund_transaction()?;
         let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
@@ -663,15 +873,7 @@ impl State6 {
     }
 
     pub fn signed_refund_transaction(&self) -> Result<Transaction> {
-        let tx_cancel = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        );
-        let tx_refund =
-            bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, self.tx_refund_fee);
+        let tx_refund = self.tx_refund();
 
         let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
 
@@ -687,4 +889,22 @@ impl State6 {
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
         self.tx_lock.txid()
     }
+
+    pub fn tx_lock(&self) -> &bitcoin::TxLock {
+        &self.tx_lock
+    }
+
+    pub fn tx_cancel(&self) -> TxCancel {
+        TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            self.tx_cancel_fee,
+        )
+    }
+
+    pub fn tx_refund(&self) -> bitcoin::TxRefund {
+        bitcoin::TxRefund::new(&self.tx_cancel(), &self.refund_address, self.tx_refund_fee)
+    }
 }
@@ -1,13 +1,54 @@
-use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
+use crate::bitcoin::wallet::{EstimateFeeRate, Subscription};
+use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxKind, TxRefund};
 use crate::cli::EventLoopHandle;
 use crate::network::swap_setup::bob::NewSwap;
-use crate::protocol::bob;
 use crate::protocol::bob::state::*;
-use crate::{bitcoin, monero};
+use crate::protocol::bob::{MoneroWallet, SwapSetupParameters};
+use crate::protocol::{bob, Database};
+use crate::{bitcoin, env, monero};
 use anyhow::{bail, Context, Result};
+use bdk::database::BatchDatabase;
+use std::convert::Infallible;
+use std::time::Duration;
 use tokio::select;
+use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
+/// The fees of the refund and the cancel transaction and the spot price that
+/// was already negotiated for the swap, if any.
+///
+/// If the swap setup was interrupted after Alice quoted the spot price, we set
+/// up the swap again on the same terms.
+async fn swap_setup_terms<B, D, C>(
+    swap_id: Uuid,
+    btc_amount: bitcoin::Amount,
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &bitcoin::Wallet<B, D, C>,
+) -> Result<(bitcoin::Amount, bitcoin::Amount, Option<monero::Amount>)>
+where
+    C: EstimateFeeRate,
+    D: BatchDatabase,
+{
+    if let Some(parameters) = db.get_swap_setup_parameters(swap_id).await? {
+        tracing::info!(%swap_id, xmr = %parameters.xmr, "Resuming swap setup with previously negotiated spot price");
+
+        return Ok((
+            parameters.tx_refund_fee,
+            parameters.tx_cancel_fee,
+            Some(parameters.xmr),
+        ));
+    }
+
+    let tx_refund_fee = bitcoin_wallet
+        .estimate_fee(TxKind::Refund, TxRefund::weight(), btc_amount)
+        .await?;
+    let tx_cancel_fee = bitcoin_wallet
+        .estimate_fee(TxKind::Cancel, TxCancel::weight(), btc_amount)
+        .await?;
+
+    Ok((tx_refund_fee, tx_cancel_fee, None))
+}
+
 pub fn is_complete(state: &BobState) -> bool {
     matches!(
         state,
@@ -18,15 +59,45 @@ pub fn is_complete(state: &BobState) -> bool {
     )
 }
 
+/// The light wallet cannot sweep the redeemed Monero, hence a swap with a light
+/// wallet ends once Bob learned the keys of the wallet holding the Monero.
+pub fn is_complete_or_btc_redeemed(state: &BobState) -> bool {
+    is_complete(state) || matches!(state, BobState::BtcRedeemed(..))
+}
+
+/// Tells the states in which a swap with the given Monero wallet ends.
+pub fn is_target_state_of(monero_wallet: &MoneroWallet) -> fn(&BobState) -> bool {
+    match monero_wallet {
+        MoneroWallet::Rpc(_) | MoneroWallet::Shared(_) => is_complete,
+        MoneroWallet::Light(_) => is_complete_or_btc_redeemed,
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn run(swap: bob::Swap) -> Result<BobState> {
-    run_until(swap, is_complete).await
+    let is_target_state = is_target_state_of(&swap.monero_wallet);
+
+    run_until(swap, is_target_state).await
 }
 
 pub async fn run_until(
-    mut swap: bob::Swap,
+    swap: bob::Swap,
     is_target_state: fn(&BobState) -> bool,
 ) -> Result<BobState> {
+    run_until_and_report(swap, is_target_state, |_| {}).await
+}
+
+/// Like [`run_until`], but calls `on_state` with every state the swap enters
+/// once the state is stored.
+#[tracing::instrument(name = "swap", skip(swap, is_target_state, on_state), fields(swap_id = %swap.id, peer_id = %swap.event_loop_handle.peer()))]
+pub async fn run_until_and_report<F>(
+    mut swap: bob::Swap,
+    is_target_state: fn(&BobState) -> bool,
+    mut on_state: F,
+) -> Result<BobState>
+where
+    F: FnMut(&BobState),
+{
     let mut current_state = swap.state;
 
     while !is_target_state(&current_state) {
@@ -34,27 +105,33 @@ pub async fn run_until(
             swap.id,
             current_state.clone(),
             &mut swap.event_loop_handle,
+            swap.db.as_ref(),
             swap.bitcoin_wallet.as_ref(),
-            swap.monero_wallet.as_ref(),
+            &swap.monero_wallet,
             swap.monero_receive_address,
+            &swap.env_config,
         )
         .await?;
 
         swap.db
             .insert_latest_state(swap.id, current_state.clone().into())
             .await?;
+        on_state(&current_state);
     }
 
     Ok(current_state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
     event_loop_handle: &mut EventLoopHandle,
+    db: &(dyn Database + Send + Sync),
     bitcoin_wallet: &bitcoin::Wallet,
-    monero_wallet: &monero::Wallet,
+    monero_wallet: &MoneroWallet,
     monero_receive_address: monero::Address,
+    env_config: &env::Config,
 ) -> Result<BobState> {
     tracing::debug!(%state, "Advancing state");
 
@@ -62,23 +139,59 @@ async fn next_state(
         BobState::Started {
             btc_amount,
             change_address,
+            timelocks,
+            confirmation_schedule,
         } => {
-            let tx_refund_fee = bitcoin_wallet
-                .estimate_fee(TxRefund::weight(), btc_amount)
-                .await?;
-            let tx_cancel_fee = bitcoin_wallet
-                .estimate_fee(TxCancel::weight(), btc_amount)
-                .await?;
-
-            let state2 = event_loop_handle
-                .setup_swap(NewSwap {
-                    swap_id,
-                    btc: btc_amount,
-                    tx_refund_fee,
-                    tx_cancel_fee,
-                    bitcoin_refund_address: change_address,
-                })
-                .await?;
+            let (tx_refund_fee, tx_cancel_fee, negotiated_xmr) =
+                swap_setup_terms(swap_id, btc_amount, db, bitcoin_wallet).await?;
+
+            let (spot_price_sender, spot_price_receiver) = oneshot::channel();
+            let (receipt_sender, mut receipt_receiver) = mpsc::unbounded_channel();
+
+            let setup_swap = event_loop_handle.setup_swap(NewSwap {
+                swap_id,
+                btc: btc_amount,
+                tx_refund_fee,
+                tx_cancel_fee,
+                bitcoin_refund_address: change_address,
+                negotiated_xmr,
+                timelocks,
+                confirmation_schedule,
+                spot_price: spot_price_sender,
+                receipts: receipt_sender,
+            });
+            let store_spot_price = async {
+                // The sender is dropped without sending if the swap setup fails before Alice
+                // quoted a spot price, in that case there is nothing to store.
+                if let Ok(xmr) = spot_price_receiver.await {
+                    db.insert_swap_setup_parameters(swap_id, SwapSetupParameters {
+                        xmr,
+                        tx_refund_fee,
+                        tx_cancel_fee,
+                    })
+                    .await?;
+                }
+
+                Ok::<_, anyhow::Error>(())
+            };
+
+            let store_receipts = async {
+                let seller = db.get_peer_id(swap_id).await?;
+
+                // The sender is dropped once the swap setup is finished, successfully or not.
+                while let Some(receipt) = receipt_receiver.recv().await {
+                    match receipt.verify(seller) {
+                        Ok(_) => db.insert_receipt(swap_id, receipt).await?,
+                        Err(error) => {
+                            tracing::warn!(%swap_id, "Ignoring invalid receipt of the seller: {:#}", error)
+                        }
+                    }
+                }
+
+                Ok::<_, anyhow::Error>(())
+            };
+
+            let (state2, (), ()) = tokio::try_join!(setup_swap, store_spot_price, store_receipts)?;
 
             tracing::info!(%swap_id, "Starting new swap");
 
@@ -87,13 +200,37 @@ async fn next_state(
         BobState::SwapSetupCompleted(state2) => {
             // Alice and Bob have exchanged info
             let (state3, tx_lock) = state2.lock_btc().await?;
-            let signed_tx = bitcoin_wallet
-                .sign_and_finalize(tx_lock.clone().into())
-                .await
-                .context("Failed to sign Bitcoin lock transaction")?;
-            let (..) = bitcoin_wallet.broadcast(signed_tx, "lock").await?;
+            let sign_tx_lock = async {
+                if bitcoin_wallet.is_watch_only() {
+                    tracing::info!(txid = %tx_lock.txid(), "Waiting for the Bitcoin lock transaction to be signed externally");
+                }
+
+                bitcoin_wallet
+                    .sign_and_finalize(tx_lock.clone().into())
+                    .await
+                    .context("Failed to sign Bitcoin lock transaction")
+            };
+
+            // Alice can abort the swap as long as we have not published the lock
+            // transaction, e.g. while the transaction is signed in an external wallet.
+            let signed_tx = select! {
+                biased;
+                Ok(()) = event_loop_handle.recv_abort() => None,
+                signed_tx = sign_tx_lock => Some(signed_tx?),
+            };
 
-            BobState::BtcLocked(state3)
+            match signed_tx {
+                Some(signed_tx) => {
+                    let (..) = bitcoin_wallet.broadcast(signed_tx, "lock").await?;
+
+                    BobState::BtcLocked(state3)
+                }
+                None => {
+                    tracing::info!("Alice aborted the swap before the Bitcoin was locked");
+
+                    BobState::SafelyAborted
+                }
+            }
         }
         // Bob has locked Btc
         // Watch for Alice to Lock Xmr or for cancel timelock to elapse
@@ -104,6 +241,11 @@ async fn next_state(
                 let transfer_proof_watcher = event_loop_handle.recv_transfer_proof();
                 let cancel_timelock_expires =
                     tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock);
+                let lock_unconfirmed_warning = warn_if_unconfirmed(
+                    swap_id,
+                    &tx_lock_status,
+                    env_config.bitcoin_lock_confirmed_timeout / 2,
+                );
 
                 // Record the current monero wallet block height so we don't have to scan from
                 // block 0 once we create the redeem wallet.
@@ -130,6 +272,7 @@ async fn next_state(
                         let state4 = state3.cancel();
                         BobState::CancelTimelockExpired(state4)
                     },
+                    never = lock_unconfirmed_warning => match never {},
                 }
             } else {
                 let state4 = state3.cancel();
@@ -174,11 +317,22 @@ async fn next_state(
 
             if let ExpiredTimelocks::None = state.expired_timelock(bitcoin_wallet).await? {
                 // Alice has locked Xmr
-                // Bob sends Alice his key
+                // Bob sends Alice his key once TxLock is final, a reorg that
+                // drops it from the chain pauses this until it is final again
+                let tx_redeem_encsig = state.tx_redeem_encsig();
+                let send_encrypted_signature = async {
+                    tx_lock_status.wait_until_final().await?;
+
+                    Ok::<_, anyhow::Error>(
+                        event_loop_handle
+                            .send_encrypted_signature(tx_redeem_encsig)
+                            .await,
+                    )
+                };
 
                 select! {
-                    result = event_loop_handle.send_encrypted_signature(state.tx_redeem_encsig()) => {
-                        match result {
+                    result = send_encrypted_signature => {
+                        match result? {
                             Ok(_) => BobState::EncSigSent(state),
                             Err(bmrng::error::RequestError::RecvError | bmrng::error::RequestError::SendError(_)) => bail!("Failed to communicate encrypted signature through event loop channel"),
                             Err(bmrng::error::RequestError::RecvTimeoutError) => unreachable!("We construct the channel with no timeout"),
@@ -211,6 +365,29 @@ async fn next_state(
             }
         }
         BobState::BtcRedeemed(state) => {
+            let monero_wallet = match monero_wallet {
+                MoneroWallet::Rpc(wallet) => wallet,
+                MoneroWallet::Shared(wallet) => {
+                    let (spend_key, view_key) = state.xmr_keys();
+
+                    wallet
+                        .create_from(
+                            swap_id.to_string(),
+                            spend_key,
+                            view_key,
+                            state.monero_wallet_restore_blockheight,
+                            monero_receive_address,
+                        )
+                        .await?;
+
+                    return Ok(BobState::XmrRedeemed {
+                        tx_lock_id: state.tx_lock_id(),
+                    });
+                }
+                MoneroWallet::Light(_) => bail!(
+                    "Cannot transfer the redeemed Monero with the light wallet, resume the swap without `--monero-light-wallet`"
+                ),
+            };
             let (spend_key, view_key) = state.xmr_keys();
 
             let wallet_file_name = swap_id.to_string();
@@ -276,3 +453,89 @@ async fn next_state(
         BobState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
     })
 }
+
+/// Warns if the Bitcoin lock transaction is not confirmed within the given
+/// time.
+///
+/// Alice aborts the swap if the lock transaction does not confirm in time,
+/// so a lock transaction stuck in the mempool has to be bumped before that
+/// happens. This future never completes.
+async fn warn_if_unconfirmed(
+    swap_id: Uuid,
+    tx_lock_status: &Subscription,
+    timeout: Duration,
+) -> Infallible {
+    let confirmed = tokio::time::timeout(timeout, tx_lock_status.wait_until_confirmed_with(1u32));
+
+    if confirmed.await.is_err() {
+        tracing::warn!(
+            "Bitcoin lock transaction has not been confirmed within {} minutes, Alice will abort the swap if it does not confirm in time. Consider bumping its fee with `swap bump-fee --swap-id {}`",
+            timeout.as_secs() / 60,
+            swap_id
+        );
+    }
+
+    futures::future::pending().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::WalletBuilder;
+    use crate::database::MemoryDatabase;
+
+    #[tokio::test]
+    async fn given_interrupted_swap_setup_then_resumes_with_negotiated_terms() {
+        let db = MemoryDatabase::new();
+        let swap_id = Uuid::new_v4();
+        let parameters = SwapSetupParameters {
+            xmr: monero::Amount::from_piconero(1_000_000_000),
+            tx_refund_fee: bitcoin::Amount::from_sat(1_000),
+            tx_cancel_fee: bitcoin::Amount::from_sat(2_000),
+        };
+        db.insert_swap_setup_parameters(swap_id, parameters)
+            .await
+            .unwrap();
+        // the fee rate rose since the swap setup was interrupted
+        let bitcoin_wallet = WalletBuilder::new(100_000).with_fees(50.0, 1000).build();
+
+        let terms = swap_setup_terms(
+            swap_id,
+            bitcoin::Amount::from_sat(50_000),
+            &db,
+            &bitcoin_wallet,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            terms,
+            (
+                parameters.tx_refund_fee,
+                parameters.tx_cancel_fee,
+                Some(parameters.xmr)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn given_new_swap_setup_then_estimates_fees_without_negotiated_spot_price() {
+        let db = MemoryDatabase::new();
+        let btc_amount = bitcoin::Amount::from_sat(50_000);
+        let bitcoin_wallet = WalletBuilder::new(100_000).build();
+
+        let terms = swap_setup_terms(Uuid::new_v4(), btc_amount, &db, &bitcoin_wallet)
+            .await
+            .unwrap();
+
+        let tx_refund_fee = bitcoin_wallet
+            .estimate_fee(TxKind::Refund, TxRefund::weight(), btc_amount)
+            .await
+            .unwrap();
+        let tx_cancel_fee = bitcoin_wallet
+            .estimate_fee(TxKind::Cancel, TxCancel::weight(), btc_amount)
+            .await
+            .unwrap();
+        assert_eq!(terms, (tx_refund_fee, tx_cancel_fee, None));
+    }
+}
@@ -0,0 +1,514 @@
+//! The graphs of the states Alice and Bob go through in a swap.
+//!
+//! The transitions mirror the `next_state` functions of both roles and are
+//! named like the states reported by the monitor. They are used to export the
+//! state machines and to simulate which outcomes a stored swap can still
+//! reach.
+
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
+use crate::monitor::{alice_event, bob_event};
+use crate::protocol::State;
+use anyhow::{bail, Result};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Alice,
+    Bob,
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "alice" => Ok(Role::Alice),
+            "bob" => Ok(Role::Bob),
+            _ => bail!("Unknown role `{}`, expected `alice` or `bob`", s),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Alice => write!(f, "alice"),
+            Role::Bob => write!(f, "bob"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => bail!("Unknown format `{}`, expected `dot` or `mermaid`", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub from: &'static str,
+    pub to: &'static str,
+    /// What makes the swap take the transition.
+    pub label: &'static str,
+}
+
+const fn transition(from: &'static str, to: &'static str, label: &'static str) -> Transition {
+    Transition { from, to, label }
+}
+
+const CANCEL_TIMELOCK_EXPIRED: &str = "cancel timelock expired";
+
+const ALICE: &[Transition] = &[
+    transition("Started", "BtcLockTransactionSeen", "lock transaction seen"),
+    transition(
+        "Started",
+        "SafelyAborted",
+        "aborted or lock transaction not seen in time",
+    ),
+    transition(
+        "BtcLockTransactionSeen",
+        "BtcLocked",
+        "lock transaction confirmed",
+    ),
+    transition(
+        "BtcLockTransactionSeen",
+        "SafelyAborted",
+        "lock transaction not confirmed in time",
+    ),
+    transition(
+        "BtcLocked",
+        "XmrLockTransactionSent",
+        "Monero lock transaction sent",
+    ),
+    transition("BtcLocked", "SafelyAborted", CANCEL_TIMELOCK_EXPIRED),
+    transition(
+        "XmrLockTransactionSent",
+        "XmrLocked",
+        "Monero lock transaction seen",
+    ),
+    transition(
+        "XmrLockTransactionSent",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition(
+        "XmrLocked",
+        "XmrLockTransferProofSent",
+        "transfer proof acknowledged",
+    ),
+    transition(
+        "XmrLocked",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition(
+        "XmrLockTransferProofSent",
+        "EncSigLearned",
+        "encrypted signature received",
+    ),
+    transition(
+        "XmrLockTransferProofSent",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition(
+        "EncSigLearned",
+        "BtcRedeemTransactionPublished",
+        "redeem transaction seen",
+    ),
+    transition(
+        "EncSigLearned",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition(
+        "BtcRedeemTransactionPublished",
+        "BtcRedeemed",
+        "redeem transaction final",
+    ),
+    transition(
+        "CancelTimelockExpired",
+        "BtcCancelled",
+        "cancel transaction published",
+    ),
+    transition("BtcCancelled", "BtcRefunded", "refund transaction seen"),
+    transition("BtcCancelled", "BtcPunishable", "punish timelock expired"),
+    transition("BtcRefunded", "XmrRefunded", "Monero refunded"),
+    transition("BtcPunishable", "BtcPunished", "punish transaction final"),
+    transition(
+        "BtcPunishable",
+        "BtcRefunded",
+        "refund transaction published instead",
+    ),
+];
+
+const BOB: &[Transition] = &[
+    transition("Started", "SwapSetupCompleted", "swap set up"),
+    transition("Started", "SafelyAborted", "aborted"),
+    transition(
+        "SwapSetupCompleted",
+        "BtcLocked",
+        "lock transaction published",
+    ),
+    transition("SwapSetupCompleted", "SafelyAborted", "aborted"),
+    transition(
+        "BtcLocked",
+        "XmrLockProofReceived",
+        "transfer proof received",
+    ),
+    transition(
+        "BtcLocked",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition(
+        "XmrLockProofReceived",
+        "XmrLocked",
+        "Monero lock transaction confirmed",
+    ),
+    transition(
+        "XmrLockProofReceived",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition(
+        "XmrLocked",
+        "EncSigSent",
+        "encrypted signature acknowledged",
+    ),
+    transition(
+        "XmrLocked",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition("EncSigSent", "BtcRedeemed", "redeem transaction seen"),
+    transition(
+        "EncSigSent",
+        "CancelTimelockExpired",
+        CANCEL_TIMELOCK_EXPIRED,
+    ),
+    transition("BtcRedeemed", "XmrRedeemed", "Monero redeemed"),
+    transition(
+        "CancelTimelockExpired",
+        "BtcCancelled",
+        "cancel transaction published",
+    ),
+    transition(
+        "BtcCancelled",
+        "BtcRefunded",
+        "refund transaction published",
+    ),
+    transition("BtcCancelled", "BtcPunished", "punish timelock expired"),
+];
+
+pub fn transitions(role: Role) -> &'static [Transition] {
+    match role {
+        Role::Alice => ALICE,
+        Role::Bob => BOB,
+    }
+}
+
+/// How a swap ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Redeem,
+    Refund,
+    Punish,
+    Abort,
+}
+
+impl Outcome {
+    fn of_final_state(role: Role, state: &str) -> Option<Self> {
+        match (role, state) {
+            (Role::Alice, "BtcRedeemed") | (Role::Bob, "XmrRedeemed") => Some(Outcome::Redeem),
+            (Role::Alice, "XmrRefunded") | (Role::Bob, "BtcRefunded") => Some(Outcome::Refund),
+            (_, "BtcPunished") => Some(Outcome::Punish),
+            (_, "SafelyAborted") => Some(Outcome::Abort),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Redeem => write!(f, "redeem"),
+            Outcome::Refund => write!(f, "refund"),
+            Outcome::Punish => write!(f, "punish"),
+            Outcome::Abort => write!(f, "abort"),
+        }
+    }
+}
+
+/// Renders the state machine of the role as a graph.
+pub fn render(role: Role, format: GraphFormat) -> String {
+    let mut graph = String::new();
+
+    // Writing to a string does not fail.
+    match format {
+        GraphFormat::Dot => {
+            let _ = writeln!(graph, "digraph {} {{", role);
+            for Transition { from, to, label } in transitions(role) {
+                let _ = writeln!(graph, "    {} -> {} [label=\"{}\"];", from, to, label);
+            }
+            graph.push_str("}\n");
+        }
+        GraphFormat::Mermaid => {
+            graph.push_str("stateDiagram-v2\n");
+            graph.push_str("    [*] --> Started\n");
+            for Transition { from, to, label } in transitions(role) {
+                let _ = writeln!(graph, "    {} --> {}: {}", from, to, label);
+            }
+            for state in final_states(role) {
+                let _ = writeln!(graph, "    {} --> [*]", state);
+            }
+        }
+    }
+
+    graph
+}
+
+/// The states without transitions out of them, in the order they appear in
+/// the transitions.
+fn final_states(role: Role) -> Vec<&'static str> {
+    let mut states = Vec::new();
+
+    for Transition { to, .. } in transitions(role) {
+        let is_final = !transitions(role).iter().any(|t| t.from == *to);
+        if is_final && !states.contains(to) {
+            states.push(*to);
+        }
+    }
+
+    states
+}
+
+/// The outcomes a swap in the given state can still reach.
+pub fn reachable_outcomes(role: Role, state: &str) -> BTreeSet<Outcome> {
+    let mut outcomes = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut queue = VecDeque::from(vec![state]);
+
+    while let Some(state) = queue.pop_front() {
+        if !visited.insert(state) {
+            continue;
+        }
+
+        if let Some(outcome) = Outcome::of_final_state(role, state) {
+            outcomes.insert(outcome);
+        }
+
+        for transition in transitions(role).iter().filter(|t| t.from == state) {
+            queue.push_back(transition.to);
+        }
+    }
+
+    outcomes
+}
+
+/// The outcome of simulating a stored swap forward from its current state.
+///
+/// The simulated chain confirms the lock transaction at `lock_height` and the
+/// cancel transaction as soon as the cancel timelock expired, which gives the
+/// earliest heights at which the paths of the swap flip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    pub role: Role,
+    pub state: &'static str,
+    pub outcomes: BTreeSet<Outcome>,
+    pub timelocks: Option<(CancelTimelock, PunishTimelock)>,
+    pub lock_height: Option<u32>,
+}
+
+impl Simulation {
+    pub fn new(state: &State, lock_height: Option<u32>) -> Self {
+        let (role, name, timelocks) = match state {
+            State::Alice(state) => (Role::Alice, alice_event(state), state.timelocks()),
+            State::Bob(state) => (Role::Bob, bob_event(state), state.timelocks()),
+        };
+
+        Simulation {
+            role,
+            state: name,
+            outcomes: reachable_outcomes(role, name),
+            timelocks: timelocks.map(|timelocks| (timelocks.cancel, timelocks.punish)),
+            lock_height,
+        }
+    }
+
+    /// The blocks at which the swap can be cancelled, from then on it can no
+    /// longer be redeemed, and at which it can be punished at the earliest.
+    fn flips(&self) -> Option<(u32, u32)> {
+        let (cancel, punish) = self.timelocks?;
+        let cancel = u32::from(cancel);
+        let punish = cancel.saturating_add(u32::from(punish));
+
+        Some((cancel, punish))
+    }
+
+    fn height(&self, blocks_after_lock: u32) -> String {
+        match self.lock_height {
+            Some(lock_height) => format!("block {}", lock_height.saturating_add(blocks_after_lock)),
+            None => format!(
+                "{} blocks after the lock transaction confirmed",
+                blocks_after_lock
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "The swap is in state {} as {}.", self.state, self.role)?;
+
+        if self.outcomes.is_empty() {
+            return writeln!(f, "No outcome is reachable anymore.");
+        }
+
+        writeln!(f, "Reachable outcomes:")?;
+        for outcome in &self.outcomes {
+            let timing = match (outcome, self.flips()) {
+                (Outcome::Redeem, Some((cancel, _))) => {
+                    format!("until the swap is cancelled at {}", self.height(cancel))
+                }
+                (Outcome::Refund, Some((cancel, punish))) => format!(
+                    "once the swap is cancelled at {} and until it can be punished at {}",
+                    self.height(cancel),
+                    self.height(punish)
+                ),
+                (Outcome::Punish, Some((_, punish))) => {
+                    format!("at {} at the earliest", self.height(punish))
+                }
+                _ => String::new(),
+            };
+
+            if timing.is_empty() {
+                writeln!(f, "  {}", outcome)?;
+            } else {
+                writeln!(f, "  {} {}", outcome, timing)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::bob::BobState;
+
+    #[test]
+    fn every_state_is_reachable_from_started() {
+        for role in &[Role::Alice, Role::Bob] {
+            let mut reachable = BTreeSet::new();
+            let mut queue = VecDeque::from(vec!["Started"]);
+
+            while let Some(state) = queue.pop_front() {
+                if reachable.insert(state) {
+                    queue.extend(
+                        transitions(*role)
+                            .iter()
+                            .filter(|t| t.from == state)
+                            .map(|t| t.to),
+                    );
+                }
+            }
+
+            for transition in transitions(*role) {
+                assert!(
+                    reachable.contains(transition.from),
+                    "{} of {} is not reachable",
+                    transition.from,
+                    role
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn final_states_are_outcomes() {
+        for role in &[Role::Alice, Role::Bob] {
+            for state in final_states(*role) {
+                assert!(
+                    Outcome::of_final_state(*role, state).is_some(),
+                    "{} of {} is not an outcome",
+                    state,
+                    role
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_swap_can_still_be_refunded_or_punished() {
+        let expected = vec![Outcome::Refund, Outcome::Punish]
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+
+        assert_eq!(reachable_outcomes(Role::Bob, "BtcCancelled"), expected);
+        assert_eq!(reachable_outcomes(Role::Alice, "BtcCancelled"), expected);
+    }
+
+    #[test]
+    fn swap_before_lock_can_reach_every_outcome() {
+        assert_eq!(
+            reachable_outcomes(Role::Bob, "SwapSetupCompleted"),
+            vec![
+                Outcome::Redeem,
+                Outcome::Refund,
+                Outcome::Punish,
+                Outcome::Abort
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn finished_swap_reaches_its_own_outcome_only() {
+        let simulation = Simulation::new(&State::Bob(BobState::SafelyAborted), None);
+
+        assert_eq!(simulation.state, "SafelyAborted");
+        assert_eq!(
+            simulation.outcomes,
+            vec![Outcome::Abort].into_iter().collect()
+        );
+        assert_eq!(simulation.timelocks, None);
+    }
+
+    #[test]
+    fn mermaid_graph_starts_and_ends_in_pseudo_states() {
+        let graph = render(Role::Bob, GraphFormat::Mermaid);
+
+        assert!(graph.starts_with("stateDiagram-v2\n    [*] --> Started\n"));
+        assert!(graph.contains("    EncSigSent --> BtcRedeemed: redeem transaction seen\n"));
+        assert!(graph.contains("    XmrRedeemed --> [*]\n"));
+        assert!(!graph.contains("    BtcRedeemed --> [*]\n"));
+    }
+
+    #[test]
+    fn dot_graph_contains_all_transitions() {
+        let graph = render(Role::Alice, GraphFormat::Dot);
+
+        assert!(graph.starts_with("digraph alice {\n"));
+        assert!(graph
+            .contains("    BtcCancelled -> BtcPunishable [label=\"punish timelock expired\"];\n"));
+        assert_eq!(graph.lines().count(), ALICE.len() + 2);
+    }
+}
@@ -1,8 +1,10 @@
+use crate::encryption::Cipher;
 use crate::fs::ensure_directory_exists;
 use ::bitcoin::secp256k1::constants::SECRET_KEY_SIZE;
 use ::bitcoin::secp256k1::{self, SecretKey};
 use anyhow::{Context, Result};
 use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bdk::keys::bip39::{self, Language, Mnemonic};
 use bitcoin::hashes::{sha256, Hash, HashEngine};
 use libp2p::identity;
 use pem::{encode, Pem};
@@ -16,6 +18,9 @@ use torut::onion::TorSecretKeyV3;
 
 pub const SEED_LENGTH: usize = 32;
 
+const SEED_TAG: &str = "SEED";
+const ENCRYPTED_SEED_TAG: &str = "ENCRYPTED SEED";
+
 #[derive(Eq, PartialEq)]
 pub struct Seed([u8; SEED_LENGTH]);
 
@@ -30,6 +35,32 @@ impl Seed {
         Ok(Seed(bytes))
     }
 
+    /// Restores the seed from the mnemonic of [`Seed::to_mnemonic`].
+    pub fn from_mnemonic(words: &str) -> Result<Self, Error> {
+        let mnemonic = Mnemonic::parse_in(Language::English, words)?;
+        let entropy = mnemonic.to_entropy();
+        if entropy.len() != SEED_LENGTH {
+            return Err(Error::MnemonicLength(mnemonic.word_count()));
+        }
+
+        let _ = SecretKey::from_slice(&entropy)?;
+
+        let mut bytes = [0u8; SEED_LENGTH];
+        bytes.copy_from_slice(&entropy);
+
+        Ok(Seed(bytes))
+    }
+
+    /// The seed as a BIP39 mnemonic of 24 words, to back it up on paper.
+    ///
+    /// The words encode the seed itself, everything that is derived from the
+    /// seed, like the Bitcoin wallet and the libp2p identity, is derived the
+    /// same way from the restored seed.
+    pub fn to_mnemonic(&self) -> Mnemonic {
+        Mnemonic::from_entropy_in(Language::English, &self.0)
+            .expect("32 bytes are a valid entropy length")
+    }
+
     pub fn derive_extended_private_key(
         &self,
         network: bitcoin::Network,
@@ -42,8 +73,22 @@ impl Seed {
     }
 
     pub fn derive_libp2p_identity(&self) -> identity::Keypair {
-        let bytes = self.derive(b"NETWORK").derive(b"LIBP2P_IDENTITY").bytes();
-        let key = identity::ed25519::SecretKey::from_bytes(bytes).expect("we always pass 32 bytes");
+        self.derive_libp2p_identity_at(0)
+    }
+
+    /// Derives the libp2p identity of the given generation, every rotation of
+    /// the identity moves to the next generation.
+    ///
+    /// Generation 0 is the identity that was used before identities could be
+    /// rotated.
+    pub fn derive_libp2p_identity_at(&self, generation: u32) -> identity::Keypair {
+        let seed = self.derive(b"NETWORK").derive(b"LIBP2P_IDENTITY");
+        let seed = match generation {
+            0 => seed,
+            generation => seed.derive(&generation.to_be_bytes()),
+        };
+        let key = identity::ed25519::SecretKey::from_bytes(seed.bytes())
+            .expect("we always pass 32 bytes");
 
         identity::Keypair::Ed25519(key.into())
     }
@@ -56,22 +101,62 @@ impl Seed {
         esk.to_bytes().into()
     }
 
-    pub fn from_file_or_generate(data_dir: &Path) -> Result<Self, Error> {
+    /// Reads the seed from the data directory or generates a new one.
+    ///
+    /// With a cipher, the seed file is encrypted, including an existing seed
+    /// file that was not encrypted yet.
+    pub fn from_file_or_generate(data_dir: &Path, cipher: Option<&Cipher>) -> Result<Self, Error> {
         let file_path_buf = data_dir.join("seed.pem");
         let file_path = Path::new(&file_path_buf);
 
         if file_path.exists() {
-            return Self::from_file(&file_path);
+            let seed = Self::from_file(&file_path, cipher)?;
+
+            if cipher.is_some() && !Self::is_encrypted(data_dir)? {
+                tracing::info!("Encrypting seed file {}", file_path.display());
+                seed.write_to(file_path.to_path_buf(), cipher)?;
+            }
+
+            return Ok(seed);
         }
 
         tracing::debug!("No seed file found, creating at {}", file_path.display());
 
         let random_seed = Seed::random()?;
-        random_seed.write_to(file_path.to_path_buf())?;
+        random_seed.write_to(file_path.to_path_buf(), cipher)?;
 
         Ok(random_seed)
     }
 
+    /// Writes the seed to the data directory, e.g. after restoring it from its
+    /// mnemonic.
+    ///
+    /// Fails if the data directory has a seed file already, so a seed that
+    /// might still be needed is never overwritten.
+    pub fn write_new(&self, data_dir: &Path, cipher: Option<&Cipher>) -> Result<(), Error> {
+        let file_path = data_dir.join("seed.pem");
+
+        if file_path.exists() {
+            return Err(Error::SeedFileExists(file_path));
+        }
+
+        self.write_to(file_path, cipher)
+    }
+
+    /// Whether the seed file in the data directory is encrypted, `false` if
+    /// there is none yet.
+    pub fn is_encrypted(data_dir: &Path) -> Result<bool, Error> {
+        let file_path = data_dir.join("seed.pem");
+
+        if !file_path.exists() {
+            return Ok(false);
+        }
+
+        let pem = pem::parse(fs::read_to_string(file_path)?)?;
+
+        Ok(pem.tag == ENCRYPTED_SEED_TAG)
+    }
+
     /// Derive a new seed using the given scope.
     ///
     /// This function is purposely kept private because it is only a helper
@@ -92,16 +177,21 @@ impl Seed {
         self.0
     }
 
-    fn from_file<D>(seed_file: D) -> Result<Self, Error>
+    fn from_file<D>(seed_file: D, cipher: Option<&Cipher>) -> Result<Self, Error>
     where
         D: AsRef<OsStr>,
     {
         let file = Path::new(&seed_file);
         let contents = fs::read_to_string(file)?;
-        let pem = pem::parse(contents)?;
+        let mut pem = pem::parse(contents)?;
 
         tracing::debug!("Reading in seed from {}", file.display());
 
+        if pem.tag == ENCRYPTED_SEED_TAG {
+            let cipher = cipher.ok_or(Error::Encrypted)?;
+            pem.contents = cipher.decrypt(&pem.contents).map_err(Error::Decrypt)?;
+        }
+
         Self::from_pem(pem)
     }
 
@@ -118,19 +208,30 @@ impl Seed {
         }
     }
 
-    fn write_to(&self, seed_file: PathBuf) -> Result<(), Error> {
+    fn write_to(&self, seed_file: PathBuf, cipher: Option<&Cipher>) -> Result<(), Error> {
         ensure_directory_exists(&seed_file)?;
 
         let data = self.bytes();
-        let pem = Pem {
-            tag: String::from("SEED"),
-            contents: data.to_vec(),
+        let pem = match cipher {
+            Some(cipher) => Pem {
+                tag: String::from(ENCRYPTED_SEED_TAG),
+                contents: cipher.encrypt(&data),
+            },
+            None => Pem {
+                tag: String::from(SEED_TAG),
+                contents: data.to_vec(),
+            },
         };
 
         let pem_string = encode(&pem);
 
-        let mut file = File::create(seed_file)?;
+        // Write to a temporary file first, so an existing seed file is not lost
+        // if we fail halfway through replacing it.
+        let tmp_file = seed_file.with_extension("pem.tmp");
+        let mut file = File::create(&tmp_file)?;
         file.write_all(pem_string.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp_file, seed_file)?;
 
         Ok(())
     }
@@ -168,11 +269,23 @@ pub enum Error {
     Rand(#[from] rand::Error),
     #[error("no default path")]
     NoDefaultPath,
+    #[error("the seed file is encrypted, a passphrase is required to decrypt it")]
+    Encrypted,
+    #[error("failed to decrypt the seed file: {0:#}")]
+    Decrypt(anyhow::Error),
+    #[error("invalid mnemonic: {0}")]
+    Mnemonic(#[from] bip39::Error),
+    #[error("expected a mnemonic of 24 words, got {0} words")]
+    MnemonicLength(usize),
+    #[error("the seed file {} exists already, move it elsewhere to restore a seed", .0.display())]
+    SeedFileExists(PathBuf),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::encryption::Passphrase;
+    use libp2p::PeerId;
     use std::env::temp_dir;
 
     #[test]
@@ -185,6 +298,18 @@ mod tests {
         let _seed = Seed::from(*b"this string is exactly 32 bytes!");
     }
 
+    #[test]
+    fn every_generation_derives_its_own_identity() {
+        let seed = Seed::from(*b"this string is exactly 32 bytes!");
+        let peer_id =
+            |generation| PeerId::from(seed.derive_libp2p_identity_at(generation).public());
+
+        let initial = PeerId::from(seed.derive_libp2p_identity().public());
+        assert_eq!(peer_id(0), initial);
+        assert_ne!(peer_id(1), initial);
+        assert_ne!(peer_id(1), peer_id(2));
+    }
+
     #[test]
     fn seed_from_pem_works() {
         let payload: &str = "syl9wSYaruvgxg9P5Q1qkZaq5YkM6GvXkxe+VYrL/XM=";
@@ -245,10 +370,87 @@ mbKANv2qKGmNVg1qtquj6Hx1pFPelpqOfE2JaJJAMEg1FlFhNRNlFlE=
         let tmpfile = temp_dir().join("seed.pem");
 
         let seed = Seed::random().unwrap();
-        seed.write_to(tmpfile.clone())
+        seed.write_to(tmpfile.clone(), None)
             .expect("Write seed to temp file");
 
-        let rinsed = Seed::from_file(tmpfile).expect("Read from temp file");
+        let rinsed = Seed::from_file(tmpfile, None).expect("Read from temp file");
         assert_eq!(seed.0, rinsed.0);
     }
+
+    #[test]
+    fn round_trip_through_encrypted_file_write_read() {
+        let tmpfile = temp_dir().join("encrypted_seed.pem");
+        let cipher = Cipher::new(Passphrase::new("passphrase".to_string()).unwrap()).unwrap();
+
+        let seed = Seed::random().unwrap();
+        seed.write_to(tmpfile.clone(), Some(&cipher))
+            .expect("Write seed to temp file");
+
+        assert!(matches!(
+            Seed::from_file(tmpfile.clone(), None),
+            Err(Error::Encrypted)
+        ));
+        let rinsed = Seed::from_file(tmpfile, Some(&cipher)).expect("Read from temp file");
+        assert_eq!(seed.0, rinsed.0);
+    }
+
+    #[test]
+    fn round_trip_through_mnemonic() {
+        let seed = Seed::random().unwrap();
+
+        let mnemonic = seed.to_mnemonic();
+        assert_eq!(mnemonic.word_count(), 24);
+
+        let restored = Seed::from_mnemonic(&mnemonic.to_string()).unwrap();
+        assert_eq!(seed, restored);
+        assert_eq!(
+            PeerId::from(seed.derive_libp2p_identity().public()),
+            PeerId::from(restored.derive_libp2p_identity().public())
+        );
+    }
+
+    #[test]
+    fn seed_from_mnemonic_matches_bip39_test_vector() {
+        let words = "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title";
+
+        let seed = Seed::from_mnemonic(words).unwrap();
+
+        assert_eq!(seed.bytes(), [0x7f; SEED_LENGTH]);
+        assert_eq!(seed.to_mnemonic().to_string(), words);
+    }
+
+    #[test]
+    fn mnemonic_with_wrong_checksum_is_rejected() {
+        // the last word carries the checksum
+        let words = "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth zoo";
+
+        assert!(matches!(
+            Seed::from_mnemonic(words),
+            Err(Error::Mnemonic(_))
+        ));
+    }
+
+    #[test]
+    fn mnemonic_of_12_words_is_rejected() {
+        let words = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        assert!(matches!(
+            Seed::from_mnemonic(words),
+            Err(Error::MnemonicLength(12))
+        ));
+    }
+
+    #[test]
+    fn existing_seed_file_is_not_overwritten() {
+        let data_dir = temp_dir().join(format!("seed-{}", rand::random::<u64>()));
+        let seed = Seed::random().unwrap();
+        seed.write_new(&data_dir, None).unwrap();
+
+        let other = Seed::random().unwrap();
+        assert!(matches!(
+            other.write_new(&data_dir, None),
+            Err(Error::SeedFileExists(_))
+        ));
+        assert_eq!(Seed::from_file_or_generate(&data_dir, None).unwrap(), seed);
+    }
 }
@@ -1,6 +1,7 @@
 use anyhow::{bail, Context, Result};
+use libp2p::core::multiaddr::{Multiaddr, Protocol};
 use std::future::Future;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
 use tokio::net::TcpStream;
 use torut::control::{AsyncEvent, AuthenticatedConn, ConnError, UnauthenticatedConn};
 use torut::onion::TorSecretKeyV3;
@@ -126,3 +127,106 @@ impl AuthenticatedClient {
             .context("Failed to add onion service")
     }
 }
+
+/// A port of a hidden service that forwards connections to one of the
+/// addresses we listen on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HiddenServicePort {
+    pub onion_port: u16,
+    pub target: SocketAddr,
+    /// The address under which the listen address is reachable over Tor.
+    pub address: Multiaddr,
+}
+
+/// Maps the TCP addresses we listen on to the ports of a hidden service with
+/// the given onion address.
+///
+/// Protocols on top of TCP such as websockets are kept in the onion address.
+/// Connections to addresses listening on all interfaces are forwarded to
+/// localhost.
+pub fn hidden_service_ports(listen: &[Multiaddr], onion_address: &str) -> Vec<HiddenServicePort> {
+    let mut ports: Vec<HiddenServicePort> = Vec::new();
+
+    for listen_address in listen {
+        let mut protocols = listen_address.iter();
+
+        let ip = match protocols.next() {
+            Some(Protocol::Ip4(ip)) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            Some(Protocol::Ip4(ip)) => IpAddr::V4(ip),
+            Some(Protocol::Ip6(ip)) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            Some(Protocol::Ip6(ip)) => IpAddr::V6(ip),
+            _ => continue,
+        };
+        let port = match protocols.next() {
+            Some(Protocol::Tcp(port)) => port,
+            _ => continue,
+        };
+
+        // Tor does not allow to forward the same port to several targets.
+        if ports.iter().any(|existing| existing.onion_port == port) {
+            continue;
+        }
+
+        let mut address = match format!("/onion3/{}:{}", onion_address, port).parse::<Multiaddr>() {
+            Ok(address) => address,
+            Err(_) => continue,
+        };
+        for protocol in protocols {
+            address.push(protocol);
+        }
+
+        ports.push(HiddenServicePort {
+            onion_port: port,
+            target: SocketAddr::new(ip, port),
+            address,
+        });
+    }
+
+    ports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONION_ADDRESS: &str = "oarchy4tamydxcitaki6bc2v4leza6v35iezmu2chg2bap63sv6f2did";
+
+    #[test]
+    fn tcp_and_websocket_listen_addresses_are_mapped_to_onion_addresses() {
+        let listen: Vec<Multiaddr> = vec![
+            "/ip4/0.0.0.0/tcp/9939".parse().unwrap(),
+            "/ip4/192.168.1.2/tcp/9940/ws".parse().unwrap(),
+        ];
+
+        let ports = hidden_service_ports(&listen, ONION_ADDRESS);
+
+        assert_eq!(ports, vec![
+            HiddenServicePort {
+                onion_port: 9939,
+                target: "127.0.0.1:9939".parse().unwrap(),
+                address: format!("/onion3/{}:9939", ONION_ADDRESS).parse().unwrap(),
+            },
+            HiddenServicePort {
+                onion_port: 9940,
+                target: "192.168.1.2:9940".parse().unwrap(),
+                address: format!("/onion3/{}:9940/ws", ONION_ADDRESS)
+                    .parse()
+                    .unwrap(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn non_tcp_and_duplicate_ports_are_skipped() {
+        let listen: Vec<Multiaddr> = vec![
+            "/ip4/0.0.0.0/tcp/9939".parse().unwrap(),
+            "/ip6/::/tcp/9939".parse().unwrap(),
+            "/ip4/0.0.0.0/udp/9941".parse().unwrap(),
+        ];
+
+        let ports = hidden_service_ports(&listen, ONION_ADDRESS);
+
+        assert_eq!(ports.len(), 1);
+        assert_eq!(ports[0].target, "127.0.0.1:9939".parse().unwrap());
+    }
+}
@@ -0,0 +1,35 @@
+pub mod harness;
+
+use harness::bob_run_until::is_swap_setup_completed;
+use harness::SlowCancelConfig;
+use swap::asb::FixedRate;
+use swap::protocol::alice::AliceState;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+
+#[tokio::test]
+async fn given_bob_aborts_before_locking_btc_alice_safely_aborts() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_state = bob::run_until(bob_swap, is_swap_setup_completed).await?;
+
+        assert!(matches!(bob_state, BobState::SwapSetupCompleted(..)));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let (mut bob_swap, _) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        assert!(matches!(bob_swap.state, BobState::SwapSetupCompleted(..)));
+
+        bob_swap.event_loop_handle.send_abort().await?;
+
+        let alice_state = alice_swap.await??;
+        assert!(matches!(alice_state, AliceState::SafelyAborted));
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,41 @@
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use std::time::Duration;
+use swap::asb::shutdown::ShutdownController;
+use swap::asb::FixedRate;
+use swap::protocol::alice::AliceState;
+use swap::protocol::{alice, bob};
+
+#[tokio::test]
+async fn given_alice_shuts_down_mid_swap_then_swap_resumes_after_restart() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::run(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let controller = ShutdownController::new();
+        let alice_swap = tokio::spawn(alice::run_until_shutdown(
+            alice_swap,
+            FixedRate::default(),
+            controller.shutdown(),
+        ));
+
+        assert!(controller.drain(Duration::from_secs(60)).await);
+        let alice_state = alice_swap.await??;
+        assert!(matches!(alice_state, AliceState::Started { .. }));
+
+        ctx.restart_alice().await;
+        let alice_swap = ctx.alice_next_swap().await;
+        assert!(matches!(alice_swap.state, AliceState::Started { .. }));
+
+        let alice_state = alice::run(alice_swap, FixedRate::default()).await?;
+        ctx.assert_alice_redeemed(alice_state).await;
+
+        let bob_state = bob_swap.await??;
+        ctx.assert_bob_redeemed(bob_state).await;
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,58 @@
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use swap::asb::FixedRate;
+use swap::cli::SwapEvent;
+use swap::protocol::alice;
+use swap::protocol::bob::BobState;
+use tokio::join;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn given_swap_client_then_swap_completes_and_every_state_is_reported() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (client, mut events) = ctx.bob_swap_client().await;
+        let swap_id = Uuid::new_v4();
+
+        let mut connection = client.connect(swap_id, ctx.alice_seller_address()).await?;
+        connection.request_quote().await?;
+        let request = ctx.bob_swap_request().await;
+        let bob_swap = tokio::spawn(async move { client.start(connection, request).await });
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let (bob_state, alice_state) = join!(bob_swap, alice_swap);
+        let bob_state = bob_state??;
+
+        ctx.assert_alice_redeemed(alice_state??).await;
+        ctx.assert_bob_redeemed(bob_state).await;
+
+        // the client is dropped with the finished swap, which closes the channel
+        let mut states = Vec::new();
+        let mut stopped = None;
+        while let Some(event) = events.recv().await {
+            match event {
+                SwapEvent::StateChanged { swap_id: id, state } => {
+                    assert_eq!(id, swap_id);
+                    states.push(state);
+                }
+                SwapEvent::Stopped { swap_id: id, state } => {
+                    assert_eq!(id, swap_id);
+                    stopped = Some(state);
+                }
+                SwapEvent::Failed { error, .. } => panic!("Swap failed: {}", error),
+            }
+        }
+
+        assert!(matches!(states.first(), Some(BobState::Started { .. })));
+        assert!(states
+            .iter()
+            .any(|state| matches!(state, BobState::BtcLocked(..))));
+        assert!(matches!(states.last(), Some(BobState::XmrRedeemed { .. })));
+        assert!(matches!(stopped, Some(BobState::XmrRedeemed { .. })));
+
+        Ok(())
+    })
+    .await;
+}
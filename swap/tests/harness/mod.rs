@@ -1,33 +1,40 @@
-mod bitcoind;
-mod electrs;
-
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
-use bitcoin_harness::{BitcoindRpcApi, Client};
 use futures::Future;
 use get_port::get_port;
+use libp2p::core::multiaddr::Protocol;
 use libp2p::core::Multiaddr;
 use libp2p::PeerId;
 use monero_harness::{image, Monero};
+use rust_decimal::Decimal;
 use std::cmp::Ordering;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use swap::asb::FixedRate;
-use swap::bitcoin::{CancelTimelock, PunishTimelock, TxCancel, TxPunish, TxRedeem, TxRefund};
+use swap::asb::pricing::FixedSpread;
+use swap::asb::{FixedRate, Rate};
+use swap::bitcoin::{
+    CancelTimelock, ConfirmationTargets, PunishTimelock, TxCancel, TxKind, TxPunish, TxRedeem,
+    TxRefund,
+};
 use swap::database::SqliteDatabase;
 use swap::env::{Config, GetConfig};
 use swap::fs::ensure_directory_exists;
+use swap::network::rendezvous::RendezvousNamespace;
+use swap::network::swap_setup::alice::ConcurrencyLimits;
+use swap::network::swap_setup::sell_xmr::alice::NewSwap;
+use swap::network::swap_setup::{ConfirmationSchedule, TimelockRanges, Timelocks};
 use swap::network::swarm;
-use swap::protocol::alice::{AliceState, Swap};
+use swap::protocol::alice::{AliceState, LocalRedeemSigner, Swap};
 use swap::protocol::bob::BobState;
-use swap::protocol::{alice, bob};
+use swap::protocol::{alice, bob, Database, State};
 use swap::seed::Seed;
 use swap::{asb, bitcoin, cli, env, monero};
+use swap_testbed::{bitcoind, electrs, init_bitcoind_container, init_electrs_container, mint};
 use tempfile::{tempdir, NamedTempFile};
 use testcontainers::clients::Cli;
-use testcontainers::{Container, Docker, RunArgs};
+use testcontainers::Container;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Receiver;
 use tokio::task::JoinHandle;
@@ -91,6 +98,7 @@ where
         env_config,
         alice_bitcoin_wallet.clone(),
         alice_monero_wallet.clone(),
+        false,
     )
     .await;
 
@@ -142,6 +150,103 @@ where
     testfn(test).await.unwrap()
 }
 
+/// Sets up a test in which the CLI sells XMR to an ASB that buys XMR.
+///
+/// The roles are reversed compared to [`setup_test`]: the CLI holds the XMR
+/// and takes the role of Alice, the ASB holds the BTC and takes the role of
+/// Bob.
+pub async fn setup_sell_xmr_test<T, F, C>(_config: C, testfn: T)
+where
+    T: Fn(SellXmrTestContext) -> F,
+    F: Future<Output = Result<()>>,
+    C: GetConfig,
+{
+    let cli = Cli::default();
+
+    let _guard = tracing_subscriber::fmt()
+        .with_env_filter("warn,swap=debug,monero_harness=debug,monero_rpc=debug,bitcoin_harness=info,testcontainers=info")
+        .with_test_writer()
+        .set_default();
+
+    let env_config = C::get_config();
+
+    let (monero, containers) = init_containers(&cli).await;
+    monero.init_miner().await.unwrap();
+
+    let btc_amount = bitcoin::Amount::from_sat(1_000_000);
+    let xmr_amount = monero::Amount::from_monero(btc_amount.as_btc() / FixedRate::RATE).unwrap();
+
+    let electrs_rpc_port = containers
+        .electrs
+        .get_host_port(electrs::RPC_PORT)
+        .expect("Could not map electrs rpc port");
+
+    let asb_seed = Seed::random().unwrap();
+    let asb_starting_balances = StartingBalances::new(btc_amount * 10, monero::Amount::ZERO, None);
+    let (asb_bitcoin_wallet, asb_monero_wallet) = init_test_wallets(
+        MONERO_WALLET_NAME_BOB,
+        containers.bitcoind_url.clone(),
+        &monero,
+        asb_starting_balances.clone(),
+        tempdir().unwrap().path(),
+        electrs_rpc_port,
+        &asb_seed,
+        env_config,
+    )
+    .await;
+
+    let asb_listen_port = get_port().expect("Failed to find a free port");
+    let asb_address: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", asb_listen_port)
+        .parse()
+        .expect("failed to parse the ASB's address");
+
+    let asb_db_path = NamedTempFile::new().unwrap().path().to_path_buf();
+    let (asb_handle, _) = start_alice(
+        &asb_seed,
+        asb_db_path.clone(),
+        asb_address.clone(),
+        env_config,
+        asb_bitcoin_wallet.clone(),
+        asb_monero_wallet.clone(),
+        true,
+    )
+    .await;
+
+    let cli_seed = Seed::random().unwrap();
+    let cli_starting_balances = StartingBalances::new(bitcoin::Amount::ZERO, xmr_amount, Some(10));
+    let (cli_bitcoin_wallet, cli_monero_wallet) = init_test_wallets(
+        MONERO_WALLET_NAME_ALICE,
+        containers.bitcoind_url,
+        &monero,
+        cli_starting_balances.clone(),
+        tempdir().unwrap().path(),
+        electrs_rpc_port,
+        &cli_seed,
+        env_config,
+    )
+    .await;
+
+    monero.start_miner().await.unwrap();
+
+    let test = SellXmrTestContext {
+        env_config,
+        xmr_amount,
+        asb_address,
+        asb_db_path,
+        asb_handle,
+        asb_bitcoin_wallet,
+        asb_monero_wallet,
+        asb_starting_balances,
+        cli_seed,
+        cli_db_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+        cli_bitcoin_wallet,
+        cli_monero_wallet,
+        cli_starting_balances,
+    };
+
+    testfn(test).await.unwrap()
+}
+
 async fn init_containers(cli: &Cli) -> (Monero, Containers<'_>) {
     let prefix = random_prefix();
     let bitcoind_name = format!("{}_{}", prefix, "bitcoind");
@@ -166,55 +271,6 @@ async fn init_containers(cli: &Cli) -> (Monero, Containers<'_>) {
     })
 }
 
-async fn init_bitcoind_container(
-    cli: &Cli,
-    volume: String,
-    name: String,
-    network: String,
-) -> Result<(Container<'_, Cli, bitcoind::Bitcoind>, Url)> {
-    let image = bitcoind::Bitcoind::default().with_volume(volume);
-
-    let run_args = RunArgs::default().with_name(name).with_network(network);
-
-    let docker = cli.run_with_args(image, run_args);
-    let a = docker
-        .get_host_port(bitcoind::RPC_PORT)
-        .context("Could not map bitcoind rpc port")?;
-
-    let bitcoind_url = {
-        let input = format!(
-            "http://{}:{}@localhost:{}",
-            bitcoind::RPC_USER,
-            bitcoind::RPC_PASSWORD,
-            a
-        );
-        Url::parse(&input).unwrap()
-    };
-
-    init_bitcoind(bitcoind_url.clone(), 5).await?;
-
-    Ok((docker, bitcoind_url.clone()))
-}
-
-pub async fn init_electrs_container(
-    cli: &Cli,
-    volume: String,
-    bitcoind_container_name: String,
-    network: String,
-) -> Result<Container<'_, Cli, electrs::Electrs>> {
-    let bitcoind_rpc_addr = format!("{}:{}", bitcoind_container_name, bitcoind::RPC_PORT);
-    let image = electrs::Electrs::default()
-        .with_volume(volume)
-        .with_daemon_rpc_addr(bitcoind_rpc_addr)
-        .with_tag("latest");
-
-    let run_args = RunArgs::default().with_network(network);
-
-    let docker = cli.run_with_args(image, run_args);
-
-    Ok(docker)
-}
-
 async fn start_alice(
     seed: &Seed,
     db_path: PathBuf,
@@ -222,6 +278,7 @@ async fn start_alice(
     env_config: Config,
     bitcoin_wallet: Arc<bitcoin::Wallet>,
     monero_wallet: Arc<monero::Wallet>,
+    buy_xmr: bool,
 ) -> (AliceApplicationHandle, Receiver<alice::Swap>) {
     if let Some(parent_dir) = db_path.parent() {
         ensure_directory_exists(parent_dir).unwrap();
@@ -229,7 +286,7 @@ async fn start_alice(
     if !&db_path.exists() {
         tokio::fs::File::create(&db_path).await.unwrap();
     }
-    let db = Arc::new(SqliteDatabase::open(db_path.as_path()).await.unwrap());
+    let db = Arc::new(SqliteDatabase::open(db_path.as_path(), None).await.unwrap());
 
     let min_buy = bitcoin::Amount::from_sat(u64::MIN);
     let max_buy = bitcoin::Amount::from_sat(u64::MAX);
@@ -238,12 +295,21 @@ async fn start_alice(
 
     let mut swarm = swarm::asb(
         &seed,
+        0,
         min_buy,
         max_buy,
         latest_rate,
         resume_only,
+        ConcurrencyLimits::default(),
+        buy_xmr,
+        bitcoin_wallet.clone(),
         env_config,
-        None,
+        TimelockRanges::exactly(Timelocks::of_network(&env_config)),
+        ConfirmationSchedule::fixed(env_config.monero_finality_confirmations),
+        vec![RendezvousNamespace::testnet()],
+        vec![],
+        false,
+        &[],
     )
     .unwrap();
     swarm.listen_on(listen_address).unwrap();
@@ -257,6 +323,9 @@ async fn start_alice(
         FixedRate::default(),
         min_buy,
         max_buy,
+        Box::new(FixedSpread),
+        None,
+        0,
     )
     .unwrap();
 
@@ -303,12 +372,13 @@ async fn init_test_wallets(
     };
 
     let btc_wallet = swap::bitcoin::Wallet::new(
-        electrum_rpc_url,
+        &[electrum_rpc_url],
         datadir,
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
         env_config,
         1,
+        ConfirmationTargets::uniform(1),
     )
     .await
     .expect("could not init btc wallet");
@@ -348,7 +418,6 @@ async fn init_test_wallets(
 
 const MONERO_WALLET_NAME_BOB: &str = "bob";
 const MONERO_WALLET_NAME_ALICE: &str = "alice";
-const BITCOIN_TEST_WALLET_NAME: &str = "testwallet";
 
 #[derive(Debug, Clone)]
 pub struct StartingBalances {
@@ -416,13 +485,13 @@ impl BobParams {
         if !self.db_path.exists() {
             tokio::fs::File::create(&self.db_path).await?;
         }
-        let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
+        let db = Arc::new(SqliteDatabase::open(&self.db_path, None).await?);
 
         let swap = bob::Swap::from_db(
             db,
             swap_id,
             self.bitcoin_wallet.clone(),
-            self.monero_wallet.clone(),
+            self.monero_wallet.clone().into(),
             self.env_config,
             handle,
             self.monero_wallet.get_main_address(),
@@ -446,23 +515,48 @@ impl BobParams {
         if !self.db_path.exists() {
             tokio::fs::File::create(&self.db_path).await?;
         }
-        let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
+        let db = Arc::new(SqliteDatabase::open(&self.db_path, None).await?);
 
         let swap = bob::Swap::new(
             db,
             swap_id,
             self.bitcoin_wallet.clone(),
-            self.monero_wallet.clone(),
+            self.monero_wallet.clone().into(),
             self.env_config,
             handle,
             self.monero_wallet.get_main_address(),
             self.bitcoin_wallet.new_address().await?,
             btc_amount,
+            None,
+            None,
         );
 
         Ok((swap, event_loop))
     }
 
+    pub async fn new_swap_client(
+        &self,
+    ) -> Result<(cli::SwapClient, mpsc::UnboundedReceiver<cli::SwapEvent>)> {
+        let tor_socks5_port = get_port()
+            .expect("We don't care about Tor in the tests so we get a free port to disable it.");
+
+        if let Some(parent_dir) = self.db_path.parent() {
+            ensure_directory_exists(parent_dir)?;
+        }
+        if !self.db_path.exists() {
+            tokio::fs::File::create(&self.db_path).await?;
+        }
+        let db = Arc::new(SqliteDatabase::open(&self.db_path, None).await?);
+
+        let (client, events) =
+            cli::SwapClient::new(db, self.bitcoin_wallet.clone(), self.env_config);
+        let client = client
+            .with_monero_wallet(self.monero_wallet.clone().into())
+            .with_network(self.seed.derive_libp2p_identity(), tor_socks5_port);
+
+        Ok((client, events))
+    }
+
     pub async fn new_eventloop(
         &self,
         swap_id: Uuid,
@@ -470,16 +564,16 @@ impl BobParams {
         let tor_socks5_port = get_port()
             .expect("We don't care about Tor in the tests so we get a free port to disable it.");
 
-        let behaviour = cli::Behaviour::new(
-            self.alice_peer_id,
-            self.env_config,
-            self.bitcoin_wallet.clone(),
-        );
-        let mut swarm = swarm::cli(
-            self.seed.derive_libp2p_identity(),
-            tor_socks5_port,
-            behaviour,
-        )
+        let identity = self.seed.derive_libp2p_identity();
+        let mut swarm = swarm::cli(identity.clone(), tor_socks5_port, |relay| {
+            cli::Behaviour::new(
+                self.alice_peer_id,
+                self.env_config,
+                self.bitcoin_wallet.clone(),
+                identity.public(),
+                relay,
+            )
+        })
         .await?;
         swarm
             .behaviour_mut()
@@ -489,7 +583,7 @@ impl BobParams {
     }
 }
 
-pub struct BobApplicationHandle(JoinHandle<()>);
+pub struct BobApplicationHandle(JoinHandle<Result<(), cli::PeerUnreachable>>);
 
 impl BobApplicationHandle {
     pub fn abort(&self) {
@@ -541,6 +635,7 @@ impl TestContext {
             self.env_config,
             self.alice_bitcoin_wallet.clone(),
             self.alice_monero_wallet.clone(),
+            false,
         )
         .await;
 
@@ -566,6 +661,33 @@ impl TestContext {
         (swap, BobApplicationHandle(join_handle))
     }
 
+    pub async fn bob_swap_client(
+        &mut self,
+    ) -> (cli::SwapClient, mpsc::UnboundedReceiver<cli::SwapEvent>) {
+        // ensure the wallet is up to date for concurrent swap tests
+        self.bob_bitcoin_wallet.sync().await.unwrap();
+
+        self.bob_params.new_swap_client().await.unwrap()
+    }
+
+    pub async fn bob_swap_request(&self) -> cli::SwapRequest {
+        cli::SwapRequest {
+            btc_amount: self.btc_amount,
+            monero_receive_address: self.bob_monero_wallet.get_main_address(),
+            bitcoin_refund_address: self.bob_bitcoin_wallet.new_address().await.unwrap(),
+            timelocks: None,
+            confirmation_schedule: None,
+        }
+    }
+
+    /// The address Bob connects to Alice with, including her peer id.
+    pub fn alice_seller_address(&self) -> Multiaddr {
+        self.bob_params
+            .alice_address
+            .clone()
+            .with(Protocol::P2p(self.bob_params.alice_peer_id.into()))
+    }
+
     pub async fn stop_and_resume_bob_from_db(
         &mut self,
         join_handle: BobApplicationHandle,
@@ -680,12 +802,12 @@ impl TestContext {
 
         let cancel_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxCancel::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Cancel, TxCancel::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         let refund_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxRefund::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Refund, TxRefund::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
 
@@ -728,7 +850,7 @@ impl TestContext {
     async fn alice_redeemed_btc_balance(&self) -> bitcoin::Amount {
         let fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxRedeem::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Redeem, TxRedeem::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         self.alice_starting_balances.btc + self.btc_amount - fee
@@ -771,12 +893,12 @@ impl TestContext {
     async fn alice_punished_btc_balance(&self) -> bitcoin::Amount {
         let cancel_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxCancel::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Cancel, TxCancel::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         let punish_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxPunish::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Punish, TxPunish::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         self.alice_starting_balances.btc + self.btc_amount - cancel_fee - punish_fee
@@ -801,6 +923,162 @@ impl TestContext {
     }
 }
 
+pub struct SellXmrTestContext {
+    env_config: Config,
+
+    xmr_amount: monero::Amount,
+
+    asb_address: Multiaddr,
+    asb_db_path: PathBuf,
+    asb_handle: AliceApplicationHandle,
+    asb_bitcoin_wallet: Arc<bitcoin::Wallet>,
+    asb_monero_wallet: Arc<monero::Wallet>,
+    asb_starting_balances: StartingBalances,
+
+    cli_seed: Seed,
+    cli_db_path: PathBuf,
+    cli_bitcoin_wallet: Arc<bitcoin::Wallet>,
+    cli_monero_wallet: Arc<monero::Wallet>,
+    cli_starting_balances: StartingBalances,
+}
+
+impl SellXmrTestContext {
+    /// Sets up a swap in which the CLI sells the XMR of the test, the way
+    /// `swap sell-xmr` does. The CLI expects the BTC the ASB quotes for the XMR
+    /// plus the given surplus.
+    pub async fn cli_sell_xmr_swap(
+        &self,
+        surplus: bitcoin::Amount,
+    ) -> Result<(alice::Swap, BobApplicationHandle)> {
+        let swap_id = Uuid::new_v4();
+        let asb_peer_id = self.asb_handle.peer_id;
+
+        if let Some(parent_dir) = self.cli_db_path.parent() {
+            ensure_directory_exists(parent_dir)?;
+        }
+        if !self.cli_db_path.exists() {
+            tokio::fs::File::create(&self.cli_db_path).await?;
+        }
+        let db = Arc::new(SqliteDatabase::open(&self.cli_db_path, None).await?);
+
+        let tor_socks5_port = get_port()
+            .expect("We don't care about Tor in the tests so we get a free port to disable it.");
+
+        let identity = self.cli_seed.derive_libp2p_identity();
+        let mut swarm = swarm::cli(identity.clone(), tor_socks5_port, |relay| {
+            cli::Behaviour::new(
+                asb_peer_id,
+                self.env_config,
+                self.cli_bitcoin_wallet.clone(),
+                identity.public(),
+                relay,
+            )
+        })
+        .await?;
+        swarm
+            .behaviour_mut()
+            .add_address(asb_peer_id, self.asb_address.clone());
+
+        let (event_loop, mut event_loop_handle) =
+            cli::EventLoop::new(swap_id, swarm, asb_peer_id, self.env_config)?;
+        let event_loop = BobApplicationHandle(tokio::spawn(event_loop.run()));
+
+        let bid_quote = event_loop_handle.request_quote().await?;
+        let buy_price = bid_quote
+            .details
+            .and_then(|details| details.buy_price)
+            .context("The ASB does not buy XMR")?;
+        let btc = Rate::new(buy_price, Decimal::ZERO).buy_quote(self.xmr_amount)? + surplus;
+
+        db.insert_peer_id(swap_id, asb_peer_id).await?;
+
+        let state3 = event_loop_handle
+            .setup_sell_xmr(NewSwap {
+                swap_id,
+                xmr: self.xmr_amount,
+                btc,
+                bitcoin_redeem_address: self.cli_bitcoin_wallet.new_address().await?,
+            })
+            .await?;
+        let state = AliceState::Started {
+            state3: Box::new(state3),
+        };
+        db.insert_latest_state(swap_id, state.clone().into())
+            .await?;
+
+        let swap = alice::Swap {
+            state,
+            event_loop_handle: event_loop_handle.into_alice_handle(),
+            bitcoin_wallet: self.cli_bitcoin_wallet.clone(),
+            monero_wallet: self.cli_monero_wallet.clone(),
+            env_config: self.env_config,
+            swap_id,
+            db,
+            redeem_signer: Arc::new(LocalRedeemSigner),
+        };
+
+        Ok((swap, event_loop))
+    }
+
+    pub async fn assert_cli_sold_xmr(&self, state: AliceState) {
+        assert!(matches!(state, AliceState::BtcRedeemed));
+
+        assert_eventual_balance(
+            self.cli_bitcoin_wallet.as_ref(),
+            Ordering::Greater,
+            self.cli_starting_balances.btc,
+        )
+        .await
+        .unwrap();
+
+        // The CLI pays fees - comparison does not take the exact lock fee into account
+        assert_eventual_balance(
+            self.cli_monero_wallet.as_ref(),
+            Ordering::Less,
+            self.cli_starting_balances.xmr - self.xmr_amount,
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Waits until the ASB swept the XMR it bought and checks its balances.
+    pub async fn assert_asb_bought_xmr(&self) {
+        let db = SqliteDatabase::open(&self.asb_db_path, None).await.unwrap();
+
+        timeout(Duration::from_secs(120), async {
+            loop {
+                let states = db.all().await.unwrap();
+                if states
+                    .iter()
+                    .any(|(_, state)| matches!(state, State::Bob(BobState::XmrRedeemed { .. })))
+                {
+                    break;
+                }
+
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        })
+        .await
+        .expect("The ASB did not redeem the XMR within 120 seconds");
+
+        assert_eventual_balance(
+            self.asb_bitcoin_wallet.as_ref(),
+            Ordering::Less,
+            self.asb_starting_balances.btc,
+        )
+        .await
+        .unwrap();
+
+        assert_eventual_balance(
+            self.asb_monero_wallet.as_ref(),
+            Ordering::Greater,
+            self.asb_starting_balances.xmr,
+        )
+        .await
+        .unwrap();
+    }
+}
+
 async fn assert_eventual_balance<A: fmt::Display + PartialOrd>(
     wallet: &impl Wallet<Amount = A>,
     ordering: Ordering,
@@ -899,55 +1177,6 @@ fn random_prefix() -> String {
     chars
 }
 
-async fn mine(bitcoind_client: Client, reward_address: bitcoin::Address) -> Result<()> {
-    loop {
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        bitcoind_client
-            .generatetoaddress(1, reward_address.clone(), None)
-            .await?;
-    }
-}
-
-async fn init_bitcoind(node_url: Url, spendable_quantity: u32) -> Result<Client> {
-    let bitcoind_client = Client::new(node_url.clone());
-
-    bitcoind_client
-        .createwallet(BITCOIN_TEST_WALLET_NAME, None, None, None, None)
-        .await?;
-
-    let reward_address = bitcoind_client
-        .with_wallet(BITCOIN_TEST_WALLET_NAME)?
-        .getnewaddress(None, None)
-        .await?;
-
-    bitcoind_client
-        .generatetoaddress(101 + spendable_quantity, reward_address.clone(), None)
-        .await?;
-    let _ = tokio::spawn(mine(bitcoind_client.clone(), reward_address));
-    Ok(bitcoind_client)
-}
-
-/// Send Bitcoin to the specified address, limited to the spendable bitcoin
-/// quantity.
-pub async fn mint(node_url: Url, address: bitcoin::Address, amount: bitcoin::Amount) -> Result<()> {
-    let bitcoind_client = Client::new(node_url.clone());
-
-    bitcoind_client
-        .send_to_address(BITCOIN_TEST_WALLET_NAME, address.clone(), amount)
-        .await?;
-
-    // Confirm the transaction
-    let reward_address = bitcoind_client
-        .with_wallet(BITCOIN_TEST_WALLET_NAME)?
-        .getnewaddress(None, None)
-        .await?;
-    bitcoind_client
-        .generatetoaddress(1, reward_address, None)
-        .await?;
-
-    Ok(())
-}
-
 // This is just to keep the containers alive
 #[allow(dead_code)]
 struct Containers<'a> {
@@ -973,6 +1202,10 @@ pub mod alice_run_until {
 pub mod bob_run_until {
     use swap::protocol::bob::BobState;
 
+    pub fn is_swap_setup_completed(state: &BobState) -> bool {
+        matches!(state, BobState::SwapSetupCompleted(..))
+    }
+
     pub fn is_btc_locked(state: &BobState) -> bool {
         matches!(state, BobState::BtcLocked(..))
     }
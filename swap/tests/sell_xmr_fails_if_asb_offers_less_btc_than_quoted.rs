@@ -0,0 +1,24 @@
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use swap::bitcoin;
+use swap::network::swap_setup::sell_xmr::alice::Error;
+
+#[tokio::test]
+async fn sell_xmr_fails_if_asb_offers_less_btc_than_quoted() {
+    harness::setup_sell_xmr_test(SlowCancelConfig, |ctx| async move {
+        let error = ctx
+            .cli_sell_xmr_swap(bitcoin::Amount::from_sat(1))
+            .await
+            .map(|_| ())
+            .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::OfferBelowQuote(_))
+        ));
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,20 @@
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use swap::asb::FixedRate;
+use swap::bitcoin;
+use swap::protocol::alice;
+
+#[tokio::test]
+async fn sell_xmr_happy_path() {
+    harness::setup_sell_xmr_test(SlowCancelConfig, |ctx| async move {
+        let (cli_swap, _cli_handle) = ctx.cli_sell_xmr_swap(bitcoin::Amount::ZERO).await?;
+        let cli_state = alice::run(cli_swap, FixedRate::default()).await?;
+
+        ctx.assert_cli_sold_xmr(cli_state).await;
+        ctx.assert_asb_bought_xmr().await;
+
+        Ok(())
+    })
+    .await;
+}